@@ -40,10 +40,13 @@ use anyhow::Error;
 use std::{
     collections::{HashMap, VecDeque},
     fmt,
+    ops::Range,
     path::Path,
 };
 
+use rand::{Rng, SeedableRng};
 use ringbuf::Producer;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::{bytequeue::*, cpu_808x::*};
 
@@ -56,18 +59,28 @@ use crate::{
 };
 
 use crate::devices::{
+    adlib::AdLibCard,
     dma::*,
-    fdc::FloppyController,
+    ems::{EmsCard, EMS_PAGE_FRAME_SIZE},
+    fdc::{FloppyController, FDC_IRQ},
+    game_port::GamePort,
     hdc::*,
     keyboard::*,
+    lpt_port::ParallelController,
     mouse::*,
     pic::*,
-    pit::Pit,
+    pit::{ChannelMode, Pit},
+    post_card::PostCard,
     ppi::*,
+    rtc::Rtc,
     serial::*,
+    sn76489::Sn76489,
 };
 
-use crate::tracelogger::TraceLogger;
+use crate::{
+    io_trace::{IoDirection, IoTrace, IoTraceEntry},
+    tracelogger::{TraceLogger, TraceTarget, TraceTargets},
+};
 
 #[cfg(feature = "ega")]
 use crate::devices::ega::{self, EGACard};
@@ -81,7 +94,15 @@ use crate::{
     },
     machine::{MachineCheckpoint, MachinePatch},
     machine_config::{normalize_conventional_memory, MachineConfiguration},
-    machine_types::{HardDiskControllerType, SerialControllerType, SerialMouseType},
+    machine_types::{
+        BootTarget,
+        HardDiskControllerType,
+        MachineType,
+        OpenBusMode,
+        SerialControllerType,
+        SerialMouseType,
+        SoundType,
+    },
     memerror::MemError,
 };
 
@@ -101,12 +122,18 @@ pub const MEM_BPE_BIT: u8 = 0b0010_0000; // Bit to signify that this address is
 pub const MEM_BPA_BIT: u8 = 0b0001_0000; // Bit to signify that this address is associated with a breakpoint on access
 pub const MEM_CP_BIT: u8 = 0b0000_1000; // Bit to signify that this address is a ROM checkpoint
 pub const MEM_MMIO_BIT: u8 = 0b0000_0100; // Bit to signify that this address is MMIO mapped
+pub const MEM_WATCH_BIT: u8 = 0b0000_0010; // Bit to signify that this address is covered by a watchpoint
 
 pub const KB_UPDATE_RATE: f64 = 5000.0; // Keyboard device update rate in microseconds
 
 pub const TIMING_TABLE_LEN: usize = 512;
 
 pub const IMMINENT_TIMER_INTERRUPT: u16 = 10;
+// The largest number of cycles a halted CPU may skip at once when halt idling is enabled. This bounds the wake
+// latency for interrupts from devices that are not yet pending at the PIC, such as the keyboard.
+pub const HALT_IDLE_MAX_CYCLES: u32 = 64;
+// CPU cycles per PIT tick at the standard clock divisors. Faster CPU clocks only make this conservative.
+const CPU_CYCLES_PER_PIT_TICK: u32 = 4;
 
 #[derive(Copy, Clone, Debug)]
 pub struct TimingTableEntry {
@@ -248,6 +275,38 @@ impl MemRangeDescriptor {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum WatchAccess {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchAccess {
+    fn matches(&self, access: WatchAccess) -> bool {
+        match self {
+            WatchAccess::ReadWrite => true,
+            _ => *self == access,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Watchpoint {
+    pub range:  Range<usize>,
+    pub access: WatchAccess,
+}
+
+/// Describes a CPU memory access that triggered a watchpoint. For reads, old_value and
+/// new_value are the same.
+#[derive(Copy, Clone, Debug)]
+pub struct WatchHit {
+    pub address: usize,
+    pub access: WatchAccess,
+    pub old_value: u8,
+    pub new_value: u8,
+}
+
 pub enum IoDeviceType {
     Ppi,
     Pit,
@@ -259,6 +318,13 @@ pub enum IoDeviceType {
     FloppyController,
     HardDiskController,
     Mouse,
+    AdLib,
+    Sn76489,
+    Ems,
+    GamePort,
+    Parallel,
+    Rtc,
+    PostCard,
     Video(VideoCardId),
 }
 
@@ -296,6 +362,7 @@ pub enum MmioDeviceType {
     Ega,
     Vga,
     Rom,
+    Ems,
 }
 
 // Main bus struct.
@@ -313,14 +380,22 @@ pub struct BusInterface {
     keyboard_type: KeyboardType,
     keyboard: Option<Keyboard>,
     conventional_size: usize,
+    open_bus_mode: OpenBusMode,
+    last_bus_value: u8,
     memory: Vec<u8>,
     memory_mask: Vec<u8>,
     desc_vec: Vec<MemRangeDescriptor>,
     mmio_map: Vec<(MemRangeDescriptor, MmioDeviceType)>,
     mmio_map_fast: [MmioDeviceType; MMIO_MAP_LEN],
+    conventional_wait_states: u32,
+    rom_wait_states: u32,
+    wait_map: [u32; MMIO_MAP_LEN],
     mmio_data: MmioData,
+    watchpoints: Vec<Watchpoint>,
+    watch_hit: Option<WatchHit>,
     cursor: usize,
     intr_imminent: bool,
+    intr_distance: u32,
 
     io_map: HashMap<u16, IoDeviceType>,
     ppi: Option<Ppi>,
@@ -334,6 +409,15 @@ pub struct BusInterface {
     fdc: Option<FloppyController>,
     hdc: Option<HardDiskController>,
     mouse: Option<Mouse>,
+    adlib: Option<AdLibCard>,
+    sn76489: Option<Sn76489>,
+    ems: Option<EmsCard>,
+    game_port: Option<GamePort>,
+    parallel: Option<ParallelController>,
+    rtc: Option<Rtc>,
+    post_card: Option<PostCard>,
+
+    floppy_boot_hold: bool,
 
     videocards:    HashMap<VideoCardId, VideoCardDispatch>,
     videocard_ids: Vec<VideoCardId>,
@@ -348,6 +432,10 @@ pub struct BusInterface {
     cga_tick_accum: u32,
     kb_us_accum:    f64,
     refresh_active: bool,
+    dram_refresh:   bool,
+
+    io_trace: Option<IoTrace>,
+    trace_targets: TraceTargets,
 }
 
 impl ByteQueue for BusInterface {
@@ -451,14 +539,22 @@ impl Default for BusInterface {
             keyboard_type: KeyboardType::ModelF,
             keyboard: None,
             conventional_size: ADDRESS_SPACE,
+            open_bus_mode: OpenBusMode::High,
+            last_bus_value: OPEN_BUS_BYTE,
             memory: vec![OPEN_BUS_BYTE; ADDRESS_SPACE],
             memory_mask: vec![0; ADDRESS_SPACE],
             desc_vec: Vec::new(),
             mmio_map: Vec::new(),
             mmio_map_fast: [MmioDeviceType::Memory; MMIO_MAP_LEN],
+            conventional_wait_states: 0,
+            rom_wait_states: 0,
+            wait_map: [DEFAULT_WAIT_STATES; MMIO_MAP_LEN],
             mmio_data: MmioData::new(),
+            watchpoints: Vec::new(),
+            watch_hit: None,
             cursor: 0,
             intr_imminent: false,
+            intr_distance: 0,
 
             io_map: HashMap::new(),
             ppi: None,
@@ -472,6 +568,15 @@ impl Default for BusInterface {
             fdc: None,
             hdc: None,
             mouse: None,
+            adlib: None,
+            sn76489: None,
+            ems: None,
+            game_port: None,
+            parallel: None,
+            rtc: None,
+            post_card: None,
+
+            floppy_boot_hold: false,
             videocards: HashMap::new(),
             videocard_ids: Vec::new(),
 
@@ -485,6 +590,10 @@ impl Default for BusInterface {
             cga_tick_accum: 0,
             kb_us_accum:    0.0,
             refresh_active: false,
+            dram_refresh:   true,
+
+            io_trace: None,
+            trace_targets: TraceTargets::default(),
         }
     }
 }
@@ -582,8 +691,53 @@ impl BusInterface {
         patch.installed = true;
     }
 
+    /// Set the amount of installed conventional memory. Writes above this size are discarded and
+    /// reads of any address above it that is not ROM or memory-mapped return open bus.
     pub fn set_conventional_size(&mut self, size: usize) {
-        self.conventional_size = size;
+        self.conventional_size = size.min(self.memory.len());
+        self.fill_open_bus();
+        self.rebuild_wait_map();
+    }
+
+    /// Set the value returned by reads of unmapped memory and unpopulated IO ports.
+    pub fn set_open_bus_mode(&mut self, mode: OpenBusMode) {
+        self.open_bus_mode = mode;
+        self.fill_open_bus();
+    }
+
+    pub fn open_bus_mode(&self) -> OpenBusMode {
+        self.open_bus_mode
+    }
+
+    /// Return the byte read from the bus when no memory or device drives it.
+    #[inline]
+    pub fn open_bus_byte(&self) -> u8 {
+        match self.open_bus_mode {
+            OpenBusMode::High => OPEN_BUS_BYTE,
+            OpenBusMode::Low => 0x00,
+            OpenBusMode::LastValue => self.last_bus_value,
+        }
+    }
+
+    /// Return whether an address that is not memory-mapped is backed by RAM or ROM.
+    #[inline]
+    fn is_populated(&self, address: usize) -> bool {
+        address < self.conventional_size || self.memory_mask[address] & MEM_ROM_BIT != 0
+    }
+
+    /// Fill any address above installed conventional memory that is not ROM with open bus bytes.
+    /// This is what the debugger sees; CPU reads of these addresses go through open_bus_byte().
+    fn fill_open_bus(&mut self) {
+        let fill = match self.open_bus_mode {
+            OpenBusMode::Low => 0x00,
+            _ => OPEN_BUS_BYTE,
+        };
+        let start = self.conventional_size;
+        for (byte_ref, mask) in self.memory[start..].iter_mut().zip(self.memory_mask[start..].iter()) {
+            if *mask & MEM_ROM_BIT == 0 {
+                *byte_ref = fill;
+            }
+        }
     }
 
     pub fn conventional_size(&self) -> usize {
@@ -654,10 +808,22 @@ impl BusInterface {
                 read_only,
             }
         });
+        self.rebuild_wait_map();
 
         Ok(())
     }
 
+    /// Remove a ROM previously mapped with copy_from(), making its address range writable
+    /// again. The contents of memory are left as they are until the next clear().
+    pub fn remove_rom(&mut self, location: usize, size: usize) {
+        let end = (location + size).min(self.memory.len());
+        for mask in self.memory_mask[location.min(end)..end].iter_mut() {
+            *mask &= !MEM_ROM_BIT;
+        }
+        self.desc_vec.retain(|desc| !(desc.read_only && desc.address == location && desc.size == size));
+        self.rebuild_wait_map();
+    }
+
     /// Write the specified bytes from src_vec into memory at location 'location'
     ///
     /// Does not obey memory mapping
@@ -684,6 +850,16 @@ impl BusInterface {
         self.memory[start..start + len].to_vec()
     }
 
+    /// Read a block of memory without side effects, using the bus peek functions to resolve MMIO
+    /// addresses. The range is clamped to the end of the address space.
+    pub fn peek_range(&self, start: usize, len: usize) -> Vec<u8> {
+        let start = start.min(self.memory.len());
+        let end = start.saturating_add(len).min(self.memory.len());
+        (start..end)
+            .map(|addr| self.peek_u8(addr).unwrap_or(OPEN_BUS_BYTE))
+            .collect()
+    }
+
     pub fn set_descriptor(&mut self, start: usize, size: usize, cycle_cost: u32, read_only: bool) {
         // TODO: prevent overlapping descriptors
         self.desc_vec.push({
@@ -694,6 +870,47 @@ impl BusInterface {
                 read_only,
             }
         });
+        self.rebuild_wait_map();
+    }
+
+    /// Set the number of wait states for each access to conventional memory.
+    pub fn set_conventional_wait_states(&mut self, wait_states: u32) {
+        self.conventional_wait_states = wait_states;
+        self.rebuild_wait_map();
+    }
+
+    /// Set the number of wait states for each access to ROM.
+    pub fn set_rom_wait_states(&mut self, wait_states: u32) {
+        self.rom_wait_states = wait_states;
+        self.rebuild_wait_map();
+    }
+
+    /// Rebuild the table of wait states for memory that is not memory-mapped. Conventional memory
+    /// and ROM cost their configured wait states, and any other range with a descriptor costs that
+    /// range's cycle cost. Wait states are only applied to CPU timing when the CPU has wait states
+    /// enabled.
+    fn rebuild_wait_map(&mut self) {
+        for (block, wait) in self.wait_map.iter_mut().enumerate() {
+            *wait = if (block << MMIO_MAP_SHIFT) < self.conventional_size {
+                self.conventional_wait_states
+            }
+            else {
+                DEFAULT_WAIT_STATES
+            };
+        }
+        for desc in self.desc_vec.iter().filter(|desc| desc.size > 0) {
+            let first = desc.address >> MMIO_MAP_SHIFT;
+            let last = ((desc.address + desc.size - 1) >> MMIO_MAP_SHIFT).min(MMIO_MAP_LEN - 1);
+            let cost = if desc.read_only {
+                self.rom_wait_states
+            }
+            else {
+                desc.cycle_cost
+            };
+            for wait in &mut self.wait_map[first..=last] {
+                *wait = cost;
+            }
+        }
     }
 
     pub fn clear(&mut self) {
@@ -702,15 +919,30 @@ impl BusInterface {
             *byte_ref &= !MEM_RET_BIT;
         }
 
-        // Set all bytes to 0
+        // Set all bytes to 0, then restore open bus above installed memory.
         for byte_ref in &mut self.memory {
             *byte_ref = 0;
         }
+        self.fill_open_bus();
+    }
+
+    /// Fill all installed non-ROM memory with a pseudo-random pattern derived from `seed`. The same
+    /// seed always produces the same memory contents.
+    pub fn seed_memory(&mut self, seed: u64) {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let installed = self.conventional_size;
+        for (byte_ref, mask) in self.memory[..installed].iter_mut().zip(self.memory_mask.iter()) {
+            let n: u8 = rng.gen();
+            if *mask & MEM_ROM_BIT == 0 {
+                *byte_ref = n;
+            }
+        }
     }
 
     pub fn reset(&mut self) {
         // Clear mem range descriptors
         self.desc_vec.clear();
+        self.rebuild_wait_map();
 
         self.clear();
     }
@@ -750,14 +982,20 @@ impl BusInterface {
     pub fn get_read_wait(&mut self, address: usize, cycles: u32) -> Result<u32, MemError> {
         if address < self.memory.len() {
             if self.memory_mask[address] & MEM_MMIO_BIT == 0 {
-                // Address is not mapped.
-                return Ok(DEFAULT_WAIT_STATES);
+                // Address is not mapped. Use the wait states for this region of memory.
+                return Ok(self.wait_map[address >> MMIO_MAP_SHIFT]);
             }
             else {
                 // Handle memory-mapped devices
                 let system_ticks = self.cpu_cycles_to_system_ticks(cycles);
 
                 match self.mmio_map_fast[address >> MMIO_MAP_SHIFT] {
+                    MmioDeviceType::Ems => {
+                        if let Some(ems) = &mut self.ems {
+                            let syswait = ems.get_read_wait(address, system_ticks);
+                            return Ok(self.system_ticks_to_cpu_cycles(syswait));
+                        }
+                    }
                     MmioDeviceType::Video(vid) => {
                         if let Some(card_dispatch) = self.videocards.get_mut(&vid) {
                             match card_dispatch {
@@ -766,7 +1004,8 @@ impl BusInterface {
                                     return Ok(self.system_ticks_to_cpu_cycles(syswait));
                                 }
                                 VideoCardDispatch::Cga(cga) => {
-                                    let syswait = cga.get_read_wait(address, system_ticks);
+                                    // Ticks we haven't handed to the CGA yet still move the beam.
+                                    let syswait = cga.get_read_wait(address, system_ticks + self.cga_tick_accum);
                                     return Ok(self.system_ticks_to_cpu_cycles(syswait));
                                 }
                                 #[cfg(feature = "ega")]
@@ -795,8 +1034,8 @@ impl BusInterface {
     pub fn get_write_wait(&mut self, address: usize, cycles: u32) -> Result<u32, MemError> {
         if address < self.memory.len() {
             if self.memory_mask[address] & MEM_MMIO_BIT == 0 {
-                // Address is not mapped.
-                return Ok(DEFAULT_WAIT_STATES);
+                // Address is not mapped. Use the wait states for this region of memory.
+                return Ok(self.wait_map[address >> MMIO_MAP_SHIFT]);
             }
             else {
                 // Handle memory-mapped devices
@@ -804,6 +1043,12 @@ impl BusInterface {
 
                 // Handle memory-mapped devices
                 match self.mmio_map_fast[address >> MMIO_MAP_SHIFT] {
+                    MmioDeviceType::Ems => {
+                        if let Some(ems) = &mut self.ems {
+                            let syswait = ems.get_write_wait(address, system_ticks);
+                            return Ok(self.system_ticks_to_cpu_cycles(syswait));
+                        }
+                    }
                     MmioDeviceType::Video(vid) => {
                         if let Some(card_dispatch) = self.videocards.get_mut(&vid) {
                             match card_dispatch {
@@ -812,7 +1057,8 @@ impl BusInterface {
                                     return Ok(self.system_ticks_to_cpu_cycles(syswait));
                                 }
                                 VideoCardDispatch::Cga(cga) => {
-                                    let syswait = cga.get_write_wait(address, system_ticks);
+                                    // Ticks we haven't handed to the CGA yet still move the beam.
+                                    let syswait = cga.get_write_wait(address, system_ticks + self.cga_tick_accum);
                                     return Ok(self.system_ticks_to_cpu_cycles(syswait));
                                 }
                                 #[cfg(feature = "ega")]
@@ -839,10 +1085,23 @@ impl BusInterface {
     }
 
     pub fn read_u8(&mut self, address: usize, cycles: u32) -> Result<(u8, u32), MemError> {
+        let result = self.read_u8_inner(address, cycles);
+        if let Ok((data, _)) = result {
+            self.last_bus_value = data;
+        }
+        result
+    }
+
+    fn read_u8_inner(&mut self, address: usize, cycles: u32) -> Result<(u8, u32), MemError> {
         if address < self.memory.len() {
             if self.memory_mask[address] & MEM_MMIO_BIT == 0 {
-                // Address is not mapped.
-                let data: u8 = self.memory[address];
+                // Address is not mapped. Read memory if it is populated, otherwise read open bus.
+                let data: u8 = if self.is_populated(address) {
+                    self.memory[address]
+                }
+                else {
+                    self.open_bus_byte()
+                };
                 return Ok((data, 0));
             }
             else {
@@ -850,6 +1109,12 @@ impl BusInterface {
                 let system_ticks = self.cpu_cycles_to_system_ticks(cycles);
 
                 match self.mmio_map_fast[address >> MMIO_MAP_SHIFT] {
+                    MmioDeviceType::Ems => {
+                        if let Some(ems) = &mut self.ems {
+                            let (data, _waits) = MemoryMappedDevice::mmio_read_u8(ems, address, system_ticks);
+                            return Ok((data, 0));
+                        }
+                    }
                     MmioDeviceType::Video(vid) => {
                         if let Some(card_dispatch) = self.videocards.get_mut(&vid) {
                             match card_dispatch {
@@ -893,6 +1158,11 @@ impl BusInterface {
             else {
                 // Handle memory-mapped devices
                 match self.mmio_map_fast[address >> MMIO_MAP_SHIFT] {
+                    MmioDeviceType::Ems => {
+                        if let Some(ems) = &self.ems {
+                            return Ok(MemoryMappedDevice::mmio_peek_u8(ems, address));
+                        }
+                    }
                     MmioDeviceType::Video(vid) => {
                         if let Some(card_dispatch) = self.videocards.get(&vid) {
                             match card_dispatch {
@@ -927,15 +1197,43 @@ impl BusInterface {
     }
 
     pub fn read_u16(&mut self, address: usize, cycles: u32) -> Result<(u16, u32), MemError> {
+        let result = self.read_u16_inner(address, cycles);
+        if let Ok((data, _)) = result {
+            self.last_bus_value = (data >> 8) as u8;
+        }
+        result
+    }
+
+    fn read_u16_inner(&mut self, address: usize, cycles: u32) -> Result<(u16, u32), MemError> {
         if address < self.memory.len() - 1 {
             if self.memory_mask[address] & MEM_MMIO_BIT == 0 {
-                // Address is not mapped.
-                let w: u16 = self.memory[address] as u16 | (self.memory[address + 1] as u16) << 8;
+                // Address is not mapped. Read memory if it is populated, otherwise read open bus.
+                // The low byte is on the bus by the time the high byte is read.
+                let lo = if self.is_populated(address) {
+                    self.memory[address]
+                }
+                else {
+                    self.open_bus_byte()
+                };
+                self.last_bus_value = lo;
+                let hi = if self.is_populated(address + 1) {
+                    self.memory[address + 1]
+                }
+                else {
+                    self.open_bus_byte()
+                };
+                let w: u16 = lo as u16 | (hi as u16) << 8;
                 return Ok((w, DEFAULT_WAIT_STATES));
             }
             else {
                 // Handle memory-mapped devices
                 match self.mmio_map_fast[address >> MMIO_MAP_SHIFT] {
+                    MmioDeviceType::Ems => {
+                        if let Some(ems) = &mut self.ems {
+                            let (data, _syswait) = MemoryMappedDevice::mmio_read_u16(ems, address, 0);
+                            return Ok((data, 0));
+                        }
+                    }
                     MmioDeviceType::Video(vid) => {
                         if let Some(card_dispatch) = self.videocards.get_mut(&vid) {
                             let system_ticks = self.cycles_to_ticks[cycles as usize];
@@ -975,6 +1273,7 @@ impl BusInterface {
     }
 
     pub fn write_u8(&mut self, address: usize, data: u8, cycles: u32) -> Result<u32, MemError> {
+        self.last_bus_value = data;
         if address < self.memory.len() {
             if self.memory_mask[address] & (MEM_MMIO_BIT | MEM_ROM_BIT) == 0 {
                 // Address is not mapped and not ROM, write to it if it is within conventional memory.
@@ -986,6 +1285,12 @@ impl BusInterface {
             else {
                 // Handle memory-mapped devices.
                 match self.mmio_map_fast[address >> MMIO_MAP_SHIFT] {
+                    MmioDeviceType::Ems => {
+                        if let Some(ems) = &mut self.ems {
+                            MemoryMappedDevice::mmio_write_u8(ems, address, data, 0);
+                            return Ok(DEFAULT_WAIT_STATES);
+                        }
+                    }
                     MmioDeviceType::Video(vid) => {
                         if let Some(card_dispatch) = self.videocards.get_mut(&vid) {
                             let system_ticks = self.cycles_to_ticks[cycles as usize];
@@ -1021,6 +1326,7 @@ impl BusInterface {
     }
 
     pub fn write_u16(&mut self, address: usize, data: u16, cycles: u32) -> Result<u32, MemError> {
+        self.last_bus_value = (data >> 8) as u8;
         if address < self.memory.len() - 1 {
             if self.memory_mask[address] & (MEM_MMIO_BIT | MEM_ROM_BIT) == 0 {
                 // Address is not mapped. Write to memory if within conventional memory size.
@@ -1036,6 +1342,12 @@ impl BusInterface {
             else {
                 // Handle memory-mapped devices
                 match self.mmio_map_fast[address >> MMIO_MAP_SHIFT] {
+                    MmioDeviceType::Ems => {
+                        if let Some(ems) = &mut self.ems {
+                            MemoryMappedDevice::mmio_write_u16(ems, address, data, 0);
+                            return Ok(DEFAULT_WAIT_STATES);
+                        }
+                    }
                     MmioDeviceType::Video(vid) => {
                         if let Some(card_dispatch) = self.videocards.get_mut(&vid) {
                             let system_ticks = self.cycles_to_ticks[cycles as usize];
@@ -1116,6 +1428,118 @@ impl BusInterface {
         }
     }
 
+    /// Add a watchpoint over the specified address range. CPU data accesses of the specified type
+    /// within the range will be recorded as a WatchHit, to be collected via take_watch_hit().
+    pub fn add_watch(&mut self, range: Range<usize>, access: WatchAccess) {
+        let range = range.start.min(self.memory.len())..range.end.min(self.memory.len());
+        log::debug!(
+            "Adding {:?} watchpoint over [{:05X}-{:05X})",
+            access,
+            range.start,
+            range.end
+        );
+        for address in range.clone() {
+            self.memory_mask[address] |= MEM_WATCH_BIT;
+        }
+        self.watchpoints.push(Watchpoint { range, access });
+    }
+
+    /// Remove any watchpoints exactly matching the specified address range.
+    pub fn remove_watch(&mut self, range: Range<usize>) {
+        self.watchpoints.retain(|wp| wp.range != range);
+        self.rebuild_watch_flags();
+    }
+
+    pub fn clear_watches(&mut self) {
+        self.watchpoints.clear();
+        self.watch_hit = None;
+        self.rebuild_watch_flags();
+    }
+
+    pub fn watches(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Return the watchpoint hit recorded since the last call, if any.
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
+
+    fn rebuild_watch_flags(&mut self) {
+        for flags in self.memory_mask.iter_mut() {
+            *flags &= !MEM_WATCH_BIT;
+        }
+        for wp in self.watchpoints.iter() {
+            for address in wp.range.clone() {
+                self.memory_mask[address] |= MEM_WATCH_BIT;
+            }
+        }
+    }
+
+    #[inline]
+    fn is_watched(&self, address: usize, size: usize) -> bool {
+        !self.watchpoints.is_empty()
+            && (address..address + size).any(|a| a < self.memory.len() && self.memory_mask[a] & MEM_WATCH_BIT != 0)
+    }
+
+    /// Check a CPU data read against the watchpoint list. Should be called after the read is
+    /// performed.
+    #[inline]
+    pub fn watch_read(&mut self, address: usize, size: usize) {
+        if self.is_watched(address, size) {
+            let value = self.peek_watched(address, size);
+            self.record_watch_hit(address, size, WatchAccess::Read, value, value);
+        }
+    }
+
+    /// Return the current contents of a watched range, to be passed to watch_write() once a CPU
+    /// write completes. Returns None if the range is not watched.
+    #[inline]
+    pub fn watch_peek(&self, address: usize, size: usize) -> Option<u16> {
+        if self.is_watched(address, size) {
+            Some(self.peek_watched(address, size))
+        }
+        else {
+            None
+        }
+    }
+
+    /// Check a CPU data write against the watchpoint list, given the value returned by
+    /// watch_peek() before the write was performed.
+    pub fn watch_write(&mut self, address: usize, size: usize, old_value: u16) {
+        let new_value = self.peek_watched(address, size);
+        self.record_watch_hit(address, size, WatchAccess::Write, old_value, new_value);
+    }
+
+    fn peek_watched(&self, address: usize, size: usize) -> u16 {
+        (0..size).fold(0, |acc, i| {
+            acc | (self.peek_u8(address + i).unwrap_or(OPEN_BUS_BYTE) as u16) << (i * 8)
+        })
+    }
+
+    fn record_watch_hit(&mut self, address: usize, size: usize, access: WatchAccess, old_value: u16, new_value: u16) {
+        if self.watch_hit.is_some() {
+            // Keep the first hit until it has been collected.
+            return;
+        }
+        for i in 0..size {
+            let byte_address = address + i;
+            if self
+                .watchpoints
+                .iter()
+                .any(|wp| wp.range.contains(&byte_address) && wp.access.matches(access))
+            {
+                self.watch_hit = Some(WatchHit {
+                    address: byte_address,
+                    access,
+                    old_value: (old_value >> (i * 8)) as u8,
+                    new_value: (new_value >> (i * 8)) as u8,
+                });
+                return;
+            }
+        }
+    }
+
     /// Dump memory to a string representation.
     ///
     /// Does not honor memory mappings.
@@ -1477,9 +1901,23 @@ impl BusInterface {
             .map(|fdc| fdc.drive.len() as u32)
             .unwrap_or(0);
 
+        // Set the open bus behavior, falling back to the most accurate mode for the machine type.
+        self.open_bus_mode = machine_config
+            .open_bus
+            .unwrap_or(OpenBusMode::default_for(machine_desc.machine_type));
+        log::debug!("Open bus mode: {:?}", self.open_bus_mode);
+
+        // Emulate DMA DRAM refresh if the machine type has it, unless overridden.
+        self.dram_refresh = machine_config
+            .dram_refresh
+            .unwrap_or(machine_desc.machine_type.has_dma_refresh());
+        log::debug!("DRAM refresh emulation: {}", self.dram_refresh);
+
         // Get normalized conventional memory and set it.
         let conventional_memory = normalize_conventional_memory(machine_config)?;
         self.set_conventional_size(conventional_memory as usize);
+        self.set_conventional_wait_states(machine_config.memory.conventional.wait_states);
+        self.set_rom_wait_states(machine_config.memory.rom_wait_states);
 
         // Set the expansion rom flag for DIP if there is anything besides a video card
         // that needs an expansion ROM.
@@ -1528,7 +1966,8 @@ impl BusInterface {
         self.pit = Some(pit);
 
         // Create DMA. One DMA controller will always exist.
-        let dma1 = DMAController::new();
+        let mut dma1 = DMAController::new();
+        dma1.set_trace_logger(self.trace_targets.logger_for(TraceTarget::Dma));
 
         // Add DMA ports to io_map
         let port_list = dma1.port_list();
@@ -1537,7 +1976,8 @@ impl BusInterface {
         self.dma1 = Some(dma1);
 
         // Create PIC. One PIC will always exist.
-        let pic1 = Pic::new();
+        let mut pic1 = Pic::new();
+        pic1.set_trace_logger(self.trace_targets.logger_for(TraceTarget::Pic));
         // Add PIC ports to io_map
         let port_list = pic1.port_list();
         self.io_map
@@ -1561,7 +2001,8 @@ impl BusInterface {
         if let Some(fdc_config) = &machine_config.fdc {
             let floppy_ct = fdc_config.drive.len();
 
-            let fdc = FloppyController::new(floppy_ct);
+            let mut fdc = FloppyController::new(floppy_ct);
+            fdc.set_trace_logger(self.trace_targets.logger_for(TraceTarget::Fdc));
             // Add FDC ports to io_map
             let port_list = fdc.port_list();
             self.io_map
@@ -1584,11 +2025,47 @@ impl BusInterface {
             }
         }
 
+        // Apply the boot order. The BIOS always tries floppy drive 0 first, so to boot from the hard disk
+        // we hold the floppy drives as empty until a boot sector is running. The machine releases the
+        // hold early if the hard disk turns out not to be bootable.
+        self.floppy_boot_hold = false;
+        match machine_config.boot_order.first() {
+            Some(BootTarget::HardDisk(0)) if self.hdc.is_some() => {
+                log::debug!("Boot order: hard disk first. Holding floppy drives until hard disk boot.");
+                self.floppy_boot_hold = true;
+            }
+            Some(BootTarget::Floppy(0)) | None => {}
+            Some(target) => {
+                log::warn!("Boot order: can't boot from {:?}. Using BIOS default.", target);
+            }
+        }
+        if let Some(fdc) = &mut self.fdc {
+            fdc.set_boot_hold(self.floppy_boot_hold);
+        }
+
         // Create a Serial card if specified
         if let Some(serial_config) = machine_config.serial.get(0) {
             match serial_config.sc_type {
                 SerialControllerType::IbmAsync => {
-                    let serial = SerialPortController::new();
+                    let mut ports: Vec<(u16, u8)> = serial_config
+                        .port
+                        .iter()
+                        .map(|port| (port.io_base as u16, port.irq as u8))
+                        .collect();
+                    if ports.is_empty() {
+                        // No ports specified: install COM1 and COM2, as the original adapter pair.
+                        ports.extend_from_slice(&SERIAL_STANDARD_PORTS[0..2]);
+                    }
+                    else if ports.len() > SERIAL_MAX_PORTS {
+                        log::warn!(
+                            "Only {} serial ports are supported. Ignoring additional ports.",
+                            SERIAL_MAX_PORTS
+                        );
+                    }
+                    let mut serial = SerialPortController::new(serial_config.uart, &ports);
+                    for (i, port) in serial_config.port.iter().take(serial.port_ct()).enumerate() {
+                        serial.set_backend(i, port.backend);
+                    }
                     // Add Serial Controller ports to io_map
                     let port_list = serial.port_list();
                     self.io_map
@@ -1601,16 +2078,122 @@ impl BusInterface {
         // Create a Serial mouse if specified
         if let Some(serial_mouse_config) = &machine_config.serial_mouse {
             // Only create mouse if we have as serial card to plug it into!
-            if self.serial.is_some() {
-                match serial_mouse_config.mouse_type {
-                    SerialMouseType::Microsoft => {
-                        let mouse = Mouse::new(serial_mouse_config.port as usize);
-                        self.mouse = Some(mouse);
+            if let Some(serial) = &self.serial {
+                if serial_mouse_config.port as usize >= serial.port_ct() {
+                    log::error!(
+                        "Serial mouse port {} is not present ({} serial ports). Mouse not installed.",
+                        serial_mouse_config.port,
+                        serial.port_ct()
+                    );
+                }
+                else {
+                    match serial_mouse_config.mouse_type {
+                        SerialMouseType::Microsoft => {
+                            let mouse = Mouse::new(
+                                serial_mouse_config.port as usize,
+                                serial_mouse_config.report_rate.unwrap_or(MOUSE_DEFAULT_REPORT_RATE),
+                            );
+                            self.mouse = Some(mouse);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Create sound devices if specified. Only one of each type is supported.
+        for sound_config in machine_config.sound.iter() {
+            match sound_config.sound_type {
+                SoundType::AdLib => {
+                    if self.adlib.is_some() {
+                        log::warn!("Only one AdLib card is supported. Ignoring additional card.");
+                        continue;
+                    }
+                    let adlib = AdLibCard::new(sound_config.io_base);
+                    // Add AdLib ports to io_map
+                    let port_list = adlib.port_list();
+                    self.io_map
+                        .extend(port_list.into_iter().map(|p| (p, IoDeviceType::AdLib)));
+                    self.adlib = Some(adlib);
+                }
+                SoundType::Sn76489 => {
+                    if self.sn76489.is_some() {
+                        log::warn!("Only one SN76489 is supported. Ignoring additional device.");
+                        continue;
                     }
+                    let sn76489 = Sn76489::new(sound_config.io_base);
+                    let port_list = sn76489.port_list();
+                    self.io_map
+                        .extend(port_list.into_iter().map(|p| (p, IoDeviceType::Sn76489)));
+                    self.sn76489 = Some(sn76489);
                 }
             }
         }
 
+        // Create EMS board if specified.
+        if let Some(ems_config) = &machine_config.ems {
+            let ems = EmsCard::new(ems_config.size_kb, ems_config.io_base, ems_config.segment);
+            let port_list = ems.port_list();
+            self.io_map
+                .extend(port_list.into_iter().map(|p| (p, IoDeviceType::Ems)));
+
+            let mem_descriptor = MemRangeDescriptor::new(ems.frame_address(), EMS_PAGE_FRAME_SIZE, false);
+            self.register_map(MmioDeviceType::Ems, mem_descriptor);
+            log::debug!(
+                "Installed {}KB EMS board with page frame at {:05X}",
+                ems.page_count() * 16,
+                ems.frame_address()
+            );
+            self.ems = Some(ems);
+        }
+
+        // Create game port if specified.
+        if let Some(game_port_config) = &machine_config.game_port {
+            let game_port = GamePort::new(game_port_config.io_base);
+            let port_list = game_port.port_list();
+            self.io_map
+                .extend(port_list.into_iter().map(|p| (p, IoDeviceType::GamePort)));
+            self.game_port = Some(game_port);
+        }
+
+        // Create parallel port if specified.
+        if let Some(parallel_config) = &machine_config.parallel {
+            let mut parallel = ParallelController::new(parallel_config.io_base, TraceLogger::None);
+            if parallel_config.printer {
+                parallel.port_mut().attach_printer();
+                if let Some(capture_file) = &parallel_config.capture_file {
+                    if let Err(e) = parallel.port_mut().set_capture_file(capture_file) {
+                        log::error!("Failed to open printer capture file {}: {}", capture_file.display(), e);
+                    }
+                }
+            }
+            let port_list = parallel.port_list();
+            self.io_map
+                .extend(port_list.into_iter().map(|p| (p, IoDeviceType::Parallel)));
+            self.parallel = Some(parallel);
+        }
+
+        // Create real time clock if specified.
+        if let Some(rtc_config) = &machine_config.rtc {
+            let rtc = Rtc::new(
+                rtc_config.rtc_type,
+                rtc_config.io_base,
+                rtc_config.fixed_time,
+                rtc_config.utc_offset_minutes.unwrap_or(0),
+            );
+            let port_list = rtc.port_list();
+            self.io_map.extend(port_list.into_iter().map(|p| (p, IoDeviceType::Rtc)));
+            self.rtc = Some(rtc);
+        }
+
+        // Create POST card if specified.
+        if let Some(post_card_config) = &machine_config.post_card {
+            let post_card = PostCard::new(post_card_config.io_base);
+            let port_list = post_card.port_list();
+            self.io_map
+                .extend(port_list.into_iter().map(|p| (p, IoDeviceType::PostCard)));
+            self.post_card = Some(post_card);
+        }
+
         // Create video cards
         for (i, card) in machine_config.video.iter().enumerate() {
             let video_dispatch;
@@ -1619,10 +2202,32 @@ impl BusInterface {
                 vtype: card.video_type,
             };
 
+            // Read the custom font, if one was specified. If it can't be read, the card keeps its
+            // built-in font.
+            let font = card.font.as_ref().and_then(|path| match std::fs::read(path) {
+                Ok(font) => Some(font),
+                Err(e) => {
+                    log::warn!("Failed to read font file {}: {}", path.display(), e);
+                    None
+                }
+            });
+
+            // Only the primary card is traced, so that multiple cards don't share a trace file.
+            let video_trace = match i {
+                0 => self.trace_targets.logger_for(TraceTarget::Video),
+                _ => TraceLogger::None,
+            };
+
             log::debug!("Creating video card of type: {:?}", card.video_type);
             match card.video_type {
                 VideoType::MDA => {
-                    let mda = MDACard::new(TraceLogger::None, clock_mode, true, video_frame_debug);
+                    let mut mda = MDACard::new(video_trace, clock_mode, true, video_frame_debug);
+                    if font.is_some() {
+                        mda.set_font(font.as_deref());
+                    }
+                    if card.vram_size.is_some() || card.vram_open_bus {
+                        log::warn!("VRAM configuration is not supported on MDA. Ignoring.");
+                    }
                     let port_list = mda.port_list();
                     self.io_map
                         .extend(port_list.into_iter().map(|p| (p, IoDeviceType::Video(video_id))));
@@ -1633,7 +2238,22 @@ impl BusInterface {
                     video_dispatch = VideoCardDispatch::Mda(mda)
                 }
                 VideoType::CGA => {
-                    let cga = CGACard::new(TraceLogger::None, clock_mode, video_frame_debug);
+                    let mut cga = CGACard::new(video_trace, clock_mode, video_frame_debug);
+                    if machine_desc.machine_type == MachineType::Tandy1000 {
+                        // The Tandy 1000's video gate array extends the CGA with 16-color modes.
+                        cga.enable_tandy_extensions();
+                    }
+                    if font.is_some() {
+                        cga.set_font(font.as_deref());
+                    }
+                    if card.vram_size.is_some() || card.vram_open_bus {
+                        if cga.is_tandy() {
+                            log::warn!("VRAM configuration is not supported on the Tandy 1000. Ignoring.");
+                        }
+                        else {
+                            cga.set_vram(card.vram_size.unwrap_or(cga::CGA_MEM_SIZE), card.vram_open_bus);
+                        }
+                    }
                     let port_list = cga.port_list();
                     self.io_map
                         .extend(port_list.into_iter().map(|p| (p, IoDeviceType::Video(video_id))));
@@ -1645,7 +2265,10 @@ impl BusInterface {
                 }
                 #[cfg(feature = "ega")]
                 VideoType::EGA => {
-                    let ega = EGACard::new(TraceLogger::None, clock_mode, video_frame_debug);
+                    if font.is_some() {
+                        log::warn!("Custom fonts are not supported on EGA. Ignoring font file.");
+                    }
+                    let ega = EGACard::new(video_trace, clock_mode, video_frame_debug);
                     let port_list = ega.port_list();
                     self.io_map
                         .extend(port_list.into_iter().map(|p| (p, IoDeviceType::Video(video_id))));
@@ -1661,7 +2284,10 @@ impl BusInterface {
                 }
                 #[cfg(feature = "vga")]
                 VideoType::VGA => {
-                    let vga = VGACard::new(TraceLogger::None);
+                    if font.is_some() {
+                        log::warn!("Custom fonts are not supported on VGA. Ignoring font file.");
+                    }
+                    let vga = VGACard::new(video_trace);
                     let port_list = vga.port_list();
                     self.io_map
                         .extend(port_list.into_iter().map(|p| (p, IoDeviceType::Video(video_id))));
@@ -1810,11 +2436,30 @@ impl BusInterface {
 
         // Save current count info.
         let (pit_reload_value, pit_counting_element, pit_counting) = pit.get_channel_count(0);
+        let pit_mode = pit.channel0_state().mode;
 
         // Set imminent interrupt flag. The CPU can use this as a hint to adjust cycles for halt instructions - using
         // more cycles when an interrupt is not imminent, and one cycle when it is. This allows for cycle-precise wake
         // from halt.
         self.intr_imminent = pit_counting & (pit_counting_element <= IMMINENT_TIMER_INTERRUPT);
+        self.intr_distance = if pit_counting {
+            // In mode 3 the counting element decrements by two on each PIT tick.
+            let elements = pit_counting_element.saturating_sub(IMMINENT_TIMER_INTERRUPT) as u32;
+            let pit_ticks = match pit_mode {
+                ChannelMode::SquareWaveGenerator => elements / 2,
+                _ => elements,
+            };
+            pit_ticks * CPU_CYCLES_PER_PIT_TICK
+        }
+        else {
+            HALT_IDLE_MAX_CYCLES
+        };
+
+        // An interrupt already pending at the PIC will wake the CPU as soon as INTR is raised, so don't let a halted
+        // CPU skip past it.
+        if self.pic1.as_ref().is_some_and(|pic| pic.intr_pending()) {
+            self.intr_distance = 0;
+        }
 
         if self.do_title_hacks {
             // Arm timer adjustment triggers for Area5150 lake/wibble effects.
@@ -1861,6 +2506,26 @@ impl BusInterface {
             }
         }
 
+        // Run the AdLib card.
+        if let Some(adlib) = &mut self.adlib {
+            adlib.run(us);
+        }
+
+        // Run the SN76489 sound chip.
+        if let Some(sn76489) = &mut self.sn76489 {
+            sn76489.run(us);
+        }
+
+        // Run the game port's one-shot timers.
+        if let Some(game_port) = &mut self.game_port {
+            game_port.run(us);
+        }
+
+        // Advance the real time clock.
+        if let Some(rtc) = &mut self.rtc {
+            rtc.run(us);
+        }
+
         let mut do_area5150_hack = false;
         let mut save_cga: VideoCardId = Default::default();
 
@@ -1953,7 +2618,17 @@ impl BusInterface {
         }
     }
 
+    /// Return whether DRAM refresh cycles are scheduled on the CPU. Refresh cycles only take
+    /// effect while CPU wait states are enabled.
+    pub fn dram_refresh(&self) -> bool {
+        self.dram_refresh
+    }
+
     pub fn handle_refresh_scheduling(&mut self, pit: &mut Pit, event: &mut Option<DeviceEvent>) {
+        if !self.dram_refresh {
+            return;
+        }
+
         // Has PIT channel 1 (DMA timer) changed?
         let (pit_dirty, pit_counting, pit_ticked) = pit.is_dirty(1);
 
@@ -2033,6 +2708,41 @@ impl BusInterface {
             dma1.reset();
         }
 
+        // Reset AdLib
+        if let Some(adlib) = self.adlib.as_mut() {
+            adlib.reset();
+        }
+
+        // Reset SN76489
+        if let Some(sn76489) = self.sn76489.as_mut() {
+            sn76489.reset();
+        }
+
+        // Reset EMS page mappings
+        if let Some(ems) = self.ems.as_mut() {
+            ems.reset();
+        }
+
+        // Reset game port
+        if let Some(game_port) = self.game_port.as_mut() {
+            game_port.reset();
+        }
+
+        // Reset real time clock
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.reset();
+        }
+
+        // Reset POST card
+        if let Some(post_card) = self.post_card.as_mut() {
+            post_card.reset();
+        }
+
+        // Re-apply the floppy boot hold
+        if let Some(fdc) = self.fdc.as_mut() {
+            fdc.set_boot_hold(self.floppy_boot_hold);
+        }
+
         // Reset video cards
         let vids: Vec<_> = self.videocards.keys().cloned().collect();
         for vid in vids {
@@ -2072,14 +2782,16 @@ impl BusInterface {
         };
         let nul_delta = DeviceRunTimeUnit::Microseconds(0.0);
 
-        if let Some(device_id) = self.io_map.get(&port) {
+        let open_bus = self.open_bus_byte();
+
+        let byte = if let Some(device_id) = self.io_map.get(&port) {
             match device_id {
                 IoDeviceType::Ppi => {
                     if let Some(ppi) = &mut self.ppi {
                         ppi.read_u8(port, nul_delta)
                     }
                     else {
-                        NO_IO_BYTE
+                        open_bus
                     }
                 }
                 IoDeviceType::Pit => {
@@ -2100,7 +2812,7 @@ impl BusInterface {
                         dma2.read_u8(port, nul_delta)
                     }
                     else {
-                        NO_IO_BYTE
+                        open_bus
                     }
                 }
                 IoDeviceType::PicPrimary => {
@@ -2113,7 +2825,7 @@ impl BusInterface {
                         pic2.read_u8(port, nul_delta)
                     }
                     else {
-                        NO_IO_BYTE
+                        open_bus
                     }
                 }
                 IoDeviceType::FloppyController => {
@@ -2121,7 +2833,7 @@ impl BusInterface {
                         fdc.read_u8(port, nul_delta)
                     }
                     else {
-                        NO_IO_BYTE
+                        open_bus
                     }
                 }
                 IoDeviceType::HardDiskController => {
@@ -2129,7 +2841,7 @@ impl BusInterface {
                         hdc.read_u8(port, nul_delta)
                     }
                     else {
-                        NO_IO_BYTE
+                        open_bus
                     }
                 }
                 IoDeviceType::Serial => {
@@ -2138,7 +2850,63 @@ impl BusInterface {
                         serial.read_u8(port, nul_delta)
                     }
                     else {
-                        NO_IO_BYTE
+                        open_bus
+                    }
+                }
+                IoDeviceType::AdLib => {
+                    if let Some(adlib) = &mut self.adlib {
+                        adlib.read_u8(port, nul_delta)
+                    }
+                    else {
+                        open_bus
+                    }
+                }
+                IoDeviceType::Sn76489 => {
+                    if let Some(sn76489) = &mut self.sn76489 {
+                        sn76489.read_u8(port, nul_delta)
+                    }
+                    else {
+                        open_bus
+                    }
+                }
+                IoDeviceType::Ems => {
+                    if let Some(ems) = &mut self.ems {
+                        ems.read_u8(port, nul_delta)
+                    }
+                    else {
+                        open_bus
+                    }
+                }
+                IoDeviceType::GamePort => {
+                    if let Some(game_port) = &mut self.game_port {
+                        game_port.read_u8(port, nul_delta)
+                    }
+                    else {
+                        open_bus
+                    }
+                }
+                IoDeviceType::Parallel => {
+                    if let Some(parallel) = &mut self.parallel {
+                        parallel.read_u8(port, nul_delta)
+                    }
+                    else {
+                        open_bus
+                    }
+                }
+                IoDeviceType::Rtc => {
+                    if let Some(rtc) = &mut self.rtc {
+                        rtc.read_u8(port, nul_delta)
+                    }
+                    else {
+                        open_bus
+                    }
+                }
+                IoDeviceType::PostCard => {
+                    if let Some(post_card) = &mut self.post_card {
+                        post_card.read_u8(port, nul_delta)
+                    }
+                    else {
+                        open_bus
                     }
                 }
 
@@ -2155,20 +2923,22 @@ impl BusInterface {
                             VideoCardDispatch::Ega(ega) => IoDevice::read_u8(ega, port, nul_delta),
                             #[cfg(feature = "vga")]
                             VideoCardDispatch::Vga(vga) => IoDevice::read_u8(vga, port, nul_delta),
-                            VideoCardDispatch::None => NO_IO_BYTE,
+                            VideoCardDispatch::None => open_bus,
                         }
                     }
                     else {
-                        NO_IO_BYTE
+                        open_bus
                     }
                 }
-                _ => NO_IO_BYTE,
+                _ => open_bus,
             }
         }
         else {
             // Unhandled IO address read
-            NO_IO_BYTE
-        }
+            open_bus
+        };
+        self.last_bus_value = byte;
+        byte
     }
 
     /// Write an 8-bit value to an IO port.
@@ -2176,6 +2946,7 @@ impl BusInterface {
     /// We provide the elapsed cycle count for the current instruction. This allows a device
     /// to optionally tick itself to bring itself in sync with CPU state.
     pub fn io_write_u8(&mut self, port: u16, data: u8, cycles: u32) {
+        self.last_bus_value = data;
         /*
         let handler_opt = self.handlers.get_mut(&port);
         if let Some(handler) = handler_opt {
@@ -2251,6 +3022,41 @@ impl BusInterface {
                         serial.write_u8(port, data, None, nul_delta);
                     }
                 }
+                IoDeviceType::AdLib => {
+                    if let Some(adlib) = &mut self.adlib {
+                        adlib.write_u8(port, data, None, nul_delta);
+                    }
+                }
+                IoDeviceType::Sn76489 => {
+                    if let Some(sn76489) = &mut self.sn76489 {
+                        sn76489.write_u8(port, data, None, nul_delta);
+                    }
+                }
+                IoDeviceType::Ems => {
+                    if let Some(ems) = &mut self.ems {
+                        ems.write_u8(port, data, None, nul_delta);
+                    }
+                }
+                IoDeviceType::GamePort => {
+                    if let Some(game_port) = &mut self.game_port {
+                        game_port.write_u8(port, data, None, nul_delta);
+                    }
+                }
+                IoDeviceType::Parallel => {
+                    if let Some(parallel) = &mut self.parallel {
+                        parallel.write_u8(port, data, None, nul_delta);
+                    }
+                }
+                IoDeviceType::Rtc => {
+                    if let Some(rtc) = &mut self.rtc {
+                        rtc.write_u8(port, data, None, nul_delta);
+                    }
+                }
+                IoDeviceType::PostCard => {
+                    if let Some(post_card) = &mut self.post_card {
+                        post_card.write_u8(port, data, None, nul_delta);
+                    }
+                }
                 IoDeviceType::Video(vid) => {
                     if let Some(video_dispatch) = self.videocards.get_mut(&vid) {
                         match video_dispatch {
@@ -2280,6 +3086,13 @@ impl BusInterface {
         self.intr_imminent
     }
 
+    /// Return the number of cycles a halted CPU can skip without stepping past the point where the next timer
+    /// interrupt becomes imminent.
+    #[inline]
+    pub fn halt_idle_cycles(&self) -> u32 {
+        self.intr_distance.min(HALT_IDLE_MAX_CYCLES)
+    }
+
     // Device accessors
     pub fn pit(&self) -> &Option<Pit> {
         &self.pit
@@ -2289,10 +3102,85 @@ impl BusInterface {
         &mut self.pit
     }
 
+    /// Return a label for each of the primary PIC's IRQ lines, naming the installed devices
+    /// connected to it. Lines with no known device have an empty label.
+    pub fn irq_labels(&self) -> [String; 8] {
+        let mut labels: [Vec<String>; 8] = Default::default();
+
+        if self.pit.is_some() {
+            labels[0].push("Timer".to_string());
+        }
+        if self.ppi.is_some() {
+            labels[1].push("Keyboard".to_string());
+        }
+        if let Some(serial) = &self.serial {
+            for (name, irq) in serial.port_irqs() {
+                if let Some(label) = labels.get_mut(irq as usize) {
+                    label.push(name);
+                }
+            }
+        }
+        if self.hdc.is_some() {
+            labels[HDC_IRQ as usize].push("HDC".to_string());
+        }
+        if self.fdc.is_some() {
+            labels[FDC_IRQ as usize].push("FDC".to_string());
+        }
+        if let Some(parallel) = &self.parallel {
+            if let Some(label) = labels.get_mut(parallel.port().irq() as usize) {
+                label.push("LPT".to_string());
+            }
+        }
+
+        labels.map(|l| l.join("/"))
+    }
+
+    /// Set the trace targets to use for devices created by install_devices().
+    pub fn set_trace_targets(&mut self, targets: TraceTargets) {
+        self.trace_targets = targets;
+    }
+
+    /// Install or remove the port IO trace.
+    pub fn set_io_trace(&mut self, trace: Option<IoTrace>) {
+        if let Some(mut old_trace) = std::mem::replace(&mut self.io_trace, trace) {
+            old_trace.flush();
+        }
+    }
+
+    pub fn io_trace_mut(&mut self) -> &mut Option<IoTrace> {
+        &mut self.io_trace
+    }
+
+    #[inline]
+    pub fn io_trace_enabled(&self) -> bool {
+        self.io_trace.is_some()
+    }
+
+    /// Record a port access made by the instruction at `address` in the IO trace, if enabled.
+    pub fn trace_io(&mut self, port: u16, value: u8, direction: IoDirection, address: CpuAddress) {
+        if let Some(trace) = &mut self.io_trace {
+            trace.record(IoTraceEntry {
+                port,
+                value,
+                direction,
+                address,
+                unmapped: !self.io_map.contains_key(&port),
+            });
+        }
+    }
+
+    pub fn pic(&self) -> &Option<Pic> {
+        &self.pic1
+    }
+
     pub fn pic_mut(&mut self) -> &mut Option<Pic> {
         &mut self.pic1
     }
 
+    pub fn ppi(&self) -> &Option<Ppi> {
+        &self.ppi
+    }
+
     pub fn ppi_mut(&mut self) -> &mut Option<Ppi> {
         &mut self.ppi
     }
@@ -2305,10 +3193,18 @@ impl BusInterface {
         &mut self.serial
     }
 
+    pub fn fdc(&self) -> &Option<FloppyController> {
+        &self.fdc
+    }
+
     pub fn fdc_mut(&mut self) -> &mut Option<FloppyController> {
         &mut self.fdc
     }
 
+    pub fn hdc(&self) -> &Option<HardDiskController> {
+        &self.hdc
+    }
+
     pub fn hdc_mut(&mut self) -> &mut Option<HardDiskController> {
         &mut self.hdc
     }
@@ -2317,6 +3213,47 @@ impl BusInterface {
         &mut self.mouse
     }
 
+    pub fn adlib_mut(&mut self) -> &mut Option<AdLibCard> {
+        &mut self.adlib
+    }
+
+    pub fn sn76489_mut(&mut self) -> &mut Option<Sn76489> {
+        &mut self.sn76489
+    }
+
+    /// Deliver a raw scancode to the keyboard interface, bypassing the keyboard device.
+    pub fn inject_scancode(&mut self, scancode: u8) {
+        if let Some(ppi) = &mut self.ppi {
+            ppi.send_keyboard(scancode);
+
+            if ppi.kb_enabled() {
+                if let Some(pic) = &mut self.pic1 {
+                    pic.pulse_interrupt(1);
+                }
+            }
+        }
+    }
+
+    pub fn ems_mut(&mut self) -> &mut Option<EmsCard> {
+        &mut self.ems
+    }
+
+    pub fn game_port_mut(&mut self) -> &mut Option<GamePort> {
+        &mut self.game_port
+    }
+
+    pub fn parallel_mut(&mut self) -> &mut Option<ParallelController> {
+        &mut self.parallel
+    }
+
+    pub fn rtc_mut(&mut self) -> &mut Option<Rtc> {
+        &mut self.rtc
+    }
+
+    pub fn post_card_mut(&mut self) -> &mut Option<PostCard> {
+        &mut self.post_card
+    }
+
     pub fn primary_video(&self) -> Option<Box<&dyn VideoCard>> {
         if self.videocard_ids.len() > 0 {
             self.video(&self.videocard_ids[0])
@@ -2326,6 +3263,20 @@ impl BusInterface {
         }
     }
 
+    /// Return the frame count of the primary video card, if one is installed. Unlike [Self::primary_video]
+    /// this doesn't box a trait object, so it is cheap enough to poll after every instruction.
+    pub fn primary_video_frame_count(&self) -> Option<u64> {
+        match self.videocards.get(self.videocard_ids.first()?)? {
+            VideoCardDispatch::Mda(mda) => Some(mda.get_frame_count()),
+            VideoCardDispatch::Cga(cga) => Some(cga.get_frame_count()),
+            #[cfg(feature = "ega")]
+            VideoCardDispatch::Ega(ega) => Some(ega.get_frame_count()),
+            #[cfg(feature = "vga")]
+            VideoCardDispatch::Vga(vga) => Some(vga.get_frame_count()),
+            VideoCardDispatch::None => None,
+        }
+    }
+
     pub fn primary_video_mut(&mut self) -> Option<Box<&mut dyn VideoCard>> {
         if self.videocard_ids.len() > 0 {
             let vid = self.videocard_ids[0];
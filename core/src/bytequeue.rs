@@ -69,3 +69,80 @@ pub trait ByteQueue {
     fn q_peek_i16(&mut self) -> i16;
     fn q_peek_farptr16(&mut self) -> (u16, u16);
 }
+
+/// A ByteQueue over a slice of bytes. This allows instructions to be decoded from a copy of
+/// memory without seeking or otherwise touching the bus. Reads past the end of the slice return
+/// 0xFF, as from open bus.
+pub struct SliceQueue<'a> {
+    bytes:  &'a [u8],
+    cursor: usize,
+}
+
+impl<'a> SliceQueue<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, cursor: 0 }
+    }
+
+    fn byte_at(&self, pos: usize) -> u8 {
+        self.bytes.get(pos).copied().unwrap_or(0xFF)
+    }
+
+    fn word_at(&self, pos: usize) -> u16 {
+        self.byte_at(pos) as u16 | (self.byte_at(pos + 1) as u16) << 8
+    }
+}
+
+impl ByteQueue for SliceQueue<'_> {
+    fn seek(&mut self, pos: usize) {
+        self.cursor = pos;
+    }
+
+    fn tell(&self) -> usize {
+        self.cursor
+    }
+
+    fn wait(&mut self, _cycles: u32) {}
+    fn wait_i(&mut self, _cycles: u32, _instr: &[u16]) {}
+    fn wait_comment(&mut self, _comment: &'static str) {}
+    fn set_pc(&mut self, _pc: u16) {}
+
+    fn q_read_u8(&mut self, _qtype: QueueType, _reader: QueueReader) -> u8 {
+        let b = self.byte_at(self.cursor);
+        self.cursor += 1;
+        b
+    }
+
+    fn q_read_i8(&mut self, qtype: QueueType, reader: QueueReader) -> i8 {
+        self.q_read_u8(qtype, reader) as i8
+    }
+
+    fn q_read_u16(&mut self, _qtype: QueueType, _reader: QueueReader) -> u16 {
+        let w = self.word_at(self.cursor);
+        self.cursor += 2;
+        w
+    }
+
+    fn q_read_i16(&mut self, qtype: QueueType, reader: QueueReader) -> i16 {
+        self.q_read_u16(qtype, reader) as i16
+    }
+
+    fn q_peek_u8(&mut self) -> u8 {
+        self.byte_at(self.cursor)
+    }
+
+    fn q_peek_i8(&mut self) -> i8 {
+        self.byte_at(self.cursor) as i8
+    }
+
+    fn q_peek_u16(&mut self) -> u16 {
+        self.word_at(self.cursor)
+    }
+
+    fn q_peek_i16(&mut self) -> i16 {
+        self.word_at(self.cursor) as i16
+    }
+
+    fn q_peek_farptr16(&mut self) -> (u16, u16) {
+        (self.word_at(self.cursor + 2), self.word_at(self.cursor))
+    }
+}
@@ -42,7 +42,10 @@ use crate::{
 };
 use std::path::PathBuf;
 
-use crate::machine_types::OnHaltBehavior;
+use crate::{
+    machine_types::{OnHaltBehavior, WatchdogConfig},
+    tracelogger::TraceTargetConfig,
+};
 use serde::Deserialize;
 
 #[derive(Copy, Clone, Debug, Deserialize)]
@@ -59,8 +62,14 @@ pub trait CoreConfig {
     fn get_machine_type(&self) -> MachineType;
 
     fn get_audio_enabled(&self) -> bool;
+    fn get_audio_volume(&self) -> Option<f32>;
+    fn get_audio_mute_speaker(&self) -> bool;
+    fn get_audio_mute_adlib(&self) -> bool;
+    fn get_audio_mute_post_beep(&self) -> bool;
     fn get_machine_noroms(&self) -> bool;
     fn get_machine_turbo(&self) -> bool;
+    fn get_machine_turbo_mhz(&self) -> Option<f64>;
+    fn get_machine_cpu_mhz(&self) -> Option<f64>;
     //fn get_keyboard_type(&self) -> Option<KeyboardType>;
     fn get_keyboard_layout(&self) -> Option<String>;
     fn get_keyboard_debug(&self) -> bool;
@@ -75,6 +84,12 @@ pub trait CoreConfig {
     fn get_cpu_trace_on(&self) -> bool;
     fn get_cpu_trace_file(&self) -> Option<PathBuf>;
     fn get_title_hacks(&self) -> bool;
+    fn get_fast_boot(&self) -> bool;
     fn get_patch_enabled(&self) -> bool;
     fn get_halt_behavior(&self) -> OnHaltBehavior;
+    fn get_watchdog(&self) -> WatchdogConfig;
+    fn get_trace_targets(&self) -> Vec<TraceTargetConfig>;
+    /// The segment and offset the CPU starts executing at after a reset, in place of the
+    /// hardware reset vector.
+    fn get_entry_point(&self) -> Option<(u16, u16)>;
 }
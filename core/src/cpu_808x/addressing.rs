@@ -83,15 +83,21 @@ impl Cpu {
         base.wrapping_add(offset as u16)
     }
 
+    /// Return the value of the segment register selected by 'segment'.
     #[inline]
-    pub fn calc_linear_address_seg(&self, segment: Segment, offset: u16) -> u32 {
-        let segment_val: u16 = match segment {
+    pub fn segment_value(&self, segment: Segment) -> u16 {
+        match segment {
             Segment::None => 0,
             Segment::ES => self.es,
             Segment::CS => self.cs,
             Segment::DS => self.ds,
             Segment::SS => self.ss,
-        };
+        }
+    }
+
+    #[inline]
+    pub fn calc_linear_address_seg(&self, segment: Segment, offset: u16) -> u32 {
+        let segment_val: u16 = self.segment_value(segment);
         (((segment_val as u32) << 4) + offset as u32) & 0xFFFFFu32
     }
 
@@ -110,6 +116,18 @@ impl Cpu {
         &mut self,
         mode: AddressingMode,
         segment_override: SegmentOverride,
+    ) -> (u16, Segment, u16) {
+        let (seg_val, seg, offset) = self.peek_effective_address(mode, segment_override);
+        self.last_ea = offset; // Save last EA to do voodoo when LEA is called with reg, reg operands
+        (seg_val, seg, offset)
+    }
+
+    /// Calculate the Effective Address for the given AddressingMode enum from the current register
+    /// state without modifying the CPU. Used by the debugger to resolve operands before execution.
+    pub fn peek_effective_address(
+        &self,
+        mode: AddressingMode,
+        segment_override: SegmentOverride,
     ) -> (u16, Segment, u16) {
         // Addressing modes that reference BP use the stack segment instead of data segment
         // unless a segment override is present.
@@ -196,7 +214,6 @@ impl Cpu {
             AddressingMode::RegisterMode => panic!("Can't calculate EA for register")
         };
 
-        self.last_ea = offset; // Save last EA to do voodoo when LEA is called with reg, reg operands
         (seg_val, seg, offset)
     }
 
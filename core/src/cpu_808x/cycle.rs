@@ -32,7 +32,7 @@
 
 */
 
-use crate::cpu_808x::*;
+use crate::{cpu_808x::*, io_trace::IoDirection};
 
 #[cfg(feature = "cpu_validator")]
 use crate::cpu_validator::{BusType, ReadType};
@@ -254,6 +254,14 @@ impl Cpu {
             self.cycle_states.push(cycle_state);
         }
 
+        // Report the cycle to the cycle observer, if set
+        if self.cycle_observer.is_some() {
+            let cycle_state = self.get_cycle_state();
+            if let Some(observer) = &mut self.cycle_observer {
+                observer(&cycle_state);
+            }
+        }
+
         // Do DRAM refresh (DMA channel 0) simulation
         if self.enable_wait_states && self.dram_refresh_simulation {
             self.dram_refresh_cycle_num = self.dram_refresh_cycle_num.saturating_sub(1);
@@ -554,6 +562,7 @@ impl Cpu {
                     .unwrap();
                 self.instr_elapsed = 0;
                 self.data_bus = byte as u16;
+                self.bus.watch_read(self.address_latch as usize, 1);
 
                 validate_read_u8!(
                     self,
@@ -569,9 +578,11 @@ impl Cpu {
                     .read_u16(self.address_latch as usize, self.instr_elapsed)
                     .unwrap();
                 self.instr_elapsed = 0;
+                self.bus.watch_read(self.address_latch as usize, 2);
             }
             (BusStatus::MemWrite, TransferSize::Byte) => {
                 self.i8288.mwtc = true;
+                let watch_old = self.bus.watch_peek(self.address_latch as usize, 1);
                 _ = self
                     .bus
                     .write_u8(
@@ -581,16 +592,23 @@ impl Cpu {
                     )
                     .unwrap();
                 self.instr_elapsed = 0;
+                if let Some(old_value) = watch_old {
+                    self.bus.watch_write(self.address_latch as usize, 1, old_value);
+                }
 
                 validate_write_u8!(self, self.address_latch, (self.data_bus & 0x00FF) as u8, BusType::Mem);
             }
             (BusStatus::MemWrite, TransferSize::Word) => {
                 self.i8288.mwtc = true;
+                let watch_old = self.bus.watch_peek(self.address_latch as usize, 2);
                 _ = self
                     .bus
                     .write_u16(self.address_latch as usize, self.data_bus, self.instr_elapsed)
                     .unwrap();
                 self.instr_elapsed = 0;
+                if let Some(old_value) = watch_old {
+                    self.bus.watch_write(self.address_latch as usize, 2, old_value);
+                }
             }
             (BusStatus::IoRead, TransferSize::Byte) => {
                 self.i8288.iorc = true;
@@ -600,6 +618,15 @@ impl Cpu {
                 self.data_bus = byte as u16;
                 self.instr_elapsed = 0;
 
+                if self.bus.io_trace_enabled() {
+                    self.bus.trace_io(
+                        (self.address_latch & 0xFFFF) as u16,
+                        byte,
+                        IoDirection::In,
+                        CpuAddress::Segmented(self.cs, self.instruction_ip),
+                    );
+                }
+
                 validate_read_u8!(
                     self,
                     self.address_latch,
@@ -617,6 +644,15 @@ impl Cpu {
                 );
                 self.instr_elapsed = 0;
 
+                if self.bus.io_trace_enabled() {
+                    self.bus.trace_io(
+                        (self.address_latch & 0xFFFF) as u16,
+                        (self.data_bus & 0x00FF) as u8,
+                        IoDirection::Out,
+                        CpuAddress::Segmented(self.cs, self.instruction_ip),
+                    );
+                }
+
                 validate_write_u8!(self, self.address_latch, (self.data_bus & 0x00FF) as u8, BusType::Io);
             }
             (BusStatus::InterruptAck, TransferSize::Byte) => {
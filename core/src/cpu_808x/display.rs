@@ -303,13 +303,21 @@ impl fmt::Display for Instruction {
 }
 
 impl Cpu {
-    pub fn tokenize_instruction(i: &Instruction) -> Vec<SyntaxToken> {
+    /// Tokenize the first and second operands of an instruction. Either vector may be empty.
+    pub fn tokenize_operands(i: &Instruction) -> (Vec<SyntaxToken>, Vec<SyntaxToken>) {
         // Dont sign-extend 8-bit port addresses.
         let op_size = match i.mnemonic {
             Mnemonic::IN | Mnemonic::OUT => OperandSize::Operand8,
             _ => i.operand1_size,
         };
 
+        (
+            tokenize_operand(i, OperandSelect::FirstOperand, op_size),
+            tokenize_operand(i, OperandSelect::SecondOperand, op_size),
+        )
+    }
+
+    pub fn tokenize_instruction(i: &Instruction) -> Vec<SyntaxToken> {
         let mut i_vec = SyntaxTokenVec(Vec::new());
 
         // Stick segment override prefix on certain opcodes (string ops)
@@ -328,11 +336,9 @@ impl Cpu {
         let mnemonic = mnemonic_to_str(i.mnemonic).to_string().to_lowercase();
         i_vec.0.push(SyntaxToken::Mnemonic(mnemonic));
 
-        let op1_vec = tokenize_operand(i, OperandSelect::FirstOperand, op_size);
+        let (op1_vec, op2_vec) = Cpu::tokenize_operands(i);
         i_vec.append(op1_vec, Some(SyntaxToken::Formatter(SyntaxFormatType::Space)), None);
 
-        let op2_vec = tokenize_operand(i, OperandSelect::SecondOperand, op_size);
-
         if !op2_vec.is_empty() {
             i_vec.0.push(SyntaxToken::Comma);
             i_vec.append(op2_vec, Some(SyntaxToken::Formatter(SyntaxFormatType::Space)), None);
@@ -78,6 +78,10 @@ impl Cpu {
             return;
         }
 
+        if interrupt == 0x19 {
+            self.service_events.push_back(ServiceEvent::Bootstrap);
+        }
+
         self.cycles_i(3, &[0x19d, 0x19e, 0x19f]);
 
         // Read the IVT
@@ -81,14 +81,11 @@ use crate::{
 
 use crate::{syntax_token::*, tracelogger::TraceLogger};
 
+use crate::cpu_validator::{AccessType, BusCycle, BusState, CycleState, VRegisters};
+
 #[cfg(feature = "cpu_validator")]
 use crate::cpu_validator::{
-    AccessType,
-    BusCycle,
-    BusState,
     CpuValidator,
-    CycleState,
-    VRegisters,
     ValidatorMode,
     ValidatorResult,
     VAL_ALLOW_ONE,
@@ -332,10 +329,13 @@ impl Display for CpuError {
 
 // Internal Emulator interrupt service events. These are returned to the machine when
 // the internal service interrupt is called to request an emulator action that cannot
-// be handled by the CPU alone.
+// be handled by the CPU alone, or when the CPU executes a software interrupt the machine
+// is interested in.
 #[derive(Copy, Clone, Debug)]
 pub enum ServiceEvent {
     TriggerPITLogging,
+    /// The CPU executed INT 19h, the bootstrap loader. The BIOS calls this at the end of POST.
+    Bootstrap,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -767,6 +767,7 @@ pub struct Cpu {
     reported_halt: bool, // Only error on halt once. The caller can determine if it wants to continue.
     halt_not_hold: bool, // Internal halt signal
     wake_timer: u32,
+    halt_idle: bool, // Step through halt in larger batches while no timer interrupt is near
 
     is_running: bool,
     is_error:   bool,
@@ -816,6 +817,8 @@ pub struct Cpu {
 
     rng: Option<rand::rngs::StdRng>,
 
+    cycle_observer: Option<Box<dyn FnMut(&CycleState)>>,
+
     #[cfg(feature = "cpu_validator")]
     validator: Option<Box<dyn CpuValidator>>,
     #[cfg(feature = "cpu_validator")]
@@ -1340,7 +1343,17 @@ impl Cpu {
         }
     }
 
-    #[cfg(feature = "cpu_validator")]
+    /// Set a function to be called with the bus and queue state of every CPU cycle. This allows
+    /// sub-instruction execution to be compared against a hardware capture or reference trace.
+    /// There is no cost beyond a check for an observer when one is not set.
+    pub fn set_cycle_observer(&mut self, observer: Box<dyn FnMut(&CycleState)>) {
+        self.cycle_observer = Some(observer);
+    }
+
+    pub fn clear_cycle_observer(&mut self) {
+        self.cycle_observer = None;
+    }
+
     pub fn get_cycle_state(&mut self) -> CycleState {
         let mut q = [0; 4];
         self.queue.to_slice(&mut q);
@@ -1491,6 +1504,33 @@ impl Cpu {
 
     #[cfg(feature = "cpu_validator")]
     pub fn get_vregisters(&self) -> VRegisters {
+        self.dump_state()
+    }
+
+    /// Load the CPU's registers and flags and reset execution to the new CS:IP with an empty
+    /// instruction queue. This is used to set up single instruction tests.
+    pub fn load_state(&mut self, regs: &VRegisters) {
+        self.set_reset_vector(CpuAddress::Segmented(regs.cs, regs.ip));
+        self.reset();
+
+        self.set_register16(Register16::AX, regs.ax);
+        self.set_register16(Register16::BX, regs.bx);
+        self.set_register16(Register16::CX, regs.cx);
+        self.set_register16(Register16::DX, regs.dx);
+        self.set_register16(Register16::SP, regs.sp);
+        self.set_register16(Register16::BP, regs.bp);
+        self.set_register16(Register16::SI, regs.si);
+        self.set_register16(Register16::DI, regs.di);
+        self.set_register16(Register16::ES, regs.es);
+        self.set_register16(Register16::CS, regs.cs);
+        self.set_register16(Register16::SS, regs.ss);
+        self.set_register16(Register16::DS, regs.ds);
+        self.set_register16(Register16::PC, regs.ip);
+        self.set_flags(regs.flags);
+    }
+
+    /// Return the CPU's registers and flags.
+    pub fn dump_state(&self) -> VRegisters {
         VRegisters {
             ax:    self.a.x(),
             bx:    self.b.x(),
@@ -1924,6 +1964,12 @@ impl Cpu {
         }
     }
 
+    /// Return true if the CPU is halted, waiting for an interrupt.
+    #[inline]
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
     /// Resume from halted state
     pub fn resume(&mut self) {
         if self.halted {
@@ -2002,6 +2048,10 @@ impl Cpu {
         });
     }
 
+    pub fn breakpoints(&self) -> &[BreakPointType] {
+        &self.breakpoints
+    }
+
     pub fn get_breakpoint_flag(&self) -> bool {
         if let CpuState::BreakpointHit = self.state {
             true
@@ -2218,6 +2268,10 @@ impl Cpu {
                 log::debug!("Setting EnableServiceInterrupt to: {:?}", state);
                 self.enable_service_interrupt = state;
             }
+            CpuOption::HaltIdle(state) => {
+                log::debug!("Setting HaltIdle to: {:?}", state);
+                self.halt_idle = state;
+            }
         }
     }
 
@@ -2232,6 +2286,7 @@ impl Cpu {
             CpuOption::EnableWaitStates(_) => self.enable_wait_states,
             CpuOption::TraceLoggingEnabled(_) => self.trace_enabled,
             CpuOption::EnableServiceInterrupt(_) => self.enable_service_interrupt,
+            CpuOption::HaltIdle(_) => self.halt_idle,
         }
     }
 
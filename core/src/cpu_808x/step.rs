@@ -121,11 +121,13 @@ impl Cpu {
 
         // The Halt state can be expensive if we only execute one cycle per halt - however precise wake from halt is
         // necessary for Area5150. We can dynamically adjust the cycle count of stepping in the halt state depending
-        // on a hint from the bus whether a timer interrupt is imminent.
+        // on a hint from the bus whether a timer interrupt is imminent. With halt idling enabled, we step in batches
+        // as large as the distance to the next timer interrupt allows, which saves host CPU in DOS idle loops.
         if self.halted {
-            let halt_cycles = match self.bus().is_intr_imminent() {
-                true => 1,
-                false => 5,
+            let halt_cycles = match (self.bus().is_intr_imminent(), self.halt_idle) {
+                (true, _) => 1,
+                (false, true) => self.bus().halt_idle_cycles().max(5),
+                (false, false) => 5,
             };
             self.cycles(halt_cycles);
             return Ok((StepResult::Normal, halt_cycles));
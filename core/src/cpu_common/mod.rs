@@ -95,6 +95,7 @@ pub enum CpuOption {
     EnableWaitStates(bool),
     TraceLoggingEnabled(bool),
     EnableServiceInterrupt(bool),
+    HaltIdle(bool),
 }
 
 use crate::cpu_808x::*;
@@ -0,0 +1,152 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    debug_session.rs
+
+    Defines the file format used to save and restore debugger state such as
+    breakpoints, watchpoints and symbols between sessions.
+
+*/
+
+use crate::{breakpoints::BreakPointType, bus::WatchAccess, cpu_808x::Cpu};
+use serde_derive::{Deserialize, Serialize};
+
+/// The size of the physical address space. Entries outside of it are rejected when a session is
+/// loaded.
+const SESSION_ADDRESS_SPACE: usize = 0x100000;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionWatchpoint {
+    pub start:  usize,
+    pub end:    usize,
+    pub access: WatchAccess,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionSymbol {
+    pub address: u32,
+    pub name:    String,
+}
+
+/// A saved debugger session. Breakpoints are stored by linear address.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DebugSession {
+    #[serde(default)]
+    pub exec_breakpoints: Vec<u32>,
+    #[serde(default)]
+    pub mem_breakpoints: Vec<u32>,
+    #[serde(default)]
+    pub int_breakpoints: Vec<u8>,
+    // Arrays of tables must follow plain values in TOML, and an empty array would be emitted as
+    // a plain value, so these are omitted when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watchpoints: Vec<SessionWatchpoint>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub symbols: Vec<SessionSymbol>,
+}
+
+impl DebugSession {
+    /// Add a list of breakpoints to the session. Segmented breakpoints are converted to linear
+    /// addresses. Offset-only breakpoints can't be resolved to an address and are skipped.
+    pub fn add_breakpoints(&mut self, breakpoints: &[BreakPointType]) {
+        for bp in breakpoints {
+            match *bp {
+                BreakPointType::Execute(segment, offset) => {
+                    self.exec_breakpoints.push(Cpu::calc_linear_address(segment, offset))
+                }
+                BreakPointType::ExecuteFlat(addr) => self.exec_breakpoints.push(addr),
+                BreakPointType::MemAccess(segment, offset) => {
+                    self.mem_breakpoints.push(Cpu::calc_linear_address(segment, offset))
+                }
+                BreakPointType::MemAccessFlat(addr) => self.mem_breakpoints.push(addr),
+                BreakPointType::Interrupt(vector) => self.int_breakpoints.push(vector),
+                BreakPointType::ExecuteOffset(offset) => {
+                    log::warn!("Not saving offset-only breakpoint *:{:04X}", offset);
+                }
+            }
+        }
+    }
+
+    /// Return the session's breakpoints. Any entries with an address outside the address space
+    /// are skipped and described in the returned list of warnings.
+    pub fn breakpoints(&self, warnings: &mut Vec<String>) -> Vec<BreakPointType> {
+        let mut breakpoints = Vec::new();
+
+        for &addr in &self.exec_breakpoints {
+            if (addr as usize) < SESSION_ADDRESS_SPACE {
+                breakpoints.push(BreakPointType::ExecuteFlat(addr));
+            }
+            else {
+                warnings.push(format!("Execute breakpoint at {:X} is outside the address space", addr));
+            }
+        }
+        for &addr in &self.mem_breakpoints {
+            if (addr as usize) < SESSION_ADDRESS_SPACE {
+                breakpoints.push(BreakPointType::MemAccessFlat(addr));
+            }
+            else {
+                warnings.push(format!("Memory breakpoint at {:X} is outside the address space", addr));
+            }
+        }
+        breakpoints.extend(self.int_breakpoints.iter().map(|&vector| BreakPointType::Interrupt(vector)));
+
+        breakpoints
+    }
+
+    /// Return the session's watchpoints as (range, access) pairs. Empty ranges and ranges that
+    /// extend past the address space are skipped and described in the returned list of warnings.
+    pub fn watchpoints(&self, warnings: &mut Vec<String>) -> Vec<(std::ops::Range<usize>, WatchAccess)> {
+        let mut watchpoints = Vec::new();
+
+        for wp in &self.watchpoints {
+            if wp.start < wp.end && wp.end <= SESSION_ADDRESS_SPACE {
+                watchpoints.push((wp.start..wp.end, wp.access));
+            }
+            else {
+                warnings.push(format!("Watchpoint over [{:X}-{:X}) is not a valid range", wp.start, wp.end));
+            }
+        }
+
+        watchpoints
+    }
+
+    /// Return the session's symbols as (linear address, name) pairs. Symbols outside the address
+    /// space are skipped and described in the returned list of warnings.
+    pub fn symbols(&self, warnings: &mut Vec<String>) -> Vec<(u32, String)> {
+        let mut symbols = Vec::new();
+
+        for symbol in &self.symbols {
+            if (symbol.address as usize) < SESSION_ADDRESS_SPACE {
+                symbols.push((symbol.address, symbol.name.clone()));
+            }
+            else {
+                warnings.push(format!("Symbol {} at {:X} is outside the address space", symbol.name, symbol.address));
+            }
+        }
+
+        symbols
+    }
+}
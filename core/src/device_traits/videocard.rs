@@ -171,12 +171,14 @@ pub struct VideoCardInterface<'a> {
 pub enum VideoOption {
     DebugDraw(bool),
     EnableSnow(bool),
+    EnableBlink(bool),
 }
 
 // This enum determines the rendering method of the given videocard device.
 // Direct mode means the video card draws to a double buffering scheme itself,
 // Indirect mode means that the video renderer draws the device's VRAM. I think
 // eventually I will want to move all devices to direct rendering.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum RenderMode {
     Direct,
     Indirect,
@@ -204,7 +206,7 @@ pub type VideoCardState = HashMap<String, Vec<(String, VideoCardStateEntry)>>;
 
 /// All valid graphics modes for CGA, EGA and VGA Cards
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum DisplayMode {
     Disabled,
     Mode0TextBw40,
@@ -219,6 +221,8 @@ pub enum DisplayMode {
     Mode8LowResTweaked,
     Mode9PCJrLowResGraphics,
     ModeAPCjrHiResGraphics,
+    Mode8TandyLowResGraphics,
+    Mode9TandyMedResGraphics,
     ModeBEGAInternal,
     ModeCEGAInternal,
     ModeDEGALowResGraphics,
@@ -323,6 +327,26 @@ pub struct DisplayExtents {
     pub row_stride: usize,               // Number of bytes in frame buffer to skip to reach next row
     pub double_scan: bool,               // Whether the display should be double-scanned when RGBA converted
     pub mode_byte: u8,                   // Mode byte. Used by CGA modes only.
+    pub border_color: u8,                // Color index of the overscan border. Used by CGA modes only.
+}
+
+/// A snapshot of everything a frontend needs to decide how to size and render the current frame,
+/// read from the card in one call so that the fields are consistent with each other.
+pub struct VideoFrameInfo<'a> {
+    pub video_type: VideoType,
+    pub render_mode: RenderMode,
+    pub display_size: (u32, u32),
+    pub scanline_double: bool,
+    pub extents: &'a DisplayExtents,
+    pub display_buf: &'a [u8],
+}
+
+impl VideoFrameInfo<'_> {
+    /// Return the (width, height) of the specified display aperture.
+    pub fn aperture_size(&self, aperture: DisplayApertureType) -> (u32, u32) {
+        let aperture = &self.extents.apertures[aperture as usize];
+        (aperture.w, aperture.h)
+    }
 }
 
 pub trait VideoCard {
@@ -353,6 +377,18 @@ pub trait VideoCard {
     /// Return the DisplayExtents struct corresponding to the last rendered frame.
     fn get_display_extents(&self) -> &DisplayExtents;
 
+    /// Return a VideoFrameInfo snapshot describing the last rendered frame.
+    fn frame_info(&self) -> VideoFrameInfo {
+        VideoFrameInfo {
+            video_type: self.get_video_type(),
+            render_mode: self.get_render_mode(),
+            display_size: self.get_display_size(),
+            scanline_double: self.get_scanline_double(),
+            extents: self.get_display_extents(),
+            display_buf: self.get_display_buf(),
+        }
+    }
+
     /// Return a list of available display aperture names, indices, and the default aperture index
     fn list_display_apertures(&self) -> Vec<DisplayApertureDesc>;
 
@@ -376,6 +412,11 @@ pub trait VideoCard {
     /// Get the position of the CRT beam (Direct rendering only)
     fn get_beam_pos(&self) -> Option<(u32, u32)>;
 
+    /// Update the state of the light pen. 'pos' is the position the pen is pointed at, in the same
+    /// display field coordinates as [VideoCard::get_beam_pos], or None if the pen is away from the
+    /// screen. 'switch' is the state of the pen's tip switch.
+    fn set_lightpen(&mut self, pos: Option<(u32, u32)>, switch: bool);
+
     /// Get the current scanline being rendered.
     fn get_scanline(&self) -> u32;
 
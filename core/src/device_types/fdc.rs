@@ -34,7 +34,8 @@ use lazy_static::lazy_static;
 use std::collections::HashMap;
 
 pub struct DiskFormat {
-    pub chs: DiskChs,
+    pub name: &'static str,
+    pub chs:  DiskChs,
 }
 
 lazy_static! {
@@ -43,43 +44,50 @@ lazy_static! {
             (
                 163_840,
                 DiskFormat {
-                    chs: DiskChs::new(40, 1, 8),
+                    name: "160K",
+                    chs:  DiskChs::new(40, 1, 8),
                 },
             ),
             (
                 184_320,
                 DiskFormat {
-                    chs: DiskChs::new(40, 1, 9),
+                    name: "180K",
+                    chs:  DiskChs::new(40, 1, 9),
                 },
             ),
             (
                 327_680,
                 DiskFormat {
-                    chs: DiskChs::new(40, 2, 8),
+                    name: "320K",
+                    chs:  DiskChs::new(40, 2, 8),
                 },
             ),
             (
                 368_640,
                 DiskFormat {
-                    chs: DiskChs::new(40, 2, 9),
+                    name: "360K",
+                    chs:  DiskChs::new(40, 2, 9),
                 },
             ),
             (
                 737_280,
                 DiskFormat {
-                    chs: DiskChs::new(80, 2, 9),
+                    name: "720K",
+                    chs:  DiskChs::new(80, 2, 9),
                 },
             ),
             (
                 1_228_800,
                 DiskFormat {
-                    chs: DiskChs::new(80, 2, 15),
+                    name: "1.2M",
+                    chs:  DiskChs::new(80, 2, 15),
                 },
             ),
             (
                 1_474_560,
                 DiskFormat {
-                    chs: DiskChs::new(80, 2, 18),
+                    name: "1.44M",
+                    chs:  DiskChs::new(80, 2, 18),
                 },
             ),
         ]);
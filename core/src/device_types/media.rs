@@ -0,0 +1,91 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    devices::types::media.rs
+
+    Defines types describing the media mounted in a machine's drives
+*/
+
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MediaKind {
+    Floppy,
+    HardDisk,
+}
+
+/// A description of the media mounted in a drive.
+#[derive(Clone, Debug)]
+pub struct MediaInfo {
+    /// The name of the image, if the frontend provided one.
+    pub name: Option<String>,
+    pub cylinders: u16,
+    pub heads: u8,
+    pub sectors: u8,
+    /// The size of the image in bytes.
+    pub size: u64,
+    pub write_protected: bool,
+    /// Whether the guest has written to the image since it was loaded or last saved. Hard disk
+    /// images are written through to the host file, so they are never dirty.
+    pub dirty: bool,
+}
+
+/// A drive and its mounted media, if any.
+#[derive(Clone, Debug)]
+pub struct MediaSlot {
+    pub kind:  MediaKind,
+    pub drive: usize,
+    pub media: Option<MediaInfo>,
+}
+
+impl fmt::Display for MediaSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            MediaKind::Floppy => write!(f, "Floppy {}: ", self.drive)?,
+            MediaKind::HardDisk => write!(f, "Hard disk {}: ", self.drive)?,
+        }
+        let media = match &self.media {
+            Some(media) => media,
+            None => return write!(f, "empty"),
+        };
+        write!(
+            f,
+            "{} c:{} h:{} s:{} {} bytes",
+            media.name.as_deref().unwrap_or("<unnamed>"),
+            media.cylinders,
+            media.heads,
+            media.sectors,
+            media.size
+        )?;
+        if media.write_protected {
+            write!(f, " [write protected]")?;
+        }
+        if media.dirty {
+            write!(f, " [modified]")?;
+        }
+        Ok(())
+    }
+}
@@ -33,3 +33,4 @@
 pub mod chs;
 pub mod fdc;
 pub mod hdc;
+pub mod media;
@@ -0,0 +1,763 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    devices::adlib.rs
+
+    Implements the AdLib Music Synthesizer Card, based on the Yamaha YM3812
+    (OPL2) FM synthesis chip.
+
+    The OPL2 provides nine two-operator FM channels. Each operator has its
+    own ADSR envelope generator, frequency multiplier, key scaling and
+    waveform select. The first operator of each channel can feed back into
+    itself, and the two operators may be connected either in series (FM) or
+    in parallel (additive synthesis).
+
+    Two timers are provided at 80us and 320us resolution. Most software
+    detects the card by starting timer 1 and polling the status register
+    for the overflow flag, so these timers are run off the emulated system
+    clock to keep detection deterministic.
+
+    Output is synthesized directly at the host sample rate. Rhythm mode is
+    not yet implemented; channels 6-8 are always played as melodic voices.
+
+    Primary Documentation:
+    Yamaha YM3812 Application Manual
+    "AdLib Music Synthesizer Card Programming Guide"
+*/
+
+use std::collections::VecDeque;
+
+use crate::bus::{BusInterface, DeviceRunTimeUnit, IoDevice, NO_IO_BYTE};
+
+pub const ADLIB_DEFAULT_IO_BASE: u16 = 0x388;
+
+/// The OPL2 is clocked at 3.579545Mhz, and produces one sample every 72 clocks.
+pub const OPL2_CLOCK: f64 = 3.579545;
+pub const OPL2_NATIVE_RATE: f64 = (OPL2_CLOCK * 1_000_000.0) / 72.0;
+
+const DEFAULT_SAMPLE_RATE: u32 = 44100;
+const MIX_SCALE: f64 = 0.25;
+
+const TIMER1_PERIOD_US: f64 = 80.0;
+const TIMER2_PERIOD_US: f64 = 320.0;
+
+// Status register bits
+const STATUS_IRQ: u8 = 0b1000_0000;
+const STATUS_T1_OVERFLOW: u8 = 0b0100_0000;
+const STATUS_T2_OVERFLOW: u8 = 0b0010_0000;
+// The low bits of the status register read as 110b on an OPL2, and 000b on an OPL3.
+const STATUS_OPL2_ID: u8 = 0b0000_0110;
+
+// Timer control register (0x04) bits
+const CONTROL_IRQ_RESET: u8 = 0b1000_0000;
+const CONTROL_T1_MASK: u8 = 0b0100_0000;
+const CONTROL_T2_MASK: u8 = 0b0010_0000;
+const CONTROL_T2_START: u8 = 0b0000_0010;
+const CONTROL_T1_START: u8 = 0b0000_0001;
+
+const REG_TEST: u8 = 0x01;
+const REG_TIMER1: u8 = 0x02;
+const REG_TIMER2: u8 = 0x03;
+const REG_TIMER_CONTROL: u8 = 0x04;
+const REG_CSM_KEYSPLIT: u8 = 0x08;
+const REG_DEPTH_RHYTHM: u8 = 0xBD;
+
+const TEST_WAVEFORM_SELECT_ENABLE: u8 = 0b0010_0000;
+const KEYSPLIT_NOTE_SELECT: u8 = 0b0100_0000;
+const DEPTH_TREMOLO: u8 = 0b1000_0000;
+const DEPTH_VIBRATO: u8 = 0b0100_0000;
+
+const OPERATOR_CT: usize = 18;
+const CHANNEL_CT: usize = 9;
+
+// Envelope constants. Attenuation is tracked in decibels.
+const MAX_ATTENUATION_DB: f64 = 96.0;
+const ATTACK_FLOOR_DB: f64 = 0.1;
+// Time taken for a full attack or 96dB decay at the slowest non-zero rate (R=1, no key scaling).
+const ATTACK_BASE_MS: f64 = 2826.24;
+const DECAY_BASE_MS: f64 = 39280.64;
+
+// Phase modulation, in cycles, produced by a full-scale modulator output.
+const MODULATION_DEPTH: f64 = 4.0;
+
+const TREMOLO_HZ: f64 = 3.7;
+const TREMOLO_DEPTH_DB: [f64; 2] = [1.0, 4.8];
+const VIBRATO_HZ: f64 = 6.07;
+const VIBRATO_DEPTH_CENTS: [f64; 2] = [7.0, 14.0];
+
+/// Frequency multiplier table, doubled so that entry 0 (x0.5) is an integer.
+const MULTIPLIER_X2: [u8; 16] = [1, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 20, 24, 24, 30, 30];
+
+/// Key scale level attenuation in 0.75dB units, indexed by the top 4 bits of the F-Number.
+const KSL_ROM: [u8; 16] = [0, 32, 40, 45, 48, 51, 53, 55, 56, 58, 59, 60, 61, 62, 63, 64];
+/// Shift applied to the KSL attenuation for each KSL register value: off, 3dB, 1.5dB, 6dB/octave.
+const KSL_SHIFT: [u8; 4] = [8, 1, 2, 0];
+
+/// Operator register offsets are not contiguous. This maps register offsets 0x00-0x15 to
+/// operator slots.
+const REG_TO_OPERATOR: [Option<usize>; 0x16] = [
+    Some(0),
+    Some(1),
+    Some(2),
+    Some(3),
+    Some(4),
+    Some(5),
+    None,
+    None,
+    Some(6),
+    Some(7),
+    Some(8),
+    Some(9),
+    Some(10),
+    Some(11),
+    None,
+    None,
+    Some(12),
+    Some(13),
+    Some(14),
+    Some(15),
+    Some(16),
+    Some(17),
+];
+
+/// Operator slots (modulator, carrier) for each channel.
+const CHANNEL_OPERATORS: [(usize, usize); CHANNEL_CT] = [
+    (0, 3),
+    (1, 4),
+    (2, 5),
+    (6, 9),
+    (7, 10),
+    (8, 11),
+    (12, 15),
+    (13, 16),
+    (14, 17),
+];
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum EnvelopeState {
+    #[default]
+    Off,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+#[derive(Clone)]
+struct Operator {
+    // Register 0x20
+    tremolo: bool,
+    vibrato: bool,
+    sustain: bool,
+    ksr: bool,
+    mult: u8,
+    // Register 0x40
+    ksl: u8,
+    total_level: u8,
+    // Register 0x60
+    attack_rate: u8,
+    decay_rate: u8,
+    // Register 0x80
+    sustain_level: u8,
+    release_rate: u8,
+    // Register 0xE0
+    waveform: u8,
+
+    phase: f64,
+    env_state: EnvelopeState,
+    env_db: f64,
+    // The last two outputs of this operator. Used for feedback.
+    out: [f64; 2],
+}
+
+impl Default for Operator {
+    fn default() -> Self {
+        Self {
+            tremolo: false,
+            vibrato: false,
+            sustain: false,
+            ksr: false,
+            mult: 0,
+            ksl: 0,
+            total_level: 0,
+            attack_rate: 0,
+            decay_rate: 0,
+            sustain_level: 0,
+            release_rate: 0,
+            waveform: 0,
+            phase: 0.0,
+            env_state: EnvelopeState::Off,
+            env_db: MAX_ATTENUATION_DB,
+            out: [0.0; 2],
+        }
+    }
+}
+
+impl Operator {
+    fn key_on(&mut self) {
+        self.env_state = EnvelopeState::Attack;
+        self.phase = 0.0;
+    }
+
+    fn key_off(&mut self) {
+        if self.env_state != EnvelopeState::Off {
+            self.env_state = EnvelopeState::Release;
+        }
+    }
+
+    /// Calculate the effective envelope rate (0-63) given a 4-bit rate register value and the
+    /// channel's key scale rate offset.
+    fn effective_rate(&self, rate: u8, rof: u8) -> u8 {
+        if rate == 0 {
+            return 0;
+        }
+        let ksr_offset = if self.ksr { rof } else { rof >> 2 };
+        std::cmp::min(63, rate * 4 + ksr_offset)
+    }
+
+    /// Return the attenuation change in dB per sample for a decay or release at the specified rate.
+    fn decay_step(&self, rate: u8, rof: u8, sample_rate: f64) -> f64 {
+        let rate = self.effective_rate(rate, rof);
+        if rate == 0 {
+            return 0.0;
+        }
+        let decay_ms = DECAY_BASE_MS / 2.0f64.powf((rate as f64 - 4.0) / 4.0);
+        MAX_ATTENUATION_DB / (decay_ms * sample_rate / 1000.0)
+    }
+
+    fn tick_envelope(&mut self, rof: u8, sample_rate: f64) {
+        match self.env_state {
+            EnvelopeState::Off => {}
+            EnvelopeState::Attack => {
+                let rate = self.effective_rate(self.attack_rate, rof);
+                if rate >= 60 {
+                    self.env_db = 0.0;
+                }
+                else if rate > 0 {
+                    // The attack curve is exponential in the dB domain.
+                    let attack_ms = ATTACK_BASE_MS / 2.0f64.powf((rate as f64 - 4.0) / 4.0);
+                    let attack_samples = (attack_ms * sample_rate / 1000.0).max(1.0);
+                    let k = 1.0 - (ATTACK_FLOOR_DB / MAX_ATTENUATION_DB).powf(1.0 / attack_samples);
+                    self.env_db -= self.env_db * k;
+                }
+                if self.env_db <= ATTACK_FLOOR_DB {
+                    self.env_db = 0.0;
+                    self.env_state = EnvelopeState::Decay;
+                }
+            }
+            EnvelopeState::Decay => {
+                let sl_db = if self.sustain_level == 15 {
+                    93.0
+                }
+                else {
+                    self.sustain_level as f64 * 3.0
+                };
+                self.env_db += self.decay_step(self.decay_rate, rof, sample_rate);
+                if self.env_db >= sl_db {
+                    self.env_db = sl_db;
+                    self.env_state = EnvelopeState::Sustain;
+                }
+            }
+            EnvelopeState::Sustain => {
+                // Percussive (non-sustaining) envelopes continue to decay at the release rate
+                // even while the key is held.
+                if !self.sustain {
+                    self.env_db += self.decay_step(self.release_rate, rof, sample_rate);
+                }
+            }
+            EnvelopeState::Release => {
+                self.env_db += self.decay_step(self.release_rate, rof, sample_rate);
+            }
+        }
+
+        if self.env_db >= MAX_ATTENUATION_DB {
+            self.env_db = MAX_ATTENUATION_DB;
+            if self.env_state == EnvelopeState::Release {
+                self.env_state = EnvelopeState::Off;
+            }
+        }
+    }
+
+    fn waveform_sample(&self, phase: f64) -> f64 {
+        let s = (phase * std::f64::consts::TAU).sin();
+        match self.waveform & 0x03 {
+            0 => s,
+            1 => {
+                if phase < 0.5 {
+                    s
+                }
+                else {
+                    0.0
+                }
+            }
+            2 => s.abs(),
+            _ => {
+                if (phase % 0.5) < 0.25 {
+                    s.abs()
+                }
+                else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// Produce the next output sample for this operator. 'modulation' is a phase offset in cycles.
+    fn output(&mut self, modulation: f64, ksl_db: f64, tremolo_db: f64) -> f64 {
+        let mut attenuation = self.env_db + self.total_level as f64 * 0.75 + ksl_db;
+        if self.tremolo {
+            attenuation += tremolo_db;
+        }
+
+        let out = if self.env_state == EnvelopeState::Off || attenuation >= MAX_ATTENUATION_DB {
+            0.0
+        }
+        else {
+            let phase = (self.phase + modulation).rem_euclid(1.0);
+            self.waveform_sample(phase) * 10.0f64.powf(-attenuation / 20.0)
+        };
+
+        self.out[1] = self.out[0];
+        self.out[0] = out;
+        out
+    }
+
+    fn advance_phase(&mut self, freq: f64, vibrato_factor: f64, sample_rate: f64) {
+        let mut step = freq * (MULTIPLIER_X2[self.mult as usize] as f64 / 2.0) / sample_rate;
+        if self.vibrato {
+            step *= vibrato_factor;
+        }
+        self.phase = (self.phase + step).fract();
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+struct Channel {
+    fnum: u16,
+    block: u8,
+    key_on: bool,
+    feedback: u8,
+    additive: bool,
+}
+
+impl Channel {
+    /// Return the channel's base frequency in Hz.
+    fn frequency(&self) -> f64 {
+        self.fnum as f64 * OPL2_NATIVE_RATE / (1u32 << (20 - self.block as u32)) as f64
+    }
+
+    /// Return the key scale rate offset, derived from block and one bit of F-Number as selected
+    /// by the NTS bit.
+    fn rate_offset(&self, nts: bool) -> u8 {
+        let note_sel = if nts { (self.fnum >> 8) & 0x01 } else { (self.fnum >> 9) & 0x01 };
+        (self.block << 1) | note_sel as u8
+    }
+
+    /// Return the key scale level attenuation in dB for the specified KSL register value.
+    fn ksl_db(&self, ksl: u8) -> f64 {
+        let base = (KSL_ROM[(self.fnum >> 6) as usize & 0x0F] as i32) * 4 - ((8 - self.block as i32) << 5);
+        if base <= 0 {
+            return 0.0;
+        }
+        // 0.1875dB units
+        ((base as u32) >> KSL_SHIFT[ksl as usize & 0x03]) as f64 * 0.1875
+    }
+}
+
+pub struct AdLibCard {
+    io_base: u16,
+    address: u8,
+    registers: [u8; 256],
+    operators: [Operator; OPERATOR_CT],
+    channels: [Channel; CHANNEL_CT],
+
+    status: u8,
+    timer1_value: u8,
+    timer2_value: u8,
+    timer1_counter: u8,
+    timer2_counter: u8,
+    timer1_running: bool,
+    timer2_running: bool,
+    timer1_masked: bool,
+    timer2_masked: bool,
+    timer1_accum: f64,
+    timer2_accum: f64,
+
+    lfo_phase_tremolo: f64,
+    lfo_phase_vibrato: f64,
+
+    sample_rate: u32,
+    sample_accum: f64,
+    samples: VecDeque<f32>,
+    max_samples: usize,
+}
+
+impl Default for AdLibCard {
+    fn default() -> Self {
+        Self {
+            io_base: ADLIB_DEFAULT_IO_BASE,
+            address: 0,
+            registers: [0; 256],
+            operators: Default::default(),
+            channels: [Channel::default(); CHANNEL_CT],
+            status: 0,
+            timer1_value: 0,
+            timer2_value: 0,
+            timer1_counter: 0,
+            timer2_counter: 0,
+            timer1_running: false,
+            timer2_running: false,
+            timer1_masked: false,
+            timer2_masked: false,
+            timer1_accum: 0.0,
+            timer2_accum: 0.0,
+            lfo_phase_tremolo: 0.0,
+            lfo_phase_vibrato: 0.0,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            sample_accum: 0.0,
+            samples: VecDeque::new(),
+            max_samples: DEFAULT_SAMPLE_RATE as usize / 2,
+        }
+    }
+}
+
+impl IoDevice for AdLibCard {
+    fn read_u8(&mut self, port: u16, _delta: DeviceRunTimeUnit) -> u8 {
+        if port == self.io_base {
+            self.status_read()
+        }
+        else {
+            NO_IO_BYTE
+        }
+    }
+
+    fn write_u8(&mut self, port: u16, data: u8, _bus: Option<&mut BusInterface>, _delta: DeviceRunTimeUnit) {
+        if port == self.io_base {
+            self.address = data;
+        }
+        else if port == self.io_base + 1 {
+            self.register_write(self.address, data);
+        }
+    }
+
+    fn port_list(&self) -> Vec<u16> {
+        vec![self.io_base, self.io_base + 1]
+    }
+}
+
+impl AdLibCard {
+    pub fn new(io_base: Option<u16>) -> Self {
+        Self {
+            io_base: io_base.unwrap_or(ADLIB_DEFAULT_IO_BASE),
+            ..Default::default()
+        }
+    }
+
+    pub fn reset(&mut self) {
+        let io_base = self.io_base;
+        let sample_rate = self.sample_rate;
+        *self = Self::new(Some(io_base));
+        self.set_sample_rate(sample_rate);
+    }
+
+    /// Set the rate at which output samples are produced. This should match the rate of the
+    /// sound player the card is mixed into.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.max_samples = sample_rate as usize / 2;
+        self.samples.clear();
+        self.sample_accum = 0.0;
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Pop the next generated sample, if one is available.
+    pub fn pop_sample(&mut self) -> Option<f32> {
+        self.samples.pop_front()
+    }
+
+    pub fn samples_available(&self) -> usize {
+        self.samples.len()
+    }
+
+    fn status_read(&self) -> u8 {
+        self.status | STATUS_OPL2_ID
+    }
+
+    fn register_write(&mut self, reg: u8, data: u8) {
+        self.registers[reg as usize] = data;
+
+        match reg {
+            REG_TEST => {}
+            REG_TIMER1 => {
+                self.timer1_value = data;
+            }
+            REG_TIMER2 => {
+                self.timer2_value = data;
+            }
+            REG_TIMER_CONTROL => {
+                if data & CONTROL_IRQ_RESET != 0 {
+                    // IRQ reset clears all status flags. Other bits are ignored.
+                    self.status = 0;
+                    return;
+                }
+                self.timer1_masked = data & CONTROL_T1_MASK != 0;
+                self.timer2_masked = data & CONTROL_T2_MASK != 0;
+
+                let t1_start = data & CONTROL_T1_START != 0;
+                if t1_start && !self.timer1_running {
+                    self.timer1_counter = self.timer1_value;
+                    self.timer1_accum = 0.0;
+                }
+                self.timer1_running = t1_start;
+
+                let t2_start = data & CONTROL_T2_START != 0;
+                if t2_start && !self.timer2_running {
+                    self.timer2_counter = self.timer2_value;
+                    self.timer2_accum = 0.0;
+                }
+                self.timer2_running = t2_start;
+            }
+            REG_CSM_KEYSPLIT => {}
+            0x20..=0x35 => {
+                if let Some(op) = REG_TO_OPERATOR[(reg - 0x20) as usize] {
+                    let op = &mut self.operators[op];
+                    op.tremolo = data & 0x80 != 0;
+                    op.vibrato = data & 0x40 != 0;
+                    op.sustain = data & 0x20 != 0;
+                    op.ksr = data & 0x10 != 0;
+                    op.mult = data & 0x0F;
+                }
+            }
+            0x40..=0x55 => {
+                if let Some(op) = REG_TO_OPERATOR[(reg - 0x40) as usize] {
+                    let op = &mut self.operators[op];
+                    op.ksl = data >> 6;
+                    op.total_level = data & 0x3F;
+                }
+            }
+            0x60..=0x75 => {
+                if let Some(op) = REG_TO_OPERATOR[(reg - 0x60) as usize] {
+                    let op = &mut self.operators[op];
+                    op.attack_rate = data >> 4;
+                    op.decay_rate = data & 0x0F;
+                }
+            }
+            0x80..=0x95 => {
+                if let Some(op) = REG_TO_OPERATOR[(reg - 0x80) as usize] {
+                    let op = &mut self.operators[op];
+                    op.sustain_level = data >> 4;
+                    op.release_rate = data & 0x0F;
+                }
+            }
+            0xA0..=0xA8 => {
+                let ch = &mut self.channels[(reg - 0xA0) as usize];
+                ch.fnum = (ch.fnum & 0x300) | data as u16;
+            }
+            0xB0..=0xB8 => {
+                let ch_idx = (reg - 0xB0) as usize;
+                let ch = &mut self.channels[ch_idx];
+                ch.fnum = (ch.fnum & 0x0FF) | ((data as u16 & 0x03) << 8);
+                ch.block = (data >> 2) & 0x07;
+
+                let key_on = data & 0x20 != 0;
+                if key_on != ch.key_on {
+                    ch.key_on = key_on;
+                    let (modulator, carrier) = CHANNEL_OPERATORS[ch_idx];
+                    if key_on {
+                        self.operators[modulator].key_on();
+                        self.operators[carrier].key_on();
+                    }
+                    else {
+                        self.operators[modulator].key_off();
+                        self.operators[carrier].key_off();
+                    }
+                }
+            }
+            REG_DEPTH_RHYTHM => {}
+            0xC0..=0xC8 => {
+                let ch = &mut self.channels[(reg - 0xC0) as usize];
+                ch.feedback = (data >> 1) & 0x07;
+                ch.additive = data & 0x01 != 0;
+            }
+            0xE0..=0xF5 => {
+                if let Some(op) = REG_TO_OPERATOR[(reg - 0xE0) as usize] {
+                    // Waveform select only has effect if enabled via the test register.
+                    if self.registers[REG_TEST as usize] & TEST_WAVEFORM_SELECT_ENABLE != 0 {
+                        self.operators[op].waveform = data & 0x03;
+                    }
+                    else {
+                        self.operators[op].waveform = 0;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Run the card for the specified number of microseconds. This advances the timers and
+    /// synthesizes the corresponding number of output samples.
+    pub fn run(&mut self, us: f64) {
+        self.run_timers(us);
+
+        self.sample_accum += us * self.sample_rate as f64 / 1_000_000.0;
+        while self.sample_accum >= 1.0 {
+            let sample = self.generate_sample();
+            if self.samples.len() >= self.max_samples {
+                // Nobody is consuming our output. Drop the oldest sample.
+                self.samples.pop_front();
+            }
+            self.samples.push_back(sample);
+            self.sample_accum -= 1.0;
+        }
+    }
+
+    fn run_timers(&mut self, us: f64) {
+        if self.timer1_running {
+            self.timer1_accum += us;
+            while self.timer1_accum >= TIMER1_PERIOD_US {
+                self.timer1_accum -= TIMER1_PERIOD_US;
+                if self.timer1_counter == 0xFF {
+                    self.timer1_counter = self.timer1_value;
+                    if !self.timer1_masked {
+                        self.status |= STATUS_IRQ | STATUS_T1_OVERFLOW;
+                    }
+                }
+                else {
+                    self.timer1_counter += 1;
+                }
+            }
+        }
+        if self.timer2_running {
+            self.timer2_accum += us;
+            while self.timer2_accum >= TIMER2_PERIOD_US {
+                self.timer2_accum -= TIMER2_PERIOD_US;
+                if self.timer2_counter == 0xFF {
+                    self.timer2_counter = self.timer2_value;
+                    if !self.timer2_masked {
+                        self.status |= STATUS_IRQ | STATUS_T2_OVERFLOW;
+                    }
+                }
+                else {
+                    self.timer2_counter += 1;
+                }
+            }
+        }
+    }
+
+    fn generate_sample(&mut self) -> f32 {
+        let sample_rate = self.sample_rate as f64;
+        let depth = self.registers[REG_DEPTH_RHYTHM as usize];
+        let nts = self.registers[REG_CSM_KEYSPLIT as usize] & KEYSPLIT_NOTE_SELECT != 0;
+
+        // Update LFOs. Tremolo is a triangle wave, vibrato a sine.
+        self.lfo_phase_tremolo = (self.lfo_phase_tremolo + TREMOLO_HZ / sample_rate).fract();
+        self.lfo_phase_vibrato = (self.lfo_phase_vibrato + VIBRATO_HZ / sample_rate).fract();
+
+        let tremolo_tri = 1.0 - (self.lfo_phase_tremolo * 2.0 - 1.0).abs();
+        let tremolo_db = tremolo_tri * TREMOLO_DEPTH_DB[(depth & DEPTH_TREMOLO != 0) as usize];
+        let vibrato_cents = (self.lfo_phase_vibrato * std::f64::consts::TAU).sin()
+            * VIBRATO_DEPTH_CENTS[(depth & DEPTH_VIBRATO != 0) as usize];
+        let vibrato_factor = 2.0f64.powf(vibrato_cents / 1200.0);
+
+        let mut mix = 0.0;
+        for (ch_idx, ch) in self.channels.iter().enumerate() {
+            let (m_idx, c_idx) = CHANNEL_OPERATORS[ch_idx];
+            let freq = ch.frequency();
+            let rof = ch.rate_offset(nts);
+
+            // Modulator, with self-feedback
+            let modulator = &mut self.operators[m_idx];
+            let fb_mod = if ch.feedback > 0 {
+                (modulator.out[0] + modulator.out[1]) / 2.0 * (1u32 << (ch.feedback - 1)) as f64 / 32.0
+            }
+            else {
+                0.0
+            };
+            let ksl_db = ch.ksl_db(modulator.ksl);
+            let m_out = modulator.output(fb_mod, ksl_db, tremolo_db);
+            modulator.tick_envelope(rof, sample_rate);
+            modulator.advance_phase(freq, vibrato_factor, sample_rate);
+
+            // Carrier
+            let carrier = &mut self.operators[c_idx];
+            let ksl_db = ch.ksl_db(carrier.ksl);
+            let ch_out = if ch.additive {
+                m_out + carrier.output(0.0, ksl_db, tremolo_db)
+            }
+            else {
+                carrier.output(m_out * MODULATION_DEPTH, ksl_db, tremolo_db)
+            };
+            carrier.tick_envelope(rof, sample_rate);
+            carrier.advance_phase(freq, vibrato_factor, sample_rate);
+
+            mix += ch_out;
+        }
+
+        (mix * MIX_SCALE).clamp(-1.0, 1.0) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_reg(card: &mut AdLibCard, reg: u8, data: u8) {
+        card.write_u8(ADLIB_DEFAULT_IO_BASE, reg, None, DeviceRunTimeUnit::SystemTicks(0));
+        card.write_u8(ADLIB_DEFAULT_IO_BASE + 1, data, None, DeviceRunTimeUnit::SystemTicks(0));
+    }
+
+    fn read_status(card: &mut AdLibCard) -> u8 {
+        card.read_u8(ADLIB_DEFAULT_IO_BASE, DeviceRunTimeUnit::SystemTicks(0))
+    }
+
+    #[test]
+    fn timer1_overflow_passes_detection_sequence() {
+        let mut card = AdLibCard::new(None);
+
+        // Reset both timers and the IRQ flag, as the standard detection routine does.
+        write_reg(&mut card, REG_TIMER_CONTROL, CONTROL_T1_MASK | CONTROL_T2_MASK);
+        write_reg(&mut card, REG_TIMER_CONTROL, CONTROL_IRQ_RESET);
+        assert_eq!(read_status(&mut card) & 0xE0, 0);
+
+        // Start timer 1 one tick before overflow, with timer 2 masked.
+        write_reg(&mut card, REG_TIMER1, 0xFF);
+        write_reg(&mut card, REG_TIMER_CONTROL, CONTROL_T2_MASK | CONTROL_T1_START);
+        card.run(TIMER1_PERIOD_US - 1.0);
+        assert_eq!(read_status(&mut card) & 0xE0, 0);
+        card.run(1.0);
+        assert_eq!(read_status(&mut card), STATUS_IRQ | STATUS_T1_OVERFLOW | STATUS_OPL2_ID);
+
+        // Timer 2 is slower and masked, so it never raises its flag.
+        write_reg(&mut card, REG_TIMER2, 0xFF);
+        write_reg(&mut card, REG_TIMER_CONTROL, CONTROL_IRQ_RESET);
+        write_reg(&mut card, REG_TIMER_CONTROL, CONTROL_T2_MASK | CONTROL_T2_START);
+        card.run(TIMER2_PERIOD_US * 2.0);
+        assert_eq!(read_status(&mut card) & 0xE0, 0);
+    }
+}
@@ -95,10 +95,10 @@ impl CGACard {
     /// Draw a single character glyph column pixel in text mode, doubling the pixel if
     /// in 40 column mode.
     pub fn draw_text_mode_pixel(&mut self) {
-        let mut new_pixel = match CGACard::get_glyph_bit(self.cur_char, self.char_col, self.vlc_c9) {
+        let mut new_pixel = match self.get_glyph_bit(self.cur_char, self.char_col, self.vlc_c9) {
             true => {
                 if self.cur_blink {
-                    if self.blink_state {
+                    if self.text_blink_state {
                         self.cur_fg
                     }
                     else {
@@ -29,7 +29,7 @@
     Implementation of the IoDevice interface trait for the IBM CGA card.
 
 */
-use super::*;
+use super::{tandy::*, *};
 use crate::bus::IoDevice;
 
 // CRTC registers are mirrored from 0x3D0 - 0x3D5 due to incomplete
@@ -116,13 +116,16 @@ impl IoDevice for CGACard {
                     log::debug!("wrote latch set register");
                     self.set_lp_latch()
                 }
+                TANDY_ADDRESS_REGISTER => self.handle_tandy_address_write(data),
+                TANDY_DATA_REGISTER => self.handle_tandy_data_write(data),
+                TANDY_PAGE_REGISTER => self.handle_tandy_page_write(data),
                 _ => {}
             }
         }
     }
 
     fn port_list(&self) -> Vec<u16> {
-        vec![
+        let mut ports = vec![
             CRTC_REGISTER_SELECT0,
             CRTC_REGISTER0,
             CRTC_REGISTER_SELECT1,
@@ -134,6 +137,8 @@ impl IoDevice for CGACard {
             CGA_LIGHTPEN_LATCH_RESET,
             CGA_LIGHTPEN_LATCH_SET,
             CGA_STATUS_REGISTER,
-        ]
+        ];
+        ports.extend(self.tandy_port_list());
+        ports
     }
 }
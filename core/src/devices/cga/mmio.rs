@@ -37,22 +37,18 @@ use crate::bus::MemoryMappedDevice;
 /// but we handle the mirroring of VRAM this way, and for consistency with other devices
 impl MemoryMappedDevice for CGACard {
     fn get_read_wait(&mut self, _address: usize, cycles: u32) -> u32 {
-        // Look up wait states given the last ticked clock cycle + elapsed cycles
-        // passed in.
-        let phase = (self.cycles + cycles as u64 + 1) as usize & (0x0F as usize);
-        let waits = WAIT_TABLE[phase];
+        // Look up wait states given the beam position + elapsed cycles passed in.
+        let waits = self.vram_wait(cycles);
 
-        trace!(self, "READ_U8 (T2): PHASE: {:02X}, WAITS: {}", phase, waits);
+        trace!(self, "READ_U8 (T2): WAITS: {}", waits);
         waits
     }
 
     fn get_write_wait(&mut self, _address: usize, cycles: u32) -> u32 {
-        // Look up wait states given the last ticked clock cycle + elapsed cycles
-        // passed in.
-        let phase = (self.cycles + cycles as u64 + 1) as usize & (0x0F as usize);
-        let waits = WAIT_TABLE[phase];
+        // Look up wait states given the beam position + elapsed cycles passed in.
+        let waits = self.vram_wait(cycles);
 
-        trace!(self, "WRITE_U8 (T2): PHASE: {:02X}, WAITS: {}", phase, waits);
+        trace!(self, "WRITE_U8 (T2): WAITS: {}", waits);
         waits
     }
 
@@ -65,8 +61,13 @@ impl MemoryMappedDevice for CGACard {
             self.catch_up(DeviceRunTimeUnit::SystemTicks(cycles * 3));
         }*/
 
-        let a_offset = (address & CGA_MEM_MASK) - CGA_MEM_ADDRESS;
-        if a_offset < CGA_MEM_SIZE {
+        if !self.vram_decoded(address) {
+            // Nothing answers past the end of VRAM.
+            return (0xFF, 0);
+        }
+
+        let a_offset = self.vram_offset(address);
+        if a_offset < self.mem.len() {
             // Do snow every other hchar
             if self.cycles & 0b1000 == 0 {
                 // Save bus parameters for snow emulation
@@ -90,20 +91,34 @@ impl MemoryMappedDevice for CGACard {
     }
 
     fn mmio_peek_u8(&self, address: usize) -> u8 {
-        let a_offset = (address & CGA_MEM_MASK) - CGA_MEM_ADDRESS;
+        if !self.vram_decoded(address) {
+            return 0xFF;
+        }
+        let a_offset = self.vram_offset(address);
 
         self.mem[a_offset]
     }
 
     fn mmio_peek_u16(&self, address: usize) -> u16 {
-        let a_offset = (address & CGA_MEM_MASK) - CGA_MEM_ADDRESS;
+        if !self.vram_decoded(address) {
+            return 0xFFFF;
+        }
+        let a_offset = self.vram_offset(address);
+
+        // The second byte wraps to the start of VRAM, or is open bus if it falls past the end.
+        let b_byte = if self.vram_decoded(address + 1) {
+            self.mem[self.vram_offset(address + 1)]
+        }
+        else {
+            0xFF
+        };
 
-        (self.mem[a_offset] as u16) << 8 | self.mem[a_offset + 1] as u16
+        (self.mem[a_offset] as u16) << 8 | b_byte as u16
     }
 
     fn mmio_write_u8(&mut self, address: usize, byte: u8, _cycles: u32) -> u32 {
-        let a_offset = (address & CGA_MEM_MASK) - CGA_MEM_ADDRESS;
-        if a_offset < CGA_MEM_SIZE {
+        let a_offset = self.vram_offset(address);
+        if self.vram_decoded(address) && a_offset < self.mem.len() {
             // Save bus parameters for snow emulation
             self.last_bus_addr = a_offset;
             self.last_bus_value = byte;
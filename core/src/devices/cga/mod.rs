@@ -45,8 +45,12 @@ mod io;
 mod draw;
 mod mmio;
 mod tablegen;
+mod tandy;
 mod videocard;
 
+pub use tandy::TandyRegisters;
+use tandy::TANDY_MEM_SIZE;
+
 use super::*;
 
 use crate::{
@@ -533,7 +537,9 @@ pub struct CGACard {
     vma_t: usize,            // VMA' register - Video memory address temporary
     vmws: usize,             // Video memory word size
     rba: usize,              // Render buffer address
-    blink_state: bool,       // Used to control blinking of cursor and text with blink attribute
+    blink_state: bool,       // Used to control blinking of the cursor
+    text_blink_state: bool,  // Used to control blinking of text with blink attribute, 1/2 cursor rate
+    enable_blink: bool,      // If false, the cursor and text with blink attribute are always shown
     blink_accum_us: f64,     // Microsecond accumulator for blink state flipflop
     blink_accum_clocks: u32, // CGA Clock accumulator for blink state flipflop
     accumulated_us: f64,
@@ -542,7 +548,9 @@ pub struct CGACard {
     ticks_accum: u32,
     clocks_accum: u32,
 
-    mem: Box<[u8; CGA_MEM_SIZE]>,
+    mem: Box<[u8]>,
+    vram_size: usize,
+    vram_open_bus: bool,
 
     back_buf: usize,
     front_buf: usize,
@@ -556,8 +564,20 @@ pub struct CGACard {
     trace_logger:  TraceLogger,
     debug_counter: u64,
 
-    lightpen_latch: bool,
-    lightpen_addr:  usize,
+    lightpen_latch:  bool,
+    lightpen_addr:   usize,
+    lightpen_pos:    Option<(u32, u32)>,
+    lightpen_switch: bool,
+
+    tandy: Option<TandyRegisters>,
+    mode_tandy16: bool,
+
+    // Character generator font, in the same row-major layout as CGA_FONT
+    font: Vec<u8>,
+
+    // Glyph tables unpacked from the font
+    hires_glyph_table:  Box<[[u64; 8]; 256]>,
+    lowres_glyph_table: Box<[[[u64; 8]; 2]; 256]>,
 }
 
 #[derive(Debug)]
@@ -597,6 +617,7 @@ impl CgaDefault for DisplayExtents {
             row_stride: CGA_XRES_MAX as usize,
             double_scan: true,
             mode_byte: 0,
+            border_color: 0,
         }
     }
 }
@@ -721,6 +742,8 @@ impl Default for CGACard {
             vmws: 2,
             rba: 0,
             blink_state: false,
+            text_blink_state: false,
+            enable_blink: true,
             blink_accum_us: 0.0,
             blink_accum_clocks: 0,
 
@@ -730,7 +753,9 @@ impl Default for CGACard {
             clocks_accum: 0,
             pixel_clocks_owed: 0,
 
-            mem: vec![0; CGA_MEM_SIZE].into_boxed_slice().try_into().unwrap(),
+            mem: vec![0; CGA_MEM_SIZE].into_boxed_slice(),
+            vram_size: CGA_MEM_SIZE,
+            vram_open_bus: false,
 
             back_buf:  1,
             front_buf: 0,
@@ -752,8 +777,18 @@ impl Default for CGACard {
             trace_logger:  TraceLogger::None,
             debug_counter: 0,
 
-            lightpen_latch: false,
-            lightpen_addr:  0,
+            lightpen_latch:  false,
+            lightpen_addr:   0,
+            lightpen_pos:    None,
+            lightpen_switch: false,
+
+            tandy: None,
+            mode_tandy16: false,
+
+            font: CGA_FONT.to_vec(),
+
+            hires_glyph_table:  Box::new(gen_hires_glyph_table(CGA_FONT)),
+            lowres_glyph_table: Box::new(gen_lowres_glyph_table(CGA_FONT)),
         }
     }
 }
@@ -780,6 +815,7 @@ impl CGACard {
         let trace_logger = std::mem::replace(&mut self.trace_logger, TraceLogger::None);
 
         // Save non-default values
+        let enable_blink = self.enable_blink;
         *self = Self {
             debug: self.debug,
             clock_mode: self.clock_mode,
@@ -787,8 +823,44 @@ impl CGACard {
             frame_count: self.frame_count, // Keep frame count as to not confuse frontend
             trace_logger,
             extents: self.extents.clone(),
+            tandy: self.tandy.as_ref().map(|_| TandyRegisters::default()),
+            mem: vec![0; self.mem.len()].into_boxed_slice(),
+            vram_size: self.vram_size,
+            vram_open_bus: self.vram_open_bus,
+            font: std::mem::take(&mut self.font),
+            hires_glyph_table: self.hires_glyph_table.clone(),
+            lowres_glyph_table: self.lowres_glyph_table.clone(),
 
             ..Self::default()
+        };
+        self.set_blink_enabled(enable_blink);
+    }
+
+    /// Enable or disable blinking of the cursor and of text with the blink attribute. While
+    /// disabled, both are drawn as if in the visible phase of the blink cycle.
+    fn set_blink_enabled(&mut self, state: bool) {
+        self.enable_blink = state;
+        if !state {
+            self.blink_state = true;
+            self.text_blink_state = true;
+        }
+    }
+
+    /// Advance the blink flipflops by the specified number of CGA clocks. The cursor toggles
+    /// every 8 frames, and text with the blink attribute every 16 frames, for a character blink
+    /// rate of about 1.87Hz.
+    #[inline]
+    fn tick_blink(&mut self, clocks: u32) {
+        self.blink_accum_clocks += clocks;
+        if self.blink_accum_clocks > CGA_CURSOR_BLINK_RATE_CLOCKS {
+            self.blink_accum_clocks -= CGA_CURSOR_BLINK_RATE_CLOCKS;
+            if self.enable_blink {
+                self.blink_state = !self.blink_state;
+                // Text blink state is 1/2 cursor blink state
+                if self.blink_state {
+                    self.text_blink_state = !self.text_blink_state;
+                }
+            }
         }
     }
 
@@ -865,6 +937,21 @@ impl CGACard {
         0
     }
 
+    /// Return the wait in system ticks for a CPU access to VRAM issued `ticks` after the card was
+    /// last run. The CGA only grants the CPU one memory slot per 16-hdot character, so the wait
+    /// depends on where the beam is within that character. Clocks sitting in the accumulator have
+    /// elapsed even though they have not been ticked yet, while ticks advanced mid-instruction by
+    /// catch_up() are already included in `ticks` and must not be counted twice.
+    #[inline]
+    fn vram_wait(&self, ticks: u32) -> u32 {
+        let beam = self
+            .cycles
+            .wrapping_add(self.clocks_accum as u64)
+            .wrapping_add(ticks as u64)
+            .wrapping_sub(self.ticks_advanced as u64);
+        WAIT_TABLE[(beam.wrapping_add(1) & 0x0F) as usize]
+    }
+
     /// Update the number of pixel clocks we must execute before we can return to clocking the
     /// CGA card by character clock.  When an IO read/write occurs, the CGA card is updated to
     /// the current clock cycle by ticking pixels. During run() we then have to tick by pixels
@@ -901,6 +988,18 @@ impl CGACard {
         self.lightpen_latch = false;
     }
 
+    /// Trigger the light pen latch if the beam is about to sweep the next 'span' pixels across the
+    /// pen's position. We have no phosphor for the pen to sense, so the pen only sees the beam while
+    /// its switch is held down.
+    #[inline]
+    fn check_lightpen(&mut self, span: u32) {
+        if let Some((pen_x, pen_y)) = self.lightpen_pos {
+            if self.lightpen_switch && pen_y == self.beam_y && pen_x >= self.beam_x && pen_x < self.beam_x + span {
+                self.set_lp_latch();
+            }
+        }
+    }
+
     fn get_cursor_span(&self) -> (u8, u8) {
         (self.crtc_cursor_start_line, self.crtc_cursor_end_line)
     }
@@ -1233,6 +1332,18 @@ impl CGACard {
             }
         };
 
+        // The Tandy gate array's 16-color modes are selected by its own mode control register in
+        // combination with the CGA graphics bit. The high resolution clock selects 320 columns.
+        self.mode_tandy16 = self.mode_graphics && self.tandy_16color_enabled();
+        if self.mode_tandy16 {
+            self.display_mode = if self.mode_hires_txt {
+                DisplayMode::Mode9TandyMedResGraphics
+            }
+            else {
+                DisplayMode::Mode8TandyLowResGraphics
+            };
+        }
+
         trace_regs!(self);
         trace!(
             self,
@@ -1332,7 +1443,9 @@ impl CGACard {
         }
 
         // This bit is logically reversed, i.e., 0 is switch on
-        //byte |= STATUS_LIGHTPEN_SWITCH_STATUS;
+        if !self.lightpen_switch {
+            byte |= STATUS_LIGHTPEN_SWITCH_STATUS;
+        }
 
         trace_regs!(self);
         trace!(
@@ -1396,21 +1509,87 @@ impl CGACard {
     }
 
     /// Return the bit value at (col,row) of the given font glyph
-    fn get_glyph_bit(glyph: u8, col: u8, row: u8) -> bool {
+    fn get_glyph_bit(&self, glyph: u8, col: u8, row: u8) -> bool {
         debug_assert!(col < CGA_HCHAR_CLOCK);
         //debug_assert!(row < CRTC_CHAR_CLOCK);
         let row_masked = row & 0x7;
 
         // Calculate byte offset
         let glyph_offset: usize = (row_masked as usize * CGA_FONT_SPAN) + glyph as usize;
-        CGA_FONT[glyph_offset] & (0x01 << (7 - col)) != 0
+        self.font[glyph_offset] & (0x01 << (7 - col)) != 0
+    }
+
+    /// Set the amount of installed VRAM, in bytes, and whether accesses beyond it within the
+    /// memory aperture mirror VRAM or return open bus. The size must be a power of two no larger
+    /// than the aperture.
+    ///
+    /// Returns false and keeps the current configuration if the size is invalid.
+    pub fn set_vram(&mut self, size: usize, open_bus: bool) -> bool {
+        if !size.is_power_of_two() || size > CGA_MEM_APERTURE {
+            log::warn!("CGA VRAM size {:#X} is invalid. Using {:#X}.", size, self.vram_size);
+            return false;
+        }
+        self.vram_size = size;
+        self.vram_open_bus = open_bus;
+        self.allocate_vram(size);
+        true
+    }
+
+    /// Grow the VRAM allocation to at least `size` bytes. The allocation never shrinks, so
+    /// reducing the installed size and restoring it keeps the original contents.
+    pub(crate) fn allocate_vram(&mut self, size: usize) {
+        if self.mem.len() < size {
+            let mut mem = vec![0; size];
+            mem[..self.mem.len()].copy_from_slice(&self.mem);
+            self.mem = mem.into_boxed_slice();
+        }
+    }
+
+    /// Return the amount of installed VRAM, in bytes.
+    pub fn vram_size(&self) -> usize {
+        if self.is_tandy() {
+            TANDY_MEM_SIZE
+        }
+        else {
+            self.vram_size
+        }
+    }
+
+    /// Replace the character generator font. `font` must be an 8x8 font in the common
+    /// glyph-major layout (8 bytes per glyph, 256 glyphs), as found in code page font files.
+    /// Passing None restores the built-in font.
+    ///
+    /// Returns false and keeps the built-in font if the font is not the expected size.
+    pub fn set_font(&mut self, font: Option<&[u8]>) -> bool {
+        let mut result = true;
+        self.font = CGA_FONT.to_vec();
+
+        if let Some(font) = font {
+            if font.len() == CGA_FONT_SPAN * CRTC_FONT_HEIGHT as usize {
+                // Transpose from glyph-major to the card's row-major layout.
+                for (glyph, glyph_rows) in font.chunks_exact(CRTC_FONT_HEIGHT as usize).enumerate() {
+                    for (row, byte) in glyph_rows.iter().enumerate() {
+                        self.font[row * CGA_FONT_SPAN + glyph] = *byte;
+                    }
+                }
+            }
+            else {
+                log::warn!("CGA font has invalid size: {} bytes. Using built-in font.", font.len());
+                result = false;
+            }
+        }
+
+        self.hires_glyph_table = Box::new(gen_hires_glyph_table(&self.font));
+        self.lowres_glyph_table = Box::new(gen_lowres_glyph_table(&self.font));
+        result
     }
 
     /// Set the character attributes for the current character.
     /// This applies to text mode only, but is computed in all modes at appropriate times.
     fn set_char_addr(&mut self) {
         // Address from CRTC is masked by 0x1FFF by the CGA card (bit 13 ignored) and doubled.
-        let addr = (self.vma & CGA_TEXT_MODE_WRAP) << 1;
+        // It then wraps within the installed VRAM.
+        let addr = ((self.vma & CGA_TEXT_MODE_WRAP) << 1) & (self.vram_size() - 1);
 
         // Generate snow if we are in hires mode, have a dirty bus, and HCLOCK is odd
         if self.enable_snow && self.mode_hires_txt && self.dirty_snow && (self.cycles & 0b1000 != 0) {
@@ -1448,11 +1627,11 @@ impl CGACard {
     /// glyph in high-resolution text mode.
     #[inline]
     pub fn get_hchar_glyph_row(&self, glyph: usize, row: usize) -> u64 {
-        if self.cur_blink && !self.blink_state {
+        if self.cur_blink && !self.text_blink_state {
             CGA_COLORS_U64[self.cur_bg as usize]
         }
         else {
-            let glyph_row_base = CGA_HIRES_GLYPH_TABLE[glyph & 0xFF][row];
+            let glyph_row_base = self.hires_glyph_table[glyph & 0xFF][row];
 
             // Combine glyph mask with foreground and background colors.
             glyph_row_base & CGA_COLORS_U64[self.cur_fg as usize]
@@ -1464,13 +1643,13 @@ impl CGACard {
     /// glyph in low-resolution (40-column) mode.
     #[inline]
     pub fn get_lchar_glyph_rows(&self, glyph: usize, row: usize) -> (u64, u64) {
-        if self.cur_blink && !self.blink_state {
+        if self.cur_blink && !self.text_blink_state {
             let glyph = CGA_COLORS_U64[self.cur_bg as usize];
             (glyph, glyph)
         }
         else {
-            let glyph_row_base_0 = CGA_LOWRES_GLYPH_TABLE[glyph & 0xFF][0][row];
-            let glyph_row_base_1 = CGA_LOWRES_GLYPH_TABLE[glyph & 0xFF][1][row];
+            let glyph_row_base_0 = self.lowres_glyph_table[glyph & 0xFF][0][row];
+            let glyph_row_base_1 = self.lowres_glyph_table[glyph & 0xFF][1][row];
 
             // Combine glyph mask with foreground and background colors.
             let glyph0 = glyph_row_base_0 & CGA_COLORS_U64[self.cur_fg as usize]
@@ -1497,7 +1676,7 @@ impl CGACard {
         }
         else if self.mode_enable {
             for i in (0..draw_span).step_by(self.clock_divisor as usize) {
-                let new_pixel = match CGACard::get_glyph_bit(self.cur_char, (i as u8 / self.clock_divisor), self.vlc_c9) {
+                let new_pixel = match self.get_glyph_bit(self.cur_char, (i as u8 / self.clock_divisor), self.vlc_c9) {
                     true => {
                         if self.cur_blink {
                             if self.blink_state { self.cur_fg } else { self.cur_bg }
@@ -1553,11 +1732,11 @@ impl CGACard {
     /// programmed into the CRTC start register is interpreted by the CGA as a word address.
     /// In graphics mode, the row counter determines whether address line A12 from the
     /// CRTC is set. This effectively creates a 0x2000 byte offset for odd character rows.
+    /// The address wraps within the installed VRAM.
     #[inline]
     pub fn get_gfx_addr(&self, row: u8) -> usize {
         let row_offset = (row as usize & 0x01) << 12;
-        let addr = (self.vma & 0x0FFF | row_offset) << 1;
-        addr
+        ((self.vma & 0x0FFF | row_offset) << 1) & (self.vram_size() - 1)
     }
 
     pub fn get_screen_ticks(&self) -> u64 {
@@ -1621,6 +1800,8 @@ impl CGACard {
             }
         }
 
+        self.check_lightpen(8 * self.clock_divisor as u32);
+
         // Update position to next pixel and character column.
         self.beam_x += 8 * self.clock_divisor as u32;
         self.rba += 8 * self.clock_divisor as usize;
@@ -1677,6 +1858,9 @@ impl CGACard {
                 if !self.mode_graphics {
                     self.draw_text_mode_hchar();
                 }
+                else if self.mode_tandy16 {
+                    self.draw_tandy_gfx_mode_char();
+                }
                 else if self.mode_hires_gfx {
                     self.draw_hires_gfx_mode_char();
                 }
@@ -1718,6 +1902,8 @@ impl CGACard {
             }*/
         }
 
+        self.check_lightpen(8 * self.clock_divisor as u32);
+
         // Update position to next pixel and character column.
         self.beam_x += 8 * self.clock_divisor as u32;
         self.rba += 8 * self.clock_divisor as usize;
@@ -1776,6 +1962,9 @@ impl CGACard {
                 if !self.mode_graphics {
                     self.draw_text_mode_lchar();
                 }
+                else if self.mode_tandy16 {
+                    self.draw_tandy_gfx_mode_char();
+                }
                 else if self.mode_hires_gfx {
                     self.draw_hires_gfx_mode_char();
                 }
@@ -1804,6 +1993,8 @@ impl CGACard {
             }
         }
 
+        self.check_lightpen(16);
+
         // Update position to next pixel and character column.
         self.beam_x += 16;
         self.rba += 16;
@@ -1850,6 +2041,8 @@ impl CGACard {
             self.draw_pixel(CGA_DEBUG_COLOR);
         }
 
+        self.check_lightpen(self.clock_divisor as u32);
+
         // Update position to next pixel and character column.
         self.beam_x += self.clock_divisor as u32;
         self.rba += self.clock_divisor as usize;
@@ -1896,6 +2089,9 @@ impl CGACard {
                 if !self.mode_graphics {
                     self.draw_text_mode_pixel();
                 }
+                else if self.mode_tandy16 {
+                    self.draw_tandy_gfx_mode_pixel();
+                }
                 else if self.mode_hires_gfx {
                     self.draw_hires_gfx_mode_pixel();
                 }
@@ -1931,6 +2127,8 @@ impl CGACard {
             }
         }
 
+        self.check_lightpen(self.clock_divisor as u32);
+
         // Update position to next pixel and character column.
         self.beam_x += self.clock_divisor as u32;
         self.rba += self.clock_divisor as usize;
@@ -2266,6 +2464,7 @@ impl CGACard {
             // The mode could have changed several times per frame, but I am not sure how the composite rendering should
             // really handle that...
             self.extents.mode_byte = self.mode_byte;
+            self.extents.border_color = self.cc_overscan_color;
 
             // Swap the display buffers
             self.swap();
@@ -2286,3 +2485,106 @@ impl CGACard {
         println!("{}", self.vtac_c5);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::MemoryMappedDevice;
+
+    #[test]
+    fn crtc_fetches_wrap_at_installed_vram() {
+        let mut cga = CGACard::new(TraceLogger::None, ClockingMode::Dynamic, false);
+        cga.mem[0] = 0x41;
+        cga.mem[1] = 0x07;
+        cga.mem[0x1000] = 0x42;
+        cga.mem[0x1001] = 0x70;
+
+        // With the standard 16K, odd graphics rows fetch from the second 8K bank.
+        assert_eq!(cga.get_gfx_addr(1), 0x2000);
+
+        // With 4K installed, both graphics and text fetches wrap back to the start of VRAM.
+        assert!(cga.set_vram(0x1000, false));
+        assert_eq!(cga.get_gfx_addr(1), 0);
+
+        cga.vma = 0x800;
+        cga.set_char_addr();
+        assert_eq!((cga.cur_char, cga.cur_attr), (0x41, 0x07));
+
+        assert!(cga.set_vram(CGA_MEM_SIZE, false));
+        cga.set_char_addr();
+        assert_eq!((cga.cur_char, cga.cur_attr), (0x42, 0x70));
+    }
+
+    #[test]
+    fn word_peeks_wrap_or_read_open_bus_at_the_end_of_vram() {
+        let mut cga = CGACard::new(TraceLogger::None, ClockingMode::Dynamic, false);
+        cga.mem[CGA_MEM_SIZE - 1] = 0x12;
+        cga.mem[0] = 0x34;
+        cga.mem[0xFFF] = 0x56;
+
+        // A peek at the last byte of VRAM takes its second byte from the start of VRAM.
+        assert_eq!(cga.mmio_peek_u16(CGA_MEM_ADDRESS + CGA_MEM_SIZE - 1), 0x1234);
+
+        // With open bus past 4K, a peek that starts or ends past the end of VRAM reads 0xFF.
+        assert!(cga.set_vram(0x1000, true));
+        assert_eq!(cga.mmio_peek_u16(CGA_MEM_ADDRESS + 0xFFF), 0x56FF);
+        assert_eq!(cga.mmio_peek_u16(CGA_MEM_ADDRESS + 0x1000), 0xFFFF);
+    }
+
+    #[test]
+    fn custom_font_replaces_the_character_generator() {
+        let mut cga = CGACard::new(TraceLogger::None, ClockingMode::Dynamic, false);
+
+        // Give glyph 0x41 a single diagonal, in the glyph-major layout of a font file.
+        let mut font = vec![0; CGA_FONT_SPAN * CRTC_FONT_HEIGHT as usize];
+        for row in 0..CRTC_FONT_HEIGHT as usize {
+            font[0x41 * CRTC_FONT_HEIGHT as usize + row] = 0x80 >> row;
+        }
+        assert!(cga.set_font(Some(&font)));
+        for row in 0..CRTC_FONT_HEIGHT {
+            for col in 0..CGA_HCHAR_CLOCK {
+                assert_eq!(cga.get_glyph_bit(0x41, col, row), col == row);
+            }
+        }
+        assert_eq!(*cga.hires_glyph_table, gen_hires_glyph_table(&cga.font));
+        assert_ne!(*cga.hires_glyph_table, gen_hires_glyph_table(CGA_FONT));
+
+        // The font survives a reset.
+        cga.reset_private();
+        assert!(cga.get_glyph_bit(0x41, 3, 3));
+        assert!(!cga.get_glyph_bit(0x41, 4, 3));
+
+        // A font of the wrong size, or no font, restores the built-in one.
+        assert!(!cga.set_font(Some(&font[..1024])));
+        assert_eq!(cga.font, CGA_FONT);
+        assert!(cga.set_font(Some(&font)));
+        assert!(cga.set_font(None));
+        assert_eq!(cga.font, CGA_FONT);
+        assert_eq!(*cga.lowres_glyph_table, gen_lowres_glyph_table(CGA_FONT));
+    }
+
+    #[test]
+    fn text_blinks_at_half_the_cursor_rate() {
+        let mut cga = CGACard::new(TraceLogger::None, ClockingMode::Dynamic, false);
+
+        // Each tick is long enough to toggle the cursor exactly once.
+        let mut states = Vec::new();
+        for _ in 0..4 {
+            cga.tick_blink(CGA_CURSOR_BLINK_RATE_CLOCKS + 1);
+            states.push((cga.blink_state, cga.text_blink_state));
+        }
+        assert_eq!(states, vec![(true, true), (false, true), (true, false), (false, false)]);
+
+        // With blinking disabled, the cursor and blinking text stay visible, even across a reset.
+        cga.set_video_option(VideoOption::EnableBlink(false));
+        cga.tick_blink(CGA_CURSOR_BLINK_RATE_CLOCKS + 1);
+        assert_eq!((cga.blink_state, cga.text_blink_state), (true, true));
+        cga.reset_private();
+        cga.tick_blink(CGA_CURSOR_BLINK_RATE_CLOCKS + 1);
+        assert_eq!((cga.blink_state, cga.text_blink_state), (true, true));
+
+        cga.set_video_option(VideoOption::EnableBlink(true));
+        cga.tick_blink(CGA_CURSOR_BLINK_RATE_CLOCKS + 1);
+        assert_eq!((cga.blink_state, cga.text_blink_state), (false, true));
+    }
+}
@@ -33,12 +33,14 @@
 
 use super::*;
 
-/// Constant initializer to unpack the CGA font by glyph into
-/// 8 rows of 64 bit values. These values are then AND'd with
-/// 64 bit color constants and then OR'd together to produce
-/// the final 64 bit drawing value for drawing by one entire
-/// character row.
-pub const CGA_HIRES_GLYPH_TABLE: [[u64; 8]; 256] = {
+/// Unpack the specified font by glyph into 8 rows of 64 bit values.
+/// These values are then AND'd with 64 bit color constants and then
+/// OR'd together to produce the final 64 bit drawing value for drawing
+/// by one entire character row.
+///
+/// The font must be in the card's row-major layout, CGA_FONT_SPAN
+/// bytes per row.
+pub const fn gen_hires_glyph_table(font: &[u8]) -> [[u64; 8]; 256] {
     let mut table: [[u64; 8]; 256] = [[0; 8]; 256];
 
     let mut glyph: usize = 0;
@@ -53,7 +55,7 @@ pub const CGA_HIRES_GLYPH_TABLE: [[u64; 8]; 256] = {
 
             loop {
                 let glyph_offset: usize = (row * CGA_FONT_SPAN) + glyph as usize;
-                let bit_val = CGA_FONT[glyph_offset] & (0x01 << (7 - bit)) != 0;
+                let bit_val = font[glyph_offset] & (0x01 << (7 - bit)) != 0;
 
                 if bit_val {
                     glyph_u64 |= (if bit_val { 0xFF } else { 0x00 }) << (bit * 8);
@@ -85,18 +87,15 @@ pub const CGA_HIRES_GLYPH_TABLE: [[u64; 8]; 256] = {
     }
 
     table
-};
+}
 
-/// Constant initializer to unpack the CGA font by glyph into
-/// 8 rows of 64 bit values. These values are then AND'd with
-/// 64 bit color constants and then OR'd together to produce
-/// the final 64 bit drawing value for drawing by one entire
-/// character row.
+/// Unpack the specified font by glyph into 8 rows of 64 bit values,
+/// as gen_hires_glyph_table().
 ///
 /// This version of the table splits each row up into two
 /// columns of 8 pixels for drawing glyphs 8 pixels at a time
 /// in low-resolution mode.
-pub const CGA_LOWRES_GLYPH_TABLE: [[[u64; 8]; 2]; 256] = {
+pub const fn gen_lowres_glyph_table(font: &[u8]) -> [[[u64; 8]; 2]; 256] {
     let mut table: [[[u64; 8]; 2]; 256] = [[[0; 8]; 2]; 256];
 
     let mut glyph: usize = 0;
@@ -112,7 +111,7 @@ pub const CGA_LOWRES_GLYPH_TABLE: [[[u64; 8]; 2]; 256] = {
 
             loop {
                 let glyph_offset: usize = (row * CGA_FONT_SPAN) + glyph as usize;
-                let bit_val = CGA_FONT[glyph_offset] & (0x01 << (7 - bit)) != 0;
+                let bit_val = font[glyph_offset] & (0x01 << (7 - bit)) != 0;
 
                 if bit_val {
                     glyph_u64 |= (if bit_val { 0xFF } else { 0x00 }) << ((bit * 2) * 8);
@@ -134,7 +133,7 @@ pub const CGA_LOWRES_GLYPH_TABLE: [[[u64; 8]; 2]; 256] = {
 
             loop {
                 let glyph_offset: usize = (row * CGA_FONT_SPAN) + glyph as usize;
-                let bit_val = CGA_FONT[glyph_offset] & (0x01 << (3 - bit)) != 0;
+                let bit_val = font[glyph_offset] & (0x01 << (3 - bit)) != 0;
 
                 if bit_val {
                     glyph_u64 |= (if bit_val { 0xFF } else { 0x00 }) << ((bit * 2) * 8);
@@ -166,7 +165,7 @@ pub const CGA_LOWRES_GLYPH_TABLE: [[[u64; 8]; 2]; 256] = {
     }
 
     table
-};
+}
 
 /// Constant initializer to unpack all possible 8 bit patterns
 pub const CGA_8BIT_TABLE: [u64; 256] = {
@@ -0,0 +1,327 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    devices::cga::tandy.rs
+
+    Implements the Tandy 1000 video gate array extensions to the CGA.
+
+    The Tandy 1000 video subsystem is a superset of the CGA. In addition to
+    the CGA registers, it provides a 16-entry palette, a palette mask, a
+    border color register and a second mode control register that enables
+    the 16-color graphics modes (160x200 and 320x200). The gate array
+    registers are accessed indirectly by writing a register index to 0x3DA
+    and data to 0x3DE.
+
+    On real hardware, video memory is carved out of the top of system RAM,
+    and the CRT/CPU page register at 0x3DF selects which page is displayed
+    and which is visible at B8000. This implementation instead gives the card
+    its own 32K of VRAM at B8000. The page register is latched, but only its
+    video address mode bits are used.
+*/
+
+use super::*;
+
+pub const TANDY_ADDRESS_REGISTER: u16 = 0x3DA; // Write-only. Reads return the CGA status register.
+pub const TANDY_DATA_REGISTER: u16 = 0x3DE;
+pub const TANDY_PAGE_REGISTER: u16 = 0x3DF;
+
+pub const TANDY_MEM_SIZE: usize = 0x8000; // 32768 bytes
+pub const TANDY_MEM_MASK: usize = 0x7FFF;
+
+const TANDY_REG_PALETTE_MASK: u8 = 0x01;
+const TANDY_REG_BORDER_COLOR: u8 = 0x02;
+const TANDY_REG_MODE_CONTROL: u8 = 0x03;
+const TANDY_REG_PALETTE_BASE: u8 = 0x10;
+const TANDY_REG_PALETTE_END: u8 = 0x1F;
+
+const TANDY_MODE_16COLOR: u8 = 0b0001_0000;
+// When both address mode bits are set, graphics memory is interleaved across four 8K banks
+// selected by the low two bits of the row counter, for the 32K 320x200x16 mode.
+const TANDY_PAGE_ADDRESS_MODE: u8 = 0b1100_0000;
+
+#[derive(Clone, Debug)]
+pub struct TandyRegisters {
+    pub address: u8,
+    pub palette_mask: u8,
+    pub border_color: u8,
+    pub mode_control: u8,
+    pub palette: [u8; 16],
+    pub page: u8,
+}
+
+impl Default for TandyRegisters {
+    fn default() -> Self {
+        Self {
+            address: 0,
+            palette_mask: 0x0F,
+            border_color: 0,
+            mode_control: 0,
+            palette: std::array::from_fn(|i| i as u8),
+            page: 0,
+        }
+    }
+}
+
+impl CGACard {
+    /// Enable the Tandy 1000 video gate array. This must be called before the card's port list
+    /// is installed.
+    pub fn enable_tandy_extensions(&mut self) {
+        self.tandy = Some(TandyRegisters::default());
+        self.allocate_vram(TANDY_MEM_SIZE);
+    }
+
+    pub fn is_tandy(&self) -> bool {
+        self.tandy.is_some()
+    }
+
+    /// Return the Tandy gate array registers, if the extensions are enabled.
+    pub fn tandy_registers(&self) -> Option<&TandyRegisters> {
+        self.tandy.as_ref()
+    }
+
+    pub(crate) fn tandy_port_list(&self) -> Vec<u16> {
+        if self.tandy.is_some() {
+            vec![TANDY_DATA_REGISTER, TANDY_PAGE_REGISTER]
+        }
+        else {
+            Vec::new()
+        }
+    }
+
+    /// Translate a bus address into an offset into VRAM. The CGA mirrors its 16K of VRAM across
+    /// the 32K aperture; the Tandy uses the whole aperture.
+    #[inline]
+    pub(crate) fn vram_offset(&self, address: usize) -> usize {
+        if self.tandy.is_some() {
+            (address - CGA_MEM_ADDRESS) & TANDY_MEM_MASK
+        }
+        else {
+            (address - CGA_MEM_ADDRESS) & (self.vram_size - 1)
+        }
+    }
+
+    /// Return whether a bus address reaches installed VRAM. Addresses past the end of VRAM only
+    /// miss if the card is configured to leave them as open bus instead of mirroring.
+    #[inline]
+    pub(crate) fn vram_decoded(&self, address: usize) -> bool {
+        self.tandy.is_some() || !self.vram_open_bus || (address - CGA_MEM_ADDRESS) < self.vram_size
+    }
+
+    pub(crate) fn handle_tandy_address_write(&mut self, byte: u8) {
+        if let Some(tandy) = &mut self.tandy {
+            tandy.address = byte & 0x1F;
+        }
+    }
+
+    pub(crate) fn handle_tandy_data_write(&mut self, byte: u8) {
+        let tandy = match &mut self.tandy {
+            Some(tandy) => tandy,
+            None => return,
+        };
+
+        match tandy.address {
+            TANDY_REG_PALETTE_MASK => tandy.palette_mask = byte & 0x0F,
+            TANDY_REG_BORDER_COLOR => {
+                tandy.border_color = byte & 0x0F;
+                self.cc_overscan_color = tandy.border_color;
+            }
+            TANDY_REG_MODE_CONTROL => {
+                tandy.mode_control = byte;
+                self.update_mode();
+            }
+            TANDY_REG_PALETTE_BASE..=TANDY_REG_PALETTE_END => {
+                tandy.palette[(tandy.address - TANDY_REG_PALETTE_BASE) as usize] = byte & 0x0F;
+            }
+            _ => {
+                log::trace!("Tandy: Write to unhandled gate array register {:02X}", tandy.address);
+            }
+        }
+    }
+
+    pub(crate) fn handle_tandy_page_write(&mut self, byte: u8) {
+        if let Some(tandy) = &mut self.tandy {
+            tandy.page = byte;
+        }
+    }
+
+    /// Return true if the gate array has enabled a 16-color graphics mode.
+    #[inline]
+    pub(crate) fn tandy_16color_enabled(&self) -> bool {
+        self.tandy
+            .as_ref()
+            .is_some_and(|tandy| tandy.mode_control & TANDY_MODE_16COLOR != 0)
+    }
+
+    /// Calculate the byte address of the current character in the 16-color graphics modes.
+    #[inline]
+    pub fn get_tandy_gfx_addr(&self, row: u8) -> usize {
+        match &self.tandy {
+            Some(tandy) if tandy.page & TANDY_PAGE_ADDRESS_MODE == TANDY_PAGE_ADDRESS_MODE => {
+                ((row as usize & 0x03) << 13) | ((self.vma & 0x0FFF) << 1)
+            }
+            _ => self.get_gfx_addr(row),
+        }
+    }
+
+    /// Return the color of the specified pixel (0-3) of the current character in the 16-color
+    /// graphics modes. Each byte holds two pixels, high nibble first.
+    #[inline]
+    pub fn get_tandy_pixel_color(&self, row: u8, pixel: u8) -> u8 {
+        let base_addr = self.get_tandy_gfx_addr(row);
+        let byte = self.mem[base_addr + ((pixel & 0x03) as usize >> 1)];
+        let index = if pixel & 0x01 == 0 { byte >> 4 } else { byte & 0x0F };
+
+        match &self.tandy {
+            Some(tandy) => tandy.palette[(index & tandy.palette_mask) as usize],
+            None => index,
+        }
+    }
+
+    /// Draw a pixel in a 16-color graphics mode. A character spans four pixels in both the
+    /// 160 and 320 column modes, and the pixel clock ticks CGA_HCHAR_CLOCK times per character
+    /// whatever the character clock, so each pixel spans an equal share of those ticks.
+    pub fn draw_tandy_gfx_mode_pixel(&mut self) {
+        let color = if self.mode_enable {
+            let pixel = self.char_col / (CGA_HCHAR_CLOCK / 4);
+            self.get_tandy_pixel_color(self.vlc_c9, pixel)
+        }
+        else {
+            self.cc_overscan_color
+        };
+        self.draw_pixel(color);
+    }
+
+    /// Draw a full character in a 16-color graphics mode. Pixels are two dots wide in 320x200
+    /// (high resolution clock) and four dots wide in 160x200 (low resolution clock).
+    pub fn draw_tandy_gfx_mode_char(&mut self) {
+        if !self.mode_enable {
+            self.draw_solid_char(self.cc_overscan_color);
+            return;
+        }
+
+        let pixel_w = self.char_clock as usize / 4;
+        let colors: [u8; 4] = std::array::from_fn(|i| self.get_tandy_pixel_color(self.vlc_c9, i as u8));
+
+        let frame = &mut self.buf[self.back_buf];
+        for (i, color) in colors.iter().enumerate() {
+            let start = self.rba + i * pixel_w;
+            frame[start..start + pixel_w].fill(*color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::MemoryMappedDevice;
+
+    fn tandy_card() -> CGACard {
+        let mut cga = CGACard::new(TraceLogger::None, ClockingMode::Dynamic, false);
+        cga.enable_tandy_extensions();
+        cga
+    }
+
+    fn set_tandy_mode(cga: &mut CGACard, mode_byte: u8) {
+        cga.mode_byte = mode_byte;
+        cga.update_mode();
+        cga.handle_tandy_address_write(TANDY_REG_MODE_CONTROL);
+        cga.handle_tandy_data_write(TANDY_MODE_16COLOR);
+    }
+
+    #[test]
+    fn only_tandy_allocates_32k_of_vram() {
+        let mut cga = CGACard::new(TraceLogger::None, ClockingMode::Dynamic, false);
+        assert_eq!(cga.mem.len(), CGA_MEM_SIZE);
+
+        cga.enable_tandy_extensions();
+        assert_eq!(cga.mem.len(), TANDY_MEM_SIZE);
+        assert_eq!(cga.vram_size(), TANDY_MEM_SIZE);
+
+        // The whole aperture is backed by VRAM instead of mirroring the first 16K.
+        cga.mmio_write_u8(CGA_MEM_ADDRESS + 0x4000, 0x5A, 0);
+        assert_eq!(cga.mmio_peek_u8(CGA_MEM_ADDRESS + 0x4000), 0x5A);
+        assert_eq!(cga.mmio_peek_u8(CGA_MEM_ADDRESS), 0x00);
+
+        cga.reset_private();
+        assert_eq!(cga.mem.len(), TANDY_MEM_SIZE);
+        assert!(cga.is_tandy());
+    }
+
+    #[test]
+    fn sixteen_color_modes_report_tandy_display_modes() {
+        let mut cga = tandy_card();
+
+        // The high resolution clock selects 320x200, the low resolution clock 160x200.
+        set_tandy_mode(&mut cga, 0b0_1011);
+        assert!(cga.mode_tandy16);
+        assert_eq!(cga.display_mode, DisplayMode::Mode9TandyMedResGraphics);
+
+        set_tandy_mode(&mut cga, 0b0_1010);
+        assert_eq!(cga.display_mode, DisplayMode::Mode8TandyLowResGraphics);
+
+        // Without the gate array's 16-color bit, the CGA modes are unchanged.
+        cga.handle_tandy_data_write(0);
+        assert!(!cga.mode_tandy16);
+        assert_eq!(cga.display_mode, DisplayMode::Mode4LowResGraphics);
+    }
+
+    #[test]
+    fn pixels_span_the_character_and_use_the_palette() {
+        let mut cga = tandy_card();
+        set_tandy_mode(&mut cga, 0b0_1011);
+        cga.mem[0] = 0x12;
+        cga.mem[1] = 0x34;
+        cga.handle_tandy_address_write(TANDY_REG_PALETTE_BASE + 2);
+        cga.handle_tandy_data_write(0x0A);
+
+        // Draw a character one pixel clock at a time, the way the pixel-accurate path does.
+        cga.clock_divisor = 1;
+        for col in 0..CGA_HCHAR_CLOCK {
+            cga.char_col = col;
+            cga.rba = col as usize;
+            cga.draw_tandy_gfx_mode_pixel();
+        }
+        let pixels = cga.buf[cga.back_buf][..CGA_HCHAR_CLOCK as usize].to_vec();
+        assert_eq!(pixels, [0x01, 0x01, 0x0A, 0x0A, 0x03, 0x03, 0x04, 0x04]);
+
+        // The character path draws the same pixels.
+        cga.buf[cga.back_buf][..CGA_HCHAR_CLOCK as usize].fill(0);
+        cga.rba = 0;
+        cga.char_clock = CGA_HCHAR_CLOCK as u32;
+        cga.draw_tandy_gfx_mode_char();
+        assert_eq!(cga.buf[cga.back_buf][..CGA_HCHAR_CLOCK as usize], pixels[..]);
+    }
+
+    #[test]
+    fn interleaved_address_mode_spans_four_banks() {
+        let mut cga = tandy_card();
+        cga.vma = 0x10;
+        assert_eq!(cga.get_tandy_gfx_addr(3), cga.get_gfx_addr(3));
+
+        cga.handle_tandy_page_write(TANDY_PAGE_ADDRESS_MODE);
+        assert_eq!(cga.get_tandy_gfx_addr(3), 0x6000 | 0x20);
+    }
+}
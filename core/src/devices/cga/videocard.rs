@@ -78,6 +78,10 @@ impl VideoCard for CGACard {
                 log::debug!("VideoOption::DebugDraw set to: {}", state);
                 self.debug_draw = state;
             }
+            VideoOption::EnableBlink(state) => {
+                log::debug!("VideoOption::EnableBlink set to: {}", state);
+                self.set_blink_enabled(state);
+            }
         }
     }
 
@@ -136,6 +140,11 @@ impl VideoCard for CGACard {
         Some((self.beam_x, self.beam_y))
     }
 
+    fn set_lightpen(&mut self, pos: Option<(u32, u32)>, switch: bool) {
+        self.lightpen_pos = pos;
+        self.lightpen_switch = switch;
+    }
+
     /// Tick the CGA the specified number of video clock cycles.
     fn debug_tick(&mut self, ticks: u32) {
         match self.clock_mode {
@@ -493,11 +502,7 @@ impl VideoCard for CGACard {
                     */
 
                     // Handle blinking. TODO: Move blink handling into tick().
-                    self.blink_accum_clocks += self.char_clock;
-                    if self.blink_accum_clocks > CGA_CURSOR_BLINK_RATE_CLOCKS {
-                        self.blink_state = !self.blink_state;
-                        self.blink_accum_clocks -= CGA_CURSOR_BLINK_RATE_CLOCKS;
-                    }
+                    self.tick_blink(self.char_clock);
 
                     // Char clock may update after tick_char() with deferred mode change, so save the
                     // current clock.
@@ -523,11 +528,7 @@ impl VideoCard for CGACard {
             ClockingMode::Cycle => {
                 while self.clocks_accum > 0 {
                     // Handle blinking. TODO: Move blink handling into tick().
-                    self.blink_accum_clocks += 1;
-                    if self.blink_accum_clocks > CGA_CURSOR_BLINK_RATE_CLOCKS {
-                        self.blink_state = !self.blink_state;
-                        self.blink_accum_clocks -= CGA_CURSOR_BLINK_RATE_CLOCKS;
-                    }
+                    self.tick_blink(1);
 
                     self.tick();
                     self.clocks_accum = self.clocks_accum.saturating_sub(1);
@@ -568,7 +569,7 @@ impl VideoCard for CGACard {
         let mut filename = path.to_path_buf();
         filename.push("cga_mem.bin");
 
-        match std::fs::write(filename.clone(), &*self.mem) {
+        match std::fs::write(filename.clone(), &self.mem[..self.vram_size()]) {
             Ok(_) => {
                 log::debug!("Wrote memory dump: {}", filename.display())
             }
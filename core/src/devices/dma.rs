@@ -30,7 +30,10 @@
 
 */
 
-use crate::bus::{BusInterface, DeviceRunTimeUnit, IoDevice};
+use crate::{
+    bus::{BusInterface, DeviceRunTimeUnit, IoDevice},
+    tracelogger::TraceLogger,
+};
 
 pub const DMA_CHANNEL_0_ADDR_PORT: u16 = 0x00; // R/W
 pub const DMA_CHANNEL_0_WC_PORT: u16 = 0x01; // R/W
@@ -176,6 +179,8 @@ pub struct DMAController {
     temp_reg: u8,
 
     dreq: bool,
+
+    trace_logger: TraceLogger,
 }
 
 impl IoDevice for DMAController {
@@ -203,6 +208,10 @@ impl IoDevice for DMAController {
     }
 
     fn write_u8(&mut self, port: u16, data: u8, _bus: Option<&mut BusInterface>, _delta: DeviceRunTimeUnit) {
+        if self.trace_logger.is_some() {
+            self.trace_logger
+                .println(format!("DMA: OUT {:04X} <- {:02X}", port, data));
+        }
         match port {
             DMA_CHANNEL_0_ADDR_PORT => {
                 self.handle_addr_port_write(0, data);
@@ -321,9 +330,15 @@ impl DMAController {
             temp_reg: 0,
 
             dreq: false,
+
+            trace_logger: TraceLogger::None,
         }
     }
 
+    pub fn set_trace_logger(&mut self, trace_logger: TraceLogger) {
+        self.trace_logger = trace_logger;
+    }
+
     /// Reset the DMA controller
     pub fn reset(&mut self) {
         // TODO:
@@ -804,6 +804,7 @@ impl EGACard {
             row_stride: EGA16_MAX_RASTER_X as usize,
             double_scan: false,
             mode_byte: 0,
+            border_color: 0,
         }
     }
 
@@ -48,6 +48,9 @@ impl VideoCard for EGACard {
                 log::debug!("VideoOption::DebugDraw set to: {}", state);
                 self.debug_draw = state;
             }
+            VideoOption::EnableBlink(_state) => {
+                log::warn!("VideoOption::EnableBlink not supported for EGA");
+            }
         }
     }
 
@@ -105,6 +108,9 @@ impl VideoCard for EGACard {
         Some((self.raster_x, self.raster_y))
     }
 
+    /// Unimplemented
+    fn set_lightpen(&mut self, _pos: Option<(u32, u32)>, _switch: bool) {}
+
     /// Unimplemented
     fn debug_tick(&mut self, _ticks: u32) {}
 
@@ -0,0 +1,253 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    devices::ems.rs
+
+    Implements a LIM EMS expanded memory board with a device-level register
+    interface, modelled after the Lo-tech 2MB EMS board.
+
+    The board provides a 64KB page frame divided into four 16KB physical
+    pages. Each physical page has a corresponding page register in IO space
+    at io_base + n. Writing a logical page number to a register maps that
+    16KB page of the expanded memory pool into the physical page's window.
+    Logical page numbers beyond the size of the pool leave the window
+    unmapped, reading as open bus. Reading a page register returns the
+    logical page mapped into its window, or 0xFF if the window is unmapped.
+
+    Like the original hardware, the board does not implement INT 67h itself.
+    A LIM 4.0 driver such as LTEMM.EXE must be loaded in CONFIG.SYS to
+    provide the EMM interface to DOS and applications.
+*/
+
+use crate::bus::{BusInterface, DeviceRunTimeUnit, IoDevice, MemoryMappedDevice, NO_IO_BYTE};
+
+pub const EMS_DEFAULT_IO_BASE: u16 = 0x260;
+pub const EMS_DEFAULT_SEGMENT: u16 = 0xE000;
+pub const EMS_PAGE_SIZE: usize = 0x4000;
+pub const EMS_PHYSICAL_PAGES: usize = 4;
+pub const EMS_PAGE_FRAME_SIZE: usize = EMS_PAGE_SIZE * EMS_PHYSICAL_PAGES;
+/// Page registers are 8 bits wide, limiting the pool to 256 logical pages (4MB).
+pub const EMS_MAX_PAGES: usize = 256;
+
+const EMS_OPEN_BUS_BYTE: u8 = 0xFF;
+
+pub struct EmsCard {
+    io_base: u16,
+    frame_address: usize,
+    mapped_pages: [Option<usize>; EMS_PHYSICAL_PAGES],
+    pool: Vec<u8>,
+}
+
+impl EmsCard {
+    /// Create a new EMS board with a pool of `size_kb` kilobytes. The size is rounded down to a
+    /// whole number of 16KB pages and clamped to what the page registers can address.
+    pub fn new(size_kb: u32, io_base: Option<u16>, segment: Option<u16>) -> Self {
+        let mut page_ct = (size_kb as usize * 1024) / EMS_PAGE_SIZE;
+        if page_ct > EMS_MAX_PAGES {
+            log::warn!(
+                "EMS size of {}KB exceeds maximum of {}KB. Clamping.",
+                size_kb,
+                EMS_MAX_PAGES * EMS_PAGE_SIZE / 1024
+            );
+            page_ct = EMS_MAX_PAGES;
+        }
+
+        let segment = segment.unwrap_or(EMS_DEFAULT_SEGMENT);
+        Self {
+            io_base: io_base.unwrap_or(EMS_DEFAULT_IO_BASE),
+            frame_address: (segment as usize) << 4,
+            mapped_pages: [None; EMS_PHYSICAL_PAGES],
+            pool: vec![0; page_ct * EMS_PAGE_SIZE],
+        }
+    }
+
+    /// Unmap all physical pages. The contents of the expanded memory pool survive a reset, as
+    /// they would on real hardware.
+    pub fn reset(&mut self) {
+        self.mapped_pages = [None; EMS_PHYSICAL_PAGES];
+    }
+
+    /// Return the physical address of the start of the page frame.
+    pub fn frame_address(&self) -> usize {
+        self.frame_address
+    }
+
+    /// Return the number of 16KB logical pages in the expanded memory pool.
+    pub fn page_count(&self) -> usize {
+        self.pool.len() / EMS_PAGE_SIZE
+    }
+
+    /// Return the logical page currently mapped into the specified physical page, if any.
+    pub fn mapped_page(&self, physical_page: usize) -> Option<usize> {
+        self.mapped_pages.get(physical_page).copied().flatten()
+    }
+
+    /// Map a logical page into a physical page of the page frame. A logical page outside the
+    /// expanded memory pool unmaps the physical page.
+    pub fn map_page(&mut self, physical_page: usize, logical_page: u8) {
+        if physical_page >= EMS_PHYSICAL_PAGES {
+            return;
+        }
+        self.mapped_pages[physical_page] = if (logical_page as usize) < self.page_count() {
+            Some(logical_page as usize)
+        }
+        else {
+            None
+        };
+        log::trace!(
+            "EMS: physical page {} -> logical page {:?}",
+            physical_page,
+            self.mapped_pages[physical_page]
+        );
+    }
+
+    /// Translate an address within the page frame to an offset into the expanded memory pool.
+    #[inline]
+    fn pool_offset(&self, address: usize) -> Option<usize> {
+        let frame_offset = address.wrapping_sub(self.frame_address);
+        if frame_offset >= EMS_PAGE_FRAME_SIZE {
+            return None;
+        }
+        self.mapped_pages[frame_offset / EMS_PAGE_SIZE]
+            .map(|page| page * EMS_PAGE_SIZE + (frame_offset % EMS_PAGE_SIZE))
+    }
+}
+
+impl IoDevice for EmsCard {
+    fn read_u8(&mut self, port: u16, _delta: DeviceRunTimeUnit) -> u8 {
+        let reg = port.wrapping_sub(self.io_base) as usize;
+        match self.mapped_pages.get(reg) {
+            Some(Some(page)) => *page as u8,
+            _ => NO_IO_BYTE,
+        }
+    }
+
+    fn write_u8(&mut self, port: u16, data: u8, _bus: Option<&mut BusInterface>, _delta: DeviceRunTimeUnit) {
+        let reg = port.wrapping_sub(self.io_base) as usize;
+        if reg < EMS_PHYSICAL_PAGES {
+            self.map_page(reg, data);
+        }
+    }
+
+    fn port_list(&self) -> Vec<u16> {
+        (0..EMS_PHYSICAL_PAGES as u16).map(|p| self.io_base + p).collect()
+    }
+}
+
+impl MemoryMappedDevice for EmsCard {
+    fn get_read_wait(&mut self, _address: usize, _cycles: u32) -> u32 {
+        0
+    }
+
+    fn mmio_read_u8(&mut self, address: usize, _cycles: u32) -> (u8, u32) {
+        (self.mmio_peek_u8(address), 0)
+    }
+
+    fn mmio_read_u16(&mut self, address: usize, _cycles: u32) -> (u16, u32) {
+        (self.mmio_peek_u16(address), 0)
+    }
+
+    fn mmio_peek_u8(&self, address: usize) -> u8 {
+        match self.pool_offset(address) {
+            Some(offset) => self.pool[offset],
+            None => EMS_OPEN_BUS_BYTE,
+        }
+    }
+
+    fn mmio_peek_u16(&self, address: usize) -> u16 {
+        // Words may straddle two physical pages, so resolve each byte separately.
+        self.mmio_peek_u8(address) as u16 | (self.mmio_peek_u8(address + 1) as u16) << 8
+    }
+
+    fn get_write_wait(&mut self, _address: usize, _cycles: u32) -> u32 {
+        0
+    }
+
+    fn mmio_write_u8(&mut self, address: usize, data: u8, _cycles: u32) -> u32 {
+        if let Some(offset) = self.pool_offset(address) {
+            self.pool[offset] = data;
+        }
+        0
+    }
+
+    fn mmio_write_u16(&mut self, address: usize, data: u16, cycles: u32) -> u32 {
+        self.mmio_write_u8(address, (data & 0xFF) as u8, cycles);
+        self.mmio_write_u8(address + 1, (data >> 8) as u8, 0);
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: usize = (EMS_DEFAULT_SEGMENT as usize) << 4;
+
+    #[test]
+    fn page_registers_redirect_frame_accesses() {
+        let mut ems = EmsCard::new(256, None, None);
+        assert_eq!(ems.page_count(), 16);
+
+        // Physical page 0 -> logical page 3, physical page 1 -> logical page 4.
+        ems.write_u8(EMS_DEFAULT_IO_BASE, 3, None, DeviceRunTimeUnit::SystemTicks(0));
+        ems.write_u8(EMS_DEFAULT_IO_BASE + 1, 4, None, DeviceRunTimeUnit::SystemTicks(0));
+        assert_eq!(ems.read_u8(EMS_DEFAULT_IO_BASE, DeviceRunTimeUnit::SystemTicks(0)), 3);
+
+        // A word straddling the two windows lands in two different logical pages.
+        ems.mmio_write_u16(FRAME + EMS_PAGE_SIZE - 1, 0xBEEF, 0);
+        assert_eq!(ems.pool[3 * EMS_PAGE_SIZE + EMS_PAGE_SIZE - 1], 0xEF);
+        assert_eq!(ems.pool[4 * EMS_PAGE_SIZE], 0xBE);
+
+        // Mapping logical page 3 into another window makes the same data visible there.
+        ems.map_page(2, 3);
+        assert_eq!(ems.mmio_peek_u8(FRAME + 3 * EMS_PAGE_SIZE - 1), 0xEF);
+
+        // A logical page past the end of the pool unmaps the window.
+        ems.map_page(0, 16);
+        assert_eq!(ems.mapped_page(0), None);
+        assert_eq!(ems.mmio_peek_u8(FRAME + EMS_PAGE_SIZE - 1), EMS_OPEN_BUS_BYTE);
+    }
+
+    #[test]
+    fn reset_unmaps_pages_and_keeps_pool() {
+        let mut ems = EmsCard::new(256, None, None);
+        ems.map_page(0, 5);
+        ems.mmio_write_u8(FRAME, 0x42, 0);
+
+        ems.reset();
+        for page in 0..EMS_PHYSICAL_PAGES {
+            assert_eq!(ems.mapped_page(page), None);
+            assert_eq!(
+                ems.read_u8(EMS_DEFAULT_IO_BASE + page as u16, DeviceRunTimeUnit::SystemTicks(0)),
+                NO_IO_BYTE
+            );
+        }
+        assert_eq!(ems.mmio_peek_u8(FRAME), EMS_OPEN_BUS_BYTE);
+
+        ems.map_page(0, 5);
+        assert_eq!(ems.mmio_peek_u8(FRAME), 0x42);
+    }
+}
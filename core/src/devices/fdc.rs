@@ -35,8 +35,9 @@ use std::{collections::VecDeque, default::Default};
 
 use crate::{
     bus::{BusInterface, DeviceRunTimeUnit, IoDevice},
-    device_types::{chs::DiskChs, fdc::DISK_FORMATS},
+    device_types::{chs::DiskChs, fdc::DISK_FORMATS, media::MediaInfo},
     devices::{dma, floppy_drive::FloppyDiskDrive},
+    tracelogger::TraceLogger,
 };
 
 pub const FDC_IRQ: u8 = 0x06;
@@ -45,6 +46,10 @@ pub const FDC_MAX_DRIVES: usize = 4;
 pub const FORMAT_BUFFER_SIZE: usize = 4;
 pub const SECTOR_SIZE: usize = 512;
 
+// How long a drive's activity flag stays latched after a read, write or format, so that brief
+// accesses are visible to a frontend polling once per frame.
+pub const FDC_ACTIVITY_LATCH_US: f64 = 100_000.0;
+
 pub const FDC_DIGITAL_OUTPUT_REGISTER: u16 = 0x3F2;
 pub const FDC_STATUS_REGISTER: u16 = 0x3F4;
 pub const FDC_DATA_REGISTER: u16 = 0x3F5;
@@ -179,6 +184,17 @@ pub enum DriveError {
     DMAError,
 }
 
+/// A snapshot of a drive's state for display, such as a drive activity indicator.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FloppyDriveStatus {
+    pub selected: bool,
+    pub motor_on: bool,
+    pub have_disk: bool,
+    pub cylinder: u8,
+    pub head: u8,
+    pub activity: bool,
+}
+
 pub struct OperationSpecifier {
     pub chs: DiskChs,
     pub sector_size: u8,
@@ -237,6 +253,8 @@ pub struct FloppyController {
     drives: [FloppyDiskDrive; 4],
     drive_ct: usize,
     drive_select: usize,
+    activity_us: [f64; FDC_MAX_DRIVES],
+    boot_hold: bool,
 
     in_dma: bool,
     dma_byte_count: usize,
@@ -244,6 +262,8 @@ pub struct FloppyController {
     xfer_size_sectors: u32,
     xfer_size_bytes: usize,
     xfer_completed_sectors: u32,
+
+    trace_logger: TraceLogger,
 }
 
 /// IO Port handlers for the FDC
@@ -319,6 +339,8 @@ impl Default for FloppyController {
             ],
             drive_ct: 0,
             drive_select: 0,
+            activity_us: [0.0; FDC_MAX_DRIVES],
+            boot_hold: false,
 
             in_dma: false,
             dma_byte_count: 0,
@@ -326,6 +348,8 @@ impl Default for FloppyController {
             xfer_size_sectors: 0,
             xfer_size_bytes: 0,
             xfer_completed_sectors: 0,
+
+            trace_logger: TraceLogger::None,
         }
     }
 }
@@ -338,6 +362,10 @@ impl FloppyController {
         }
     }
 
+    pub fn set_trace_logger(&mut self, trace_logger: TraceLogger) {
+        self.trace_logger = trace_logger;
+    }
+
     /// Reset the Floppy Drive Controller
     pub fn reset(&mut self) {
         self.status_byte = 0;
@@ -372,6 +400,38 @@ impl FloppyController {
         self.dma_bytes_left = 0;
     }
 
+    /// Hold all drives as empty to read commands, so that the BIOS will skip booting from floppy.
+    pub fn set_boot_hold(&mut self, state: bool) {
+        self.boot_hold = state;
+    }
+
+    pub fn boot_hold(&self) -> bool {
+        self.boot_hold
+    }
+
+    /// Return the currently selected drive.
+    pub fn drive_select(&self) -> usize {
+        self.drive_select
+    }
+
+    /// Return the status of the specified drive.
+    pub fn drive_status(&self, drive_select: usize) -> FloppyDriveStatus {
+        let drive = &self.drives[drive_select];
+        FloppyDriveStatus {
+            selected: drive_select == self.drive_select,
+            motor_on: drive.motor_on,
+            have_disk: drive.have_disk,
+            cylinder: drive.chs.c(),
+            head: drive.chs.h(),
+            activity: self.activity_us[drive_select] > 0.0,
+        }
+    }
+
+    /// Return the status of all installed drives.
+    pub fn drive_status_all(&self) -> Vec<FloppyDriveStatus> {
+        (0..self.drive_ct).map(|d| self.drive_status(d)).collect()
+    }
+
     pub fn drive_ct(&self) -> usize {
         self.drive_ct
     }
@@ -389,6 +449,11 @@ impl FloppyController {
 
         let image_len: usize = src_vec.len();
 
+        // An empty file is not a blank disk
+        if image_len == 0 {
+            return Err("Image is empty");
+        }
+
         // Disk images must contain whole sectors
         if image_len % SECTOR_SIZE > 0 {
             return Err("Invalid image length");
@@ -417,6 +482,8 @@ impl FloppyController {
 
         self.drives[drive_select].have_disk = true;
         self.drives[drive_select].disk_image = src_vec;
+        self.drives[drive_select].image_name = None;
+        self.drives[drive_select].dirty = false;
         log::debug!(
             "Loaded floppy image, drive: {} size: {} c: {} h: {} s: {}",
             drive_select,
@@ -441,6 +508,11 @@ impl FloppyController {
         }
     }
 
+    /// Return whether a disk image is loaded in the specified drive.
+    pub fn is_disk_loaded(&self, drive_select: usize) -> bool {
+        self.drives.get(drive_select).is_some_and(|drive| drive.have_disk)
+    }
+
     /// Unload (eject) the disk in the specified drive
     pub fn unload_image(&mut self, drive_select: usize) {
         let drive = &mut self.drives[drive_select];
@@ -453,6 +525,37 @@ impl FloppyController {
         drive.max_sectors = 8;
         drive.have_disk = false;
         drive.disk_image.clear();
+        drive.image_name = None;
+        drive.dirty = false;
+    }
+
+    /// Set the name of the image in the specified drive, for display. The name is cleared when
+    /// another image is loaded or the disk is ejected.
+    pub fn set_image_name(&mut self, drive_select: usize, name: Option<String>) {
+        if let Some(drive) = self.drives.get_mut(drive_select) {
+            drive.image_name = name;
+        }
+    }
+
+    /// Clear the modified flag of the specified drive, such as after its image has been saved.
+    pub fn mark_clean(&mut self, drive_select: usize) {
+        if let Some(drive) = self.drives.get_mut(drive_select) {
+            drive.dirty = false;
+        }
+    }
+
+    /// Return a description of the media in the specified drive, or None if the drive is empty.
+    pub fn media_info(&self, drive_select: usize) -> Option<MediaInfo> {
+        let drive = self.drives.get(drive_select).filter(|drive| drive.have_disk)?;
+        Some(MediaInfo {
+            name: drive.image_name.clone(),
+            cylinders: drive.max_cylinders as u16,
+            heads: drive.max_heads,
+            sectors: drive.max_sectors,
+            size: drive.disk_image.len() as u64,
+            write_protected: drive.write_protected,
+            dirty: drive.dirty,
+        })
     }
 
     pub fn handle_status_register_read(&mut self) -> u8 {
@@ -783,7 +886,10 @@ impl FloppyController {
                 self.command_byte_n -= 1;
                 if self.command_byte_n == 0 {
                     // We read last byte expected for this command, so dispatch to the appropriate command handler
-                    // We read last byte expected for this command, so dispatch to the appropriate command handler
+                    if self.trace_logger.is_some() {
+                        self.trace_logger
+                            .println(format!("FDC: {:?} {:02X?}", self.command, self.data_register_in));
+                    }
                     let mut result = Continuation::CommandComplete;
 
                     match self.command_fn {
@@ -797,6 +903,12 @@ impl FloppyController {
 
                     // Clear command if complete
                     if let Continuation::CommandComplete = result {
+                        if self.trace_logger.is_some() {
+                            self.trace_logger.println(format!(
+                                "FDC: {:?} complete, result: {:02X?}",
+                                self.command, self.data_register_out
+                            ));
+                        }
                         self.last_command = self.command;
                         self.command = Command::NoCommand;
                         self.command_fn = None;
@@ -1019,7 +1131,8 @@ impl FloppyController {
         // listings, or produce a "General error" reading drive instead of "Not Ready".
         // Also, returning error codes would cause the BIOS to issue an error 601.
         // So, we just let this operation time out if no disk is present, and that seems to work.
-        if !self.drives[drive_select].have_disk {
+        // Drives on boot hold look empty so that the BIOS will boot from the hard disk instead.
+        if !self.drives[drive_select].have_disk || self.boot_hold {
             return Continuation::CommandComplete;
         }
 
@@ -1140,9 +1253,12 @@ impl FloppyController {
         let gap3_len = self.data_register_in.pop_front().unwrap();
         let fill_byte = self.data_register_in.pop_front().unwrap();
 
-        let _drive_select = (drive_head_select & 0x03) as usize;
+        let drive_select = (drive_head_select & 0x03) as usize;
         let _head_select = (drive_head_select >> 2) & 0x01;
 
+        // Set drive_select for status register reads and the format operation
+        self.drive_select = drive_select;
+
         // Start format operation
         self.operation_init = false;
         self.operation = Operation::FormatTrack(sector_size, track_len, gap3_len, fill_byte);
@@ -1302,6 +1418,7 @@ impl FloppyController {
 
         if self.dma_bytes_left > 0 {
             // Bytes left to transfer
+            dma.request_service(FDC_DMA);
 
             // Calculate how many sectors we've done
             if (self.dma_bytes_left < self.xfer_size_bytes) && (self.dma_bytes_left % SECTOR_SIZE == 0) {
@@ -1350,6 +1467,7 @@ impl FloppyController {
         else {
             // No more bytes left to transfer. Finalize operation
 
+            dma.clear_service(FDC_DMA);
             let tc = dma.check_terminal_count(FDC_DMA);
             if !tc {
                 log::warn!("FDC sector read complete without DMA terminal count.");
@@ -1438,6 +1556,7 @@ impl FloppyController {
 
         if self.dma_bytes_left > 0 {
             // Bytes left to transfer
+            dma.request_service(FDC_DMA);
 
             // Check if DMA is ready
             if dma.check_dma_ready(FDC_DMA) {
@@ -1457,6 +1576,7 @@ impl FloppyController {
                 else {
                     let byte = dma.do_dma_read_u8(bus, FDC_DMA);
                     self.drives[self.drive_select].disk_image[byte_address] = byte;
+                    self.drives[self.drive_select].dirty = true;
                     self.dma_byte_count += 1;
                     self.dma_bytes_left -= 1;
 
@@ -1475,6 +1595,7 @@ impl FloppyController {
         else {
             // No more bytes left to transfer. Finalize operation
 
+            dma.clear_service(FDC_DMA);
             let tc = dma.check_terminal_count(FDC_DMA);
             if !tc {
                 log::warn!("FDC sector write complete without DMA terminal count.");
@@ -1564,17 +1685,28 @@ impl FloppyController {
             log::trace!("Format Track: DMA programmed for transfer of {} sectors", xfer_sectors);
 
             self.dma_bytes_left = track_len as usize * FORMAT_BUFFER_SIZE;
+            self.format_buffer.clear();
             self.operation_init = true;
         }
 
         if self.dma_bytes_left > 0 {
             // Bytes left to transfer
+            dma.request_service(FDC_DMA);
 
             // Check if DMA is ready
             if dma.check_dma_ready(FDC_DMA) {
                 let byte = dma.do_dma_read_u8(bus, FDC_DMA);
                 self.format_buffer.push_back(byte);
                 self.dma_bytes_left = self.dma_bytes_left.saturating_sub(1);
+
+                // A terminal count ends the format early, just like a sector read or write.
+                if dma.check_terminal_count(FDC_DMA) && self.dma_bytes_left > 0 {
+                    log::warn!(
+                        "DMA terminal count triggered end of Format Track operation, {} byte(s) left.",
+                        self.dma_bytes_left
+                    );
+                    self.dma_bytes_left = 0;
+                }
             }
 
             // Have we read in all 4 bytes of a format buffer? Format the sector specified by the buffer.
@@ -1594,19 +1726,17 @@ impl FloppyController {
                 );
 
                 self.format_sector(f_cylinder, f_head, f_sector, fill_byte);
-                self.send_interrupt = true;
 
                 // Clear for next 4 bytes
                 self.format_buffer.clear();
             }
         }
         else {
-            // No more bytes left to transfer. Finalize operation
-
-            //let tc = dma.check_terminal_count(FDC_DMA);
-            //if !tc {
-            //    log::warn!("FDC Format Track complete without DMA terminal count.");
-            //}
+            // No more bytes left to transfer. Finalize operation.
+            // The FDC stops after formatting track_len sectors, so a terminal count is not expected here:
+            // DOS programs the DMA controller for the entire track length.
+            dma.clear_service(FDC_DMA);
+            self.format_buffer.clear();
 
             self.dma_byte_count = 0;
             self.dma_bytes_left = 0;
@@ -1635,10 +1765,39 @@ impl FloppyController {
         }
     }
 
-    pub fn format_sector(&mut self, _cylinder: u8, _head: u8, _sector: u8, _fill_byte: u8) {}
+    /// Format the specified sector of the selected drive, filling it with fill_byte.
+    pub fn format_sector(&mut self, cylinder: u8, head: u8, sector: u8, fill_byte: u8) {
+        let drive_select = self.drive_select;
+
+        if sector == 0 || !self.is_id_valid(drive_select, cylinder, head, sector) {
+            log::warn!(
+                "format_sector: invalid chs: drive:{}, c:{} h:{} s:{}",
+                drive_select,
+                cylinder,
+                head,
+                sector
+            );
+            return;
+        }
+
+        let base_address = self.get_image_address(drive_select, cylinder, head, sector);
+        let drive = &mut self.drives[drive_select];
+        if base_address + SECTOR_SIZE <= drive.disk_image.len() {
+            drive.disk_image[base_address..base_address + SECTOR_SIZE].fill(fill_byte);
+            drive.dirty = true;
+        }
+    }
 
     /// Run the Floppy Drive Controller. Process running Operations.
-    pub fn run(&mut self, dma: &mut dma::DMAController, bus: &mut BusInterface, _us: f64) {
+    pub fn run(&mut self, dma: &mut dma::DMAController, bus: &mut BusInterface, us: f64) {
+        // Expire drive activity
+        for activity in self.activity_us.iter_mut() {
+            *activity = (*activity - us).max(0.0);
+        }
+        if !matches!(self.operation, Operation::NoOperation) {
+            self.activity_us[self.drive_select] = FDC_ACTIVITY_LATCH_US;
+        }
+
         // Send an interrupt if one is queued
         if self.send_interrupt {
             bus.pic_mut().as_mut().unwrap().request_interrupt(FDC_IRQ);
@@ -51,6 +51,8 @@ pub struct FloppyDiskDrive {
     pub(crate) have_disk: bool,
     pub(crate) write_protected: bool,
     pub(crate) disk_image: Vec<u8>,
+    pub(crate) image_name: Option<String>,
+    pub(crate) dirty: bool,
 }
 
 impl Default for FloppyDiskDrive {
@@ -69,6 +71,8 @@ impl Default for FloppyDiskDrive {
             have_disk: false,
             write_protected: true,
             disk_image: Vec::new(),
+            image_name: None,
+            dirty: false,
         }
     }
 }
@@ -93,6 +97,8 @@ impl FloppyDiskDrive {
             motor_on: false,
             positioning: false,
             disk_image: image,
+            image_name: self.image_name.take(),
+            dirty: self.dirty,
             ..Default::default()
         };
     }
@@ -0,0 +1,248 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    devices::game_port.rs
+
+    Implements the IBM Game Control Adapter, supporting two joysticks with
+    two axes and two buttons each.
+
+    Each axis is read through a one-shot timer driven by the joystick's
+    potentiometer. Writing any value to the port fires all four one-shots,
+    and each axis bit reads 1 until its one-shot expires, after a time
+    proportional to the axis resistance. Software measures an axis position
+    by counting how many times it can read the port before the bit drops.
+
+    Button bits read 0 while the button is pressed.
+
+*/
+
+use crate::bus::{BusInterface, DeviceRunTimeUnit, IoDevice};
+
+pub const GAMEPORT_DEFAULT_IO_BASE: u16 = 0x201;
+pub const GAMEPORT_STICK_COUNT: usize = 2;
+
+// One-shot timing from the Game Control Adapter technical reference:
+// t = 24.2us + 0.011us * R, where R is the joystick resistance from 0 to 100K ohms.
+const ONESHOT_BASE_US: f64 = 24.2;
+const ONESHOT_US_PER_OHM: f64 = 0.011;
+const JOYSTICK_MAX_OHMS: f64 = 100_000.0;
+
+/// The state of one joystick. Axis positions range from -1.0 (left/up) to 1.0 (right/down).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct JoystickState {
+    pub x: f64,
+    pub y: f64,
+    pub button_1: bool,
+    pub button_2: bool,
+}
+
+pub struct GamePort {
+    io_base: u16,
+    sticks: [Option<JoystickState>; GAMEPORT_STICK_COUNT],
+    // Remaining time, in microseconds, for each axis one-shot: A X, A Y, B X, B Y.
+    oneshot_us: [f64; GAMEPORT_STICK_COUNT * 2],
+}
+
+impl GamePort {
+    pub fn new(io_base: Option<u16>) -> Self {
+        Self {
+            io_base: io_base.unwrap_or(GAMEPORT_DEFAULT_IO_BASE),
+            sticks: [None; GAMEPORT_STICK_COUNT],
+            oneshot_us: [0.0; GAMEPORT_STICK_COUNT * 2],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.oneshot_us = [0.0; GAMEPORT_STICK_COUNT * 2];
+    }
+
+    /// Update the state of the specified joystick, connecting it if it was not connected.
+    pub fn set_stick(&mut self, stick: usize, state: JoystickState) {
+        if let Some(slot) = self.sticks.get_mut(stick) {
+            *slot = Some(JoystickState {
+                x: state.x.clamp(-1.0, 1.0),
+                y: state.y.clamp(-1.0, 1.0),
+                ..state
+            });
+        }
+    }
+
+    /// Disconnect the specified joystick. The axis bits of a disconnected joystick never time out,
+    /// which is how software detects that no joystick is present.
+    pub fn disconnect_stick(&mut self, stick: usize) {
+        if let Some(slot) = self.sticks.get_mut(stick) {
+            *slot = None;
+        }
+    }
+
+    pub fn stick(&self, stick: usize) -> Option<JoystickState> {
+        self.sticks.get(stick).copied().flatten()
+    }
+
+    /// Advance the one-shot timers by the specified number of microseconds.
+    pub fn run(&mut self, us: f64) {
+        for remaining in self.oneshot_us.iter_mut() {
+            *remaining = (*remaining - us).max(0.0);
+        }
+    }
+
+    fn axis_duration(position: f64) -> f64 {
+        let ohms = (position + 1.0) / 2.0 * JOYSTICK_MAX_OHMS;
+        ONESHOT_BASE_US + ONESHOT_US_PER_OHM * ohms
+    }
+
+    fn fire_oneshots(&mut self) {
+        for (i, stick) in self.sticks.iter().enumerate() {
+            let (x_us, y_us) = match stick {
+                Some(state) => (Self::axis_duration(state.x), Self::axis_duration(state.y)),
+                None => (f64::INFINITY, f64::INFINITY),
+            };
+            self.oneshot_us[i * 2] = x_us;
+            self.oneshot_us[i * 2 + 1] = y_us;
+        }
+    }
+
+    fn status(&self) -> u8 {
+        let mut byte = 0xF0;
+        for (i, remaining) in self.oneshot_us.iter().enumerate() {
+            if *remaining > 0.0 {
+                byte |= 1 << i;
+            }
+        }
+        for (i, stick) in self.sticks.iter().enumerate() {
+            if let Some(state) = stick {
+                if state.button_1 {
+                    byte &= !(0x10 << (i * 2));
+                }
+                if state.button_2 {
+                    byte &= !(0x20 << (i * 2));
+                }
+            }
+        }
+        byte
+    }
+}
+
+impl IoDevice for GamePort {
+    fn read_u8(&mut self, _port: u16, _delta: DeviceRunTimeUnit) -> u8 {
+        self.status()
+    }
+
+    fn write_u8(&mut self, _port: u16, _data: u8, _bus: Option<&mut BusInterface>, _delta: DeviceRunTimeUnit) {
+        self.fire_oneshots();
+    }
+
+    fn port_list(&self) -> Vec<u16> {
+        vec![self.io_base]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AXIS_BITS: u8 = 0x0F;
+
+    #[test]
+    fn axis_bits_time_out_with_stick_position() {
+        let mut port = GamePort::new(None);
+        port.set_stick(
+            0,
+            JoystickState {
+                x: -1.0,
+                y: 0.0,
+                ..Default::default()
+            },
+        );
+        port.set_stick(
+            1,
+            JoystickState {
+                x: 1.0,
+                y: 2.0,
+                ..Default::default()
+            },
+        );
+        assert_eq!(port.status() & AXIS_BITS, 0);
+
+        // Any write fires the one-shots.
+        port.write_u8(GAMEPORT_DEFAULT_IO_BASE, 0, None, DeviceRunTimeUnit::SystemTicks(0));
+        let status = port.read_u8(GAMEPORT_DEFAULT_IO_BASE, DeviceRunTimeUnit::SystemTicks(0));
+        assert_eq!(status & AXIS_BITS, 0x0F);
+
+        // Stick A X is fully left: 24.2us.
+        port.run(24.0);
+        assert_eq!(port.status() & AXIS_BITS, 0x0F);
+        port.run(0.5);
+        assert_eq!(port.status() & AXIS_BITS, 0x0E);
+
+        // Stick A Y is centered: 24.2us + 0.011us * 50K = 574.2us.
+        port.run(549.0);
+        assert_eq!(port.status() & AXIS_BITS, 0x0E);
+        port.run(1.0);
+        assert_eq!(port.status() & AXIS_BITS, 0x0C);
+
+        // Stick B is fully right and down, as positions are clamped: 1124.2us.
+        port.run(549.0);
+        assert_eq!(port.status() & AXIS_BITS, 0x0C);
+        port.run(1.0);
+        assert_eq!(port.status() & AXIS_BITS, 0x00);
+    }
+
+    #[test]
+    fn disconnected_stick_never_times_out() {
+        let mut port = GamePort::new(None);
+        port.set_stick(0, JoystickState::default());
+        port.write_u8(GAMEPORT_DEFAULT_IO_BASE, 0, None, DeviceRunTimeUnit::SystemTicks(0));
+        port.run(1_000_000.0);
+        assert_eq!(port.status() & AXIS_BITS, 0x0C);
+
+        port.reset();
+        assert_eq!(port.status() & AXIS_BITS, 0x00);
+    }
+
+    #[test]
+    fn pressed_buttons_read_low() {
+        let mut port = GamePort::new(Some(0x209));
+        assert_eq!(port.port_list(), vec![0x209]);
+        assert_eq!(port.status(), 0xF0);
+
+        port.set_stick(
+            0,
+            JoystickState {
+                button_2: true,
+                ..Default::default()
+            },
+        );
+        port.set_stick(
+            1,
+            JoystickState {
+                button_1: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(port.status(), 0xF0 & !0x20 & !0x40);
+    }
+}
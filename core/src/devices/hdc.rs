@@ -41,7 +41,11 @@ use crate::{
     devices::dma,
 };
 //use crate::fdc::Operation;
-use crate::{bus::IoDevice, device_types::hdc::HardDiskFormat, vhd::VirtualHardDisk};
+use crate::{
+    bus::IoDevice,
+    device_types::{hdc::HardDiskFormat, media::MediaInfo},
+    vhd::VirtualHardDisk,
+};
 
 // Public consts
 pub const HDC_IRQ: u8 = 0x05;
@@ -206,6 +210,7 @@ pub struct HardDisk {
     max_sectors: u8,
     sector_buf: Vec<u8>,
     vhd: Option<VirtualHardDisk>,
+    vhd_name: Option<String>,
 }
 
 impl HardDisk {
@@ -219,6 +224,7 @@ impl HardDisk {
             max_sectors: 0,
             sector_buf: vec![0; SECTOR_SIZE],
             vhd: None,
+            vhd_name: None,
         }
     }
 
@@ -382,6 +388,16 @@ impl HardDiskController {
         self.command_byte_n = 0;
     }
 
+    /// Return true if the specified drive has a VHD mounted whose first sector carries a valid
+    /// boot signature.
+    pub fn is_bootable(&mut self, device_id: usize) -> bool {
+        let mut sector = [0u8; SECTOR_SIZE];
+        match self.drives.get_mut(device_id).and_then(|drive| drive.vhd.as_mut()) {
+            Some(vhd) => vhd.read_sector(&mut sector, 0, 0, 1).is_ok() && sector[510..] == [0x55, 0xAA],
+            None => false,
+        }
+    }
+
     pub fn drive_ct(&self) -> usize {
         self.drive_ct
     }
@@ -413,6 +429,7 @@ impl HardDiskController {
             self.drives[device_id].max_heads = vhd.max_heads as u8;
             self.drives[device_id].max_sectors = vhd.max_sectors as u8;
             self.drives[device_id].vhd = Some(vhd);
+            self.drives[device_id].vhd_name = None;
         }
         else {
             return Err(ControllerError::UnsupportedVHD);
@@ -421,6 +438,30 @@ impl HardDiskController {
         Ok(())
     }
 
+    /// Set the name of the VHD attached to the specified drive, for display. The name is cleared
+    /// when another VHD is attached.
+    pub fn set_vhd_name(&mut self, device_id: usize, name: Option<String>) {
+        if let Some(drive) = self.drives.get_mut(device_id) {
+            drive.vhd_name = name;
+        }
+    }
+
+    /// Return a description of the VHD attached to the specified drive, or None if no VHD is
+    /// attached.
+    pub fn media_info(&self, device_id: usize) -> Option<MediaInfo> {
+        let drive = self.drives.get(device_id)?;
+        let vhd = drive.vhd.as_ref()?;
+        Some(MediaInfo {
+            name: drive.vhd_name.clone(),
+            cylinders: drive.max_cylinders,
+            heads: drive.max_heads,
+            sectors: drive.max_sectors,
+            size: vhd.size(),
+            write_protected: false,
+            dirty: false,
+        })
+    }
+
     pub fn set_command(&mut self, command: Command, n_bytes: u32, command_fn: CommandDispatchFn) {
         self.state = State::ReceivingCommand;
         self.receiving_dcb = true;
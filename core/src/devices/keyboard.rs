@@ -32,7 +32,7 @@
 
 use anyhow::{bail, Result};
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     fs::read_to_string,
     path::Path,
     str::FromStr,
@@ -45,6 +45,100 @@ use toml;
 
 use crate::{keys::MartyKey, machine::KeybufferEntry};
 
+/// XT scancode of the left shift key.
+pub const SCANCODE_SHIFT_LEFT: u8 = 0x2A;
+/// Set on a make code to produce the corresponding break code.
+pub const SCANCODE_BREAK_BIT: u8 = 0x80;
+
+/// Translate an ASCII character into an XT scancode for a US keyboard layout, and whether
+/// shift must be held to produce the character.
+pub fn ascii_to_scancode(c: char) -> Option<(u8, bool)> {
+    let code = match c.to_ascii_lowercase() {
+        '1' => (0x02, false),
+        '2' => (0x03, false),
+        '3' => (0x04, false),
+        '4' => (0x05, false),
+        '5' => (0x06, false),
+        '6' => (0x07, false),
+        '7' => (0x08, false),
+        '8' => (0x09, false),
+        '9' => (0x0A, false),
+        '0' => (0x0B, false),
+        '-' => (0x0C, false),
+        '=' => (0x0D, false),
+        '!' => (0x02, true),
+        '@' => (0x03, true),
+        '#' => (0x04, true),
+        '$' => (0x05, true),
+        '%' => (0x06, true),
+        '^' => (0x07, true),
+        '&' => (0x08, true),
+        '*' => (0x09, true),
+        '(' => (0x0A, true),
+        ')' => (0x0B, true),
+        '_' => (0x0C, true),
+        '+' => (0x0D, true),
+        'q' => (0x10, false),
+        'w' => (0x11, false),
+        'e' => (0x12, false),
+        'r' => (0x13, false),
+        't' => (0x14, false),
+        'y' => (0x15, false),
+        'u' => (0x16, false),
+        'i' => (0x17, false),
+        'o' => (0x18, false),
+        'p' => (0x19, false),
+        '[' => (0x1A, false),
+        ']' => (0x1B, false),
+        '{' => (0x1A, true),
+        '}' => (0x1B, true),
+        'a' => (0x1E, false),
+        's' => (0x1F, false),
+        'd' => (0x20, false),
+        'f' => (0x21, false),
+        'g' => (0x22, false),
+        'h' => (0x23, false),
+        'j' => (0x24, false),
+        'k' => (0x25, false),
+        'l' => (0x26, false),
+        ';' => (0x27, false),
+        '\'' => (0x28, false),
+        '`' => (0x29, false),
+        ':' => (0x27, true),
+        '"' => (0x28, true),
+        '~' => (0x29, true),
+        '\\' => (0x2B, false),
+        '|' => (0x2B, true),
+        'z' => (0x2C, false),
+        'x' => (0x2D, false),
+        'c' => (0x2E, false),
+        'v' => (0x2F, false),
+        'b' => (0x30, false),
+        'n' => (0x31, false),
+        'm' => (0x32, false),
+        ',' => (0x33, false),
+        '.' => (0x34, false),
+        '/' => (0x35, false),
+        '<' => (0x33, true),
+        '>' => (0x34, true),
+        '?' => (0x35, true),
+        ' ' => (0x39, false),
+        '\r' | '\n' => (0x1C, false),
+        '\t' => (0x0F, false),
+        '\x08' => (0x0E, false),
+        '\x1B' => (0x01, false),
+        _ => return None,
+    };
+
+    // Upper case letters need shift.
+    if c.is_ascii_uppercase() {
+        Some((code.0, true))
+    }
+    else {
+        Some(code)
+    }
+}
+
 // Define the various types of keyboard we can emulate.
 #[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
 pub enum KeyboardType {
@@ -71,6 +165,8 @@ pub struct KeyboardModifiers {
     pub alt: bool,
     pub shift: bool,
     pub meta: bool,
+    /// The right Alt key (AltGr) is held. Hosts may report AltGr as Alt, Ctrl+Alt, or neither.
+    pub altgr: bool,
 }
 
 impl Default for KeyboardModifiers {
@@ -80,6 +176,7 @@ impl Default for KeyboardModifiers {
             alt: false,
             shift: false,
             meta: false,
+            altgr: false,
         }
     }
 }
@@ -165,6 +262,7 @@ pub struct Keyboard {
     kb_type: KeyboardType,
     kb_hash: HashMap<MartyKey, KeyState>,
     keys_pressed: Vec<MartyKey>,
+    repeat_key: Option<MartyKey>, // The key eligible for typematic repeat: the most recently pressed one.
     typematic: bool,
     typematic_delay: f64, // Typematic repeat delay from initial keypress (ms)
     typematic_rate: f64,  // Typematic repeat rate (ms)
@@ -172,6 +270,7 @@ pub struct Keyboard {
     kb_buffer: Vec<u8>, // Keyboard buffer. Variable length depending on keyboard model.
     kb_buffer_overflow: bool,
     keycode_mappings: Vec<KeycodeMapping>,
+    unmapped_keys: HashSet<MartyKey>,
 }
 
 impl Default for Keyboard {
@@ -181,6 +280,7 @@ impl Default for Keyboard {
             kb_type: KeyboardType::ModelF,
             kb_hash: HashMap::new(),
             keys_pressed: Vec::new(),
+            repeat_key: None,
             typematic: true,
             typematic_delay: 500.0,
             typematic_rate: 100.0,
@@ -188,6 +288,7 @@ impl Default for Keyboard {
             kb_buffer: Vec::new(),
             kb_buffer_overflow: false,
             keycode_mappings: Vec::new(),
+            unmapped_keys: HashSet::new(),
         }
     }
 }
@@ -236,7 +337,12 @@ impl Keyboard {
 
     pub fn load_mapping(&mut self, map_file: &Path) -> Result<()> {
         let toml_mapping_str = read_to_string(map_file)?;
-        let toml_mapping: KeyboardMappingFile = toml::from_str(&toml_mapping_str)?;
+        self.load_mapping_from_str(&toml_mapping_str)
+    }
+
+    /// Load a keyboard mapping from the contents of a keyboard translation file.
+    pub fn load_mapping_from_str(&mut self, toml_mapping_str: &str) -> Result<()> {
+        let toml_mapping: KeyboardMappingFile = toml::from_str(toml_mapping_str)?;
 
         match self.kb_type {
             KeyboardType::ModelF => {
@@ -281,6 +387,10 @@ impl Keyboard {
             if mstring.eq_ignore_ascii_case("meta") {
                 modifiers.meta = true;
             }
+
+            if mstring.eq_ignore_ascii_case("altgr") {
+                modifiers.altgr = true;
+            }
         }
 
         modifiers
@@ -492,11 +602,13 @@ impl Keyboard {
                             key.pressed_time = 0.0;
 
                             self.keys_pressed.push(key_code);
+                            self.repeat_key = Some(key_code);
                             self.send_scancodes(&svec);
                         }
                     }
                 }
-                else {
+                else if self.unmapped_keys.insert(key_code) {
+                    // Only warn the first time an unmapped key is pressed.
                     log::warn!("key_down(): Got no scancode translation for key: {:?}", key_code);
                 }
             }
@@ -527,6 +639,10 @@ impl Keyboard {
 
         // Remove this key from keys_pressed.
         self.keys_pressed.retain(|&k| k != key_code);
+        // Releasing the repeating key doesn't resume repeat of any other key still held.
+        if self.repeat_key == Some(key_code) {
+            self.repeat_key = None;
+        }
     }
 
     /// Reset key states for all keys to unpressed.
@@ -534,6 +650,7 @@ impl Keyboard {
         for key in self.kb_hash.keys().cloned().collect::<Vec<MartyKey>>() {
             self.kb_hash.insert(key, KeyState::default());
         }
+        self.repeat_key = None;
     }
 
     /// Send the corresponding scancodes to the keyboard buffer.
@@ -588,6 +705,15 @@ impl Keyboard {
                         matched = true;
                     }
                 }
+                else if trans_modifiers.altgr {
+                    // Only the right Alt key matches, so left Alt shortcuts still reach the guest. Hosts
+                    // that report AltGr as Ctrl+Alt need no translation, as DOS keyboard drivers such
+                    // as KEYB treat Ctrl+Alt as AltGr.
+                    matched = modifiers.altgr
+                        && !modifiers.control
+                        && modifiers.shift == trans_modifiers.shift
+                        && modifiers.meta == trans_modifiers.meta;
+                }
                 else {
                     log::debug!(
                         "We have multiple modifiers: {:?}, translation modifiers: {:?}",
@@ -671,45 +797,44 @@ impl Keyboard {
     }
 
     /// Run the keyboard device for the specified number of microseconds.
+    ///
+    /// As on a real keyboard, only the most recently pressed key repeats. Its make code is sent
+    /// again once it has been held for the typematic delay, then once per typematic rate period.
+    /// Pressing another key stops the repeat, even if the first key is still held.
     pub fn run(&mut self, us: f64) {
         // Convert to milliseconds, all typematic delays are in ms.
         let ms: f64 = us / 1000.0;
 
-        let mut repeating_keys = Vec::new();
+        let vkey = match self.repeat_key {
+            Some(vkey) if self.typematic && self.is_typematic_key(vkey) => vkey,
+            _ => return,
+        };
 
-        // Update keys pressed.
-        for vkey in &self.keys_pressed {
-            if self.typematic && self.is_typematic_key(*vkey) {
-                if let Some(key_state) = self.kb_hash.get_mut(&vkey) {
-                    key_state.pressed_time += ms;
-                    if key_state.pressed_time > (self.typematic_delay - self.typematic_rate) {
-                        if self.debug {
-                            log::debug!("typematic delay elapsed for: {:?}", vkey);
-                        }
+        let mut repeat = None;
+        if let Some(key_state) = self.kb_hash.get_mut(&vkey) {
+            let was_repeating = key_state.pressed_time >= self.typematic_delay;
+            key_state.pressed_time += ms;
 
-                        key_state.repeat_time += ms;
-                        if key_state.repeat_time > self.typematic_rate {
-                            key_state.repeat_time -= self.typematic_rate;
-                            repeating_keys.push(key_state.clone());
-                        }
+            if !was_repeating {
+                if key_state.pressed_time >= self.typematic_delay {
+                    if self.debug {
+                        log::debug!("typematic delay elapsed for: {:?}", vkey);
                     }
+                    key_state.repeat_time = key_state.pressed_time - self.typematic_delay;
+                    repeat = key_state.translation.clone();
+                }
+            }
+            else {
+                key_state.repeat_time += ms;
+                if key_state.repeat_time >= self.typematic_rate {
+                    key_state.repeat_time -= self.typematic_rate;
+                    repeat = key_state.translation.clone();
                 }
             }
         }
 
-        // Sort all repeating keys by pressed_time
-        repeating_keys.sort_by(|a, b| {
-            a.pressed_time
-                .partial_cmp(&b.pressed_time)
-                .unwrap_or(std::cmp::Ordering::Equal)
-                .reverse()
-        });
-
-        // Only repeat the oldest pressed key
-        if let Some(key) = repeating_keys.pop() {
-            if let Some(translation) = key.translation {
-                self.send_scancodes(&translation);
-            }
+        if let Some(translation) = repeat {
+            self.send_scancodes(&translation);
         }
     }
 
@@ -733,3 +858,87 @@ impl Keyboard {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_most_recent_key_repeats() {
+        let mut kb = Keyboard::new(KeyboardType::ModelF, false);
+        kb.set_typematic_params(Some(true), Some(500.0), Some(100.0));
+        let modifiers = KeyboardModifiers::default();
+
+        kb.key_down(MartyKey::KeyA, &modifiers, None);
+        let a_make = kb.recv_scancode().unwrap();
+
+        // No repeat until the delay has elapsed.
+        kb.run(499_000.0);
+        assert_eq!(kb.recv_scancode(), None);
+        kb.run(1_000.0);
+        assert_eq!(kb.recv_scancode(), Some(a_make));
+        kb.run(100_000.0);
+        assert_eq!(kb.recv_scancode(), Some(a_make));
+
+        // Pressing another key stops A repeating, and B repeats after its own delay.
+        kb.key_down(MartyKey::KeyB, &modifiers, None);
+        let b_make = kb.recv_scancode().unwrap();
+        kb.run(400_000.0);
+        assert_eq!(kb.recv_scancode(), None);
+        kb.run(100_000.0);
+        assert_eq!(kb.recv_scancode(), Some(b_make));
+
+        // Releasing B doesn't resume repeat of A, which is still held.
+        kb.key_up(MartyKey::KeyB);
+        kb.recv_scancode();
+        kb.run(1_000_000.0);
+        assert_eq!(kb.recv_scancode(), None);
+    }
+
+    #[test]
+    fn altgr_macros_leave_left_alt_shortcuts_alone() {
+        let mut kb = Keyboard::new(KeyboardType::ModelF, false);
+        kb.load_mapping_from_str(include_str!(
+            "../../../install/configs/keyboard_layouts/keyboard_DE.toml"
+        ))
+        .unwrap();
+
+        // AltGr-Q types '@' under KEYB GR, which expects Ctrl+Alt+Q.
+        let altgr = KeyboardModifiers {
+            altgr: true,
+            ..Default::default()
+        };
+        match kb.translate_keydown(MartyKey::KeyQ, &altgr) {
+            TranslationType::Keycode(keys) => {
+                let keys: Vec<_> = keys.iter().map(|k| (k.keycode, k.pressed)).collect();
+                assert_eq!(
+                    keys,
+                    [
+                        (MartyKey::ControlLeft, true),
+                        (MartyKey::KeyQ, true),
+                        (MartyKey::KeyQ, false),
+                        (MartyKey::ControlLeft, false),
+                    ]
+                );
+            }
+            other => panic!("expected a macro, got {:?}", other),
+        }
+
+        // Left Alt-Q and Ctrl+Alt-Q (how some hosts report AltGr) are passed through untranslated.
+        let alt = KeyboardModifiers {
+            alt: true,
+            ..Default::default()
+        };
+        let ctrl_alt = KeyboardModifiers {
+            control: true,
+            altgr: true,
+            ..alt
+        };
+        for modifiers in [alt, ctrl_alt] {
+            assert!(matches!(
+                kb.translate_keydown(MartyKey::KeyQ, &modifiers),
+                TranslationType::Scancode(sc) if sc == [0x10]
+            ));
+        }
+    }
+}
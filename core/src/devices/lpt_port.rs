@@ -26,16 +26,35 @@
 
     devices::lpt_port.rs
 
-    Implementation of a basic Centronics printer port. This is a component
-    implementation, and must be embedded into a card implementation that can
-    decode the proper port address.
+    Implementation of a basic Centronics printer port. ParallelPort is a
+    component implementation, and must be embedded into a card implementation
+    that can decode the proper port address, such as ParallelController.
+
+    A printer may be attached to the port. The emulated printer is always
+    ready, and captures every byte strobed out by the guest into a buffer
+    and optionally a host file. The buffer holds the most recent
+    LPT_CAPTURE_MAX bytes and should be drained with take_capture().
 
 */
 
-use crate::tracelogger::TraceLogger;
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    bus::{BusInterface, DeviceRunTimeUnit, IoDevice},
+    tracelogger::TraceLogger,
+};
+use anyhow::Error;
 use modular_bitfield::{bitfield, prelude::*};
 
 pub const LPT_DEFAULT_IRQ: u16 = 7;
+pub const LPT1_DEFAULT_IO_BASE: u16 = 0x378;
+/// Maximum number of bytes held in the capture buffer. Older output is discarded.
+pub const LPT_CAPTURE_MAX: usize = 0x10000;
 
 #[bitfield]
 #[derive(Copy, Clone)]
@@ -68,6 +87,9 @@ pub struct ParallelPort {
     control: ParallelControl,
     irq: u16,
     trace_logger: TraceLogger,
+    printer_attached: bool,
+    capture: VecDeque<u8>,
+    capture_file: Option<BufWriter<File>>,
 }
 
 impl Default for ParallelPort {
@@ -78,6 +100,9 @@ impl Default for ParallelPort {
             control: ParallelControl::from_bytes([0]),
             irq: LPT_DEFAULT_IRQ,
             trace_logger: TraceLogger::None,
+            printer_attached: false,
+            capture: VecDeque::new(),
+            capture_file: None,
         }
     }
 }
@@ -91,6 +116,54 @@ impl ParallelPort {
         }
     }
 
+    /// Return the IRQ line used by this port.
+    pub fn irq(&self) -> u16 {
+        self.irq
+    }
+
+    /// Attach a printer to the port. The printer is always online and ready, so the BIOS never
+    /// times out waiting on it.
+    pub fn attach_printer(&mut self) {
+        self.printer_attached = true;
+        // Busy and error are active low on the status lines, so a ready printer sets them.
+        self.status = ParallelStatus::new()
+            .with_busy(1)
+            .with_ack(1)
+            .with_paper_out(0)
+            .with_select(1)
+            .with_error(1);
+    }
+
+    /// Append printer output to the specified host file in addition to the capture buffer.
+    pub fn set_capture_file(&mut self, path: &Path) -> Result<(), Error> {
+        let file = File::options().create(true).append(true).open(path)?;
+        self.capture_file = Some(BufWriter::new(file));
+        Ok(())
+    }
+
+    /// Take the printer output captured since the last call, clearing the capture buffer. This
+    /// also flushes the capture file, if one is set.
+    pub fn take_capture(&mut self) -> Vec<u8> {
+        if let Some(file) = &mut self.capture_file {
+            _ = file.flush();
+        }
+        self.capture.drain(..).collect()
+    }
+
+    fn print_byte(&mut self, byte: u8) {
+        if self.capture.len() >= LPT_CAPTURE_MAX {
+            // Nobody is draining the capture buffer. Drop the oldest byte.
+            self.capture.pop_front();
+        }
+        self.capture.push_back(byte);
+        if let Some(file) = &mut self.capture_file {
+            if let Err(e) = file.write_all(&[byte]) {
+                log::error!("LPT: Failed to write to capture file: {}", e);
+                self.capture_file = None;
+            }
+        }
+    }
+
     pub fn port_write(&mut self, port: u16, data: u8) {
         match port & 0x03 {
             0 => {
@@ -128,13 +201,22 @@ impl ParallelPort {
     }
 
     pub fn status_register_write(&mut self, data: u8) {
+        if self.printer_attached {
+            // The status lines are driven by the printer.
+            return;
+        }
         self.status = ParallelStatus::from_bytes([data]);
         self.trace_logger
             .print(format!("LPT: Status register write: {:#02X}", data));
     }
 
     pub fn control_register_write(&mut self, data: u8) {
-        self.control = ParallelControl::from_bytes([data]);
+        let new_control = ParallelControl::from_bytes([data]);
+        // The printer latches the data lines when the strobe is asserted.
+        if self.printer_attached && self.control.strobe() == 0 && new_control.strobe() == 1 {
+            self.print_byte(self.data);
+        }
+        self.control = new_control;
         self.trace_logger
             .print(format!("LPT: Control register write: {:#02X}", data));
     }
@@ -159,3 +241,84 @@ impl ParallelPort {
         byte
     }
 }
+
+/// A standalone parallel port card.
+pub struct ParallelController {
+    io_base: u16,
+    port: ParallelPort,
+}
+
+impl ParallelController {
+    pub fn new(io_base: Option<u16>, trace_logger: TraceLogger) -> Self {
+        Self {
+            io_base: io_base.unwrap_or(LPT1_DEFAULT_IO_BASE),
+            port: ParallelPort::new(None, trace_logger),
+        }
+    }
+
+    pub fn port(&self) -> &ParallelPort {
+        &self.port
+    }
+
+    pub fn port_mut(&mut self) -> &mut ParallelPort {
+        &mut self.port
+    }
+}
+
+impl IoDevice for ParallelController {
+    fn read_u8(&mut self, port: u16, _delta: DeviceRunTimeUnit) -> u8 {
+        self.port.port_read(port - self.io_base)
+    }
+
+    fn write_u8(&mut self, port: u16, data: u8, _bus: Option<&mut BusInterface>, _delta: DeviceRunTimeUnit) {
+        self.port.port_write(port - self.io_base, data);
+    }
+
+    fn port_list(&self) -> Vec<u16> {
+        vec![self.io_base, self.io_base + 1, self.io_base + 2]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strobe_byte(port: &mut ParallelPort, byte: u8) {
+        port.port_write(0, byte);
+        port.port_write(2, 0x01);
+        port.port_write(2, 0x00);
+    }
+
+    #[test]
+    fn printer_captures_strobed_bytes() {
+        let mut port = ParallelPort::new(None, TraceLogger::None);
+
+        // Without a printer, strobes go nowhere.
+        strobe_byte(&mut port, b'X');
+        assert!(port.take_capture().is_empty());
+
+        port.attach_printer();
+        // Ready: not busy, acknowledged, selected, no error, paper present.
+        assert_eq!(port.port_read(1), 0b1101_1000);
+        // Data written without a strobe is not printed.
+        port.port_write(0, b'Z');
+        for byte in b"HI\r\n" {
+            strobe_byte(&mut port, *byte);
+        }
+        assert_eq!(port.take_capture(), b"HI\r\n");
+        assert!(port.take_capture().is_empty());
+    }
+
+    #[test]
+    fn capture_buffer_keeps_most_recent_output() {
+        let mut port = ParallelPort::new(None, TraceLogger::None);
+        port.attach_printer();
+
+        for i in 0..LPT_CAPTURE_MAX + 2 {
+            strobe_byte(&mut port, i as u8);
+        }
+        let capture = port.take_capture();
+        assert_eq!(capture.len(), LPT_CAPTURE_MAX);
+        assert_eq!(capture[0], 2);
+    }
+}
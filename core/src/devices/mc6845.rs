@@ -384,6 +384,14 @@ impl Crtc6845 {
         }
     }
 
+    /// Latch the current memory address into the Light Pen Position registers (R16, R17), as
+    /// happens on a rising edge of the LPSTB input.
+    pub fn latch_lightpen(&mut self) {
+        self.lightpen_position = self.vma & 0x3FFF;
+        self.reg[16] = (self.lightpen_position >> 8) as u8;
+        self.reg[17] = (self.lightpen_position & 0xFF) as u8;
+    }
+
     #[inline]
     pub fn start_address(&self) -> u16 {
         self.start_address_latch
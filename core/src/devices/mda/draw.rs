@@ -65,7 +65,7 @@ impl MDACard {
     /// Draw a single character glyph column pixel in text mode, doubling the pixel if
     /// in 40 column mode.
     pub fn draw_text_mode_pixel(&mut self) {
-        let mut new_pixel = match MDACard::get_glyph_bit(self.cur_char, self.char_col, self.crtc.vlc()) {
+        let mut new_pixel = match self.get_glyph_bit(self.cur_char, self.char_col, self.crtc.vlc()) {
             true => {
                 if self.cur_blink {
                     if self.text_blink_state {
@@ -110,7 +110,7 @@ impl MDACard {
         let mut do_ul = false;
         if self.mode.display_enable() {
             for hdot in 0..(MDA_CHAR_CLOCK - 1) {
-                let mut new_pixel = match MDACard::get_glyph_bit(self.cur_char, hdot, glyph_row) {
+                let mut new_pixel = match self.get_glyph_bit(self.cur_char, hdot, glyph_row) {
                     true => {
                         self.last_bit |= true;
                         glyph_on_color
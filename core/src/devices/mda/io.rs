@@ -53,8 +53,7 @@ pub const CRTC_REGISTER_MASK: u16 = !0x007;
 
 pub const MDA_MODE_CONTROL_REGISTER: u16 = 0x3B8;
 pub const MDA_STATUS_REGISTER: u16 = 0x3BA;
-//pub const CGA_LIGHTPEN_LATCH_RESET: u16 = 0x3DB;
-//pub const CGA_LIGHTPEN_LATCH_SET: u16 = 0x3DC;
+pub const MDA_LIGHTPEN_LATCH_RESET: u16 = 0x3BB;
 
 impl IoDevice for MDACard {
     fn read_u8(&mut self, port: u16, _delta: DeviceRunTimeUnit) -> u8 {
@@ -113,6 +112,7 @@ impl IoDevice for MDACard {
                 MDA_MODE_CONTROL_REGISTER => {
                     self.handle_mode_register(data);
                 }
+                MDA_LIGHTPEN_LATCH_RESET => self.clear_lp_latch(),
                 _ => {}
             }
         }
@@ -130,6 +130,7 @@ impl IoDevice for MDACard {
             CRTC_REGISTER3,
             MDA_MODE_CONTROL_REGISTER,
             MDA_STATUS_REGISTER,
+            MDA_LIGHTPEN_LATCH_RESET,
         ];
 
         if self.lpt.is_some() {
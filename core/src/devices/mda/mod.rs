@@ -450,6 +450,7 @@ pub struct MDACard {
     rba: usize,               // Render buffer address
     cursor_blink_state: bool, // Used to control blinking of cursor and text with blink attribute
     text_blink_state: bool,
+    enable_blink: bool,       // If false, the cursor and text with blink attribute are always shown
 
     accumulated_us: f64,
     ticks_advanced: u32, // Number of ticks we have advanced mid-instruction via port or mmio access.
@@ -471,8 +472,9 @@ pub struct MDACard {
     trace_logger:  TraceLogger,
     debug_counter: u64,
 
-    lightpen_latch: bool,
-    lightpen_addr:  usize,
+    lightpen_latch:  bool,
+    lightpen_pos:    Option<(u32, u32)>,
+    lightpen_switch: bool,
 
     hblank_fn: Box<HBlankCallback>,
 
@@ -480,6 +482,9 @@ pub struct MDACard {
     lpt: Option<ParallelPort>,
 
     tmp_color: u8,
+
+    // Character generator font, in the same row-major layout as MDA_FONT
+    font: Vec<u8>,
 }
 
 #[derive(Debug)]
@@ -519,6 +524,7 @@ impl MdaDefault for DisplayExtents {
             row_stride: MDA_XRES_MAX as usize,
             double_scan: false,
             mode_byte: 0,
+            border_color: 0,
         }
     }
 }
@@ -603,6 +609,7 @@ impl Default for MDACard {
             rba: 0,
             cursor_blink_state: false,
             text_blink_state: false,
+            enable_blink: true,
 
             accumulated_us: 0.0,
             ticks_advanced: 0,
@@ -632,8 +639,9 @@ impl Default for MDACard {
             trace_logger:  TraceLogger::None,
             debug_counter: 0,
 
-            lightpen_latch: false,
-            lightpen_addr:  0,
+            lightpen_latch:  false,
+            lightpen_pos:    None,
+            lightpen_switch: false,
 
             hblank_fn: Box::new(|| 10),
 
@@ -641,6 +649,8 @@ impl Default for MDACard {
             lpt: None,
 
             tmp_color: 0,
+
+            font: MDA_FONT.to_vec(),
         }
     }
 }
@@ -678,6 +688,7 @@ impl MDACard {
         let lpt = std::mem::replace(&mut self.lpt, None);
 
         // Save non-default values
+        let enable_blink = self.enable_blink;
         *self = Self {
             debug: self.debug,
             clock_mode: self.clock_mode,
@@ -687,7 +698,19 @@ impl MDACard {
             extents: self.extents.clone(),
             hblank_fn,
             lpt,
+            font: std::mem::take(&mut self.font),
             ..Self::default()
+        };
+        self.set_blink_enabled(enable_blink);
+    }
+
+    /// Enable or disable blinking of the cursor and of text with the blink attribute. While
+    /// disabled, both are drawn as if in the visible phase of the blink cycle.
+    fn set_blink_enabled(&mut self, state: bool) {
+        self.enable_blink = state;
+        if !state {
+            self.cursor_blink_state = true;
+            self.text_blink_state = true;
         }
     }
 
@@ -791,7 +814,7 @@ impl MDACard {
         if self.lightpen_latch == false {
             // Low to high transition of light pen latch, set latch addr.
             log::debug!("Updating lightpen latch address");
-            self.lightpen_addr = self.vma;
+            self.crtc.latch_lightpen();
         }
 
         self.lightpen_latch = true;
@@ -802,6 +825,17 @@ impl MDACard {
         self.lightpen_latch = false;
     }
 
+    /// Trigger the light pen latch if the beam is about to sweep the next 'span' pixels across the
+    /// pen's position. As on the CGA, the pen only sees the beam while its switch is held down.
+    #[inline]
+    fn check_lightpen(&mut self, span: u32) {
+        if let Some((pen_x, pen_y)) = self.lightpen_pos {
+            if self.lightpen_switch && pen_y == self.beam_y && pen_x >= self.beam_x && pen_x < self.beam_x + span {
+                self.set_lp_latch();
+            }
+        }
+    }
+
     fn get_cursor_span(&self) -> (u8, u8) {
         self.crtc.cursor_extents()
     }
@@ -880,17 +914,43 @@ impl MDACard {
     }
 
     /// Return the bit value at (col,row) of the given font glyph
-    fn get_glyph_bit(glyph: u8, col: u8, row: u8) -> bool {
+    fn get_glyph_bit(&self, glyph: u8, col: u8, row: u8) -> bool {
         let col = if col > 7 { 7 } else { col };
         //debug_assert!(row < CRTC_CHAR_CLOCK);
         let row_masked = row & 0xF; // Font was padded to 16 pixels high.
 
         // Calculate byte offset
         let glyph_offset: usize = (row_masked as usize * MDA_FONT_SPAN) + glyph as usize;
-        let pixel = (MDA_FONT[glyph_offset] & (0x80 >> col)) != 0;
+        let pixel = (self.font[glyph_offset] & (0x80 >> col)) != 0;
         pixel
     }
 
+    /// Replace the character generator font. `font` must be an 8x14 font in the common
+    /// glyph-major layout (14 bytes per glyph, 256 glyphs), as found in code page font files.
+    /// Passing None restores the built-in font.
+    ///
+    /// Returns false and keeps the built-in font if the font is not the expected size.
+    pub fn set_font(&mut self, font: Option<&[u8]>) -> bool {
+        self.font = MDA_FONT.to_vec();
+
+        if let Some(font) = font {
+            if font.len() != MDA_FONT_SPAN * CRTC_FONT_HEIGHT as usize {
+                log::warn!("MDA font has invalid size: {} bytes. Using built-in font.", font.len());
+                return false;
+            }
+
+            // Transpose from glyph-major to the card's row-major layout. The internal font is
+            // padded to 16 rows; the padding rows are left blank.
+            self.font.fill(0);
+            for (glyph, glyph_rows) in font.chunks_exact(CRTC_FONT_HEIGHT as usize).enumerate() {
+                for (row, byte) in glyph_rows.iter().enumerate() {
+                    self.font[row * MDA_FONT_SPAN + glyph] = *byte;
+                }
+            }
+        }
+        true
+    }
+
     /// Fetch the character and attribute for the specified CRTC address.
     fn fetch_char(&mut self, vma: u16) {
         let addr = (vma as usize & MDA_TEXT_MODE_WRAP) << 1;
@@ -1015,6 +1075,8 @@ impl MDACard {
             }
         }
 
+        self.check_lightpen(MDA_CHAR_CLOCK as u32);
+
         // Update position to next pixel and character column.
         self.beam_x += MDA_CHAR_CLOCK as u32;
         self.rba += MDA_CHAR_CLOCK as usize;
@@ -1145,6 +1207,8 @@ impl MDACard {
             }
         }
 
+        self.check_lightpen(self.clock_divisor as u32);
+
         // Update position to next pixel and character column.
         self.beam_x += self.clock_divisor as u32;
         self.rba += self.clock_divisor as usize;
@@ -1275,7 +1339,7 @@ impl MDACard {
             self.extents.mode_byte = self.mode_byte;
 
             // Toggle blink state. This is toggled every 8 frames by default.
-            if (self.frame_count % MDA_DEFAULT_CURSOR_FRAME_CYCLE) == 0 {
+            if self.enable_blink && (self.frame_count % MDA_DEFAULT_CURSOR_FRAME_CYCLE) == 0 {
                 self.cursor_blink_state = !self.cursor_blink_state;
                 // Text blink state is 1/2 cursor blink state
                 if self.cursor_blink_state {
@@ -1288,3 +1352,95 @@ impl MDACard {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{io::MDA_LIGHTPEN_LATCH_RESET, *};
+    use crate::bus::IoDevice;
+
+    const NO_DELTA: DeviceRunTimeUnit = DeviceRunTimeUnit::Microseconds(0.0);
+
+    fn mda_80x25() -> MDACard {
+        let mut mda = MDACard::new(TraceLogger::None, ClockingMode::Character, false, false);
+        // Program the CRTC the way the BIOS sets up 80x25 monochrome text.
+        let regs = [0x61, 0x50, 0x52, 0x0F, 0x19, 0x06, 0x19, 0x19, 0x02, 0x0D, 0x0B, 0x0C];
+        for (reg, value) in regs.into_iter().enumerate() {
+            mda.write_u8(0x3B4, reg as u8, None, NO_DELTA);
+            mda.write_u8(0x3B5, value, None, NO_DELTA);
+        }
+        mda
+    }
+
+    fn lightpen_position(mda: &mut MDACard) -> u16 {
+        mda.write_u8(0x3B4, 16, None, NO_DELTA);
+        let high = mda.read_u8(0x3B5, NO_DELTA) as u16;
+        mda.write_u8(0x3B4, 17, None, NO_DELTA);
+        (high << 8) | mda.read_u8(0x3B5, NO_DELTA) as u16
+    }
+
+    const FRAME_CHARS: u32 = MDA_XRES_MAX * 400 / MDA_CHAR_CLOCK as u32;
+
+    /// Tick a frame's worth of characters, stopping early once the light pen latch triggers.
+    fn run_until_latched(mda: &mut MDACard) -> bool {
+        for _ in 0..FRAME_CHARS {
+            mda.tick_hchar();
+            if mda.lightpen_latch {
+                return true;
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn lightpen_latches_the_address_under_the_pen() {
+        let mut mda = mda_80x25();
+
+        // The pen sees nothing while its switch is released.
+        mda.set_lightpen(Some((400, 100)), false);
+        assert!(!run_until_latched(&mut mda));
+
+        mda.set_lightpen(Some((400, 100)), true);
+        assert!(run_until_latched(&mut mda));
+        let first = lightpen_position(&mut mda);
+
+        // The latch holds its address until it is reset.
+        mda.set_lightpen(Some((490, 100)), true);
+        for _ in 0..FRAME_CHARS {
+            mda.tick_hchar();
+        }
+        assert_eq!(lightpen_position(&mut mda), first);
+
+        mda.write_u8(MDA_LIGHTPEN_LATCH_RESET, 0, None, NO_DELTA);
+        assert!(!mda.lightpen_latch);
+        assert!(run_until_latched(&mut mda));
+        assert_eq!(lightpen_position(&mut mda), first + 10);
+    }
+
+    #[test]
+    fn custom_font_replaces_the_character_generator() {
+        let mut mda = mda_80x25();
+
+        // Give glyph 0xDB a single column, in the glyph-major layout of a font file.
+        let mut font = vec![0; MDA_FONT_SPAN * CRTC_FONT_HEIGHT as usize];
+        for row in 0..CRTC_FONT_HEIGHT as usize {
+            font[0xDB * CRTC_FONT_HEIGHT as usize + row] = 0x10;
+        }
+        assert!(mda.set_font(Some(&font)));
+        for row in 0..16 {
+            for col in 0..8 {
+                assert_eq!(mda.get_glyph_bit(0xDB, col, row), col == 3 && row < CRTC_FONT_HEIGHT);
+            }
+        }
+
+        // The font survives a reset.
+        mda.reset_private();
+        assert!(mda.get_glyph_bit(0xDB, 3, 13));
+
+        // A font of the wrong size, or no font, restores the built-in one.
+        assert!(!mda.set_font(Some(&font[..2048])));
+        assert_eq!(mda.font, MDA_FONT);
+        assert!(mda.set_font(Some(&font)));
+        assert!(mda.set_font(None));
+        assert_eq!(mda.font, MDA_FONT);
+    }
+}
@@ -52,6 +52,10 @@ impl VideoCard for MDACard {
                 log::debug!("VideoOption::DebugDraw set to: {}", state);
                 self.debug_draw = state;
             }
+            VideoOption::EnableBlink(state) => {
+                log::debug!("VideoOption::EnableBlink set to: {}", state);
+                self.set_blink_enabled(state);
+            }
         }
     }
 
@@ -81,10 +85,10 @@ impl VideoCard for MDACard {
     }
 
     fn get_display_size(&self) -> (u32, u32) {
-        // MDA supports a single fixed 8x14 font. The size of the displayed window
+        // MDA supports a single fixed 8x14 font in a 9-dot character cell. The size of the displayed window
         // is always HorizontalDisplayed * (VerticalDisplayed * (MaximumScanlineAddress + 1))
         // (Excepting fancy CRTC tricks that delay vsync)
-        let width = self.crtc.reg[0] as u32 * MDA_CHAR_CLOCK as u32;
+        let width = self.crtc.reg[1] as u32 * MDA_CHAR_CLOCK as u32;
         let height = self.crtc.reg[6] as u32 * (self.crtc.reg[9] as u32 + 1);
         (width, height)
     }
@@ -106,6 +110,11 @@ impl VideoCard for MDACard {
         Some((self.beam_x, self.beam_y))
     }
 
+    fn set_lightpen(&mut self, pos: Option<(u32, u32)>, switch: bool) {
+        self.lightpen_pos = pos;
+        self.lightpen_switch = switch;
+    }
+
     /// Tick the MDA the specified number of video clock cycles.
     fn debug_tick(&mut self, ticks: u32) {
         match self.clock_mode {
@@ -37,9 +37,12 @@ pub mod mda;
 #[cfg(feature = "vga")]
 pub mod vga;
 
+pub mod adlib;
 pub mod dma;
+pub mod ems;
 pub mod fdc;
 pub mod floppy_drive;
+pub mod game_port;
 pub mod hdc;
 pub mod keyboard;
 pub mod lpt_port;
@@ -47,5 +50,8 @@ pub mod mc6845;
 pub mod mouse;
 pub mod pic;
 pub mod pit;
+pub mod post_card;
 pub mod ppi;
+pub mod rtc;
 pub mod serial;
+pub mod sn76489;
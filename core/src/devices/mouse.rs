@@ -37,6 +37,19 @@ use crate::devices::serial::SerialPortController;
 // this value until it feels right.
 const MOUSE_SCALE: f64 = 0.25;
 
+// Default maximum report rate in Hz. A 1200 baud Microsoft mouse can send a three byte report
+// about 40 times per second.
+pub const MOUSE_DEFAULT_REPORT_RATE: f64 = 40.0;
+
+// Largest motion sent in a single report in absolute mode. Mouse drivers accelerate large
+// motions, which would throw off the assumed guest cursor position, so the walk toward the
+// target is made in small steps.
+const ABSOLUTE_MAX_STEP: i32 = 16;
+
+// Default coordinate range in absolute mode, in mickeys. The Microsoft mouse driver's virtual
+// screen is 640x200, at 8 mickeys per 8 pixels horizontally and 16 per 8 vertically.
+pub const MOUSE_DEFAULT_ABSOLUTE_RANGE: (u32, u32) = (640, 400);
+
 // Microseconds with RTS low before mouse considers itself reset
 const MOUSE_RESET_TIME: f64 = 10_000.0;
 
@@ -53,27 +66,81 @@ const MOUSE_UPDATE_LO_BITS: u8 = 0b0011_1111;
 #[allow(dead_code)]
 pub struct Mouse {
     updates: VecDeque<MouseUpdate>,
+    // Button state of the last report sent to the serial port
+    sent_buttons: (bool, bool),
+    report_interval: f64,
+    report_timer: f64,
     rts: bool,
     rts_low_timer: f64,
     dtr: bool,
     port: usize,
+    // Guest coordinate range in mickeys when in absolute mode
+    absolute_range: Option<(i32, i32)>,
+    // Assumed guest cursor position in absolute mode. None until the cursor has been homed.
+    absolute_pos: Option<(i32, i32)>,
 }
 
 pub enum MouseUpdate {
-    Update(u8, u8, u8),
+    /// Left button, right button, accumulated delta x, accumulated delta y
+    Update(bool, bool, i32, i32),
 }
 
 impl Mouse {
-    pub fn new(port: usize) -> Self {
+    /// Create a mouse attached to the specified serial port. The mouse sends at most
+    /// `report_rate` reports per second, accumulating motion in between.
+    pub fn new(port: usize, report_rate: f64) -> Self {
         Self {
             updates: VecDeque::new(),
+            sent_buttons: (false, false),
+            report_interval: 1_000_000.0 / report_rate.max(1.0),
+            report_timer: 0.0,
             rts: false,
             rts_low_timer: 0.0,
             dtr: false,
             port,
+            absolute_range: None,
+            absolute_pos: None,
         }
     }
 
+    /// Enable absolute positioning with the guest's coordinate range in mickeys, or disable it
+    /// with None. In absolute mode the host supplies a target position with update_absolute()
+    /// rather than relative motion, so the host pointer need not be captured.
+    pub fn set_absolute_mode(&mut self, range: Option<(u32, u32)>) {
+        self.absolute_range = range.map(|(w, h)| (w.max(1) as i32, h.max(1) as i32));
+        self.absolute_pos = None;
+    }
+
+    pub fn is_absolute_mode(&self) -> bool {
+        self.absolute_range.is_some()
+    }
+
+    /// Move the guest cursor toward an absolute position, given as a fraction (0.0-1.0) of the
+    /// guest coordinate range. The serial mouse protocol is relative only, so the mouse tracks
+    /// where it assumes the guest cursor is and sends the motion needed to reach the target.
+    /// The first update homes the cursor by moving it fully to the top left, where the driver
+    /// will clamp it. Has no effect unless absolute mode is enabled.
+    pub fn update_absolute(&mut self, l_button_pressed: bool, r_button_pressed: bool, x: f64, y: f64) {
+        let (range_w, range_h) = match self.absolute_range {
+            Some(range) => range,
+            None => return,
+        };
+
+        let (cur_x, cur_y) = match self.absolute_pos {
+            Some(pos) => pos,
+            None => {
+                self.queue_motion(l_button_pressed, r_button_pressed, -range_w, -range_h);
+                (0, 0)
+            }
+        };
+
+        let target_x = (x.clamp(0.0, 1.0) * (range_w - 1) as f64).round() as i32;
+        let target_y = (y.clamp(0.0, 1.0) * (range_h - 1) as f64).round() as i32;
+        self.absolute_pos = Some((target_x, target_y));
+
+        self.queue_motion(l_button_pressed, r_button_pressed, target_x - cur_x, target_y - cur_y);
+    }
+
     pub fn update(&mut self, l_button_pressed: bool, r_button_pressed: bool, delta_x: f64, delta_y: f64) {
         let mut scaled_x = delta_x * MOUSE_SCALE;
         let mut scaled_y = delta_y * MOUSE_SCALE;
@@ -91,9 +158,42 @@ impl Mouse {
         if scaled_y < 0.0 && scaled_y > -1.0 {
             scaled_y = -1.0;
         }
-        let delta_x_i8 = scaled_x as i8;
-        let delta_y_i8 = scaled_y as i8;
+        self.queue_motion(l_button_pressed, r_button_pressed, scaled_x as i32, scaled_y as i32);
+    }
+
+    /// Queue motion in mickeys with the specified button state for sending by run().
+    fn queue_motion(&mut self, l_button_pressed: bool, r_button_pressed: bool, delta_x: i32, delta_y: i32) {
+        // An update without motion only needs to be reported if it changes the button state
+        // from that of the last report queued or sent.
+        if delta_x == 0 && delta_y == 0 && (l_button_pressed, r_button_pressed) == self.last_buttons() {
+            return;
+        }
 
+        // Coalesce motion into the last pending update if the button state hasn't changed. The
+        // serial line can't carry updates as fast as the host produces them, so only button
+        // transitions get an update of their own.
+        if let Some(MouseUpdate::Update(l, r, dx, dy)) = self.updates.back_mut() {
+            if *l == l_button_pressed && *r == r_button_pressed {
+                *dx = dx.saturating_add(delta_x);
+                *dy = dy.saturating_add(delta_y);
+                return;
+            }
+        }
+
+        self.updates
+            .push_back(MouseUpdate::Update(l_button_pressed, r_button_pressed, delta_x, delta_y));
+    }
+
+    /// Return the button state of the most recent report, whether still queued or already sent.
+    fn last_buttons(&self) -> (bool, bool) {
+        match self.updates.back() {
+            Some(MouseUpdate::Update(l, r, _, _)) => (*l, *r),
+            None => self.sent_buttons,
+        }
+    }
+
+    /// Pack a mouse update into the three byte Microsoft mouse protocol.
+    fn pack_update(l_button_pressed: bool, r_button_pressed: bool, delta_x_i8: i8, delta_y_i8: i8) -> (u8, u8, u8) {
         let mut byte1 = MOUSE_UPDATE_STARTBIT;
 
         if l_button_pressed {
@@ -120,23 +220,41 @@ impl Mouse {
         // LO 6 bits of Y into byte 3
         let byte3 = (delta_y_i8 as u8) & MOUSE_UPDATE_LO_BITS;
 
-        // Queue update
-
-        self.updates.push_back(MouseUpdate::Update(byte1, byte2, byte3));
-        /*
-        let mut serial = self.serial_ctrl.borrow_mut();
-        serial.queue_byte(MOUSE_PORT, byte1);
-        serial.queue_byte(MOUSE_PORT, byte2);
-        serial.queue_byte(MOUSE_PORT, byte3);*/
+        (byte1, byte2, byte3)
     }
 
     /// Run the mouse device for the specified number of microseconds
     pub fn run(&mut self, serial: &mut SerialPortController, us: f64) {
-        // Send a queued update.
-        if let Some(MouseUpdate::Update(byte1, byte2, byte3)) = self.updates.pop_front() {
-            serial.queue_byte(self.port, byte1);
-            serial.queue_byte(self.port, byte2);
-            serial.queue_byte(self.port, byte3);
+        // Send at most one report per report interval, and only once the previous report has been
+        // shifted out. Motion accumulated beyond what a single report can carry is sent over
+        // the following reports.
+        self.report_timer += us;
+        if self.report_timer >= self.report_interval && serial.rx_queue_len(self.port) == 0 {
+            let max_step = match self.absolute_range {
+                Some(_) => ABSOLUTE_MAX_STEP,
+                None => i8::MAX as i32,
+            };
+            if let Some(MouseUpdate::Update(l, r, dx, dy)) = self.updates.front_mut() {
+                let (l, r) = (*l, *r);
+                let report_dx = (*dx).clamp(-max_step, max_step);
+                let report_dy = (*dy).clamp(-max_step, max_step);
+                *dx -= report_dx;
+                *dy -= report_dy;
+                if *dx == 0 && *dy == 0 {
+                    self.updates.pop_front();
+                }
+
+                self.sent_buttons = (l, r);
+                let (byte1, byte2, byte3) = Mouse::pack_update(l, r, report_dx as i8, report_dy as i8);
+                serial.queue_byte(self.port, byte1);
+                serial.queue_byte(self.port, byte2);
+                serial.queue_byte(self.port, byte3);
+                self.report_timer = 0.0;
+            }
+        }
+        // Don't bank idle time - a report after a pause in motion waits at most one interval.
+        if self.updates.is_empty() {
+            self.report_timer = self.report_timer.min(self.report_interval);
         }
 
         // Check RTS line for mouse reset
@@ -159,6 +277,9 @@ impl Mouse {
             if self.rts_low_timer > MOUSE_RESET_TIME {
                 // Reset mouse
                 self.rts_low_timer = 0.0;
+                self.sent_buttons = (false, false);
+                // The driver may have reset the guest cursor position, so home it again.
+                self.absolute_pos = None;
                 // Send reset ack byte
                 log::trace!("Sending reset byte: {:02X}", MOUSE_RESET_ACK_BYTE);
                 serial.queue_byte(self.port, MOUSE_RESET_ACK_BYTE);
@@ -166,3 +287,84 @@ impl Mouse {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{devices::serial::SERIAL_STANDARD_PORTS, machine_types::UartType};
+
+    #[test]
+    fn click_without_motion_is_reported_once() {
+        let mut mouse = Mouse::new(0, MOUSE_DEFAULT_REPORT_RATE);
+
+        mouse.update(true, false, 0.0, 0.0);
+        // A repeated update with the same button state and no motion adds nothing.
+        mouse.update(true, false, 0.0, 0.0);
+        assert_eq!(mouse.updates.len(), 1);
+
+        // The release is a transition and must produce its own report.
+        mouse.update(false, false, 0.0, 0.0);
+        assert_eq!(mouse.updates.len(), 2);
+    }
+
+    #[test]
+    fn button_chords_are_reported() {
+        let mut mouse = Mouse::new(0, MOUSE_DEFAULT_REPORT_RATE);
+        let buttons = MOUSE_UPDATE_LBUTTON | MOUSE_UPDATE_RBUTTON;
+
+        // Left down, right down while left is held, then left up while right is held.
+        mouse.update(true, false, 0.0, 0.0);
+        mouse.update(true, true, 0.0, 0.0);
+        mouse.update(false, true, 0.0, 0.0);
+        assert_eq!(mouse.updates.len(), 3);
+
+        let expected = [MOUSE_UPDATE_LBUTTON, buttons, MOUSE_UPDATE_RBUTTON];
+        for (update, expected) in mouse.updates.iter().zip(expected) {
+            let MouseUpdate::Update(l, r, dx, dy) = *update;
+            let (byte1, _, _) = Mouse::pack_update(l, r, dx as i8, dy as i8);
+            assert_eq!(byte1 & MOUSE_UPDATE_STARTBIT, MOUSE_UPDATE_STARTBIT);
+            assert_eq!(byte1 & buttons, expected);
+        }
+    }
+
+    #[test]
+    fn motion_is_coalesced_and_rate_limited() {
+        let mut serial = SerialPortController::new(UartType::Ins8250, &SERIAL_STANDARD_PORTS[0..1]);
+        let mut mouse = Mouse::new(0, MOUSE_DEFAULT_REPORT_RATE);
+
+        // More motion than a single report can carry accumulates into one pending update.
+        for _ in 0..100 {
+            mouse.update(false, false, 40.0, 0.0);
+        }
+        assert_eq!(mouse.updates.len(), 1);
+
+        // Nothing is sent until a report interval has elapsed.
+        mouse.run(&mut serial, 1_000.0);
+        assert_eq!(serial.rx_queue_len(0), 0);
+
+        mouse.run(&mut serial, mouse.report_interval);
+        assert_eq!(serial.rx_queue_len(0), 3);
+        // The remainder of the motion is left for the next report.
+        assert!(matches!(mouse.updates.front(), Some(MouseUpdate::Update(false, false, 873, 0))));
+    }
+
+    #[test]
+    fn absolute_mode_walks_cursor_to_target() {
+        let mut serial = SerialPortController::new(UartType::Ins8250, &SERIAL_STANDARD_PORTS[0..1]);
+        let mut mouse = Mouse::new(0, MOUSE_DEFAULT_REPORT_RATE);
+        mouse.set_absolute_mode(Some((641, 401)));
+
+        // The first update homes the cursor to the top left, then moves to the target.
+        mouse.update_absolute(false, false, 0.5, 0.25);
+        assert!(matches!(mouse.updates.front(), Some(MouseUpdate::Update(false, false, -321, -301))));
+
+        // Moving to a new target only sends the difference from the assumed position.
+        mouse.update_absolute(false, false, 0.0, 0.5);
+        assert!(matches!(mouse.updates.front(), Some(MouseUpdate::Update(false, false, -641, -201))));
+
+        // Each report carries a limited step.
+        mouse.run(&mut serial, mouse.report_interval);
+        assert_eq!(serial.rx_queue_len(0), 3);
+        assert!(matches!(mouse.updates.front(), Some(MouseUpdate::Update(false, false, -625, -185))));
+    }
+}
@@ -34,7 +34,10 @@
 
 //use std::io::Read;
 
-use crate::bus::{BusInterface, DeviceRunTimeUnit, IoDevice};
+use crate::{
+    bus::{BusInterface, DeviceRunTimeUnit, IoDevice},
+    tracelogger::TraceLogger,
+};
 
 //pub const PIC_INTERRUPT_OFFSET: u8 = 8;
 
@@ -125,6 +128,8 @@ pub struct Pic {
     interrupt_stats: Vec<InterruptStats>,
     intr_scheduled: bool,
     intr_timer: u32,
+
+    trace_logger: TraceLogger,
 }
 
 impl Default for Pic {
@@ -154,6 +159,8 @@ impl Default for Pic {
             interrupt_stats: vec![InterruptStats::new(); 8],
             intr_scheduled: false,
             intr_timer: 0,
+
+            trace_logger: TraceLogger::None,
         }
     }
 }
@@ -171,6 +178,17 @@ pub struct PicStringState {
     pub interrupt_stats: Vec<(String, String, String)>,
 }
 
+/// A snapshot of the PIC's register state. All fields are read at the same instant, so the
+/// IRR and ISR are always consistent with each other.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PicSnapshot {
+    pub imr:  u8,
+    pub irr:  u8,
+    pub isr:  u8,
+    pub ir:   u8,
+    pub intr: bool,
+}
+
 impl IoDevice for Pic {
     fn read_u8(&mut self, port: u16, _delta: DeviceRunTimeUnit) -> u8 {
         match port {
@@ -202,7 +220,15 @@ impl Pic {
     }
 
     pub fn reset(&mut self) {
-        *self = Default::default();
+        let trace_logger = std::mem::take(&mut self.trace_logger);
+        *self = Pic {
+            trace_logger,
+            ..Default::default()
+        };
+    }
+
+    pub fn set_trace_logger(&mut self, trace_logger: TraceLogger) {
+        self.trace_logger = trace_logger;
     }
 
     pub fn handle_command_register_write(&mut self, byte: u8) {
@@ -271,6 +297,10 @@ impl Pic {
     /// If an IR number is provided, it will perform a specific EOI and reset a specific bit.
     /// If None is provided, it will perform a non-specific EOI and reset the highest priority bit.
     pub fn eoi(&mut self, line: Option<u8>) {
+        if self.trace_logger.is_some() {
+            self.trace_logger
+                .println(format!("PIC: EOI {:?} ISR: {:08b}", line, self.isr));
+        }
         if let Some(ir) = line {
             // Specific EOI
 
@@ -422,7 +452,12 @@ impl Pic {
             panic!("PIC: Received interrupt out of range: {}", interrupt);
         }
 
-        //log::trace!("PIC: Interrupt {} requested by device", interrupt);
+        if self.trace_logger.is_some() {
+            self.trace_logger.println(format!(
+                "PIC: IRQ {} requested. IMR: {:08b} ISR: {:08b}",
+                interrupt, self.imr, self.isr
+            ));
+        }
 
         // Interrupts 0-7 map to bits 0-7 in IMR register
         let ir_bit: u8 = 0x01 << interrupt;
@@ -542,6 +577,10 @@ impl Pic {
                 // Finally, set INTR line low
                 self.intr = false;
 
+                if self.trace_logger.is_some() {
+                    self.trace_logger
+                        .println(format!("PIC: INTA IRQ {} vector {:02X}", irq, irq | self.int_offset));
+                }
                 return Some(irq | self.int_offset);
             }
             ir_bit <<= 1;
@@ -554,6 +593,22 @@ impl Pic {
         Some(SPURIOUS_INTERRUPT)
     }
 
+    /// Return a snapshot of the PIC's mask, request and in-service registers.
+    pub fn snapshot(&self) -> PicSnapshot {
+        PicSnapshot {
+            imr:  self.imr,
+            irr:  self.irr,
+            isr:  self.isr,
+            ir:   self.ir,
+            intr: self.intr,
+        }
+    }
+
+    /// Return the total number of interrupt requests passed on to the CPU.
+    pub fn serviced_count(&self) -> u64 {
+        self.interrupt_stats.iter().map(|s| s.serviced_count).sum()
+    }
+
     pub fn get_string_state(&self) -> PicStringState {
         let mut state = PicStringState {
             imr: format!("{:08b}", self.imr),
@@ -600,6 +655,12 @@ impl Pic {
         false
     }
 
+    /// Return true if INTR is raised, or an unmasked request is waiting to raise it.
+    #[inline]
+    pub fn intr_pending(&self) -> bool {
+        self.intr || self.intr_scheduled || self.calc_intr()
+    }
+
     /// Run the PIC. This is primarily used to effect a delay in raising INTR when the IMR is changed.
     pub fn run(&mut self, sys_ticks: u32) {
         if self.intr_scheduled {
@@ -57,13 +57,17 @@ const PIT_BCD_MODE_MASK: u8       = 0b0000_0001;
 //pub const PIT_FREQ: f64 = 1_193_182.0;
 pub const PIT_MHZ: f64 = 1.193182;
 pub const PIT_TICK_US: f64 = 1.0 / PIT_MHZ;
+
+// Square waves above this frequency are not sent to the speaker. This silences reload values of 170 and
+// below, which some software programs into channel 2 to quiet the speaker without touching PB1.
+pub const SPEAKER_MAX_FREQ: f64 = 7_000.0;
 //pub const PIT_DIVISOR: f64 = 0.25;
 
 // Minimum number of system ticks that must elapse between a counter write and the falling edge
 // of the PIT input clock that would latch the value.
 pub const PIT_WRITE_LATENCY: u32 = 3;
 
-#[derive(Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ChannelMode {
     InterruptOnTerminalCount,
     HardwareRetriggerableOneShot,
@@ -183,6 +187,7 @@ pub struct Channel {
     dirty: bool,  // Have channel parameters changed since last checked?
     ticked: bool, // Has the counting element been ticked at least once?
     defer_reload_flag: bool,
+    software_reload: Option<u16>, // Reload value programmed by software, saved while an override is active
 }
 
 #[allow(dead_code)]
@@ -202,6 +207,17 @@ pub struct ProgrammableIntervalTimer {
 }
 pub type Pit = ProgrammableIntervalTimer;
 
+/// A snapshot of a single PIT channel's counter state.
+#[derive(Copy, Clone, Debug)]
+pub struct PitChannelState {
+    pub count:  u16,
+    pub reload: u16,
+    pub mode:   ChannelMode,
+    pub output: bool,
+    /// True if the reload value has been overridden by the frontend.
+    pub reload_overridden: bool,
+}
+
 #[derive(Default, Clone)]
 pub struct PitStringState {
     pub c0_value: SyntaxToken,
@@ -292,6 +308,7 @@ impl Channel {
             dirty: false,
             ticked: false,
             defer_reload_flag: false,
+            software_reload: None,
         }
     }
 
@@ -356,6 +373,9 @@ impl Channel {
         self.bcd_mode = bcd;
         self.dirty = true;
 
+        // Reprogramming the channel discards any reload override.
+        self.software_reload = None;
+
         // Setting any mode stops counter.
         self.change_channel_state(ChannelState::WaitingForReload);
         self.read_state = ReadState::NoRead;
@@ -544,7 +564,9 @@ impl Channel {
 
     pub fn finalize_load(&mut self, defer_reload: bool) {
         // The count register is transferred to the counting element when a complete count is written.
+        // A new count from software always takes precedence over a reload override.
         self.reload_value.update(*self.count_register);
+        self.software_reload = None;
 
         let next_reload_state = match defer_reload {
             true => ChannelState::DeferLoadCycle,
@@ -952,6 +974,50 @@ impl ProgrammableIntervalTimer {
         self.channels[port].read_byte()
     }
 
+    /// Return the current count, reload value, mode and output of channel 0.
+    pub fn channel0_state(&self) -> PitChannelState {
+        let channel = &self.channels[0];
+        PitChannelState {
+            count:  *channel.counting_element,
+            reload: *channel.reload_value,
+            mode:   *channel.mode,
+            output: *channel.output,
+            reload_overridden: channel.software_reload.is_some(),
+        }
+    }
+
+    /// Override the reload value of channel 0, changing the rate of the system timer tick.
+    /// The new value takes effect at the channel's next reload. Passing None restores the value
+    /// programmed by software.
+    ///
+    /// An override is only accepted while channel 0 is counting in a periodic mode (2 or 3), and
+    /// is discarded as soon as software reprograms the channel. Returns false if the override was
+    /// rejected.
+    pub fn set_channel0_reload(&mut self, reload: Option<u16>) -> bool {
+        let channel = &mut self.channels[0];
+
+        match reload {
+            Some(value) => {
+                let periodic = matches!(*channel.mode, ChannelMode::RateGenerator | ChannelMode::SquareWaveGenerator);
+                if !periodic || channel.channel_state == ChannelState::WaitingForReload {
+                    return false;
+                }
+                if channel.software_reload.is_none() {
+                    channel.software_reload = Some(*channel.reload_value);
+                }
+                channel.reload_value.update(value);
+            }
+            None => {
+                if let Some(value) = channel.software_reload.take() {
+                    channel.reload_value.update(value);
+                }
+            }
+        }
+
+        channel.dirty = true;
+        true
+    }
+
     pub fn set_channel_gate(&mut self, channel: usize, state: bool, bus: &mut BusInterface) {
         if channel > 2 {
             return;
@@ -1079,6 +1145,21 @@ impl ProgrammableIntervalTimer {
         )
     }
 
+    /// Return the output frequency in Hz of the specified channel, derived from its reload value.
+    /// A reload value of 0 represents a count of 65536. Returns None if the channel's count has
+    /// not been loaded.
+    pub fn get_channel_frequency(&self, channel: usize) -> Option<f64> {
+        let channel = &self.channels[channel];
+        if matches!(channel.channel_state, ChannelState::WaitingForReload) {
+            return None;
+        }
+        let count = match *channel.count_register {
+            0 => 0x10000,
+            n => n as u32,
+        };
+        Some((PIT_MHZ * 1_000_000.0) / count as f64)
+    }
+
     #[inline]
     pub fn does_channel_retrigger(&self, channel: usize) -> bool {
         match *self.channels[channel].mode {
@@ -1120,12 +1201,16 @@ impl ProgrammableIntervalTimer {
         //log::trace!("tick(): cycle: {} channel 1 count: {}", self.pit_cycles * 4 + 7, *self.channels[1].counting_element);
 
         if self.do_speaker {
+            // The speaker is driven by the AND of channel 2's output and PPI PB1. When the channel 2 gate
+            // is low, the output is held high, so software can drive the speaker directly via PB1 (PWM).
             let mut speaker_sample = *self.channels[2].output && speaker_data;
 
             if let ChannelMode::SquareWaveGenerator = *self.channels[2].mode {
-                // Silence speaker if frequency is > 14Khz (approx)
-                if *self.channels[2].count_register <= 170 {
-                    speaker_sample = false;
+                // Silence speaker if frequency is inaudible.
+                if let Some(freq) = self.get_channel_frequency(2) {
+                    if freq > SPEAKER_MAX_FREQ {
+                        speaker_sample = false;
+                    }
                 }
             }
 
@@ -0,0 +1,96 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    devices::post_card.rs
+
+    Implements a POST diagnostic card.
+
+    A POST card latches each byte written to its IO port (normally 0x80) and
+    shows the most recent one on a pair of seven-segment digits. BIOSes that
+    support such cards write a progress code to the port as each stage of the
+    power-on self test begins, so the last code displayed identifies where a
+    failed boot stopped.
+
+*/
+
+use std::collections::VecDeque;
+
+use crate::bus::{BusInterface, DeviceRunTimeUnit, IoDevice, NO_IO_BYTE};
+
+pub const POST_CARD_DEFAULT_IO_BASE: u16 = 0x80;
+pub const POST_CARD_HISTORY_LEN: usize = 16;
+
+pub struct PostCard {
+    io_base: u16,
+    code: Option<u8>,
+    history: VecDeque<u8>,
+}
+
+impl PostCard {
+    pub fn new(io_base: Option<u16>) -> Self {
+        Self {
+            io_base: io_base.unwrap_or(POST_CARD_DEFAULT_IO_BASE),
+            code: None,
+            history: VecDeque::with_capacity(POST_CARD_HISTORY_LEN),
+        }
+    }
+
+    /// Clear the display. A real card keeps its latch across a reset, but clearing it lets the
+    /// frontend distinguish codes from the current boot.
+    pub fn reset(&mut self) {
+        self.code = None;
+        self.history.clear();
+    }
+
+    /// Return the most recent POST code, or None if nothing has been written since reset.
+    pub fn code(&self) -> Option<u8> {
+        self.code
+    }
+
+    /// Return the most recent POST codes, oldest first.
+    pub fn history(&self) -> Vec<u8> {
+        self.history.iter().copied().collect()
+    }
+}
+
+impl IoDevice for PostCard {
+    fn read_u8(&mut self, _port: u16, _delta: DeviceRunTimeUnit) -> u8 {
+        // The card only listens to writes.
+        NO_IO_BYTE
+    }
+
+    fn write_u8(&mut self, _port: u16, data: u8, _bus: Option<&mut BusInterface>, _delta: DeviceRunTimeUnit) {
+        if self.history.len() == POST_CARD_HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(data);
+        self.code = Some(data);
+    }
+
+    fn port_list(&self) -> Vec<u16> {
+        vec![self.io_base]
+    }
+}
@@ -259,14 +259,14 @@ impl Ppi {
             machine_type,
             port_a_mode: match machine_type {
                 MachineType::Ibm5150v64K | MachineType::Ibm5150v256K => PortAMode::SwitchBlock1,
-                MachineType::Ibm5160 => PortAMode::KeyboardByte,
+                MachineType::Ibm5160 | MachineType::Tandy1000 => PortAMode::KeyboardByte,
                 _ => {
                     panic!("Machine type: {:?} has no PPI", machine_type);
                 }
             },
             port_c_mode: match machine_type {
                 MachineType::Ibm5150v64K | MachineType::Ibm5150v256K => PortCMode::Switch2OneToFour,
-                MachineType::Ibm5160 => PortCMode::Switch1FiveToEight,
+                MachineType::Ibm5160 | MachineType::Tandy1000 => PortCMode::Switch1FiveToEight,
                 _ => {
                     panic!("Machine type: {:?} has no PPI", machine_type);
                 }
@@ -289,7 +289,7 @@ impl Ppi {
                     log::debug!("DIP SW1: {:08b}", dip_sw1);
                     !dip_sw1
                 }
-                MachineType::Ibm5160 => {
+                MachineType::Ibm5160 | MachineType::Tandy1000 => {
                     let dip_sw1 = sw1_bank_bits | sw1_floppy_ct_bits | sw1_video_bits | sw1_master_floppy_bit;
                     log::debug!("DIP SW1: {:08b}", dip_sw1);
                     !dip_sw1
@@ -419,7 +419,7 @@ impl Ppi {
 
     pub fn turbo_bit(&self) -> bool {
         match self.machine_type {
-            MachineType::Ibm5150v64K | MachineType::Ibm5150v256K => false,
+            MachineType::Ibm5150v64K | MachineType::Ibm5150v256K | MachineType::Tandy1000 => false,
             MachineType::Ibm5160 => self.pb_byte & PORTB_SW2_SELECT != 0,
             _ => {
                 log::error!("turbo_bit(): Machine type has no PPI!");
@@ -459,7 +459,7 @@ impl Ppi {
                     self.port_a_mode = PortAMode::KeyboardByte
                 }
             }
-            MachineType::Ibm5160 => {
+            MachineType::Ibm5160 | MachineType::Tandy1000 => {
                 // 5160 Behavior only
                 if byte & PORTB_SW1_SELECT == 0 {
                     // If Bit 3 is OFF, PC0-PC3 represent SW1 S1-S4
@@ -520,7 +520,7 @@ impl Ppi {
 
     pub fn calc_port_c_value(&self) -> u8 {
         let mut speaker_bit = 0;
-        if let MachineType::Ibm5160 = self.machine_type {
+        if let MachineType::Ibm5160 | MachineType::Tandy1000 = self.machine_type {
             speaker_bit = (self.speaker_in as u8) << 4;
         }
         let timer_bit = (self.timer_in as u8) << 5;
@@ -535,11 +535,11 @@ impl Ppi {
                 // If Port C is in Switch Block 2 mode, switches 6, 7, 8 and will read high (off)
                 (self.dip_sw2 >> 4 & 0x01) | timer_bit
             }
-            (MachineType::Ibm5160, PortCMode::Switch1OneToFour) => {
+            (MachineType::Ibm5160 | MachineType::Tandy1000, PortCMode::Switch1OneToFour) => {
                 // Cassette data line has been replaced with a speaker monitor line.
                 (self.dip_sw1 & 0x0F) | speaker_bit | timer_bit
             }
-            (MachineType::Ibm5160, PortCMode::Switch1FiveToEight) => {
+            (MachineType::Ibm5160 | MachineType::Tandy1000, PortCMode::Switch1FiveToEight) => {
                 // Cassette data line has been replaced with a speaker monitor line.
                 // On 5160, all four switches 5-8 are readable
                 (self.dip_sw1 >> 4 & 0x0F) | speaker_bit | timer_bit
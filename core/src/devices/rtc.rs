@@ -0,0 +1,518 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    devices::rtc.rs
+
+    Implements real time clock devices.
+
+    Two clocks are supported:
+     - The National MM58167, as found on XT multifunction cards such as the
+       AST SixPakPlus, which requires a driver such as ASTCLOCK to set the
+       DOS date and time at boot. It has no year register; drivers keep the
+       year in one of the chip's RAM latches.
+     - The Motorola MC146818 found in the IBM AT, with its CMOS NVRAM
+       accessed through an index and a data port.
+
+    The clock is started from the host clock, or from a fixed timestamp so
+    that test runs are reproducible, and then advances with emulated time.
+
+*/
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::bus::{BusInterface, DeviceRunTimeUnit, IoDevice};
+use serde_derive::Deserialize;
+
+pub const MM58167_DEFAULT_IO_BASE: u16 = 0x2C0;
+pub const MC146818_DEFAULT_IO_BASE: u16 = 0x70;
+
+const MM58167_PORT_COUNT: u16 = 0x20;
+const MM58167_RAM_BASE: usize = 0x08;
+const MM58167_RAM_SIZE: usize = 8;
+
+const MC146818_NVRAM_SIZE: usize = 128;
+const MC146818_REG_A: usize = 0x0A;
+const MC146818_REG_B: usize = 0x0B;
+const MC146818_REG_C: usize = 0x0C;
+const MC146818_REG_D: usize = 0x0D;
+const MC146818_REG_CENTURY: usize = 0x32;
+const MC146818_REG_A_DEFAULT: u8 = 0x26; // 32.768KHz time base, 1024Hz periodic rate
+const MC146818_REG_B_DEFAULT: u8 = 0x02; // 24 hour mode, BCD
+const MC146818_REG_B_SET: u8 = 0x80; // Halt updates while the time is set
+const MC146818_REG_D_VRT: u8 = 0x80; // Valid RAM and time
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq)]
+pub enum RtcType {
+    Mm58167,
+    Mc146818,
+}
+
+/// A calendar date and time, with a day of the week from 0 (Sunday) to 6.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct RtcDateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub weekday: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl RtcDateTime {
+    pub fn from_timestamp(timestamp: i64) -> Self {
+        let days = timestamp.div_euclid(86400);
+        let secs = timestamp.rem_euclid(86400) as u32;
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            // 1970-01-01 was a Thursday.
+            weekday: (days + 4).rem_euclid(7) as u32,
+            hour: secs / 3600,
+            minute: secs / 60 % 60,
+            second: secs % 60,
+        }
+    }
+
+    /// Return true if every field holds a value that exists on the calendar.
+    pub fn is_valid(&self) -> bool {
+        (1..=12).contains(&self.month)
+            && (1..=days_in_month(self.year, self.month)).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+
+    /// Limit the day to the length of the month, so that changing the month of a date at the end
+    /// of a month gives a valid date.
+    fn clamp_day(&mut self) {
+        if (1..=12).contains(&self.month) {
+            self.day = self.day.min(days_in_month(self.year, self.month));
+        }
+    }
+
+    /// Convert back to a timestamp. Out of range fields carry into the next larger field.
+    pub fn to_timestamp(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) * 86400
+            + self.hour as i64 * 3600
+            + self.minute as i64 * 60
+            + self.second as i64
+    }
+}
+
+// Calendar conversions from Howard Hinnant's "chrono-Compatible Low-Level Date Algorithms".
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let yoe = year - era * 400;
+    let month = month as i64;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn to_bcd(value: u32) -> u8 {
+    (((value / 10 % 10) << 4) | (value % 10)) as u8
+}
+
+fn from_bcd(value: u8) -> u32 {
+    (value >> 4) as u32 * 10 + (value & 0x0F) as u32
+}
+
+pub struct Rtc {
+    rtc_type: RtcType,
+    io_base: u16,
+    fixed_time: Option<i64>,
+    utc_offset_minutes: i32,
+    base_timestamp: i64,
+    elapsed_us: f64,
+    // The date and time being written while the MC146818's SET bit halts the clock.
+    pending_date_time: Option<RtcDateTime>,
+    index: usize,
+    nvram: [u8; MC146818_NVRAM_SIZE],
+}
+
+impl Rtc {
+    /// Create a new RTC. If `fixed_time` is specified the clock starts at that Unix timestamp,
+    /// otherwise it starts at the host's current time. `utc_offset_minutes` is applied to the
+    /// host time, as DOS expects the clock to hold local time.
+    pub fn new(rtc_type: RtcType, io_base: Option<u16>, fixed_time: Option<i64>, utc_offset_minutes: i32) -> Self {
+        let io_base = io_base.unwrap_or(match rtc_type {
+            RtcType::Mm58167 => MM58167_DEFAULT_IO_BASE,
+            RtcType::Mc146818 => MC146818_DEFAULT_IO_BASE,
+        });
+
+        let start_timestamp = Self::start_timestamp(fixed_time, utc_offset_minutes);
+        let mut rtc = Self {
+            rtc_type,
+            io_base,
+            fixed_time,
+            utc_offset_minutes,
+            base_timestamp: start_timestamp,
+            elapsed_us: 0.0,
+            pending_date_time: None,
+            index: 0,
+            nvram: [0; MC146818_NVRAM_SIZE],
+        };
+        rtc.nvram[MC146818_REG_A] = MC146818_REG_A_DEFAULT;
+        rtc.nvram[MC146818_REG_B] = MC146818_REG_B_DEFAULT;
+        rtc.nvram[MC146818_REG_D] = MC146818_REG_D_VRT;
+        rtc
+    }
+
+    fn start_timestamp(fixed_time: Option<i64>, utc_offset_minutes: i32) -> i64 {
+        match fixed_time {
+            Some(timestamp) => timestamp,
+            None => {
+                let host_secs = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs() as i64)
+                    .unwrap_or(0);
+                host_secs + utc_offset_minutes as i64 * 60
+            }
+        }
+    }
+
+    /// Reset the clock. A fixed time clock returns to its starting time, otherwise the clock is
+    /// resynchronized with the host. The contents of NVRAM are preserved, as if battery backed.
+    pub fn reset(&mut self) {
+        self.base_timestamp = Self::start_timestamp(self.fixed_time, self.utc_offset_minutes);
+        self.elapsed_us = 0.0;
+        self.pending_date_time = None;
+        self.nvram[MC146818_REG_B] &= !MC146818_REG_B_SET;
+        self.index = 0;
+    }
+
+    pub fn fixed_time(&self) -> Option<i64> {
+        self.fixed_time
+    }
+
+    /// Pin the clock to start at the specified Unix timestamp, or follow the host clock if None,
+    /// and reset it.
+    pub fn set_fixed_time(&mut self, fixed_time: Option<i64>) {
+        self.fixed_time = fixed_time;
+        self.reset();
+    }
+
+    pub fn run(&mut self, us: f64) {
+        self.elapsed_us += us;
+    }
+
+    /// Return the current time held by the clock as a Unix timestamp.
+    pub fn timestamp(&self) -> i64 {
+        self.base_timestamp + (self.elapsed_us / 1_000_000.0) as i64
+    }
+
+    /// Return the current date and time held by the clock.
+    pub fn date_time(&self) -> RtcDateTime {
+        RtcDateTime::from_timestamp(self.timestamp())
+    }
+
+    fn hundredths(&self) -> u32 {
+        ((self.elapsed_us / 10_000.0) as u64 % 100) as u32
+    }
+
+    /// Return the date and time shown in the clock registers. While the MC146818's SET bit is
+    /// on, these are the values being written rather than the running time.
+    fn register_date_time(&self) -> RtcDateTime {
+        self.pending_date_time.unwrap_or_else(|| self.date_time())
+    }
+
+    /// Change the date and time held by the clock. While the MC146818's SET bit is on the change
+    /// is held until the bit is cleared, so that the whole date is validated at once.
+    fn set_date_time<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut RtcDateTime),
+    {
+        let mut date_time = self.register_date_time();
+        f(&mut date_time);
+        match self.pending_date_time {
+            Some(_) => self.pending_date_time = Some(date_time),
+            None => self.commit_date_time(date_time),
+        }
+    }
+
+    /// Start the clock from the specified date and time. An invalid date is ignored rather than
+    /// carried into a different date.
+    fn commit_date_time(&mut self, date_time: RtcDateTime) {
+        if !date_time.is_valid() {
+            log::warn!("RTC: Ignoring invalid date and time: {:?}", date_time);
+            return;
+        }
+        self.base_timestamp += date_time.to_timestamp() - self.timestamp();
+    }
+
+    fn mm58167_read(&self, reg: usize) -> u8 {
+        let dt = self.date_time();
+        match reg {
+            0x00 => 0,
+            0x01 => to_bcd(self.hundredths()),
+            0x02 => to_bcd(dt.second),
+            0x03 => to_bcd(dt.minute),
+            0x04 => to_bcd(dt.hour),
+            0x05 => to_bcd(dt.weekday + 1),
+            0x06 => to_bcd(dt.day),
+            0x07 => to_bcd(dt.month),
+            MM58167_RAM_BASE..=0x0F => self.nvram[reg - MM58167_RAM_BASE],
+            _ => 0,
+        }
+    }
+
+    fn mm58167_write(&mut self, reg: usize, data: u8) {
+        let value = from_bcd(data);
+        match reg {
+            0x02 => self.set_date_time(|dt| dt.second = value),
+            0x03 => self.set_date_time(|dt| dt.minute = value),
+            0x04 => self.set_date_time(|dt| dt.hour = value),
+            0x06 => self.set_date_time(|dt| dt.day = value),
+            0x07 => self.set_date_time(|dt| {
+                dt.month = value;
+                dt.clamp_day();
+            }),
+            MM58167_RAM_BASE..=0x0F => self.nvram[reg - MM58167_RAM_BASE] = data,
+            0x13 => self.nvram[..MM58167_RAM_SIZE].fill(0),
+            _ => {}
+        }
+    }
+
+    fn mc146818_encode(&self, value: u32) -> u8 {
+        if self.nvram[MC146818_REG_B] & 0x04 != 0 {
+            value as u8
+        }
+        else {
+            to_bcd(value)
+        }
+    }
+
+    fn mc146818_decode(&self, data: u8) -> u32 {
+        if self.nvram[MC146818_REG_B] & 0x04 != 0 {
+            data as u32
+        }
+        else {
+            from_bcd(data)
+        }
+    }
+
+    fn mc146818_read(&mut self) -> u8 {
+        let dt = self.register_date_time();
+        match self.index {
+            0x00 => self.mc146818_encode(dt.second),
+            0x02 => self.mc146818_encode(dt.minute),
+            0x04 => self.mc146818_encode(dt.hour),
+            0x06 => self.mc146818_encode(dt.weekday + 1),
+            0x07 => self.mc146818_encode(dt.day),
+            0x08 => self.mc146818_encode(dt.month),
+            0x09 => self.mc146818_encode(dt.year.rem_euclid(100) as u32),
+            MC146818_REG_CENTURY => self.mc146818_encode(dt.year.div_euclid(100) as u32),
+            MC146818_REG_C => {
+                // Reading register C clears pending interrupt flags.
+                std::mem::take(&mut self.nvram[MC146818_REG_C])
+            }
+            index => self.nvram[index],
+        }
+    }
+
+    fn mc146818_write(&mut self, data: u8) {
+        let value = self.mc146818_decode(data);
+        match self.index {
+            0x00 => self.set_date_time(|dt| dt.second = value),
+            0x02 => self.set_date_time(|dt| dt.minute = value),
+            0x04 => self.set_date_time(|dt| dt.hour = value),
+            0x07 => self.set_date_time(|dt| dt.day = value),
+            0x08 => self.set_date_time(|dt| {
+                dt.month = value;
+                dt.clamp_day();
+            }),
+            0x09 => self.set_date_time(|dt| {
+                dt.year = dt.year.div_euclid(100) * 100 + value as i64;
+                dt.clamp_day();
+            }),
+            MC146818_REG_CENTURY => self.set_date_time(|dt| {
+                dt.year = value as i64 * 100 + dt.year.rem_euclid(100);
+                dt.clamp_day();
+            }),
+            // The update-in-progress flag and registers C and D are read only.
+            MC146818_REG_A => self.nvram[MC146818_REG_A] = data & 0x7F,
+            MC146818_REG_B => {
+                // Setting SET freezes the time registers for writing. Clearing it starts the
+                // clock from the values written.
+                if data & MC146818_REG_B_SET != 0 {
+                    if self.pending_date_time.is_none() {
+                        self.pending_date_time = Some(self.date_time());
+                    }
+                }
+                else if let Some(date_time) = self.pending_date_time.take() {
+                    self.commit_date_time(date_time);
+                }
+                self.nvram[MC146818_REG_B] = data;
+            }
+            MC146818_REG_C | MC146818_REG_D => {}
+            index => self.nvram[index] = data,
+        }
+    }
+}
+
+impl IoDevice for Rtc {
+    fn read_u8(&mut self, port: u16, _delta: DeviceRunTimeUnit) -> u8 {
+        let reg = port.wrapping_sub(self.io_base) as usize;
+        match self.rtc_type {
+            RtcType::Mm58167 => self.mm58167_read(reg),
+            RtcType::Mc146818 => match reg {
+                1 => self.mc146818_read(),
+                _ => 0xFF,
+            },
+        }
+    }
+
+    fn write_u8(&mut self, port: u16, data: u8, _bus: Option<&mut BusInterface>, _delta: DeviceRunTimeUnit) {
+        let reg = port.wrapping_sub(self.io_base) as usize;
+        match self.rtc_type {
+            RtcType::Mm58167 => self.mm58167_write(reg, data),
+            RtcType::Mc146818 => match reg {
+                // Bit 7 of the index port is the AT's NMI mask, which is not part of the RTC.
+                0 => self.index = (data & 0x7F) as usize,
+                _ => self.mc146818_write(data),
+            },
+        }
+    }
+
+    fn port_list(&self) -> Vec<u16> {
+        match self.rtc_type {
+            RtcType::Mm58167 => (0..MM58167_PORT_COUNT).map(|p| self.io_base + p).collect(),
+            RtcType::Mc146818 => vec![self.io_base, self.io_base + 1],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_time_round_trip() {
+        // 2024-02-29 13:45:30 UTC, a Thursday.
+        let timestamp = 1709214330;
+        let dt = RtcDateTime::from_timestamp(timestamp);
+        assert_eq!((dt.year, dt.month, dt.day), (2024, 2, 29));
+        assert_eq!((dt.hour, dt.minute, dt.second), (13, 45, 30));
+        assert_eq!(dt.weekday, 4);
+        assert_eq!(dt.to_timestamp(), timestamp);
+    }
+
+    #[test]
+    fn fixed_time_is_reproducible() {
+        let mut rtc = Rtc::new(RtcType::Mc146818, None, Some(1709214330), 0);
+        rtc.run(2_500_000.0);
+
+        let nul_delta = DeviceRunTimeUnit::Microseconds(0.0);
+        let mut read_reg = |reg: u8| {
+            rtc.write_u8(MC146818_DEFAULT_IO_BASE, reg, None, nul_delta);
+            rtc.read_u8(MC146818_DEFAULT_IO_BASE + 1, nul_delta)
+        };
+        assert_eq!(read_reg(0x00), 0x32);
+        assert_eq!(read_reg(0x09), 0x24);
+        assert_eq!(read_reg(MC146818_REG_CENTURY as u8), 0x20);
+    }
+
+    fn write_reg(rtc: &mut Rtc, reg: usize, data: u8) {
+        let nul_delta = DeviceRunTimeUnit::Microseconds(0.0);
+        rtc.write_u8(MC146818_DEFAULT_IO_BASE, reg as u8, None, nul_delta);
+        rtc.write_u8(MC146818_DEFAULT_IO_BASE + 1, data, None, nul_delta);
+    }
+
+    #[test]
+    fn invalid_date_is_not_carried() {
+        // 2024-02-10 00:00:00 UTC.
+        let mut rtc = Rtc::new(RtcType::Mc146818, None, Some(1707523200), 0);
+
+        // February 31st doesn't exist, and must not become March 2nd.
+        write_reg(&mut rtc, 0x07, 0x31);
+        let dt = rtc.date_time();
+        assert_eq!((dt.year, dt.month, dt.day), (2024, 2, 10));
+
+        write_reg(&mut rtc, 0x08, 0x13);
+        write_reg(&mut rtc, 0x04, 0x24);
+        assert_eq!(rtc.date_time(), RtcDateTime::from_timestamp(1707523200));
+
+        // Changing the month of a date at the end of a month keeps the date valid.
+        write_reg(&mut rtc, 0x07, 0x29);
+        write_reg(&mut rtc, 0x08, 0x03);
+        write_reg(&mut rtc, 0x07, 0x31);
+        write_reg(&mut rtc, 0x08, 0x04);
+        let dt = rtc.date_time();
+        assert_eq!((dt.year, dt.month, dt.day), (2024, 4, 30));
+    }
+
+    #[test]
+    fn set_bit_validates_the_whole_date() {
+        // 2024-01-31 00:00:00 UTC.
+        let mut rtc = Rtc::new(RtcType::Mc146818, None, Some(1706659200), 0);
+
+        // With SET on, February 29th can be written a field at a time, day last.
+        write_reg(&mut rtc, MC146818_REG_B, MC146818_REG_B_DEFAULT | MC146818_REG_B_SET);
+        write_reg(&mut rtc, 0x08, 0x02);
+        write_reg(&mut rtc, 0x07, 0x29);
+        write_reg(&mut rtc, 0x04, 0x12);
+        // The clock doesn't start until SET is cleared.
+        assert_eq!(rtc.date_time(), RtcDateTime::from_timestamp(1706659200));
+        write_reg(&mut rtc, MC146818_REG_B, MC146818_REG_B_DEFAULT);
+
+        let dt = rtc.date_time();
+        assert_eq!((dt.year, dt.month, dt.day, dt.hour), (2024, 2, 29, 12));
+
+        // A date that is invalid once SET is cleared is discarded.
+        write_reg(&mut rtc, MC146818_REG_B, MC146818_REG_B_DEFAULT | MC146818_REG_B_SET);
+        write_reg(&mut rtc, 0x04, 0x25);
+        write_reg(&mut rtc, MC146818_REG_B, MC146818_REG_B_DEFAULT);
+        let dt = rtc.date_time();
+        assert_eq!((dt.year, dt.month, dt.day, dt.hour), (2024, 2, 29, 12));
+    }
+}
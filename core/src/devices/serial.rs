@@ -27,20 +27,26 @@
     devices::serial.rs
 
     Implements the IBM Asynchronous Communications Adapter based on the
-    INS8250 Serial Controller chip.
+    INS8250 Serial Controller chip, with optional NS16550A FIFO support.
 
-    Two adapters are emulated, a primary and secondary controller.
+    Up to four ports are emulated, COM1 through COM4. COM1 and COM3 share
+    IRQ4, and COM2 and COM4 share IRQ3.
 
     Primary Documentation:
     IBM Publication 6361501
     "IBM Asynchronous Communications Adapter"
 */
 
-use std::{collections::VecDeque, io::Read};
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+};
 
 use crate::{
     bus::{BusInterface, DeviceRunTimeUnit, IoDevice},
     devices::pic,
+    machine_types::{SerialBackend, UartType},
 };
 
 /*  1.8Mhz Oscillator.
@@ -50,35 +56,33 @@ use crate::{
     nerds might recognize.
 */
 const SERIAL_CLOCK: f64 = 1.8432;
+// Minimum divisor we will honor, giving the 8250's rated maximum of 9600 baud.
+const SERIAL_MIN_DIVISOR: u16 = 12;
+
+pub const SERIAL_MAX_PORTS: usize = 4;
+
+/// Standard base addresses and IRQs for COM1 through COM4.
+pub const SERIAL_STANDARD_PORTS: [(u16, u8); SERIAL_MAX_PORTS] = [(0x3F8, 4), (0x2F8, 3), (0x3E8, 4), (0x2E8, 3)];
+
+/* - Registers -
 
-pub const SERIAL1_IRQ: u8 = 4;
-pub const SERIAL2_IRQ: u8 = 3;
+    Registers are addressed as an offset from each port's base address.
 
-/* - Ports -
+    Offsets 0 & 1 are multiplexed via use of the Divisor Latch Access Bit (DSLAB). If this bit is
+    set in the Line Control Register, these two registers access the LSB and MSB of the clock
+    Divisor instead of the RX/TX Buffer and Interrupt Enable Register.
 
-    Ports 0x3F8 & 0x3F9 (And their corresponding secondary ports) are multiplexed via
-    use of the Divisor Latch Access Bit (DSLAB). If this bit is set in the Line Control Register,
-    These two ports access the LSB and MSB of the clock Divisor instead of the RX/TX Buffer.
+    On the 16550A, a write to offset 2 accesses the FIFO Control Register. Offset 7 is the Scratch
+    Register, which the INS8250 lacks. Software uses it to tell the two apart.
 */
-pub const SERIAL1_RX_TX_BUFFER: u16 = 0x3F8;
-//pub const SERIAL1_DIVISOR_LATCH_LSB: u16 = 0x3F8;
-//pub const SERIAL1_DIVISOR_LATCH_MSB: u16 = 0x3F9;
-pub const SERIAL1_INTERRUPT_ENABLE: u16 = 0x3F9;
-pub const SERIAL1_INTERRUPT_ID: u16 = 0x3FA;
-pub const SERIAL1_LINE_CONTROL: u16 = 0x3FB;
-pub const SERIAL1_MODEM_CONTROL: u16 = 0x3FC;
-pub const SERIAL1_LINE_STATUS: u16 = 0x3FD;
-pub const SERIAL1_MODEM_STATUS: u16 = 0x3FE;
-
-pub const SERIAL2_RX_TX_BUFFER: u16 = 0x2F8;
-//pub const SERIAL2_DIVISOR_LATCH_LSB: u16 = 0x2F8;
-//pub const SERIAL2_DIVISOR_LATCH_MSB: u16 = 0x2F9;
-pub const SERIAL2_INTERRUPT_ENABLE: u16 = 0x2F9;
-pub const SERIAL2_INTERRUPT_ID: u16 = 0x2FA;
-pub const SERIAL2_LINE_CONTROL: u16 = 0x2FB;
-pub const SERIAL2_MODEM_CONTROL: u16 = 0x2FC;
-pub const SERIAL2_LINE_STATUS: u16 = 0x2FD;
-pub const SERIAL2_MODEM_STATUS: u16 = 0x2FE;
+const REG_RX_TX_BUFFER: u16 = 0;
+const REG_INTERRUPT_ENABLE: u16 = 1;
+const REG_INTERRUPT_ID: u16 = 2;
+const REG_LINE_CONTROL: u16 = 3;
+const REG_MODEM_CONTROL: u16 = 4;
+const REG_LINE_STATUS: u16 = 5;
+const REG_MODEM_STATUS: u16 = 6;
+const REG_SCRATCH: u16 = 7;
 
 // Line Control Register constants
 const WORD_LENGTH_SELECT_MASK: u8 = 0b0000_0011;
@@ -88,14 +92,17 @@ const DIVISOR_LATCH_ACCESS_BIT: u8 = 0b1000_0000;
 
 // Line Status Register constants
 const STATUS_DATA_READY: u8 = 0b0000_0001;
-//const STATUS_OVERRUN_ERROR: u8 = 0b0000_0010;
+const STATUS_OVERRUN_ERROR: u8 = 0b0000_0010;
 //const STATUS_PARITY_ERROR: u8 = 0b0000_0100;
 //const STATUS_FRAMING_ERROR: u8 = 0b0000_1000;
 //const STATUS_BREAK_INTERRUPT: u8 = 0b0001_0000;
 const STATUS_TRANSMIT_EMPTY: u8 = 0b0010_0000;
 //const STATUS_TX_SHIFT_EMPTY: u8 = 0b0100_0000;
 
-const INTERRUPT_ID_MASK: u8 = 0b0000_0011;
+// Set in the Interrupt ID Register when the FIFOs are enabled
+const INTERRUPT_ID_FIFOS_ENABLED: u8 = 0b1100_0000;
+// Interrupt ID for a character timeout. Reported in place of Received Data Available.
+const INTERRUPT_ID_CHAR_TIMEOUT: u8 = 0b0000_1100;
 
 const INTERRUPT_DATA_AVAIL: u8 = 0b0000_0001;
 const INTERRUPT_TX_EMPTY: u8 = 0b0000_0010;
@@ -115,10 +122,27 @@ const MODEM_CONTROL_OUT1: u8 = 0b0000_0100;
 const MODEM_CONTROL_OUT2: u8 = 0b0000_1000;
 const MODEM_CONTROL_LOOP: u8 = 0b0001_0000;
 
+// FIFO Control Register bits (16550A only)
+const FIFO_CONTROL_ENABLE: u8 = 0b0000_0001;
+const FIFO_CONTROL_CLEAR_RX: u8 = 0b0000_0010;
+const FIFO_CONTROL_CLEAR_TX: u8 = 0b0000_0100;
+const FIFO_CONTROL_TRIGGER_SHIFT: u8 = 6;
+
+const FIFO_LEN: usize = 16;
+// A character timeout is signalled after four character times without FIFO activity.
+const FIFO_TIMEOUT_CHARS: f64 = 4.0;
+
+// Limit on bytes received from a TCP bridge awaiting delivery to the guest. Once reached we stop
+// reading the socket, so the sender is held back by TCP flow control instead of buffering here.
+const TCP_BRIDGE_RX_LIMIT: usize = 4096;
+// Limit on bytes sent by the guest awaiting a write to a TCP bridge. A peer that stops reading
+// for long enough to fill this loses further data, as would a stalled serial receiver.
+const TCP_BRIDGE_TX_LIMIT: usize = 0x10_0000;
+
 const MODEM_STATUS_DCTS: u8 = 0b0000_0001;
 const MODEM_STATUS_DDSR: u8 = 0b0000_0010;
 //const MODEM_STATUS_TERI: u8 = 0b0000_0100;
-//const MODEM_STATUS_DRLSD: u8 = 0b0000_1000;
+const MODEM_STATUS_DRLSD: u8 = 0b0000_1000;
 const MODEM_STATUS_CTS: u8 = 0b0001_0000;
 const MODEM_STATUS_DSR: u8 = 0b0010_0000;
 const MODEM_STATUS_RI: u8 = 0b0100_0000;
@@ -126,62 +150,43 @@ const MODEM_STATUS_RLSD: u8 = 0b1000_0000;
 
 impl IoDevice for SerialPortController {
     fn read_u8(&mut self, port: u16, _delta: DeviceRunTimeUnit) -> u8 {
-        match port {
-            SERIAL1_RX_TX_BUFFER => self.port[0].rx_buffer_read(),
-            SERIAL2_RX_TX_BUFFER => self.port[1].rx_buffer_read(),
-            SERIAL1_INTERRUPT_ENABLE => self.port[0].interrupt_enable_read(),
-            SERIAL2_INTERRUPT_ENABLE => self.port[1].interrupt_enable_read(),
-            SERIAL1_INTERRUPT_ID => self.port[0].interrupt_id_read(),
-            SERIAL2_INTERRUPT_ID => self.port[1].interrupt_id_read(),
-            SERIAL1_LINE_CONTROL => self.port[0].line_control_read(),
-            SERIAL2_LINE_CONTROL => self.port[1].line_control_read(),
-            SERIAL1_MODEM_CONTROL => 0,
-            SERIAL2_MODEM_CONTROL => 0,
-            SERIAL1_LINE_STATUS => self.port[0].line_status_read(),
-            SERIAL2_LINE_STATUS => self.port[1].line_status_read(),
-            SERIAL1_MODEM_STATUS => self.port[0].modem_status_read(),
-            SERIAL2_MODEM_STATUS => self.port[1].modem_status_read(),
-            _ => 0,
+        if let Some((serial_port, reg)) = self.decode_port(port) {
+            match reg {
+                REG_RX_TX_BUFFER => serial_port.rx_buffer_read(),
+                REG_INTERRUPT_ENABLE => serial_port.interrupt_enable_read(),
+                REG_INTERRUPT_ID => serial_port.interrupt_id_read(),
+                REG_LINE_CONTROL => serial_port.line_control_read(),
+                REG_MODEM_CONTROL => 0,
+                REG_LINE_STATUS => serial_port.line_status_read(),
+                REG_MODEM_STATUS => serial_port.modem_status_read(),
+                REG_SCRATCH => serial_port.scratch_reg,
+                _ => 0,
+            }
+        }
+        else {
+            0
         }
     }
 
     fn write_u8(&mut self, port: u16, byte: u8, _bus: Option<&mut BusInterface>, _delta: DeviceRunTimeUnit) {
-        match port {
-            SERIAL1_RX_TX_BUFFER => self.port[0].tx_buffer_write(byte),
-            SERIAL2_RX_TX_BUFFER => self.port[1].tx_buffer_write(byte),
-            SERIAL1_INTERRUPT_ENABLE => self.port[0].interrupt_enable_write(byte),
-            SERIAL2_INTERRUPT_ENABLE => self.port[1].interrupt_enable_write(byte),
-            SERIAL1_INTERRUPT_ID => {}
-            SERIAL2_INTERRUPT_ID => {}
-            SERIAL1_LINE_CONTROL => self.port[0].line_control_write(byte),
-            SERIAL2_LINE_CONTROL => self.port[1].line_control_write(byte),
-            SERIAL1_MODEM_CONTROL => self.port[0].modem_control_write(byte),
-            SERIAL2_MODEM_CONTROL => self.port[1].modem_control_write(byte),
-            SERIAL1_LINE_STATUS => {}
-            SERIAL2_LINE_STATUS => {}
-            SERIAL1_MODEM_STATUS => {}
-            SERIAL2_MODEM_STATUS => {}
-            _ => {}
+        if let Some((serial_port, reg)) = self.decode_port(port) {
+            match reg {
+                REG_RX_TX_BUFFER => serial_port.tx_buffer_write(byte),
+                REG_INTERRUPT_ENABLE => serial_port.interrupt_enable_write(byte),
+                REG_INTERRUPT_ID => serial_port.fifo_control_write(byte),
+                REG_LINE_CONTROL => serial_port.line_control_write(byte),
+                REG_MODEM_CONTROL => serial_port.modem_control_write(byte),
+                REG_SCRATCH => serial_port.scratch_reg = byte,
+                _ => {}
+            }
         }
     }
 
     fn port_list(&self) -> Vec<u16> {
-        vec![
-            SERIAL1_RX_TX_BUFFER,
-            SERIAL1_INTERRUPT_ENABLE,
-            SERIAL1_INTERRUPT_ID,
-            SERIAL1_LINE_CONTROL,
-            SERIAL1_MODEM_CONTROL,
-            SERIAL1_LINE_STATUS,
-            SERIAL1_MODEM_STATUS,
-            SERIAL2_RX_TX_BUFFER,
-            SERIAL2_INTERRUPT_ENABLE,
-            SERIAL2_INTERRUPT_ID,
-            SERIAL2_LINE_CONTROL,
-            SERIAL2_MODEM_CONTROL,
-            SERIAL2_LINE_STATUS,
-            SERIAL2_MODEM_STATUS,
-        ]
+        self.port
+            .iter()
+            .flat_map(|serial_port| (0..serial_port.register_ct()).map(move |reg| serial_port.io_base + reg))
+            .collect()
     }
 }
 
@@ -213,6 +218,146 @@ pub struct SerialPortDebuggerState {
     tx_byte: u8,
 }
 
+/// Whether a TCP serial bridge should initiate a connection to a remote host or wait for one.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TcpBridgeMode {
+    Connect,
+    Listen,
+}
+
+/// A serial port backend that forwards the port's data path over a TCP socket.
+/// In Listen mode a single client is accepted at a time; when it disconnects we go back to
+/// listening for the next one.
+struct TcpBridge {
+    address:    String,
+    listener:   Option<TcpListener>,
+    stream:     Option<TcpStream>,
+    pending_tx: VecDeque<u8>,
+}
+
+impl TcpBridge {
+    fn new(address: &str, mode: TcpBridgeMode) -> anyhow::Result<Self> {
+        let mut bridge = TcpBridge {
+            address:    address.to_string(),
+            listener:   None,
+            stream:     None,
+            pending_tx: VecDeque::new(),
+        };
+
+        match mode {
+            TcpBridgeMode::Connect => {
+                let stream = TcpStream::connect(address)?;
+                stream.set_nonblocking(true)?;
+                stream.set_nodelay(true)?;
+                bridge.stream = Some(stream);
+            }
+            TcpBridgeMode::Listen => {
+                let listener = TcpListener::bind(address)?;
+                listener.set_nonblocking(true)?;
+                bridge.listener = Some(listener);
+            }
+        }
+        Ok(bridge)
+    }
+
+    /// Accept a pending connection if we are listening and have no client.
+    fn poll_accept(&mut self) {
+        if self.stream.is_some() {
+            return;
+        }
+        if let Some(listener) = &self.listener {
+            match listener.accept() {
+                Ok((stream, addr)) => {
+                    log::debug!("TCP serial bridge {}: accepted connection from {}", self.address, addr);
+                    if stream.set_nonblocking(true).is_ok() {
+                        _ = stream.set_nodelay(true);
+                        self.stream = Some(stream);
+                    }
+                }
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => log::error!("TCP serial bridge {}: accept error: {}", self.address, e),
+            }
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Write bytes to the connected client, if any. Bytes sent while no client is connected
+    /// are dropped, as they would be on a disconnected serial line. Bytes the socket can't take
+    /// yet are kept and retried by flush().
+    fn write(&mut self, bytes: &[u8]) {
+        if self.stream.is_none() {
+            return;
+        }
+        let room = TCP_BRIDGE_TX_LIMIT.saturating_sub(self.pending_tx.len());
+        if bytes.len() > room {
+            log::warn!(
+                "TCP serial bridge {}: peer is not reading, dropped {} bytes",
+                self.address,
+                bytes.len() - room
+            );
+        }
+        self.pending_tx.extend(&bytes[0..bytes.len().min(room)]);
+        self.flush();
+    }
+
+    /// Write as many pending bytes as the socket will accept without blocking.
+    fn flush(&mut self) {
+        if let Some(stream) = &mut self.stream {
+            while !self.pending_tx.is_empty() {
+                let (pending, _) = self.pending_tx.as_slices();
+                match stream.write(pending) {
+                    Ok(0) => {
+                        log::debug!("TCP serial bridge {}: connection closed", self.address);
+                        self.disconnect();
+                        return;
+                    }
+                    Ok(ct) => {
+                        self.pending_tx.drain(0..ct);
+                    }
+                    Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => return,
+                    Err(e) => {
+                        log::debug!("TCP serial bridge {}: write error: {}", self.address, e);
+                        self.disconnect();
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    fn has_pending_tx(&self) -> bool {
+        !self.pending_tx.is_empty()
+    }
+
+    /// Drop the client. Unsent bytes are lost, as they would be on a disconnected serial line.
+    fn disconnect(&mut self) {
+        self.stream = None;
+        self.pending_tx.clear();
+    }
+
+    /// Read any available bytes into the provided buffer, returning the count read.
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        if let Some(stream) = &mut self.stream {
+            match stream.read(buf) {
+                Ok(0) => {
+                    log::debug!("TCP serial bridge {}: connection closed", self.address);
+                    self.disconnect();
+                }
+                Ok(ct) => return ct,
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => {
+                    log::debug!("TCP serial bridge {}: read error: {}", self.address, e);
+                    self.disconnect();
+                }
+            }
+        }
+        0
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct SerialPortDescriptor {
     pub id: usize,
@@ -222,7 +367,10 @@ pub struct SerialPortDescriptor {
 
 pub struct SerialPort {
     name: String,
+    io_base: u16,
     irq: u8,
+    uart: UartType,
+    backend: SerialBackend,
     line_control_reg: u8,
     word_length: u8,
     stop_bits: StopBits,
@@ -247,18 +395,33 @@ pub struct SerialPort {
     tx_queue: VecDeque<u8>,
     tx_timer: f64,
     us_per_byte: f64,
+    scratch_reg: u8,
+    // Whether this port is currently driving its IRQ line
+    irq_asserted: bool,
+
+    // 16550A FIFO state
+    fifo_enabled: bool,
+    rx_fifo: VecDeque<u8>,
+    rx_trigger: usize,
+    rx_timeout: bool,
+    rx_timeout_timer: f64,
+    tx_fifo: VecDeque<u8>,
 
     // Serial port bridge
     bridge_port_id: Option<usize>,
     bridge_port: Option<Box<dyn serialport::SerialPort>>,
     bridge_buf: Vec<u8>,
+    tcp_bridge: Option<TcpBridge>,
 }
 
 impl Default for SerialPort {
     fn default() -> Self {
         Self {
             name: String::new(),
-            irq: 4,
+            io_base: SERIAL_STANDARD_PORTS[0].0,
+            irq: SERIAL_STANDARD_PORTS[0].1,
+            uart: UartType::Ins8250,
+            backend: SerialBackend::None,
             line_control_reg: 0,
             word_length: 8,
             stop_bits: StopBits::One,
@@ -274,7 +437,7 @@ impl Default for SerialPort {
             modem_status_reg: 0,
             rx_byte: 0,
             rx_count: 0,
-            rx_was_read: false,
+            rx_was_read: true, // No byte is waiting to be read, so the first one received can't overrun.
             tx_holding_reg: 0,
             tx_holding_empty: true,
             rx_queue: VecDeque::new(),
@@ -282,20 +445,32 @@ impl Default for SerialPort {
             tx_count: 0,
             tx_queue: VecDeque::new(),
             tx_timer: 0.0,
-            us_per_byte: 833.333, // 9600 baud
+            us_per_byte: 1041.667, // 9600 baud, 8N1
+            scratch_reg: 0,
+            irq_asserted: false,
+
+            fifo_enabled: false,
+            rx_fifo: VecDeque::with_capacity(FIFO_LEN),
+            rx_trigger: 1,
+            rx_timeout: false,
+            rx_timeout_timer: 0.0,
+            tx_fifo: VecDeque::with_capacity(FIFO_LEN),
 
             bridge_port_id: None,
             bridge_port: None,
             bridge_buf: vec![0; 1000],
+            tcp_bridge: None,
         }
     }
 }
 
 impl SerialPort {
-    pub fn new(name: String, irq: u8) -> Self {
+    pub fn new(name: String, io_base: u16, irq: u8, uart: UartType) -> Self {
         Self {
             name,
+            io_base,
             irq,
+            uart,
             ..Default::default()
         }
     }
@@ -303,26 +478,53 @@ impl SerialPort {
     pub fn reset(&mut self) {
         *self = Self {
             name: self.name.clone(),
+            io_base: self.io_base,
             irq: self.irq,
+            uart: self.uart,
+            backend: self.backend,
+            bridge_port_id: self.bridge_port_id,
+            bridge_port: self.bridge_port.take(),
+            tcp_bridge: self.tcp_bridge.take(),
             ..Default::default()
+        };
+        if self.bridge_port.is_some() {
+            self.set_modem_status_connected();
+        }
+    }
+
+    /// Return the number of registers decoded by this port. The INS8250 has no Scratch Register.
+    fn register_ct(&self) -> u16 {
+        match self.uart {
+            UartType::Ins8250 => REG_SCRATCH,
+            UartType::Ns16550A => REG_SCRATCH + 1,
         }
     }
 
     /// Convert the integer divisor value into baud rate
-    fn divisor_to_baud(divisor: u16) -> u16 {
-        return ((SERIAL_CLOCK * 1_000_000.0) / divisor as f64 / 16.0) as u16;
+    fn divisor_to_baud(divisor: u16) -> u32 {
+        return ((SERIAL_CLOCK * 1_000_000.0) / divisor.max(1) as f64 / 16.0) as u32;
+    }
+
+    /// Return the number of bits in a complete character frame: the start bit, data bits,
+    /// optional parity bit and stop bits.
+    fn frame_bits(&self) -> f64 {
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 1.0,
+            StopBits::OneAndAHalf => 1.5,
+            StopBits::Two => 2.0,
+        };
+        let parity_bits = if self.parity_enable { 1.0 } else { 0.0 };
+
+        1.0 + self.word_length as f64 + parity_bits + stop_bits
     }
 
     /// Sets the value of us_per_byte, the microsecond delay between sending a byte out of the
-    /// Send or receive queue based on the current baud rate.
-    /// This function should be called whenever the divisor has changed.
+    /// Send or receive queue based on the current baud rate and frame format.
+    /// This function should be called whenever the divisor or line control register has changed.
     fn set_timing(&mut self) {
-        if self.divisor < 12 {
-            // Minimum divisor of 12 (9600 baud)
-            self.divisor = 12;
-        }
-        let bytes_per_second = SerialPort::divisor_to_baud(self.divisor) / self.word_length as u16;
-        self.us_per_byte = 1.0 / bytes_per_second as f64 * 1_000_000.0;
+        let effective_divisor = self.divisor.max(SERIAL_MIN_DIVISOR);
+        let baud = SerialPort::divisor_to_baud(effective_divisor) as f64;
+        self.us_per_byte = self.frame_bits() / baud * 1_000_000.0;
     }
 
     fn line_control_read(&self) -> u8 {
@@ -350,6 +552,8 @@ impl SerialPort {
 
         self.parity_enable = byte & PARITY_ENABLE_BIT != 0;
         self.divisor_latch_access = byte & DIVISOR_LATCH_ACCESS_BIT != 0;
+        // Frame format affects the time it takes to shift a character in or out.
+        self.set_timing();
 
         log::trace!(
             "{}: Write to Line Control Register: {:02X} Word Length: {} Parity: {} Stop Bits: {:?}",
@@ -368,6 +572,22 @@ impl SerialPort {
         if self.divisor_latch_access {
             return (self.divisor & 0xFF) as u8;
         }
+        else if self.fifo_enabled {
+            let byte = self.rx_fifo.pop_front().unwrap_or(0);
+            self.rx_byte = byte;
+            // Reading the FIFO restarts the character timeout.
+            self.rx_timeout = false;
+            self.rx_timeout_timer = 0.0;
+            if self.rx_fifo.is_empty() {
+                self.line_status_reg &= !STATUS_DATA_READY;
+            }
+            // The Data Available interrupt is cleared once the FIFO drops below the trigger level.
+            if self.rx_fifo.len() < self.rx_trigger {
+                self.lower_interrupt_type(INTERRUPT_DATA_AVAIL);
+            }
+
+            byte
+        }
         else {
             // Read the byte in the RX buffer
             if !self.rx_was_read {
@@ -403,6 +623,12 @@ impl SerialPort {
         }
         else {
             log::trace!("{}: Tx buffer write: {:02X}", self.name, byte);
+            if self.fifo_enabled {
+                // Bytes written to a full transmit FIFO are lost.
+                if self.tx_fifo.len() < FIFO_LEN {
+                    self.tx_fifo.push_back(byte);
+                }
+            }
             self.tx_holding_reg = byte;
             self.tx_holding_empty = false;
             self.line_status_reg &= !STATUS_TRANSMIT_EMPTY;
@@ -465,9 +691,71 @@ impl SerialPort {
         }
     }
 
-    // Handle reading the Line Status Register
-    fn line_status_read(&self) -> u8 {
-        self.line_status_reg
+    /// Handle a write to the FIFO Control Register. This shares an address with the Interrupt ID
+    /// Register and is ignored by the INS8250.
+    fn fifo_control_write(&mut self, byte: u8) {
+        if self.uart != UartType::Ns16550A {
+            return;
+        }
+
+        let enable = byte & FIFO_CONTROL_ENABLE != 0;
+        log::trace!("{}: Write to FIFO Control Register: {:08b}", self.name, byte);
+
+        // Changing FIFO mode clears both FIFOs.
+        if enable != self.fifo_enabled {
+            self.fifo_enabled = enable;
+            self.clear_rx_fifo();
+            self.clear_tx_fifo();
+        }
+        // The remaining bits have no effect unless the FIFOs are enabled.
+        if !enable {
+            return;
+        }
+        if byte & FIFO_CONTROL_CLEAR_RX != 0 {
+            self.clear_rx_fifo();
+        }
+        if byte & FIFO_CONTROL_CLEAR_TX != 0 {
+            self.clear_tx_fifo();
+        }
+        self.rx_trigger = match byte >> FIFO_CONTROL_TRIGGER_SHIFT {
+            0 => 1,
+            1 => 4,
+            2 => 8,
+            _ => 14,
+        };
+    }
+
+    fn clear_rx_fifo(&mut self) {
+        self.rx_fifo.clear();
+        self.rx_byte = 0;
+        self.rx_was_read = true;
+        self.rx_timeout = false;
+        self.rx_timeout_timer = 0.0;
+        self.line_status_reg &= !STATUS_DATA_READY;
+        self.lower_interrupt_type(INTERRUPT_DATA_AVAIL);
+    }
+
+    fn clear_tx_fifo(&mut self) {
+        self.tx_fifo.clear();
+        self.tx_holding_reg = 0;
+        self.tx_holding_empty = true;
+        self.line_status_reg |= STATUS_TRANSMIT_EMPTY;
+    }
+
+    // Handle reading the Line Status Register. Reading it clears the error bits and the
+    // Receiver Line Status interrupt they raised.
+    fn line_status_read(&mut self) -> u8 {
+        let byte = self.line_status_reg;
+        self.line_status_reg &= !STATUS_OVERRUN_ERROR;
+        self.lower_interrupt_type(INTERRUPT_RX_LINE_STATUS);
+        byte
+    }
+
+    /// Flag a receiver overrun in the Line Status Register and raise the Receiver Line Status
+    /// interrupt if enabled.
+    fn set_overrun(&mut self) {
+        self.line_status_reg |= STATUS_OVERRUN_ERROR;
+        self.raise_interrupt_type(INTERRUPT_RX_LINE_STATUS);
     }
 
     /// Handle a read of the Interrupt ID Register.
@@ -490,7 +778,7 @@ impl SerialPort {
         let mut byte = 0;
 
         // Set bit 0 to 1 if interrupt is NOT pending
-        if self.interrupts_active & INTERRUPT_ID_MASK == 0 {
+        if self.interrupts_active == 0 {
             byte |= 1;
         }
 
@@ -505,7 +793,12 @@ impl SerialPort {
             byte |= 3 << 1;
         }
         else if self.interrupts_active & INTERRUPT_DATA_AVAIL != 0 {
-            byte |= 2 << 1;
+            if self.rx_timeout {
+                byte |= INTERRUPT_ID_CHAR_TIMEOUT;
+            }
+            else {
+                byte |= 2 << 1;
+            }
         }
         else if self.interrupts_active & INTERRUPT_TX_EMPTY != 0 {
             byte |= 1 << 1;
@@ -513,18 +806,70 @@ impl SerialPort {
         else {
             // Modem status interrupt == 0
         }
+
+        if self.fifo_enabled {
+            byte |= INTERRUPT_ID_FIFOS_ENABLED;
+        }
         byte
     }
 
     /// Handle writing to the Modem Control Register
     fn modem_control_write(&mut self, byte: u8) {
         log::trace!("{}: Write to Modem Control Register: {:05b}", self.name, byte & 0x1F);
+        let old_modem_control_reg = self.modem_control_reg;
         self.modem_control_reg = byte & 0x1F;
 
+        // OUT2 gates the adapter's IRQ driver. While it is low the port leaves the IRQ line free
+        // for any other port sharing it.
+        if (old_modem_control_reg ^ self.modem_control_reg) & MODEM_CONTROL_OUT2 != 0 {
+            if self.modem_control_reg & MODEM_CONTROL_OUT2 == 0 {
+                self.intr_action = IntrAction::Lower;
+            }
+            else if self.interrupts_active != 0 {
+                self.intr_action = IntrAction::Raise;
+            }
+        }
+
         self.loopback = self.modem_control_reg & MODEM_CONTROL_LOOP != 0;
         if self.loopback {
             log::trace!("{}: Loopback mode enabled", self.name);
         }
+
+        if self.backend == SerialBackend::Loopback {
+            self.update_loopback_modem_status();
+        }
+    }
+
+    /// Drive the modem status inputs from the modem control outputs, as wired by a loopback plug:
+    /// DTR to DSR and RLSD (DCD), and RTS to CTS. Changed inputs set their delta bits and raise a
+    /// Modem Status interrupt.
+    fn update_loopback_modem_status(&mut self) {
+        let dtr = self.modem_control_reg & MODEM_CONTROL_DTR != 0;
+        let rts = self.modem_control_reg & MODEM_CONTROL_RTS != 0;
+
+        let mut status = self.modem_status_reg & !(MODEM_STATUS_CTS | MODEM_STATUS_DSR | MODEM_STATUS_RLSD);
+        if rts {
+            status |= MODEM_STATUS_CTS;
+        }
+        if dtr {
+            status |= MODEM_STATUS_DSR | MODEM_STATUS_RLSD;
+        }
+
+        let changed = status ^ self.modem_status_reg;
+        if changed & MODEM_STATUS_CTS != 0 {
+            status |= MODEM_STATUS_DCTS;
+        }
+        if changed & MODEM_STATUS_DSR != 0 {
+            status |= MODEM_STATUS_DDSR;
+        }
+        if changed & MODEM_STATUS_RLSD != 0 {
+            status |= MODEM_STATUS_DRLSD;
+        }
+        self.modem_status_reg = status;
+
+        if changed != 0 {
+            self.raise_interrupt_type(INTERRUPT_MODEM_STATUS);
+        }
     }
 
     /// Handle reading from the Modem Status register
@@ -551,9 +896,12 @@ impl SerialPort {
         else {
             let byte = self.modem_status_reg;
 
-            // Clear DCTS and DDSR flags
+            // Clear DCTS, DDSR and DRLSD flags
             self.modem_status_reg &= !MODEM_STATUS_DCTS;
             self.modem_status_reg &= !MODEM_STATUS_DDSR;
+            self.modem_status_reg &= !MODEM_STATUS_DRLSD;
+            // Reading the Modem Status Register clears the Modem Status interrupt.
+            self.lower_interrupt_type(INTERRUPT_MODEM_STATUS);
 
             byte
         }
@@ -595,6 +943,41 @@ impl SerialPort {
         }
     }
 
+    /// Receive a byte into the RX buffer register, or the receive FIFO if enabled.
+    fn receive_byte(&mut self, byte: u8) {
+        if self.fifo_enabled {
+            // Bytes received into a full FIFO are lost.
+            if self.rx_fifo.len() < FIFO_LEN {
+                self.rx_fifo.push_back(byte);
+            }
+            else {
+                self.set_overrun();
+            }
+            self.rx_timeout_timer = 0.0;
+            self.line_status_reg |= STATUS_DATA_READY;
+
+            // Raise Data Available interrupt once the trigger level is reached
+            if self.rx_fifo.len() >= self.rx_trigger {
+                self.raise_interrupt_type(INTERRUPT_DATA_AVAIL);
+            }
+        }
+        else {
+            if !self.rx_was_read {
+                // Previous byte was never read :(
+                self.set_overrun();
+            }
+
+            self.rx_byte = byte;
+            self.rx_was_read = false;
+            // Set Data Available bit in LSR
+            self.line_status_reg |= STATUS_DATA_READY;
+
+            // Raise Data Available interrupt if not masked
+            self.raise_interrupt_type(INTERRUPT_DATA_AVAIL);
+        }
+        //log::trace!("{}: Received byte: {:02X}", self.name, byte);
+    }
+
     fn bridge_port(&mut self, port_name: String, port_id: usize) -> anyhow::Result<bool> {
         let port_result = serialport::new(port_name.clone(), 9600)
             .timeout(std::time::Duration::from_millis(5))
@@ -616,20 +999,66 @@ impl SerialPort {
             }
         }
     }
+
+    fn bridge_tcp(&mut self, address: &str, mode: TcpBridgeMode) -> anyhow::Result<()> {
+        match TcpBridge::new(address, mode) {
+            Ok(bridge) => {
+                log::debug!("{}: bridged to TCP address {} ({:?})", self.name, address, mode);
+                self.tcp_bridge = Some(bridge);
+                Ok(())
+            }
+            Err(e) => {
+                log::error!("{}: Error opening TCP bridge to {}: {}", self.name, address, e);
+                anyhow::bail!("Error opening TCP bridge to {}: {}", address, e)
+            }
+        }
+    }
 }
 
 pub struct SerialPortController {
-    port: [SerialPort; 2],
+    port: Vec<SerialPort>,
 }
 
 impl SerialPortController {
-    pub fn new() -> Self {
-        Self {
-            port: [
-                SerialPort::new("COM1".to_string(), SERIAL1_IRQ),
-                SerialPort::new("COM2".to_string(), SERIAL2_IRQ),
-            ],
+    /// Create a serial controller with a port of the specified UART type at each of the given
+    /// base addresses and IRQs. At most SERIAL_MAX_PORTS ports are created.
+    pub fn new(uart: UartType, ports: &[(u16, u8)]) -> Self {
+        let port = ports
+            .iter()
+            .take(SERIAL_MAX_PORTS)
+            .enumerate()
+            .map(|(i, &(io_base, irq))| {
+                // Name the port after the standard COM port at its address, if there is one.
+                let name = match SERIAL_STANDARD_PORTS.iter().position(|&(base, _)| base == io_base) {
+                    Some(com) => format!("COM{}", com + 1),
+                    None => format!("Serial{}", i),
+                };
+                SerialPort::new(name, io_base, irq, uart)
+            })
+            .collect();
+
+        Self { port }
+    }
+
+    /// Return the number of ports on this controller.
+    pub fn port_ct(&self) -> usize {
+        self.port.len()
+    }
+
+    /// Return the name and IRQ line of each port on this controller.
+    pub fn port_irqs(&self) -> Vec<(String, u8)> {
+        self.port.iter().map(|p| (p.name.clone(), p.irq)).collect()
+    }
+
+    /// Resolve an IO address to the port that decodes it and the register offset within that port.
+    fn decode_port(&mut self, address: u16) -> Option<(&mut SerialPort, u16)> {
+        for serial_port in self.port.iter_mut() {
+            if address >= serial_port.io_base && address < serial_port.io_base + serial_port.register_ct() {
+                let reg = address - serial_port.io_base;
+                return Some((serial_port, reg));
+            }
         }
+        None
     }
 
     pub fn enumerate_ports(&self) -> Vec<SerialPortDescriptor> {
@@ -679,60 +1108,96 @@ impl SerialPortController {
         self.port[port].modem_control_reg & MODEM_CONTROL_DTR != 0
     }
 
-    /// Queue a byte for delivery to the specified serial port's RX buffer
+    /// Queue a byte for delivery to the specified serial port's RX buffer.
+    /// Queued bytes are delivered to the guest at the port's programmed baud rate.
     pub fn queue_byte(&mut self, port: usize, byte: u8) {
         self.port[port].rx_queue.push_back(byte);
     }
 
+    /// Return the number of bytes waiting to be delivered to the specified serial port's RX buffer
+    pub fn rx_queue_len(&self, port: usize) -> usize {
+        self.port[port].rx_queue.len()
+    }
+
+    /// Return the baud rate currently programmed into the specified serial port's divisor latch
+    pub fn get_baud_rate(&self, port: usize) -> u32 {
+        SerialPort::divisor_to_baud(self.port[port].divisor.max(SERIAL_MIN_DIVISOR))
+    }
+
+    /// Attach the specified backend to the specified serial port.
+    pub fn set_backend(&mut self, port: usize, backend: SerialBackend) {
+        if let Some(serial_port) = self.port.get_mut(port) {
+            log::debug!("{}: attached backend {:?}", serial_port.name, backend);
+            serial_port.backend = backend;
+        }
+    }
+
     /// Bridge the specified serial port
     pub fn bridge_port(&mut self, port: usize, host_port_name: String, host_port_id: usize) -> anyhow::Result<bool> {
-        self.port[port].bridge_port(host_port_name, host_port_id)
+        match self.port.get_mut(port) {
+            Some(serial_port) => serial_port.bridge_port(host_port_name, host_port_id),
+            None => anyhow::bail!("Serial port {} is not present", port),
+        }
+    }
+
+    /// Bridge the specified serial port to a TCP socket, either connecting to or listening on
+    /// the given address.
+    pub fn bridge_tcp(&mut self, port: usize, address: &str, mode: TcpBridgeMode) -> anyhow::Result<()> {
+        match self.port.get_mut(port) {
+            Some(serial_port) => serial_port.bridge_tcp(address, mode),
+            None => anyhow::bail!("Serial port {} is not present", port),
+        }
     }
 
     /// Run the serial ports for the specified number of microseconds
     pub fn run(&mut self, pic: &mut pic::Pic, us: f64) {
-        for port in self.port.iter_mut() {
-            // Handle pending interrupt action
-            match port.intr_action {
+        // Handle pending interrupt actions. Ports may share an IRQ line, so the line is only
+        // deasserted once no port on it has an interrupt pending.
+        for i in 0..self.port.len() {
+            let irq = self.port[i].irq;
+            match self.port[i].intr_action {
                 IntrAction::Raise => {
-                    //log::trace!("asserting irq: {}", port.irq);
-                    pic.request_interrupt(port.irq);
+                    //log::trace!("asserting irq: {}", irq);
+                    self.port[i].irq_asserted = true;
+                    pic.request_interrupt(irq);
                 }
                 IntrAction::Lower => {
-                    //log::trace!("deasserting irq: {}", port.irq);
-                    pic.clear_interrupt(port.irq);
+                    self.port[i].irq_asserted = false;
+                    if !self.port.iter().any(|p| p.irq == irq && p.irq_asserted) {
+                        //log::trace!("deasserting irq: {}", irq);
+                        pic.clear_interrupt(irq);
+                    }
                 }
                 IntrAction::None => {}
             }
-            port.intr_action = IntrAction::None;
+            self.port[i].intr_action = IntrAction::None;
+        }
 
+        for port in self.port.iter_mut() {
             // Receive bytes from queue
             port.rx_timer += us;
             while port.rx_timer > port.us_per_byte {
                 // Time to receive a byte at current baud rate
                 if let Some(b) = port.rx_queue.pop_front() {
                     // We have a byte to receive
+                    port.receive_byte(b);
+                }
 
-                    if !port.rx_was_read {
-                        // Previous byte was never read :(
-                        // TODO: Handle overflow interrupt
-                    }
-
-                    port.rx_byte = b;
-                    port.rx_was_read = false;
-                    // Set Data Available bit in LSR
-                    port.line_status_reg |= STATUS_DATA_READY;
+                port.rx_timer -= port.us_per_byte;
+            }
+            // Don't bank idle time - an idle line doesn't let a later burst of bytes arrive faster
+            // than the baud rate allows.
+            if port.rx_queue.is_empty() {
+                port.rx_timer = port.rx_timer.min(port.us_per_byte);
+            }
 
-                    // Raise Data Available interrupt if not masked
+            // Signal a character timeout if bytes below the trigger level have sat in the FIFO.
+            if port.fifo_enabled && !port.rx_fifo.is_empty() {
+                port.rx_timeout_timer += us;
+                if !port.rx_timeout && port.rx_timeout_timer > port.us_per_byte * FIFO_TIMEOUT_CHARS {
+                    port.rx_timeout = true;
                     port.raise_interrupt_type(INTERRUPT_DATA_AVAIL);
-
-                    if port.name.eq("COM2") {
-                        log::trace!("{}: Received byte: {:02X}", port.name, b);
-                    }
-                    //log::trace!("{}: Received byte: {:02X}", port.name, b );
                 }
-
-                port.rx_timer -= port.us_per_byte;
             }
 
             // Transmit byte timer
@@ -740,21 +1205,45 @@ impl SerialPortController {
             while port.tx_timer > port.us_per_byte {
                 // Is there a byte waiting to be sent in the tx holding register?
                 if !port.tx_holding_empty {
+                    let tx_byte = if port.fifo_enabled {
+                        port.tx_fifo.pop_front().unwrap_or(0)
+                    }
+                    else {
+                        port.tx_holding_reg
+                    };
+
                     // If we have bridged this serial port, send the byte to the tx queue
-                    if let Some(_) = &port.bridge_port {
-                        //log::trace!("{}: Sending byte: {:02X}", port.name, port.tx_holding_reg);
-                        port.tx_queue.push_back(port.tx_holding_reg);
+                    if port.bridge_port.is_some() || port.tcp_bridge.is_some() {
+                        //log::trace!("{}: Sending byte: {:02X}", port.name, tx_byte);
+                        port.tx_queue.push_back(tx_byte);
+                    }
+                    // A loopback plug receives the byte as soon as it has been sent.
+                    if port.backend == SerialBackend::Loopback {
+                        port.receive_byte(tx_byte);
                     }
 
-                    port.tx_holding_reg = 0;
-                    port.tx_holding_empty = true;
-                    port.line_status_reg |= STATUS_TRANSMIT_EMPTY;
+                    // The transmitter is only empty once the FIFO has drained.
+                    if !port.fifo_enabled || port.tx_fifo.is_empty() {
+                        port.tx_holding_reg = 0;
+                        port.tx_holding_empty = true;
+                        port.line_status_reg |= STATUS_TRANSMIT_EMPTY;
 
-                    port.raise_interrupt_type(INTERRUPT_TX_EMPTY);
+                        port.raise_interrupt_type(INTERRUPT_TX_EMPTY);
+                    }
                 }
 
                 port.tx_timer -= port.us_per_byte;
             }
+            if port.tx_holding_empty {
+                port.tx_timer = port.tx_timer.min(port.us_per_byte);
+            }
+
+            // Retry bytes the TCP bridge couldn't send when the socket was full.
+            if let Some(tcp_bridge) = &mut port.tcp_bridge {
+                if tcp_bridge.has_pending_tx() {
+                    tcp_bridge.flush();
+                }
+            }
         }
     }
 
@@ -800,6 +1289,199 @@ impl SerialPortController {
                 }
                 None => {}
             }
+
+            let mut tcp_connected = false;
+            if let Some(tcp_bridge) = &mut port.tcp_bridge {
+                tcp_bridge.poll_accept();
+                tcp_connected = tcp_bridge.is_connected();
+
+                if port.tx_queue.len() > 0 {
+                    port.tx_queue.make_contiguous();
+                    let (tx1, _) = port.tx_queue.as_slices();
+                    tcp_bridge.write(tx1);
+                    port.tx_queue.clear();
+                }
+
+                // Received bytes are delivered to the guest at the current baud rate by run().
+                let room = TCP_BRIDGE_RX_LIMIT.saturating_sub(port.rx_queue.len());
+                let read_len = room.min(port.bridge_buf.len());
+                if read_len > 0 {
+                    let ct = tcp_bridge.read(&mut port.bridge_buf[0..read_len]);
+                    port.rx_queue.extend(&port.bridge_buf[0..ct]);
+                }
+            }
+            if tcp_connected {
+                port.set_modem_status_connected();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_DELTA: DeviceRunTimeUnit = DeviceRunTimeUnit::Microseconds(0.0);
+
+    fn ir_line(pic: &pic::Pic, irq: u8) -> bool {
+        let ir = u8::from_str_radix(&pic.get_string_state().ir, 2).unwrap();
+        ir & (1 << irq) != 0
+    }
+
+    #[test]
+    fn uart_type_is_detectable() {
+        let mut serial = SerialPortController::new(UartType::Ins8250, &SERIAL_STANDARD_PORTS[0..1]);
+        assert!(!serial.port_list().contains(&(0x3F8 + REG_SCRATCH)));
+        serial.write_u8(0x3FA, 0xC1, None, NO_DELTA);
+        assert_eq!(serial.read_u8(0x3FA, NO_DELTA) & INTERRUPT_ID_FIFOS_ENABLED, 0);
+
+        let mut serial = SerialPortController::new(UartType::Ns16550A, &SERIAL_STANDARD_PORTS[0..1]);
+        serial.write_u8(0x3FF, 0x55, None, NO_DELTA);
+        assert_eq!(serial.read_u8(0x3FF, NO_DELTA), 0x55);
+        serial.write_u8(0x3FA, 0xC1, None, NO_DELTA);
+        assert_eq!(
+            serial.read_u8(0x3FA, NO_DELTA) & INTERRUPT_ID_FIFOS_ENABLED,
+            INTERRUPT_ID_FIFOS_ENABLED
+        );
+    }
+
+    #[test]
+    fn shared_irq_stays_asserted_until_all_ports_clear() {
+        // COM1 and COM3 share IRQ4.
+        let ports = [SERIAL_STANDARD_PORTS[0], SERIAL_STANDARD_PORTS[2]];
+        let mut serial = SerialPortController::new(UartType::Ins8250, &ports);
+        let mut pic = pic::Pic::new();
+
+        // Enable OUT2 and the Transmitter Holding Register Empty interrupt on both ports.
+        for base in [0x3F8, 0x3E8] {
+            serial.write_u8(base + REG_MODEM_CONTROL, MODEM_CONTROL_OUT2, None, NO_DELTA);
+            serial.write_u8(base + REG_INTERRUPT_ENABLE, INTERRUPT_TX_EMPTY, None, NO_DELTA);
+        }
+        serial.run(&mut pic, 0.0);
+        assert!(ir_line(&pic, 4));
+
+        // Reading the Interrupt ID Register clears the THRE interrupt on COM1 only.
+        serial.read_u8(0x3F8 + REG_INTERRUPT_ID, NO_DELTA);
+        serial.run(&mut pic, 0.0);
+        assert!(ir_line(&pic, 4));
+
+        serial.read_u8(0x3E8 + REG_INTERRUPT_ID, NO_DELTA);
+        serial.run(&mut pic, 0.0);
+        assert!(!ir_line(&pic, 4));
+    }
+
+    #[test]
+    fn loopback_backend_echoes_data_and_modem_lines() {
+        let mut serial = SerialPortController::new(UartType::Ins8250, &SERIAL_STANDARD_PORTS[0..1]);
+        serial.set_backend(0, SerialBackend::Loopback);
+        let mut pic = pic::Pic::new();
+
+        serial.write_u8(0x3F8 + REG_MODEM_CONTROL, MODEM_CONTROL_DTR | MODEM_CONTROL_RTS, None, NO_DELTA);
+        let status = serial.read_u8(0x3F8 + REG_MODEM_STATUS, NO_DELTA);
+        assert_eq!(
+            status & (MODEM_STATUS_CTS | MODEM_STATUS_DSR | MODEM_STATUS_RLSD),
+            MODEM_STATUS_CTS | MODEM_STATUS_DSR | MODEM_STATUS_RLSD
+        );
+        assert_ne!(status & (MODEM_STATUS_DCTS | MODEM_STATUS_DDSR | MODEM_STATUS_DRLSD), 0);
+        assert_eq!(serial.read_u8(0x3F8 + REG_MODEM_STATUS, NO_DELTA) & 0x0F, 0);
+
+        serial.write_u8(0x3F8 + REG_RX_TX_BUFFER, 0xA5, None, NO_DELTA);
+        serial.run(&mut pic, 2000.0);
+        assert_ne!(serial.read_u8(0x3F8 + REG_LINE_STATUS, NO_DELTA) & STATUS_DATA_READY, 0);
+        assert_eq!(serial.read_u8(0x3F8 + REG_RX_TX_BUFFER, NO_DELTA), 0xA5);
+    }
+
+    #[test]
+    fn fifo_overrun_raises_line_status_interrupt() {
+        let mut serial = SerialPortController::new(UartType::Ns16550A, &SERIAL_STANDARD_PORTS[0..1]);
+        let mut pic = pic::Pic::new();
+        serial.write_u8(0x3F8 + REG_INTERRUPT_ID, FIFO_CONTROL_ENABLE, None, NO_DELTA);
+        serial.write_u8(0x3F8 + REG_MODEM_CONTROL, MODEM_CONTROL_OUT2, None, NO_DELTA);
+        serial.write_u8(0x3F8 + REG_INTERRUPT_ENABLE, INTERRUPT_RX_LINE_STATUS, None, NO_DELTA);
+
+        for byte in 0..FIFO_LEN as u8 {
+            serial.port[0].receive_byte(byte);
+        }
+        serial.run(&mut pic, 0.0);
+        assert!(!ir_line(&pic, 4));
+        assert_eq!(serial.read_u8(0x3F8 + REG_LINE_STATUS, NO_DELTA) & STATUS_OVERRUN_ERROR, 0);
+
+        // The byte that doesn't fit is lost and flagged as an overrun.
+        serial.port[0].receive_byte(0xFF);
+        serial.run(&mut pic, 0.0);
+        assert!(ir_line(&pic, 4));
+        assert_eq!(serial.read_u8(0x3F8 + REG_INTERRUPT_ID, NO_DELTA) & 0x0F, 0b0110);
+
+        // Reading the Line Status Register reports the overrun once and clears the interrupt.
+        assert_ne!(serial.read_u8(0x3F8 + REG_LINE_STATUS, NO_DELTA) & STATUS_OVERRUN_ERROR, 0);
+        assert_eq!(serial.read_u8(0x3F8 + REG_LINE_STATUS, NO_DELTA) & STATUS_OVERRUN_ERROR, 0);
+        serial.run(&mut pic, 0.0);
+        assert!(!ir_line(&pic, 4));
+        assert_eq!(serial.read_u8(0x3F8 + REG_RX_TX_BUFFER, NO_DELTA), 0);
+    }
+
+    /// Open a TCP bridge connected to a local listener, and return it with the accepted peer.
+    fn tcp_bridge_pair() -> (TcpBridge, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let bridge = TcpBridge::new(&address, TcpBridgeMode::Connect).unwrap();
+        let (peer, _) = listener.accept().unwrap();
+        (bridge, peer)
+    }
+
+    #[test]
+    fn tcp_bridge_keeps_bytes_the_socket_cannot_take() {
+        let (mut bridge, mut peer) = tcp_bridge_pair();
+
+        // Write until the socket buffers fill and bytes are left pending.
+        let chunk: Vec<u8> = (0..0x1_0000).map(|i| (i % 251) as u8).collect();
+        let mut sent = Vec::new();
+        while !bridge.has_pending_tx() {
+            assert!(sent.len() < 0x400_0000, "socket never reported it was full");
+            bridge.write(&chunk);
+            sent.extend_from_slice(&chunk);
+        }
+
+        // Once the peer reads, retried writes deliver everything in order.
+        let expected_len = sent.len();
+        let reader = std::thread::spawn(move || {
+            let mut received = vec![0; expected_len];
+            peer.read_exact(&mut received).unwrap();
+            received
+        });
+        while bridge.has_pending_tx() {
+            bridge.flush();
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert!(reader.join().unwrap() == sent);
+    }
+
+    #[test]
+    fn tcp_bridge_rx_queue_is_capped() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let mut serial = SerialPortController::new(UartType::Ins8250, &SERIAL_STANDARD_PORTS[0..1]);
+        serial.bridge_tcp(0, &address, TcpBridgeMode::Connect).unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+
+        peer.write_all(&[0x55; TCP_BRIDGE_RX_LIMIT * 4]).unwrap();
+
+        // Poll until the queue is full. It must never grow past the limit.
+        for _ in 0..1000 {
+            serial.update();
+            assert!(serial.rx_queue_len(0) <= TCP_BRIDGE_RX_LIMIT);
+            if serial.rx_queue_len(0) == TCP_BRIDGE_RX_LIMIT {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
         }
+        assert_eq!(serial.rx_queue_len(0), TCP_BRIDGE_RX_LIMIT);
+
+        // Delivering bytes to the guest makes room to read more.
+        let mut pic = pic::Pic::new();
+        serial.run(&mut pic, 10_000.0);
+        assert!(serial.rx_queue_len(0) < TCP_BRIDGE_RX_LIMIT);
+        serial.update();
+        assert!(serial.rx_queue_len(0) <= TCP_BRIDGE_RX_LIMIT);
     }
 }
@@ -0,0 +1,337 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    devices::sn76489.rs
+
+    Implements the Texas Instruments SN76489 Digital Complex Sound Generator,
+    as found in the Tandy 1000 and IBM PCjr.
+
+    The SN76489 provides three square wave tone generators and one noise
+    generator, each with a 4-bit attenuator in 2dB steps. The chip is
+    write-only; a latch byte selects a channel and register and supplies the
+    low 4 bits of data, and a following data byte supplies the upper 6 bits
+    of a tone period.
+
+    Primary Documentation:
+    Texas Instruments SN76489AN Data Sheet
+*/
+
+use std::collections::VecDeque;
+
+use crate::bus::{BusInterface, DeviceRunTimeUnit, IoDevice, NO_IO_BYTE};
+
+pub const SN76489_DEFAULT_IO_BASE: u16 = 0xC0;
+
+/// The Tandy 1000 clocks the SN76489 from the 3.579545Mhz colorburst crystal. The chip divides
+/// this by 16 to clock its tone counters.
+pub const SN76489_CLOCK: f64 = 3.579545;
+pub const SN76489_TICK_RATE: f64 = (SN76489_CLOCK * 1_000_000.0) / 16.0;
+
+const DEFAULT_SAMPLE_RATE: u32 = 44100;
+const MIX_SCALE: f32 = 0.25;
+
+const TONE_CT: usize = 3;
+const NOISE_CHANNEL: usize = 3;
+
+const LATCH_BIT: u8 = 0b1000_0000;
+const LATCH_VOLUME: u8 = 0b0001_0000;
+const NOISE_WHITE: u8 = 0b0000_0100;
+const NOISE_RATE_MASK: u8 = 0b0000_0011;
+const NOISE_RATE_TONE2: u8 = 0b0000_0011;
+
+/// The noise shift register is 15 bits wide. White noise feeds back the XOR of bits 0 and 1.
+const LFSR_RESET: u16 = 0x4000;
+const LFSR_WHITE_TAPS: u16 = 0x0003;
+
+/// Attenuation table in 2dB steps. An attenuation of 15 turns the channel off.
+const VOLUME_TABLE: [f32; 16] = [
+    1.0, 0.794328, 0.630957, 0.501187, 0.398107, 0.316228, 0.251189, 0.199526, 0.158489, 0.125893, 0.1, 0.079433,
+    0.063096, 0.050119, 0.039811, 0.0,
+];
+
+#[derive(Copy, Clone, Default)]
+struct ToneChannel {
+    period:  u16,
+    counter: u16,
+    output:  bool,
+}
+
+pub struct Sn76489 {
+    io_base: u16,
+    latched_channel: usize,
+    latched_volume: bool,
+
+    tones: [ToneChannel; TONE_CT],
+    attenuation: [u8; 4],
+
+    noise_control: u8,
+    noise_counter: u16,
+    noise_flipflop: bool,
+    lfsr: u16,
+
+    ticks_per_sample: f64,
+    tick_accum: f64,
+
+    sample_rate: u32,
+    sample_accum: f64,
+    samples: VecDeque<f32>,
+    max_samples: usize,
+}
+
+impl Default for Sn76489 {
+    fn default() -> Self {
+        Self {
+            io_base: SN76489_DEFAULT_IO_BASE,
+            latched_channel: 0,
+            latched_volume: false,
+            tones: [ToneChannel::default(); TONE_CT],
+            attenuation: [0x0F; 4],
+            noise_control: 0,
+            noise_counter: 0,
+            noise_flipflop: false,
+            lfsr: LFSR_RESET,
+            ticks_per_sample: SN76489_TICK_RATE / DEFAULT_SAMPLE_RATE as f64,
+            tick_accum: 0.0,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            sample_accum: 0.0,
+            samples: VecDeque::new(),
+            max_samples: DEFAULT_SAMPLE_RATE as usize / 2,
+        }
+    }
+}
+
+impl IoDevice for Sn76489 {
+    fn read_u8(&mut self, _port: u16, _delta: DeviceRunTimeUnit) -> u8 {
+        // The SN76489 is write-only.
+        NO_IO_BYTE
+    }
+
+    fn write_u8(&mut self, port: u16, data: u8, _bus: Option<&mut BusInterface>, _delta: DeviceRunTimeUnit) {
+        if port == self.io_base {
+            self.data_write(data);
+        }
+    }
+
+    fn port_list(&self) -> Vec<u16> {
+        vec![self.io_base]
+    }
+}
+
+impl Sn76489 {
+    pub fn new(io_base: Option<u16>) -> Self {
+        Self {
+            io_base: io_base.unwrap_or(SN76489_DEFAULT_IO_BASE),
+            ..Default::default()
+        }
+    }
+
+    pub fn reset(&mut self) {
+        let io_base = self.io_base;
+        let sample_rate = self.sample_rate;
+        *self = Self::new(Some(io_base));
+        self.set_sample_rate(sample_rate);
+    }
+
+    /// Set the rate at which output samples are produced. This should match the rate of the
+    /// sound player the chip is mixed into.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.ticks_per_sample = SN76489_TICK_RATE / sample_rate as f64;
+        self.max_samples = sample_rate as usize / 2;
+        self.samples.clear();
+        self.sample_accum = 0.0;
+        self.tick_accum = 0.0;
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Pop the next generated sample, if one is available.
+    pub fn pop_sample(&mut self) -> Option<f32> {
+        self.samples.pop_front()
+    }
+
+    pub fn samples_available(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Return the 10-bit tone period of the specified tone channel.
+    pub fn tone_period(&self, channel: usize) -> u16 {
+        self.tones[channel].period
+    }
+
+    /// Return the 4-bit attenuation of the specified channel. Channel 3 is the noise channel.
+    pub fn attenuation(&self, channel: usize) -> u8 {
+        self.attenuation[channel]
+    }
+
+    fn data_write(&mut self, data: u8) {
+        if data & LATCH_BIT != 0 {
+            self.latched_channel = ((data >> 5) & 0x03) as usize;
+            self.latched_volume = data & LATCH_VOLUME != 0;
+            self.register_write(data & 0x0F, false);
+        }
+        else {
+            self.register_write(data & 0x3F, true);
+        }
+    }
+
+    fn register_write(&mut self, data: u8, data_byte: bool) {
+        let channel = self.latched_channel;
+
+        if self.latched_volume {
+            self.attenuation[channel] = data & 0x0F;
+        }
+        else if channel == NOISE_CHANNEL {
+            // Any write to the noise control register resets the shift register.
+            self.noise_control = data & 0x07;
+            self.lfsr = LFSR_RESET;
+        }
+        else if data_byte {
+            let tone = &mut self.tones[channel];
+            tone.period = (tone.period & 0x00F) | ((data as u16 & 0x3F) << 4);
+        }
+        else {
+            let tone = &mut self.tones[channel];
+            tone.period = (tone.period & 0x3F0) | (data as u16 & 0x0F);
+        }
+    }
+
+    /// Run the chip for the specified number of microseconds, synthesizing the corresponding
+    /// number of output samples.
+    pub fn run(&mut self, us: f64) {
+        self.sample_accum += us * self.sample_rate as f64 / 1_000_000.0;
+        while self.sample_accum >= 1.0 {
+            let sample = self.generate_sample();
+            if self.samples.len() >= self.max_samples {
+                // Nobody is consuming our output. Drop the oldest sample.
+                self.samples.pop_front();
+            }
+            self.samples.push_back(sample);
+            self.sample_accum -= 1.0;
+        }
+    }
+
+    /// Advance the chip by one output sample's worth of ticks, averaging the output over the
+    /// interval to take the edge off aliasing of high tone frequencies.
+    fn generate_sample(&mut self) -> f32 {
+        self.tick_accum += self.ticks_per_sample;
+        let ticks = self.tick_accum as u32;
+        self.tick_accum -= ticks as f64;
+
+        if ticks == 0 {
+            return self.output() * MIX_SCALE;
+        }
+
+        let mut sum = 0.0;
+        for _ in 0..ticks {
+            self.tick();
+            sum += self.output();
+        }
+        (sum / ticks as f32) * MIX_SCALE
+    }
+
+    fn tick(&mut self) {
+        let mut tone2_edge = false;
+
+        for (i, tone) in self.tones.iter_mut().enumerate() {
+            if tone.counter > 0 {
+                tone.counter -= 1;
+            }
+            if tone.counter == 0 {
+                // A period of 0 behaves as 0x400.
+                tone.counter = if tone.period == 0 { 0x400 } else { tone.period };
+                tone.output = !tone.output;
+                if i == 2 && tone.output {
+                    tone2_edge = true;
+                }
+            }
+        }
+
+        if self.noise_control & NOISE_RATE_MASK == NOISE_RATE_TONE2 {
+            if tone2_edge {
+                self.shift_lfsr();
+            }
+        }
+        else {
+            if self.noise_counter > 0 {
+                self.noise_counter -= 1;
+            }
+            if self.noise_counter == 0 {
+                self.noise_counter = 0x10 << (self.noise_control & NOISE_RATE_MASK);
+                self.noise_flipflop = !self.noise_flipflop;
+                if self.noise_flipflop {
+                    self.shift_lfsr();
+                }
+            }
+        }
+    }
+
+    fn shift_lfsr(&mut self) {
+        let feedback = if self.noise_control & NOISE_WHITE != 0 {
+            ((self.lfsr & LFSR_WHITE_TAPS).count_ones() & 0x01) as u16
+        }
+        else {
+            self.lfsr & 0x01
+        };
+        self.lfsr = (self.lfsr >> 1) | (feedback << 14);
+    }
+
+    fn output(&self) -> f32 {
+        let mut out = 0.0;
+        for (i, tone) in self.tones.iter().enumerate() {
+            // A period of 1 holds the output high, which software uses to play PCM samples
+            // through the attenuator.
+            let high = tone.output || tone.period == 1;
+            let volume = VOLUME_TABLE[self.attenuation[i] as usize];
+            out += if high { volume } else { -volume };
+        }
+        let volume = VOLUME_TABLE[self.attenuation[NOISE_CHANNEL] as usize];
+        out += if self.lfsr & 0x01 != 0 { volume } else { -volume };
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latch_and_data_bytes_set_tone_period() {
+        let mut sn = Sn76489::new(None);
+
+        // Latch channel 1 tone with low nibble 0xE, then supply upper 6 bits 0x0F.
+        sn.data_write(0b1010_1110);
+        sn.data_write(0b0000_1111);
+        assert_eq!(sn.tone_period(1), 0x0FE);
+
+        // Latch channel 2 attenuation.
+        sn.data_write(0b1101_0011);
+        assert_eq!(sn.attenuation(2), 0x03);
+        assert_eq!(sn.tone_period(1), 0x0FE);
+    }
+}
@@ -1292,6 +1292,8 @@ impl VideoCard for VGACard {
         None
     }
 
+    fn set_lightpen(&mut self, _pos: Option<(u32, u32)>, _switch: bool) {}
+
     fn debug_tick(&mut self, _ticks: u32) {
         self.tick();
     }
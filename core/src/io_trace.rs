@@ -0,0 +1,125 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    ---------------------------------------------------------------------------
+
+    io_trace.rs
+
+    Implements a trace of CPU port IO, for discovering which ports a program
+    talks to. Accesses within a configured port range are kept in a ring buffer
+    for display and may also be written to a TraceLogger.
+
+*/
+
+use std::{collections::VecDeque, fmt, ops::RangeInclusive};
+
+use crate::{cpu_808x::CpuAddress, tracelogger::TraceLogger};
+
+pub const IO_TRACE_DEFAULT_LEN: usize = 4096;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IoDirection {
+    In,
+    Out,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct IoTraceEntry {
+    pub port: u16,
+    pub value: u8,
+    pub direction: IoDirection,
+    /// Address of the instruction that performed the access.
+    pub address: CpuAddress,
+    /// Set if no device is mapped at the port.
+    pub unmapped: bool,
+}
+
+impl fmt::Display for IoTraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.direction {
+            IoDirection::In => write!(f, "{} IN  {:04X} -> {:02X}", self.address, self.port, self.value)?,
+            IoDirection::Out => write!(f, "{} OUT {:04X} <- {:02X}", self.address, self.port, self.value)?,
+        }
+        if self.unmapped {
+            write!(f, " (unmapped)")?;
+        }
+        Ok(())
+    }
+}
+
+pub struct IoTrace {
+    ports: RangeInclusive<u16>,
+    entries: VecDeque<IoTraceEntry>,
+    capacity: usize,
+    logger: TraceLogger,
+}
+
+impl IoTrace {
+    /// Create a trace of accesses to `ports`, keeping the most recent `capacity` entries. Every
+    /// traced access is also written to `logger`.
+    pub fn new(ports: RangeInclusive<u16>, capacity: usize, logger: TraceLogger) -> Self {
+        Self {
+            ports,
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            logger,
+        }
+    }
+
+    pub fn ports(&self) -> &RangeInclusive<u16> {
+        &self.ports
+    }
+
+    pub fn record(&mut self, entry: IoTraceEntry) {
+        if !self.ports.contains(&entry.port) {
+            return;
+        }
+
+        if self.logger.is_some() {
+            self.logger.println(entry.to_string());
+        }
+
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Return the buffered entries, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &IoTraceEntry> {
+        self.entries.iter()
+    }
+
+    /// Remove and return the buffered entries, oldest first.
+    pub fn take_entries(&mut self) -> Vec<IoTraceEntry> {
+        self.entries.drain(..).collect()
+    }
+
+    pub fn flush(&mut self) {
+        self.logger.flush();
+    }
+}
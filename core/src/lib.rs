@@ -41,18 +41,24 @@ pub mod bytequeue;
 pub mod coreconfig;
 pub mod cpu_808x;
 pub mod cpu_common;
+pub mod debug_session;
 pub mod device_traits;
 pub mod device_types;
 pub mod devices;
 pub mod file_util;
 pub mod interrupt;
+pub mod io_trace;
 pub mod keys;
 pub mod machine;
 pub mod machine_config;
 pub mod memerror;
 pub mod rom_manager;
 pub mod sound;
+pub mod symbols;
 pub mod syntax_token;
+#[cfg(any(test, feature = "test_util"))]
+#[doc(hidden)]
+pub mod test_util;
 pub mod tracelogger;
 pub mod updatable;
 pub mod util;
@@ -38,38 +38,63 @@ use log;
 use anyhow::{anyhow, Error};
 use std::{
     cell::Cell,
-    collections::{HashMap, VecDeque},
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
     fs::File,
     io::{BufWriter, Write},
-    path::PathBuf,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
 };
 
 use crate::{
     breakpoints::BreakPointType,
-    bus::{BusInterface, ClockFactor, DeviceEvent, MEM_CP_BIT},
+    bus::{BusInterface, ClockFactor, DeviceEvent, WatchAccess, WatchHit, MEM_CP_BIT, OPEN_BUS_BYTE},
+    bytequeue::{ByteQueue, SliceQueue},
     coreconfig::CoreConfig,
-    cpu_808x::{Cpu, CpuAddress, CpuError, ServiceEvent, StepResult},
+    cpu_808x::{
+        mnemonic::Mnemonic,
+        Cpu,
+        CpuAddress,
+        CpuError,
+        Instruction,
+        OperandSize,
+        OperandType,
+        Register16,
+        Segment,
+        ServiceEvent,
+        StepResult,
+    },
     cpu_common::{CpuOption, CpuType, TraceMode},
-    device_traits::videocard::{VideoCard, VideoCardId, VideoCardInterface, VideoCardState, VideoOption},
+    cpu_validator::CycleState,
+    debug_session::{DebugSession, SessionSymbol, SessionWatchpoint},
+    device_traits::videocard::{DisplayMode, VideoCard, VideoCardId, VideoCardInterface, VideoCardState, VideoOption},
+    device_types::media::{MediaKind, MediaSlot},
     devices::{
         dma::DMAControllerStringState,
-        fdc::FloppyController,
+        fdc::{FloppyController, FDC_MAX_DRIVES},
+        game_port::JoystickState,
         hdc::HardDiskController,
-        keyboard::KeyboardModifiers,
+        keyboard::{self, KeyboardModifiers},
         mouse::Mouse,
-        pic::PicStringState,
-        pit::{self, PitDisplayState},
+        pic::{PicSnapshot, PicStringState},
+        pit::{self, PitChannelState, PitDisplayState},
+        post_card::PostCard,
         ppi::PpiStringState,
+        serial::TcpBridgeMode,
     },
+    io_trace::{IoTrace, IoTraceEntry},
     keys::MartyKey,
     machine_config::{get_machine_descriptor, MachineConfiguration, MachineDescriptor},
     machine_types::MachineType,
-    sound::{SoundPlayer, BUFFER_MS, VOLUME_ADJUST},
-    tracelogger::TraceLogger,
+    sound::{SoundPlayer, SoundSource, BUFFER_MS, VOLUME_ADJUST},
+    symbols,
+    syntax_token::{SyntaxFormatType, SyntaxToken, SyntaxTokenVec},
+    tracelogger::{TraceLogger, TraceTarget, TraceTargets},
+    util,
 };
 
 use ringbuf::{Consumer, Producer, RingBuffer};
-use crate::machine_types::OnHaltBehavior;
+use crate::machine_types::{OnHaltBehavior, WatchdogConfig};
 
 pub const STEP_OVER_TIMEOUT: u32 = 320000;
 
@@ -77,6 +102,65 @@ pub const STEP_OVER_TIMEOUT: u32 = 320000;
 
 pub const MAX_MEMORY_ADDRESS: usize = 0xFFFFF;
 
+// Pole of the DC blocking filter applied to PC speaker output. Gives a cutoff of roughly 35Hz at 44.1KHz.
+const SPEAKER_DC_BLOCK_R: f32 = 0.995;
+
+// Range of clock factors considered by set_cpu_mhz().
+const MAX_CLOCK_DIVISOR: u8 = 8;
+const MAX_CLOCK_MULTIPLIER: u8 = 4;
+
+// The BIOS data area word checked at boot to skip the memory test, and the value that marks a
+// warm boot.
+const BIOS_RESET_FLAG_ADDR: usize = 0x472;
+const BIOS_RESET_FLAG_WARM: u16 = 0x1234;
+
+// The flat address of the 8088 reset vector, FFFF:0000.
+const RESET_VECTOR_ADDR: u32 = 0xFFFF0;
+// Address the bootstrap loader reads the boot sector into and jumps to.
+const BOOT_SECTOR_ADDR: u32 = 0x07C00;
+
+// The time a deterministic machine's RTC starts at unless one is configured, 1985-01-01 00:00:00.
+const DETERMINISTIC_RTC_TIME: i64 = 473_385_600;
+
+// Delay between injected scancodes, in microseconds. This gives the BIOS keyboard handler time
+// to read each scancode, as the PPI has no buffer.
+const SCANCODE_INJECT_INTERVAL: f64 = 20_000.0;
+
+// Size of the window of code, in bytes, the watchdog considers a single loop.
+const WATCHDOG_WINDOW: u32 = 0x100;
+// Number of cycles the CPU may spend in a hardware interrupt handler before the watchdog treats it
+// as having left the loop.
+const WATCHDOG_ISR_CYCLES: u64 = 20_000;
+// Number of instructions to disassemble when the watchdog trips.
+const WATCHDOG_DISASSEMBLY_LEN: usize = 8;
+
+/// Find the CPU clock factor of the specified system crystal that comes closest to the requested
+/// clock speed. Returns the factor and the resulting clock speed in MHz.
+fn closest_clock_factor(crystal: f64, mhz: f64) -> Result<(ClockFactor, f64), Error> {
+    if !mhz.is_finite() || mhz <= 0.0 {
+        return Err(anyhow!("Invalid CPU clock speed: {} MHz", mhz));
+    }
+
+    let candidates = (1..=MAX_CLOCK_DIVISOR)
+        .map(ClockFactor::Divisor)
+        .chain((2..=MAX_CLOCK_MULTIPLIER).map(ClockFactor::Multiplier));
+
+    let mut best_factor = ClockFactor::Divisor(1);
+    let mut best_mhz = 0.0;
+    for factor in candidates {
+        let factor_mhz = match factor {
+            ClockFactor::Divisor(n) => crystal / (n as f64),
+            ClockFactor::Multiplier(n) => crystal * (n as f64),
+        };
+        if best_mhz == 0.0 || (factor_mhz - mhz).abs() < (best_mhz - mhz).abs() {
+            best_factor = factor;
+            best_mhz = factor_mhz;
+        }
+    }
+
+    Ok((best_factor, best_mhz))
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct KeybufferEntry {
     pub keycode:   MartyKey,
@@ -85,11 +169,142 @@ pub struct KeybufferEntry {
     pub translate: bool,
 }
 
+/// Execution statistics measured over the most recent sampling interval. See
+/// [Machine::sample_stats].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MachineStats {
+    /// Effective CPU clock speed in MHz, measured from the CPU cycles actually executed.
+    pub effective_mhz: f64,
+    /// CPU cycles executed per second.
+    pub cps: u64,
+    /// Instructions executed per second.
+    pub ips: u64,
+    /// Frames completed by the primary video card per second.
+    pub emulated_fps: u32,
+    /// Frames presented by the frontend per second.
+    pub host_fps: u32,
+}
+
+/// The reason the guest is considered to have stopped. See [MachineEvent::GuestHalted].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GuestHaltReason {
+    /// The CPU executed HLT with interrupts disabled. Only a reset can resume it.
+    HaltInterruptsDisabled,
+    /// Execution reached the reset vector without the machine being reset. This is how software
+    /// reboots the machine, and is also where runaway code tends to end up; it is the closest
+    /// thing the 8088 has to a triple fault.
+    ResetVector,
+    /// The bootstrap loader (INT 19h) was called after the machine had already booted, as some
+    /// software does to exit or restart.
+    Bootstrap,
+    /// The watchdog detected the CPU stuck in a loop.
+    Hung,
+}
+
+impl fmt::Display for GuestHaltReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GuestHaltReason::HaltInterruptsDisabled => write!(f, "HLT with interrupts disabled"),
+            GuestHaltReason::ResetVector => write!(f, "jump to reset vector"),
+            GuestHaltReason::Bootstrap => write!(f, "bootstrap loader called after boot"),
+            GuestHaltReason::Hung => write!(f, "CPU stuck in a loop"),
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum MachineEvent {
     CheckpointHit(usize, u32),
+    /// A breakpoint was hit at the specified address.
+    BreakpointHit(CpuAddress),
+    /// A watchpoint was triggered by the instruction at the specified address.
+    WatchpointHit(WatchHit, CpuAddress),
+    /// The CPU executed HLT with interrupts disabled. The guest can't continue.
     Halted,
     Reset,
+    /// The watchdog detected the CPU stuck in a loop at the specified address.
+    WatchdogTripped(CpuAddress),
+    /// A disk was loaded into the specified floppy drive.
+    FloppyInserted(usize),
+    /// The disk in the specified floppy drive was ejected.
+    FloppyEjected(usize),
+    /// The display mode of the primary video card changed.
+    VideoModeChanged(DisplayMode),
+    /// The tone output by the PC speaker changed, in Hz, or None if the speaker went silent.
+    SpeakerFrequencyChanged(Option<u32>),
+    /// The guest has stopped or restarted itself for the specified reason, at the specified
+    /// address. This is sent regardless of the configured halt behavior, so that an embedding
+    /// application can detect a dead machine and reset it.
+    GuestHalted(GuestHaltReason, CpuAddress),
+}
+
+/// Device state that is compared against on each call to Machine::drain_events() to detect
+/// changes that should be reported as MachineEvents.
+#[derive(Copy, Clone, Default)]
+struct DeviceEventState {
+    floppy_loaded: [bool; FDC_MAX_DRIVES],
+    display_mode: Option<DisplayMode>,
+    speaker_freq: Option<u32>,
+}
+
+/// The number of bytes read at CS:IP when decoding the current instruction. This covers the longest
+/// 8088 instruction with several prefixes.
+const DECODE_WINDOW_LEN: u16 = 16;
+
+/// A memory operand of a decoded instruction, resolved against the current CPU state.
+#[derive(Clone, Debug)]
+pub struct MemoryOperand {
+    /// The segment the operand is addressed through, after any segment override.
+    pub segment: Segment,
+    pub segment_value: u16,
+    /// The effective address of the operand within its segment.
+    pub offset: u16,
+    pub linear_address: u32,
+    /// The bytes currently at the operand's address: one for byte operands, two for word operands
+    /// and four for far pointers.
+    pub bytes: Vec<u8>,
+}
+
+/// The instruction at CS:IP, as returned by Machine::decode_current().
+#[derive(Clone)]
+pub struct DecodedInstruction {
+    pub address: CpuAddress,
+    pub opcode: u8,
+    /// The raw bytes of the instruction, including any prefixes.
+    pub instruction_bytes: Vec<u8>,
+    /// The decoded instruction, or None if the opcode is invalid.
+    pub instruction: Option<Instruction>,
+    /// Tokens for the complete disassembled instruction.
+    pub tokens: Vec<SyntaxToken>,
+    pub operand1: Vec<SyntaxToken>,
+    pub operand2: Vec<SyntaxToken>,
+    /// The explicit memory operand of the instruction, if it has one. Implicit memory operands,
+    /// such as those of string and stack instructions, are not resolved.
+    pub memory_operand: Option<MemoryOperand>,
+}
+
+/// The outcome of a call to Machine::run_until(). Each variant carries the number of CPU cycles
+/// executed during the call.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RunResult {
+    /// The predicate returned true.
+    PredicateMet(u64),
+    /// The cycle limit was reached before the predicate returned true.
+    CycleLimitReached(u64),
+    /// Execution stopped on a breakpoint.
+    BreakpointHit(u64),
+    /// The CPU halted or the program ended.
+    Halted(u64),
+}
+
+/// The kind of reset performed by Machine::reset().
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResetKind {
+    /// Equivalent to Ctrl-Alt-Del. The CPU and devices are reset, but RAM is kept and the BIOS
+    /// warm boot flag is set so that the memory test is skipped.
+    Warm,
+    /// Equivalent to power cycling the machine. RAM is cleared and ROMs and patches are reloaded.
+    Cold,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -112,6 +327,8 @@ pub enum ExecutionState {
     Paused,
     BreakpointHit,
     Running,
+    /// Running until the primary video card completes its next frame, then pausing.
+    StepFrame,
     Halted,
 }
 
@@ -122,10 +339,23 @@ pub enum ExecutionOperation {
     Pause,
     Step,
     StepOver,
+    StepFrame,
     Run,
     Reset,
 }
 
+/// Tracks how long the CPU has been executing within a small window of code. Time spent in
+/// hardware interrupt handlers that return to the window counts as time in the window.
+#[derive(Default)]
+struct WatchdogState {
+    window_base: u32,
+    cycles: u64,
+    serviced: u64,
+    interrupted: bool,
+    isr_cycles: Option<u64>,
+    tripped: bool,
+}
+
 #[derive(Copy, Clone, Debug, Default)]
 pub struct DelayParams {
     pub dram_delay: u32,
@@ -163,8 +393,8 @@ impl ExecutionControl {
     pub fn set_op(&mut self, op: ExecutionOperation) {
         match op {
             ExecutionOperation::Pause => {
-                // Can only pause if Running
-                if let ExecutionState::Running = self.state {
+                // Can only pause if Running or stepping a frame
+                if let ExecutionState::Running | ExecutionState::StepFrame = self.state {
                     self.state = ExecutionState::Paused;
                     self.op.set(op);
                 }
@@ -181,6 +411,12 @@ impl ExecutionControl {
                     self.op.set(op);
                 }
             }
+            ExecutionOperation::StepFrame => {
+                // Can only Step Frame if paused / breakpointhit
+                if let ExecutionState::Paused | ExecutionState::BreakpointHit = self.state {
+                    self.op.set(op);
+                }
+            }
             ExecutionOperation::Run => {
                 // Can only Run if paused / breakpointhit
                 if let ExecutionState::Paused | ExecutionState::BreakpointHit = self.state {
@@ -216,6 +452,9 @@ pub struct PitData {
     logging_triggered: bool,
     fractional_part: f64,
     next_sample_size: usize,
+    // DC blocking filter state. The PC speaker is AC coupled, so a constant speaker level is silent.
+    dc_last_in: f32,
+    dc_last_out: f32,
 }
 
 #[derive(Clone, Default, Debug)]
@@ -269,6 +508,19 @@ impl MachineRomManifest {
     }
 }
 
+/// The arguments to [Machine::new], bundled for constructors that build on it.
+pub struct MachineParams<'a> {
+    pub core_config: &'a dyn CoreConfig,
+    pub machine_config: MachineConfiguration,
+    pub machine_type: MachineType,
+    pub machine_desc: MachineDescriptor,
+    pub trace_mode: TraceMode,
+    pub trace_logger: TraceLogger,
+    pub sound_player: Option<SoundPlayer>,
+    pub rom_manifest: MachineRomManifest,
+    pub keyboard_layout_file: Option<PathBuf>,
+}
+
 #[derive(Default)]
 pub struct MachineBuilder<'a> {
     mtype: Option<MachineType>,
@@ -281,6 +533,7 @@ pub struct MachineBuilder<'a> {
     sound_player: Option<SoundPlayer>,
     sound_override: Option<bool>,
     keyboard_layout_file: Option<PathBuf>,
+    seed: Option<u64>,
 }
 
 impl<'a> MachineBuilder<'a> {
@@ -344,6 +597,12 @@ impl<'a> MachineBuilder<'a> {
         self
     }
 
+    /// Build a deterministic machine using the specified seed. See [Machine::new_deterministic].
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
     pub fn build(mut self) -> Result<Machine, Error> {
         let core_config = self.core_config.ok_or(anyhow!("No core configuration specified"))?;
         let machine_config = self
@@ -361,17 +620,22 @@ impl<'a> MachineBuilder<'a> {
             }
         }
 
-        Ok(Machine::new(
-            *core_config,
+        let params = MachineParams {
+            core_config: *core_config,
             machine_config,
             machine_type,
             machine_desc,
-            self.trace_mode,
+            trace_mode: self.trace_mode,
             trace_logger,
-            self.sound_player,
+            sound_player: self.sound_player,
             rom_manifest,
-            self.keyboard_layout_file,
-        ))
+            keyboard_layout_file: self.keyboard_layout_file,
+        };
+
+        match self.seed {
+            Some(seed) => Ok(Machine::new_deterministic(seed, params)),
+            None => Ok(Machine::from_params(params)),
+        }
     }
 }
 
@@ -384,25 +648,46 @@ pub struct Machine {
     sound_player: Option<SoundPlayer>,
     rom_manifest: MachineRomManifest,
     load_bios: bool,
+    patch_enabled: bool,
     cpu: Cpu,
     speaker_buf_producer: Producer<u8>,
     pit_data: PitData,
     debug_snd_file: Option<File>,
     kb_buf: VecDeque<KeybufferEntry>,
+    scancode_queue: VecDeque<u8>,
+    scancode_us_accum: f64,
     error: bool,
     error_str: Option<String>,
     turbo_bit: bool,
     turbo_button: bool,
     cpu_factor: ClockFactor,
     next_cpu_factor: ClockFactor,
+    turbo_factor: ClockFactor,
     cpu_cycles: u64,
     cpu_instructions: u64,
+    halt_cycles: u32,
+    step_frame_start: Option<u64>,
     system_ticks: u64,
     checkpoint_map: HashMap<u32, usize>,
     patch_map: HashMap<u32, usize>,
+    symbols: BTreeMap<u32, String>,
     events: Vec<MachineEvent>,
+    device_event_state: DeviceEventState,
+    last_checkpoint: Option<usize>,
+    last_watch_hit: Option<(WatchHit, CpuAddress)>,
+    stats: MachineStats,
+    stats_sample: (u64, u64, u64),
     reload_pending: bool,
     halt_behavior: OnHaltBehavior,
+    seed: Option<u64>,
+    fast_boot: bool,
+    // Whether the BIOS is still in POST. Cleared when the bootstrap loader is called.
+    in_post: bool,
+    mute_post_beep: bool,
+    // Set on reset, so that the first instruction at the reset vector isn't reported as a runaway.
+    at_reset: bool,
+    watchdog: WatchdogConfig,
+    watchdog_state: WatchdogState,
 }
 
 impl Machine {
@@ -446,6 +731,15 @@ impl Machine {
         #[cfg(feature = "cpu_validator")]
         use crate::cpu_validator::ValidatorMode;
 
+        // A "cpu" trace target overrides the trace log passed in and turns tracing on.
+        let mut trace_targets = TraceTargets::new(core_config.get_trace_targets());
+        let mut trace_logger = trace_logger;
+        let mut trace_on = core_config.get_cpu_trace_on();
+        if trace_targets.includes(TraceTarget::Cpu) {
+            trace_logger = trace_targets.logger_for(TraceTarget::Cpu);
+            trace_on = true;
+        }
+
         //noinspection ALL
         let mut cpu = Cpu::new(
             CpuType::Intel8088,
@@ -461,7 +755,7 @@ impl Machine {
             core_config.get_validator_baud().unwrap_or(1_000_000),
         );
 
-        cpu.set_option(CpuOption::TraceLoggingEnabled(core_config.get_cpu_trace_on()));
+        cpu.set_option(CpuOption::TraceLoggingEnabled(trace_on));
 
         // Set bus options from core configuration now that CPU has created the bus
         cpu.bus_mut().set_options(core_config.get_title_hacks());
@@ -485,6 +779,8 @@ impl Machine {
             logging_triggered: false,
             fractional_part: pit_ticks_per_sample.fract(),
             next_sample_size: pit_ticks_per_sample.trunc() as usize,
+            dc_last_in: 0.0,
+            dc_last_out: 0.0,
         };
 
         // open a file to write the sound to
@@ -507,6 +803,7 @@ impl Machine {
         let have_audio = core_config.get_audio_enabled() && sound_player.is_some();
 
         // Install devices
+        cpu.bus_mut().set_trace_targets(trace_targets);
         if let Err(err) = cpu
             .bus_mut()
             .install_devices(&machine_desc, &machine_config, have_audio)
@@ -514,6 +811,14 @@ impl Machine {
             log::error!("Failed to install devices: {}", err);
         }
 
+        // Sound devices synthesize directly at the sound player's sample rate.
+        if let Some(adlib) = cpu.bus_mut().adlib_mut() {
+            adlib.set_sample_rate(sample_rate);
+        }
+        if let Some(sn76489) = cpu.bus_mut().sn76489_mut() {
+            sn76489.set_sample_rate(sample_rate);
+        }
+
         // Load keyboard translation file if specified.
         if let Some(kb_translation_path) = keyboard_layout_file {
             if let Some(keyboard) = cpu.bus_mut().keyboard_mut() {
@@ -551,14 +856,31 @@ impl Machine {
             //cpu.set_reset_vector(CpuAddress::Segmented(rom_entry_point.0, rom_entry_point.1));
         }
 
+        // A configured turbo clock replaces the machine's default turbo factor.
+        let turbo_factor = match core_config.get_machine_turbo_mhz() {
+            Some(mhz) => match closest_clock_factor(machine_desc.system_crystal, mhz) {
+                Ok((factor, _)) => factor,
+                Err(e) => {
+                    log::warn!("Ignoring turbo clock: {}", e);
+                    machine_desc.cpu_turbo_factor
+                }
+            },
+            None => machine_desc.cpu_turbo_factor,
+        };
+
         // Set CPU clock divisor/multiplier
         let cpu_factor = if core_config.get_machine_turbo() {
-            machine_desc.cpu_turbo_factor
+            turbo_factor
         }
         else {
             machine_desc.cpu_factor
         };
 
+        if let Some((segment, offset)) = core_config.get_entry_point() {
+            log::debug!("Using entry point {:04X}:{:04X}", segment, offset);
+            cpu.set_reset_vector(CpuAddress::Segmented(segment, offset));
+        }
+
         cpu.emit_header();
         cpu.reset();
 
@@ -569,7 +891,7 @@ impl Machine {
             patch_map = rom_manifest.patch_map();
         }
 
-        Machine {
+        let mut machine = Machine {
             machine_type,
             machine_desc,
             machine_config,
@@ -577,25 +899,107 @@ impl Machine {
             sound_player,
             rom_manifest,
             load_bios: !core_config.get_machine_noroms(),
+            patch_enabled: core_config.get_patch_enabled(),
             cpu,
             speaker_buf_producer,
             pit_data,
             debug_snd_file: None,
             kb_buf: VecDeque::new(),
+            scancode_queue: VecDeque::new(),
+            scancode_us_accum: 0.0,
             error: false,
             error_str: None,
             turbo_bit: false,
             turbo_button: false,
             cpu_factor,
             next_cpu_factor: cpu_factor,
+            turbo_factor,
             cpu_cycles: 0,
             cpu_instructions: 0,
+            halt_cycles: 0,
+            step_frame_start: None,
             system_ticks: 0,
             checkpoint_map,
             patch_map,
+            symbols: BTreeMap::new(),
             events: Vec::new(),
+            device_event_state: Default::default(),
+            last_checkpoint: None,
+            last_watch_hit: None,
+            stats: Default::default(),
+            stats_sample: (0, 0, 0),
             reload_pending: false,
             halt_behavior: core_config.get_halt_behavior(),
+            seed: None,
+            fast_boot: core_config.get_fast_boot(),
+            in_post: true,
+            mute_post_beep: core_config.get_audio_mute_post_beep(),
+            at_reset: true,
+            watchdog: core_config.get_watchdog(),
+            watchdog_state: Default::default(),
+        };
+        machine.set_fast_boot_flag();
+        machine
+    }
+
+    /// Create a machine from a bundle of [Machine::new] arguments.
+    pub fn from_params(params: MachineParams) -> Machine {
+        Machine::new(
+            params.core_config,
+            params.machine_config,
+            params.machine_type,
+            params.machine_desc,
+            params.trace_mode,
+            params.trace_logger,
+            params.sound_player,
+            params.rom_manifest,
+            params.keyboard_layout_file,
+        )
+    }
+
+    /// Create a machine whose state is fully determined by `seed` and its inputs, for use by
+    /// headless test harnesses. Running two such machines with the same seed, configuration and
+    /// inputs produces identical state.
+    ///
+    /// The seed is consumed by:
+    ///  - Conventional memory, which is filled with a seeded pattern instead of being zeroed.
+    ///  - The CPU's random number generator.
+    ///
+    /// The seed is reapplied on every reset. An RTC without a configured fixed time would start
+    /// from the host clock, so it is pinned to start at DETERMINISTIC_RTC_TIME. All other devices
+    /// are already deterministic, as the core advances them only by emulated time.
+    pub fn new_deterministic(seed: u64, params: MachineParams) -> Machine {
+        let mut machine = Machine::from_params(params);
+        machine.seed = Some(seed);
+        machine.apply_seed();
+        if let Some(rtc) = machine.cpu.bus_mut().rtc_mut() {
+            if rtc.fixed_time().is_none() {
+                rtc.set_fixed_time(Some(DETERMINISTIC_RTC_TIME));
+            }
+        }
+        machine.set_fast_boot_flag();
+        machine
+    }
+
+    /// Return the seed of a deterministic machine, if any.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    fn apply_seed(&mut self) {
+        if let Some(seed) = self.seed {
+            self.cpu.randomize_seed(seed);
+            self.cpu.bus_mut().seed_memory(seed);
+        }
+    }
+
+    /// If fast boot is enabled, set the BIOS reset flag so that the BIOS treats the next boot as
+    /// a warm boot and skips its memory test. This must be done after memory is cleared.
+    ///
+    /// Not all BIOSes check the flag, so fast boot may have no effect on some machines.
+    fn set_fast_boot_flag(&mut self) {
+        if self.fast_boot {
+            _ = self.cpu.bus_mut().write_u16(BIOS_RESET_FLAG_ADDR, BIOS_RESET_FLAG_WARM, 0);
         }
     }
 
@@ -631,6 +1035,36 @@ impl Machine {
         Ok(())
     }
 
+    /// Replace the machine's ROMs with those of a new manifest, such as one created for a
+    /// different ROM set, without reconstructing the machine. The previous ROMs are unmapped,
+    /// checkpoints and patch triggers are rearmed for the new manifest, and the machine is cold
+    /// reset, which copies the new ROMs into memory.
+    pub fn reload_roms(&mut self, rom_manifest: MachineRomManifest) {
+        let bus = self.cpu.bus_mut();
+        for rom in self.rom_manifest.roms.iter() {
+            bus.remove_rom(rom.addr as usize, rom.data.len());
+        }
+
+        bus.clear_checkpoints();
+        if self.load_bios {
+            bus.install_checkpoints(&rom_manifest.checkpoints);
+            if self.patch_enabled {
+                bus.install_patch_checkpoints(&rom_manifest.patches);
+            }
+        }
+
+        self.checkpoint_map = rom_manifest.checkpoint_map();
+        self.patch_map = if self.patch_enabled {
+            rom_manifest.patch_map()
+        }
+        else {
+            HashMap::new()
+        };
+        self.rom_manifest = rom_manifest;
+        self.reload_pending = false;
+        self.reset(ResetKind::Cold);
+    }
+
     pub fn change_state(&mut self, new_state: MachineState) {
         match (self.state, new_state) {
             (MachineState::Off, MachineState::On) => {
@@ -639,12 +1073,12 @@ impl Machine {
             }
             (MachineState::On, MachineState::Off) => {
                 log::debug!("Turning machine off...");
-                self.reset();
+                self.reset(ResetKind::Cold);
                 self.state = new_state;
             }
             (MachineState::On, MachineState::Rebooting) => {
                 log::debug!("Rebooting machine...");
-                self.reset();
+                self.reset(ResetKind::Cold);
                 self.state = MachineState::On;
             }
             (MachineState::On, MachineState::Paused) => {
@@ -667,10 +1101,62 @@ impl Machine {
         self.events.pop()
     }
 
+    /// Return all pending machine events in the order they occurred, and clear the queue.
+    /// Changes in device state since the last call, such as a floppy being inserted or a new
+    /// video mode, are detected here, so this should be called once per frame.
+    pub fn drain_events(&mut self) -> Vec<MachineEvent> {
+        self.collect_device_events();
+        std::mem::take(&mut self.events)
+    }
+
+    /// Compare watched device state against the state at the last call, queuing an event for
+    /// anything that changed.
+    fn collect_device_events(&mut self) {
+        let last = self.device_event_state;
+        let mut current = DeviceEventState::default();
+        let bus = self.cpu.bus();
+
+        if let Some(fdc) = bus.fdc() {
+            for (drive, loaded) in current.floppy_loaded.iter_mut().enumerate() {
+                *loaded = fdc.is_disk_loaded(drive);
+            }
+        }
+        current.display_mode = bus.primary_video().map(|video| video.get_display_mode());
+        if let (Some(pit), Some(ppi)) = (bus.pit(), bus.ppi()) {
+            if ppi.get_pb0_state() && ppi.get_pb1_state() {
+                current.speaker_freq = pit.get_channel_frequency(2).map(|freq| freq.round() as u32);
+            }
+        }
+
+        for (drive, (was, is)) in last.floppy_loaded.iter().zip(current.floppy_loaded.iter()).enumerate() {
+            match (was, is) {
+                (false, true) => self.events.push(MachineEvent::FloppyInserted(drive)),
+                (true, false) => self.events.push(MachineEvent::FloppyEjected(drive)),
+                _ => {}
+            }
+        }
+        if let Some(mode) = current.display_mode {
+            if last.display_mode != Some(mode) {
+                self.events.push(MachineEvent::VideoModeChanged(mode));
+            }
+        }
+        if current.speaker_freq != last.speaker_freq {
+            self.events.push(MachineEvent::SpeakerFrequencyChanged(current.speaker_freq));
+        }
+
+        self.device_event_state = current;
+    }
+
     pub fn get_cpu_factor(&mut self) -> ClockFactor {
         self.cpu_factor
     }
 
+    /// Return the address the CPU starts executing at after a reset. This is the hardware reset
+    /// vector unless an entry point was configured or a program was loaded.
+    pub fn entry_point(&self) -> CpuAddress {
+        self.cpu.get_reset_vector()
+    }
+
     pub fn load_program(&mut self, program: &[u8], program_seg: u16, program_ofs: u16) -> Result<(), bool> {
         let location = Cpu::calc_linear_address(program_seg, program_ofs);
 
@@ -679,6 +1165,7 @@ impl Machine {
         self.cpu
             .set_reset_vector(CpuAddress::Segmented(program_seg, program_ofs));
         self.cpu.reset();
+        self.at_reset = true;
 
         self.cpu
             .set_end_address(((location as usize) + program.len()) & 0xFFFFF);
@@ -690,10 +1177,375 @@ impl Machine {
         self.cpu.bus()
     }
 
+    /// Set a function to be called with the CPU's state every cycle. See [Cpu::set_cycle_observer].
+    pub fn set_cycle_observer(&mut self, observer: Box<dyn FnMut(&CycleState)>) {
+        self.cpu.set_cycle_observer(observer);
+    }
+
+    pub fn clear_cycle_observer(&mut self) {
+        self.cpu.clear_cycle_observer();
+    }
+
     pub fn bus_mut(&mut self) -> &mut BusInterface {
         self.cpu.bus_mut()
     }
 
+    /// Read a block of guest memory without side effects. Memory-mapped devices are peeked rather
+    /// than read. Reads past the end of the address space are truncated.
+    pub fn read_memory(&self, address: usize, len: usize) -> Vec<u8> {
+        self.cpu.bus().peek_range(address, len)
+    }
+
+    /// Format a block of guest memory for a hex viewer. Each row holds up to 16 bytes and consists
+    /// of an address token, followed by hex value tokens and then ASCII value tokens. If any bytes
+    /// in the row have a symbol, a final label token lists their names.
+    pub fn dump_memory(&self, address: usize, len: usize) -> Vec<Vec<SyntaxToken>> {
+        let mut rows = Vec::new();
+        let mut row_address = address;
+
+        for row in self.read_memory(address, len).chunks(16) {
+            let mut line_vec = Vec::with_capacity(1 + row.len() * 2);
+
+            line_vec.push(SyntaxToken::MemoryAddressFlat(
+                row_address as u32,
+                format!("{:05X}", row_address),
+            ));
+
+            for (i, byte) in row.iter().enumerate() {
+                line_vec.push(SyntaxToken::MemoryByteHexValue(
+                    (row_address + i) as u32,
+                    *byte,
+                    format!("{:02X}", *byte),
+                    false,
+                    0,
+                ));
+            }
+
+            for (i, byte) in row.iter().enumerate() {
+                let char_str = match byte {
+                    32..=126 => format!("{}", *byte as char),
+                    _ => ".".to_string(),
+                };
+                line_vec.push(SyntaxToken::MemoryByteAsciiValue(
+                    (row_address + i) as u32,
+                    *byte,
+                    char_str,
+                    0,
+                ));
+            }
+
+            let names: Vec<&str> = (0..row.len())
+                .filter_map(|i| self.symbol_at((row_address + i) as u32))
+                .collect();
+            if !names.is_empty() {
+                line_vec.push(SyntaxToken::Label(names.join(", ")));
+            }
+
+            rows.push(line_vec);
+            row_address += 16;
+        }
+
+        rows
+    }
+
+    /// Update the watchdog with the instruction just executed at `flat_address`. The watchdog
+    /// trips if the CPU stays within a small window of code for longer than the configured
+    /// threshold. Leaving the window to service a hardware interrupt does not count as leaving the
+    /// loop, as long as the handler returns to it. Returns true if the watchdog tripped on this
+    /// instruction. It will not trip again until the CPU leaves the loop.
+    fn update_watchdog(&mut self, flat_address: u32, cycles: u32) -> bool {
+        let serviced = self.cpu.bus().pic().as_ref().map_or(0, |pic| pic.serviced_count());
+
+        let state = &mut self.watchdog_state;
+        state.interrupted |= serviced != state.serviced;
+        state.serviced = serviced;
+        state.cycles += cycles as u64;
+
+        if flat_address.abs_diff(state.window_base) <= WATCHDOG_WINDOW {
+            state.isr_cycles = None;
+            state.interrupted = false;
+        }
+        else {
+            // Left the window. If an interrupt was taken, give its handler time to return.
+            let isr_cycles = match (state.isr_cycles, state.interrupted) {
+                (Some(isr_cycles), _) => Some(isr_cycles + cycles as u64),
+                (None, true) => Some(cycles as u64),
+                (None, false) => None,
+            };
+            match isr_cycles {
+                Some(isr_cycles) if isr_cycles <= WATCHDOG_ISR_CYCLES => {
+                    state.isr_cycles = Some(isr_cycles);
+                    state.interrupted = false;
+                }
+                _ => {
+                    *state = WatchdogState {
+                        window_base: flat_address,
+                        serviced,
+                        ..Default::default()
+                    };
+                }
+            }
+            return false;
+        }
+
+        if state.tripped || state.cycles < self.watchdog.threshold_mcycles as u64 * 1_000_000 {
+            return false;
+        }
+        state.tripped = true;
+
+        let csip = self.cpu.get_csip();
+        let listing = self
+            .disassemble(csip, WATCHDOG_DISASSEMBLY_LEN)
+            .into_iter()
+            .map(|(addr, tokens)| format!("{} {}", addr, SyntaxTokenVec(tokens)))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        log::warn!(
+            "Watchdog: CPU has been looping near {} for over {} million cycles:\n{}",
+            csip,
+            self.watchdog.threshold_mcycles,
+            listing
+        );
+        self.events.push(MachineEvent::WatchdogTripped(csip));
+        self.events.push(MachineEvent::GuestHalted(GuestHaltReason::Hung, csip));
+        true
+    }
+
+    /// Decode up to `instr_count` instructions starting at `address` without executing them.
+    /// Each entry holds the address of the instruction and tokens for its bytes and disassembly.
+    ///
+    /// If a segmented address is given, decoding follows segment wraparound. Decoding stops early
+    /// if an instruction would run past the end of the address space.
+    pub fn disassemble(&mut self, address: CpuAddress, instr_count: usize) -> Vec<(CpuAddress, Vec<SyntaxToken>)> {
+        let mut listing = Vec::with_capacity(instr_count);
+        let mut addr = address;
+
+        for _ in 0..instr_count {
+            let (size, mut tokens) = match self.decode_at(addr) {
+                Some(decoded) => decoded,
+                None => break,
+            };
+            if let Some(name) = self.symbol_at(u32::from(addr)) {
+                tokens.insert(0, SyntaxToken::Label(format!("{}:", name)));
+            }
+            listing.push((addr, tokens));
+            addr = Machine::advance_address(addr, size);
+        }
+
+        listing
+    }
+
+    /// Write a disassembled listing of the memory range `start..=end` to the file at `path`.
+    /// Each line holds the address, raw instruction bytes and disassembly of one instruction.
+    ///
+    /// Decoding is linear from `start`, so data mixed in with code will be decoded as instructions.
+    /// Invalid opcodes and instructions that extend past `end` are marked with a trailing comment.
+    /// Returns the number of instructions written.
+    pub fn export_disassembly(&mut self, start: CpuAddress, end: CpuAddress, path: &str) -> Result<usize, Error> {
+        let start_flat = u32::from(start) as usize;
+        let end_flat = u32::from(end) as usize;
+        if end_flat < start_flat {
+            return Err(anyhow!("End address {} precedes start address {}", end, start));
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "; Disassembly of {} - {}", start, end)?;
+
+        // Count remaining bytes rather than comparing addresses, so that a segmented range that
+        // wraps within its segment still terminates.
+        let mut remaining = end_flat - start_flat + 1;
+        let mut addr = start;
+        let mut instr_count = 0;
+
+        while remaining > 0 {
+            let (size, tokens) = match self.decode_at(addr) {
+                Some(decoded) => decoded,
+                None => {
+                    writeln!(writer, "; listing truncated at end of address space")?;
+                    break;
+                }
+            };
+
+            if let Some(name) = self.symbol_at(u32::from(addr)) {
+                writeln!(writer, "{}:", name)?;
+            }
+
+            let invalid = matches!(tokens.first(), Some(SyntaxToken::ErrorString(_)));
+            let mut line = format!("{} {}", addr, SyntaxTokenVec(tokens));
+            if invalid {
+                line.push_str(" ; invalid opcode");
+            }
+            else if size > remaining {
+                line.push_str(" ; runs past end of range");
+            }
+            writeln!(writer, "{}", line)?;
+
+            instr_count += 1;
+            remaining = remaining.saturating_sub(size);
+            addr = Machine::advance_address(addr, size);
+        }
+
+        writer.flush()?;
+        Ok(instr_count)
+    }
+
+    /// Decode a single instruction at `addr`, returning its size and tokens for its bytes and
+    /// disassembly. If the instruction branches to an address with a symbol, the name is appended
+    /// as a comment. An undecodable byte is returned as a one-byte INVALID entry. Returns None if
+    /// the instruction would run past the end of the address space.
+    fn decode_at(&mut self, addr: CpuAddress) -> Option<(usize, Vec<SyntaxToken>)> {
+        let flat_addr = u32::from(addr) as usize;
+        if flat_addr > MAX_MEMORY_ADDRESS {
+            return None;
+        }
+
+        let bus = self.cpu.bus_mut();
+        bus.seek(flat_addr);
+        match Cpu::decode(bus) {
+            Ok(i) => {
+                let size = i.size as usize;
+                if flat_addr + size > MAX_MEMORY_ADDRESS + 1 {
+                    // Instruction runs off the end of memory.
+                    return None;
+                }
+                let instr_bytes_str = util::fmt_byte_array(bus.get_slice_at(flat_addr, size));
+                let mut tokens = vec![SyntaxToken::InstructionBytes(format!("{:012}", instr_bytes_str))];
+                tokens.append(&mut Cpu::tokenize_instruction(&i));
+
+                if let Some(name) = Machine::branch_target(addr, &i).and_then(|target| self.symbol_at(target)) {
+                    tokens.push(SyntaxToken::Formatter(SyntaxFormatType::Space));
+                    tokens.push(SyntaxToken::Label(format!("; {}", name)));
+                }
+                Some((size, tokens))
+            }
+            Err(_) => Some((1, vec![SyntaxToken::ErrorString("INVALID".to_string())])),
+        }
+    }
+
+    /// Return the linear address that the instruction `i` at `addr` branches to, if it is a
+    /// direct branch. Relative branches can't be resolved from an offset-only address.
+    fn branch_target(addr: CpuAddress, i: &Instruction) -> Option<u32> {
+        let rel = match i.operand1_type {
+            OperandType::FarAddress(segment, offset) => return Some(Cpu::calc_linear_address(segment, offset)),
+            OperandType::Relative8(rel8) => rel8 as i16,
+            OperandType::Relative16(rel16) => rel16,
+            _ => return None,
+        };
+        let next = (i.size as u16).wrapping_add(rel as u16);
+        match addr {
+            CpuAddress::Segmented(segment, offset) => {
+                Some(Cpu::calc_linear_address(segment, offset.wrapping_add(next)))
+            }
+            CpuAddress::Flat(flat) => Some(flat.wrapping_add(next as i16 as u32) & 0xFFFFF),
+            CpuAddress::Offset(_) => None,
+        }
+    }
+
+    /// Decode the instruction at CS:IP and resolve its memory operand, if any, against the current
+    /// register state. Memory is read with the bus peek functions, so decoding has no side effects
+    /// on the CPU, memory-mapped devices or IO.
+    pub fn decode_current(&self) -> DecodedInstruction {
+        let bus = self.cpu.bus();
+        let cs = self.cpu.get_register16(Register16::CS);
+        let ip = self.cpu.ip();
+
+        // Read a window of bytes at CS:IP, following segment wraparound.
+        let window: Vec<u8> = (0..DECODE_WINDOW_LEN)
+            .map(|n| {
+                bus.peek_u8(Cpu::calc_linear_address(cs, ip.wrapping_add(n)) as usize)
+                    .unwrap_or(OPEN_BUS_BYTE)
+            })
+            .collect();
+
+        let instruction = Cpu::decode(&mut SliceQueue::new(&window)).ok();
+
+        let (size, opcode, tokens, operand1, operand2) = match &instruction {
+            Some(i) => {
+                let (operand1, operand2) = Cpu::tokenize_operands(i);
+                let size = (i.size as usize).min(window.len());
+                (size, i.opcode, Cpu::tokenize_instruction(i), operand1, operand2)
+            }
+            None => (
+                1,
+                window[0],
+                vec![SyntaxToken::ErrorString("INVALID".to_string())],
+                Vec::new(),
+                Vec::new(),
+            ),
+        };
+        let memory_operand = instruction.as_ref().and_then(|i| self.resolve_memory_operand(i));
+
+        DecodedInstruction {
+            address: CpuAddress::Segmented(cs, ip),
+            opcode,
+            instruction_bytes: window[..size].to_vec(),
+            instruction,
+            tokens,
+            operand1,
+            operand2,
+            memory_operand,
+        }
+    }
+
+    /// Resolve the explicit memory operand of `i`, if it has one, using the current CPU registers.
+    fn resolve_memory_operand(&self, i: &Instruction) -> Option<MemoryOperand> {
+        let is_memory = |op: OperandType| {
+            matches!(op, OperandType::AddressingMode(_) | OperandType::Offset8(_) | OperandType::Offset16(_))
+        };
+        let (op_type, op_size) = if is_memory(i.operand1_type) {
+            (i.operand1_type, i.operand1_size)
+        }
+        else if is_memory(i.operand2_type) {
+            (i.operand2_type, i.operand2_size)
+        }
+        else {
+            return None;
+        };
+
+        // The decoder converts register-mode modrm operands to register operands, so any
+        // AddressingMode operand here references memory.
+        let (segment_value, segment, offset) = match op_type {
+            OperandType::AddressingMode(mode) => self.cpu.peek_effective_address(mode, i.segment_override),
+            OperandType::Offset8(offset) | OperandType::Offset16(offset) => {
+                let segment = Cpu::segment_override(i.segment_override, Segment::DS);
+                (self.cpu.segment_value(segment), segment, offset)
+            }
+            _ => return None,
+        };
+
+        let len = match (i.mnemonic, op_size) {
+            (Mnemonic::LES | Mnemonic::LDS | Mnemonic::CALLF | Mnemonic::JMPF, _) => 4,
+            (_, OperandSize::Operand8) => 1,
+            _ => 2,
+        };
+
+        let bus = self.cpu.bus();
+        let bytes = (0..len)
+            .map(|n| {
+                bus.peek_u8(Cpu::calc_linear_address(segment_value, offset.wrapping_add(n)) as usize)
+                    .unwrap_or(OPEN_BUS_BYTE)
+            })
+            .collect();
+
+        Some(MemoryOperand {
+            segment,
+            segment_value,
+            offset,
+            linear_address: Cpu::calc_linear_address(segment_value, offset),
+            bytes,
+        })
+    }
+
+    /// Advance `addr` by `size` bytes, following segment wraparound for segmented addresses.
+    fn advance_address(addr: CpuAddress, size: usize) -> CpuAddress {
+        match addr {
+            CpuAddress::Segmented(segment, offset) => CpuAddress::Segmented(segment, offset.wrapping_add(size as u16)),
+            CpuAddress::Offset(offset) => CpuAddress::Offset(offset.wrapping_add(size as u16)),
+            CpuAddress::Flat(flat) => CpuAddress::Flat(flat + size as u32),
+        }
+    }
+
     pub fn video_buffer_mut(&mut self, _vid: VideoCardId) -> Option<&mut u8> {
         None
     }
@@ -702,6 +1554,11 @@ impl Machine {
         self.cpu.bus_mut().primary_video_mut()
     }
 
+    /// Return the frame count of the primary video card, if one is installed.
+    fn primary_frame_count(&self) -> Option<u64> {
+        self.cpu.bus().primary_video_frame_count()
+    }
+
     /*
     pub fn enumerate_video_cards(&mut self) -> Vec<VideoCardInterface> {
         let mut vcivec = Vec::new();
@@ -781,7 +1638,7 @@ impl Machine {
     pub fn set_turbo_mode(&mut self, state: bool) {
         self.turbo_button = state;
         if state {
-            self.next_cpu_factor = self.machine_desc.cpu_turbo_factor;
+            self.next_cpu_factor = self.turbo_factor;
         }
         else {
             self.next_cpu_factor = self.machine_desc.cpu_factor;
@@ -793,65 +1650,300 @@ impl Machine {
         );
     }
 
-    pub fn fdc(&mut self) -> &mut Option<FloppyController> {
-        self.cpu.bus_mut().fdc_mut()
+    /// Return the state of the turbo button.
+    pub fn get_turbo_mode(&self) -> bool {
+        self.turbo_button
     }
 
-    pub fn hdc(&mut self) -> &mut Option<HardDiskController> {
-        self.cpu.bus_mut().hdc_mut()
-    }
+    /// Set the CPU clock speed in MHz. The CPU clock is derived from the system crystal by an integer
+    /// divisor or multiplier, so the closest achievable clock is selected. Returns the resulting clock
+    /// speed in MHz. As with the turbo button, the change takes effect on the next call to run().
+    pub fn set_cpu_mhz(&mut self, mhz: f64) -> Result<f64, Error> {
+        let (best_factor, best_mhz) = closest_clock_factor(self.machine_desc.system_crystal, mhz)?;
 
-    pub fn cpu_cycles(&self) -> u64 {
-        self.cpu_cycles
+        self.next_cpu_factor = best_factor;
+        log::debug!(
+            "Requested CPU clock of {} MHz, using {:?} ({} MHz)",
+            mhz,
+            best_factor,
+            best_mhz
+        );
+        Ok(best_mhz)
     }
 
-    pub fn cpu_instructions(&self) -> u64 {
-        self.cpu.get_instruction_ct()
-    }
+    /// Set the CPU clock speed used while turbo is enabled, or None to restore the machine's default
+    /// turbo speed. Returns the resulting turbo clock speed in MHz. If turbo is currently enabled,
+    /// the new speed takes effect on the next call to run().
+    pub fn set_turbo_mhz(&mut self, mhz: Option<f64>) -> Result<f64, Error> {
+        self.turbo_factor = match mhz {
+            Some(mhz) => closest_clock_factor(self.machine_desc.system_crystal, mhz)?.0,
+            None => self.machine_desc.cpu_turbo_factor,
+        };
 
-    pub fn system_ticks(&self) -> u64 {
-        self.system_ticks
-    }
+        if self.turbo_button || self.turbo_bit {
+            self.next_cpu_factor = self.turbo_factor;
+        }
 
-    /// Return the number of cycles the PIT has ticked.
-    pub fn pit_cycles(&self) -> u64 {
-        // Safe to unwrap pit as a PIT will always exist on any machine type
-        self.cpu.bus().pit().as_ref().unwrap().get_cycles()
+        Ok(match self.turbo_factor {
+            ClockFactor::Divisor(n) => self.machine_desc.system_crystal / (n as f64),
+            ClockFactor::Multiplier(n) => self.machine_desc.system_crystal * (n as f64),
+        })
     }
 
-    /// Return the PIT's state as a PitDisplaySate struct.
-    /// This is a mutable function as receiving the display state resets the various
-    /// state variable's dirty flags.
-    pub fn pit_state(&mut self) -> PitDisplayState {
-        // Safe to unwrap pit as a PIT will always exist on any machine type
-        let pit = self.cpu.bus_mut().pit_mut().as_mut().unwrap();
-        pit.get_display_state(true)
-    }
+    /// Apply the subset of configuration options that can be changed while the machine is running.
+    /// Options that are only read when the machine is built are left alone. The caller is
+    /// responsible for reporting changes to those that require a restart.
+    pub fn apply_runtime_config(&mut self, core_config: &dyn CoreConfig) {
+        if let Err(e) = self.set_turbo_mhz(core_config.get_machine_turbo_mhz()) {
+            log::warn!("Ignoring turbo clock: {}", e);
+        }
+        // The base CPU clock only applies while turbo is off.
+        if !(self.turbo_button || self.turbo_bit) {
+            match core_config.get_machine_cpu_mhz() {
+                Some(mhz) => {
+                    if let Err(e) = self.set_cpu_mhz(mhz) {
+                        log::warn!("Ignoring CPU clock: {}", e);
+                    }
+                }
+                None => self.next_cpu_factor = self.machine_desc.cpu_factor,
+            }
+        }
+        if core_config.get_machine_turbo() != self.turbo_button {
+            self.set_turbo_mode(core_config.get_machine_turbo());
+        }
 
-    pub fn get_pit_buf(&self) -> Vec<u8> {
-        let (a, b) = self.pit_data.buffer_consumer.as_slices();
+        self.halt_behavior = core_config.get_halt_behavior();
+        self.watchdog = core_config.get_watchdog();
+        self.watchdog_state = Default::default();
+        // Fast boot takes effect on the next cold reset.
+        self.fast_boot = core_config.get_fast_boot();
+        self.mute_post_beep = core_config.get_audio_mute_post_beep();
+        self.cpu
+            .set_option(CpuOption::TraceLoggingEnabled(core_config.get_cpu_trace_on()));
+        self.cpu.bus_mut().set_options(core_config.get_title_hacks());
 
-        a.iter().cloned().chain(b.iter().cloned()).collect()
-    }
+        if let Some(sound_player) = &mut self.sound_player {
+            sound_player.set_master_volume(core_config.get_audio_volume().unwrap_or(1.0));
+            sound_player.set_source_muted(SoundSource::PcSpeaker, core_config.get_audio_mute_speaker());
+            sound_player.set_source_muted(SoundSource::AdLib, core_config.get_audio_mute_adlib());
+        }
 
-    /// Adjust the relative phase of CPU and PIT; this is done by subtracting the relevant number of
-    /// system ticks from the next run of the PIT.
-    pub fn pit_adjust(&mut self, ticks: u32) {
-        self.cpu.bus_mut().adjust_pit(ticks);
+        log::debug!("Applied runtime configuration.");
     }
 
-    pub fn pic_state(&mut self) -> PicStringState {
-        // There will always be a primary PIC, so safe to unwrap.
-        // TODO: Handle secondary PIC if present.
-        self.cpu.bus_mut().pic_mut().as_mut().unwrap().get_string_state()
+    pub fn fdc(&mut self) -> &mut Option<FloppyController> {
+        self.cpu.bus_mut().fdc_mut()
     }
 
-    pub fn ppi_state(&mut self) -> Option<PpiStringState> {
-        self.cpu.bus_mut().ppi_mut().as_mut().map(|ppi| ppi.get_string_state())
+    pub fn post_card(&mut self) -> &mut Option<PostCard> {
+        self.cpu.bus_mut().post_card_mut()
     }
 
-    pub fn set_nmi(&mut self, state: bool) {
-        self.cpu.set_nmi(state);
+    pub fn hdc(&mut self) -> &mut Option<HardDiskController> {
+        self.cpu.bus_mut().hdc_mut()
+    }
+
+    /// Return a description of every floppy and hard disk drive slot in the machine, along with
+    /// metadata about the media mounted in each, if any.
+    pub fn media_info(&self) -> Vec<MediaSlot> {
+        let mut slots = Vec::new();
+
+        if let Some(fdc) = self.cpu.bus().fdc() {
+            for drive in 0..fdc.drive_ct() {
+                slots.push(MediaSlot {
+                    kind: MediaKind::Floppy,
+                    drive,
+                    media: fdc.media_info(drive),
+                });
+            }
+        }
+        if let Some(hdc) = self.cpu.bus().hdc() {
+            for drive in 0..hdc.drive_ct() {
+                slots.push(MediaSlot {
+                    kind: MediaKind::HardDisk,
+                    drive,
+                    media: hdc.media_info(drive),
+                });
+            }
+        }
+        slots
+    }
+
+    /// Update the state of a joystick attached to the game port, if one is installed.
+    pub fn set_joystick(&mut self, stick: usize, state: JoystickState) {
+        if let Some(game_port) = self.cpu.bus_mut().game_port_mut() {
+            game_port.set_stick(stick, state);
+        }
+    }
+
+    /// Disconnect a joystick from the game port, if one is installed.
+    pub fn disconnect_joystick(&mut self, stick: usize) {
+        if let Some(game_port) = self.cpu.bus_mut().game_port_mut() {
+            game_port.disconnect_stick(stick);
+        }
+    }
+
+    /// Release the floppy boot hold applied by a hard disk first boot order, if it is still set.
+    fn release_floppy_boot_hold(&mut self, reason: &str) {
+        if let Some(fdc) = self.cpu.bus_mut().fdc_mut() {
+            if fdc.boot_hold() {
+                log::debug!("Releasing floppy boot hold: {}", reason);
+                fdc.set_boot_hold(false);
+            }
+        }
+    }
+
+    /// Take the output captured by the printer on the parallel port, if one is installed.
+    pub fn take_printer_output(&mut self) -> Option<Vec<u8>> {
+        self.cpu
+            .bus_mut()
+            .parallel_mut()
+            .as_mut()
+            .map(|parallel| parallel.port_mut().take_capture())
+    }
+
+    pub fn cpu_cycles(&self) -> u64 {
+        self.cpu_cycles
+    }
+
+    pub fn cpu_instructions(&self) -> u64 {
+        self.cpu.get_instruction_ct()
+    }
+
+    /// Return true if the CPU is halted, waiting for an interrupt. A frontend can use this to
+    /// idle the host while the guest is idle.
+    pub fn is_halted(&self) -> bool {
+        self.cpu.is_halted()
+    }
+
+    /// Return the number of cycles the CPU spent halted during the last call to run().
+    pub fn halt_cycles(&self) -> u32 {
+        self.halt_cycles
+    }
+
+    pub fn system_ticks(&self) -> u64 {
+        self.system_ticks
+    }
+
+    /// Update execution statistics. Frontends should call this at a regular interval, normally
+    /// once per second, with the wall-clock time in seconds elapsed since the previous call and the
+    /// number of frames they presented in that time. Returns the updated statistics.
+    pub fn sample_stats(&mut self, elapsed_secs: f64, host_frames: u32) -> MachineStats {
+        let cycles = self.cpu_cycles;
+        let instructions = self.cpu_instructions();
+        let frames = self.primary_videocard().map_or(0, |vc| vc.get_frame_count());
+        let (last_cycles, last_instructions, last_frames) = self.stats_sample;
+        self.stats_sample = (cycles, instructions, frames);
+
+        if elapsed_secs > 0.0 {
+            let cps = cycles.saturating_sub(last_cycles) as f64 / elapsed_secs;
+            self.stats = MachineStats {
+                effective_mhz: cps / 1_000_000.0,
+                cps: cps as u64,
+                ips: (instructions.saturating_sub(last_instructions) as f64 / elapsed_secs) as u64,
+                emulated_fps: (frames.saturating_sub(last_frames) as f64 / elapsed_secs).round() as u32,
+                host_fps: (host_frames as f64 / elapsed_secs).round() as u32,
+            };
+        }
+        self.stats
+    }
+
+    /// Return the execution statistics from the most recent call to [Machine::sample_stats].
+    pub fn stats(&self) -> MachineStats {
+        self.stats
+    }
+
+    /// Return the number of cycles the PIT has ticked.
+    pub fn pit_cycles(&self) -> u64 {
+        // Safe to unwrap pit as a PIT will always exist on any machine type
+        self.cpu.bus().pit().as_ref().unwrap().get_cycles()
+    }
+
+    /// Return the PIT's state as a PitDisplaySate struct.
+    /// This is a mutable function as receiving the display state resets the various
+    /// state variable's dirty flags.
+    pub fn pit_state(&mut self) -> PitDisplayState {
+        // Safe to unwrap pit as a PIT will always exist on any machine type
+        let pit = self.cpu.bus_mut().pit_mut().as_mut().unwrap();
+        pit.get_display_state(true)
+    }
+
+    /// Return the current count, reload value and mode of PIT channel 0 (the system timer).
+    pub fn pit_channel0_state(&self) -> PitChannelState {
+        self.cpu.bus().pit().as_ref().unwrap().channel0_state()
+    }
+
+    /// Override the reload value of PIT channel 0 to speed up or slow down the system timer tick,
+    /// or restore the software-programmed value with None. The override is dropped if the guest
+    /// reprograms the timer. Returns false if the override could not be applied.
+    pub fn set_pit_channel0_reload(&mut self, reload: Option<u16>) -> bool {
+        self.cpu
+            .bus_mut()
+            .pit_mut()
+            .as_mut()
+            .unwrap()
+            .set_channel0_reload(reload)
+    }
+
+    pub fn get_pit_buf(&self) -> Vec<u8> {
+        let (a, b) = self.pit_data.buffer_consumer.as_slices();
+
+        a.iter().cloned().chain(b.iter().cloned()).collect()
+    }
+
+    /// Adjust the relative phase of CPU and PIT; this is done by subtracting the relevant number of
+    /// system ticks from the next run of the PIT.
+    pub fn pit_adjust(&mut self, ticks: u32) {
+        self.cpu.bus_mut().adjust_pit(ticks);
+    }
+
+    pub fn pic_state(&mut self) -> PicStringState {
+        // There will always be a primary PIC, so safe to unwrap.
+        // TODO: Handle secondary PIC if present.
+        self.cpu.bus_mut().pic_mut().as_mut().unwrap().get_string_state()
+    }
+
+    /// Return a snapshot of the primary PIC's IMR, IRR and ISR registers.
+    pub fn pic_snapshot(&self) -> PicSnapshot {
+        // There will always be a primary PIC, so safe to unwrap.
+        self.cpu.bus().pic().as_ref().unwrap().snapshot()
+    }
+
+    /// Return a label for each of the primary PIC's IRQ lines, naming the devices using it.
+    pub fn irq_labels(&self) -> [String; 8] {
+        self.cpu.bus().irq_labels()
+    }
+
+    /// Start tracing CPU port IO to ports in `ports`, keeping the most recent `capacity`
+    /// accesses for display. If `log_file` is given, every traced access is also written to it.
+    /// Any previous trace is replaced.
+    pub fn start_io_trace(&mut self, ports: RangeInclusive<u16>, capacity: usize, log_file: Option<&Path>) {
+        let logger = log_file.map_or(TraceLogger::None, TraceLogger::from_filename);
+        self.cpu
+            .bus_mut()
+            .set_io_trace(Some(IoTrace::new(ports, capacity, logger)));
+    }
+
+    /// Stop tracing port IO.
+    pub fn stop_io_trace(&mut self) {
+        self.cpu.bus_mut().set_io_trace(None);
+    }
+
+    /// Remove and return the buffered port IO trace entries, oldest first.
+    pub fn take_io_trace(&mut self) -> Vec<IoTraceEntry> {
+        self.cpu
+            .bus_mut()
+            .io_trace_mut()
+            .as_mut()
+            .map_or(Vec::new(), |trace| trace.take_entries())
+    }
+
+    pub fn ppi_state(&mut self) -> Option<PpiStringState> {
+        self.cpu.bus_mut().ppi_mut().as_mut().map(|ppi| ppi.get_string_state())
+    }
+
+    pub fn set_nmi(&mut self, state: bool) {
+        self.cpu.set_nmi(state);
     }
 
     pub fn dma_state(&mut self) -> DMAControllerStringState {
@@ -892,6 +1984,35 @@ impl Machine {
         });
     }
 
+    /// Queue a raw XT scancode for delivery to the keyboard interface. Queued scancodes are
+    /// delivered one at a time, SCANCODE_INJECT_INTERVAL microseconds apart in emulated time.
+    pub fn send_scancode(&mut self, code: u8) {
+        self.scancode_queue.push_back(code);
+    }
+
+    /// Type an ASCII string by queueing make and break scancodes for each character, using a
+    /// US keyboard layout. Shift is pressed around characters that require it. Characters with
+    /// no scancode mapping are skipped.
+    pub fn type_string(&mut self, text: &str) {
+        for c in text.chars() {
+            match keyboard::ascii_to_scancode(c) {
+                Some((code, shift)) => {
+                    if shift {
+                        self.send_scancode(keyboard::SCANCODE_SHIFT_LEFT);
+                    }
+                    self.send_scancode(code);
+                    self.send_scancode(code | keyboard::SCANCODE_BREAK_BIT);
+                    if shift {
+                        self.send_scancode(keyboard::SCANCODE_SHIFT_LEFT | keyboard::SCANCODE_BREAK_BIT);
+                    }
+                }
+                None => {
+                    log::warn!("type_string(): No scancode for character {:?}, skipping.", c);
+                }
+            }
+        }
+    }
+
     #[rustfmt::skip]
     /// Simulate the user pressing control-alt-delete.
     pub fn emit_ctrl_alt_del(&mut self) {
@@ -940,12 +2061,83 @@ impl Machine {
         Ok(())
     }
 
+    /// Bridge the specified guest serial port to a TCP socket. In Connect mode `address` is the
+    /// remote host to connect to; in Listen mode it is the local address to bind.
+    pub fn bridge_serial_tcp(&mut self, port_num: usize, address: &str, mode: TcpBridgeMode) -> Result<(), Error> {
+        if let Some(spc) = self.cpu.bus_mut().serial_mut() {
+            if let Err(e) = spc.bridge_tcp(port_num, address, mode) {
+                log::error!("Failed to bridge serial port to TCP: {}", e);
+                return Err(anyhow!(format!("Failed to bridge serial port to TCP: {}", e)));
+            }
+        }
+        else {
+            log::error!("No serial port controller present!");
+            return Err(anyhow!("No serial port controller present!"));
+        }
+        Ok(())
+    }
+
     pub fn set_breakpoints(&mut self, bp_list: Vec<BreakPointType>) {
         self.cpu.set_breakpoints(bp_list)
     }
 
-    pub fn reset(&mut self) {
+    /// Save the current breakpoints, watchpoints and user symbols to a TOML file at `path`, to be
+    /// restored by load_debug_session().
+    pub fn save_debug_session(&self, path: &Path) -> Result<(), Error> {
+        let mut session = DebugSession::default();
+        session.add_breakpoints(self.cpu.breakpoints());
+        session.watchpoints = self
+            .cpu
+            .bus()
+            .watches()
+            .iter()
+            .map(|wp| SessionWatchpoint {
+                start:  wp.range.start,
+                end:    wp.range.end,
+                access: wp.access,
+            })
+            .collect();
+        session.symbols = self
+            .symbols
+            .iter()
+            .map(|(address, name)| SessionSymbol {
+                address: *address,
+                name:    name.clone(),
+            })
+            .collect();
+
+        std::fs::write(path, toml::to_string(&session)?)?;
+        log::debug!("Saved debug session to {:?}", path);
+        Ok(())
+    }
+
+    /// Restore breakpoints, watchpoints and user symbols from a file written by
+    /// save_debug_session(), replacing any currently set. Stale entries, such as addresses outside
+    /// the address space, are skipped. A description of each skipped entry is logged and returned.
+    pub fn load_debug_session(&mut self, path: &Path) -> Result<Vec<String>, Error> {
+        let session: DebugSession = toml::from_str(&std::fs::read_to_string(path)?)?;
+
+        let mut warnings = Vec::new();
+        let breakpoints = session.breakpoints(&mut warnings);
+        let watchpoints = session.watchpoints(&mut warnings);
+        let symbols = session.symbols(&mut warnings);
+
+        self.set_breakpoints(breakpoints);
+        self.clear_watches();
+        for (range, access) in watchpoints {
+            self.add_watch(range, access);
+        }
+        self.symbols = symbols.into_iter().collect();
+
+        for warning in &warnings {
+            log::warn!("Skipped stale entry in debug session {:?}: {}", path, warning);
+        }
+        Ok(warnings)
+    }
+
+    pub fn reset(&mut self, kind: ResetKind) {
         // TODO: Reload any program specified here?
+        log::debug!("Performing {:?} reset.", kind);
 
         // Clear any error state.
         self.error = false;
@@ -954,20 +2146,39 @@ impl Machine {
         // Reset CPU.
         self.cpu.reset();
 
-        // Clear RAM
-        self.cpu.bus_mut().clear();
-
-        // Reload BIOS ROM images
-        if self.load_bios {
-            Machine::install_roms(self.cpu.bus_mut(), &self.rom_manifest);
-            //self.rom_manager.copy_into_memory(self.cpu.bus_mut());
-            // Clear patch installation status
-            //self.rom_manager.reset_patches();
+        match kind {
+            ResetKind::Cold => {
+                // Clear RAM
+                self.cpu.bus_mut().clear();
+                self.apply_seed();
+                self.set_fast_boot_flag();
+
+                // Reload BIOS ROM images
+                if self.load_bios {
+                    Machine::install_roms(self.cpu.bus_mut(), &self.rom_manifest);
+                    // Clear patch installation status, so patches are installed again when their
+                    // checkpoints are reached.
+                    for patch in self.rom_manifest.patches.iter_mut() {
+                        patch.installed = false;
+                    }
+                }
+            }
+            ResetKind::Warm => {
+                // Set the BIOS reset flag in the BIOS data area, as the keyboard interrupt
+                // handler does on Ctrl-Alt-Del.
+                _ = self.cpu.bus_mut().write_u16(BIOS_RESET_FLAG_ADDR, BIOS_RESET_FLAG_WARM, 0);
+            }
         }
 
         // Reset all installed devices.
         self.cpu.bus_mut().reset_devices();
         self.events.push(MachineEvent::Reset);
+        self.last_checkpoint = None;
+        self.last_watch_hit = None;
+        self.in_post = true;
+        self.at_reset = true;
+        self.scancode_queue.clear();
+        self.scancode_us_accum = 0.0;
     }
 
     pub fn set_reload_pending(&mut self, state: bool) {
@@ -1016,10 +2227,117 @@ impl Machine {
         }
     }
 
+    /// Load symbols from a symbol file at `path`, adding them to any already loaded. Both a simple
+    /// 'address name' text format and Borland/Watcom .map files are accepted; see the symbols
+    /// module. Returns the number of symbols loaded.
+    pub fn load_symbols(&mut self, path: &Path) -> Result<usize, Error> {
+        let symbols = symbols::parse_symbols(&std::fs::read_to_string(path)?);
+        if symbols.is_empty() {
+            return Err(anyhow!("No symbols found in {:?}", path));
+        }
+
+        let count = symbols.len();
+        self.symbols.extend(symbols);
+        log::debug!("Loaded {} symbols from {:?}", count, path);
+        Ok(count)
+    }
+
+    pub fn add_symbol(&mut self, address: u32, name: &str) {
+        self.symbols.insert(address, name.to_string());
+    }
+
+    pub fn clear_symbols(&mut self) {
+        self.symbols.clear();
+    }
+
+    /// Return the user symbols that have been loaded or added, by linear address.
+    pub fn symbols(&self) -> &BTreeMap<u32, String> {
+        &self.symbols
+    }
+
+    /// Return the name for the specified linear address, if any. User symbols take precedence
+    /// over the descriptions of the ROM checkpoints.
+    pub fn symbol_at(&self, address: u32) -> Option<&str> {
+        self.symbols.get(&address).map(|s| s.as_str()).or_else(|| {
+            self.checkpoint_map
+                .get(&address)
+                .map(|idx| self.rom_manifest.checkpoints[*idx].desc.as_str())
+        })
+    }
+
+    /// Return the index of the most recently hit ROM checkpoint, if any. The description can be
+    /// retrieved with get_checkpoint_string().
+    pub fn last_checkpoint(&self) -> Option<usize> {
+        self.last_checkpoint
+    }
+
+    /// Set a watchpoint over a range of physical addresses. Execution pauses in the BreakpointHit
+    /// state after any instruction that accesses the range.
+    pub fn add_watch(&mut self, range: std::ops::Range<usize>, access: WatchAccess) {
+        self.cpu.bus_mut().add_watch(range, access);
+    }
+
+    pub fn remove_watch(&mut self, range: std::ops::Range<usize>) {
+        self.cpu.bus_mut().remove_watch(range);
+    }
+
+    pub fn clear_watches(&mut self) {
+        self.cpu.bus_mut().clear_watches();
+        self.last_watch_hit = None;
+    }
+
+    /// Return the most recent watchpoint hit and the address of the instruction that caused it.
+    pub fn last_watch_hit(&self) -> Option<(WatchHit, CpuAddress)> {
+        self.last_watch_hit
+    }
+
+    /// Run the machine without any frontend until `predicate` returns true or `max_cycles` CPU
+    /// cycles have elapsed. The predicate is evaluated after every instruction.
+    ///
+    /// This is intended for headless use such as automated tests; it does not require a
+    /// SoundPlayer or any video output. Machine events are still queued and may be retrieved
+    /// with get_event().
+    pub fn run_until(&mut self, mut predicate: impl FnMut(&Machine) -> bool, max_cycles: u64) -> RunResult {
+        let mut exec_control = ExecutionControl::new();
+        exec_control.set_state(ExecutionState::Running);
+
+        let start_cycles = self.cpu_cycles;
+        loop {
+            let elapsed = self.cpu_cycles - start_cycles;
+            if predicate(self) {
+                return RunResult::PredicateMet(elapsed);
+            }
+            if elapsed >= max_cycles {
+                return RunResult::CycleLimitReached(elapsed);
+            }
+
+            // A cycle target of 1 executes a single instruction.
+            let instructions = self.run(1, &mut exec_control);
+            let new_elapsed = self.cpu_cycles - start_cycles;
+
+            match exec_control.get_state() {
+                ExecutionState::BreakpointHit => return RunResult::BreakpointHit(new_elapsed),
+                ExecutionState::Halted => return RunResult::Halted(new_elapsed),
+                _ => {}
+            }
+            if instructions == 0 || new_elapsed == elapsed {
+                // The machine is off, a reload is pending, or the CPU is permanently halted.
+                // Nothing will change.
+                return RunResult::Halted(new_elapsed);
+            }
+        }
+    }
+
+    /// Run the machine for approximately `cycle_target` CPU cycles, subject to the state of
+    /// `exec_control`. Execution may overshoot the target by part of an instruction.
+    ///
+    /// Returns the number of instructions executed, not cycles. Use [Machine::cpu_cycles] to
+    /// measure the cycles actually executed.
     pub fn run(&mut self, cycle_target: u32, exec_control: &mut ExecutionControl) -> u64 {
         let mut kb_event_processed = false;
         let mut skip_breakpoint = false;
         let mut instr_count = 0;
+        self.halt_cycles = 0;
 
         // Update cpu factor.
         let new_factor = self.next_cpu_factor;
@@ -1034,11 +2352,20 @@ impl Machine {
         // Was reset requested?
         if let ExecutionOperation::Reset = exec_control.peek_op() {
             _ = exec_control.get_op(); // Clear the reset operation
-            self.reset();
+            self.reset(ResetKind::Cold);
             exec_control.state = ExecutionState::Paused;
             return 0;
         }
 
+        // Silence audio while not running. A Run or StepFrame operation resumes it below, so don't
+        // pause first: pausing discards queued samples, which would open a gap on every frame step.
+        if !matches!(
+            exec_control.peek_op(),
+            ExecutionOperation::Run | ExecutionOperation::StepFrame
+        ) {
+            self.update_audio_pause(exec_control.state);
+        }
+
         let mut step_over = false;
         let cycle_target_adj = match exec_control.state {
             ExecutionState::Paused => {
@@ -1057,6 +2384,14 @@ impl Machine {
                         // Execute 1 instruction
                         1
                     }
+                    ExecutionOperation::StepFrame => {
+                        // Skip current breakpoint, if any
+                        skip_breakpoint = true;
+                        // Transition to ExecutionState::StepFrame
+                        self.step_frame_start = self.primary_frame_count();
+                        exec_control.state = ExecutionState::StepFrame;
+                        cycle_target
+                    }
                     ExecutionOperation::Run => {
                         // Transition to ExecutionState::Running
                         exec_control.state = ExecutionState::Running;
@@ -1065,7 +2400,7 @@ impl Machine {
                     _ => return 0,
                 }
             }
-            ExecutionState::Running => {
+            ExecutionState::Running | ExecutionState::StepFrame => {
                 _ = exec_control.get_op(); // Clear any pending operation
                 cycle_target
             }
@@ -1097,6 +2432,16 @@ impl Machine {
                         // Execute one instruction only
                         1
                     }
+                    ExecutionOperation::StepFrame => {
+                        // Clear CPU's breakpoint flag
+                        self.cpu.clear_breakpoint_flag();
+                        // Skip current breakpoint, if any
+                        skip_breakpoint = true;
+                        // Transition to ExecutionState::StepFrame
+                        self.step_frame_start = self.primary_frame_count();
+                        exec_control.state = ExecutionState::StepFrame;
+                        cycle_target
+                    }
                     ExecutionOperation::Run => {
                         // Clear CPU's breakpoint flag
                         self.cpu.clear_breakpoint_flag();
@@ -1120,6 +2465,7 @@ impl Machine {
                 }
             }
         };
+        self.update_audio_pause(exec_control.state);
 
         let do_run = matches!(self.state, MachineState::On);
         if !do_run {
@@ -1137,6 +2483,7 @@ impl Machine {
             // }
 
             let flat_address = self.cpu.flat_ip();
+            let instr_address = self.cpu.get_csip();
 
             // Match checkpoints. The first check is against a simple bit flag so that we do not 
             // need to constantly do a hash lookup.
@@ -1150,6 +2497,7 @@ impl Machine {
 
                     self.events
                         .push(MachineEvent::CheckpointHit(*cp, self.rom_manifest.checkpoints[*cp].lvl));
+                    self.last_checkpoint = Some(*cp);
                 }
 
                 if let Some(&cp) = self.patch_map.get(&flat_address) {
@@ -1176,7 +2524,24 @@ impl Machine {
                  */
             }
 
+            if flat_address == RESET_VECTOR_ADDR {
+                // A jump to the BIOS reset entry, such as a Ctrl+Alt+Del warm boot, runs POST again.
+                self.in_post = true;
+                if !self.at_reset {
+                    log::debug!("Execution reached the reset vector without a reset.");
+                    self.events
+                        .push(MachineEvent::GuestHalted(GuestHaltReason::ResetVector, instr_address));
+                }
+            }
+            self.at_reset = false;
+
+            if flat_address == BOOT_SECTOR_ADDR {
+                // A boot sector is running, so the floppy drives no longer need to be hidden from the BIOS.
+                self.release_floppy_boot_hold("boot sector is running");
+            }
+
             let mut step_over_target = None;
+            let was_halted = self.cpu.is_halted();
 
             match self.cpu.step(skip_breakpoint) {
                 Ok((step_result, step_cycles)) => match step_result {
@@ -1189,6 +2554,7 @@ impl Machine {
                     }
                     StepResult::BreakpointHit => {
                         exec_control.state = ExecutionState::BreakpointHit;
+                        self.events.push(MachineEvent::BreakpointHit(self.cpu.get_csip()));
                         return 1;
                     }
                     StepResult::ProgramEnd => {
@@ -1203,6 +2569,12 @@ impl Machine {
                     if let CpuError::CpuHaltedError(_) = err {
                         log::warn!("CPU Halted!");
                         self.cpu.trace_flush();
+                        if !was_halted {
+                            self.events.push(MachineEvent::GuestHalted(
+                                GuestHaltReason::HaltInterruptsDisabled,
+                                instr_address,
+                            ));
+                        }
 
                         match self.halt_behavior {
                             OnHaltBehavior::Continue => {
@@ -1237,6 +2609,9 @@ impl Machine {
             instr_count += 1;
             cycles_elapsed += cpu_cycles;
             self.cpu_cycles += cpu_cycles as u64;
+            if was_halted && self.cpu.is_halted() {
+                self.halt_cycles += cpu_cycles;
+            }
 
             if cpu_cycles == 0 {
                 log::warn!("Instruction returned 0 cycles");
@@ -1256,6 +2631,11 @@ impl Machine {
                 log::error!("CPU Error: {}\n{}", err, self.cpu.dump_instruction_history_string());
             }
 
+            if self.watchdog.enabled && self.update_watchdog(flat_address, cpu_cycles) && self.watchdog.pause {
+                exec_control.state = ExecutionState::Paused;
+                break;
+            }
+
             // If we returned a step over target address, execution is paused, and step over was requested,
             // then consume as many instructions as needed to get to the 'next' instruction. This will
             // skip over any CALL or interrupt encountered.
@@ -1278,6 +2658,7 @@ impl Machine {
                                         // We can hit an 'inner' breakpoint while stepping over. This is fine, and ends the step
                                         // over operation at the breakpoint.
                                         exec_control.state = ExecutionState::BreakpointHit;
+                                        self.events.push(MachineEvent::BreakpointHit(self.cpu.get_csip()));
                                         return instr_count;
                                     }
                                     StepResult::ProgramEnd => {
@@ -1287,9 +2668,13 @@ impl Machine {
                                 }
                             }
                             Err(err) => {
-                                if let CpuError::CpuHaltedError(_) = err {
+                                if let CpuError::CpuHaltedError(addr) = err {
                                     log::error!("CPU Halted!");
                                     exec_control.state = ExecutionState::Halted;
+                                    self.events.push(MachineEvent::GuestHalted(
+                                        GuestHaltReason::HaltInterruptsDisabled,
+                                        CpuAddress::Flat(addr),
+                                    ));
                                 }
                                 self.error = true;
                                 self.error_str = Some(format!("{}", err));
@@ -1330,8 +2715,61 @@ impl Machine {
                         log::debug!("PIT logging has been triggered.");
                         self.pit_data.logging_triggered = true;
                     }
+                    ServiceEvent::Bootstrap => {
+                        if self.in_post {
+                            log::debug!("Bootstrap loader called; POST complete.");
+                            self.in_post = false;
+
+                            // Don't hide the floppy drives if there's no hard disk to boot from instead.
+                            let hdd_bootable = self
+                                .cpu
+                                .bus_mut()
+                                .hdc_mut()
+                                .as_mut()
+                                .map_or(false, |hdc| hdc.is_bootable(0));
+                            if !hdd_bootable {
+                                self.release_floppy_boot_hold("hard disk 0 is not bootable");
+                            }
+                        }
+                        else {
+                            self.events
+                                .push(MachineEvent::GuestHalted(GuestHaltReason::Bootstrap, instr_address));
+                        }
+                    }
                 }
             }
+
+            // Pause on a watchpoint hit. The instruction that triggered it has completed.
+            if let Some(hit) = self.cpu.bus_mut().take_watch_hit() {
+                log::debug!(
+                    "WATCHPOINT: {:?} at [{:05X}] {:02X}->{:02X} by instruction at {}",
+                    hit.access,
+                    hit.address,
+                    hit.old_value,
+                    hit.new_value,
+                    instr_address
+                );
+                self.events.push(MachineEvent::WatchpointHit(hit, instr_address));
+                self.last_watch_hit = Some((hit, instr_address));
+                exec_control.state = ExecutionState::BreakpointHit;
+                break;
+            }
+
+            // The frame count of the primary card advances as it leaves vertical retrace and flips buffers.
+            if let ExecutionState::StepFrame = exec_control.state {
+                if self.step_frame_start.is_some() && self.primary_frame_count() != self.step_frame_start {
+                    log::debug!("Frame step complete at {}", self.cpu.get_csip());
+                    exec_control.state = ExecutionState::Paused;
+                    break;
+                }
+            }
+        }
+
+        // Without a video card there is no retrace to wait for, so a frame step lasts a single update.
+        if let ExecutionState::StepFrame = exec_control.state {
+            if self.step_frame_start.is_none() {
+                exec_control.state = ExecutionState::Paused;
+            }
         }
 
         //log::debug!("cycles_elapsed: {}", cycles_elapsed);
@@ -1353,6 +2791,17 @@ impl Machine {
         // Convert cycles into system clock ticks
         let sys_ticks = self.cpu_cycles_to_system_ticks(cpu_cycles);
 
+        // Deliver injected scancodes at a fixed rate in emulated time.
+        if !self.scancode_queue.is_empty() {
+            self.scancode_us_accum += us;
+            if self.scancode_us_accum >= SCANCODE_INJECT_INTERVAL {
+                self.scancode_us_accum = 0.0;
+                if let Some(code) = self.scancode_queue.pop_front() {
+                    self.cpu.bus_mut().inject_scancode(code);
+                }
+            }
+        }
+
         // Process a keyboard event once per frame.
         // A reasonably fast typist can generate two events in a single 16ms frame, and to the virtual cpu
         // they then appear to happen instantaneously. The PPI has no buffer, so one scancode gets lost.
@@ -1409,6 +2858,11 @@ impl Machine {
             self.pit_buf_to_sound_buf();
         }
 
+        // If the PC speaker is not producing samples, queue synthesizer samples on their own.
+        if !self.machine_config.speaker {
+            self.synth_buf_to_sound_buf();
+        }
+
         // Query interrupt line after device processing.
         let intr = self.cpu.bus_mut().pic_mut().as_ref().unwrap().query_interrupt_line();
 
@@ -1459,7 +2913,7 @@ impl Machine {
                                 // Turbo bit has changed.
                                 match turbo_bit {
                                     true => {
-                                        self.next_cpu_factor = self.machine_desc.cpu_turbo_factor;
+                                        self.next_cpu_factor = self.turbo_factor;
                                         device_events.push(DeviceEvent::TurboToggled(true));
                                     }
                                     false => {
@@ -1484,6 +2938,32 @@ impl Machine {
         device_events
     }
 
+    /// Pause sound output when execution is not running, and resume it when it is.
+    fn update_audio_pause(&mut self, state: ExecutionState) {
+        if let Some(sound_player) = &mut self.sound_player {
+            match state {
+                ExecutionState::Running | ExecutionState::StepFrame => sound_player.resume(),
+                _ => sound_player.pause(),
+            }
+        }
+    }
+
+    /// Return the gain applied to the PC speaker. The speaker is silent during POST if the POST
+    /// beep is muted.
+    fn speaker_gain(&self) -> f32 {
+        match &self.sound_player {
+            Some(sound_player) if !(self.in_post && self.mute_post_beep) => {
+                sound_player.source_gain(SoundSource::PcSpeaker)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Return a mutable reference to the sound player, if one is present.
+    pub fn sound_player_mut(&mut self) -> Option<&mut SoundPlayer> {
+        self.sound_player.as_mut()
+    }
+
     pub fn play_sound_buffer(&self) {
         if let Some(sound_player) = &self.sound_player {
             sound_player.play();
@@ -1533,11 +3013,37 @@ impl Machine {
         // TODO: replace with actual lowpass filter from biquad?
         let average: f32 = sum as f32 / nsamples as f32;
 
+        // Remove any DC offset. This lets the speaker settle to silence when the timer gate is off
+        // and the speaker data line is held at a constant level, while preserving PWM audio.
+        let filtered = average - self.pit_data.dc_last_in + SPEAKER_DC_BLOCK_R * self.pit_data.dc_last_out;
+        self.pit_data.dc_last_in = average;
+        self.pit_data.dc_last_out = filtered;
+
         //log::trace!("Sample: sum: {}, ticks: {}, avg: {}", sum, pit_ticks, average);
         self.pit_data.samples_produced += 1;
         //log::trace!("producer: {}", self.pit_samples_produced);
+        // Mix in samples from the AdLib card and SN76489, if present.
+        let adlib_sample = self
+            .cpu
+            .bus_mut()
+            .adlib_mut()
+            .as_mut()
+            .and_then(|adlib| adlib.pop_sample())
+            .unwrap_or(0.0);
+        let sn76489_sample = self
+            .cpu
+            .bus_mut()
+            .sn76489_mut()
+            .as_mut()
+            .and_then(|sn76489| sn76489.pop_sample())
+            .unwrap_or(0.0);
+
+        let speaker_gain = self.speaker_gain();
         if let Some(sound_player) = &mut self.sound_player {
-            sound_player.queue_sample(average * VOLUME_ADJUST);
+            let speaker_sample = filtered * VOLUME_ADJUST * speaker_gain;
+            let adlib_sample = adlib_sample * sound_player.source_gain(SoundSource::AdLib);
+            let sn76489_sample = sn76489_sample * sound_player.source_gain(SoundSource::Sn76489);
+            sound_player.queue_sample(speaker_sample + adlib_sample + sn76489_sample);
         }
 
         // Calculate size of next audio sample in pit samples by carrying over fractional part
@@ -1547,6 +3053,26 @@ impl Machine {
         self.pit_data.fractional_part = next_sample_f.fract();
     }
 
+    /// Drain generated AdLib and SN76489 samples into the sound player. Used when there is no
+    /// PC speaker sample stream to mix them into.
+    pub fn synth_buf_to_sound_buf(&mut self) {
+        loop {
+            let bus = self.cpu.bus_mut();
+            let adlib_sample = bus.adlib_mut().as_mut().and_then(|adlib| adlib.pop_sample());
+            let sn76489_sample = bus.sn76489_mut().as_mut().and_then(|sn76489| sn76489.pop_sample());
+
+            if adlib_sample.is_none() && sn76489_sample.is_none() {
+                break;
+            }
+
+            if let Some(sound_player) = &mut self.sound_player {
+                let sample = adlib_sample.unwrap_or(0.0) * sound_player.source_gain(SoundSource::AdLib)
+                    + sn76489_sample.unwrap_or(0.0) * sound_player.source_gain(SoundSource::Sn76489);
+                sound_player.queue_sample(sample);
+            }
+        }
+    }
+
     pub fn for_each_videocard<F>(&mut self, f: F)
     where
         F: FnMut(VideoCardInterface),
@@ -1554,3 +3080,1104 @@ impl Machine {
         self.bus_mut().for_each_videocard(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+    use crate::{
+        cpu_validator::{BusCycle, BusState},
+        device_traits::videocard::VideoType,
+        devices::{cga, dma, fdc, hdc, pit, rtc::RtcType},
+        machine_config::{
+            FloppyControllerConfig,
+            FloppyDriveConfig,
+            HardDriveControllerConfig,
+            RtcConfig,
+            VideoCardConfig,
+        },
+        machine_types::{BootTarget, FdcType, FloppyDriveType, HardDiskControllerType, OpenBusMode},
+        test_util::{test_machine_config as test_config, TestConfig, TEST_CONFIG},
+    };
+
+    fn test_machine() -> Machine {
+        test_machine_from(test_config())
+    }
+
+    fn test_machine_from(machine_config: MachineConfiguration) -> Machine {
+        Machine::from_params(test_params(machine_config))
+    }
+
+    fn test_params(machine_config: MachineConfiguration) -> MachineParams<'static> {
+        MachineParams {
+            core_config: &TEST_CONFIG,
+            machine_config,
+            machine_type: MachineType::Ibm5160,
+            machine_desc: *get_machine_descriptor(MachineType::Ibm5160).unwrap(),
+            trace_mode: TraceMode::default(),
+            trace_logger: TraceLogger::None,
+            sound_player: None,
+            rom_manifest: MachineRomManifest::new(),
+            keyboard_layout_file: None,
+        }
+    }
+
+    #[test]
+    fn type_string_queues_make_and_break_codes() {
+        let mut machine = test_machine();
+        machine.type_string("Ab\u{e9}!");
+        assert_eq!(
+            machine.scancode_queue.iter().copied().collect::<Vec<u8>>(),
+            vec![0x2A, 0x1E, 0x9E, 0xAA, 0x30, 0xB0, 0x2A, 0x02, 0x82, 0xAA]
+        );
+
+        // Scancodes are delivered one at a time, SCANCODE_INJECT_INTERVAL apart.
+        let mhz = machine.get_cpu_mhz();
+        let cycles = (SCANCODE_INJECT_INTERVAL * mhz / 2.0) as u32;
+        let mut kb_event_processed = false;
+        machine.run_devices(cycles, &mut kb_event_processed);
+        assert_eq!(machine.scancode_queue.len(), 10);
+        machine.run_devices(cycles + 1, &mut kb_event_processed);
+        assert_eq!(machine.scancode_queue.len(), 9);
+        assert_eq!(machine.scancode_queue.front(), Some(&0x1E));
+
+        machine.reset(ResetKind::Warm);
+        assert!(machine.scancode_queue.is_empty());
+    }
+
+    #[test]
+    fn entry_point_replaces_the_reset_vector() {
+        static ENTRY_POINT_CONFIG: TestConfig = TestConfig {
+            entry_point: Some((0xF000, 0xE000)),
+            ..TEST_CONFIG
+        };
+        let mut machine = Machine::from_params(MachineParams {
+            core_config: &ENTRY_POINT_CONFIG,
+            ..test_params(test_config())
+        });
+        assert_eq!(machine.entry_point(), CpuAddress::Segmented(0xF000, 0xE000));
+        assert_eq!(machine.cpu().get_csip(), CpuAddress::Segmented(0xF000, 0xE000));
+
+        // The entry point is kept across resets.
+        let mut exec_control = ExecutionControl::new();
+        exec_control.set_state(ExecutionState::Running);
+        machine.run(1000, &mut exec_control);
+        machine.reset(ResetKind::Warm);
+        assert_eq!(machine.cpu().get_csip(), CpuAddress::Segmented(0xF000, 0xE000));
+        machine.reset(ResetKind::Cold);
+        assert_eq!(machine.cpu().get_csip(), CpuAddress::Segmented(0xF000, 0xE000));
+
+        assert_eq!(test_machine().entry_point(), CpuAddress::Segmented(0xFFFF, 0x0000));
+    }
+
+    #[test]
+    fn run_returns_instruction_count_for_stats() {
+        let mut machine = test_machine();
+        // A tight 'JMP $' loop.
+        machine.load_program(&[0xEB, 0xFE], 0x1000, 0x0000).unwrap();
+
+        let mut exec_control = ExecutionControl::new();
+        exec_control.set_state(ExecutionState::Running);
+
+        // Run one emulated second in 60 frames.
+        let cycles_per_frame = (machine.get_cpu_mhz() * 1_000_000.0 / 60.0) as u32;
+        let mut instructions = 0;
+        for _ in 0..60 {
+            instructions += machine.run(cycles_per_frame, &mut exec_control);
+        }
+        assert_eq!(instructions, machine.cpu_instructions());
+
+        let stats = machine.sample_stats(1.0, 60);
+        assert_eq!(stats.ips, instructions);
+        assert_eq!(stats.cps, machine.cpu_cycles());
+        assert_eq!(stats.host_fps, 60);
+
+        // Cycle targets may only be overshot by part of an instruction.
+        let mhz = machine.get_cpu_mhz();
+        assert!((stats.effective_mhz - mhz).abs() / mhz < 0.01);
+
+        // A short JMP takes roughly 15 cycles on the 8088, including prefetch stalls.
+        let cycles_per_instruction = stats.cps as f64 / stats.ips as f64;
+        assert!(
+            (10.0..30.0).contains(&cycles_per_instruction),
+            "unexpected cycles per instruction: {}",
+            cycles_per_instruction
+        );
+    }
+
+    #[test]
+    fn bios_beep_sets_speaker_frequency_and_ends_post() {
+        let mut machine = test_machine();
+        // The BEEP routine of the 5150/5160 BIOS, followed by the bootstrap loader call.
+        #[rustfmt::skip]
+        let program = [
+            0xB0, 0xB6,       // MOV AL, 0B6h    ; Channel 2, LSB then MSB, square wave
+            0xE6, 0x43,       // OUT 43h, AL
+            0xB8, 0x33, 0x05, // MOV AX, 0533h   ; Divisor for ~896Hz
+            0xE6, 0x42,       // OUT 42h, AL
+            0x8A, 0xC4,       // MOV AL, AH
+            0xE6, 0x42,       // OUT 42h, AL
+            0xE4, 0x61,       // IN AL, 61h
+            0x0C, 0x03,       // OR AL, 3        ; Gate timer 2 and enable speaker data
+            0xE6, 0x61,       // OUT 61h, AL
+            0xCD, 0x19,       // INT 19h
+            0xEB, 0xFE,       // JMP $
+        ];
+        machine.load_program(&program, 0x1000, 0x0000).unwrap();
+        // Point the bootstrap vector at the final JMP.
+        machine.bus_mut().write_u16(0x19 * 4, 0x0015, 0).unwrap();
+        machine.bus_mut().write_u16(0x19 * 4 + 2, 0x1000, 0).unwrap();
+        assert!(machine.in_post);
+
+        let mut exec_control = ExecutionControl::new();
+        exec_control.set_state(ExecutionState::Running);
+        machine.run(10_000, &mut exec_control);
+
+        let events = machine.drain_events();
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, MachineEvent::SpeakerFrequencyChanged(Some(896)))));
+        assert!(!machine.in_post);
+    }
+
+    #[test]
+    fn post_beep_is_muted_until_bootstrap() {
+        let mut machine = test_machine();
+        machine.sound_player = Some(SoundPlayer::collector(44100));
+        machine.mute_post_beep = true;
+
+        // Turn on the speaker, then call the bootstrap loader.
+        #[rustfmt::skip]
+        let program = [
+            0xE4, 0x61, // IN AL, 61h
+            0x0C, 0x03, // OR AL, 3
+            0xE6, 0x61, // OUT 61h, AL
+            0xCD, 0x19, // INT 19h
+            0xEB, 0xFE, // JMP $
+        ];
+        machine.load_program(&program, 0x1000, 0x0000).unwrap();
+        machine.bus_mut().write_u16(0x19 * 4, 0x0008, 0).unwrap();
+        machine.bus_mut().write_u16(0x19 * 4 + 2, 0x1000, 0).unwrap();
+        assert_eq!(machine.speaker_gain(), 0.0);
+
+        let mut exec_control = ExecutionControl::new();
+        exec_control.set_state(ExecutionState::Running);
+        machine.run(10_000, &mut exec_control);
+        assert!(!machine.in_post);
+        assert_eq!(machine.speaker_gain(), 1.0);
+
+        // A jump to the BIOS reset entry, as on a warm boot, mutes the speaker again.
+        let reset_entry = RESET_VECTOR_ADDR as usize;
+        machine
+            .bus_mut()
+            .copy_from(&[0xEB, 0xFE], reset_entry, 0, false)
+            .unwrap();
+        let program = [0xEA, 0xF0, 0xFF, 0x00, 0xF0]; // JMP F000:FFF0
+        machine.load_program(&program, 0x2000, 0x0000).unwrap();
+        machine.run(10_000, &mut exec_control);
+        assert!(machine.in_post);
+        assert_eq!(machine.speaker_gain(), 0.0);
+    }
+
+    #[test]
+    fn halt_with_interrupts_disabled_reports_guest_halted() {
+        let mut machine = test_machine();
+        let program = [0xFA, 0xF4]; // CLI, HLT
+        machine.load_program(&program, 0x1000, 0x0000).unwrap();
+
+        let mut exec_control = ExecutionControl::new();
+        exec_control.set_state(ExecutionState::Running);
+        machine.run(1_000, &mut exec_control);
+
+        let halts: Vec<_> = machine
+            .drain_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                MachineEvent::GuestHalted(reason, _) => Some(reason),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(halts, vec![GuestHaltReason::HaltInterruptsDisabled]);
+    }
+
+    #[test]
+    fn run_until_stops_on_predicate_limit_or_halt() {
+        let mut machine = test_machine();
+        #[rustfmt::skip]
+        let program = [
+            0xB8, 0x34, 0x12, // MOV AX, 1234h
+            0xEB, 0xFE,       // JMP $
+        ];
+        machine.load_program(&program, 0x1000, 0x0000).unwrap();
+
+        let ax_loaded = |m: &Machine| m.cpu().get_register16(Register16::AX) == 0x1234;
+        let result = machine.run_until(ax_loaded, 1_000);
+        assert!(matches!(result, RunResult::PredicateMet(cycles) if cycles > 0 && cycles < 1_000));
+
+        // Once the predicate holds, it is met without running any further.
+        assert_eq!(machine.run_until(ax_loaded, 1_000), RunResult::PredicateMet(0));
+
+        // The loop never satisfies the predicate, so the limit is reached. Cycles may overshoot
+        // the limit by no more than an instruction.
+        let result = machine.run_until(|_| false, 1_000);
+        assert!(matches!(result, RunResult::CycleLimitReached(cycles) if (1_000..1_100).contains(&cycles)));
+
+        let program = [0xFA, 0xF4]; // CLI, HLT
+        machine.load_program(&program, 0x2000, 0x0000).unwrap();
+        machine.halt_behavior = OnHaltBehavior::Stop;
+        assert!(matches!(machine.run_until(|_| false, 1_000), RunResult::Halted(_)));
+    }
+
+    /// Program DMA channel 2 for a single mode transfer of `len` bytes at `address`.
+    fn program_fdc_dma(machine: &mut Machine, mode: u8, address: usize, len: usize) {
+        let bus = machine.bus_mut();
+        let count = len - 1;
+        bus.io_write_u8(dma::DMA_CHANNEL_MASK_REGISTER, 0x04 | fdc::FDC_DMA as u8, 0);
+        bus.io_write_u8(dma::DMA_CLEAR_FLIPFLOP, 0, 0);
+        bus.io_write_u8(dma::DMA_CHANNEL_MODE_REGISTER, mode | fdc::FDC_DMA as u8, 0);
+        bus.io_write_u8(dma::DMA_CHANNEL_2_ADDR_PORT, address as u8, 0);
+        bus.io_write_u8(dma::DMA_CHANNEL_2_ADDR_PORT, (address >> 8) as u8, 0);
+        bus.io_write_u8(dma::DMA_CHANNEL_2_PAGE_REGISTER, (address >> 16) as u8, 0);
+        bus.io_write_u8(dma::DMA_CHANNEL_2_WC_PORT, count as u8, 0);
+        bus.io_write_u8(dma::DMA_CHANNEL_2_WC_PORT, (count >> 8) as u8, 0);
+        bus.io_write_u8(dma::DMA_CHANNEL_MASK_REGISTER, fdc::FDC_DMA as u8, 0);
+    }
+
+    /// Send a command to the FDC, run it to completion and return the result bytes.
+    fn run_fdc_command(machine: &mut Machine, command: &[u8]) -> Vec<u8> {
+        let bus = machine.bus_mut();
+        for byte in command {
+            bus.io_write_u8(fdc::FDC_DATA_REGISTER, *byte, 0);
+        }
+
+        let mut dma = bus.dma_mut().take().unwrap();
+        let mut fdc = bus.fdc_mut().take().unwrap();
+        for _ in 0..1024 {
+            fdc.run(&mut dma, bus, 1.0);
+            dma.run(bus);
+        }
+        *bus.dma_mut() = Some(dma);
+        *bus.fdc_mut() = Some(fdc);
+
+        let mut results = Vec::new();
+        while results.len() < 16 && bus.io_read_u8(fdc::FDC_STATUS_REGISTER, 0) & fdc::FDC_STATUS_DIO != 0 {
+            results.push(bus.io_read_u8(fdc::FDC_DATA_REGISTER, 0));
+        }
+        results
+    }
+
+    #[test]
+    fn fdc_format_and_read_back_through_dma() {
+        let mut config = test_config();
+        config.fdc = Some(FloppyControllerConfig {
+            fdc_type: FdcType::IbmNec,
+            drive:    vec![FloppyDriveConfig {
+                fd_type: FloppyDriveType::Floppy360K,
+                image:   None,
+            }],
+        });
+        let mut machine = test_machine_from(config);
+        machine
+            .fdc()
+            .as_mut()
+            .unwrap()
+            .load_image_from(0, vec![0; 368_640], false)
+            .unwrap();
+
+        // Motor A on, DMA enabled, out of reset.
+        machine.bus_mut().io_write_u8(fdc::FDC_DIGITAL_OUTPUT_REGISTER, 0x1C, 0);
+
+        // Format cylinder 0, head 0 with 9 sectors filled with 0xF6, reading the ID fields by DMA.
+        let format_buffer = 0x2000;
+        for s in 0..9u8 {
+            let id = [0, 0, s + 1, 2];
+            for (i, byte) in id.iter().enumerate() {
+                machine
+                    .bus_mut()
+                    .write_u8(format_buffer + s as usize * 4 + i, *byte, 0)
+                    .unwrap();
+            }
+        }
+        program_fdc_dma(&mut machine, 0x48, format_buffer, 9 * 4);
+        let results = run_fdc_command(&mut machine, &[0x4D, 0x00, 0x02, 0x09, 0x50, 0xF6]);
+        assert_eq!(results.len(), 7);
+        assert_eq!(results[0] & 0xC0, 0, "format track terminated abnormally");
+
+        let image = machine.fdc().as_ref().unwrap().get_image_data(0).unwrap();
+        assert!(image[..9 * fdc::SECTOR_SIZE].iter().all(|b| *b == 0xF6));
+        assert!(image[9 * fdc::SECTOR_SIZE..].iter().all(|b| *b == 0));
+
+        // Read sector 3 back into memory by DMA.
+        let read_buffer = 0x3000;
+        program_fdc_dma(&mut machine, 0x44, read_buffer, fdc::SECTOR_SIZE);
+        let results = run_fdc_command(&mut machine, &[0x66, 0x00, 0x00, 0x00, 0x03, 0x02, 0x09, 0x2A, 0xFF]);
+        assert_eq!(results.len(), 7);
+        assert_eq!(results[0] & 0xC0, 0, "read sector terminated abnormally");
+
+        let bus = machine.bus_mut();
+        for address in read_buffer..read_buffer + fdc::SECTOR_SIZE {
+            assert_eq!(bus.peek_u8(address).unwrap(), 0xF6);
+        }
+        assert_eq!(bus.peek_u8(read_buffer + fdc::SECTOR_SIZE).unwrap(), 0);
+
+        // The transfer raised terminal count on channel 2 and the floppy IRQ.
+        assert_ne!(bus.io_read_u8(dma::DMA_STATUS_REGISTER, 0) & (1 << fdc::FDC_DMA), 0);
+        let irr = bus.pic_mut().as_ref().unwrap().get_string_state().irr;
+        assert_eq!(irr.as_bytes()[7 - fdc::FDC_IRQ as usize], b'1');
+    }
+
+    /// Return a test machine configuration with two 360K floppy drives.
+    fn test_config_with_floppies() -> MachineConfiguration {
+        let mut config = test_config();
+        config.fdc = Some(FloppyControllerConfig {
+            fdc_type: FdcType::IbmNec,
+            drive:    vec![
+                FloppyDriveConfig {
+                    fd_type: FloppyDriveType::Floppy360K,
+                    image:   None,
+                },
+                FloppyDriveConfig {
+                    fd_type: FloppyDriveType::Floppy360K,
+                    image:   None,
+                },
+            ],
+        });
+        config
+    }
+
+    /// Return a test machine with two 360K floppy drives.
+    fn test_machine_with_floppies() -> Machine {
+        test_machine_from(test_config_with_floppies())
+    }
+
+    #[test]
+    fn irq_labels_name_installed_devices() {
+        let mut config = test_config_with_floppies();
+        config.hdc = Some(HardDriveControllerConfig {
+            hdc_type: HardDiskControllerType::IbmXebec,
+            drive:    None,
+        });
+        let labels = test_machine_from(config).irq_labels();
+        assert_eq!(labels[0], "Timer");
+        assert_eq!(labels[1], "Keyboard");
+        assert_eq!(labels[hdc::HDC_IRQ as usize], "HDC");
+        assert_eq!(labels[fdc::FDC_IRQ as usize], "FDC");
+        assert_eq!(labels[4], "");
+
+        assert_eq!(test_machine().irq_labels()[fdc::FDC_IRQ as usize], "");
+    }
+
+    #[test]
+    fn pic_snapshot_reports_requested_interrupts() {
+        let mut machine = test_machine();
+        let before = machine.pic_snapshot();
+        assert_eq!(before.irr & 0x08, 0);
+
+        machine.bus_mut().pic_mut().as_mut().unwrap().request_interrupt(3);
+        let after = machine.pic_snapshot();
+        assert_eq!(after.irr & 0x08, 0x08);
+        assert_eq!(after.ir & 0x08, 0x08);
+        assert_eq!(after.imr, before.imr);
+        // INTR is only raised if the line is unmasked.
+        assert_eq!(after.intr, before.intr || after.imr & 0x08 == 0);
+    }
+
+    #[test]
+    fn floppy_boot_hold_is_released_at_bootstrap_or_boot_sector() {
+        let mut config = test_config_with_floppies();
+        config.hdc = Some(HardDriveControllerConfig {
+            hdc_type: HardDiskControllerType::IbmXebec,
+            drive:    None,
+        });
+        config.boot_order = vec![BootTarget::HardDisk(0), BootTarget::Floppy(0)];
+        let mut machine = test_machine_from(config);
+        assert!(machine.fdc().as_ref().unwrap().boot_hold());
+
+        let mut exec_control = ExecutionControl::new();
+        exec_control.set_state(ExecutionState::Running);
+
+        // No VHD is mounted, so the hold is released when the bootstrap loader is called.
+        machine.load_program(&[0xCD, 0x19, 0xEB, 0xFE], 0x1000, 0x0000).unwrap();
+        machine.bus_mut().write_u16(0x19 * 4, 0x0002, 0).unwrap();
+        machine.bus_mut().write_u16(0x19 * 4 + 2, 0x1000, 0).unwrap();
+        machine.run(1000, &mut exec_control);
+        assert!(!machine.in_post);
+        assert!(!machine.fdc().as_ref().unwrap().boot_hold());
+
+        // Otherwise, it is held until a boot sector runs.
+        machine.fdc().as_mut().unwrap().set_boot_hold(true);
+        machine.load_program(&[0xEB, 0xFE], 0x1000, 0x0000).unwrap();
+        machine.run(1000, &mut exec_control);
+        assert!(machine.fdc().as_ref().unwrap().boot_hold());
+
+        machine.load_program(&[0xEB, 0xFE], 0x0000, 0x7C00).unwrap();
+        machine.run(1000, &mut exec_control);
+        assert!(!machine.fdc().as_ref().unwrap().boot_hold());
+    }
+
+    #[test]
+    fn media_info_reports_mounted_floppy() {
+        let mut machine = test_machine_with_floppies();
+        let fdc = machine.fdc().as_mut().unwrap();
+        fdc.load_image_from(0, vec![0; 368_640], true).unwrap();
+        fdc.set_image_name(0, Some("dos.img".to_string()));
+
+        let slots = machine.media_info();
+        assert_eq!(slots.len(), 2);
+        assert_eq!(slots[0].kind, MediaKind::Floppy);
+
+        let media = slots[0].media.as_ref().unwrap();
+        assert_eq!(media.name.as_deref(), Some("dos.img"));
+        assert_eq!((media.cylinders, media.heads, media.sectors), (40, 2, 9));
+        assert_eq!(media.size, 368_640);
+        assert!(media.write_protected);
+        assert!(!media.dirty);
+
+        assert!(slots[1].media.is_none());
+        assert_eq!(slots[1].to_string(), "Floppy 1: empty");
+    }
+
+    #[test]
+    fn reads_above_installed_memory_return_open_bus() {
+        let mut config = test_config();
+        config.memory.conventional_memory_kb = Some(32);
+        let mut machine = test_machine_from(config);
+        assert_eq!(machine.bus().conventional_size(), 0x8000);
+
+        machine.bus_mut().write_u8(0x7FFF, 0x55, 0).unwrap();
+        machine.bus_mut().write_u8(0x8000, 0x55, 0).unwrap();
+        assert_eq!(machine.bus_mut().read_u8(0x7FFF, 0).unwrap().0, 0x55);
+        assert_eq!(machine.bus_mut().read_u8(0x8000, 0).unwrap().0, 0xFF);
+        // A word read straddling the top of memory gets open bus in the high byte.
+        assert_eq!(machine.bus_mut().read_u16(0x7FFF, 0).unwrap().0, 0xFF55);
+
+        // Clearing memory on reset must not make missing memory readable.
+        machine.reset(ResetKind::Cold);
+        assert_eq!(machine.bus_mut().read_u8(0x7FFF, 0).unwrap().0, 0x00);
+        assert_eq!(machine.bus_mut().read_u8(0x8000, 0).unwrap().0, 0xFF);
+    }
+
+    #[test]
+    fn open_bus_last_value_mode_returns_last_bus_value() {
+        let mut config = test_config();
+        config.memory.conventional_memory_kb = Some(32);
+        config.open_bus = Some(OpenBusMode::LastValue);
+        let mut machine = test_machine_from(config);
+
+        machine.bus_mut().write_u8(0x0100, 0xA5, 0).unwrap();
+        assert_eq!(machine.bus_mut().read_u8(0x9000, 0).unwrap().0, 0xA5);
+        // Nothing decodes port 0x310, so it reads back the last byte on the bus.
+        machine.bus_mut().io_write_u8(0x0310, 0x3C, 0);
+        assert_eq!(machine.bus_mut().io_read_u8(0x0310, 0), 0x3C);
+    }
+
+    #[test]
+    fn memory_wait_states_follow_region() {
+        let mut config = test_config();
+        config.memory.conventional.wait_states = 1;
+        let mut machine = test_machine_from(config);
+
+        machine.bus_mut().copy_from(&[0xCC; 0x2000], 0xFE000, 3, true).unwrap();
+        assert_eq!(machine.bus_mut().get_read_wait(0x00100, 0).unwrap(), 1);
+        assert_eq!(machine.bus_mut().get_read_wait(0x20000, 0).unwrap(), 0);
+        // ROM is zero-wait regardless of the cycle cost it was loaded with.
+        assert_eq!(machine.bus_mut().get_read_wait(0xFE100, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn rom_wait_states_follow_config() {
+        let mut config = test_config();
+        config.memory.rom_wait_states = 2;
+        let mut machine = test_machine_from(config);
+
+        machine.bus_mut().copy_from(&[0xCC; 0x2000], 0xFE000, 0, true).unwrap();
+        assert_eq!(machine.bus_mut().get_read_wait(0x00100, 0).unwrap(), 0);
+        assert_eq!(machine.bus_mut().get_read_wait(0xFE100, 0).unwrap(), 2);
+    }
+
+    /// Run a fixed LOOP of `iterations` with PIT channel 1 programmed as the BIOS does for DRAM
+    /// refresh, and return the number of CPU cycles taken.
+    fn refresh_loop_cycles(dram_refresh: bool, iterations: u16) -> u64 {
+        let mut config = test_config();
+        config.dram_refresh = Some(dram_refresh);
+        let mut machine = test_machine_from(config);
+        machine.set_cpu_option(CpuOption::EnableWaitStates(true));
+
+        // Channel 1, LSB only, mode 2 (rate generator), with the BIOS refresh count of 18.
+        machine.bus_mut().io_write_u8(pit::PIT_COMMAND_REGISTER, 0x54, 0);
+        machine.bus_mut().io_write_u8(pit::PIT_CHANNEL_1_DATA_PORT, 18, 0);
+
+        // MOV CX, iterations; LOOP $; CLI; HLT
+        let [lo, hi] = iterations.to_le_bytes();
+        machine.load_program(&[0xB9, lo, hi, 0xE2, 0xFE, 0xFA, 0xF4], 0x1000, 0x0000).unwrap();
+
+        let mut exec_control = ExecutionControl::new();
+        exec_control.set_state(ExecutionState::Running);
+        for _ in 0..100_000 {
+            if machine.is_halted() {
+                break;
+            }
+            machine.run(16, &mut exec_control);
+        }
+        assert!(machine.is_halted());
+        machine.cpu_cycles()
+    }
+
+    #[test]
+    fn dram_refresh_slows_cpu_when_enabled() {
+        let without_refresh = refresh_loop_cycles(false, 2000);
+        let with_refresh = refresh_loop_cycles(true, 2000);
+
+        // A refresh cycle steals about 4 bus cycles every 72 CPU cycles, so the loop should run
+        // a few percent slower with refresh enabled.
+        assert!(
+            with_refresh > without_refresh + without_refresh / 100,
+            "refresh on: {} cycles, off: {} cycles",
+            with_refresh,
+            without_refresh
+        );
+        assert!(
+            with_refresh < without_refresh + without_refresh / 10,
+            "refresh on: {} cycles, off: {} cycles",
+            with_refresh,
+            without_refresh
+        );
+    }
+
+    #[test]
+    fn cga_vram_writes_wait_for_cpu_slot() {
+        let mut config = test_config();
+        config.video.push(VideoCardConfig {
+            video_type: VideoType::CGA,
+            font: None,
+            vram_size: None,
+            vram_open_bus: false,
+        });
+        let mut machine = test_machine_from(config);
+
+        // A write loop with a 10 cycle body. The first write lands mid-character and waits 5
+        // cycles, after which the loop locks to every third CPU slot at 16 cycles per write.
+        let mut elapsed = 0;
+        let mut waits = Vec::new();
+        for i in 0..8 {
+            let wait = machine.bus_mut().get_write_wait(cga::CGA_MEM_ADDRESS + i, elapsed).unwrap();
+            waits.push(wait);
+            elapsed += 10 + wait;
+        }
+        assert_eq!(waits, [5, 6, 6, 6, 6, 6, 6, 6]);
+        assert_eq!(elapsed, 127);
+
+        // An 11 cycle body lands on the same phase every time.
+        let mut elapsed = 0;
+        for i in 0..8 {
+            let wait = machine.bus_mut().get_write_wait(cga::CGA_MEM_ADDRESS + i, elapsed).unwrap();
+            assert_eq!(wait, 5);
+            elapsed += 11 + wait;
+        }
+    }
+
+    #[test]
+    fn cga_vram_wraps_or_returns_open_bus_past_installed_memory() {
+        let mut config = test_config();
+        config.video.push(VideoCardConfig {
+            video_type: VideoType::CGA,
+            font: None,
+            vram_size: None,
+            vram_open_bus: false,
+        });
+        let mut machine = test_machine_from(config.clone());
+
+        // By default the 16K of VRAM is mirrored, so a write just past it lands at the start.
+        machine.bus_mut().write_u8(0xBBFFF + 1, 0x5A, 0).unwrap();
+        assert_eq!(machine.bus_mut().read_u8(cga::CGA_MEM_ADDRESS, 0).unwrap().0, 0x5A);
+
+        config.video[0].vram_open_bus = true;
+        let mut machine = test_machine_from(config);
+        machine.bus_mut().write_u8(0xBBFFF + 1, 0x5A, 0).unwrap();
+        assert_eq!(machine.bus_mut().read_u8(cga::CGA_MEM_ADDRESS, 0).unwrap().0, 0x00);
+        assert_eq!(machine.bus_mut().read_u8(0xBBFFF + 1, 0).unwrap().0, 0xFF);
+    }
+
+    #[test]
+    fn mda_reports_display_size_in_nine_dot_cells() {
+        let mut config = test_config();
+        config.video.push(VideoCardConfig {
+            video_type: VideoType::MDA,
+            font: None,
+            vram_size: None,
+            vram_open_bus: false,
+        });
+        let mut machine = test_machine_from(config);
+
+        // Program the CRTC the way the BIOS sets up 80x25 monochrome text.
+        for (reg, value) in [(1, 80), (6, 25), (9, 13)] {
+            machine.bus_mut().io_write_u8(0x3B4, reg, 0);
+            machine.bus_mut().io_write_u8(0x3B5, value, 0);
+        }
+
+        let video = machine.bus().primary_video().unwrap();
+        assert_eq!(video.get_video_type(), VideoType::MDA);
+        assert_eq!(video.get_display_size(), (720, 350));
+    }
+
+    #[test]
+    fn step_frame_pauses_after_one_frame() {
+        let mut config = test_config();
+        config.video.push(VideoCardConfig {
+            video_type: VideoType::CGA,
+            font: None,
+            vram_size: None,
+            vram_open_bus: false,
+        });
+        let mut machine = test_machine_from(config);
+        machine.load_program(&[0xEB, 0xFE], 0x1000, 0x0000).unwrap();
+
+        let start_frame = machine.bus().primary_video_frame_count().unwrap();
+        let mut exec_control = ExecutionControl::new();
+        exec_control.set_state(ExecutionState::Paused);
+        exec_control.set_op(ExecutionOperation::StepFrame);
+
+        // A CGA frame is roughly 80,000 CPU cycles, so this is plenty of updates.
+        for _ in 0..50 {
+            machine.run(10_000, &mut exec_control);
+            if let ExecutionState::Paused = exec_control.get_state() {
+                break;
+            }
+        }
+        assert!(matches!(exec_control.get_state(), ExecutionState::Paused));
+        assert_eq!(machine.bus().primary_video_frame_count(), Some(start_frame + 1));
+
+        // Another update without a new operation stays paused.
+        machine.run(10_000, &mut exec_control);
+        assert_eq!(machine.bus().primary_video_frame_count(), Some(start_frame + 1));
+    }
+
+    #[test]
+    fn halted_cpu_reports_halt_cycles() {
+        let mut machine = test_machine();
+        machine.set_cpu_option(CpuOption::HaltIdle(true));
+        // STI, HLT. Nothing will raise an interrupt, so the CPU stays halted.
+        machine.load_program(&[0xFB, 0xF4], 0x1000, 0x0000).unwrap();
+
+        let mut exec_control = ExecutionControl::new();
+        exec_control.set_state(ExecutionState::Running);
+
+        machine.run(1000, &mut exec_control);
+        assert!(machine.is_halted());
+
+        machine.run(10_000, &mut exec_control);
+        assert!(machine.is_halted());
+        assert!(machine.halt_cycles() >= 10_000);
+    }
+
+    fn rom_manifest(roms: &[(u32, u8, usize)]) -> MachineRomManifest {
+        let mut manifest = MachineRomManifest::new();
+        for &(rom_addr, fill, len) in roms {
+            manifest.roms.push(MachineRomEntry {
+                md5:  String::new(),
+                addr: rom_addr,
+                data: vec![fill; len],
+            });
+            manifest.rom_paths.push(PathBuf::new());
+        }
+        manifest
+    }
+
+    #[test]
+    fn reload_roms_replaces_previous_rom_set() {
+        let mut machine = test_machine();
+        // TestConfig suppresses ROM loading; allow the manifest to be installed.
+        machine.load_bios = true;
+        machine.reload_roms(rom_manifest(&[(0xF6000, 0xAA, 0x100), (0xFE000, 0xCC, 0x100)]));
+        assert_eq!(machine.bus_mut().read_u8(0xF6000, 0).unwrap().0, 0xAA);
+        assert_eq!(machine.bus_mut().read_u8(0xFE000, 0).unwrap().0, 0xCC);
+
+        let mut manifest = rom_manifest(&[(0xFE000, 0xBB, 0x100)]);
+        manifest.checkpoints.push(MachineCheckpoint {
+            addr: 0xFE000,
+            lvl:  0,
+            desc: "New BIOS".to_string(),
+        });
+        machine.reload_roms(manifest);
+
+        assert_eq!(machine.bus_mut().read_u8(0xFE000, 0).unwrap().0, 0xBB);
+        // The ROM that is no longer part of the set is unmapped and reads as open bus.
+        assert_eq!(machine.bus_mut().read_u8(0xF6000, 0).unwrap().0, 0xFF);
+        assert_eq!(machine.get_checkpoint_string(0).as_deref(), Some("New BIOS"));
+    }
+
+    /// Program PIT channel 0, LSB then MSB, with the given mode bits and reload value.
+    fn program_channel0(machine: &mut Machine, mode: u8, reload: u16) {
+        let [lo, hi] = reload.to_le_bytes();
+        machine.bus_mut().io_write_u8(pit::PIT_COMMAND_REGISTER, 0x30 | (mode << 1), 0);
+        machine.bus_mut().io_write_u8(pit::PIT_CHANNEL_0_DATA_PORT, lo, 0);
+        machine.bus_mut().io_write_u8(pit::PIT_CHANNEL_0_DATA_PORT, hi, 0);
+    }
+
+    #[test]
+    fn channel0_reload_override_is_dropped_on_reprogram() {
+        let mut machine = test_machine();
+        let mut kb_event_processed = false;
+
+        // Only a periodic mode accepts an override.
+        program_channel0(&mut machine, 0, 0x1000);
+        machine.run_devices(100, &mut kb_event_processed);
+        assert!(!machine.set_pit_channel0_reload(Some(0x0800)));
+
+        program_channel0(&mut machine, 2, 0x1000);
+        machine.run_devices(100, &mut kb_event_processed);
+        let state = machine.pit_channel0_state();
+        assert_eq!((state.mode, state.reload), (pit::ChannelMode::RateGenerator, 0x1000));
+        assert!(!state.reload_overridden);
+
+        // The override takes effect at the next reload.
+        assert!(machine.set_pit_channel0_reload(Some(0x0800)));
+        let cycles_per_tick = (machine.get_cpu_mhz() / pit::PIT_MHZ).ceil() as u32;
+        for _ in 0..0x1100 {
+            machine.run_devices(cycles_per_tick, &mut kb_event_processed);
+        }
+        let state = machine.pit_channel0_state();
+        assert_eq!(state.reload, 0x0800);
+        assert!(state.reload_overridden);
+        assert!(state.count <= 0x0800);
+
+        // None restores the value programmed by software...
+        assert!(machine.set_pit_channel0_reload(None));
+        let state = machine.pit_channel0_state();
+        assert_eq!(state.reload, 0x1000);
+        assert!(!state.reload_overridden);
+
+        // ...and so does reprogramming the channel.
+        assert!(machine.set_pit_channel0_reload(Some(0x0800)));
+        program_channel0(&mut machine, 3, 0x2000);
+        let state = machine.pit_channel0_state();
+        assert_eq!((state.mode, state.reload), (pit::ChannelMode::SquareWaveGenerator, 0x2000));
+        assert!(!state.reload_overridden);
+    }
+
+    /// Program the PIC and PIT channel 0 in square wave mode to raise IRQ0 every 0x1000 PIT ticks,
+    /// with the INT 08h vector pointing at `handler`.
+    fn enable_timer_interrupt(machine: &mut Machine, handler: u16) {
+        // ICW1, ICW2 (vector 8), ICW4, then unmask IRQ0.
+        for (port, byte) in [(0x20, 0x13), (0x21, 0x08), (0x21, 0x09), (0x21, 0xFE)] {
+            machine.bus_mut().io_write_u8(port, byte, 0);
+        }
+        // Channel 0, LSB then MSB, mode 3, with a reload value of 0x1000.
+        machine.bus_mut().io_write_u8(pit::PIT_COMMAND_REGISTER, 0x36, 0);
+        machine.bus_mut().io_write_u8(pit::PIT_CHANNEL_0_DATA_PORT, 0x00, 0);
+        machine.bus_mut().io_write_u8(pit::PIT_CHANNEL_0_DATA_PORT, 0x10, 0);
+
+        let [lo, hi] = handler.to_le_bytes();
+        machine.bus_mut().copy_from(&[lo, hi, 0x00, 0x00], 0x0020, 0, false).unwrap();
+    }
+
+    /// Run a HLT loop woken by PIT channel 0 in square wave mode with halt idling on or off.
+    /// Returns the number of timer interrupts serviced and the channel 0 count the handler
+    /// latched on the last one.
+    fn timer_halt_loop(halt_idle: bool) -> (u16, u16) {
+        let mut machine = test_machine();
+        machine.set_cpu_option(CpuOption::HaltIdle(halt_idle));
+        enable_timer_interrupt(&mut machine, 0x0600);
+
+        // INT 08h handler at 0000:0600. Count the interrupt, latch channel 0 and store the count, then EOI.
+        #[rustfmt::skip]
+        let handler = [
+            0xFF, 0x06, 0x00, 0x05, // inc word [0x0500]
+            0xB0, 0x00,             // mov al, 0x00
+            0xE6, 0x43,             // out 0x43, al
+            0xE4, 0x40,             // in al, 0x40
+            0xA2, 0x02, 0x05,       // mov [0x0502], al
+            0xE4, 0x40,             // in al, 0x40
+            0xA2, 0x03, 0x05,       // mov [0x0503], al
+            0xB0, 0x20,             // mov al, 0x20
+            0xE6, 0x20,             // out 0x20, al
+            0xCF,                   // iret
+        ];
+        machine.bus_mut().copy_from(&handler, 0x0600, 0, false).unwrap();
+
+        // xor ax, ax; mov ds, ax; sti; hlt; jmp back to hlt
+        machine
+            .load_program(&[0x31, 0xC0, 0x8E, 0xD8, 0xFB, 0xF4, 0xEB, 0xFD], 0x0000, 0x0700)
+            .unwrap();
+
+        let mut exec_control = ExecutionControl::new();
+        exec_control.set_state(ExecutionState::Running);
+        for _ in 0..20 {
+            machine.run(10_000, &mut exec_control);
+        }
+
+        let count = machine.bus_mut().read_u16(0x0500, 0).unwrap().0;
+        let latched = machine.bus_mut().read_u16(0x0502, 0).unwrap().0;
+        (count, latched)
+    }
+
+    #[test]
+    fn halt_idle_wakes_on_the_same_timer_cycle() {
+        let (count, latched) = timer_halt_loop(false);
+        assert!(count > 0);
+        assert_eq!(timer_halt_loop(true), (count, latched));
+    }
+
+    #[test]
+    fn watchdog_trips_on_loop_with_interrupts_enabled() {
+        let mut machine = test_machine();
+        machine.watchdog = WatchdogConfig {
+            enabled: true,
+            threshold_mcycles: 1,
+            pause: false,
+        };
+        enable_timer_interrupt(&mut machine, 0x2000);
+        // INT 08h handler: mov al, 0x20; out 0x20, al; iret
+        machine.bus_mut().copy_from(&[0xB0, 0x20, 0xE6, 0x20, 0xCF], 0x2000, 0, false).unwrap();
+        // sti; jmp $
+        machine.load_program(&[0xFB, 0xEB, 0xFE], 0x0000, 0x0700).unwrap();
+
+        let mut exec_control = ExecutionControl::new();
+        exec_control.set_state(ExecutionState::Running);
+        for _ in 0..15 {
+            machine.run(100_000, &mut exec_control);
+        }
+
+        let serviced = machine.bus().pic().as_ref().unwrap().serviced_count();
+        assert!(serviced > 0);
+        assert!(machine
+            .drain_events()
+            .iter()
+            .any(|event| matches!(event, MachineEvent::WatchdogTripped(_))));
+    }
+
+    #[test]
+    fn warm_reset_keeps_ram_and_sets_reset_flag() {
+        let mut machine = test_machine();
+        machine.bus_mut().write_u8(0x1000, 0xAA, 0).unwrap();
+
+        machine.reset(ResetKind::Warm);
+        assert_eq!(machine.bus_mut().read_u8(0x1000, 0).unwrap().0, 0xAA);
+        assert_eq!(machine.bus_mut().read_u16(0x0472, 0).unwrap().0, 0x1234);
+
+        machine.reset(ResetKind::Cold);
+        assert_eq!(machine.bus_mut().read_u8(0x1000, 0).unwrap().0, 0x00);
+        assert_eq!(machine.bus_mut().read_u16(0x0472, 0).unwrap().0, 0x0000);
+    }
+
+    #[test]
+    fn cycle_observer_sees_memory_write_bus_cycle() {
+        let mut machine = test_machine();
+        // MOV CS:[0300h], AL followed by a 'JMP $' loop.
+        machine
+            .load_program(&[0x2E, 0xA2, 0x00, 0x03, 0xEB, 0xFE], 0x0100, 0x0000)
+            .unwrap();
+
+        let states = Rc::new(RefCell::new(Vec::new()));
+        let observer_states = states.clone();
+        machine.set_cycle_observer(Box::new(move |state: &CycleState| {
+            observer_states.borrow_mut().push(*state);
+        }));
+
+        let mut exec_control = ExecutionControl::new();
+        exec_control.set_state(ExecutionState::Running);
+        machine.run(200, &mut exec_control);
+        machine.clear_cycle_observer();
+
+        let states = states.borrow();
+        assert!(!states.is_empty());
+
+        // The first code fetch is from the start of the program.
+        let fetch = states.iter().find(|s| s.b_state == BusState::CODE).unwrap();
+        assert_eq!(fetch.addr, 0x1000);
+
+        // The write is a T1-T4 bus cycle to CS:0300h, with ALE asserted at T1.
+        let write_idx = states.iter().position(|s| s.b_state == BusState::MEMW).unwrap();
+        assert_eq!(states[write_idx].addr, 0x1300);
+        assert!(states[write_idx].ale);
+
+        let bus_cycle: Vec<BusCycle> = states[write_idx..]
+            .iter()
+            .map(|s| s.t_state)
+            .filter(|t| *t != BusCycle::Tw)
+            .take(4)
+            .collect();
+        assert_eq!(bus_cycle, [BusCycle::T1, BusCycle::T2, BusCycle::T3, BusCycle::T4]);
+    }
+
+    #[test]
+    fn export_disassembly_marks_instruction_past_end() {
+        let mut machine = test_machine();
+        // NOP, NOP, MOV AX,1234h
+        machine.load_program(&[0x90, 0x90, 0xB8, 0x34, 0x12], 0x1000, 0x0000).unwrap();
+
+        let path = std::env::temp_dir().join(format!("martypc_disasm_{}.txt", std::process::id()));
+        let count = machine
+            .export_disassembly(
+                CpuAddress::Segmented(0x1000, 0x0000),
+                CpuAddress::Segmented(0x1000, 0x0003),
+                path.to_str().unwrap(),
+            )
+            .unwrap();
+        let listing = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(count, 3);
+        let lines: Vec<&str> = listing.lines().skip(1).collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("nop") && !lines[0].contains(';'));
+        assert!(lines[2].contains("mov") && lines[2].ends_with("; runs past end of range"));
+    }
+
+    #[test]
+    fn decode_current_resolves_memory_operand() {
+        let mut machine = test_machine();
+        // MOV AX,CS:[0004h], followed by the word it loads
+        machine
+            .load_program(&[0x2E, 0xA1, 0x04, 0x00, 0x34, 0x12], 0x1000, 0x0000)
+            .unwrap();
+
+        let decoded = machine.decode_current();
+        assert_eq!(decoded.instruction_bytes, [0x2E, 0xA1, 0x04, 0x00]);
+        assert_eq!(decoded.opcode, 0xA1);
+
+        let operand = decoded.memory_operand.unwrap();
+        assert_eq!(operand.segment_value, 0x1000);
+        assert_eq!(operand.offset, 0x0004);
+        assert_eq!(operand.linear_address, 0x10004);
+        assert_eq!(operand.bytes, [0x34, 0x12]);
+
+        // Decoding must not disturb the CPU.
+        assert_eq!(machine.cpu().get_csip(), CpuAddress::Segmented(0x1000, 0x0000));
+    }
+
+    #[test]
+    fn debug_session_round_trip_skips_stale_entries() {
+        let mut machine = test_machine();
+        machine.set_breakpoints(vec![
+            BreakPointType::Execute(0xF000, 0xE05B),
+            BreakPointType::MemAccessFlat(0x00400),
+            BreakPointType::Interrupt(0x21),
+        ]);
+        machine.add_watch(0x0500..0x0504, WatchAccess::Write);
+
+        let path = std::env::temp_dir().join(format!("martypc_session_{}.toml", std::process::id()));
+        machine.save_debug_session(&path).unwrap();
+
+        // Append a watchpoint that runs past the end of the address space.
+        let mut session_str = std::fs::read_to_string(&path).unwrap();
+        session_str.push_str("\n[[watchpoints]]\nstart = 1048575\nend = 1048577\naccess = \"Read\"\n");
+        std::fs::write(&path, session_str).unwrap();
+
+        let mut restored = test_machine();
+        let warnings = restored.load_debug_session(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(
+            restored.cpu().breakpoints(),
+            [
+                BreakPointType::ExecuteFlat(0xFE05B),
+                BreakPointType::MemAccessFlat(0x00400),
+                BreakPointType::Interrupt(0x21)
+            ]
+        ));
+        let watches = restored.bus().watches();
+        assert_eq!(watches.len(), 1);
+        assert_eq!(watches[0].range, 0x0500..0x0504);
+        assert_eq!(watches[0].access, WatchAccess::Write);
+    }
+
+    #[test]
+    fn drain_events_reports_floppy_changes_once() {
+        let mut machine = test_machine_with_floppies();
+        machine.drain_events();
+
+        machine
+            .fdc()
+            .as_mut()
+            .unwrap()
+            .load_image_from(1, vec![0; 368_640], false)
+            .unwrap();
+        let events = machine.drain_events();
+        assert!(events.iter().any(|e| matches!(e, MachineEvent::FloppyInserted(1))));
+
+        // Nothing has changed since the last drain.
+        assert!(machine.drain_events().is_empty());
+
+        machine.fdc().as_mut().unwrap().unload_image(1);
+        let events = machine.drain_events();
+        assert!(matches!(events[..], [MachineEvent::FloppyEjected(1)]));
+    }
+
+    #[test]
+    fn deterministic_machines_with_the_same_seed_match() {
+        // Sum 256 bytes of uninitialized memory at 0800:0000 and store the result at its start.
+        #[rustfmt::skip]
+        let program = [
+            0xBB, 0x00, 0x08,       // MOV BX, 0800h
+            0x8E, 0xDB,             // MOV DS, BX
+            0x31, 0xF6,             // XOR SI, SI
+            0x31, 0xD2,             // XOR DX, DX
+            0xB9, 0x00, 0x01,       // MOV CX, 0100h
+            0xAC,                   // LODSB
+            0x00, 0xC2,             // ADD DL, AL
+            0xE2, 0xFB,             // LOOP -5
+            0x88, 0x16, 0x00, 0x00, // MOV [0000h], DL
+            0xEB, 0xFE,             // JMP $
+        ];
+        let run = |seed| {
+            let mut machine = Machine::new_deterministic(seed, test_params(test_config()));
+            machine.load_program(&program, 0x1000, 0x0000).unwrap();
+            let mut exec_control = ExecutionControl::new();
+            exec_control.set_state(ExecutionState::Running);
+            machine.run(20_000, &mut exec_control);
+
+            let memory: Vec<u8> = (0x8000..0x8100)
+                .map(|address| machine.bus().peek_u8(address).unwrap())
+                .collect();
+            (memory, machine.cpu_cycles(), machine.cpu_instructions())
+        };
+
+        let (memory_a, cycles_a, instructions_a) = run(1);
+        let (memory_b, cycles_b, instructions_b) = run(1);
+        assert_eq!(memory_a, memory_b);
+        assert_eq!((cycles_a, instructions_a), (cycles_b, instructions_b));
+
+        let (memory_c, _, _) = run(2);
+        assert_ne!(memory_a, memory_c);
+    }
+
+    #[test]
+    fn deterministic_machine_pins_rtc() {
+        let mut machine_config = test_config();
+        machine_config.rtc = Some(RtcConfig {
+            rtc_type: RtcType::Mc146818,
+            io_base: None,
+            fixed_time: None,
+            utc_offset_minutes: None,
+        });
+        let mut machine = Machine::new_deterministic(1234, test_params(machine_config));
+
+        let rtc_time = |machine: &mut Machine| machine.bus_mut().rtc_mut().as_ref().unwrap().timestamp();
+        assert_eq!(rtc_time(&mut machine), DETERMINISTIC_RTC_TIME);
+
+        // The clock returns to the same time on reset.
+        machine.bus_mut().rtc_mut().as_mut().unwrap().run(5_000_000.0);
+        machine.reset(ResetKind::Cold);
+        assert_eq!(rtc_time(&mut machine), DETERMINISTIC_RTC_TIME);
+    }
+
+    #[test]
+    fn runtime_config_restores_default_cpu_clock() {
+        let mut machine = test_machine();
+        machine.load_program(&[0xEB, 0xFE], 0x1000, 0x0000).unwrap();
+        let mut exec_control = ExecutionControl::new();
+        exec_control.set_state(ExecutionState::Running);
+
+        let default_mhz = machine.get_cpu_mhz();
+        machine.set_cpu_mhz(default_mhz * 2.0).unwrap();
+        machine.run(1000, &mut exec_control);
+        assert!(machine.get_cpu_mhz() > default_mhz);
+
+        // TestConfig leaves the CPU clock unset, so reloading it returns to the machine's default.
+        machine.apply_runtime_config(&TEST_CONFIG);
+        machine.run(1000, &mut exec_control);
+        assert_eq!(machine.get_cpu_mhz(), default_mhz);
+    }
+}
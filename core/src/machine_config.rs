@@ -31,23 +31,28 @@
 */
 
 use crate::machine_types::{
+    BootTarget,
     FdcType,
     FloppyDriveType,
     HardDiskControllerType,
     HardDriveFormat,
     MachineType,
+    OpenBusMode,
+    SerialBackend,
     SerialControllerType,
     SerialMouseType,
+    SoundType,
+    UartType,
 };
 use anyhow::{anyhow, Error};
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf};
 
 use crate::{
     bus::ClockFactor,
     cpu_common::CpuType,
     device_traits::videocard::VideoType,
-    devices::{keyboard::KeyboardType, pit::PitType},
+    devices::{keyboard::KeyboardType, pit::PitType, rtc::RtcType},
     tracelogger::TraceLogger,
 };
 
@@ -116,6 +121,7 @@ lazy_static! {
         m.insert(MachineType::Ibm5150v64K, vec!["ibm5150v64k"]);
         m.insert(MachineType::Ibm5150v256K, vec!["ibm5150v256k"]);
         m.insert(MachineType::Ibm5160, vec!["ibm5160"]);
+        m.insert(MachineType::Tandy1000, vec!["tandy1000"]);
         m
     };
 
@@ -127,6 +133,7 @@ lazy_static! {
         m.insert(MachineType::Ibm5150v64K, vec!["ibm_basic"]);
         m.insert(MachineType::Ibm5150v256K, vec!["ibm_basic"]);
         m.insert(MachineType::Ibm5160, vec!["ibm_basic"]);
+        m.insert(MachineType::Tandy1000, vec![]);
         m
     };
 }
@@ -226,6 +233,28 @@ lazy_static! {
                     dma_type: DmaType::Single,
                 },
             ),
+            (
+                // The Tandy 1000 runs its 8088 off the same 14.318MHz crystal as the PC and XT, and
+                // keeps a PPI-compatible keyboard and speaker interface at ports 0x60-0x63.
+                MachineType::Tandy1000,
+                MachineDescriptor {
+                    machine_type: MachineType::Tandy1000,
+                    system_crystal: IBM_PC_SYSTEM_CLOCK,
+                    timer_crystal: None,
+                    bus_crystal: IBM_PC_SYSTEM_CLOCK,
+                    cpu_type: CpuType::Intel8088,
+                    cpu_factor: ClockFactor::Divisor(3),
+                    cpu_turbo_factor: ClockFactor::Divisor(2),
+                    bus_type: BusType::Isa8,
+                    bus_factor: ClockFactor::Divisor(1),
+                    timer_divisor: PIT_DIVISOR,
+                    have_ppi: true,
+                    kb_controller: KbControllerType::Ppi,
+                    pit_type: PitType::Model8253,
+                    pic_type: PicType::Single,
+                    dma_type: DmaType::Single,
+                },
+            ),
         ]);
         map
     };
@@ -237,10 +266,18 @@ pub fn get_machine_descriptor(machine_type: MachineType) -> Option<&'static Mach
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct MemoryConfig {
+    #[serde(default)]
     pub conventional: ConventionalMemoryConfig,
+    /// Installed conventional memory in KiB. Overrides `conventional.size` when present.
+    #[serde(default)]
+    pub conventional_memory_kb: Option<u32>,
+    /// Wait states to apply to each access to ROM. ROM is zero-wait unless this is set.
+    #[serde(default)]
+    pub rom_wait_states: u32,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
 pub struct ConventionalMemoryConfig {
     pub size: u32,
     pub wait_states: u32,
@@ -262,25 +299,86 @@ pub struct SerialMouseConfig {
     #[serde(rename = "type")]
     pub mouse_type: SerialMouseType,
     pub port: u32,
+    /// Maximum number of reports per second. Motion is accumulated between reports.
+    pub report_rate: Option<f64>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct VideoCardConfig {
     #[serde(rename = "type")]
     pub video_type: VideoType,
+    /// Optional font file to replace the card's built-in character ROM font. CGA expects an 8x8
+    /// font (2048 bytes) and MDA an 8x14 font (3584 bytes).
+    pub font: Option<PathBuf>,
+    /// Optional size of the card's video memory in bytes, overriding the card's standard amount.
+    /// Must be a power of two no larger than the card's memory aperture. Currently honored by
+    /// the CGA only.
+    pub vram_size: Option<usize>,
+    /// If true, accesses within the card's memory aperture but beyond its installed video memory
+    /// return open bus instead of wrapping around to the start of video memory.
+    #[serde(default)]
+    pub vram_open_bus: bool,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct SerialPortConfig {
     pub io_base: u32,
     pub irq: u32,
+    #[serde(default)]
+    pub backend: SerialBackend,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct SerialControllerConfig {
     #[serde(rename = "type")]
     pub sc_type: SerialControllerType,
-    pub port:    Vec<SerialPortConfig>,
+    #[serde(default)]
+    pub uart: UartType,
+    #[serde(default)]
+    pub port: Vec<SerialPortConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SoundDeviceConfig {
+    #[serde(rename = "type")]
+    pub sound_type: SoundType,
+    pub io_base: Option<u16>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EmsConfig {
+    pub size_kb: u32,
+    pub io_base: Option<u16>,
+    pub segment: Option<u16>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ParallelControllerConfig {
+    pub io_base: Option<u16>,
+    #[serde(default)]
+    pub printer: bool,
+    pub capture_file: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GamePortConfig {
+    pub io_base: Option<u16>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PostCardConfig {
+    pub io_base: Option<u16>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RtcConfig {
+    #[serde(rename = "type")]
+    pub rtc_type: RtcType,
+    pub io_base: Option<u16>,
+    /// Start the clock at this Unix timestamp instead of the host time.
+    pub fixed_time: Option<i64>,
+    /// Offset in minutes applied to the host time, which is read as UTC.
+    pub utc_offset_minutes: Option<i32>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -334,17 +432,29 @@ pub struct MachineConfiguration {
     pub ppi_turbo: Option<bool>,
     pub machine_type: MachineType,
     pub memory: MemoryConfig,
+    pub open_bus: Option<OpenBusMode>,
+    pub dram_refresh: Option<bool>,
     pub keyboard: Option<KeyboardConfig>,
     pub serial_mouse: Option<SerialMouseConfig>,
     pub video: Vec<VideoCardConfig>,
     pub serial: Vec<SerialControllerConfig>,
+    pub sound: Vec<SoundDeviceConfig>,
+    pub ems: Option<EmsConfig>,
+    pub game_port: Option<GamePortConfig>,
+    pub parallel: Option<ParallelControllerConfig>,
+    pub rtc: Option<RtcConfig>,
+    pub post_card: Option<PostCardConfig>,
     pub fdc: Option<FloppyControllerConfig>,
     pub hdc: Option<HardDriveControllerConfig>,
     pub media: Option<MediaConfig>,
+    pub boot_order: Vec<BootTarget>,
 }
 
 pub fn normalize_conventional_memory(config: &MachineConfiguration) -> Result<u32, Error> {
-    let mut conventional_memory = config.memory.conventional.size;
+    let mut conventional_memory = match config.memory.conventional_memory_kb {
+        Some(kb) => kb.saturating_mul(1024),
+        None => config.memory.conventional.size,
+    };
     conventional_memory = conventional_memory & 0xfffff000; // Normalize to 4K boundary
 
     // For 5150 machines we set conventional memory to the next largest valid DIP value
@@ -41,6 +41,33 @@ pub enum MachineType {
     Ibm5150v64K,
     Ibm5150v256K,
     Ibm5160,
+    Tandy1000,
+}
+
+impl MachineType {
+    /// Return the names of subdirectories that may hold ROMs for this machine type within a ROM
+    /// directory, in order of preference. Names are matched without regard to case.
+    pub fn rom_dir_names(&self) -> &'static [&'static str] {
+        match self {
+            MachineType::Fuzzer8088 => &["fuzzer8088"],
+            MachineType::Ibm5150v64K => &["ibm5150v64k", "ibm5150", "5150"],
+            MachineType::Ibm5150v256K => &["ibm5150v256k", "ibm5150", "5150"],
+            MachineType::Ibm5160 => &["ibm5160", "5160"],
+            MachineType::Tandy1000 => &["tandy1000"],
+        }
+    }
+
+    /// Return whether this machine type refreshes DRAM with DMA channel 0, triggered by PIT
+    /// channel 1. Refresh cycles steal the bus from the CPU, so they are emulated by default on
+    /// machines that have them.
+    pub fn has_dma_refresh(&self) -> bool {
+        match self {
+            MachineType::Ibm5150v64K | MachineType::Ibm5150v256K | MachineType::Ibm5160 => true,
+            // The Tandy 1000's video gate array refreshes memory during video fetches instead.
+            MachineType::Tandy1000 => false,
+            MachineType::Fuzzer8088 => false,
+        }
+    }
 }
 
 impl FromStr for MachineType {
@@ -54,6 +81,7 @@ impl FromStr for MachineType {
             "ibm5150v64k" => Ok(MachineType::Ibm5150v64K),
             "ibm5150v256k" => Ok(MachineType::Ibm5150v64K),
             "ibm5160" => Ok(MachineType::Ibm5160),
+            "tandy1000" => Ok(MachineType::Tandy1000),
             _ => Err("Bad value for model".to_string()),
         }
     }
@@ -82,6 +110,37 @@ impl FromStr for OnHaltBehavior {
     }
 }
 
+/// Default number of CPU cycles, in millions, before the watchdog trips.
+pub const WATCHDOG_DEFAULT_MCYCLES: u32 = 20;
+
+fn default_watchdog_mcycles() -> u32 {
+    WATCHDOG_DEFAULT_MCYCLES
+}
+
+/// Settings for the CPU watchdog, which reports a guest that appears to be stuck in a tight loop.
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct WatchdogConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Number of CPU cycles, in millions, the CPU may spend in a small range of code before the
+    /// watchdog trips. Time spent in hardware interrupt handlers counts towards the threshold.
+    #[serde(default = "default_watchdog_mcycles")]
+    pub threshold_mcycles: u32,
+    /// Pause execution when the watchdog trips.
+    #[serde(default)]
+    pub pause: bool,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_mcycles: WATCHDOG_DEFAULT_MCYCLES,
+            pause: false,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum FloppyDriveType {
     Floppy360K,
@@ -162,6 +221,59 @@ impl FromStr for HardDiskControllerType {
     }
 }
 
+/// A device the BIOS may boot from, as specified in a machine's boot order.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BootTarget {
+    Floppy(usize),
+    HardDisk(usize),
+}
+
+impl FromStr for BootTarget {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String>
+    where
+        Self: Sized,
+    {
+        let s = s.to_lowercase();
+        let bad_drive = |_| format!("Bad drive number for boot target: {}", s);
+        if let Some(drive) = s.strip_prefix("floppy") {
+            drive.parse::<usize>().map(BootTarget::Floppy).map_err(bad_drive)
+        }
+        else if let Some(drive) = s.strip_prefix("hdd") {
+            drive.parse::<usize>().map(BootTarget::HardDisk).map_err(bad_drive)
+        }
+        else {
+            Err(format!("Bad value for boot target: {}", s))
+        }
+    }
+}
+
+/// The value returned by reads of unmapped memory or unpopulated IO ports.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq)]
+pub enum OpenBusMode {
+    /// The bus floats high and reads return 0xFF.
+    #[serde(alias = "0xFF", alias = "0xff")]
+    High,
+    /// The bus is pulled low and reads return 0x00.
+    #[serde(alias = "0x00")]
+    Low,
+    /// The bus retains the last value driven onto it by the CPU or a device.
+    #[serde(alias = "last")]
+    LastValue,
+}
+
+impl OpenBusMode {
+    /// Return the most accurate open bus behavior for the specified machine type.
+    pub fn default_for(machine_type: MachineType) -> OpenBusMode {
+        match machine_type {
+            // The data bus on the PC and XT has pull-up resistors, so undriven reads return 0xFF.
+            MachineType::Ibm5150v64K | MachineType::Ibm5150v256K | MachineType::Ibm5160 => OpenBusMode::High,
+            MachineType::Tandy1000 => OpenBusMode::High,
+            MachineType::Fuzzer8088 => OpenBusMode::High,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq)]
 pub enum HardDriveFormat {
     Mfm,
@@ -173,7 +285,48 @@ pub enum SerialControllerType {
     IbmAsync,
 }
 
+/// The UART chip fitted to a serial controller. The original IBM adapter uses an INS8250, which
+/// has no scratch register or FIFOs. A 16550A adds the FIFO Control Register and 16-byte receive
+/// and transmit FIFOs.
+#[derive(Copy, Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+pub enum UartType {
+    #[default]
+    Ins8250,
+    Ns16550A,
+}
+
+/// A device attached to a serial port by configuration. Ports may still be bridged to a host
+/// serial port or TCP socket at runtime.
+#[derive(Copy, Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+pub enum SerialBackend {
+    #[default]
+    None,
+    /// A loopback plug. Transmitted bytes are received back by the same port, and the modem
+    /// control outputs are wired to the modem status inputs: DTR to DSR and DCD, RTS to CTS.
+    Loopback,
+}
+
 #[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq)]
 pub enum SerialMouseType {
     Microsoft,
 }
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq)]
+pub enum SoundType {
+    AdLib,
+    Sn76489,
+}
+
+impl FromStr for SoundType {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String>
+    where
+        Self: Sized,
+    {
+        match s.to_lowercase().as_str() {
+            "adlib" => Ok(SoundType::AdLib),
+            "sn76489" => Ok(SoundType::Sn76489),
+            _ => Err("Bad value for SoundType".to_string()),
+        }
+    }
+}
@@ -65,6 +65,8 @@ use crate::{
 use serde::Deserialize;
 
 pub const BIOS_READ_CYCLE_COST: u32 = 4;
+/// The IBM BIOS transfers control here via INT 18h when there is no bootable media.
+pub const ROM_BASIC_ADDRESS: u32 = 0xF6000;
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct RomOverride {
@@ -141,13 +143,42 @@ pub enum RomFeature {
     VGA,
 }
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum RomType {
     BIOS,
     BASIC,
     Diagnostic,
 }
 
+/// Identifies a single ROM in the active ROM set.
+#[derive(Clone, Debug)]
+pub struct RomInfo {
+    pub md5: &'static str,
+    pub rom_type: RomType,
+    pub name: &'static str,
+}
+
+/// Describes the active ROM set, for bug reports and the about box.
+#[derive(Clone, Debug)]
+pub struct RomSetInfo {
+    pub machine_type: MachineType,
+    pub roms: Vec<RomInfo>,
+    pub has_basic: bool,
+}
+
+impl Display for RomSetInfo {
+    /// Display the distinct ROM names in the set, ie "IBM 5150 BIOS 10/27/82 + BASIC 1.01".
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut names: Vec<&str> = Vec::new();
+        for rom in &self.roms {
+            if !names.contains(&rom.name) {
+                names.push(rom.name);
+            }
+        }
+        write!(f, "{}", names.join(" + "))
+    }
+}
+
 #[derive(Clone)]
 pub struct RomPatch {
     desc: &'static str,
@@ -171,6 +202,7 @@ pub struct RomSetOverride {
 }
 
 pub struct RomDescriptor {
+    name: &'static str,
     rom_type: RomType,
     present: bool,
     filename: PathBuf,
@@ -204,6 +236,28 @@ pub struct RomManager {
     raw_roms: Vec<(Vec<u8>, RawRomDescriptor)>,
 }
 
+/// Return the subdirectory of 'dir' that holds ROMs for 'machine_type', if one exists. This
+/// allows a ROM directory to keep each machine's ROMs in a folder of their own, such as
+/// 'roms/ibm5160'. See MachineType::rom_dir_names() for the recognized folder names.
+pub fn machine_rom_subdir(dir: &Path, machine_type: MachineType) -> Option<PathBuf> {
+    let subdirs: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir())
+        .collect();
+
+    machine_type.rom_dir_names().iter().find_map(|name| {
+        subdirs
+            .iter()
+            .find(|path| {
+                path.file_name()
+                    .and_then(|f| f.to_str())
+                    .is_some_and(|f| f.eq_ignore_ascii_case(name))
+            })
+            .cloned()
+    })
+}
+
 impl RomManager {
     pub fn new(
         machine_type: MachineType,
@@ -366,6 +420,7 @@ impl RomManager {
                 (
                     "6338a9808445de12109a2389b71ee2eb", // 5150 BIOS v1 04/24/81
                     RomDescriptor {
+                        name: "IBM 5150 BIOS 04/24/81",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -409,6 +464,7 @@ impl RomManager {
                 (
                     "6a1ed4e3f500d785a01ff4d3e000d79c", // 5150 BIOS v2 10/19/81
                     RomDescriptor {
+                        name: "IBM 5150 BIOS 10/19/81",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -429,6 +485,7 @@ impl RomManager {
                 (
                     "f453eb2df6daf21ec644d33663d85434",
                     RomDescriptor {
+                        name: "IBM 5150 BIOS 10/27/82",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -449,6 +506,7 @@ impl RomManager {
                 (
                     "d2fbadfecb1bd5509ddeaf40acf143ec", // GLABIOS_0.2.5_8PC.ROM
                     RomDescriptor {
+                        name: "GLaBIOS 0.2.5 (8PC)",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -469,6 +527,7 @@ impl RomManager {
                 (
                     "2ad31da203a49b504fad3a34af0c719f",
                     RomDescriptor {
+                        name: "BASIC 1.0",
                         rom_type: RomType::BASIC,
                         present: false,
                         filename: PathBuf::new(),
@@ -489,6 +548,7 @@ impl RomManager {
                 (
                     "eb28f0e8d3f641f2b58a3677b3b998cc",
                     RomDescriptor {
+                        name: "BASIC 1.01",
                         rom_type: RomType::BASIC,
                         present: false,
                         filename: PathBuf::new(),
@@ -509,6 +569,7 @@ impl RomManager {
                 (
                     "1a2ac1ae0fe0f7783197e78da8b3126c", // BIOS_5160_08NOV82_U18_1501512.BIN
                     RomDescriptor {
+                        name: "IBM 5160 BIOS 11/08/82",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -529,6 +590,7 @@ impl RomManager {
                 (
                     "e816a89768a1bf4b8d52b454d5c9d1e1", // BIOS_5160_08NOV82_U19_5000027_27256.BIN (32k Version)
                     RomDescriptor {
+                        name: "IBM 5160 BIOS 11/08/82",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -582,6 +644,7 @@ impl RomManager {
                 (
                     "69e2bd1d08c893cbf841607c8749d5bd", // BIOS_5160_08NOV82_U19_5000027.BIN (86box 8k version)
                     RomDescriptor {
+                        name: "IBM 5160 BIOS 11/08/82",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -635,6 +698,7 @@ impl RomManager {
                 (
                     "fd9ff9cbe0a8f154746ccb0a33f6d3e7", // BIOS_5160_10JAN86_U18_62X0851_27256_F800.BIN
                     RomDescriptor {
+                        name: "IBM 5160 BIOS 01/10/86",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -655,6 +719,7 @@ impl RomManager {
                 (
                     "f051b4bbc3b60c3a14df94a0e4ee720f", // BIOS_5160_10JAN86_U19_62X0854_27256_F000.BIN
                     RomDescriptor {
+                        name: "IBM 5160 BIOS 01/10/86",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -675,6 +740,7 @@ impl RomManager {
                 (
                     "9696472098999c02217bf922786c1f4a", // BIOS_5160_09MAY86_U18_59X7268_62X0890_27256_F800.BIN
                     RomDescriptor {
+                        name: "IBM 5160 BIOS 05/09/86",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -695,6 +761,7 @@ impl RomManager {
                 (
                     "df9f29de490d7f269a6405df1fed69b7", // BIOS_5160_09MAY86_U19_62X0819_68X4370_27256_F000.BIN
                     RomDescriptor {
+                        name: "IBM 5160 BIOS 05/09/86",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -759,6 +826,7 @@ impl RomManager {
                 (
                     "c9090b75c0332fc3509642ea193de7a2", // GLABIOS_0.2.4_8X.ROM
                     RomDescriptor {
+                        name: "GLaBIOS 0.2.4 (8X)",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -779,6 +847,7 @@ impl RomManager {
                 (
                     "f36c2dd29344eff6f55135f8b3014b81", // GLABIOS_0.2.5_8XC.ROM
                     RomDescriptor {
+                        name: "GLaBIOS 0.2.5 (8XC)",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -799,6 +868,7 @@ impl RomManager {
                 (
                     "66631d1a095d8d0d54cc917fbdece684", // IBM / Xebec 20 MB Fixed Disk Drive Adapter
                     RomDescriptor {
+                        name: "IBM/Xebec 20MB Fixed Disk Adapter",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -827,6 +897,7 @@ impl RomManager {
                     // ibm_6277356_ega_card_u44_27128.bin
                     "528455ed0b701722c166c6536ba4ff46",
                     RomDescriptor {
+                        name: "IBM EGA BIOS",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -859,6 +930,7 @@ impl RomManager {
                     // ibm_6277356_ega_card_u44_27128.bin
                     "0636f46316f3e15cb287ce3da6ba43a1",
                     RomDescriptor {
+                        name: "IBM EGA BIOS",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -889,6 +961,7 @@ impl RomManager {
                 (
                     "2057a38cb472300205132fb9c01d9d85", // IBM VGA Card ROM (32K)
                     RomDescriptor {
+                        name: "IBM VGA BIOS",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -924,6 +997,7 @@ impl RomManager {
                 (
                     "2c8a4e1db93d2cbe148b66122747e4f2", // IBM VGA Card ROM (24K)
                     RomDescriptor {
+                        name: "IBM VGA BIOS",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -944,6 +1018,7 @@ impl RomManager {
                 (
                     "5455948e02dcb8824af45f30e8e46ce6", // SeaBios VGA BIOS
                     RomDescriptor {
+                        name: "SeaBIOS VGA BIOS",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -964,6 +1039,7 @@ impl RomManager {
                 (
                     "3a0eacac07f1020b95ce06043982dfd1", // Supersoft PC/XT Diagnostic ROM
                     RomDescriptor {
+                        name: "Supersoft PC/XT Diagnostic ROM",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -984,6 +1060,7 @@ impl RomManager {
                 (
                     "b612305db2df43f88f9fb7f9b42d696e", // add.bin test suite
                     RomDescriptor {
+                        name: "Test suite: add.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1004,6 +1081,7 @@ impl RomManager {
                 (
                     "7c075d48c950ef1d2900c1a10698ac6c", // bitwise.bin test suite
                     RomDescriptor {
+                        name: "Test suite: bitwise.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1024,6 +1102,7 @@ impl RomManager {
                 (
                     "a3e85d6807b8f92547681eaca5fbb92f", // bcdcnv.bin test suite
                     RomDescriptor {
+                        name: "Test suite: bcdcnv.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1044,6 +1123,7 @@ impl RomManager {
                 (
                     "6b0a52be2b82fbfaf0e00b0c195c11c1", // cmpneg.bin test suite
                     RomDescriptor {
+                        name: "Test suite: cmpneg.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1064,6 +1144,7 @@ impl RomManager {
                 (
                     "d0d91c22fce1d2d57fa591190362d0a8", // datatrnf.bin test suite
                     RomDescriptor {
+                        name: "Test suite: datatrnf.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1084,6 +1165,7 @@ impl RomManager {
                 (
                     "87e6183b7a3f9e6f797e7bea092bc74d", // control.bin test suite
                     RomDescriptor {
+                        name: "Test suite: control.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1104,6 +1186,7 @@ impl RomManager {
                 (
                     "19a32b41480d0e7a6f77f748eaa231c9", // div.bin test suite
                     RomDescriptor {
+                        name: "Test suite: div.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1124,6 +1207,7 @@ impl RomManager {
                 (
                     "4cee4ef637299fe7e48196d3da1eb846", // interrupt.bin test suite
                     RomDescriptor {
+                        name: "Test suite: interrupt.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1144,6 +1228,7 @@ impl RomManager {
                 (
                     "edcd652c64df0bfb923d5499ea713992", // jmpmov.bin test suite
                     RomDescriptor {
+                        name: "Test suite: jmpmov.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1164,6 +1249,7 @@ impl RomManager {
                 (
                     "bdd8489b68773ccaeab434e985409ba6", // jump1.bin test suite
                     RomDescriptor {
+                        name: "Test suite: jump1.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1184,6 +1270,7 @@ impl RomManager {
                 (
                     "c9243ef5e2c6b6723db313473bf2519b", // jump2.bin test suite
                     RomDescriptor {
+                        name: "Test suite: jump2.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1204,6 +1291,7 @@ impl RomManager {
                 (
                     "7e81ea262fec23f0c20c8e11e7b2689a", // mul.bin test suite
                     RomDescriptor {
+                        name: "Test suite: mul.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1224,6 +1312,7 @@ impl RomManager {
                 (
                     "cb8c54acd992166a67ea3927131cf219", // rep.bin test suite
                     RomDescriptor {
+                        name: "Test suite: rep.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1244,6 +1333,7 @@ impl RomManager {
                 (
                     "317e7c9ce01851b6227ac01d48c7778e", // rotate.bin test suite
                     RomDescriptor {
+                        name: "Test suite: rotate.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1264,6 +1354,7 @@ impl RomManager {
                 (
                     "b2e5c51c10a1ce987cccebca8d0ba5c2", // segpr.bin test suite
                     RomDescriptor {
+                        name: "Test suite: segpr.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1284,6 +1375,7 @@ impl RomManager {
                 (
                     "3aa4d3110127adfa652812f0428d620a", // shifts.bin test suite
                     RomDescriptor {
+                        name: "Test suite: shifts.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1304,6 +1396,7 @@ impl RomManager {
                 (
                     "845902b2b98e43580c3b44a3c09c8376", // strings.bin test suite
                     RomDescriptor {
+                        name: "Test suite: strings.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1324,6 +1417,7 @@ impl RomManager {
                 (
                     "2e8df7c7c23646760dd18749d03b7b5a", // sub.bin test suite
                     RomDescriptor {
+                        name: "Test suite: sub.bin",
                         rom_type: RomType::BIOS,
                         present: false,
                         filename: PathBuf::new(),
@@ -1354,13 +1448,9 @@ impl RomManager {
         Ok(true)
     }
 
-    pub fn try_load_from_dir(&mut self, path: &Path) -> Result<bool, RomError> {
-        if let Some(_) = &self.rom_override {
-            // We have a rom override statement. Load the explicitly specified roms.
-
-            return self.try_load_override();
-        }
-
+    /// Check the files in 'path' against known ROM definitions for the current machine type,
+    /// marking any that match as present. Subdirectories are not scanned.
+    fn scan_dir(&mut self, path: &Path) -> Result<(), RomError> {
         // Read in directory entries within the provided path
         let dir = match fs::read_dir(path) {
             Ok(dir) => dir,
@@ -1371,6 +1461,9 @@ impl RomManager {
         // files that match rom definitions
         for entry in dir {
             if let Ok(entry) = entry {
+                if entry.path().is_dir() {
+                    continue;
+                }
                 let file_vec = match std::fs::read(entry.path()) {
                     Ok(vec) => vec,
                     Err(e) => {
@@ -1404,6 +1497,23 @@ impl RomManager {
             }
         }
 
+        Ok(())
+    }
+
+    pub fn try_load_from_dir(&mut self, path: &Path) -> Result<bool, RomError> {
+        if let Some(_) = &self.rom_override {
+            // We have a rom override statement. Load the explicitly specified roms.
+
+            return self.try_load_override();
+        }
+
+        // Scan the provided path, then the subdirectory for this machine type if there is one.
+        self.scan_dir(path)?;
+        if let Some(subdir) = machine_rom_subdir(path, self.machine_type) {
+            log::debug!("Scanning machine ROM directory: {:?}", subdir);
+            self.scan_dir(&subdir)?;
+        }
+
         // Loop through all ROM set definitions for this machine type and mark which are complete
         // and them to a vec of complete rom sets
         for set in self
@@ -1589,6 +1699,43 @@ impl RomManager {
         Ok(true)
     }
 
+    /// Return a description of the ROM set selected by try_load_from_dir(), or None if no ROM
+    /// set has been loaded.
+    pub fn active_rom_set_info(&self) -> Option<RomSetInfo> {
+        let rom_set = self.rom_set_active.as_ref()?;
+
+        let roms: Vec<RomInfo> = rom_set
+            .roms
+            .iter()
+            .filter_map(|md5| {
+                self.get_romdesc(md5).map(|desc| RomInfo {
+                    md5: *md5,
+                    rom_type: desc.rom_type,
+                    name: desc.name,
+                })
+            })
+            .collect();
+        let has_basic = roms.iter().any(|rom| rom.rom_type == RomType::BASIC);
+
+        Some(RomSetInfo {
+            machine_type: rom_set.machine_type,
+            roms,
+            has_basic,
+        })
+    }
+
+    /// Return whether ROM BASIC will be present in memory. If raw ROMs have been added we can't
+    /// identify them, so we check whether any of them is mapped over the ROM BASIC address instead.
+    pub fn has_basic(&self) -> bool {
+        if !self.raw_roms.is_empty() {
+            return self.raw_roms.iter().any(|(rom, desc)| {
+                let len = rom.len().saturating_sub(desc.offset as usize) as u32;
+                (desc.addr..desc.addr + len).contains(&ROM_BASIC_ADDRESS)
+            });
+        }
+        self.active_rom_set_info().map_or(false, |info| info.has_basic)
+    }
+
     pub fn get_romdesc(&self, key: &str) -> Option<&RomDescriptor> {
         self.rom_defs.get(key)
     }
@@ -32,14 +32,25 @@
 
 #![allow(dead_code)]
 
+use anyhow::{anyhow, Error};
+#[cfg(feature = "sound")]
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use ringbuf::{
     Producer,
     //Consumer,
     RingBuffer,
 };
-//use std::fs::File;
-//use std::io::Write;
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use serde::Deserialize;
 
 pub const VOLUME_ADJUST: f32 = 0.10;
 
@@ -49,11 +60,153 @@ pub const BUFFER_MS: f32 = 100.0;
 #[cfg(not(target_arch = "wasm32"))]
 pub const BUFFER_MS: f32 = 30.0;
 
+// Samples above this magnitude are gently compressed instead of hard-clipped.
+const SOFT_CLIP_THRESHOLD: f32 = 0.8;
+
+// Length of the ramp applied to output when pausing or resuming, to avoid a pop.
+const FADE_MS: f32 = 5.0;
+
+// Sample rate reported by a null player, which has no device to take a rate from.
+const NULL_SAMPLE_RATE: u32 = 44100;
+
+/// Selects how sound output is produced. The Cpal backend is only available when marty_core is
+/// built with the `sound` feature; otherwise SoundPlayer::open_default() returns an error.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioBackend {
+    /// Output to the host's default audio device via cpal.
+    #[default]
+    Cpal,
+    /// Discard all output. Useful for CI, containers and other hosts without audio.
+    None,
+}
+
+/// Identifies a sound-producing device that is mixed into the SoundPlayer's output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SoundSource {
+    PcSpeaker,
+    AdLib,
+    Sn76489,
+}
+
+impl SoundSource {
+    pub const COUNT: usize = 3;
+}
+
+#[derive(Copy, Clone, Debug)]
+struct SourceControl {
+    volume: f32,
+    muted:  bool,
+}
+
+impl Default for SourceControl {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            muted:  false,
+        }
+    }
+}
+
+const WAV_HEADER_LEN: u32 = 44;
+const WAV_FORMAT_PCM: u16 = 1;
+const WAV_FORMAT_IEEE_FLOAT: u16 = 3;
+
+/// The format samples are stored in when captured to a WAV file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum SampleFormat {
+    F32,
+    I16,
+}
+
+#[cfg(feature = "sound")]
+impl From<cpal::SampleFormat> for SampleFormat {
+    fn from(format: cpal::SampleFormat) -> Self {
+        match format {
+            cpal::SampleFormat::F32 => SampleFormat::F32,
+            // WAV has no unsigned 16-bit format, so U16 output is captured as signed PCM.
+            cpal::SampleFormat::I16 | cpal::SampleFormat::U16 => SampleFormat::I16,
+        }
+    }
+}
+
+/// A minimal WAV file writer. Samples are written in the host's output sample format, and the
+/// RIFF and data chunk lengths are patched into the header on finalize().
+struct WavWriter {
+    writer: BufWriter<File>,
+    sample_format: SampleFormat,
+    channels: u16,
+    data_len: u32,
+}
+
+impl WavWriter {
+    fn new(path: &Path, sample_format: SampleFormat, sample_rate: u32, channels: u16) -> Result<Self, Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        let (format_tag, bytes_per_sample) = match sample_format {
+            SampleFormat::F32 => (WAV_FORMAT_IEEE_FLOAT, 4u16),
+            SampleFormat::I16 => (WAV_FORMAT_PCM, 2u16),
+        };
+        let block_align = channels * bytes_per_sample;
+
+        // Lengths are written as 0 and filled in when the capture is finalized.
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&format_tag.to_le_bytes())?;
+        writer.write_all(&channels.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&(bytes_per_sample * 8).to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self {
+            writer,
+            sample_format,
+            channels,
+            data_len: 0,
+        })
+    }
+
+    /// Write a mono sample, duplicated across all channels as it is sent to the audio device.
+    fn write_sample(&mut self, sample: f32) -> Result<(), Error> {
+        for _ in 0..self.channels {
+            match self.sample_format {
+                SampleFormat::F32 => {
+                    self.writer.write_all(&sample.to_le_bytes())?;
+                    self.data_len += 4;
+                }
+                SampleFormat::I16 => {
+                    let s = sample_to_i16(sample);
+                    self.writer.write_all(&s.to_le_bytes())?;
+                    self.data_len += 2;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finalize(mut self) -> Result<(), Error> {
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer
+            .write_all(&(WAV_HEADER_LEN - 8 + self.data_len).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(40))?;
+        self.writer.write_all(&self.data_len.to_le_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
 pub struct SoundPlayer {
-    audio_device: cpal::Device,
+    #[cfg(feature = "sound")]
+    audio_device: Option<cpal::Device>,
     //audio_config_s: cpal::SupportedStreamConfig,
     //audio_config: cpal::StreamConfig,
-    sample_format: cpal::SampleFormat,
+    sample_format: SampleFormat,
     sample_rate: u32,
     channels: usize,
 
@@ -61,10 +214,21 @@ pub struct SoundPlayer {
     pub samples_produced: u64,
 
     pub buffer_producer: Producer<f32>,
-    output_stream: cpal::Stream,
+    #[cfg(feature = "sound")]
+    output_stream: Option<cpal::Stream>,
+    paused: Arc<AtomicBool>,
+
+    wav_capture: Option<WavWriter>,
+    // Samples retained for retrieval with take_samples(), if this is a collecting player.
+    collected: Option<Vec<f32>>,
+
+    master_volume: f32,
+    master_muted: bool,
+    sources: [SourceControl; SoundSource::COUNT],
 }
 
 impl SoundPlayer {
+    #[cfg(feature = "sound")]
     pub fn get_device() -> (cpal::Device, cpal::SampleFormat) {
         let audio_device = cpal::default_host()
             .default_output_device()
@@ -84,13 +248,103 @@ impl SoundPlayer {
         (audio_device, config.sample_format())
     }
 
+    /// Open the host's default output device, choosing the sample type from its default config.
+    /// Unlike get_device() and new(), this returns an error if no usable device is available so
+    /// that the caller can fall back to a null player.
+    #[cfg(feature = "sound")]
+    pub fn open_default() -> Result<Self, Error> {
+        let audio_device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| anyhow!("No default audio output device"))?;
+        let config = audio_device.default_output_config()?;
+
+        log::info!(
+            "Using default audio device: {}",
+            audio_device.name().unwrap_or_else(|_| "Unknown".to_string())
+        );
+        log::debug!("Default audio config: {:?}", config);
+
+        // The cpal sound library uses generics to initialize depending on the SampleFormat type.
+        match config.sample_format() {
+            cpal::SampleFormat::F32 => Self::try_new::<f32>(audio_device),
+            cpal::SampleFormat::I16 => Self::try_new::<i16>(audio_device),
+            cpal::SampleFormat::U16 => Self::try_new::<u16>(audio_device),
+        }
+    }
+
+    /// Without the `sound` feature there is no audio output, so this always returns an error.
+    #[cfg(not(feature = "sound"))]
+    pub fn open_default() -> Result<Self, Error> {
+        Err(anyhow!("marty_core was built without the sound feature"))
+    }
+
+    /// Create a player with no output device. Samples are mixed and may be captured to a WAV file
+    /// as usual, but are otherwise discarded.
+    pub fn null() -> Self {
+        let (buffer_producer, _) = RingBuffer::new(1).split();
+        Self {
+            #[cfg(feature = "sound")]
+            audio_device: None,
+            sample_format: SampleFormat::F32,
+            sample_rate: NULL_SAMPLE_RATE,
+            samples_consumed: 0,
+            samples_produced: 0,
+            channels: 1,
+            buffer_producer,
+            #[cfg(feature = "sound")]
+            output_stream: None,
+            paused: Arc::new(AtomicBool::new(false)),
+            wav_capture: None,
+            collected: None,
+            master_volume: 1.0,
+            master_muted: false,
+            sources: [SourceControl::default(); SoundSource::COUNT],
+        }
+    }
+
+    /// Create a player with no output device that retains the samples it is given, at the
+    /// specified sample rate. This is intended for applications embedding the emulator that
+    /// output audio themselves; retrieve the samples with take_samples().
+    pub fn collector(sample_rate: u32) -> Self {
+        let mut player = Self::null();
+        player.sample_rate = sample_rate;
+        player.collected = Some(Vec::new());
+        player
+    }
+
+    /// Return and clear the samples collected since the last call. Returns an empty Vec unless
+    /// this player was created with collector().
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        self.collected.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Returns true if this player has no output device.
+    #[cfg(feature = "sound")]
+    pub fn is_null(&self) -> bool {
+        self.output_stream.is_none()
+    }
+
+    #[cfg(not(feature = "sound"))]
+    pub fn is_null(&self) -> bool {
+        true
+    }
+
+    #[cfg(feature = "sound")]
     pub fn new<T>(audio_device: cpal::Device) -> Self
     where
         T: cpal::Sample,
     {
-        let config = audio_device.default_output_config().unwrap();
+        Self::try_new::<T>(audio_device).expect("Failed to initialize audio output")
+    }
 
-        let sample_format = config.sample_format();
+    #[cfg(feature = "sound")]
+    pub fn try_new<T>(audio_device: cpal::Device) -> Result<Self, Error>
+    where
+        T: cpal::Sample,
+    {
+        let config = audio_device.default_output_config()?;
+
+        let sample_format = config.sample_format().into();
         let sample_rate = config.sample_rate().0;
         let channels = config.channels() as usize;
 
@@ -108,13 +362,27 @@ impl SoundPlayer {
 
         //let mut debug_snd_file = File::create("output2.pcm").expect("Couldn't open debug pcm file");
 
+        let paused = Arc::new(AtomicBool::new(false));
+        let stream_paused = paused.clone();
+        let fade_step = 1.0 / (sample_rate as f32 * (FADE_MS / 1000.0)).max(1.0);
+        let mut fade: f32 = 0.0;
+        let mut last_value: f32 = 0.0;
+
         let mut _consumer_count: u64 = 0;
-        let _last_value: f32 = 0.0;
         let mut refill_buffer: bool = true;
         let mut next_value = move || {
             _consumer_count += 1;
             //log::trace!("consumer: {}", consumer_count);
 
+            if stream_paused.load(Ordering::Relaxed) {
+                // Discard anything queued before the pause so it isn't played on resume, and ramp
+                // the last sample down to silence.
+                while buffer_consumer.pop().is_some() {}
+                refill_buffer = true;
+                fade = (fade - fade_step).max(0.0);
+                return last_value * fade;
+            }
+
             if refill_buffer {
                 if buffer_consumer.len() < min_buffer {
                     return 0.0;
@@ -133,19 +401,21 @@ impl SoundPlayer {
                 }
             };
             //debug_snd_file.write(&s.to_be_bytes());
-            sample
+
+            // Ramp back up after a pause.
+            fade = (fade + fade_step).min(1.0);
+            last_value = sample;
+            sample * fade
         };
 
-        let output_stream = audio_device
-            .build_output_stream(
-                &config.into(),
-                move |data: &mut [T], _: &cpal::OutputCallbackInfo| write_data(data, channels, &mut next_value),
-                err_fn,
-            )
-            .expect("Failed to build an output audio stream");
+        let output_stream = audio_device.build_output_stream(
+            &config.into(),
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| write_data(data, channels, &mut next_value),
+            err_fn,
+        )?;
 
-        Self {
-            audio_device,
+        Ok(Self {
+            audio_device: Some(audio_device),
             //audio_config_s: config,
             //audio_config: config.into(),
             sample_format,
@@ -154,23 +424,169 @@ impl SoundPlayer {
             samples_produced: 0,
             channels,
             buffer_producer,
-            output_stream,
-        }
+            output_stream: Some(output_stream),
+            paused,
+            wav_capture: None,
+            collected: None,
+            master_volume: 1.0,
+            master_muted: false,
+            sources: [SourceControl::default(); SoundSource::COUNT],
+        })
     }
 
     pub fn play(&self) {
-        self.output_stream.play().unwrap();
+        #[cfg(feature = "sound")]
+        if let Some(stream) = &self.output_stream {
+            stream.play().unwrap();
+        }
     }
 
+    /// Silence output while emulation is paused. Queued samples are discarded and output is
+    /// faded out rather than cut off. On the web, the stream is suspended, which suspends the
+    /// AudioContext.
+    pub fn pause(&mut self) {
+        if self.paused.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        log::debug!("Pausing sound output.");
+        #[cfg(all(feature = "sound", target_arch = "wasm32"))]
+        if let Some(Err(e)) = self.output_stream.as_ref().map(|s| s.pause()) {
+            log::warn!("Failed to suspend audio stream: {}", e);
+        }
+    }
+
+    /// Resume output after pause(). Output fades in once the buffer has refilled.
+    pub fn resume(&mut self) {
+        if !self.paused.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        log::debug!("Resuming sound output.");
+        #[cfg(all(feature = "sound", target_arch = "wasm32"))]
+        if let Some(Err(e)) = self.output_stream.as_ref().map(|s| s.play()) {
+            log::warn!("Failed to resume audio stream: {}", e);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Queue a mixed sample for output. Master volume and soft clipping are applied here.
     pub fn queue_sample(&mut self, data: f32) {
-        match self.buffer_producer.push(data) {
-            Ok(_) => {}
-            Err(_) => {}
+        let sample = self.apply_master_volume(data);
+        // Only capture samples that will actually be played, so the capture matches what was heard.
+        if self.is_null() || self.buffer_producer.push(sample).is_ok() {
+            self.capture_samples(&[sample]);
         }
     }
 
     pub fn queue_sample_slice(&mut self, data: &[f32]) {
-        self.buffer_producer.push_slice(data);
+        let samples: Vec<f32> = data.iter().map(|s| self.apply_master_volume(*s)).collect();
+        let queued = if self.is_null() {
+            samples.len()
+        }
+        else {
+            self.buffer_producer.push_slice(&samples)
+        };
+        self.capture_samples(&samples[..queued]);
+    }
+
+    /// Set the master output volume, from 0.0 (silent) to 1.0 (full). The value is clamped.
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn master_volume(&self) -> f32 {
+        self.master_volume
+    }
+
+    /// Silence all output without discarding the master volume setting. Unlike pause(), samples
+    /// continue to be queued, so output resumes immediately when unmuted.
+    pub fn set_master_muted(&mut self, muted: bool) {
+        self.master_muted = muted;
+    }
+
+    pub fn is_master_muted(&self) -> bool {
+        self.master_muted
+    }
+
+    /// Set the volume of an individual sound source, from 0.0 to 1.0. The value is clamped.
+    pub fn set_source_volume(&mut self, source: SoundSource, volume: f32) {
+        self.sources[source as usize].volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn source_volume(&self, source: SoundSource) -> f32 {
+        self.sources[source as usize].volume
+    }
+
+    pub fn set_source_muted(&mut self, source: SoundSource, muted: bool) {
+        self.sources[source as usize].muted = muted;
+    }
+
+    pub fn is_source_muted(&self, source: SoundSource) -> bool {
+        self.sources[source as usize].muted
+    }
+
+    /// Return the gain to apply to a source's samples before mixing.
+    pub fn source_gain(&self, source: SoundSource) -> f32 {
+        let control = &self.sources[source as usize];
+        if control.muted {
+            0.0
+        }
+        else {
+            control.volume
+        }
+    }
+
+    fn apply_master_volume(&self, sample: f32) -> f32 {
+        if self.master_muted {
+            return 0.0;
+        }
+        soft_clip(sample * self.master_volume)
+    }
+
+    /// Begin recording all queued samples to a WAV file at the specified path. The file is written
+    /// at the player's sample rate, channel count and sample format. Any capture in progress is
+    /// finalized first.
+    pub fn start_wav_capture(&mut self, path: &Path) -> Result<(), Error> {
+        self.stop_wav_capture()?;
+        self.wav_capture = Some(WavWriter::new(
+            path,
+            self.sample_format,
+            self.sample_rate,
+            self.channels as u16,
+        )?);
+        log::debug!("Started WAV capture: {}", path.display());
+        Ok(())
+    }
+
+    /// Stop recording and finalize the WAV header. Does nothing if no capture is in progress.
+    pub fn stop_wav_capture(&mut self) -> Result<(), Error> {
+        if let Some(wav) = self.wav_capture.take() {
+            let data_len = wav.data_len;
+            wav.finalize()?;
+            log::debug!("Stopped WAV capture. Wrote {} bytes of sample data.", data_len);
+        }
+        Ok(())
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.wav_capture.is_some()
+    }
+
+    fn capture_samples(&mut self, data: &[f32]) {
+        if let Some(collected) = &mut self.collected {
+            collected.extend_from_slice(data);
+        }
+        if let Some(wav) = &mut self.wav_capture {
+            for sample in data {
+                if let Err(e) = wav.write_sample(*sample) {
+                    log::error!("Error writing WAV capture, stopping: {}", e);
+                    self.wav_capture = None;
+                    return;
+                }
+            }
+        }
     }
 
     pub fn sample_rate(&self) -> u32 {
@@ -178,6 +594,36 @@ impl SoundPlayer {
     }
 }
 
+impl Drop for SoundPlayer {
+    fn drop(&mut self) {
+        // Make sure an in-progress capture gets a valid header.
+        _ = self.stop_wav_capture();
+    }
+}
+
+/// Compress samples above SOFT_CLIP_THRESHOLD smoothly toward +/-1.0 to avoid harsh clipping.
+fn soft_clip(sample: f32) -> f32 {
+    let magnitude = sample.abs();
+    if magnitude <= SOFT_CLIP_THRESHOLD {
+        return sample;
+    }
+    let headroom = 1.0 - SOFT_CLIP_THRESHOLD;
+    let compressed = SOFT_CLIP_THRESHOLD + headroom * ((magnitude - SOFT_CLIP_THRESHOLD) / headroom).tanh();
+    compressed.copysign(sample)
+}
+
+/// Convert a sample to signed 16-bit PCM, scaling positive and negative values to the full range.
+fn sample_to_i16(sample: f32) -> i16 {
+    let sample = sample.clamp(-1.0, 1.0);
+    if sample >= 0.0 {
+        (sample * i16::MAX as f32) as i16
+    }
+    else {
+        (-sample * i16::MIN as f32) as i16
+    }
+}
+
+#[cfg(feature = "sound")]
 fn write_data<T>(output: &mut [T], channels: usize, next_sample: &mut dyn FnMut() -> f32)
 where
     T: cpal::Sample,
@@ -190,3 +636,61 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn null_player_discards_samples() {
+        let mut player = SoundPlayer::null();
+        assert!(player.is_null());
+        assert_eq!(player.sample_rate(), NULL_SAMPLE_RATE);
+
+        player.queue_sample(0.5);
+        player.queue_sample_slice(&[0.25; 4096]);
+        player.play();
+        player.pause();
+        player.resume();
+        assert!(player.take_samples().is_empty());
+    }
+
+    #[test]
+    fn collector_returns_queued_samples() {
+        let mut player = SoundPlayer::collector(22050);
+        assert!(player.is_null());
+        assert_eq!(player.sample_rate(), 22050);
+
+        player.queue_sample(0.5);
+        player.queue_sample_slice(&[0.25, -0.25]);
+        assert_eq!(player.take_samples(), vec![0.5, 0.25, -0.25]);
+        assert!(player.take_samples().is_empty());
+    }
+
+    #[test]
+    fn null_player_captures_wav() {
+        let path = std::env::temp_dir().join(format!("martypc_null_capture_{}.wav", std::process::id()));
+        let mut player = SoundPlayer::null();
+        player.start_wav_capture(&path).unwrap();
+        player.queue_sample_slice(&[0.0, 0.5, -0.5]);
+        player.stop_wav_capture().unwrap();
+
+        let wav = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(wav.len(), WAV_HEADER_LEN as usize + 3 * 4);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(
+            u16::from_le_bytes(wav[20..22].try_into().unwrap()),
+            WAV_FORMAT_IEEE_FLOAT
+        );
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), NULL_SAMPLE_RATE);
+        assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()), 3 * 4);
+        assert_eq!(f32::from_le_bytes(wav[48..52].try_into().unwrap()), 0.5);
+    }
+
+    #[cfg(not(feature = "sound"))]
+    #[test]
+    fn open_default_fails_without_sound_feature() {
+        assert!(SoundPlayer::open_default().is_err());
+    }
+}
@@ -0,0 +1,119 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    symbols.rs
+
+    Parses symbol files that map addresses to names. Two formats are accepted:
+
+    A simple text format with one 'address name' pair per line, where the
+    address is either segment:offset or a linear address in hex.
+
+    The public symbol listings of Borland and Watcom linker .map files. Lines
+    that don't start with an address, such as headers and segment tables, are
+    ignored.
+
+*/
+
+use crate::cpu_808x::Cpu;
+
+const SYMBOL_ADDRESS_SPACE: u32 = 0x100000;
+
+/// Parse the text of a symbol file into a list of (linear address, name) pairs, in file order.
+pub fn parse_symbols(text: &str) -> Vec<(u32, String)> {
+    let mut symbols = Vec::new();
+
+    for line in text.lines() {
+        let mut tokens = line.split_whitespace();
+        let address = match tokens.next().and_then(parse_symbol_address) {
+            Some(address) => address,
+            None => continue,
+        };
+        // Borland maps mark absolute and unreferenced publics with a tag before the name.
+        let name = match tokens.find(|t| !matches!(*t, "Abs" | "Idle")) {
+            Some(name) => name,
+            None => continue,
+        };
+        // Names can't start with a digit. This rejects the sizes listed in map segment tables.
+        if name.starts_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+        symbols.push((address, name.to_string()));
+    }
+
+    symbols
+}
+
+/// Parse a segment:offset or linear hex address. Watcom maps may follow an address with '*' or
+/// '+' to flag unreferenced or local symbols.
+fn parse_symbol_address(token: &str) -> Option<u32> {
+    let token = token.trim_end_matches(['*', '+']);
+
+    if let Some((segment, offset)) = token.split_once(':') {
+        let segment = u16::from_str_radix(segment, 16).ok()?;
+        let offset = u16::from_str_radix(offset, 16).ok()?;
+        return Some(Cpu::calc_linear_address(segment, offset));
+    }
+
+    let hex = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+        .unwrap_or(token);
+    u32::from_str_radix(hex, 16)
+        .ok()
+        .filter(|address| *address < SYMBOL_ADDRESS_SPACE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_simple_and_map_formats() {
+        let text = "\
+; simple format
+F000:E05B reset
+0xFE6F2 int_19
+
+  Address         Publics by Value
+
+ 0000:0010       _main
+ 0000:0000  Abs  __AHINCR
+ 0000:0040 0AB2 C=CODE S=_TEXT
+0010:0020*     _unused_
+";
+        let symbols = parse_symbols(text);
+        assert_eq!(
+            symbols,
+            vec![
+                (0xFE05B, "reset".to_string()),
+                (0xFE6F2, "int_19".to_string()),
+                (0x00010, "_main".to_string()),
+                (0x00000, "__AHINCR".to_string()),
+                (0x00120, "_unused_".to_string()),
+            ]
+        );
+    }
+}
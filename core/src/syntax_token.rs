@@ -76,6 +76,8 @@ pub enum SyntaxToken {
     HexValue(String),
     Register(String),
     Displacement(String),
+    // Symbolic name for an address
+    Label(String),
 
     Formatter(SyntaxFormatType),
 }
@@ -111,6 +113,7 @@ impl fmt::Display for SyntaxToken {
             SyntaxToken::HexValue(value) => write!(f, "{}", value),
             SyntaxToken::Register(register) => write!(f, "{}", register),
             SyntaxToken::Displacement(displacement) => write!(f, "{}", displacement),
+            SyntaxToken::Label(label) => write!(f, "{}", label),
 
             SyntaxToken::Formatter(fmt_type) => match fmt_type {
                 SyntaxFormatType::Space => write!(f, " "),
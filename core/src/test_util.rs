@@ -0,0 +1,171 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    test_util.rs
+
+    Fixtures for building a Machine in tests, shared by the core's own tests
+    and by frontend crates through the 'test_util' feature.
+
+*/
+
+use std::path::PathBuf;
+
+use crate::{
+    coreconfig::CoreConfig,
+    cpu_common::TraceMode,
+    cpu_validator::ValidatorType,
+    machine_config::{ConventionalMemoryConfig, MachineConfiguration, MemoryConfig},
+    machine_types::{MachineType, OnHaltBehavior, WatchdogConfig},
+    tracelogger::TraceTargetConfig,
+};
+
+/// A CoreConfig for an IBM 5160 that loads no ROMs. Audio sources are muted unless audio is
+/// enabled.
+pub struct TestConfig {
+    pub audio_enabled: bool,
+    pub halt_behavior: OnHaltBehavior,
+    pub entry_point:   Option<(u16, u16)>,
+}
+
+pub const TEST_CONFIG: TestConfig = TestConfig {
+    audio_enabled: false,
+    halt_behavior: OnHaltBehavior::Continue,
+    entry_point:   None,
+};
+
+impl CoreConfig for TestConfig {
+    fn get_base_dir(&self) -> PathBuf {
+        PathBuf::new()
+    }
+    fn get_machine_type(&self) -> MachineType {
+        MachineType::Ibm5160
+    }
+    fn get_audio_enabled(&self) -> bool {
+        self.audio_enabled
+    }
+    fn get_audio_volume(&self) -> Option<f32> {
+        None
+    }
+    fn get_audio_mute_speaker(&self) -> bool {
+        !self.audio_enabled
+    }
+    fn get_audio_mute_adlib(&self) -> bool {
+        !self.audio_enabled
+    }
+    fn get_audio_mute_post_beep(&self) -> bool {
+        false
+    }
+    fn get_machine_noroms(&self) -> bool {
+        true
+    }
+    fn get_machine_turbo(&self) -> bool {
+        false
+    }
+    fn get_machine_turbo_mhz(&self) -> Option<f64> {
+        None
+    }
+    fn get_machine_cpu_mhz(&self) -> Option<f64> {
+        None
+    }
+    fn get_keyboard_layout(&self) -> Option<String> {
+        None
+    }
+    fn get_keyboard_debug(&self) -> bool {
+        false
+    }
+    fn get_validator_type(&self) -> Option<ValidatorType> {
+        None
+    }
+    fn get_validator_trace_file(&self) -> Option<PathBuf> {
+        None
+    }
+    fn get_validator_baud(&self) -> Option<u32> {
+        None
+    }
+    fn get_cpu_trace_mode(&self) -> Option<TraceMode> {
+        None
+    }
+    fn get_cpu_trace_on(&self) -> bool {
+        false
+    }
+    fn get_cpu_trace_file(&self) -> Option<PathBuf> {
+        None
+    }
+    fn get_title_hacks(&self) -> bool {
+        false
+    }
+    fn get_fast_boot(&self) -> bool {
+        false
+    }
+    fn get_patch_enabled(&self) -> bool {
+        false
+    }
+    fn get_halt_behavior(&self) -> OnHaltBehavior {
+        self.halt_behavior
+    }
+    fn get_watchdog(&self) -> WatchdogConfig {
+        WatchdogConfig::default()
+    }
+    fn get_trace_targets(&self) -> Vec<TraceTargetConfig> {
+        Vec::new()
+    }
+    fn get_entry_point(&self) -> Option<(u16, u16)> {
+        self.entry_point
+    }
+}
+
+/// An IBM 5160 with 64K of conventional memory and no devices beyond the motherboard.
+pub fn test_machine_config() -> MachineConfiguration {
+    MachineConfiguration {
+        speaker: false,
+        ppi_turbo: None,
+        machine_type: MachineType::Ibm5160,
+        memory: MemoryConfig {
+            conventional: ConventionalMemoryConfig {
+                size: 0x10000,
+                wait_states: 0,
+            },
+            conventional_memory_kb: None,
+            rom_wait_states: 0,
+        },
+        open_bus: None,
+        dram_refresh: None,
+        keyboard: None,
+        serial_mouse: None,
+        video: Vec::new(),
+        serial: Vec::new(),
+        sound: Vec::new(),
+        ems: None,
+        game_port: None,
+        parallel: None,
+        rtc: None,
+        post_card: None,
+        fdc: None,
+        hdc: None,
+        media: None,
+        boot_order: Vec::new(),
+    }
+}
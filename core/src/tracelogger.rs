@@ -33,14 +33,115 @@
 */
 
 use std::{
+    collections::HashMap,
     fs::File,
     io::{BufWriter, Write},
-    path::Path,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 
+use serde::Deserialize;
+
+/// A subsystem that can produce trace output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceTarget {
+    Cpu,
+    Video,
+    Fdc,
+    Dma,
+    Pic,
+    All,
+}
+
+/// Selects a trace target and where its output goes. If no file is specified, trace output is
+/// printed to the console along with any other target without a file.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TraceTargetConfig {
+    pub target: TraceTarget,
+    pub file: Option<PathBuf>,
+}
+
+impl TraceTargetConfig {
+    /// Return whether this entry enables tracing for 'target'.
+    pub fn includes(&self, target: TraceTarget) -> bool {
+        self.target == target || self.target == TraceTarget::All
+    }
+
+    /// Return the file that 'target' traces to under this entry, or None to trace to the console.
+    /// When tracing all targets to a file, each target gets its own file, named by appending the
+    /// target to the file stem.
+    pub fn file_for(&self, target: TraceTarget) -> Option<PathBuf> {
+        match &self.file {
+            Some(file) if self.target == TraceTarget::All => {
+                let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+                let mut name = format!("{}_{:?}", stem, target).to_lowercase();
+                if let Some(ext) = file.extension() {
+                    name.push('.');
+                    name.push_str(&ext.to_string_lossy());
+                }
+                Some(file.with_file_name(name))
+            }
+            Some(file) => Some(file.clone()),
+            None => None,
+        }
+    }
+}
+
+/// The configured trace targets, along with the trace files opened for them so far. Targets that
+/// name the same file share a single writer, rather than each truncating and overwriting it.
+#[derive(Default)]
+pub struct TraceTargets {
+    configs: Vec<TraceTargetConfig>,
+    open_files: HashMap<PathBuf, Arc<Mutex<BufWriter<File>>>>,
+}
+
+impl TraceTargets {
+    pub fn new(configs: Vec<TraceTargetConfig>) -> Self {
+        Self {
+            configs,
+            open_files: HashMap::new(),
+        }
+    }
+
+    /// Return whether any entry enables tracing for 'target'.
+    pub fn includes(&self, target: TraceTarget) -> bool {
+        self.configs.iter().any(|config| config.includes(target))
+    }
+
+    /// Return a TraceLogger for 'target' from the first matching entry, or TraceLogger::None if
+    /// the target isn't being traced.
+    pub fn logger_for(&mut self, target: TraceTarget) -> TraceLogger {
+        let config = match self.configs.iter().find(|config| config.includes(target)) {
+            Some(config) => config,
+            None => return TraceLogger::None,
+        };
+        let path = match config.file_for(target) {
+            Some(path) => path,
+            None => return TraceLogger::Console,
+        };
+
+        if let Some(writer) = self.open_files.get(&path) {
+            return TraceLogger::SharedFileWriter(writer.clone());
+        }
+        match File::create(&path) {
+            Ok(file) => {
+                let writer = Arc::new(Mutex::new(BufWriter::new(file)));
+                self.open_files.insert(path, writer.clone());
+                TraceLogger::SharedFileWriter(writer)
+            }
+            Err(e) => {
+                eprintln!("Couldn't create specified tracelog file: {}", e);
+                TraceLogger::None
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum TraceLogger {
     FileWriter(BufWriter<File>),
+    SharedFileWriter(Arc<Mutex<BufWriter<File>>>),
     Console,
     None,
 }
@@ -56,7 +157,7 @@ impl TraceLogger {
         match File::create(filename) {
             Ok(file) => TraceLogger::FileWriter(BufWriter::new(file)),
             Err(e) => {
-                eprintln!("Couldn't create specified video tracelog file: {}", e);
+                eprintln!("Couldn't create specified tracelog file: {}", e);
                 TraceLogger::None
             }
         }
@@ -68,6 +169,11 @@ impl TraceLogger {
             TraceLogger::FileWriter(buf) => {
                 _ = buf.write_all(msg.as_ref().as_bytes());
             }
+            TraceLogger::SharedFileWriter(writer) => {
+                if let Ok(mut buf) = writer.lock() {
+                    _ = buf.write_all(msg.as_ref().as_bytes());
+                }
+            }
             TraceLogger::Console => println!("{}", msg),
             TraceLogger::None => (),
         }
@@ -80,21 +186,91 @@ impl TraceLogger {
                 _ = buf.write_all(msg.as_ref().as_bytes());
                 _ = buf.write_all("\n".as_bytes());
             }
+            TraceLogger::SharedFileWriter(writer) => {
+                if let Ok(mut buf) = writer.lock() {
+                    _ = buf.write_all(msg.as_ref().as_bytes());
+                    _ = buf.write_all("\n".as_bytes());
+                }
+            }
             TraceLogger::Console => println!("{}", msg),
             TraceLogger::None => (),
         }
     }
 
     pub fn flush(&mut self) {
-        if let TraceLogger::FileWriter(file) = self {
-            if let Err(e) = file.flush() {
-                log::error!("Failed to flush trace log: {}", e);
-            }
+        let result = match self {
+            TraceLogger::FileWriter(file) => file.flush(),
+            TraceLogger::SharedFileWriter(writer) => match writer.lock() {
+                Ok(mut file) => file.flush(),
+                Err(_) => Ok(()),
+            },
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            log::error!("Failed to flush trace log: {}", e);
         }
     }
 
     #[inline(always)]
     pub fn is_some(&self) -> bool {
-        matches!(*self, TraceLogger::FileWriter(_) | TraceLogger::Console)
+        matches!(
+            *self,
+            TraceLogger::FileWriter(_) | TraceLogger::SharedFileWriter(_) | TraceLogger::Console
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(target: TraceTarget, file: Option<&Path>) -> TraceTargetConfig {
+        TraceTargetConfig {
+            target,
+            file: file.map(Path::to_path_buf),
+        }
+    }
+
+    #[test]
+    fn targets_sharing_a_file_write_to_one_writer() {
+        let path = std::env::temp_dir().join(format!("martypc_trace_shared_{}.log", std::process::id()));
+        let mut targets = TraceTargets::new(vec![
+            target(TraceTarget::Dma, Some(&path)),
+            target(TraceTarget::Pic, Some(&path)),
+        ]);
+
+        let mut dma = targets.logger_for(TraceTarget::Dma);
+        let mut pic = targets.logger_for(TraceTarget::Pic);
+        dma.println("dma");
+        pic.println("pic");
+        dma.flush();
+        pic.flush();
+        drop((dma, pic));
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        _ = std::fs::remove_file(&path);
+        assert_eq!(contents, "dma\npic\n");
+    }
+
+    #[test]
+    fn first_matching_entry_selects_the_logger() {
+        let mut targets = TraceTargets::new(vec![target(TraceTarget::Fdc, None)]);
+        assert!(targets.includes(TraceTarget::Fdc));
+        assert!(!targets.includes(TraceTarget::Cpu));
+        assert!(matches!(targets.logger_for(TraceTarget::Fdc), TraceLogger::Console));
+        assert!(matches!(targets.logger_for(TraceTarget::Cpu), TraceLogger::None));
+    }
+
+    #[test]
+    fn tracing_all_targets_splits_the_file_per_target() {
+        let all = target(TraceTarget::All, Some(Path::new("traces/trace.log")));
+        assert!(all.includes(TraceTarget::Video));
+        assert_eq!(
+            all.file_for(TraceTarget::Video),
+            Some(PathBuf::from("traces/trace_video.log"))
+        );
+
+        let cpu = target(TraceTarget::Cpu, Some(Path::new("traces/trace.log")));
+        assert_eq!(cpu.file_for(TraceTarget::Cpu), Some(PathBuf::from("traces/trace.log")));
     }
 }
@@ -324,6 +324,11 @@ impl VirtualHardDisk {
         })
     }
 
+    /// Return the size of the image file in bytes, including the VHD footer.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
     /// Return a byte offset given a CHS (Cylinder, Head, Sector) address
     ///
     /// Hard drive sectors are allowed to start at 0
@@ -0,0 +1,284 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    tests::single_step_tests.rs
+
+    Run the 8088 SingleStepTests JSON test suite.
+
+    Each test specifies the initial registers and memory, the expected final
+    registers and memory after a single instruction, and a cycle-by-cycle bus
+    trace. Tests are read from the directory given by MARTY_SST_DIR, either as
+    plain .json files or gzipped .json.gz files as distributed. If the directory
+    contains the suite's metadata.json, undefined flags are masked out of the
+    flag comparison.
+
+    Set MARTY_SST_CYCLES to also compare the bus transactions of each test
+    against the cycle trace. Set MARTY_SST_OPCODES to a comma separated list of
+    opcodes to only run some files, eg. "F6,F7".
+
+    The test is skipped if MARTY_SST_DIR is not set.
+*/
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    env,
+    fs::{self, File},
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+
+use marty_core::{
+    cpu_808x::Cpu,
+    cpu_common::{CpuOption, CpuType, TraceMode},
+    cpu_validator::{BusState, CycleState, VRegisters},
+    tracelogger::TraceLogger,
+};
+
+#[cfg(feature = "cpu_validator")]
+use marty_core::cpu_validator::{ValidatorMode, ValidatorType};
+
+const MAX_REP_ITERATIONS: usize = 0x10000;
+
+#[derive(Deserialize)]
+struct TestState {
+    regs: VRegisters,
+    ram:  Vec<[u32; 2]>,
+}
+
+#[derive(Deserialize)]
+struct CpuTest {
+    name:   String,
+    #[serde(rename = "initial")]
+    initial_state: TestState,
+    #[serde(rename = "final")]
+    final_state: TestState,
+    cycles: Vec<CycleState>,
+}
+
+#[derive(Default)]
+struct OpcodeResult {
+    passed: usize,
+    failed: usize,
+    first_failure: Option<String>,
+}
+
+fn read_tests(path: &Path) -> Result<Vec<CpuTest>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut json = String::new();
+    if path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("gz")) {
+        GzDecoder::new(BufReader::new(file))
+            .read_to_string(&mut json)
+            .map_err(|e| e.to_string())?;
+    }
+    else {
+        BufReader::new(file)
+            .read_to_string(&mut json)
+            .map_err(|e| e.to_string())?;
+    }
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+/// Return the opcode name of a test file, such as "F6.4" for "F6.4.json.gz".
+fn opcode_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    let name = file_name.strip_suffix(".gz").unwrap_or(file_name);
+    let name = name.strip_suffix(".json")?;
+    (name != "metadata").then(|| name.to_uppercase())
+}
+
+/// Look up the mask of defined flags for an opcode in the suite's metadata.
+fn flags_mask(metadata: &Option<serde_json::Value>, opcode: &str) -> u16 {
+    let Some(metadata) = metadata
+    else {
+        return 0xFFFF;
+    };
+
+    let mut parts = opcode.split('.');
+    let mut entry = &metadata["opcodes"][parts.next().unwrap_or_default()];
+    if let Some(reg) = parts.next() {
+        entry = &entry["reg"][reg];
+    }
+    entry["flags-mask"].as_u64().map_or(0xFFFF, |mask| mask as u16)
+}
+
+/// Return the bus transactions of a cycle trace as address and bus state pairs, taken at ALE.
+fn bus_transactions(cycles: &[CycleState]) -> Vec<(u32, BusState)> {
+    cycles.iter().filter(|c| c.ale).map(|c| (c.addr, c.b_state)).collect()
+}
+
+fn new_cpu() -> Cpu {
+    let mut cpu = Cpu::new(
+        CpuType::Intel8088,
+        TraceMode::None,
+        TraceLogger::None,
+        #[cfg(feature = "cpu_validator")]
+        ValidatorType::None,
+        #[cfg(feature = "cpu_validator")]
+        TraceLogger::None,
+        #[cfg(feature = "cpu_validator")]
+        ValidatorMode::Instruction,
+        #[cfg(feature = "cpu_validator")]
+        1_000_000,
+    );
+    cpu.set_option(CpuOption::EnableWaitStates(false));
+    cpu
+}
+
+/// Run a single test, returning a description of the first mismatch on failure.
+fn run_test(
+    cpu: &mut Cpu,
+    test: &CpuTest,
+    flags_mask: u16,
+    check_cycles: bool,
+    cycles: &Rc<RefCell<Vec<CycleState>>>,
+) -> Result<(), String> {
+    cpu.load_state(&test.initial_state.regs);
+    for [address, byte] in &test.initial_state.ram {
+        cpu.bus_mut()
+            .write_u8(*address as usize, *byte as u8, 0)
+            .map_err(|e| format!("failed to write initial memory: {}", e))?;
+    }
+
+    cycles.borrow_mut().clear();
+
+    // REP string instructions are stepped once per iteration.
+    for _ in 0..MAX_REP_ITERATIONS {
+        cpu.step(false).map_err(|e| format!("CPU error: {}", e))?;
+        if !cpu.in_rep() {
+            break;
+        }
+    }
+    _ = cpu.step_finish();
+
+    let mut expected = test.final_state.regs;
+    let mut actual = cpu.dump_state();
+    expected.flags &= flags_mask;
+    actual.flags &= flags_mask;
+    if expected != actual {
+        return Err(format!("registers:\nexpected:\n{}\nactual:\n{}", expected, actual));
+    }
+
+    for [address, byte] in &test.final_state.ram {
+        let actual = cpu.bus().peek_u8(*address as usize).unwrap_or(0xFF);
+        if actual != *byte as u8 {
+            return Err(format!(
+                "memory at {:05X}: expected {:02X}, actual {:02X}",
+                address, byte, actual
+            ));
+        }
+    }
+
+    if check_cycles {
+        // The CPU may prefetch past the end of the test's trace, so only its start must match.
+        let expected = bus_transactions(&test.cycles);
+        let actual = bus_transactions(&cycles.borrow());
+        if !actual.starts_with(&expected) {
+            return Err(format!(
+                "bus transactions:\nexpected: {:05X?}\nactual: {:05X?}",
+                expected, actual
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn single_step_tests() {
+    let Ok(test_dir) = env::var("MARTY_SST_DIR")
+    else {
+        eprintln!("MARTY_SST_DIR not set, skipping SingleStepTests.");
+        return;
+    };
+    let test_dir = PathBuf::from(test_dir);
+    let check_cycles = env::var("MARTY_SST_CYCLES").is_ok();
+    let opcode_filter: Option<Vec<String>> = env::var("MARTY_SST_OPCODES")
+        .ok()
+        .map(|s| s.split(',').map(|op| op.trim().to_uppercase()).collect());
+
+    let metadata = fs::read_to_string(test_dir.join("metadata.json"))
+        .ok()
+        .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok());
+
+    let mut paths: Vec<(String, PathBuf)> = fs::read_dir(&test_dir)
+        .expect("Couldn't read MARTY_SST_DIR")
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter_map(|path| opcode_name(&path).map(|name| (name, path)))
+        .filter(|(name, _)| {
+            opcode_filter
+                .as_ref()
+                .map_or(true, |filter| filter.iter().any(|op| name.starts_with(op.as_str())))
+        })
+        .collect();
+    paths.sort();
+
+    let mut cpu = new_cpu();
+    let cycles = Rc::new(RefCell::new(Vec::new()));
+    if check_cycles {
+        let observer_cycles = cycles.clone();
+        cpu.set_cycle_observer(Box::new(move |state: &CycleState| {
+            observer_cycles.borrow_mut().push(*state);
+        }));
+    }
+
+    let mut results: BTreeMap<String, OpcodeResult> = BTreeMap::new();
+    for (name, path) in &paths {
+        let tests = match read_tests(path) {
+            Ok(tests) => tests,
+            Err(e) => panic!("Failed to read test file {}: {}", path.display(), e),
+        };
+
+        let mask = flags_mask(&metadata, name);
+        let result = results.entry(name.clone()).or_default();
+        for (n, test) in tests.iter().enumerate() {
+            match run_test(&mut cpu, test, mask, check_cycles, &cycles) {
+                Ok(()) => result.passed += 1,
+                Err(e) => {
+                    result.failed += 1;
+                    result
+                        .first_failure
+                        .get_or_insert_with(|| format!("test {} ({}): {}", n, test.name, e));
+                }
+            }
+        }
+    }
+
+    let mut total_failed = 0;
+    for (name, result) in &results {
+        println!("{:<6} passed: {:>6} failed: {:>6}", name, result.passed, result.failed);
+        if let Some(failure) = &result.first_failure {
+            println!("       first failure: {}", failure);
+        }
+        total_failed += result.failed;
+    }
+
+    assert_eq!(total_failed, 0, "{} SingleStepTests failed", total_failed);
+}
@@ -30,22 +30,32 @@
 */
 
 use display_manager_wgpu::DisplayManager;
-use std::{cell::RefCell, ffi::OsString, rc::Rc};
+use std::{
+    cell::RefCell,
+    ffi::OsString,
+    rc::Rc,
+    time::{Duration, Instant, SystemTime},
+};
 
 use crate::{input::HotkeyManager, Counter, KeyboardData, MouseData};
 use anyhow::Error;
 use config_toml_bpaf::ConfigFileParams;
 use display_manager_wgpu::WgpuDisplayManager;
 use frontend_common::{
+    constants::NORMAL_NOTIFICATION_TIME,
     display_scaler::SCALER_MODES,
     floppy_manager::FloppyManager,
+    log_buffer::LogBuffer,
     resource_manager::ResourceManager,
     rom_manager::RomManager,
     timestep_manager::PerfSnapshot,
+    types::resize_debounce::ResizeDebouncer,
     vhd_manager::VhdManager,
 };
 use marty_core::{
     cpu_common::CpuOption,
+    device_traits::videocard::VideoOption,
+    devices::serial::TcpBridgeMode,
     machine::{ExecutionControl, Machine, MachineEvent, MachineState},
     vhd::VirtualHardDisk,
 };
@@ -80,6 +90,32 @@ pub struct Emulator {
     pub flags: EmuFlags,
     pub perf: PerfSnapshot,
     pub hkm: HotkeyManager,
+    pub config_watch: ConfigWatch,
+    pub time_scale: f64,
+    pub resize_debouncer: ResizeDebouncer,
+    pub log_buffer: LogBuffer,
+}
+
+/// Tracks the modification time of the configuration file so that changes can be hot-reloaded.
+pub struct ConfigWatch {
+    pub mtime: Option<SystemTime>,
+    pub last_poll: Instant,
+}
+
+// How often to check the configuration file for changes.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+impl ConfigWatch {
+    pub fn new(config: &ConfigFileParams) -> Self {
+        Self {
+            mtime: config.config_path.as_ref().and_then(|p| Self::get_mtime(p)),
+            last_poll: Instant::now(),
+        }
+    }
+
+    fn get_mtime(path: &std::path::Path) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
 }
 
 impl Emulator {
@@ -107,6 +143,11 @@ impl Emulator {
         self.machine
             .pit_adjust(self.config.machine.pit_phase.unwrap_or(0) & 0x03);
 
+        // Override the machine's CPU clock if requested.
+        if let Some(cpu_mhz) = self.config.machine.cpu_mhz {
+            self.machine.set_cpu_mhz(cpu_mhz)?;
+        }
+
         // Set options from config. We do this now so that we can set the same state for both GUI and machine
 
         // TODO: Add GUI for these two options?
@@ -116,6 +157,12 @@ impl Emulator {
         self.machine.set_cpu_option(CpuOption::EnableServiceInterrupt(
             self.config.machine.cpu.service_interrupt.unwrap_or(false),
         ));
+        self.machine.set_cpu_option(CpuOption::HaltIdle(
+            self.config.machine.cpu.halt_idle.unwrap_or(false),
+        ));
+
+        self.machine
+            .set_video_option(VideoOption::EnableBlink(self.config.emulator.video_blink));
 
         // TODO: Re-enable these
         //gui.set_option(GuiBoolean::EnableSnow, config.machine.cga_snow.unwrap_or(false));
@@ -261,6 +308,106 @@ impl Emulator {
         vhd_names
     }
 
+    /// Check whether the configuration file has changed on disk, and if so, re-read it and apply
+    /// any settings that can be changed while running.
+    pub fn poll_config_reload(&mut self) {
+        if self.config_watch.last_poll.elapsed() < CONFIG_POLL_INTERVAL {
+            return;
+        }
+        self.config_watch.last_poll = Instant::now();
+
+        let Some(path) = self.config.config_path.clone() else {
+            return;
+        };
+        let mtime = ConfigWatch::get_mtime(&path);
+        if mtime.is_none() || mtime == self.config_watch.mtime {
+            return;
+        }
+        self.config_watch.mtime = mtime;
+
+        log::info!("Configuration file {} changed, reloading...", path.display());
+        let toml_text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                log::error!("Failed to read configuration file: {}", e);
+                return;
+            }
+        };
+
+        match self.config.reload_from_str(&toml_text) {
+            Ok(needs_restart) => {
+                self.machine.apply_runtime_config(&self.config);
+                self.machine
+                    .set_video_option(VideoOption::EnableBlink(self.config.emulator.video_blink));
+                self.dm.reload_scaler_presets(&self.config.emulator.scaler_preset);
+                self.log_buffer.set_capacity(self.config.emulator.log_buffer_lines);
+
+                for setting in needs_restart.iter() {
+                    log::warn!("Configuration setting '{}' changed, but requires a restart.", setting);
+                }
+                self.gui
+                    .toasts()
+                    .info("Configuration reloaded.".to_string())
+                    .set_duration(Some(NORMAL_NOTIFICATION_TIME));
+            }
+            Err(e) => {
+                log::error!("Failed to reload configuration: {}", e);
+                self.gui
+                    .toasts()
+                    .error(format!("Failed to reload configuration: {}", e))
+                    .set_duration(Some(NORMAL_NOTIFICATION_TIME));
+            }
+        }
+    }
+
+    /// Load floppy images specified in the main configuration into floppy drives.
+    /// Unlike images selected from the GUI, these may be located anywhere on the host filesystem.
+    pub fn mount_floppies(&mut self) -> Result<(), Error> {
+        let write_protect = self.config.emulator.media.write_protect_default;
+
+        for entry in self.config.emulator.media.floppy.as_ref().unwrap_or(&Vec::new()).iter() {
+            let floppy_image = std::fs::read(&entry.filename)
+                .map_err(|e| anyhow::anyhow!("Failed to read floppy image {:?}: {}", entry.filename, e))?;
+            FloppyManager::validate_image(&floppy_image)
+                .map_err(|e| anyhow::anyhow!("Failed to load floppy image {:?}: {}", entry.filename, e))?;
+
+            if let Some(fdc) = self.machine.fdc() {
+                fdc.load_image_from(entry.drive, floppy_image, write_protect)
+                    .map_err(|e| anyhow::anyhow!("Failed to load floppy image {:?}: {}", entry.filename, e))?;
+                let image_name = entry.filename.file_name().map(|name| name.to_string_lossy().to_string());
+                fdc.set_image_name(entry.drive, image_name);
+                log::info!(
+                    "Floppy image {:?} successfully loaded into virtual drive: {}",
+                    entry.filename,
+                    entry.drive
+                );
+                self.gui.set_floppy_write_protected(entry.drive, write_protect);
+            }
+            else {
+                return Err(anyhow::anyhow!("No floppy controller present!"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Bridge any guest serial ports configured for TCP passthrough.
+    pub fn bridge_serial_tcp(&mut self) {
+        for entry in self.config.emulator.serial_tcp.as_ref().unwrap_or(&Vec::new()).iter() {
+            let mode = match entry.listen {
+                true => TcpBridgeMode::Listen,
+                false => TcpBridgeMode::Connect,
+            };
+            match self.machine.bridge_serial_tcp(entry.port, &entry.address, mode) {
+                Ok(_) => {
+                    log::info!("Serial port {} bridged to TCP address {}", entry.port, entry.address);
+                }
+                Err(e) => {
+                    log::error!("Failed to bridge serial port {} to {}: {}", entry.port, entry.address, e);
+                }
+            }
+        }
+    }
+
     /// Mount VHD images into hard drive devices.
     /// VHD images can be specified either in the machine configuration, or in the main configuration.
     /// Images specified in the main configuration will override images specified in a machine configuration.
@@ -300,6 +447,7 @@ impl Emulator {
                         if let Some(hdc) = self.machine.hdc() {
                             match hdc.set_vhd(config_drive_idx, vhd) {
                                 Ok(_) => {
+                                    hdc.set_vhd_name(config_drive_idx, Some(vhd_os_name.to_string_lossy().to_string()));
                                     log::info!(
                                         "VHD image {:?} successfully loaded into virtual drive: {}",
                                         vhd_os_name,
@@ -372,6 +520,10 @@ impl Emulator {
         // Set list of video cards
         self.gui.set_card_list(card_strs);
 
+        // Set list of ROM sets that can be selected at runtime.
+        self.gui
+            .set_rom_sets(self.romm.complete_rom_sets_by_feature(), self.romsets.clone());
+
         // Set floppy drives.
         self.gui.set_floppy_drives(self.machine.bus().floppy_drive_ct());
 
@@ -29,13 +29,13 @@
     Process received egui events.
 */
 
-use crate::Emulator;
+use crate::{event_loop::keyboard::paste_clipboard, Emulator};
 use display_manager_wgpu::DisplayManager;
 use marty_core::{
     breakpoints::BreakPointType,
     cpu_common::CpuOption,
     device_traits::videocard::ClockingMode,
-    machine::MachineState,
+    machine::{MachineState, ResetKind},
     vhd,
 };
 use marty_egui::{
@@ -84,6 +84,12 @@ pub fn handle_egui_event(emu: &mut Emulator, elwt: &EventLoopWindowTarget<()>, g
                 (GuiBoolean::TurboButton, state) => {
                     emu.machine.set_turbo_mode(state);
                 }
+                (GuiBoolean::LightPen, false) => {
+                    // Take the pen away from the screen.
+                    if let Some(mut video) = emu.machine.primary_videocard() {
+                        video.set_lightpen(None, false);
+                    }
+                }
                 _ => {}
             },
             GuiVariable::Enum(op) => match ctx {
@@ -136,6 +142,15 @@ pub fn handle_egui_event(emu: &mut Emulator, elwt: &EventLoopWindowTarget<()>, g
                             renderer.set_composite(*state);
                         }
                     }
+                    GuiEnum::DisplayOverscan(state) => {
+                        if let Some(vid) = emu.dm.set_display_overscan(*d_idx, *state).ok().flatten() {
+                            if let Some(video_card) = emu.machine.bus().video(&vid) {
+                                if let Err(e) = emu.dm.on_card_resized(&vid, video_card.get_display_extents()) {
+                                    log::error!("Failed to set overscan display for display target: {:?}", e);
+                                }
+                            }
+                        }
+                    }
                     GuiEnum::DisplayAspectCorrect(state) => {
                         if let Err(_e) = emu.dm.set_aspect_correction(*d_idx, *state) {
                             log::error!("Failed to set aspect correction state for display target!");
@@ -165,6 +180,7 @@ pub fn handle_egui_event(emu: &mut Emulator, elwt: &EventLoopWindowTarget<()>, g
                             match hdc.set_vhd(*drive_idx, vhd) {
                                 Ok(_) => {
                                     let vhd_name = emu.vhd_manager.get_vhd_name(*image_idx).unwrap();
+                                    hdc.set_vhd_name(*drive_idx, Some(vhd_name.to_string_lossy().to_string()));
                                     log::info!(
                                         "VHD image {:?} successfully loaded into virtual drive: {}",
                                         vhd_name,
@@ -268,6 +284,7 @@ pub fn handle_egui_event(emu: &mut Emulator, elwt: &EventLoopWindowTarget<()>, g
                         ) {
                             Ok(()) => {
                                 log::info!("Floppy image successfully loaded into virtual drive.");
+                                fdc.set_image_name(*drive_select, Some(name.to_string_lossy().to_string()));
                                 emu.gui
                                     .set_floppy_selection(*drive_select, Some(*item_idx), Some(name.clone().into()));
 
@@ -338,6 +355,7 @@ pub fn handle_egui_event(emu: &mut Emulator, elwt: &EventLoopWindowTarget<()>, g
                     match emu.floppy_manager.save_floppy_data(floppy_image, *image_idx, &emu.rm) {
                         Ok(path) => {
                             log::info!("Floppy image successfully saved: {:?}", path);
+                            fdc.mark_clean(*drive_select);
 
                             emu.gui
                                 .toasts()
@@ -568,6 +586,29 @@ pub fn handle_egui_event(emu: &mut Emulator, elwt: &EventLoopWindowTarget<()>, g
         GuiEvent::CtrlAltDel => {
             emu.machine.emit_ctrl_alt_del();
         }
+        GuiEvent::PasteText => {
+            paste_clipboard(emu);
+        }
+        GuiEvent::WarmReset => {
+            emu.machine.reset(ResetKind::Warm);
+        }
+        GuiEvent::SelectRomSet(alias) => match emu.romm.select_rom_set(alias, &mut emu.romsets, &emu.rm) {
+            Ok(mut manifest) => {
+                if let Err(e) = emu
+                    .romm
+                    .add_raw_roms(&mut manifest, &emu.config.machine.raw_roms, &emu.rm)
+                {
+                    log::error!("Error loading raw ROMs: {}", e);
+                }
+                log::info!("Switching to ROM sets: {:?}", emu.romsets);
+                emu.machine.reload_roms(manifest);
+                emu.gui
+                    .set_rom_sets(emu.romm.complete_rom_sets_by_feature(), emu.romsets.clone());
+            }
+            Err(e) => {
+                log::error!("Error selecting ROM set: {}", e);
+            }
+        },
         GuiEvent::CompositeAdjust(dt_idx, params) => {
             //log::warn!("got composite params: {:?}", params);
             emu.dm.with_renderer(*dt_idx, |renderer| {
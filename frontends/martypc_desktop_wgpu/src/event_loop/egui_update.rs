@@ -293,4 +293,12 @@ pub fn update_egui(emu: &mut Emulator, tm: &TimestepManager, elwt: &EventLoopWin
             );
         });
     }
+
+    // Update log viewer, if anything has been logged since the last update.
+    if emu.gui.is_window_open(GuiWindow::LogViewer) {
+        let generation = emu.log_buffer.generation();
+        if generation != emu.gui.log_viewer.generation() {
+            emu.gui.log_viewer.set_lines(emu.log_buffer.lines(), generation);
+        }
+    }
 }
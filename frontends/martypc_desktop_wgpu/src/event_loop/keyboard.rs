@@ -37,8 +37,12 @@ use winit::{
 };
 
 use display_manager_wgpu::DisplayManager;
-use frontend_common::{constants::LONG_NOTIFICATION_TIME, HotkeyEvent};
-use marty_core::machine::{ExecutionOperation, MachineState};
+use frontend_common::{
+    constants::{LONG_NOTIFICATION_TIME, NORMAL_NOTIFICATION_TIME, SHORT_NOTIFICATION_TIME},
+    HotkeyEvent,
+};
+use marty_core::machine::{ExecutionOperation, ExecutionState, MachineState, ResetKind};
+use marty_egui::GuiBoolean;
 
 use crate::{input::TranslateKey, Emulator};
 
@@ -81,6 +85,11 @@ pub fn handle_key_event(emu: &mut Emulator, window_id: WindowId, key_event: &Key
 
     match (physical_key, gui_has_focus) {
         (PhysicalKey::Code(keycode), gui_focus) => {
+            // Track AltGr ourselves, as winit's modifier state doesn't distinguish left and right Alt.
+            if *keycode == KeyCode::AltRight {
+                emu.kb_data.modifiers.altgr = matches!(state, ElementState::Pressed);
+            }
+
             // An egui widget doesn't have focus, so send an event to the emulated machine
 
             process_hotkeys(
@@ -207,10 +216,18 @@ pub fn process_hotkeys(emu: &mut Emulator, keycode: KeyCode, pressed: bool, wind
                 log::debug!("CtrlAltDel hotkey triggered. Sending Ctrl-Alt-Del to machine.");
                 emu.machine.emit_ctrl_alt_del();
             }
+            HotkeyEvent::PasteText => {
+                log::debug!("PasteText hotkey triggered. Typing clipboard contents into machine.");
+                paste_clipboard(emu);
+            }
             HotkeyEvent::Reboot => {
                 log::debug!("Reboot hotkey triggered. Restarting machine.");
                 emu.machine.change_state(MachineState::Rebooting);
             }
+            HotkeyEvent::WarmReboot => {
+                log::debug!("WarmReboot hotkey triggered. Performing warm reset.");
+                emu.machine.reset(ResetKind::Warm);
+            }
             HotkeyEvent::ToggleFullscreen => {
                 log::debug!("ToggleFullscreen hotkey triggered.");
                 // Get the window for this event.
@@ -230,6 +247,30 @@ pub fn process_hotkeys(emu: &mut Emulator, keycode: KeyCode, pressed: bool, wind
                     }
                 }
             }
+            HotkeyEvent::ToggleTurbo => {
+                let state = !emu.machine.get_turbo_mode();
+                log::debug!("ToggleTurbo hotkey triggered. Setting turbo to: {}", state);
+                emu.machine.set_turbo_mode(state);
+                emu.gui.set_option(GuiBoolean::TurboButton, state);
+            }
+            HotkeyEvent::ToggleSlowMotion => {
+                // Step down through slow motion speeds, then back to normal speed.
+                emu.time_scale = match emu.time_scale {
+                    s if s > 0.5 => 0.5,
+                    s if s > 0.25 => 0.25,
+                    s if s > 0.1 => 0.1,
+                    _ => 1.0,
+                };
+                log::debug!("ToggleSlowMotion hotkey triggered. Setting time scale to: {}", emu.time_scale);
+                emu.gui
+                    .toasts()
+                    .info(format!("Emulation speed: {}x", emu.time_scale))
+                    .set_duration(Some(SHORT_NOTIFICATION_TIME));
+            }
+            HotkeyEvent::TogglePerfOverlay => {
+                let state = !emu.gui.get_option(GuiBoolean::ShowPerfOverlay).unwrap_or(false);
+                emu.gui.set_option(GuiBoolean::ShowPerfOverlay, state);
+            }
             HotkeyEvent::Screenshot => {
                 log::debug!("Screenshot hotkey triggered. Capturing screenshot.");
 
@@ -244,15 +285,51 @@ pub fn process_hotkeys(emu: &mut Emulator, keycode: KeyCode, pressed: bool, wind
                         .set_duration(Some(LONG_NOTIFICATION_TIME));
                 }
             }
+            HotkeyEvent::TogglePause => {
+                let mut exec_control = emu.exec_control.borrow_mut();
+                match exec_control.get_state() {
+                    ExecutionState::Running | ExecutionState::StepFrame => {
+                        log::debug!("TogglePause hotkey triggered. Pausing.");
+                        exec_control.set_op(ExecutionOperation::Pause);
+                    }
+                    _ => {
+                        log::debug!("TogglePause hotkey triggered. Resuming.");
+                        exec_control.set_op(ExecutionOperation::Run);
+                    }
+                }
+            }
             HotkeyEvent::DebugStep => {
                 emu.exec_control.borrow_mut().set_op(ExecutionOperation::Step);
             }
             HotkeyEvent::DebugStepOver => {
                 emu.exec_control.borrow_mut().set_op(ExecutionOperation::StepOver);
             }
+            HotkeyEvent::DebugStepFrame => {
+                emu.exec_control.borrow_mut().set_op(ExecutionOperation::StepFrame);
+            }
             _ => {
                 log::debug!("Unhandled Hotkey triggered: {:?}", hotkey);
             }
         }
     }
 }
+
+/// Type the text contents of the host clipboard into the emulated machine. The text is queued as
+/// scancodes and delivered at the keyboard's injection rate, so long pastes take a while to
+/// arrive. Characters with no scancode mapping are dropped by type_string().
+pub fn paste_clipboard(emu: &mut Emulator) {
+    let text = match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+        Ok(text) => text,
+        Err(err) => {
+            log::warn!("Couldn't read text from clipboard: {}", err);
+            emu.gui
+                .toasts()
+                .warning("Clipboard does not contain text.".to_string())
+                .set_duration(Some(NORMAL_NOTIFICATION_TIME));
+            return;
+        }
+    };
+
+    // Windows line endings would otherwise press Enter twice.
+    emu.machine.type_string(&text.replace("\r\n", "\n"));
+}
@@ -43,7 +43,7 @@ use std::time::Instant;
 use winit::{
     event::{DeviceEvent, ElementState, Event, StartCause, WindowEvent},
     event_loop::EventLoopWindowTarget,
-    window::WindowLevel,
+    window::{WindowId, WindowLevel},
 };
 
 use crate::{
@@ -53,6 +53,8 @@ use crate::{
 };
 use display_manager_wgpu::DisplayManager;
 use frontend_common::timestep_manager::TimestepManager;
+use marty_core::devices::mouse::MOUSE_DEFAULT_ABSOLUTE_RANGE;
+use marty_egui::GuiBoolean;
 
 pub fn handle_event(emu: &mut Emulator, tm: &mut TimestepManager, event: Event<()>, elwt: &EventLoopWindowTarget<()>) {
     match event {
@@ -127,13 +129,12 @@ pub fn handle_event(emu: &mut Emulator, tm: &mut TimestepManager, event: Event<(
                     });
                 }
                 WindowEvent::Resized(size) => {
-                    if size.width > 0 && size.height > 0 {
-                        if let Err(e) = emu.dm.on_window_resized(window_id, size.width, size.height) {
-                            log::error!("Failed to resize window: {}", e);
-                        }
+                    // A zero size is reported when the window is minimized. The display manager
+                    // stops rendering the window until it is restored.
+                    if let Err(e) = emu.dm.on_window_resized(window_id, size.width, size.height) {
+                        log::error!("Failed to resize window: {}", e);
                     }
-                    else {
-                        log::debug!("Ignoring invalid size: {:?}", size);
+                    if size.width == 0 || size.height == 0 {
                         return;
                     }
                 }
@@ -153,6 +154,36 @@ pub fn handle_event(emu: &mut Emulator, tm: &mut TimestepManager, event: Event<(
                 WindowEvent::RedrawRequested => {
                     process_update(emu, tm, elwt);
                 }
+                WindowEvent::CursorMoved { position, .. } => {
+                    emu.mouse_data.cursor_pos = Some((position.x, position.y));
+                    update_lightpen(emu, window_id);
+                    update_absolute_mouse(emu, window_id);
+                    pass_to_egui = true;
+                }
+                WindowEvent::CursorLeft { .. } => {
+                    emu.mouse_data.cursor_pos = None;
+                    update_lightpen(emu, window_id);
+                    pass_to_egui = true;
+                }
+                WindowEvent::MouseInput {
+                    state,
+                    button: winit::event::MouseButton::Left,
+                    ..
+                } => {
+                    emu.mouse_data.cursor_l_pressed = state == ElementState::Pressed;
+                    update_lightpen(emu, window_id);
+                    update_absolute_mouse(emu, window_id);
+                    pass_to_egui = true;
+                }
+                WindowEvent::MouseInput {
+                    state,
+                    button: winit::event::MouseButton::Right,
+                    ..
+                } => {
+                    emu.mouse_data.cursor_r_pressed = state == ElementState::Pressed;
+                    update_absolute_mouse(emu, window_id);
+                    pass_to_egui = true;
+                }
                 WindowEvent::Focused(state) => match state {
                     true => {
                         log::debug!("Window {:?} gained focus", window_id);
@@ -203,3 +234,57 @@ pub fn handle_event(emu: &mut Emulator, tm: &mut TimestepManager, event: Event<(
         _ => (),
     }
 }
+
+/// Point the light pen of the card shown in the specified window at the current cursor position,
+/// if the mouse is being used as a light pen.
+fn update_lightpen(emu: &mut Emulator, wid: WindowId) {
+    if !emu.gui.get_option(GuiBoolean::LightPen).unwrap_or(false) {
+        return;
+    }
+
+    let switch = emu.mouse_data.cursor_l_pressed;
+    let target = emu.mouse_data.cursor_pos.and_then(|(x, y)| emu.dm.window_to_display(wid, x, y));
+
+    match target {
+        Some((vid, aperture, u, v)) => {
+            if let Some(mut video) = emu.machine.bus_mut().video_mut(&vid) {
+                let pos = video
+                    .get_display_extents()
+                    .apertures
+                    .get(aperture as usize)
+                    .map(|ap| (ap.x + (u * ap.w as f32) as u32, ap.y + (v * ap.h as f32) as u32));
+                video.set_lightpen(pos, switch);
+            }
+        }
+        None => {
+            // Cursor is outside the displayed image, so the pen can't see the beam.
+            if let Some(mut video) = emu.machine.primary_videocard() {
+                video.set_lightpen(None, switch);
+            }
+        }
+    }
+}
+
+/// Walk the guest mouse cursor toward the host cursor position, if absolute mouse mode is enabled
+/// and the mouse is not captured. The light pen takes priority when enabled.
+fn update_absolute_mouse(emu: &mut Emulator, wid: WindowId) {
+    if !emu.config.emulator.input.mouse_absolute
+        || emu.mouse_data.is_captured
+        || emu.gui.get_option(GuiBoolean::LightPen).unwrap_or(false)
+    {
+        return;
+    }
+
+    let target = emu.mouse_data.cursor_pos.and_then(|(x, y)| emu.dm.window_to_display(wid, x, y));
+    if let (Some((_, _, u, v)), Some(mouse)) = (target, emu.machine.mouse_mut()) {
+        if !mouse.is_absolute_mode() {
+            mouse.set_absolute_mode(Some(MOUSE_DEFAULT_ABSOLUTE_RANGE));
+        }
+        mouse.update_absolute(
+            emu.mouse_data.cursor_l_pressed,
+            emu.mouse_data.cursor_r_pressed,
+            u as f64,
+            v as f64,
+        );
+    }
+}
@@ -43,6 +43,7 @@ pub fn render_frame(emu: &mut Emulator) {
             // Check if the emulator is paused - if paused, optionally select the back buffer
             // so we can watch the raster beam draw
             let mut beam_pos = None;
+            let mut split_pos = None;
             match emu.exec_control.borrow_mut().get_state() {
                 ExecutionState::Paused | ExecutionState::BreakpointHit | ExecutionState::Halted => {
                     if emu.gui.get_option(GuiBoolean::ShowBackBuffer).unwrap_or(false) {
@@ -50,6 +51,9 @@ pub fn render_frame(emu: &mut Emulator) {
                         if emu.gui.get_option(GuiBoolean::ShowRasterPosition).unwrap_or(false) {
                             beam_pos = videocard.get_beam_pos();
                         }
+                        if emu.gui.get_option(GuiBoolean::ShowSplitFrame).unwrap_or(false) {
+                            split_pos = videocard.get_beam_pos();
+                        }
                     }
                     else {
                         renderer.select_buffer(BufferSelect::Front);
@@ -70,12 +74,24 @@ pub fn render_frame(emu: &mut Emulator) {
             }
 
             //log::debug!("Drawing renderer for vid: {:?}", vid);
-            renderer.draw(
-                videocard.get_buf(renderer.get_selected_buffer()),
-                backend_buf,
-                extents,
-                beam_pos,
-            )
+            if let Some(split_pos) = split_pos {
+                renderer.draw_split(
+                    videocard.get_buf(BufferSelect::Back),
+                    videocard.get_buf(BufferSelect::Front),
+                    backend_buf,
+                    extents,
+                    split_pos,
+                    beam_pos,
+                )
+            }
+            else {
+                renderer.draw(
+                    videocard.get_buf(renderer.get_selected_buffer()),
+                    backend_buf,
+                    extents,
+                    beam_pos,
+                )
+            }
         }
     });
 
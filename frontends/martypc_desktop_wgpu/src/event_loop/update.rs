@@ -46,10 +46,21 @@ use crate::{
 };
 
 pub fn process_update(emu: &mut Emulator, tm: &mut TimestepManager, elwt: &EventLoopWindowTarget<()>) {
+    // Apply any change to the time scale. Audio can't keep up in slow motion, so mute it.
+    if emu.time_scale != tm.time_scale() {
+        tm.set_time_scale(emu.time_scale);
+        if let Some(sound_player) = emu.machine.sound_player_mut() {
+            sound_player.set_master_muted(tm.time_scale() < 1.0);
+        }
+    }
+
     tm.wm_update(
         emu,
         |emuc| {
             // Per second freq
+            let stats = emuc.machine.sample_stats(1.0, emuc.perf.wm_fps);
+            emuc.gui.set_machine_stats(stats);
+
             MachinePerfStats {
                 cpu_mhz: emuc.machine.get_cpu_mhz(),
                 cpu_cycles: emuc.machine.cpu_cycles(),
@@ -60,6 +71,9 @@ pub fn process_update(emu: &mut Emulator, tm: &mut TimestepManager, elwt: &Event
         },
         |emuc, cycles| {
             // Per emu update freq
+            if emuc.config.emulator.pause_when_minimized && emuc.dm.is_main_window_minimized() {
+                return;
+            }
             emuc.machine.run(cycles, &mut emuc.exec_control.borrow_mut());
         },
         |emuc, tmc, &perf| {
@@ -69,31 +83,8 @@ pub fn process_update(emu: &mut Emulator, tm: &mut TimestepManager, elwt: &Event
             if let Some(mouse) = emuc.machine.mouse_mut() {
                 // Send any pending mouse update to machine if mouse is captured
                 if emuc.mouse_data.is_captured && emuc.mouse_data.have_update {
-                    mouse.update(
-                        emuc.mouse_data.l_button_was_pressed,
-                        emuc.mouse_data.r_button_was_pressed,
-                        emuc.mouse_data.frame_delta_x,
-                        emuc.mouse_data.frame_delta_y,
-                    );
-
-                    // Handle release event
-                    let l_release_state = if emuc.mouse_data.l_button_was_released {
-                        false
-                    }
-                    else {
-                        emuc.mouse_data.l_button_was_pressed
-                    };
-
-                    let r_release_state = if emuc.mouse_data.r_button_was_released {
-                        false
-                    }
-                    else {
-                        emuc.mouse_data.r_button_was_pressed
-                    };
-
-                    if emuc.mouse_data.l_button_was_released || emuc.mouse_data.r_button_was_released {
-                        // Send release event
-                        mouse.update(l_release_state, r_release_state, 0.0, 0.0);
+                    for (l_button, r_button, delta_x, delta_y) in emuc.mouse_data.frame_updates() {
+                        mouse.update(l_button, r_button, delta_x, delta_y);
                     }
 
                     // Reset mouse for next frame
@@ -101,8 +92,29 @@ pub fn process_update(emu: &mut Emulator, tm: &mut TimestepManager, elwt: &Event
                 }
             }
 
+            // Update floppy drive indicators
+            if let Some(fdc) = emuc.machine.fdc() {
+                let status = fdc.drive_status_all();
+                emuc.gui.set_floppy_status(status);
+            }
+
+            // Update POST card display
+            if let Some(post_card) = emuc.machine.post_card() {
+                emuc.gui.set_post_card(post_card.code(), post_card.history());
+            }
+
+            // Drain printer output. This also flushes the printer capture file.
+            if let Some(output) = emuc.machine.take_printer_output() {
+                if !output.is_empty() {
+                    log::debug!("Printer output: {}", String::from_utf8_lossy(&output));
+                }
+            }
+
+            // Check for changes to the configuration file
+            emuc.poll_config_reload();
+
             // Drain machine events
-            while let Some(event) = emuc.machine.get_event() {
+            for event in emuc.machine.drain_events() {
                 match event {
                     MachineEvent::CheckpointHit(checkpoint, pri) => {
                         log::info!(
@@ -128,7 +140,17 @@ pub fn process_update(emu: &mut Emulator, tm: &mut TimestepManager, elwt: &Event
                             }
                         }
                     }
+                    MachineEvent::WatchpointHit(hit, instr_address) => {
+                        emuc.gui
+                            .toasts()
+                            .info(format!(
+                                "WATCHPOINT: {:?} [{:05X}] {:02X}->{:02X} at {}",
+                                hit.access, hit.address, hit.old_value, hit.new_value, instr_address
+                            ))
+                            .set_duration(Some(NORMAL_NOTIFICATION_TIME));
+                    }
                     MachineEvent::Reset => {
+                        emuc.resize_debouncer.reset();
                         // Send notification
                         emuc.gui
                             .toasts()
@@ -169,6 +191,28 @@ pub fn process_update(emu: &mut Emulator, tm: &mut TimestepManager, elwt: &Event
                             .error("CPU permanently halted!".to_string())
                             .set_duration(Some(LONG_NOTIFICATION_TIME));
                     }
+                    MachineEvent::WatchdogTripped(address) => {
+                        emuc.gui
+                            .toasts()
+                            .warning(format!("Watchdog: CPU appears stuck in a loop at {}", address))
+                            .set_duration(Some(LONG_NOTIFICATION_TIME));
+                    }
+                    MachineEvent::BreakpointHit(address) => {
+                        log::debug!("Breakpoint hit at {}", address);
+                    }
+                    MachineEvent::FloppyInserted(drive) => {
+                        log::debug!("Floppy inserted in drive {}", drive);
+                    }
+                    MachineEvent::FloppyEjected(drive) => {
+                        log::debug!("Floppy ejected from drive {}", drive);
+                    }
+                    MachineEvent::VideoModeChanged(mode) => {
+                        log::debug!("Video mode changed to {:?}", mode);
+                    }
+                    MachineEvent::SpeakerFrequencyChanged(_) => {}
+                    MachineEvent::GuestHalted(reason, address) => {
+                        log::info!("Guest halted at {}: {}", address, reason);
+                    }
                 }
             }
 
@@ -202,9 +246,14 @@ pub fn process_update(emu: &mut Emulator, tm: &mut TimestepManager, elwt: &Event
 
             let render_start = Instant::now();
 
-            // Check if any videocard has resized and handle it
+            // Check if any videocard has resized and handle it. Resolution changes are held back
+            // until they have settled, so that rapid mode switches don't resize the display repeatedly.
+            let debouncer = &mut emuc.resize_debouncer;
             emuc.machine.for_each_videocard(|vci| {
                 let extents = vci.card.get_display_extents();
+                if !debouncer.update(&vci.id, extents, vci.card.get_frame_count()) {
+                    return;
+                }
                 // Resize the card.
                 if let Err(_) = emuc.dm.on_card_resized(&vci.id, &extents) {
                     log::error!("Error resizing videocard");
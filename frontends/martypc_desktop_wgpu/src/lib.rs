@@ -61,24 +61,28 @@ use config_toml_bpaf::TestMode;
 
 use marty_core::{
     devices::keyboard::KeyboardModifiers,
-    machine::{ExecutionControl, ExecutionState, MachineBuilder},
-    sound::SoundPlayer,
+    machine::{ExecutionControl, ExecutionState, MachineBuilder, MachineRomEntry, MachineRomManifest, MAX_MEMORY_ADDRESS},
+    sound::{AudioBackend, SoundPlayer, SoundSource},
 };
 
 use display_manager_wgpu::{DisplayBackend, DisplayManager, DisplayManagerGuiOptions, WgpuDisplayManagerBuilder};
 use frontend_common::{
     floppy_manager::FloppyManager,
+    input::KeyboardLayout,
+    log_buffer::{LogBuffer, MultiLogger, DEFAULT_LOG_BUFFER_LINES},
     resource_manager::ResourceManager,
     timestep_manager::TimestepManager,
+    types::resize_debounce::ResizeDebouncer,
     vhd_manager::VhdManager,
 };
+use log::LevelFilter;
 use marty_egui::state::GuiState;
 
 #[cfg(feature = "cpu_validator")]
 use run_tests::run_runtests;
 
 use crate::{
-    emulator::{EmuFlags, Emulator},
+    emulator::{ConfigWatch, EmuFlags, Emulator},
     event_loop::handle_event,
     input::HotkeyManager,
 };
@@ -174,6 +178,9 @@ pub struct MouseData {
     pub r_button_is_pressed: bool,
     pub frame_delta_x: f64,
     pub frame_delta_y: f64,
+    pub cursor_pos: Option<(f64, f64)>, // Cursor position within the window, in physical pixels.
+    pub cursor_l_pressed: bool,         // Left button state as reported by the window, for the light pen.
+    pub cursor_r_pressed: bool,         // Right button state as reported by the window.
 }
 
 impl MouseData {
@@ -192,8 +199,30 @@ impl MouseData {
             r_button_is_pressed: false,
             frame_delta_x: 0.0,
             frame_delta_y: 0.0,
+            cursor_pos: None,
+            cursor_l_pressed: false,
+            cursor_r_pressed: false,
         }
     }
+
+    /// Return the mouse updates to send to the machine for this frame, as (left, right, dx, dy).
+    /// Buttons pressed during the frame are reported down along with the motion. If a button was
+    /// released, a second update then reports the buttons' current state, so that a button still
+    /// held in a chord stays down, and a button released and pressed again within the frame ends
+    /// up down.
+    pub fn frame_updates(&self) -> Vec<(bool, bool, f64, f64)> {
+        let mut updates = vec![(
+            self.l_button_was_pressed,
+            self.r_button_was_pressed,
+            self.frame_delta_x,
+            self.frame_delta_y,
+        )];
+        if self.l_button_was_released || self.r_button_was_released {
+            updates.push((self.l_button_is_pressed, self.r_button_is_pressed, 0.0, 0.0));
+        }
+        updates
+    }
+
     pub fn reset(&mut self) {
         if !self.l_button_is_pressed {
             self.l_button_was_pressed = false;
@@ -231,7 +260,16 @@ fn main() {
 
 #[cfg(not(target_arch = "wasm32"))]
 pub fn run() {
-    env_logger::init();
+    // Keep recent log lines for the log viewer window, alongside the usual env_logger output.
+    let log_buffer = LogBuffer::new(DEFAULT_LOG_BUFFER_LINES);
+    let env_logger = env_logger::Builder::from_default_env().build();
+    let env_level = env_logger.filter();
+    if let Err(e) = MultiLogger::new(log_buffer.clone(), LevelFilter::Info)
+        .with_logger(Box::new(env_logger))
+        .init(env_level)
+    {
+        eprintln!("Couldn't initialize logger: {}", e);
+    }
 
     // TODO: Move most of everything from here into an EmulatorBuilder
 
@@ -254,15 +292,20 @@ pub fn run() {
                 std::process::exit(1);
             }
             None => {
-                eprintln!(
-                    "Failed to parse configuration file. There may be a typo or otherwise invalid toml:\n{}",
-                    e
-                );
+                match e.downcast_ref::<config_toml_bpaf::ConfigParseError>() {
+                    Some(e) => eprintln!("Error in configuration file: {}", e),
+                    None => eprintln!(
+                        "Failed to parse configuration file. There may be a typo or otherwise invalid toml:\n{}",
+                        e
+                    ),
+                }
                 std::process::exit(1);
             }
         },
     };
 
+    log_buffer.set_capacity(config.emulator.log_buffer_lines);
+
     // Now that we have our configuration, we can instantiate a ResourceManager.
     let mut resource_manager = ResourceManager::from_config(config.emulator.basedir.clone(), &config.emulator.paths)
         .unwrap_or_else(|e| {
@@ -361,7 +404,7 @@ pub fn run() {
     }
 
     // Get the ROM requirements for the requested machine type
-    let machine_config_file = {
+    let mut machine_config_file = {
         for overlay in init_config_overlays.iter() {
             log::debug!("Have machine config overlay from global config: {}", overlay);
         }
@@ -375,11 +418,22 @@ pub fn run() {
             }
         }
     };
+    // Apply the --model override before resolving ROM requirements, so the ROM sets match the
+    // machine that will actually be built.
+    if let Some(model) = config.machine.model {
+        log::debug!("Overriding machine type with: {:?}", model);
+        machine_config_file.set_machine_type(model);
+    }
     let (required_features, optional_features) = machine_config_file.get_rom_requirements().unwrap_or_else(|e| {
         eprintln!("Error getting ROM requirements for machine: {}", e);
         std::process::exit(1);
     });
 
+    // Add the configured ROM directory, if any, to the scan
+    if let Some(rom_dir) = &config.machine.rom_dir {
+        rom_manager.set_rom_dir(rom_dir.clone(), machine_config_file.machine_type());
+    }
+
     // Scan the rom resource director(ies)
     if let Err(err) = rom_manager.scan(&resource_manager) {
         eprintln!("Error scanning ROM resource directories: {}", err);
@@ -418,13 +472,19 @@ pub fn run() {
     // Determine if the machine configuration specifies a particular ROM set
     let specified_rom_set = machine_config_file.get_specified_rom_set();
 
-    // Resolve the ROM requirements for the requested ROM features
-    let rom_sets_resolved = rom_manager
-        .resolve_requirements(required_features, optional_features, specified_rom_set)
-        .unwrap_or_else(|err| {
-            eprintln!("Error resolving ROM sets for machine: {}", err);
-            std::process::exit(1);
-        });
+    // Resolve the ROM requirements for the requested ROM features. A ROM override replaces the
+    // machine's ROM sets entirely, so we don't require them to be present.
+    let rom_sets_resolved = if config.machine.rom_override.is_some() {
+        Vec::new()
+    }
+    else {
+        rom_manager
+            .resolve_requirements(required_features, optional_features, specified_rom_set)
+            .unwrap_or_else(|err| {
+                eprintln!("Error resolving ROM sets for machine: {}", err);
+                std::process::exit(1);
+            })
+    };
 
     println!(
         "Selected machine config {} has resolved the following ROM sets:",
@@ -435,12 +495,45 @@ pub fn run() {
     }
 
     // Create the ROM manifest
-    let rom_manifest = rom_manager
-        .create_manifest(rom_sets_resolved.clone(), &resource_manager)
-        .unwrap_or_else(|err| {
-            eprintln!("Error loading ROM set: {}", err);
+    let mut rom_manifest = if let Some(rom_override) = &config.machine.rom_override {
+        // Load the override ROM image so that it ends at the top of the address space, where the
+        // reset vector lives.
+        let rom_data = std::fs::read(rom_override).unwrap_or_else(|err| {
+            eprintln!("Error reading ROM override file {:?}: {}", rom_override, err);
+            std::process::exit(1);
+        });
+        if rom_data.is_empty() || rom_data.len() > MAX_MEMORY_ADDRESS + 1 {
+            eprintln!("ROM override file {:?} has invalid size: {}", rom_override, rom_data.len());
             std::process::exit(1);
+        }
+        println!("Using ROM override: {:?}", rom_override);
+
+        let mut manifest = MachineRomManifest::new();
+        manifest.roms.push(MachineRomEntry {
+            md5:  String::new(),
+            addr: (MAX_MEMORY_ADDRESS + 1 - rom_data.len()) as u32,
+            data: rom_data,
         });
+        manifest.rom_paths.push(rom_override.clone());
+        manifest
+    }
+    else {
+        rom_manager
+            .create_manifest(rom_sets_resolved.clone(), &resource_manager)
+            .unwrap_or_else(|err| {
+                eprintln!("Error loading ROM set: {}", err);
+                std::process::exit(1);
+            })
+    };
+
+    // Add any raw ROMs specified in the configuration, such as option ROMs not in the ROM database.
+    if let Err(err) = rom_manager.add_raw_roms(&mut rom_manifest, &config.machine.raw_roms, &resource_manager) {
+        eprintln!("Error loading raw ROMs: {}", err);
+        std::process::exit(1);
+    }
+    for raw_rom in &config.machine.raw_roms {
+        println!("Using raw ROM {:?} at address {:05X}", raw_rom.path, raw_rom.addr);
+    }
 
     log::debug!("Created manifest!");
     for (i, rom) in rom_manifest.roms.iter().enumerate() {
@@ -467,6 +560,23 @@ pub fn run() {
         std::process::exit(1);
     }
 
+    // Check the configuration and the media it names for problems, and report them all before we
+    // go any further.
+    let mut config_errors = config.validate().err().unwrap_or_default();
+    config_errors.extend(
+        config
+            .validate_media(|name| vhd_manager.find_first_name(name.into()).is_some())
+            .err()
+            .unwrap_or_default(),
+    );
+    if !config_errors.is_empty() {
+        eprintln!("The configuration file has {} problem(s):", config_errors.len());
+        for error in config_errors.iter() {
+            eprintln!("  {}", error);
+        }
+        std::process::exit(1);
+    }
+
     // Enumerate host serial ports
     let serial_ports = serialport::available_ports().unwrap_or_else(|e| {
         log::warn!("Didn't find any serial ports: {:?}", e);
@@ -547,14 +657,19 @@ pub fn run() {
     // Init sound
     let sound_player_opt = {
         if config.emulator.audio.enabled {
-            // The cpal sound library uses generics to initialize depending on the SampleFormat type.
-            // On Windows at least a sample type of f32 is typical, but just in case...
-            let (audio_device, sample_fmt) = SoundPlayer::get_device();
-            let sp = match sample_fmt {
-                cpal::SampleFormat::F32 => SoundPlayer::new::<f32>(audio_device),
-                cpal::SampleFormat::I16 => SoundPlayer::new::<i16>(audio_device),
-                cpal::SampleFormat::U16 => SoundPlayer::new::<u16>(audio_device),
+            let mut sp = match config.emulator.audio.backend {
+                AudioBackend::Cpal => SoundPlayer::open_default().unwrap_or_else(|e| {
+                    log::warn!("Failed to initialize audio output, falling back to no audio: {}", e);
+                    SoundPlayer::null()
+                }),
+                AudioBackend::None => {
+                    log::debug!("Audio backend is 'none'. Sound output will be discarded.");
+                    SoundPlayer::null()
+                }
             };
+            sp.set_master_volume(config.emulator.audio.volume.unwrap_or(1.0));
+            sp.set_source_muted(SoundSource::PcSpeaker, config.emulator.audio.mute_speaker);
+            sp.set_source_muted(SoundSource::AdLib, config.emulator.audio.mute_adlib);
             Some(sp)
         }
         else {
@@ -562,7 +677,21 @@ pub fn run() {
         }
     };
 
-    let machine_config = machine_config_file.to_machine_config();
+    let mut machine_config = machine_config_file.to_machine_config();
+
+    // Printer capture files are relative to the 'print' resource path.
+    if let Some(capture_file) = machine_config
+        .parallel
+        .as_mut()
+        .and_then(|parallel| parallel.capture_file.as_mut())
+    {
+        match resource_manager.get_resource_path("print") {
+            Some(print_path) => *capture_file = print_path.join(&capture_file),
+            None => log::warn!(
+                "No 'print' resource path defined. Printer capture file will be relative to the working directory."
+            ),
+        }
+    }
 
     let trace_file_base = resource_manager.get_resource_path("trace").unwrap_or_else(|| {
         eprintln!("Failed to retrieve 'trace' resource path.");
@@ -588,9 +717,17 @@ pub fn run() {
         }
     }
 
-    if let Some(mut kb_layout_resource_path) = resource_manager.get_resource_path("keyboard_layout") {
-        kb_layout_resource_path.push(format!("keyboard_{}.toml", kb_string));
-        kb_layout_file_path = Some(kb_layout_resource_path);
+    if let Some(kb_layout_resource_path) = resource_manager.get_resource_path("keyboard_layout") {
+        let kb_layout = KeyboardLayout::from_config(&kb_string);
+        let mut kb_layout_path = kb_layout_resource_path.join(kb_layout.file_name());
+        if !kb_layout_path.exists() {
+            log::warn!(
+                "Keyboard layout file {} not found. Falling back to US layout.",
+                kb_layout_path.display()
+            );
+            kb_layout_path = kb_layout_resource_path.join(KeyboardLayout::Us.file_name());
+        }
+        kb_layout_file_path = Some(kb_layout_path);
     }
 
     let machine_builder = MachineBuilder::new()
@@ -659,6 +796,8 @@ pub fn run() {
     let machine_events = Vec::new();
 
     // Put everything we want to handle in event loop into an Emulator struct
+    let config_watch = ConfigWatch::new(&config);
+
     let mut emu = Emulator {
         rm: resource_manager,
         dm: display_manager,
@@ -680,6 +819,10 @@ pub fn run() {
             debug_keyboard: false,
         },
         hkm: hotkey_manager,
+        config_watch,
+        time_scale: 1.0,
+        resize_debouncer: ResizeDebouncer::new(),
+        log_buffer,
     };
 
     // Resize video cards
@@ -726,6 +869,13 @@ pub fn run() {
         std::process::exit(1);
     }
 
+    if let Err(e) = emu.mount_floppies() {
+        log::error!("Failed to mount floppy images: {}", e);
+        std::process::exit(1);
+    }
+
+    emu.bridge_serial_tcp();
+
     // Start emulator
     emu.start();
 
@@ -741,3 +891,76 @@ pub fn run() {
 
     std::process::exit(0);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn left(mouse: &mut MouseData, pressed: bool) {
+        if pressed {
+            mouse.l_button_was_pressed = true;
+        }
+        else {
+            mouse.l_button_was_released = true;
+        }
+        mouse.l_button_is_pressed = pressed;
+    }
+
+    fn right(mouse: &mut MouseData, pressed: bool) {
+        if pressed {
+            mouse.r_button_was_pressed = true;
+        }
+        else {
+            mouse.r_button_was_released = true;
+        }
+        mouse.r_button_is_pressed = pressed;
+    }
+
+    #[test]
+    fn released_button_leaves_chord_held() {
+        let mut mouse = MouseData::new(false);
+        left(&mut mouse, true);
+        right(&mut mouse, true);
+        assert_eq!(mouse.frame_updates(), vec![(true, true, 0.0, 0.0)]);
+        mouse.reset();
+
+        // Releasing the left button keeps the right button down.
+        left(&mut mouse, false);
+        assert_eq!(
+            mouse.frame_updates(),
+            vec![(true, true, 0.0, 0.0), (false, true, 0.0, 0.0)]
+        );
+        mouse.reset();
+
+        right(&mut mouse, false);
+        assert_eq!(
+            mouse.frame_updates(),
+            vec![(false, true, 0.0, 0.0), (false, false, 0.0, 0.0)]
+        );
+        mouse.reset();
+        assert_eq!(mouse.frame_updates(), vec![(false, false, 0.0, 0.0)]);
+    }
+
+    #[test]
+    fn click_within_a_frame_is_reported() {
+        let mut mouse = MouseData::new(false);
+        mouse.frame_delta_x = 2.0;
+        left(&mut mouse, true);
+        left(&mut mouse, false);
+        assert_eq!(
+            mouse.frame_updates(),
+            vec![(true, false, 2.0, 0.0), (false, false, 0.0, 0.0)]
+        );
+        mouse.reset();
+
+        // A button released and pressed again within the frame ends up down.
+        left(&mut mouse, true);
+        mouse.reset();
+        left(&mut mouse, false);
+        left(&mut mouse, true);
+        assert_eq!(
+            mouse.frame_updates(),
+            vec![(true, false, 0.0, 0.0), (true, false, 0.0, 0.0)]
+        );
+    }
+}
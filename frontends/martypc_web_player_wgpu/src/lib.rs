@@ -7,12 +7,23 @@ use js_sys::{self, Reflect};
 use pixels::wgpu::TextureView;
 use wasm_bindgen::{closure::Closure, prelude::*, JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{console, window, Blob, FileReader, Headers, ProgressEvent, Request, RequestInit, Response};
+use web_sys::{
+    console,
+    window,
+    Blob,
+    FileReader,
+    Headers,
+    ProgressEvent,
+    ReadableStreamDefaultReader,
+    Request,
+    RequestInit,
+    Response,
+};
 
 use error_iter::ErrorIter as _;
 use log::error;
 use pixels::{Pixels, SurfaceTexture};
-use std::rc::Rc;
+use std::{cell::RefCell, path::PathBuf, rc::Rc, sync::OnceLock};
 use winit::{
     dpi::{LogicalSize, PhysicalSize},
     event::{Event, VirtualKeyCode, WindowEvent},
@@ -28,18 +39,26 @@ use marty_core::{
     floppy_manager::{FloppyError, FloppyManager},
     input::{self, MouseButton},
     lib::{self, *},
-    machine::{self, ExecutionControl, ExecutionState, Machine, MachineState},
+    devices::keyboard::KeyboardModifiers,
+    machine::{self, Machine, MachineRomManifest, MachineState},
     machine_config::MACHINE_DESCS,
-    rom_manager::{RawRomDescriptor, RomManager},
+    rom_manager::ROM_BASIC_ADDRESS,
     sound::SoundPlayer,
     syntax_token::SyntaxToken,
     util,
     vhd::{self, VirtualHardDisk},
     vhd_manager::{VHDManager, VHDManagerError},
-    videocard::RenderMode,
 };
 
-use marty_render::{CompositeParams, ResampleContext, VideoData, VideoRenderer};
+use config_toml_bpaf::get_config_from_str;
+use frontend_common::{
+    embed::EmbeddedMachine,
+    input::KeyboardLayout,
+    log_buffer::{LogBuffer, MultiLogger, DEFAULT_LOG_BUFFER_LINES},
+    resource_manager::ResourceManager,
+    rom_manager::RomManager,
+};
+use videocard_renderer::{AspectCorrectionMode, AspectRatio, ScalingMode, VideoRenderer};
 //use pixels_stretch_renderer::{StretchingRenderer, SurfaceSize};
 
 const DEFAULT_RENDER_WIDTH: u32 = 768;
@@ -48,12 +67,82 @@ const DEFAULT_RENDER_HEIGHT: u32 = 524;
 const DEFAULT_ASPECT_WIDTH: u32 = 768;
 const DEFAULT_ASPECT_HEIGHT: u32 = 576;
 
-const MIN_RENDER_WIDTH: u32 = 160;
-const MIN_RENDER_HEIGHT: u32 = 200;
-const RENDER_ASPECT: f32 = 0.75;
+// Recent log lines, shown in the page's log overlay.
+static LOG_BUFFER: OnceLock<LogBuffer> = OnceLock::new();
+
+/// Forwards log records to the browser console.
+#[cfg(target_arch = "wasm32")]
+struct ConsoleLogger(log::Level);
+
+#[cfg(target_arch = "wasm32")]
+impl log::Log for ConsoleLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.0
+    }
+
+    fn log(&self, record: &log::Record) {
+        console_log::log(record);
+    }
+
+    fn flush(&self) {}
+}
+
+/// The shape the display is corrected to before it is presented.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum DisplayAspect {
+    /// Present the display as rendered, with square pixels.
+    Square,
+    /// Scale the display to the specified ratio of height to width.
+    Ratio(f32),
+}
+
+impl Default for DisplayAspect {
+    /// The 4:3 ratio of a PC monitor.
+    fn default() -> Self {
+        DisplayAspect::Ratio(0.75)
+    }
+}
+
+impl std::str::FromStr for DisplayAspect {
+    type Err = String;
+
+    /// Parse an aspect ratio of the form "4:3", a height to width ratio such as "0.75", or
+    /// "square" for square pixels.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("square") {
+            return Ok(DisplayAspect::Square);
+        }
+        let ratio = match s.split_once(':') {
+            Some((w, h)) => match (w.trim().parse::<f32>(), h.trim().parse::<f32>()) {
+                (Ok(w), Ok(h)) if w > 0.0 => h / w,
+                _ => return Err(format!("Invalid aspect ratio: {}", s)),
+            },
+            None => s.parse::<f32>().map_err(|_| format!("Invalid aspect ratio: {}", s))?,
+        };
+        if !ratio.is_finite() || ratio <= 0.0 {
+            return Err(format!("Invalid aspect ratio: {}", s));
+        }
+        Ok(DisplayAspect::Ratio(ratio))
+    }
+}
+
+impl DisplayAspect {
+    /// Return the aspect ratio to give the renderer, or None for square pixels.
+    fn aspect_ratio(self) -> Option<AspectRatio> {
+        match self {
+            DisplayAspect::Square => None,
+            DisplayAspect::Ratio(ratio) => Some(AspectRatio {
+                h: 1000,
+                v: (1000.0 * ratio).round() as u32,
+            }),
+        }
+    }
+}
 
 pub const FPS_TARGET: f64 = 60.0;
 const MICROS_PER_FRAME: f64 = 1.0 / FPS_TARGET * 1000000.0;
+const MAX_CATCHUP_FRAMES: u128 = 4;
 
 #[wasm_bindgen]
 extern "C" {
@@ -82,6 +171,7 @@ struct Counter {
     last_sndbuf: Instant,
     last_second: Instant,
     last_cpu_cycles: u64,
+    last_instr_count: u64,
     current_cpu_cps: u64,
     last_system_ticks: u64,
     last_pit_ticks: u64,
@@ -90,9 +180,6 @@ struct Counter {
     emulation_time: Duration,
     render_time: Duration,
     accumulated_us: u128,
-    cpu_mhz: f64,
-    cycles_per_frame: u32,
-    cycle_target: u32,
 }
 
 impl Counter {
@@ -117,6 +204,7 @@ impl Counter {
             last_sndbuf: Instant::now(),
             last_frame: Instant::now(),
             last_cpu_cycles: 0,
+            last_instr_count: 0,
             current_cpu_cps: 0,
             last_system_ticks: 0,
             last_pit_ticks: 0,
@@ -125,9 +213,6 @@ impl Counter {
             emulation_time: Duration::ZERO,
             render_time: Duration::ZERO,
             accumulated_us: 0,
-            cpu_mhz: 0.0,
-            cycles_per_frame: 0,
-            cycle_target: 0,
         }
     }
 }
@@ -139,9 +224,15 @@ fn start() {
         std::panic::set_hook(Box::new(console_error_panic_hook::hook));
 
         log::warn!("Initializing logger...");
-        match console_log::init_with_level(log::Level::Warn) {
+        let log_buffer = LOG_BUFFER
+            .get_or_init(|| LogBuffer::new(DEFAULT_LOG_BUFFER_LINES))
+            .clone();
+        match MultiLogger::new(log_buffer, log::LevelFilter::Info)
+            .with_logger(Box::new(ConsoleLogger(log::Level::Warn)))
+            .init(log::LevelFilter::Warn)
+        {
             Ok(()) => {}
-            Err(e) => log::error!("Couldn't initialize logger: {}", e),
+            Err(e) => console::error_1(&format!("Couldn't initialize logger: {}", e).into()),
         };
 
         //wasm_bindgen_futures::spawn_local(run());
@@ -154,21 +245,244 @@ fn start() {
     }
 }
 
-pub async fn fetch_binary_file(url: &str) -> Result<Vec<u8>, JsValue> {
-    let client_window = window().expect("no global `window` exists");
+/// Display an error message in the player's container element in place of the emulator. The page
+/// stays alive so the user can correct the configuration and reload.
+fn show_error(message: &str) {
+    log::error!("{}", message);
+
+    let shown = web_sys::window()
+        .and_then(|win| win.document())
+        .and_then(|doc| {
+            let container = doc.query_selector("#marty-canvas-container").ok().flatten()?;
+            let div = doc.create_element("pre").ok()?;
+            div.set_class_name("marty-error");
+            div.set_text_content(Some(message));
+            container.append_child(&div).ok()
+        });
+
+    if shown.is_none() {
+        log::error!("Couldn't display error message in page.");
+    }
+}
+
+/// Show a loading bar over the canvas for a file download, or update the existing one.
+/// The canvas itself can't be drawn on here, as its context belongs to the renderer.
+fn show_progress(label: &str, loaded: usize, total: usize) {
+    let shown = web_sys::window()
+        .and_then(|win| win.document())
+        .and_then(|doc| {
+            let div = match doc.get_element_by_id("marty-progress") {
+                Some(div) => div,
+                None => {
+                    let container = doc.query_selector("#marty-canvas-container").ok().flatten()?;
+                    let div = doc.create_element("div").ok()?;
+                    div.set_id("marty-progress");
+                    div.set_class_name("marty-progress");
+                    div.set_inner_html("<span></span><progress></progress>");
+                    container.append_child(&div).ok()?;
+                    div
+                }
+            };
+
+            let text = div.query_selector("span").ok().flatten()?;
+            text.set_text_content(Some(&format!(
+                "Loading {}: {:.1} of {:.1} MB",
+                label,
+                loaded as f64 / 1_048_576.0,
+                total as f64 / 1_048_576.0
+            )));
+
+            let bar = div.query_selector("progress").ok().flatten()?;
+            bar.set_attribute("max", &total.to_string()).ok()?;
+            bar.set_attribute("value", &loaded.min(total).to_string()).ok()
+        });
+
+    if shown.is_none() {
+        log::warn!("Couldn't display download progress in page.");
+    }
+}
+
+/// Remove the loading bar, if one is shown.
+fn hide_progress() {
+    if let Some(div) = web_sys::window()
+        .and_then(|win| win.document())
+        .and_then(|doc| doc.get_element_by_id("marty-progress"))
+    {
+        div.remove();
+    }
+}
+
+/// Show the buffered log lines in the log overlay over the canvas, creating it if needed. The
+/// overlay starts hidden; the page's Log button toggles it. Nothing is done unless something has
+/// been logged since `last_generation`.
+fn update_log_overlay(last_generation: &mut u64) {
+    let log_buffer = match LOG_BUFFER.get() {
+        Some(log_buffer) => log_buffer,
+        None => return,
+    };
+    let generation = log_buffer.generation();
+    if generation == *last_generation {
+        return;
+    }
+    *last_generation = generation;
+
+    let text = log_buffer
+        .lines()
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if let Some(doc) = web_sys::window().and_then(|win| win.document()) {
+        let pre = match doc.get_element_by_id("marty-log") {
+            Some(pre) => Some(pre),
+            None => doc
+                .query_selector("#marty-canvas-container")
+                .ok()
+                .flatten()
+                .and_then(|container| {
+                    let pre = doc.create_element("pre").ok()?;
+                    pre.set_id("marty-log");
+                    pre.set_class_name("marty-log hidden");
+                    container.append_child(&pre).ok()?;
+                    Some(pre)
+                }),
+        };
+        if let Some(pre) = pre {
+            pre.set_text_content(Some(&text));
+            // Keep the newest lines in view.
+            pre.set_scroll_top(pre.scroll_height());
+        }
+    }
+}
+
+/// Ask the browser for the text contents of the clipboard. The Clipboard API is asynchronous, so the
+/// text is appended to 'pasted_text' once the promise resolves, and typed into the machine from the
+/// event loop.
+fn request_clipboard_text(pasted_text: Rc<RefCell<String>>) {
+    let read_promise = web_sys::window()
+        .and_then(|win| Reflect::get(&win.navigator(), &JsValue::from_str("clipboard")).ok())
+        .filter(|clipboard| !clipboard.is_undefined())
+        .and_then(|clipboard| {
+            let read_text = Reflect::get(&clipboard, &JsValue::from_str("readText")).ok()?;
+            let read_text = read_text.dyn_into::<js_sys::Function>().ok()?;
+            read_text.call0(&clipboard).ok()?.dyn_into::<js_sys::Promise>().ok()
+        });
+
+    let read_promise = match read_promise {
+        Some(promise) => promise,
+        None => {
+            log::warn!("Clipboard API is not available in this browser.");
+            return;
+        }
+    };
+
+    wasm_bindgen_futures::spawn_local(async move {
+        match JsFuture::from(read_promise).await {
+            Ok(text) => {
+                if let Some(text) = text.as_string() {
+                    pasted_text.borrow_mut().push_str(&text);
+                }
+            }
+            Err(e) => {
+                log::warn!("Couldn't read text from clipboard: {}", js_error_string(&e));
+            }
+        }
+    });
+}
+
+/// Return the file name at the end of a URL path, without any query string or fragment, to
+/// describe media that was fetched rather than read from disk.
+fn url_file_name(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    path.rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_string())
+}
+
+/// Convert a JavaScript error value into a readable string.
+fn js_error_string(value: &JsValue) -> String {
+    value.as_string().unwrap_or_else(|| format!("{:?}", value))
+}
+
+async fn fetch_response(url: &str, content_type: &str) -> Result<Response, JsValue> {
+    let client_window = window().ok_or_else(|| JsValue::from_str("no global `window` exists"))?;
 
     let mut opts = RequestInit::new();
     opts.method("GET");
 
     let request = Request::new_with_str_and_init(url, &opts)?;
-    request
-        .headers()
-        .set("Content-Type", "application/octet-stream")
-        .unwrap();
+    request.headers().set("Content-Type", content_type)?;
 
     let resp_value = JsFuture::from(client_window.fetch_with_request(&request)).await?;
     let resp: Response = resp_value.dyn_into()?;
 
+    if !resp.ok() {
+        return Err(JsValue::from_str(&format!("HTTP status {}", resp.status())));
+    }
+    Ok(resp)
+}
+
+pub async fn fetch_text_file(url: &str) -> Result<String, JsValue> {
+    let resp = fetch_response(url, "text/plain").await?;
+    let text = JsFuture::from(resp.text()?).await?;
+
+    text.as_string().ok_or_else(|| JsValue::from_str("response was not text"))
+}
+
+pub async fn fetch_binary_file(url: &str) -> Result<Vec<u8>, JsValue> {
+    let resp = fetch_response(url, "application/octet-stream").await?;
+    read_response_blob(resp).await
+}
+
+/// Fetch a binary file, calling `progress` with the number of bytes downloaded so far and the
+/// total size as the file arrives. The response body is streamed when the server provides a
+/// Content-Length; otherwise the file is read in one piece and progress is reported once on
+/// completion.
+pub async fn fetch_binary_file_with_progress(
+    url: &str,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<Vec<u8>, JsValue> {
+    let resp = fetch_response(url, "application/octet-stream").await?;
+
+    let total = resp
+        .headers()
+        .get("Content-Length")?
+        .and_then(|len| len.parse::<usize>().ok());
+
+    let (total, body) = match (total, resp.body()) {
+        (Some(total), Some(body)) => (total, body),
+        _ => {
+            let vec = read_response_blob(resp).await?;
+            progress(vec.len(), vec.len());
+            return Ok(vec);
+        }
+    };
+
+    let reader: ReadableStreamDefaultReader = body.get_reader().dyn_into()?;
+    let mut vec = Vec::with_capacity(total);
+    progress(0, total);
+
+    loop {
+        let chunk = JsFuture::from(reader.read()).await?;
+        if Reflect::get(&chunk, &JsValue::from_str("done"))?.is_truthy() {
+            break;
+        }
+
+        let data = js_sys::Uint8Array::new(&Reflect::get(&chunk, &JsValue::from_str("value"))?);
+        let start = vec.len();
+        vec.resize(start + data.length() as usize, 0);
+        data.copy_to(&mut vec[start..]);
+
+        // Content-Length may count compressed bytes, so don't let progress run past the total.
+        progress(vec.len().min(total), total);
+    }
+
+    Ok(vec)
+}
+
+async fn read_response_blob(resp: Response) -> Result<Vec<u8>, JsValue> {
     let blob = JsFuture::from(resp.blob()?).await?;
     let blob: Blob = blob.dyn_into()?;
 
@@ -205,24 +519,8 @@ pub async fn run(cfg: &str) {
     // Emulator stuff
     let mut stat_counter = Counter::new();
 
-    let mut video_data = VideoData {
-        render_w: DEFAULT_RENDER_WIDTH,
-        render_h: DEFAULT_RENDER_HEIGHT,
-        aspect_w: DEFAULT_ASPECT_WIDTH,
-        aspect_h: DEFAULT_ASPECT_HEIGHT,
-        aspect_correction_enabled: false,
-        composite_params: Default::default(),
-        last_mode_byte: 0,
-    };
-
-    // Create the video renderer
-    let mut video;
-    let mut render_src = vec![0; (DEFAULT_RENDER_WIDTH * DEFAULT_RENDER_HEIGHT * 4) as usize];
-    // Create resampling context
-    let mut resample_context = ResampleContext::new();
-
-    let mut exec_control = ExecutionControl::new();
-    exec_control.set_state(ExecutionState::Running);
+    let mut display_aspect = DisplayAspect::default();
+    let mut scaling = ScalingMode::default();
 
     // Winit stuff
     let event_loop = EventLoop::new();
@@ -239,7 +537,6 @@ pub async fn run(cfg: &str) {
 
     let window = Rc::new(window);
 
-    let mut composite_enabled;
     let mut machine;
 
     //#[cfg(target_arch = "wasm32")]
@@ -307,72 +604,126 @@ pub async fn run(cfg: &str) {
         log::warn!("Got config file name: {}", cfg);
 
         // Try to load toml config.
-        let mut opts = web_sys::RequestInit::new();
-        opts.method("GET");
-
-        let request = web_sys::Request::new_with_str_and_init(&format!("./cfg/{}", cfg), &opts)
-            .expect("Couldn't create request for configuration file.");
-        request
-            .headers()
-            .set("Content-Type", "text/plain")
-            .expect("Couldn't set headers!");
-
-        let resp_value = JsFuture::from(client_window.fetch_with_request(&request))
-            .await
-            .unwrap();
-        let resp: Response = resp_value.into();
-
-        // Get the response as text
-        let toml_text = JsFuture::from(resp.text().unwrap()).await.unwrap();
+        let config_path = format!("./cfg/{}", cfg);
+        let toml_text = match fetch_text_file(&config_path).await {
+            Ok(text) => text,
+            Err(e) => {
+                show_error(&format!(
+                    "Configuration file not found at {}: {}",
+                    config_path,
+                    js_error_string(&e)
+                ));
+                return;
+            }
+        };
 
         // Read config file from toml text
-        let mut config = match lib::get_config_from_str(&toml_text.as_string().unwrap()) {
+        let mut config = match get_config_from_str(&toml_text) {
             Ok(config) => config,
             Err(e) => {
                 match e.downcast_ref::<std::io::Error>() {
-                    Some(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                        log::error!("Configuration file not found!");
-                    }
                     Some(e) => {
-                        log::error!("Unknown IO error reading configuration file:\n{}", e);
+                        show_error(&format!("Unknown IO error reading configuration file:\n{}", e));
                     }
                     None => {
-                        log::error!(
-                            "Failed to parse configuration file. There may be a typo or otherwise invalid toml:\n{}",
-                            e
-                        );
+                        // Parse errors carry the line and column of the problem, or the missing key.
+                        show_error(&format!("Error in configuration file: {}", e));
                     }
                 }
                 return;
             }
         };
 
-        video = VideoRenderer::new(config.machine.video);
-
-        let rom_override = match config.machine.rom_override {
-            Some(ref rom_override) => rom_override,
-            None => panic!("No rom file specified!"),
+        // Check the configuration for everything the player needs, and report all problems at once.
+        // Media is fetched rather than read from disk, so only the shared value checks apply here.
+        let mut config_errors: Vec<String> = match config.validate() {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.iter().map(|e| e.to_string()).collect(),
         };
+        if config.machine.raw_roms.is_empty() {
+            config_errors.push("machine.raw_roms: no ROM file specified".to_string());
+        }
+        if let Some(aspect_ratio) = &config.emulator.aspect_ratio {
+            match aspect_ratio.parse::<DisplayAspect>() {
+                Ok(aspect) => display_aspect = aspect,
+                Err(e) => config_errors.push(format!("emulator.aspect_ratio: {}", e)),
+            }
+        }
+        if let Some(scaling_str) = &config.emulator.scaling {
+            match scaling_str.parse::<ScalingMode>() {
+                Ok(mode) => scaling = mode,
+                Err(e) => config_errors.push(format!("emulator.scaling: {}", e)),
+            }
+        }
+        if let Some(log_buffer) = LOG_BUFFER.get() {
+            log_buffer.set_capacity(config.emulator.log_buffer_lines);
+        }
+        if !config_errors.is_empty() {
+            show_error(&format!(
+                "The configuration file has {} problem(s):\n{}",
+                config_errors.len(),
+                config_errors.join("\n")
+            ));
+            return;
+        }
 
-        let floppy_path_str = match config.machine.floppy0 {
-            Some(ref floppy) => floppy,
-            None => panic!("No floppy image specified!"),
-        };
+        let floppy_path_str = config.machine.floppy0.as_ref();
 
         log::warn!(
-            "Read config file. Rom to load: {:?} Floppy to load: {:?}",
-            rom_override[0].path,
+            "Read config file. Roms to load: {:?} Floppy to load: {:?}",
+            config.machine.raw_roms.iter().map(|rom| &rom.path).collect::<Vec<_>>(),
             floppy_path_str
         );
 
-        // Convert Path to str
-        let rom_path_str = &rom_override[0].path.clone().into_os_string().into_string().unwrap();
+        // Fetch each ROM file and hand it to the ROM manager, which places the raw ROMs as it
+        // would if they had been read from disk.
+        let mut rom_manager = RomManager::new(false);
+        for rom in config.machine.raw_roms.iter() {
+            let rom_path_str = rom.path.to_string_lossy().to_string();
 
-        // Get the rom file as a vec<u8>
-        let rom_vec = fetch_binary_file(rom_path_str).await.unwrap();
+            match fetch_binary_file_with_progress(&rom_path_str, |l, t| show_progress("ROM", l, t)).await {
+                Ok(vec) => rom_manager.add_raw_rom_image(rom.path.clone(), vec),
+                Err(e) => {
+                    show_error(&format!("ROM not found at {}: {}", rom_path_str, js_error_string(&e)));
+                    return;
+                }
+            }
+        }
+
+        let resource_manager = ResourceManager::new(PathBuf::new());
+        let mut rom_manifest = MachineRomManifest::new();
+        if let Err(e) = rom_manager.add_raw_roms(&mut rom_manifest, &config.machine.raw_roms, &resource_manager) {
+            show_error(&format!("Error loading ROMs: {}", e));
+            return;
+        }
+
+        // Get the floppy image as a vec<u8>, if one was specified. A machine without any boot media
+        // is valid; the BIOS will start ROM BASIC or report that there is no boot device.
+        let mut floppy_vec = None;
+        let mut floppy_name = None;
+        if let Some(floppy_path_str) = floppy_path_str {
+            match fetch_binary_file_with_progress(floppy_path_str, |l, t| show_progress("floppy", l, t)).await {
+                Ok(vec) => {
+                    // Don't mount a truncated download or an error page as a disk.
+                    if let Err(e) = FloppyManager::validate_image(&vec) {
+                        show_error(&format!("Floppy image at {} can't be used: {}", floppy_path_str, e));
+                        return;
+                    }
+                    floppy_vec = Some(vec);
+                    floppy_name = url_file_name(floppy_path_str);
+                }
+                Err(e) => {
+                    show_error(&format!(
+                        "Floppy image not found at {}: {}",
+                        floppy_path_str,
+                        js_error_string(&e)
+                    ));
+                    return;
+                }
+            }
+        }
 
-        // Get the floppy image as a vec<u8>
-        let floppy_vec = fetch_binary_file(floppy_path_str).await.unwrap();
+        hide_progress();
 
         //log::warn!("rom: {:?}", rom_vec);
 
@@ -386,55 +737,66 @@ pub async fn run(cfg: &str) {
             );
         }
         else {
-            log::error!(
+            show_error(&format!(
                 "Couldn't get machine description for machine type {:?}. \
                  Check that you have a valid machine type specified in configuration file.",
                 config.machine.model
-            );
+            ));
             return;
         }
 
-        // Init sound
-        // The cpal sound library uses generics to initialize depending on the SampleFormat type.
-        // On Windows at least a sample type of f32 is typical, but just in case...
-        let sample_fmt = SoundPlayer::get_sample_format();
-        let sp = match sample_fmt {
-            cpal::SampleFormat::F32 => SoundPlayer::new::<f32>(),
-            cpal::SampleFormat::I16 => SoundPlayer::new::<i16>(),
-            cpal::SampleFormat::U16 => SoundPlayer::new::<u16>(),
-        };
-
-        // Empty features
-        let mut features = Vec::new();
-
-        let mut rom_manager = RomManager::new(config.machine.model, features, config.machine.rom_override.clone());
-
-        rom_manager.add_raw_rom(
-            &rom_vec,
-            RawRomDescriptor {
-                addr:   rom_override[0].address,
-                offset: rom_override[0].offset,
-                org:    rom_override[0].org,
-            },
-        );
+        // Init sound. Browsers may refuse to create an audio context, in which case the machine
+        // runs with a null player that discards its output.
+        let sp = SoundPlayer::open_default().unwrap_or_else(|e| {
+            log::warn!("Failed to initialize audio output, falling back to no audio: {}", e);
+            SoundPlayer::null()
+        });
 
-        // capture option before moving to machine
-        composite_enabled = config.machine.composite;
+        if floppy_vec.is_none() {
+            // Raw ROMs can't be identified, so look for any ROM mapped over the ROM BASIC address.
+            let has_basic = rom_manifest
+                .roms
+                .iter()
+                .any(|rom| (rom.addr..rom.addr + rom.data.len() as u32).contains(&ROM_BASIC_ADDRESS));
+            if has_basic {
+                log::warn!("No floppy image specified. Machine will boot to ROM BASIC.");
+            }
+            else {
+                log::warn!("No floppy image specified and no ROM BASIC present. BIOS will report no boot device.");
+            }
+        }
 
-        machine = Machine::new(
+        machine = EmbeddedMachine::new(Machine::new(
             &config,
             config.machine.model,
             *machine_desc_opt.unwrap(),
             config.emulator.trace_mode,
             config.machine.video,
             sp,
-            rom_manager,
-        );
+            rom_manifest,
+        ));
+
+        // The renderer corrects the aspect ratio into the presented frame itself, so the frame is
+        // drawn straight into the pixels buffer without an intermediate buffer of our own.
+        if let Some(renderer) = machine.renderer_mut() {
+            renderer.set_composite(config.machine.composite);
+            renderer.set_scaling(scaling);
+            // Integer scaling draws the frame at the size of the canvas.
+            let surface = window.inner_size();
+            renderer.set_surface_size((surface.width, surface.height).into());
+            match display_aspect.aspect_ratio() {
+                Some(aspect_ratio) => {
+                    renderer.set_aspect_ratio(Some(aspect_ratio), Some(AspectCorrectionMode::Software))
+                }
+                None => renderer.set_aspect_ratio(None, Some(AspectCorrectionMode::None)),
+            }
+        }
 
-        if let Some(fdc) = machine.fdc() {
+        if let (Some(fdc), Some(floppy_vec)) = (machine.machine_mut().fdc(), floppy_vec) {
             match fdc.load_image_from(0, floppy_vec) {
                 Ok(()) => {
                     log::warn!("Floppy image successfully loaded into virtual drive.");
+                    fdc.set_image_name(0, floppy_name);
                 }
                 Err(err) => {
                     log::error!("Floppy image failed to load: {}", err);
@@ -443,7 +805,29 @@ pub async fn run(cfg: &str) {
         }
 
         // Set CPU options
-        machine.set_cpu_option(CpuOption::EnableWaitStates(config.cpu.wait_states_enabled));
+        machine
+            .machine_mut()
+            .set_cpu_option(CpuOption::EnableWaitStates(config.cpu.wait_states_enabled));
+
+        // Load the translation file for the keyboard layout. Without one, keys use the US layout.
+        let kb_layout = KeyboardLayout::from_config(config.machine.input.keyboard_layout.as_deref().unwrap_or("US"));
+        let kb_layout_path = format!("./keyboard_layouts/{}", kb_layout.file_name());
+        match fetch_text_file(&kb_layout_path).await {
+            Ok(kb_layout_text) => {
+                if let Some(keyboard) = machine.machine_mut().bus_mut().keyboard_mut() {
+                    if let Err(e) = keyboard.load_mapping_from_str(&kb_layout_text) {
+                        log::error!("Failed to load keyboard layout file {}: {}", kb_layout_path, e);
+                    }
+                }
+            }
+            Err(e) => {
+                log::warn!(
+                    "Keyboard layout file {} not found: {}. Falling back to US layout.",
+                    kb_layout_path,
+                    js_error_string(&e)
+                );
+            }
+        }
 
         /*
         // Listen for resize event on browser client. Adjust winit window dimensions
@@ -466,28 +850,46 @@ pub async fn run(cfg: &str) {
         let window_size = window.inner_size();
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, window.as_ref());
 
-        Pixels::new_async(DEFAULT_RENDER_WIDTH, DEFAULT_RENDER_HEIGHT, surface_texture)
+        // Size the buffer as handle_mode_change() would, so the first frame is presented correctly.
+        Pixels::new_async(DEFAULT_ASPECT_WIDTH, DEFAULT_ASPECT_HEIGHT, surface_texture)
             .await
             .expect("Pixels error")
     };
 
     let stretching_renderer = StretchingRenderer::new(
         &pixels,
-        video_data.render_w,
-        video_data.render_h,
-        video_data.aspect_w,
-        video_data.aspect_h,
+        DEFAULT_RENDER_WIDTH,
+        DEFAULT_RENDER_HEIGHT,
+        DEFAULT_ASPECT_WIDTH,
+        DEFAULT_ASPECT_HEIGHT,
     );
 
     // Start buffer playback
-    machine.play_sound_buffer();
+    machine.machine().play_sound_buffer();
+
+    // The dimensions of the pixels buffer, which follow the dimensions of the rendered frame.
+    let mut frame_dims = (DEFAULT_ASPECT_WIDTH, DEFAULT_ASPECT_HEIGHT);
+
+    // Ctrl+V pastes the browser clipboard into the machine instead of being sent as a keystroke.
+    // The V key-up is swallowed too, so the machine never sees a release without a press.
+    let mut modifiers = KeyboardModifiers::default();
+    let mut paste_key_held = false;
+    let pasted_text = Rc::new(RefCell::new(String::new()));
+
+    let mut log_generation = 0;
 
     event_loop.run(move |event, _, control_flow| {
         match event {
             Event::WindowEvent { event, .. } => {
                 match event {
                     WindowEvent::ModifiersChanged(modifier_state) => {
-                        //kb_data.ctrl_pressed = modifier_state.ctrl();
+                        modifiers = KeyboardModifiers {
+                            control: modifier_state.ctrl(),
+                            alt: modifier_state.alt(),
+                            shift: modifier_state.shift(),
+                            meta: modifier_state.logo(),
+                            ..modifiers
+                        };
                     }
                     WindowEvent::KeyboardInput {
                         input:
@@ -498,11 +900,22 @@ pub async fn run(cfg: &str) {
                             },
                         ..
                     } => {
+                        // Track AltGr, which the modifier state doesn't distinguish from left Alt.
+                        if keycode == VirtualKeyCode::RAlt {
+                            modifiers.altgr = state == winit::event::ElementState::Pressed;
+                        }
                         match state {
+                            winit::event::ElementState::Pressed if modifiers.control && keycode == VirtualKeyCode::V => {
+                                paste_key_held = true;
+                                request_clipboard_text(Rc::clone(&pasted_text));
+                            }
+                            winit::event::ElementState::Released if paste_key_held && keycode == VirtualKeyCode::V => {
+                                paste_key_held = false;
+                            }
                             winit::event::ElementState::Pressed => {
                                 if let Some(keycode) = input::match_virtual_keycode(keycode) {
                                     //log::debug!("Key pressed, keycode: {:?}: xt: {:02X}", keycode, keycode);
-                                    machine.key_press(keycode);
+                                    machine.key_press(keycode, modifiers);
                                 };
                             }
                             winit::event::ElementState::Released => {
@@ -518,10 +931,10 @@ pub async fn run(cfg: &str) {
             }
             // Draw the current frame
             Event::RedrawRequested(event) => {
-                //world.draw(pixels.frame_mut());
-
                 //stat_counter.current_fps += 1;
 
+                update_log_overlay(&mut log_generation);
+
                 if let Err(e) = pixels.render_with(|encoder, render_target, context| {
                     let fill_texture = stretching_renderer.get_texture_view();
 
@@ -546,7 +959,21 @@ pub async fn run(cfg: &str) {
 
         let elapsed_ms = stat_counter.last_second.elapsed().as_millis();
         if elapsed_ms > 1000 {
-            log::warn!("FPS: {}", stat_counter.current_fps);
+            let elapsed_s = elapsed_ms as f64 / 1000.0;
+            stat_counter.current_ips =
+                ((stat_counter.instr_count - stat_counter.last_instr_count) as f64 / elapsed_s) as u64;
+            stat_counter.current_cps =
+                ((stat_counter.cycle_count - stat_counter.last_cpu_cycles) as f64 / elapsed_s) as u64;
+            stat_counter.last_instr_count = stat_counter.instr_count;
+            stat_counter.last_cpu_cycles = stat_counter.cycle_count;
+
+            let stats = machine.machine_mut().sample_stats(elapsed_s, stat_counter.current_fps);
+            log::warn!(
+                "FPS: {} IPS: {} CPU: {:.3}MHz",
+                stats.host_fps,
+                stats.ips,
+                stats.effective_mhz
+            );
             stat_counter.fps = stat_counter.current_fps;
             stat_counter.current_fps = 0;
             stat_counter.last_second = Instant::now();
@@ -560,195 +987,63 @@ pub async fn run(cfg: &str) {
             return;
         }
 
-        // Decide whether to draw a frame
+        // Emulation is stepped by elapsed wall time, independent of how often the browser delivers
+        // redraw events. If we fall behind (such as when a background tab is throttled) we only
+        // catch up a few frames and drop the rest, so the machine slows down instead of stalling
+        // on a long burst of emulation.
         let elapsed_us = stat_counter.last_frame.elapsed().as_micros();
         stat_counter.last_frame = Instant::now();
 
         stat_counter.accumulated_us += elapsed_us;
+        stat_counter.accumulated_us = std::cmp::min(
+            stat_counter.accumulated_us,
+            MAX_CATCHUP_FRAMES * MICROS_PER_FRAME as u128,
+        );
 
+        let mut frame_ready = false;
         while stat_counter.accumulated_us > MICROS_PER_FRAME as u128 {
             stat_counter.accumulated_us -= MICROS_PER_FRAME as u128;
-            stat_counter.last_frame = Instant::now();
             stat_counter.frame_count += 1;
             stat_counter.current_fps += 1;
 
-            // Emulate a frame worth of instructions
-            // ---------------------------------------------------------------------------
-
-            // Recalculate cycle target based on current CPU speed if it has changed (or uninitialized)
-            let mhz = machine.get_cpu_mhz();
-            if mhz != stat_counter.cpu_mhz {
-                stat_counter.cycles_per_frame = (machine.get_cpu_mhz() * 1000000.0 / FPS_TARGET) as u32;
-                stat_counter.cycle_target = stat_counter.cycles_per_frame;
-                log::info!(
-                    "CPU clock has changed to {}Mhz; new cycle target: {}",
-                    mhz,
-                    stat_counter.cycle_target
-                );
-                stat_counter.cpu_mhz = mhz;
+            // Type any text pasted from the clipboard. Scancodes are queued and delivered at the
+            // keyboard's injection rate, and characters without a scancode mapping are dropped.
+            let text = pasted_text.take();
+            if !text.is_empty() {
+                machine.machine_mut().type_string(&text.replace("\r\n", "\n"));
             }
 
+            // Emulate until the video card completes a frame.
             let emulation_start = Instant::now();
-            stat_counter.instr_count += machine.run(stat_counter.cycle_target, &mut exec_control);
+            stat_counter.instr_count += machine.run_frame();
             stat_counter.emulation_time = Instant::now() - emulation_start;
 
-            // Add instructions to IPS counter
-            stat_counter.cycle_count += stat_counter.cycle_target as u64;
-
-            // Check if there was a resolution change, if a video card is present
-            if let Some(video_card) = machine.videocard() {
-                let new_w;
-                let mut new_h;
-
-                match video_card.get_render_mode() {
-                    RenderMode::Direct => {
-                        (new_w, new_h) = video_card.get_display_aperture();
-
-                        // Set a sane maximum
-                        if new_h > 240 {
-                            new_h = 240;
-                        }
-                    }
-                    RenderMode::Indirect => {
-                        (new_w, new_h) = video_card.get_display_size();
-                    }
-                }
-
-                // If CGA, we will double scanlines later in the renderer, so make our buffer twice
-                // as high.
-                if video_card.get_scanline_double() {
-                    new_h = new_h * 2;
-                }
-
-                if new_w >= MIN_RENDER_WIDTH && new_h >= MIN_RENDER_HEIGHT {
-                    let vertical_delta = (video_data.render_h as i32).wrapping_sub(new_h as i32).abs();
-
-                    // TODO: The vertical delta hack was used for area 8088mph for the old style of rendering.
-                    // Now that we marty_render into a fixed frame, we should refactor this
-                    if (new_w != video_data.render_w) || ((new_h != video_data.render_h) && (vertical_delta <= 2)) {
-                        // Resize buffers
-                        log::debug!("Setting internal resolution to ({},{})", new_w, new_h);
-                        video_card.write_trace_log(format!("Setting internal resolution to ({},{})", new_w, new_h));
-
-                        // Calculate new aspect ratio (make this option)
-                        video_data.render_w = new_w;
-                        video_data.render_h = new_h;
-                        render_src.resize((new_w * new_h * 4) as usize, 0);
-                        render_src.fill(0);
-
-                        video_data.aspect_w = video_data.render_w;
-                        let aspect_corrected_h = f32::floor(video_data.render_w as f32 * RENDER_ASPECT) as u32;
-                        // Don't make height smaller
-                        let new_height = std::cmp::max(video_data.render_h, aspect_corrected_h);
-                        video_data.aspect_h = new_height;
-
-                        // Recalculate sampling factors
-                        resample_context.precalc(
-                            video_data.render_w,
-                            video_data.render_h,
-                            video_data.aspect_w,
-                            video_data.aspect_h,
-                        );
-
-                        pixels.frame_mut().fill(0);
-
-                        if let Err(e) = pixels.resize_buffer(video_data.aspect_w, video_data.aspect_h) {
-                            log::error!("Failed to resize pixel pixel buffer: {}", e);
-                        }
-
-                        VideoRenderer::set_alpha(pixels.frame_mut(), video_data.aspect_w, video_data.aspect_h, 255);
-                    }
-                }
-            }
+            // A frame may run longer or shorter than a host frame, so count the cycles actually executed.
+            stat_counter.cycle_count = machine.machine().cpu_cycles();
 
-            // -- Draw video memory --
-            let aspect_correct = false;
+            frame_ready = true;
+        }
 
+        // Render the most recently emulated frame once, and let the next redraw present it.
+        if frame_ready {
             let render_start = Instant::now();
 
-            // Draw video if there is a video card present
-            let bus = machine.bus_mut();
-
-            if let Some(video_card) = bus.video() {
-                let beam_pos;
-                let video_buffer;
-
-                video_buffer = video_card.get_display_buf();
-                beam_pos = None;
-
-                // Get the marty_render mode from the device and marty_render appropriately
-                match (video_card.get_video_type(), video_card.get_render_mode()) {
-                    (VideoType::CGA, RenderMode::Direct) => {
-                        // Draw device's front buffer in direct mode (CGA only for now)
-
-                        let extents = video_card.get_display_extents();
-
-                        if video_data.last_mode_byte != extents.mode_byte {
-                            // Mode byte has changed, recalculate composite parameters
-                            video.cga_direct_mode_update(extents.mode_byte);
-                            video_data.last_mode_byte = extents.mode_byte;
-                        }
-
-                        match aspect_correct {
-                            true => {
-                                video.draw_cga_direct(
-                                    &mut render_src,
-                                    video_data.render_w,
-                                    video_data.render_h,
-                                    video_buffer,
-                                    extents,
-                                    composite_enabled,
-                                    &video_data.composite_params,
-                                    beam_pos,
-                                );
-
-                                marty_render::resize_linear_fast(
-                                    &mut render_src,
-                                    video_data.render_w,
-                                    video_data.render_h,
-                                    pixels.frame_mut(),
-                                    video_data.aspect_w,
-                                    video_data.aspect_h,
-                                    &mut resample_context,
-                                );
-                            }
-                            false => {
-                                video.draw_cga_direct(
-                                    pixels.frame_mut(),
-                                    video_data.render_w,
-                                    video_data.render_h,
-                                    video_buffer,
-                                    extents,
-                                    composite_enabled,
-                                    &video_data.composite_params,
-                                    beam_pos,
-                                );
-                            }
-                        }
+            // Present the frame at the renderer's output size, after aspect correction. A machine
+            // without a video card has nothing to draw.
+            let (w, h) = machine.frame_dimensions();
+            if w > 0 && h > 0 {
+                if (w, h) != frame_dims {
+                    log::debug!("Setting display resolution to ({},{})", w, h);
+                    pixels.frame_mut().fill(0);
+                    if let Err(e) = pixels.resize_buffer(w, h) {
+                        log::error!("Failed to resize pixel buffer: {}", e);
                     }
-                    (_, RenderMode::Indirect) => {
-                        // Draw VRAM in indirect mode
-                        match aspect_correct {
-                            true => {
-                                video.draw(&mut render_src, video_card, bus, composite_enabled);
-                                marty_render::resize_linear(
-                                    &render_src,
-                                    video_data.render_w,
-                                    video_data.render_h,
-                                    pixels.frame_mut(),
-                                    video_data.aspect_w,
-                                    video_data.aspect_h,
-                                    &resample_context,
-                                );
-                            }
-                            false => {
-                                video.draw(pixels.frame_mut(), video_card, bus, composite_enabled);
-                            }
-                        }
-                    }
-                    _ => panic!("Invalid combination of VideoType and RenderMode"),
+                    VideoRenderer::set_alpha(pixels.frame_mut(), w, h, 255);
+                    frame_dims = (w, h);
                 }
+                machine.render_into(pixels.frame_mut());
             }
+            stat_counter.render_time = render_start.elapsed();
 
             window.request_redraw();
         }
@@ -761,3 +1056,30 @@ fn log_error<E: std::error::Error + 'static>(method_name: &str, err: E) {
         error!("  Caused by: {source}");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_aspect_parses_ratios() {
+        assert_eq!("square".parse::<DisplayAspect>(), Ok(DisplayAspect::Square));
+        assert_eq!(" Square ".parse::<DisplayAspect>(), Ok(DisplayAspect::Square));
+        assert_eq!("4:3".parse::<DisplayAspect>(), Ok(DisplayAspect::Ratio(0.75)));
+        assert_eq!("16 : 10".parse::<DisplayAspect>(), Ok(DisplayAspect::Ratio(0.625)));
+        assert_eq!("0.8".parse::<DisplayAspect>(), Ok(DisplayAspect::Ratio(0.8)));
+    }
+
+    #[test]
+    fn display_aspect_rejects_invalid_ratios() {
+        for s in ["", "wide", "4:", ":3", "0:3", "4:0", "-4:3", "0", "-0.5", "inf", "NaN"] {
+            assert!(s.parse::<DisplayAspect>().is_err(), "{:?} should not parse", s);
+        }
+    }
+
+    #[test]
+    fn display_aspect_converts_to_renderer_ratio() {
+        assert_eq!(DisplayAspect::Square.aspect_ratio(), None);
+        assert_eq!(DisplayAspect::default().aspect_ratio(), Some(AspectRatio { h: 1000, v: 750 }));
+    }
+}
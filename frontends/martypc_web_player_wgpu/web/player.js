@@ -17,6 +17,14 @@
     // Attach the runWasm function to the button
     document.getElementById('run-button').addEventListener('click', runWasm);
 
+    // Show or hide the log overlay. The emulator creates it once something has been logged.
+    document.getElementById('log-button').addEventListener('click', function() {
+        const log = document.getElementById('marty-log');
+        if (log != null) {
+            log.classList.toggle('hidden');
+        }
+    });
+
     function updateCanvasPosition() {
 
         const canvasContainer = document.getElementById('marty-canvas-container');
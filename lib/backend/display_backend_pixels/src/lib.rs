@@ -30,7 +30,7 @@
 */
 
 pub use pixels::{
-    wgpu::{CommandEncoder, PowerPreference, RequestAdapterOptions, TextureView},
+    wgpu::{CommandEncoder, PowerPreference, RequestAdapterOptions, TextureFormat, TextureView},
     Pixels,
     PixelsBuilder,
     SurfaceTexture,
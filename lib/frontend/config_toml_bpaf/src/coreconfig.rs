@@ -34,13 +34,14 @@
 
 use std::path::PathBuf;
 
-use crate::ConfigFileParams;
+use crate::{parse_segmented_address, ConfigFileParams};
 
 use marty_core::{
     coreconfig::CoreConfig,
     cpu_common::TraceMode,
     cpu_validator::ValidatorType,
-    machine_types::{MachineType, OnHaltBehavior},
+    machine_types::{MachineType, OnHaltBehavior, WatchdogConfig},
+    tracelogger::TraceTargetConfig,
 };
 
 /*
@@ -65,12 +66,30 @@ impl CoreConfig for ConfigFileParams {
     fn get_audio_enabled(&self) -> bool {
         self.emulator.audio.enabled
     }
+    fn get_audio_volume(&self) -> Option<f32> {
+        self.emulator.audio.volume
+    }
+    fn get_audio_mute_speaker(&self) -> bool {
+        self.emulator.audio.mute_speaker
+    }
+    fn get_audio_mute_adlib(&self) -> bool {
+        self.emulator.audio.mute_adlib
+    }
+    fn get_audio_mute_post_beep(&self) -> bool {
+        self.emulator.audio.mute_post_beep
+    }
     fn get_machine_noroms(&self) -> bool {
         self.machine.no_roms
     }
     fn get_machine_turbo(&self) -> bool {
         self.machine.turbo
     }
+    fn get_machine_turbo_mhz(&self) -> Option<f64> {
+        self.machine.turbo_mhz
+    }
+    fn get_machine_cpu_mhz(&self) -> Option<f64> {
+        self.machine.cpu_mhz
+    }
     //fn get_keyboard_type(&self) -> Option<KeyboardType> { self.machine.keyboard_type }
     fn get_keyboard_layout(&self) -> Option<String> {
         self.machine.input.keyboard_layout.clone()
@@ -105,10 +124,22 @@ impl CoreConfig for ConfigFileParams {
     fn get_title_hacks(&self) -> bool {
         self.emulator.title_hacks
     }
+    fn get_fast_boot(&self) -> bool {
+        self.emulator.fast_boot
+    }
     fn get_patch_enabled(&self) -> bool {
         self.machine.patch_roms
     }
     fn get_halt_behavior(&self) -> OnHaltBehavior {
         self.machine.cpu.on_halt.unwrap_or_default()
     }
+    fn get_watchdog(&self) -> WatchdogConfig {
+        self.machine.cpu.watchdog
+    }
+    fn get_trace_targets(&self) -> Vec<TraceTargetConfig> {
+        self.emulator.trace_targets.clone()
+    }
+    fn get_entry_point(&self) -> Option<(u16, u16)> {
+        self.emulator.entry_point.as_deref().and_then(parse_segmented_address)
+    }
 }
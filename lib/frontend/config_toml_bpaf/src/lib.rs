@@ -37,11 +37,20 @@ use std::{
     str::FromStr,
 };
 
-use marty_core::{cpu_common::TraceMode, cpu_validator::ValidatorType, machine_types::OnHaltBehavior};
+use marty_core::{
+    cpu_common::TraceMode,
+    cpu_validator::ValidatorType,
+    machine_config::MACHINE_DESCS,
+    machine_types::{MachineType, OnHaltBehavior, WatchdogConfig},
+    sound::AudioBackend,
+    tracelogger::TraceTargetConfig,
+};
 
 use frontend_common::{
     display_scaler::ScalerPreset,
+    log_buffer::DEFAULT_LOG_BUFFER_LINES,
     resource_manager::PathConfigItem,
+    rom_manager::RawRomDescriptor,
     BenchmarkEndCondition,
     HotkeyConfigEntry,
     MartyGuiTheme,
@@ -57,8 +66,16 @@ const fn _default_true() -> bool {
 const fn _default_false() -> bool {
     true
 }
+const fn _default_log_buffer_lines() -> usize {
+    DEFAULT_LOG_BUFFER_LINES
+}
 
 mod coreconfig;
+mod parse_error;
+mod validate;
+
+pub use parse_error::ConfigParseError;
+pub use validate::ConfigError;
 
 #[derive(Copy, Clone, Debug, Bpaf, Deserialize, PartialEq)]
 pub enum TestMode {
@@ -91,24 +108,56 @@ impl FromStr for TestMode {
     }
 }
 
+/// Parse a segmented address in the form `SSSS:OOOO`, with both halves in hexadecimal.
+pub fn parse_segmented_address(s: &str) -> Option<(u16, u16)> {
+    let (segment, offset) = s.trim().split_once(':')?;
+    let segment = u16::from_str_radix(segment.trim(), 16).ok()?;
+    let offset = u16::from_str_radix(offset.trim(), 16).ok()?;
+    Some((segment, offset))
+}
+
 #[derive(Debug, Deserialize)]
 pub struct VhdConfigEntry {
     pub drive:    usize,
     pub filename: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct FloppyConfigEntry {
+    pub drive:    usize,
+    pub filename: PathBuf,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Media {
     pub raw_sector_image_extensions: Option<Vec<String>>,
     #[serde(default)]
     pub write_protect_default: bool,
+    pub floppy: Option<Vec<FloppyConfigEntry>>,
     pub vhd: Option<Vec<VhdConfigEntry>>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SerialTcpConfigEntry {
+    pub port: usize,
+    pub address: String,
+    #[serde(default)]
+    pub listen: bool,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Audio {
     #[serde(default = "_default_true")]
     pub enabled: bool,
+    #[serde(default)]
+    pub backend: AudioBackend,
+    pub volume: Option<f32>,
+    #[serde(default)]
+    pub mute_speaker: bool,
+    #[serde(default)]
+    pub mute_adlib: bool,
+    #[serde(default)]
+    pub mute_post_beep: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -141,18 +190,34 @@ pub struct Emulator {
     #[serde(default)]
     pub title_hacks: bool,
     #[serde(default)]
+    pub fast_boot: bool,
+    #[serde(default = "_default_true")]
+    pub video_blink: bool,
+    #[serde(default)]
+    pub pause_when_minimized: bool,
+    #[serde(default = "_default_log_buffer_lines")]
+    pub log_buffer_lines: usize,
+    #[serde(default)]
     pub debug_mode: bool,
     #[serde(default = "_default_true")]
     pub debug_warn: bool,
     pub media: Media,
     pub debugger: Debugger,
     pub audio: Audio,
+    pub serial_tcp: Option<Vec<SerialTcpConfigEntry>>,
     pub run_bin: Option<String>,
     pub run_bin_seg: Option<u16>,
     pub run_bin_ofs: Option<u16>,
+    pub entry_point: Option<String>,
+    /// Aspect ratio the web player corrects the display to, such as "4:3", "16:10", "0.75" or "square".
+    pub aspect_ratio: Option<String>,
+    /// How the web player scales the display when correcting its aspect ratio: "linear" or "integer".
+    pub scaling: Option<String>,
 
     #[serde(default)]
     pub video_trace_file: Option<PathBuf>,
+    #[serde(default)]
+    pub trace_targets: Vec<TraceTargetConfig>,
     //pub video_frame_debug: bool,
     #[serde(default)]
     pub pit_output_file: Option<PathBuf>,
@@ -212,8 +277,11 @@ pub struct Cpu {
     pub wait_states: Option<bool>,
     pub off_rails_detection: Option<bool>,
     pub on_halt: Option<OnHaltBehavior>,
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
     pub instruction_history: Option<bool>,
     pub service_interrupt: Option<bool>,
+    pub halt_idle: Option<bool>,
     #[serde(default)]
     pub trace_on: bool,
     pub trace_mode: Option<TraceMode>,
@@ -231,7 +299,12 @@ pub struct Machine {
     pub config_overlays: Option<Vec<String>>,
     #[serde(default = "_default_true")]
     pub prefer_oem: bool,
-    //pub model: MachineType,
+    pub model: Option<MachineType>,
+    pub rom_override: Option<PathBuf>,
+    pub rom_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub raw_roms: Vec<RawRomDescriptor>,
+    pub cpu_mhz: Option<f64>,
     #[serde(default)]
     pub reload_roms: bool,
     #[serde(default)]
@@ -242,6 +315,7 @@ pub struct Machine {
     pub raw_rom: bool,
     #[serde(default)]
     pub turbo: bool,
+    pub turbo_mhz: Option<f64>,
     pub cpu: Cpu,
     pub pit_phase: Option<u32>,
     pub input: MachineInput,
@@ -251,6 +325,8 @@ pub struct Machine {
 pub struct EmulatorInput {
     #[serde(default)]
     pub reverse_mouse_buttons: bool,
+    #[serde(default)]
+    pub mouse_absolute: bool,
     pub hotkeys: Vec<HotkeyConfigEntry>,
     #[serde(default)]
     pub debug_keyboard: bool,
@@ -278,6 +354,9 @@ pub struct ConfigFileParams {
     pub machine: Machine,
     pub validator: Validator,
     pub tests: Tests,
+    /// The path the configuration was loaded from, if it was loaded from a file.
+    #[serde(skip)]
+    pub config_path: Option<PathBuf>,
 }
 
 #[derive(Debug, Bpaf)]
@@ -318,6 +397,9 @@ pub struct CmdLineArgs {
     #[bpaf(long, switch)]
     pub title_hacks: bool,
 
+    #[bpaf(long, switch)]
+    pub fast_boot: bool,
+
     #[bpaf(long, switch)]
     pub off_rails_detection: bool,
 
@@ -357,10 +439,38 @@ pub struct CmdLineArgs {
     pub run_bin_seg: Option<u16>,
     #[bpaf(long)]
     pub run_bin_ofs: Option<u16>,
+
+    // Media and machine overrides
+    #[bpaf(long("floppy0"))]
+    pub floppy0: Option<PathBuf>,
+    #[bpaf(long("hdd0"))]
+    pub hdd0: Option<String>,
+    #[bpaf(long("rom-override"))]
+    pub rom_override: Option<PathBuf>,
+    #[bpaf(long("model"))]
+    pub model: Option<String>,
+    #[bpaf(long("cpu-mhz"))]
+    pub cpu_mhz: Option<f64>,
+}
+
+/// Parse a machine model name, returning an error listing the valid models if the name is not
+/// recognized or has no machine descriptor.
+fn parse_model(model_str: &str) -> Result<MachineType, anyhow::Error> {
+    let model = MachineType::from_str(model_str).ok().filter(|m| MACHINE_DESCS.contains_key(m));
+
+    model.ok_or_else(|| {
+        let mut valid: Vec<String> = MACHINE_DESCS.keys().map(|m| format!("{:?}", m)).collect();
+        valid.sort();
+        anyhow::anyhow!(
+            "Invalid machine model '{}'. Valid models are: {}",
+            model_str,
+            valid.join(", ")
+        )
+    })
 }
 
 impl ConfigFileParams {
-    pub fn overlay(&mut self, shell_args: CmdLineArgs) {
+    pub fn overlay(&mut self, shell_args: CmdLineArgs) -> Result<(), anyhow::Error> {
         if let Some(config_name) = shell_args.machine_config_name {
             self.machine.config_name = config_name;
         }
@@ -384,6 +494,7 @@ impl ConfigFileParams {
         self.emulator.auto_poweron |= shell_args.auto_poweron;
         self.emulator.warpspeed |= shell_args.warpspeed;
         self.emulator.title_hacks |= shell_args.title_hacks;
+        self.emulator.fast_boot |= shell_args.fast_boot;
         self.emulator.audio.enabled &= !shell_args.noaudio;
 
         //self.emulator.scaler_aspect_correction |= shell_args.scaler_aspect_correction;
@@ -420,6 +531,97 @@ impl ConfigFileParams {
 
         self.emulator.romscan = shell_args.romscan;
         self.emulator.machinescan = shell_args.romscan;
+
+        if let Some(model_str) = shell_args.model {
+            self.machine.model = Some(parse_model(&model_str)?);
+        }
+        if let Some(rom_override) = shell_args.rom_override {
+            self.machine.rom_override = Some(rom_override);
+        }
+        if let Some(cpu_mhz) = shell_args.cpu_mhz {
+            self.machine.cpu_mhz = Some(cpu_mhz);
+        }
+
+        if let Some(floppy0) = shell_args.floppy0 {
+            let floppies = self.emulator.media.floppy.get_or_insert_with(Vec::new);
+            floppies.retain(|f| f.drive != 0);
+            floppies.push(FloppyConfigEntry {
+                drive:    0,
+                filename: floppy0,
+            });
+        }
+        if let Some(hdd0) = shell_args.hdd0 {
+            let vhds = self.emulator.media.vhd.get_or_insert_with(Vec::new);
+            vhds.retain(|v| v.drive != 0);
+            vhds.insert(
+                0,
+                VhdConfigEntry {
+                    drive:    0,
+                    filename: hdd0,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Re-read configuration from a TOML string, updating only the settings that can be safely
+    /// changed while the emulator is running: audio volume and muting, scaler presets, turbo, CPU
+    /// clock, fast boot, and some CPU debugging options. Other settings keep their current values.
+    ///
+    /// Returns the names of any changed settings that will not take effect until MartyPC is
+    /// restarted.
+    pub fn reload_from_str(&mut self, toml_text: &str) -> Result<Vec<String>, anyhow::Error> {
+        let new = parse_toml(toml_text)?;
+        let mut needs_restart = Vec::new();
+
+        if new.machine.config_name != self.machine.config_name {
+            needs_restart.push("machine.config_name".to_string());
+        }
+        if new.machine.config_overlays != self.machine.config_overlays {
+            needs_restart.push("machine.config_overlays".to_string());
+        }
+        if new.machine.no_roms != self.machine.no_roms {
+            needs_restart.push("machine.no_roms".to_string());
+        }
+        if new.machine.patch_roms != self.machine.patch_roms {
+            needs_restart.push("machine.patch_roms".to_string());
+        }
+        if new.machine.input.keyboard_layout != self.machine.input.keyboard_layout {
+            needs_restart.push("machine.input.keyboard_layout".to_string());
+        }
+        if new.emulator.audio.enabled != self.emulator.audio.enabled {
+            needs_restart.push("emulator.audio.enabled".to_string());
+        }
+        if new.emulator.audio.backend != self.emulator.audio.backend {
+            needs_restart.push("emulator.audio.backend".to_string());
+        }
+        if new.emulator.window.len() != self.emulator.window.len() {
+            needs_restart.push("emulator.window".to_string());
+        }
+        if new.emulator.entry_point != self.emulator.entry_point {
+            needs_restart.push("emulator.entry_point".to_string());
+        }
+
+        self.emulator.audio.volume = new.emulator.audio.volume;
+        self.emulator.audio.mute_speaker = new.emulator.audio.mute_speaker;
+        self.emulator.audio.mute_adlib = new.emulator.audio.mute_adlib;
+        self.emulator.audio.mute_post_beep = new.emulator.audio.mute_post_beep;
+        self.emulator.scaler_preset = new.emulator.scaler_preset;
+        self.emulator.title_hacks = new.emulator.title_hacks;
+        self.emulator.fast_boot = new.emulator.fast_boot;
+        self.emulator.video_blink = new.emulator.video_blink;
+        self.emulator.pause_when_minimized = new.emulator.pause_when_minimized;
+        self.emulator.log_buffer_lines = new.emulator.log_buffer_lines;
+        self.emulator.debugger = new.emulator.debugger;
+        self.machine.turbo = new.machine.turbo;
+        self.machine.turbo_mhz = new.machine.turbo_mhz;
+        self.machine.cpu_mhz = new.machine.cpu_mhz;
+        self.machine.cpu.on_halt = new.machine.cpu.on_halt;
+        self.machine.cpu.trace_on = new.machine.cpu.trace_on;
+        self.machine.cpu.off_rails_detection = new.machine.cpu.off_rails_detection;
+
+        Ok(needs_restart)
     }
 }
 
@@ -433,27 +635,65 @@ where
     // Allow configuration file path to be overridden by command line argument 'configfile'
     if let Some(configfile_path) = shell_args.configfile.as_ref() {
         let toml_string = std::fs::read_to_string(configfile_path)?;
-        toml_args = toml::from_str(&toml_string)?;
+        toml_args = parse_toml(&toml_string)?;
+        toml_args.config_path = Some(configfile_path.clone());
     }
     else {
-        let toml_string = std::fs::read_to_string(default_path)?;
-        toml_args = toml::from_str(&toml_string)?;
+        let toml_string = std::fs::read_to_string(default_path.as_ref())?;
+        toml_args = parse_toml(&toml_string)?;
+        toml_args.config_path = Some(default_path.as_ref().to_path_buf());
     }
 
     //log::debug!("toml_config: {:?}", toml_args);
 
     // Command line arguments override config file arguments
-    toml_args.overlay(shell_args);
+    toml_args.overlay(shell_args)?;
 
     Ok(toml_args)
 }
 
+/// Parse configuration from a TOML string. A parse failure is returned as a ConfigParseError,
+/// which reports the line and column of the problem, or the full path of a missing key.
 pub fn get_config_from_str(toml_text: &str) -> Result<ConfigFileParams, anyhow::Error> {
     let toml_args: ConfigFileParams;
 
-    toml_args = toml::from_str(toml_text)?;
+    toml_args = parse_toml(toml_text)?;
 
     //log::debug!("toml_config: {:?}", toml_args);
 
     Ok(toml_args)
 }
+
+fn parse_toml(toml_text: &str) -> Result<ConfigFileParams, ConfigParseError> {
+    toml::from_str(toml_text).map_err(|e| ConfigParseError::from_toml(toml_text, &e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT_CONFIG: &str = include_str!("../../../../install/martypc.toml");
+
+    #[test]
+    fn parses_segmented_addresses() {
+        assert_eq!(parse_segmented_address("F000:E000"), Some((0xF000, 0xE000)));
+        assert_eq!(parse_segmented_address(" 0:7c00 "), Some((0x0000, 0x7C00)));
+        assert_eq!(parse_segmented_address("F000 : E000"), Some((0xF000, 0xE000)));
+        assert_eq!(parse_segmented_address("F000E000"), None);
+        assert_eq!(parse_segmented_address("F000:"), None);
+        assert_eq!(parse_segmented_address("10000:0000"), None);
+        assert_eq!(parse_segmented_address("G000:0000"), None);
+    }
+
+    #[test]
+    fn changing_entry_point_needs_restart() {
+        let mut config = parse_toml(DEFAULT_CONFIG).unwrap();
+        assert!(config.emulator.entry_point.is_none());
+
+        let changed = DEFAULT_CONFIG.replace("#entry_point = \"F000:E000\"", "entry_point = \"F000:E000\"");
+        assert_ne!(changed, DEFAULT_CONFIG);
+        let needs_restart = config.reload_from_str(&changed).unwrap();
+        assert_eq!(needs_restart, vec!["emulator.entry_point".to_string()]);
+        assert!(config.emulator.entry_point.is_none());
+    }
+}
@@ -0,0 +1,182 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    config_toml_bpaf::parse_error.rs
+
+    Conversion of TOML deserialization errors into errors that report where
+    in the configuration file the problem is.
+*/
+
+use std::{fmt, ops::Range};
+
+/// An error encountered while parsing the configuration file.
+#[derive(Debug)]
+pub struct ConfigParseError {
+    /// The error message from the TOML parser.
+    pub message: String,
+    /// The full path of a required key that was not found, such as 'machine.config_name'.
+    pub missing_key: Option<String>,
+    /// The 1-based line and column of the problem, if known.
+    pub location: Option<(usize, usize)>,
+    /// The text of the offending line, if known.
+    pub line_text: Option<String>,
+}
+
+impl ConfigParseError {
+    pub fn from_toml(toml_text: &str, err: &toml::de::Error) -> Self {
+        let message = err.message().trim().to_string();
+        let span = err.span();
+
+        let location = span.as_ref().map(|span| line_col(toml_text, span.start));
+        let line_text = location.and_then(|(line, _)| toml_text.lines().nth(line - 1).map(|s| s.to_string()));
+
+        let missing_key = missing_field(&message).map(|field| match enclosing_table(toml_text, span) {
+            Some(table) => format!("{}.{}", table, field),
+            None => field.to_string(),
+        });
+
+        Self {
+            message,
+            missing_key,
+            location,
+            line_text,
+        }
+    }
+}
+
+impl fmt::Display for ConfigParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(key) = &self.missing_key {
+            return write!(f, "missing required setting '{}'", key);
+        }
+        match self.location {
+            Some((line, column)) => {
+                write!(f, "line {}, column {}: {}", line, column, self.message)?;
+                if let Some(text) = &self.line_text {
+                    write!(f, "\n  {}\n  {:>width$}", text, "^", width = column)?;
+                }
+                Ok(())
+            }
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigParseError {}
+
+/// Convert a byte offset into a 1-based line and column.
+fn line_col(text: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(text.len());
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+/// Extract the field name from serde's "missing field `name`" message.
+fn missing_field(message: &str) -> Option<&str> {
+    let rest = message.strip_prefix("missing field `")?;
+    rest.split('`').next()
+}
+
+/// Return the name of the table header in effect at the start of 'span', such as 'machine' for
+/// a span within the [machine] table, or None for the root table. A table's span is its header
+/// line, while the root table's span starts at the beginning of the document and runs past its
+/// first line, so a header on the line where the span starts only counts if the span ends on
+/// that line.
+fn enclosing_table(text: &str, span: Option<Range<usize>>) -> Option<String> {
+    let span = span?;
+    let start = span.start.min(text.len());
+    let line_end = text[start..].find('\n').map_or(text.len(), |i| start + i);
+    let end = if span.end <= line_end + 1 {
+        line_end
+    }
+    else {
+        text[..start].rfind('\n').map_or(0, |i| i + 1)
+    };
+
+    text[..end]
+        .lines()
+        .filter_map(|line| {
+            let header = line.trim().strip_prefix('[')?;
+            let header = header.split(']').next()?.trim_start_matches('[').trim();
+            (!header.is_empty()).then(|| header.to_string())
+        })
+        .last()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    struct TestConfig {
+        name:    String,
+        machine: TestMachine,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    struct TestMachine {
+        model: String,
+        turbo: bool,
+    }
+
+    fn parse_error(text: &str) -> ConfigParseError {
+        let err = toml::from_str::<TestConfig>(text).unwrap_err();
+        ConfigParseError::from_toml(text, &err)
+    }
+
+    #[test]
+    fn reports_location_of_syntax_errors() {
+        let text = "[machine]\nturbo = = true\n";
+        let err = toml::from_str::<toml::Table>(text).unwrap_err();
+        let err = ConfigParseError::from_toml(text, &err);
+        assert_eq!(err.location.map(|(line, _)| line), Some(2));
+        assert!(err.missing_key.is_none());
+    }
+
+    #[test]
+    fn reports_missing_root_key() {
+        // The document starts with a table header, which must not be mistaken for the table the
+        // missing key belongs to.
+        let err = parse_error("[machine]\nmodel = \"ibm5160\"\nturbo = true\n");
+        assert_eq!(err.missing_key.as_deref(), Some("name"));
+        assert_eq!(err.to_string(), "missing required setting 'name'");
+    }
+
+    #[test]
+    fn reports_missing_nested_key() {
+        let err = parse_error("name = \"test\"\n\n[machine]\nmodel = \"ibm5160\"\n");
+        assert_eq!(err.missing_key.as_deref(), Some("machine.turbo"));
+
+        let err = parse_error("[machine]\nturbo = true\n");
+        assert_eq!(err.missing_key.as_deref(), Some("machine.model"));
+    }
+}
@@ -0,0 +1,290 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    config_toml_bpaf::validate.rs
+
+    Validation of the parsed configuration, so that problems can be reported
+    all at once before any emulator state is constructed.
+*/
+
+use std::{fmt, path::PathBuf};
+
+use marty_core::{machine_config::MACHINE_DESCS, machine_types::MachineType};
+
+use crate::{parse_segmented_address, ConfigFileParams};
+
+/// A single problem found while validating the configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    InvalidModel(MachineType),
+    FileNotFound { setting: String, path: PathBuf },
+    InvalidValue { setting: String, reason: String },
+    UnknownScalerPreset { window: String, preset: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::InvalidModel(model) => {
+                let mut valid: Vec<String> = MACHINE_DESCS.keys().map(|m| format!("{:?}", m)).collect();
+                valid.sort();
+                write!(
+                    f,
+                    "machine.model: {:?} is not a supported machine. Valid models are: {}",
+                    model,
+                    valid.join(", ")
+                )
+            }
+            ConfigError::FileNotFound { setting, path } => {
+                write!(f, "{}: file not found: {}", setting, path.display())
+            }
+            ConfigError::InvalidValue { setting, reason } => {
+                write!(f, "{}: {}", setting, reason)
+            }
+            ConfigError::UnknownScalerPreset { window, preset } => {
+                write!(
+                    f,
+                    "window '{}' references scaler preset '{}', which is not defined by any [[emulator.scaler_preset]]",
+                    window, preset
+                )
+            }
+        }
+    }
+}
+
+impl ConfigFileParams {
+    /// Check the configuration for problems that would otherwise surface later as a failure to
+    /// start. All problems found are returned, not just the first. Media files are not checked,
+    /// as not every frontend loads them from the host filesystem; see validate_media().
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        if let Some(model) = self.machine.model {
+            if !MACHINE_DESCS.contains_key(&model) {
+                errors.push(ConfigError::InvalidModel(model));
+            }
+        }
+
+        if self.machine.config_name.trim().is_empty() {
+            errors.push(ConfigError::InvalidValue {
+                setting: "machine.config_name".to_string(),
+                reason:  "no machine configuration name specified".to_string(),
+            });
+        }
+
+        for (setting, mhz) in [
+            ("machine.cpu_mhz", self.machine.cpu_mhz),
+            ("machine.turbo_mhz", self.machine.turbo_mhz),
+        ] {
+            if let Some(mhz) = mhz {
+                if !mhz.is_finite() || mhz <= 0.0 {
+                    errors.push(ConfigError::InvalidValue {
+                        setting: setting.to_string(),
+                        reason:  format!("{} is not a valid clock speed", mhz),
+                    });
+                }
+            }
+        }
+
+        if let Some(entry_point) = &self.emulator.entry_point {
+            if parse_segmented_address(entry_point).is_none() {
+                errors.push(ConfigError::InvalidValue {
+                    setting: "emulator.entry_point".to_string(),
+                    reason:  format!("'{}' is not a segment:offset address in hexadecimal", entry_point),
+                });
+            }
+        }
+
+        let vhds = self.emulator.media.vhd.as_ref().map(|v| v.as_slice()).unwrap_or(&[]);
+        for (i, vhd) in vhds.iter().enumerate() {
+            if vhd.filename.trim().is_empty() {
+                errors.push(ConfigError::InvalidValue {
+                    setting: format!("emulator.media.vhd (drive {})", vhd.drive),
+                    reason:  "no filename specified".to_string(),
+                });
+            }
+            if vhds[..i].iter().any(|other| other.drive == vhd.drive) {
+                errors.push(ConfigError::InvalidValue {
+                    setting: format!("emulator.media.vhd (drive {})", vhd.drive),
+                    reason:  "drive specified more than once".to_string(),
+                });
+            }
+        }
+
+        for window in self.emulator.window.iter().filter(|w| w.enabled) {
+            let preset = window.scaler_preset.clone().unwrap_or("default".to_string());
+            if !self.emulator.scaler_preset.iter().any(|p| p.name == preset) {
+                errors.push(ConfigError::UnknownScalerPreset {
+                    window: window.name.clone(),
+                    preset,
+                });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(errors)
+        }
+    }
+
+    /// Check that the ROM, floppy and VHD images named in the configuration exist on the host.
+    /// VHDs are named relative to the hdd resource path, so 'vhd_available' is called to check
+    /// each one.
+    pub fn validate_media(&self, vhd_available: impl Fn(&str) -> bool) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        if let Some(rom_override) = &self.machine.rom_override {
+            if !rom_override.is_file() {
+                errors.push(ConfigError::FileNotFound {
+                    setting: "machine.rom_override".to_string(),
+                    path:    rom_override.clone(),
+                });
+            }
+        }
+
+        for raw_rom in &self.machine.raw_roms {
+            if !raw_rom.path.is_file() {
+                errors.push(ConfigError::FileNotFound {
+                    setting: format!("machine.raw_roms (address {:05X})", raw_rom.addr),
+                    path:    raw_rom.path.clone(),
+                });
+            }
+        }
+
+        for floppy in self.emulator.media.floppy.as_ref().unwrap_or(&Vec::new()).iter() {
+            if !floppy.filename.is_file() {
+                errors.push(ConfigError::FileNotFound {
+                    setting: format!("emulator.media.floppy (drive {})", floppy.drive),
+                    path:    floppy.filename.clone(),
+                });
+            }
+        }
+
+        for vhd in self.emulator.media.vhd.as_ref().unwrap_or(&Vec::new()).iter() {
+            if !vhd.filename.trim().is_empty() && !vhd_available(&vhd.filename) {
+                errors.push(ConfigError::FileNotFound {
+                    setting: format!("emulator.media.vhd (drive {})", vhd.drive),
+                    path:    PathBuf::from(&vhd.filename),
+                });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_config_from_str, VhdConfigEntry};
+    use frontend_common::rom_manager::RawRomDescriptor;
+
+    const DEFAULT_CONFIG: &str = include_str!("../../../../install/martypc.toml");
+
+    fn setting(error: &ConfigError) -> String {
+        match error {
+            ConfigError::InvalidModel(_) => "machine.model".to_string(),
+            ConfigError::FileNotFound { setting, .. } | ConfigError::InvalidValue { setting, .. } => setting.clone(),
+            ConfigError::UnknownScalerPreset { window, .. } => format!("window '{}'", window),
+        }
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        let config = get_config_from_str(DEFAULT_CONFIG).unwrap();
+        assert!(config.validate().is_ok());
+        assert!(config.validate_media(|_| true).is_ok());
+    }
+
+    #[test]
+    fn all_problems_are_reported() {
+        let mut config = get_config_from_str(DEFAULT_CONFIG).unwrap();
+        config.machine.cpu_mhz = Some(-4.77);
+        config.emulator.media.vhd = Some(vec![
+            VhdConfigEntry {
+                drive:    0,
+                filename: "dos330.vhd".to_string(),
+            },
+            VhdConfigEntry {
+                drive:    0,
+                filename: "hdd1.vhd".to_string(),
+            },
+        ]);
+        config.emulator.window[0].scaler_preset = Some("No Such Preset".to_string());
+        let main_window = config.emulator.window[0].name.clone();
+
+        let errors = config.validate().unwrap_err();
+        let settings: Vec<String> = errors.iter().map(setting).collect();
+        assert_eq!(
+            settings,
+            vec![
+                "machine.cpu_mhz".to_string(),
+                "emulator.media.vhd (drive 0)".to_string(),
+                format!("window '{}'", main_window),
+            ]
+        );
+        assert!(matches!(
+            &errors[2],
+            ConfigError::UnknownScalerPreset { preset, .. } if preset == "No Such Preset"
+        ));
+    }
+
+    #[test]
+    fn missing_raw_roms_are_reported() {
+        let dir = std::env::temp_dir().join(format!("martypc_validate_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("option.bin"), [0u8; 16]).unwrap();
+
+        let mut config = get_config_from_str(DEFAULT_CONFIG).unwrap();
+        for (name, addr) in [("option.bin", 0xC8000), ("missing.bin", 0xD0000)] {
+            config.machine.raw_roms.push(RawRomDescriptor {
+                path: dir.join(name),
+                addr,
+                offset: None,
+                org: None,
+            });
+        }
+
+        let errors = config.validate_media(|_| true).unwrap_err();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(errors.len(), 1);
+        match &errors[0] {
+            ConfigError::FileNotFound { setting, path } => {
+                assert_eq!(setting, "machine.raw_roms (address D0000)");
+                assert_eq!(path, &dir.join("missing.bin"));
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+}
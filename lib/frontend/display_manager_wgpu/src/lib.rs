@@ -48,6 +48,7 @@ pub use display_backend_pixels::{
     Pixels,
     PixelsBackend,
     SurfaceDimensions,
+    TextureFormat,
     TextureView,
 };
 
@@ -85,10 +86,18 @@ use marty_core::{
 };
 use marty_egui::context::GuiRenderContext;
 use marty_pixels_scaler::{DisplayScaler, MartyScaler, ScalerMode};
-use videocard_renderer::{AspectCorrectionMode, AspectRatio, VideoRenderer};
+use videocard_renderer::{AspectCorrectionMode, AspectRatio, OutputFormat, VideoRenderer};
 
 const EGUI_MENU_BAR: u32 = 24;
 
+/// Determine the format a VideoRenderer should draw in for a Pixels instance, from the format of
+/// its buffer texture and of the surface the texture is presented on.
+fn renderer_output_format(pixels: &Pixels) -> OutputFormat {
+    let buffer_format = pixels.context().texture_format;
+    let bgra = matches!(buffer_format, TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb);
+    OutputFormat::new(bgra, buffer_format.is_srgb(), pixels.render_texture_format().is_srgb())
+}
+
 /*
 pub(crate) const WINDOW_MIN_WIDTH: u32 = 640;
 pub(crate) const WINDOW_MIN_HEIGHT: u32 = 480;
@@ -150,6 +159,7 @@ pub struct DisplayTargetContext<T> {
     pub requested_params: Option<DisplayTargetParams>,
     pub window: Option<Window>, // The winit window, if any
     pub window_opts: Option<DisplayManagerWindowOptions>,
+    pub minimized: bool, // The window has a zero-size surface and should not be rendered
     pub(crate) gui_ctx: Option<GuiRenderContext>, // The egui render context, if any
     pub(crate) card_id: Option<VideoCardId>,      // The video card device id, if any
     pub(crate) renderer: Option<VideoRenderer>,   // The renderer
@@ -159,6 +169,7 @@ pub struct DisplayTargetContext<T> {
         Option<Box<dyn DisplayScaler<Pixels, NativeTextureView = TextureView, NativeEncoder = CommandEncoder>>>, // The scaler pipeline
     pub(crate) scaler_params: Option<ScalerParams>,
     pub(crate) card_scale: Option<f32>, // If Some, the card resolution is scaled by this factor
+    pub(crate) scaler_preset: Option<String>, // The name of the last scaler preset applied
 }
 
 pub struct WgpuDisplayManagerBuilder {}
@@ -469,6 +480,8 @@ impl DisplayTargetContext<PixelsBackend> {
     }
 
     pub fn apply_scaler_preset(&mut self, preset: &ScalerPreset) {
+        self.scaler_preset = Some(preset.name.clone());
+
         // We must have a backend and scaler to continue...
         if !self.backend.is_some() || !self.scaler.is_some() {
             return;
@@ -500,6 +513,10 @@ impl DisplayTargetContext<PixelsBackend> {
                 renderer.set_aspect_ratio(preset.renderer.aspect_ratio, Some(AspectCorrectionMode::Hardware));
             }
             renderer.set_composite(preset.renderer.composite);
+            renderer.set_scaling(preset.renderer.scaling);
+            if let Some(cga_palette) = preset.renderer.cga_palette {
+                renderer.set_palette_preset(cga_palette);
+            }
         }
     }
 
@@ -764,6 +781,7 @@ impl DisplayManager<PixelsBackend, GuiRenderContext, WindowId, Window> for WgpuD
                     let mut video = VideoRenderer::new(card_id.vtype);
 
                     video.set_config_params(&scaler_preset.renderer);
+                    video.set_output_format(renderer_output_format(pb.get_backend_raw().unwrap()));
                     Some(video)
                 }
                 else {
@@ -803,6 +821,7 @@ impl DisplayManager<PixelsBackend, GuiRenderContext, WindowId, Window> for WgpuD
                     requested_params: None,
                     window: Some(window),
                     window_opts,
+                    minimized: false,
                     gui_ctx,
                     card_id,
                     renderer,
@@ -811,6 +830,7 @@ impl DisplayManager<PixelsBackend, GuiRenderContext, WindowId, Window> for WgpuD
                     scaler: Some(Box::new(scaler)), // The scaler pipeline
                     scaler_params: Some(ScalerParams::from(scaler_preset.clone())),
                     card_scale,
+                    scaler_preset: None,
                 };
 
                 dtc.apply_scaler_preset(&scaler_preset);
@@ -927,6 +947,10 @@ impl DisplayManager<PixelsBackend, GuiRenderContext, WindowId, Window> for WgpuD
         self.targets[0].window.as_ref()
     }
 
+    fn is_main_window_minimized(&self) -> bool {
+        self.targets.first().is_some_and(|dt| dt.minimized)
+    }
+
     fn get_main_backend(&mut self) -> Option<&PixelsBackend> {
         // Main display should always be index 0.
         self.targets[0].backend.as_ref()
@@ -1000,7 +1024,7 @@ impl DisplayManager<PixelsBackend, GuiRenderContext, WindowId, Window> for WgpuD
 
                     software_aspect = matches!(renderer.get_params().aspect_correction, AspectCorrectionMode::Software);
 
-                    let aperture = renderer.get_params().aperture;
+                    let aperture = renderer.get_effective_aperture();
                     let w = extents.apertures[aperture as usize].w;
                     let mut h = extents.apertures[aperture as usize].h;
 
@@ -1193,7 +1217,19 @@ impl DisplayManager<PixelsBackend, GuiRenderContext, WindowId, Window> for WgpuD
     }
 
     fn on_window_resized(&mut self, wid: WindowId, w: u32, h: u32) -> Result<(), Error> {
-        let _idx = self.window_id_map.get(&wid).context("Failed to look up window")?;
+        let idx = *self.window_id_map.get(&wid).context("Failed to look up window")?;
+
+        // A surface can't be resized to zero, so leave it alone and stop rendering to it until
+        // the window is restored.
+        let minimized = w == 0 || h == 0;
+        if self.targets[idx].minimized != minimized {
+            log::debug!("on_window_resized(): dt{}: minimized: {}", idx, minimized);
+            self.targets[idx].minimized = minimized;
+        }
+        if minimized {
+            self.window_id_resize_requests.remove(&wid);
+            return Ok(());
+        }
 
         self.window_id_resize_requests
             .entry(wid)
@@ -1286,7 +1322,7 @@ impl DisplayManager<PixelsBackend, GuiRenderContext, WindowId, Window> for WgpuD
     where
         F: FnMut(&mut VideoRenderer, VideoCardId, &mut [u8]),
     {
-        for dtc in &mut self.targets {
+        for dtc in self.targets.iter_mut().filter(|dtc| !dtc.minimized) {
             if let Some(renderer) = &mut dtc.renderer {
                 f(renderer, dtc.card_id.unwrap(), dtc.backend.as_mut().unwrap().buf_mut())
             }
@@ -1299,7 +1335,7 @@ impl DisplayManager<PixelsBackend, GuiRenderContext, WindowId, Window> for WgpuD
     {
         for dtc in &mut self.targets {
             match dtc.ttype {
-                DisplayTargetType::WindowBackground { .. } => {
+                DisplayTargetType::WindowBackground { .. } if !dtc.minimized => {
                     // A WindowBackground target will have a PixelsBackend.
                     if let Some(backend) = &mut dtc.backend {
                         if let Some(scaler) = &mut dtc.scaler {
@@ -1411,6 +1447,18 @@ impl DisplayManager<PixelsBackend, GuiRenderContext, WindowId, Window> for WgpuD
         self.scaler_presets.get(&name)
     }
 
+    fn reload_scaler_presets(&mut self, presets: &[ScalerPreset]) {
+        for preset in presets {
+            self.scaler_presets.insert(preset.name.clone(), preset.clone());
+        }
+
+        for dtc in self.targets.iter_mut() {
+            if let Some(preset) = dtc.scaler_preset.as_ref().and_then(|name| self.scaler_presets.get(name)) {
+                dtc.apply_scaler_preset(&preset.clone());
+            }
+        }
+    }
+
     fn apply_scaler_preset(&mut self, dt_idx: usize, name: String) -> Result<(), Error> {
         if dt_idx < self.targets.len() {
             let preset = self.get_scaler_preset(name).unwrap().clone();
@@ -1458,6 +1506,27 @@ impl DisplayManager<PixelsBackend, GuiRenderContext, WindowId, Window> for WgpuD
         Ok(dt.card_id)
     }
 
+    fn window_to_display(&self, wid: WindowId, x: f64, y: f64) -> Option<(VideoCardId, DisplayApertureType, f32, f32)> {
+        let dt = &self.targets[*self.window_id_map.get(&wid)?];
+        let card_id = dt.card_id?;
+        let renderer = dt.renderer.as_ref()?;
+        let (u, v) = dt.scaler.as_ref()?.surface_to_texture(x as f32, y as f32)?;
+        Some((card_id, renderer.get_effective_aperture(), u, v))
+    }
+
+    fn set_display_overscan(&mut self, dt_idx: usize, state: bool) -> Result<Option<VideoCardId>, Error> {
+        if dt_idx >= self.targets.len() {
+            return Err(anyhow!("Display target out of range!"));
+        }
+
+        let dt = &mut self.targets[dt_idx];
+
+        if let Some(renderer) = &mut dt.renderer {
+            renderer.set_show_overscan(state);
+        }
+        Ok(dt.card_id)
+    }
+
     fn set_aspect_correction(&mut self, dt_idx: usize, state: bool) -> Result<(), Error> {
         if dt_idx >= self.targets.len() {
             return Err(anyhow!("Display target out of range!"));
@@ -179,6 +179,9 @@ pub trait DisplayManager<B, G, Wi, W> {
     /// is rendered.
     fn get_main_window(&self) -> Option<&W>;
 
+    /// Returns true if the main window has a zero-size surface, such as when it is minimized.
+    fn is_main_window_minimized(&self) -> bool;
+
     /// Returns the associated Backend for the main window.
     fn get_main_backend(&mut self) -> Option<&B>;
 
@@ -210,6 +213,8 @@ pub trait DisplayManager<B, G, Wi, W> {
     /// The window is not actually updated on this call since multiple resize events may be received
     /// per frame. To actually resize the window we must call resize_windows(), which will apply the
     /// last received resize dimensions for each window.
+    /// A zero width or height, as reported when a window is minimized, is not applied. Instead the
+    /// window is marked minimized and is not rendered until it has a positive size again.
     fn on_window_resized(&mut self, wid: Wi, w: u32, h: u32) -> Result<(), Error>;
 
     /// Reflect pending window resize events, resizing associated resources as needed.
@@ -227,11 +232,13 @@ pub trait DisplayManager<B, G, Wi, W> {
     ///
     /// The card ID can be used to retrieve the internal buffer for the card from the Machine and
     /// call the renderer to create a frame buffer.
+    /// Renderers of minimized windows are skipped.
     fn for_each_renderer<F>(&mut self, f: F)
     where
         F: FnMut(&mut VideoRenderer, VideoCardId, &mut [u8]);
 
     /// Execute a closure that is passed a mutable reference to each Backend in the manager.
+    /// Backends of minimized windows are skipped.
     fn for_each_backend<F>(&mut self, f: F)
     //where F: FnMut(&mut B, &mut dyn DisplayScaler<B, NativeTextureView=Self::NativeTextureView, NativeEncoder=Self::NativeEncoder>, Option<&mut G>);
     where
@@ -278,6 +285,10 @@ pub trait DisplayManager<B, G, Wi, W> {
     /// Retrieve the scaler preset by name.
     fn get_scaler_preset(&mut self, name: String) -> Option<&ScalerPreset>;
 
+    /// Replace the definitions of the specified scaler presets, and re-apply them to any display
+    /// targets currently using them.
+    fn reload_scaler_presets(&mut self, presets: &[ScalerPreset]);
+
     /// Apply the named scaler preset to the specified display target.
     fn apply_scaler_preset(&mut self, dt_idx: usize, name: String) -> Result<(), Error>;
 
@@ -296,6 +307,17 @@ pub trait DisplayManager<B, G, Wi, W> {
         aperture: DisplayApertureType,
     ) -> Result<Option<VideoCardId>, Error>;
 
+    /// Map a position within the specified window, in physical pixels, to the image of the display
+    /// target shown in that window. Returns the id of the card being displayed, the effective
+    /// aperture of its renderer and the normalized (0.0-1.0) position within that aperture, or None
+    /// if the position falls outside the displayed image.
+    fn window_to_display(&self, wid: Wi, x: f64, y: f64) -> Option<(VideoCardId, DisplayApertureType, f32, f32)>;
+
+    /// Enable or disable rendering of the full overscan border for the specified display target.
+    /// Returns the associated VideoCardId, as the card will need to be resized when the effective
+    /// aperture changes.
+    fn set_display_overscan(&mut self, dt_idx: usize, state: bool) -> Result<Option<VideoCardId>, Error>;
+
     /// Enable or disable aspect correction for the specified display target.
     /// The display manager will perform the required resizing of display target resources
     /// and perform buffer clearing.
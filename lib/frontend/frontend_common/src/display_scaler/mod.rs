@@ -192,4 +192,7 @@ pub trait DisplayScaler<B>: Send + Sync {
     fn set_fill_color(&mut self, fill: MartyColor);
     fn set_option(&mut self, pixels: &B, opt: ScalerOption, update: bool) -> bool;
     fn set_options(&mut self, pixels: &B, opts: Vec<ScalerOption>);
+    /// Map a position on the destination surface, in pixels, to a normalized (0.0-1.0) position on
+    /// the source texture. Returns None if the position falls outside the scaled image.
+    fn surface_to_texture(&self, x: f32, y: f32) -> Option<(f32, f32)>;
 }
@@ -0,0 +1,356 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    frontend_common::embed::mod.rs
+
+    A minimal interface for driving a Machine from an application other than
+    the provided front ends. The application owns the window, audio output and
+    input handling; EmbeddedMachine runs the machine one frame at a time and
+    returns the rendered frame and the audio produced during it.
+
+    The minimal sequence to get a booting machine on screen is:
+
+      1. Load the ROM definitions and machine configurations, and scan for ROMs:
+           ResourceManager::from_config(), MachineManager::load_configs(),
+           RomManager::load_defs(), RomManager::scan(), RomManager::resolve_rom_sets()
+      2. Pick a machine configuration with MachineManager::get_config(), resolve
+         its ROM features with RomManager::resolve_requirements() and build a
+         manifest with RomManager::create_manifest().
+      3. Build the Machine with MachineBuilder, passing
+         SoundPlayer::collector(sample_rate) to with_sound_player() to receive
+         audio samples.
+      4. Wrap the Machine with EmbeddedMachine::new().
+      5. Once per host frame, call step_frame() and present the returned RGBA
+         buffer and samples. Feed input between frames with key_press(),
+         key_release(), mouse_update() and set_joystick().
+
+    An application that presents frames from its own buffer, or plays audio
+    through a SoundPlayer with an output device, can instead call run_frame(),
+    then frame_dimensions() and render_into() to draw the frame without an
+    intermediate copy.
+
+*/
+
+use marty_core::{
+    device_traits::videocard::BufferSelect,
+    devices::{game_port::JoystickState, keyboard::KeyboardModifiers},
+    keys::MartyKey,
+    machine::{ExecutionControl, ExecutionOperation, ExecutionState, Machine, ResetKind},
+};
+use videocard_renderer::VideoRenderer;
+
+// Refresh rate assumed for machines without a video card, which have no frame to wait for.
+const DEFAULT_FRAME_RATE: f64 = 60.0;
+// Limit on the length of a frame, in frames at DEFAULT_FRAME_RATE. A card that stops producing
+// frames, such as one with its display disabled, must not stall the host.
+const MAX_FRAME_LENGTH: u32 = 2;
+
+/// The output of a single emulated frame.
+#[derive(Clone, Debug, Default)]
+pub struct FrameOutput {
+    /// The rendered frame as 8-bit RGBA pixels, `width` * `height` * 4 bytes long. Empty if the
+    /// machine has no video card.
+    pub rgba: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// Mono audio samples produced during the frame. Empty unless the machine was built with a
+    /// collecting SoundPlayer.
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    /// Bytes printed to the parallel port printer during the frame. Empty unless the machine has
+    /// a printer attached.
+    pub printer: Vec<u8>,
+}
+
+/// Runs a Machine one frame at a time on behalf of an embedding application.
+pub struct EmbeddedMachine {
+    machine: Machine,
+    exec_control: ExecutionControl,
+    renderer: Option<VideoRenderer>,
+}
+
+impl EmbeddedMachine {
+    pub fn new(machine: Machine) -> Self {
+        let renderer = machine
+            .bus()
+            .primary_video()
+            .map(|video| VideoRenderer::new(video.get_video_type()));
+
+        Self {
+            machine,
+            exec_control: ExecutionControl::new(),
+            renderer,
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine {
+        &mut self.machine
+    }
+
+    /// Return the renderer for the primary video card, so that options such as the aperture,
+    /// palette or composite mode can be set. Returns None if the machine has no video card.
+    pub fn renderer_mut(&mut self) -> Option<&mut VideoRenderer> {
+        self.renderer.as_mut()
+    }
+
+    /// Reset the machine. This also resumes a machine that has halted.
+    pub fn reset(&mut self, kind: ResetKind) {
+        self.machine.reset(kind);
+        self.exec_control.set_state(ExecutionState::Paused);
+    }
+
+    /// Run the machine until the primary video card completes a frame, then render it. A machine
+    /// that has halted is not run, but its last frame is still returned.
+    pub fn step_frame(&mut self) -> FrameOutput {
+        self.run_frame();
+
+        let (width, height) = self.frame_dimensions();
+        let mut rgba = vec![0; (width * height * 4) as usize];
+        self.render_into(&mut rgba);
+
+        let (samples, sample_rate) = match self.machine.sound_player_mut() {
+            Some(sound_player) => (sound_player.take_samples(), sound_player.sample_rate()),
+            None => (Vec::new(), 0),
+        };
+
+        FrameOutput {
+            rgba,
+            width,
+            height,
+            samples,
+            sample_rate,
+            printer: self.machine.take_printer_output().unwrap_or_default(),
+        }
+    }
+
+    /// Run the machine until the primary video card completes a frame, without rendering it.
+    /// Returns the number of instructions executed. A machine that has halted is not run.
+    pub fn run_frame(&mut self) -> u64 {
+        if matches!(self.exec_control.get_state(), ExecutionState::Halted) {
+            return 0;
+        }
+
+        let slice = (self.machine.get_cpu_mhz() * 1_000_000.0 / DEFAULT_FRAME_RATE) as u32;
+        self.exec_control.set_state(ExecutionState::Paused);
+        self.exec_control.set_op(ExecutionOperation::StepFrame);
+
+        let mut instructions = 0;
+        for _ in 0..MAX_FRAME_LENGTH {
+            instructions += self.machine.run(slice, &mut self.exec_control);
+            if !matches!(self.exec_control.get_state(), ExecutionState::StepFrame) {
+                break;
+            }
+        }
+        instructions
+    }
+
+    /// Update the renderer for the primary video card's current display mode, and return the
+    /// dimensions of the frame that render_into() will draw. These are the aspect corrected
+    /// dimensions if the renderer performs software aspect correction. Returns (0, 0) if the
+    /// machine has no video card.
+    pub fn frame_dimensions(&mut self) -> (u32, u32) {
+        let (renderer, video) = match (&mut self.renderer, self.machine.bus().primary_video()) {
+            (Some(renderer), Some(video)) => (renderer, video),
+            _ => return (0, 0),
+        };
+
+        let extents = video.get_display_extents();
+        renderer.set_line_double(extents.double_scan);
+        let aperture = &extents.apertures[renderer.get_effective_aperture() as usize];
+        let mut h = aperture.h;
+        if extents.double_scan {
+            h *= 2;
+        }
+        if renderer.would_resize((aperture.w, h).into()) {
+            renderer.resize((aperture.w, h).into());
+        }
+        if renderer.get_mode_byte() != extents.mode_byte {
+            renderer.cga_direct_mode_update(extents.mode_byte);
+            renderer.set_mode_byte(extents.mode_byte);
+        }
+
+        let dims = renderer.get_display_dimensions();
+        (dims.w, dims.h)
+    }
+
+    /// Draw the primary video card's last complete frame into 'output' as 8-bit RGBA pixels.
+    /// 'output' must hold the frame at the dimensions last returned by frame_dimensions().
+    pub fn render_into(&mut self, output: &mut [u8]) {
+        if let (Some(renderer), Some(video)) = (&mut self.renderer, self.machine.bus().primary_video()) {
+            renderer.draw(
+                video.get_buf(BufferSelect::Front),
+                output,
+                video.get_display_extents(),
+                None,
+            );
+        }
+    }
+
+    /// Press a key on the emulated keyboard.
+    pub fn key_press(&mut self, key: MartyKey, modifiers: KeyboardModifiers) {
+        self.machine.key_press(key, modifiers);
+    }
+
+    /// Release a key on the emulated keyboard.
+    pub fn key_release(&mut self, key: MartyKey) {
+        self.machine.key_release(key);
+    }
+
+    /// Send the state of the mouse buttons and the relative motion of the mouse since the last
+    /// update to the emulated mouse, if one is installed.
+    pub fn mouse_update(&mut self, l_button_pressed: bool, r_button_pressed: bool, delta_x: f64, delta_y: f64) {
+        if let Some(mouse) = self.machine.mouse_mut() {
+            mouse.update(l_button_pressed, r_button_pressed, delta_x, delta_y);
+        }
+    }
+
+    /// Update the state of a joystick attached to the game port, if one is installed.
+    pub fn set_joystick(&mut self, stick: usize, state: JoystickState) {
+        self.machine.set_joystick(stick, state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use marty_core::{
+        device_traits::videocard::VideoType,
+        machine::{MachineBuilder, MachineRomManifest},
+        machine_config::{MachineConfiguration, VideoCardConfig},
+        machine_types::OnHaltBehavior,
+        sound::SoundPlayer,
+        test_util::{test_machine_config, TestConfig, TEST_CONFIG},
+    };
+    use videocard_renderer::{AspectCorrectionMode, AspectRatio};
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    static EMBED_CONFIG: TestConfig = TestConfig {
+        audio_enabled: true,
+        halt_behavior: OnHaltBehavior::Stop,
+        ..TEST_CONFIG
+    };
+
+    fn test_config(video: Option<VideoType>) -> MachineConfiguration {
+        let mut config = test_machine_config();
+        config.speaker = true;
+        config.video = video
+            .into_iter()
+            .map(|video_type| VideoCardConfig {
+                video_type,
+                font: None,
+                vram_size: None,
+                vram_open_bus: false,
+            })
+            .collect();
+        config
+    }
+
+    /// Build a machine without ROMs that runs 'program' from 1000:0000, collecting its audio.
+    fn test_embedded_machine(video: Option<VideoType>, program: &[u8]) -> EmbeddedMachine {
+        let mut machine = MachineBuilder::new()
+            .with_core_config(Box::new(&EMBED_CONFIG))
+            .with_machine_config(&test_config(video))
+            .with_roms(MachineRomManifest::new())
+            .with_sound_player(Some(SoundPlayer::collector(SAMPLE_RATE)))
+            .build()
+            .unwrap();
+        machine.load_program(program, 0x1000, 0x0000).unwrap();
+        EmbeddedMachine::new(machine)
+    }
+
+    #[test]
+    fn step_frame_returns_one_frame_of_audio() {
+        // A tight 'JMP $' loop.
+        let mut embedded = test_embedded_machine(None, &[0xEB, 0xFE]);
+
+        let frame = embedded.step_frame();
+        assert!(frame.rgba.is_empty());
+        assert_eq!((frame.width, frame.height), (0, 0));
+        assert_eq!(frame.sample_rate, SAMPLE_RATE);
+
+        // Without a video card a frame lasts one frame at the default frame rate.
+        let expected = SAMPLE_RATE as f64 / DEFAULT_FRAME_RATE;
+        let produced = frame.samples.len() as f64;
+        assert!(
+            (produced - expected).abs() / expected < 0.05,
+            "expected about {} samples, got {}",
+            expected,
+            produced
+        );
+
+        // Samples are only returned once.
+        let frame = embedded.step_frame();
+        assert!((frame.samples.len() as f64 - expected).abs() / expected < 0.05);
+    }
+
+    #[test]
+    fn step_frame_renders_at_display_dimensions() {
+        let mut embedded = test_embedded_machine(Some(VideoType::CGA), &[0xEB, 0xFE]);
+        embedded
+            .renderer_mut()
+            .unwrap()
+            .set_aspect_ratio(Some(AspectRatio::default()), Some(AspectCorrectionMode::Software));
+
+        let frame = embedded.step_frame();
+        assert!(frame.width > 0 && frame.height > 0);
+        assert_eq!(frame.rgba.len(), (frame.width * frame.height * 4) as usize);
+
+        // The frame is returned after software aspect correction, not at the card's native size.
+        let renderer = embedded.renderer_mut().unwrap();
+        let corrected =
+            VideoRenderer::get_aspect_corrected_res(renderer.get_buf_dimensions(), Some(AspectRatio::default()));
+        assert_eq!((frame.width, frame.height), (corrected.w, corrected.h));
+
+        // Each frame step runs until the card completes exactly one frame.
+        let frame_count =
+            |embedded: &EmbeddedMachine| embedded.machine().bus().primary_video().unwrap().get_frame_count();
+        let start = frame_count(&embedded);
+        embedded.step_frame();
+        assert_eq!(frame_count(&embedded), start + 1);
+    }
+
+    #[test]
+    fn step_frame_does_not_run_halted_machine() {
+        // 'CLI; HLT' halts the CPU permanently, which stops the machine.
+        let mut embedded = test_embedded_machine(Some(VideoType::CGA), &[0xFA, 0xF4]);
+
+        let first = embedded.step_frame();
+        let cycles = embedded.machine().cpu_cycles();
+
+        let frame = embedded.step_frame();
+        assert_eq!(embedded.machine().cpu_cycles(), cycles);
+        assert!(frame.samples.is_empty());
+
+        // The last frame is still returned.
+        assert_eq!((frame.width, frame.height), (first.width, first.height));
+        assert_eq!(frame.rgba, first.rgba);
+    }
+}
@@ -41,6 +41,11 @@ use std::{
 };
 
 use anyhow::Error;
+use marty_core::{device_types::fdc::DISK_FORMATS, devices::fdc::SECTOR_SIZE};
+
+// Images smaller than the smallest standard format are accepted as partial single-sided disks,
+// such as bare boot sector images.
+const MIN_STANDARD_IMAGE_SIZE: usize = 163_840;
 
 #[derive(Debug)]
 pub enum FloppyError {
@@ -48,6 +53,7 @@ pub enum FloppyError {
     ImageNotFound,
     FileReadError,
     FileWriteError,
+    InvalidImage(String),
 }
 impl std::error::Error for FloppyError {}
 impl Display for FloppyError {
@@ -57,6 +63,7 @@ impl Display for FloppyError {
             FloppyError::ImageNotFound => write!(f, "Specified image name could not be found in floppy manager."),
             FloppyError::FileReadError => write!(f, "A file read error occurred."),
             FloppyError::FileWriteError => write!(f, "A file write error occurred."),
+            FloppyError::InvalidImage(reason) => write!(f, "Invalid floppy image: {}", reason),
         }
     }
 }
@@ -228,9 +235,47 @@ impl FloppyManager {
                 return Err(FloppyError::FileReadError);
             }
         };
+        FloppyManager::validate_image(&floppy_vec)?;
         Ok(floppy_vec)
     }
 
+    /// Check that 'data' has the length of a known floppy geometry before it is mounted, so that
+    /// a truncated download or an HTML error page is reported instead of being loaded as a disk.
+    pub fn validate_image(data: &[u8]) -> Result<(), FloppyError> {
+        let len = data.len();
+        if len == 0 {
+            return Err(FloppyError::InvalidImage("file is empty".to_string()));
+        }
+        if DISK_FORMATS.contains_key(&len) {
+            return Ok(());
+        }
+
+        // An error page can happen to be a multiple of the sector size, so check for one before
+        // accepting small images.
+        if data.starts_with(b"<") {
+            return Err(FloppyError::InvalidImage(format!(
+                "got {} bytes of what appears to be HTML or XML, not a disk image",
+                len
+            )));
+        }
+        if len < MIN_STANDARD_IMAGE_SIZE && len % SECTOR_SIZE == 0 {
+            return Ok(());
+        }
+
+        // Describe the format the image is closest to in size, as it is most likely truncated or padded.
+        let nearest = DISK_FORMATS
+            .iter()
+            .min_by_key(|(size, _)| size.abs_diff(len))
+            .map(|(size, fmt)| (*size, fmt.name));
+        match nearest {
+            Some((size, name)) => Err(FloppyError::InvalidImage(format!(
+                "expected {} bytes for {}, got {}",
+                size, name, len
+            ))),
+            None => Err(FloppyError::InvalidImage(format!("unrecognized image size: {} bytes", len))),
+        }
+    }
+
     /*
     pub fn load_floppy_data(&self, name: &OsString) -> Result<Vec<u8>, FloppyError> {
         let mut floppy_vec = Vec::new();
@@ -282,3 +327,36 @@ impl FloppyManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_image_accepts_known_and_small_images() {
+        assert!(FloppyManager::validate_image(&vec![0xF6; 368_640]).is_ok());
+        assert!(FloppyManager::validate_image(&vec![0xF6; SECTOR_SIZE * 4]).is_ok());
+    }
+
+    #[test]
+    fn validate_image_rejects_html_and_odd_sizes() {
+        assert!(matches!(
+            FloppyManager::validate_image(&[]),
+            Err(FloppyError::InvalidImage(_))
+        ));
+
+        // An error page padded to a whole number of sectors is still rejected.
+        let mut page = b"<!DOCTYPE html><html>Not Found</html>".to_vec();
+        page.resize(SECTOR_SIZE * 2, b' ');
+        match FloppyManager::validate_image(&page) {
+            Err(FloppyError::InvalidImage(reason)) => assert!(reason.contains("HTML")),
+            other => panic!("expected an HTML error, got {:?}", other),
+        }
+
+        // A truncated 360K image is reported against the nearest format.
+        match FloppyManager::validate_image(&vec![0xF6; 368_640 - 100]) {
+            Err(FloppyError::InvalidImage(reason)) => assert!(reason.contains("expected 368640 bytes")),
+            other => panic!("expected a size error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    frontend_common::input::mod.rs
+
+    Input handling shared between front ends.
+
+    KeyboardLayout is the table of keyboard layouts MartyPC ships translation
+    files for. Each layout's file maps host keycodes to XT scancodes, along
+    with the shifted and AltGr symbols that differ from the US layout. An
+    unknown layout falls back to the US layout so that the keyboard still
+    works with the default scancode translation.
+
+*/
+
+use std::{fmt, str::FromStr};
+
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+#[derive(Copy, Clone, Debug, Default, EnumIter, Eq, PartialEq)]
+pub enum KeyboardLayout {
+    #[default]
+    Us,
+    Uk,
+    It,
+    De,
+    Fr,
+}
+
+impl KeyboardLayout {
+    /// Return the layout named by a configuration string such as "DE". An unknown layout logs a
+    /// warning and falls back to the US layout.
+    pub fn from_config(layout_str: &str) -> Self {
+        layout_str.parse().unwrap_or_else(|e| {
+            log::warn!("{} Falling back to US layout.", e);
+            KeyboardLayout::Us
+        })
+    }
+
+    /// Return the short name of the layout, as used in configuration files.
+    pub fn code(&self) -> &'static str {
+        match self {
+            KeyboardLayout::Us => "US",
+            KeyboardLayout::Uk => "UK",
+            KeyboardLayout::It => "IT",
+            KeyboardLayout::De => "DE",
+            KeyboardLayout::Fr => "FR",
+        }
+    }
+
+    /// Return the name of the layout's keyboard translation file.
+    pub fn file_name(&self) -> String {
+        format!("keyboard_{}.toml", self.code())
+    }
+}
+
+impl fmt::Display for KeyboardLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+impl FromStr for KeyboardLayout {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        KeyboardLayout::iter()
+            .find(|layout| layout.code().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("Unknown keyboard layout: {}.", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layouts_parse_and_fall_back_to_us() {
+        assert_eq!(KeyboardLayout::from_config("de"), KeyboardLayout::De);
+        assert_eq!(KeyboardLayout::from_config("FR").file_name(), "keyboard_FR.toml");
+        assert_eq!(KeyboardLayout::from_config("XX"), KeyboardLayout::Us);
+        assert!("XX".parse::<KeyboardLayout>().is_err());
+    }
+
+    #[test]
+    fn every_layout_has_a_translation_file() {
+        let layout_dir =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../install/configs/keyboard_layouts");
+        for layout in KeyboardLayout::iter() {
+            assert!(
+                layout_dir.join(layout.file_name()).exists(),
+                "missing file for {}",
+                layout
+            );
+        }
+    }
+}
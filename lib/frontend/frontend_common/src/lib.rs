@@ -38,7 +38,10 @@ pub mod constants;
 pub mod display_manager;
 #[cfg(feature = "use_wgpu")]
 pub mod display_scaler;
+pub mod embed;
 pub mod floppy_manager;
+pub mod input;
+pub mod log_buffer;
 pub mod machine_manager;
 pub mod resource_manager;
 pub mod rom_manager;
@@ -0,0 +1,263 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    --------------------------------------------------------------------------
+
+    frontend_common::log_buffer::mod.rs
+
+    Keeps the most recent log records in memory so that a front end can show
+    them to the user. MultiLogger is installed as the global logger and passes
+    each record on to the front end's usual loggers (env_logger, the browser
+    console) as well as recording it in a LogBuffer.
+
+*/
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+pub const DEFAULT_LOG_BUFFER_LINES: usize = 500;
+
+/// A single formatted log record.
+#[derive(Clone, Debug)]
+pub struct LogLine {
+    pub level:   Level,
+    pub target:  String,
+    pub message: String,
+}
+
+impl fmt::Display for LogLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{:<5} {}] {}", self.level, self.target, self.message)
+    }
+}
+
+struct LogRing {
+    lines: VecDeque<LogLine>,
+    capacity: usize,
+    generation: u64,
+}
+
+/// A shared handle to a ring buffer of the most recent log lines. Clones refer to the same buffer.
+#[derive(Clone)]
+pub struct LogBuffer {
+    ring: Arc<Mutex<LogRing>>,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            ring: Arc::new(Mutex::new(LogRing {
+                lines: VecDeque::with_capacity(capacity),
+                capacity,
+                generation: 0,
+            })),
+        }
+    }
+
+    /// Set the number of lines kept, discarding the oldest lines if the buffer shrinks.
+    pub fn set_capacity(&self, capacity: usize) {
+        if let Ok(mut ring) = self.ring.lock() {
+            ring.capacity = capacity;
+            while ring.lines.len() > capacity {
+                ring.lines.pop_front();
+            }
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ring.lock().map(|ring| ring.capacity).unwrap_or(0)
+    }
+
+    fn push(&self, line: LogLine) {
+        if let Ok(mut ring) = self.ring.lock() {
+            if ring.capacity == 0 {
+                return;
+            }
+            while ring.lines.len() >= ring.capacity {
+                ring.lines.pop_front();
+            }
+            ring.lines.push_back(line);
+            ring.generation = ring.generation.wrapping_add(1);
+        }
+    }
+
+    /// Return a copy of the buffered lines, oldest first.
+    pub fn lines(&self) -> Vec<LogLine> {
+        self.ring
+            .lock()
+            .map(|ring| ring.lines.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Return a counter that changes whenever a line is added, so that a front end can skip
+    /// redrawing the log when nothing has been logged.
+    pub fn generation(&self) -> u64 {
+        self.ring.lock().map(|ring| ring.generation).unwrap_or(0)
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut ring) = self.ring.lock() {
+            ring.lines.clear();
+            ring.generation = ring.generation.wrapping_add(1);
+        }
+    }
+}
+
+/// A logger that records log lines in a LogBuffer and forwards every record to any number of
+/// other loggers. Each forwarded logger applies its own filtering.
+pub struct MultiLogger {
+    loggers: Vec<Box<dyn Log>>,
+    buffer: LogBuffer,
+    buffer_level: LevelFilter,
+}
+
+impl MultiLogger {
+    /// Create a logger that records lines at `buffer_level` or more severe in `buffer`.
+    pub fn new(buffer: LogBuffer, buffer_level: LevelFilter) -> Self {
+        Self {
+            loggers: Vec::new(),
+            buffer,
+            buffer_level,
+        }
+    }
+
+    pub fn with_logger(mut self, logger: Box<dyn Log>) -> Self {
+        self.loggers.push(logger);
+        self
+    }
+
+    /// Install this logger as the global logger. `max_level` should be the most verbose level any
+    /// of the forwarded loggers accepts; records more verbose than both it and the buffer level
+    /// are discarded before reaching any logger.
+    pub fn init(self, max_level: LevelFilter) -> Result<(), SetLoggerError> {
+        let max_level = max_level.max(self.buffer_level);
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+impl Log for MultiLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.buffer_level || self.loggers.iter().any(|logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= self.buffer_level {
+            self.buffer.push(LogLine {
+                level:   record.level(),
+                target:  record.target().to_string(),
+                message: record.args().to_string(),
+            });
+        }
+        for logger in &self.loggers {
+            if logger.enabled(record.metadata()) {
+                logger.log(record);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        for logger in &self.loggers {
+            logger.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(message: &str) -> LogLine {
+        LogLine {
+            level:   Level::Info,
+            target:  "test".to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    fn messages(buffer: &LogBuffer) -> Vec<String> {
+        buffer.lines().into_iter().map(|line| line.message).collect()
+    }
+
+    #[test]
+    fn full_buffer_drops_the_oldest_lines() {
+        let buffer = LogBuffer::new(3);
+        for message in ["a", "b", "c", "d", "e"] {
+            buffer.push(line(message));
+        }
+        assert_eq!(messages(&buffer), vec!["c", "d", "e"]);
+        assert_eq!(buffer.generation(), 5);
+
+        // Shrinking keeps the newest lines.
+        buffer.set_capacity(2);
+        assert_eq!(messages(&buffer), vec!["d", "e"]);
+        buffer.set_capacity(4);
+        buffer.push(line("f"));
+        assert_eq!(messages(&buffer), vec!["d", "e", "f"]);
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let buffer = LogBuffer::new(0);
+        buffer.push(line("a"));
+        assert!(buffer.lines().is_empty());
+        assert_eq!(buffer.generation(), 0);
+    }
+
+    #[test]
+    fn clones_share_the_buffer() {
+        let buffer = LogBuffer::new(4);
+        buffer.clone().push(line("a"));
+        assert_eq!(messages(&buffer), vec!["a"]);
+
+        buffer.clear();
+        assert!(buffer.lines().is_empty());
+        assert_eq!(buffer.generation(), 2);
+    }
+
+    #[test]
+    fn multi_logger_buffers_records_at_the_buffer_level() {
+        let buffer = LogBuffer::new(4);
+        let logger = MultiLogger::new(buffer.clone(), LevelFilter::Info);
+        for (level, message) in [(Level::Debug, "debug"), (Level::Info, "info"), (Level::Error, "error")] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target("machine")
+                    .args(format_args!("{}", message))
+                    .build(),
+            );
+        }
+
+        let lines = buffer.lines();
+        assert_eq!(messages(&buffer), vec!["info", "error"]);
+        assert_eq!(lines[1].to_string(), "[ERROR machine] error");
+    }
+}
@@ -34,17 +34,23 @@ use anyhow::Error;
 use marty_core::{
     device_traits::videocard::VideoType,
     machine_config::{
+        EmsConfig,
         FloppyControllerConfig,
+        GamePortConfig,
         HardDriveControllerConfig,
         KeyboardConfig,
         MachineConfiguration,
         MediaConfig,
         MemoryConfig,
+        ParallelControllerConfig,
+        PostCardConfig,
+        RtcConfig,
         SerialControllerConfig,
         SerialMouseConfig,
+        SoundDeviceConfig,
         VideoCardConfig,
     },
-    machine_types::{HardDiskControllerType, MachineType},
+    machine_types::{BootTarget, HardDiskControllerType, MachineType, OpenBusMode},
 };
 
 use serde_derive::Deserialize;
@@ -73,39 +79,49 @@ pub struct MachineConfigFileEntry {
     rom_set: String,
     overlays: Option<Vec<String>>,
     memory: MemoryConfig,
+    open_bus: Option<OpenBusMode>,
+    dram_refresh: Option<bool>,
     #[serde(default)]
     speaker: bool,
     ppi_turbo: Option<bool>, // This bool is an option so that it is three state - missing means no turbo feature, true means ppi high = turbo, false means ppi low = turbo.
     fdc: Option<FloppyControllerConfig>,
     hdc: Option<HardDriveControllerConfig>,
     serial: Option<Vec<SerialControllerConfig>>,
+    sound: Option<Vec<SoundDeviceConfig>>,
+    ems: Option<EmsConfig>,
+    game_port: Option<GamePortConfig>,
+    parallel: Option<ParallelControllerConfig>,
+    rtc: Option<RtcConfig>,
+    post_card: Option<PostCardConfig>,
     video: Option<Vec<VideoCardConfig>>,
     keyboard: Option<KeyboardConfig>,
     serial_mouse: Option<SerialMouseConfig>,
     media: Option<MediaConfig>,
+    boot_order: Option<Vec<String>>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct MachineConfigFileOverlayEntry {
     name: String,
     memory: Option<MemoryConfig>,
+    open_bus: Option<OpenBusMode>,
+    dram_refresh: Option<bool>,
     fdc: Option<FloppyControllerConfig>,
     hdc: Option<HardDriveControllerConfig>,
     serial: Option<Vec<SerialControllerConfig>>,
+    sound: Option<Vec<SoundDeviceConfig>>,
+    ems: Option<EmsConfig>,
+    game_port: Option<GamePortConfig>,
+    parallel: Option<ParallelControllerConfig>,
+    rtc: Option<RtcConfig>,
+    post_card: Option<PostCardConfig>,
     video: Option<Vec<VideoCardConfig>>,
     keyboard: Option<KeyboardConfig>,
     serial_mouse: Option<SerialMouseConfig>,
     media: Option<MediaConfig>,
+    boot_order: Option<Vec<String>>,
 }
 
-/*
-#[derive(Clone, Debug, Deserialize)]
-pub struct ParallelControllerConfig {
-    type: ParallelControllerType,
-    port: Vec<ParallelPortConfig>,
-}
- */
-
 pub struct MachineManager {
     active_config: Option<MachineConfigFileEntry>,
     config_names: HashSet<String>,
@@ -267,6 +283,16 @@ impl MachineManager {
 }
 
 impl MachineConfigFileEntry {
+    pub fn machine_type(&self) -> MachineType {
+        self.machine_type
+    }
+
+    /// Override the machine type, such as from the --model command line option. ROM requirements
+    /// and the machine configuration follow the new type.
+    pub fn set_machine_type(&mut self, machine_type: MachineType) {
+        self.machine_type = machine_type;
+    }
+
     pub fn get_specified_rom_set(&self) -> Option<String> {
         if self.rom_set.contains("auto") {
             return None;
@@ -351,6 +377,14 @@ impl MachineConfigFileEntry {
             log::debug!("Applying memory overlay: {:?}", memory);
             self.memory = memory;
         }
+        if let Some(open_bus) = overlay.open_bus {
+            log::debug!("Applying open bus overlay: {:?}", open_bus);
+            self.open_bus = Some(open_bus);
+        }
+        if let Some(dram_refresh) = overlay.dram_refresh {
+            log::debug!("Applying DRAM refresh overlay: {:?}", dram_refresh);
+            self.dram_refresh = Some(dram_refresh);
+        }
         if let Some(fdc) = overlay.fdc {
             log::debug!("Applying FDC overlay: {:?}", fdc);
             self.fdc = Some(fdc);
@@ -363,6 +397,30 @@ impl MachineConfigFileEntry {
             log::debug!("Applying serial overlay: {:?}", serial);
             self.serial = Some(serial);
         }
+        if let Some(sound) = overlay.sound {
+            log::debug!("Applying sound overlay: {:?}", sound);
+            self.sound = Some(sound);
+        }
+        if let Some(ems) = overlay.ems {
+            log::debug!("Applying EMS overlay: {:?}", ems);
+            self.ems = Some(ems);
+        }
+        if let Some(game_port) = overlay.game_port {
+            log::debug!("Applying game port overlay: {:?}", game_port);
+            self.game_port = Some(game_port);
+        }
+        if let Some(parallel) = overlay.parallel {
+            log::debug!("Applying parallel port overlay: {:?}", parallel);
+            self.parallel = Some(parallel);
+        }
+        if let Some(rtc) = overlay.rtc {
+            log::debug!("Applying RTC overlay: {:?}", rtc);
+            self.rtc = Some(rtc);
+        }
+        if let Some(post_card) = overlay.post_card {
+            log::debug!("Applying POST card overlay: {:?}", post_card);
+            self.post_card = Some(post_card);
+        }
         if let Some(video) = overlay.video {
             log::debug!("Applying video overlay: {:?}", video);
             self.video = Some(video);
@@ -375,6 +433,10 @@ impl MachineConfigFileEntry {
             log::debug!("Applying serial mouse overlay: {:?}", serial_mouse);
             self.serial_mouse = Some(serial_mouse);
         }
+        if let Some(boot_order) = overlay.boot_order {
+            log::debug!("Applying boot order overlay: {:?}", boot_order);
+            self.boot_order = Some(boot_order);
+        }
     }
 
     pub fn to_machine_config(&self) -> MachineConfiguration {
@@ -383,13 +445,109 @@ impl MachineConfigFileEntry {
             ppi_turbo: self.ppi_turbo,
             machine_type: self.machine_type,
             memory: self.memory.clone(),
+            open_bus: self.open_bus,
+            dram_refresh: self.dram_refresh,
             fdc: self.fdc.clone(),
             hdc: self.hdc.clone(),
             serial: self.serial.clone().unwrap_or_default(),
+            sound: self.sound.clone().unwrap_or_default(),
+            ems: self.ems.clone(),
+            game_port: self.game_port.clone(),
+            parallel: self.parallel.clone(),
+            rtc: self.rtc.clone(),
+            post_card: self.post_card.clone(),
             video: self.video.clone().unwrap_or_default(),
             keyboard: self.keyboard.clone(),
             serial_mouse: self.serial_mouse.clone(),
             media: self.media.clone(),
+            boot_order: self.parse_boot_order(),
+        }
+    }
+
+    /// Parse the boot order, skipping invalid targets. An empty boot order uses the BIOS default.
+    fn parse_boot_order(&self) -> Vec<BootTarget> {
+        self.boot_order
+            .iter()
+            .flatten()
+            .filter_map(|target| match target.parse::<BootTarget>() {
+                Ok(target) => Some(target),
+                Err(e) => {
+                    log::warn!("Ignoring invalid boot target: {}", e);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use marty_core::machine_config::normalize_conventional_memory;
+
+    fn shipped_config_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../../install/configs/machines")
+    }
+
+    /// Load every shipped machine configuration file, as load_configs() would.
+    fn load_shipped_configs() -> MachineManager {
+        let mut manager = MachineManager::new();
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(shipped_config_dir())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+
+        for path in paths {
+            let config_file = manager
+                .parse_config_file(&path)
+                .unwrap_or_else(|e| panic!("{:?} failed to parse: {}", path, e));
+            for config in config_file.machine.unwrap_or_default() {
+                manager.configs.insert(config.name.clone(), config);
+            }
+            for overlay in config_file.overlay.unwrap_or_default() {
+                manager.overlays.insert(overlay.name.clone(), overlay);
+            }
+        }
+        manager
+    }
+
+    #[test]
+    fn shipped_machine_configs_and_overlays_parse() {
+        let manager = load_shipped_configs();
+        assert!(manager.configs.contains_key("ibm5160"));
+        assert!(manager.overlays.contains_key("ibm5150_16k_conventional"));
+    }
+
+    #[test]
+    fn memory_overlay_without_size_uses_installed_kb() {
+        let mut manager = load_shipped_configs();
+        let config = manager
+            .get_config_with_overlays("ibm5160", &vec!["ibm5150_16k_conventional".to_string()])
+            .unwrap();
+        let conventional = normalize_conventional_memory(&config.to_machine_config()).unwrap();
+        assert_eq!(conventional, 16 * 1024);
+    }
+
+    #[test]
+    fn model_override_changes_rom_requirements() {
+        let manager = load_shipped_configs();
+        let mut config = manager.get_config("ibm5160").unwrap().clone();
+        let base_features = |machine_type| {
+            marty_core::machine_config::get_base_rom_features(machine_type)
+                .unwrap()
+                .iter()
+                .map(|feature| feature.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        config.set_machine_type(MachineType::Ibm5150v64K);
+        let (required, _) = config.get_rom_requirements().unwrap();
+        for feature in base_features(MachineType::Ibm5150v64K) {
+            assert!(required.contains(&feature), "missing 5150 feature {}", feature);
         }
+        assert_eq!(config.to_machine_config().machine_type, MachineType::Ibm5150v64K);
     }
 }
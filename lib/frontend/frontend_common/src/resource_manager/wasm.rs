@@ -31,6 +31,7 @@
 */
 
 use crate::resource_manager::{ResourceItem, ResourceItemType, ResourceManager};
+use anyhow::Error;
 use std::{fs, path::PathBuf};
 
 impl ResourceManager {
@@ -52,4 +53,10 @@ impl ResourceManager {
     pub fn path_is_dir(path: &PathBuf) -> bool {
         false
     }
+
+    /// On wasm targets, we can't read from the filesystem. Resources must be fetched by the
+    /// frontend and supplied from memory instead.
+    pub fn read_resource_from_path(&self, path: &PathBuf) -> Result<Vec<u8>, Error> {
+        Err(anyhow::anyhow!("Can't read {:?}: no filesystem is available", path))
+    }
 }
@@ -31,7 +31,11 @@
 
 use crate::resource_manager::ResourceManager;
 use anyhow::Error;
-use marty_core::machine::{MachineCheckpoint, MachinePatch, MachineRomEntry, MachineRomManifest};
+use marty_core::{
+    machine::{MachineCheckpoint, MachinePatch, MachineRomEntry, MachineRomManifest},
+    machine_types::MachineType,
+    rom_manager::machine_rom_subdir,
+};
 use serde::Deserialize;
 use std::{
     collections::{hash_map::Entry, HashMap, HashSet},
@@ -95,6 +99,42 @@ pub struct RomDescriptor {
     present: bool,
 }
 
+/// A ROM image loaded from an arbitrary file and placed at an explicit address, bypassing the ROM
+/// set definitions. Used to map option ROMs that are not in the ROM database.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RawRomDescriptor {
+    pub path: PathBuf,
+    pub addr: u32,
+    pub offset: Option<u32>,
+    pub org: Option<RomOrganization>,
+}
+
+/// The address range a ROM image occupies, and the byte lane it fills if it is one half of an
+/// interleaved pair.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct RomSpan {
+    start: usize,
+    end:   usize,
+    lane:  Option<usize>,
+}
+
+/// Return the indices of the first two ROMs whose spans overlap. The even and odd halves of an
+/// interleaved pair occupy the same span but fill different byte lanes, so they do not overlap
+/// each other.
+fn find_rom_overlap(spans: &[RomSpan]) -> Option<(usize, usize)> {
+    for (i, span) in spans.iter().enumerate() {
+        for (j, other) in spans.iter().enumerate().skip(i + 1) {
+            let pair = span.start == other.start
+                && span.end == other.end
+                && matches!((span.lane, other.lane), (Some(a), Some(b)) if a != b);
+            if !pair && span.start < other.end && other.start < span.end {
+                return Some((i, j));
+            }
+        }
+    }
+    None
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct RomPatch {
     desc:    String,
@@ -153,9 +193,12 @@ pub struct RomManager {
     rom_candidate_name_map: NameMap,      // Rom names resolve to md5sums
     rom_paths: HashMap<String, PathBuf>,  // Rom paths are hashed by md5sum
     rom_images: HashMap<String, Vec<u8>>, // Rom images are hashed by md5sum
+    // Raw rom images are hashed by the path they stand in for
+    raw_rom_images: HashMap<PathBuf, Vec<u8>>,
     features_available: Vec<String>,
     features_required: Vec<String>,
     rom_override: Option<String>, // Rom override forces a specific rom set alias to be loaded
+    rom_dirs: Vec<PathBuf>,       // Additional directories to scan for ROMs, not recursed
 
     checkpoints_active: HashMap<u32, RomCheckpoint>,
     patches_active: HashMap<u32, RomPatch>,
@@ -177,9 +220,11 @@ impl Default for RomManager {
             rom_candidate_name_map: HashMap::new(),
             rom_paths: HashMap::new(),
             rom_images: HashMap::new(), // Rom images can be stored by name or md5 hash.
+            raw_rom_images: HashMap::new(),
             features_available: Vec::new(),
             features_required: Vec::new(),
             rom_override: None, // Rom override forces a specific rom set alias to be loaded
+            rom_dirs: Vec::new(),
 
             checkpoints_active: HashMap::new(),
             patches_active: HashMap::new(),
@@ -195,6 +240,18 @@ impl RomManager {
         }
     }
 
+    /// Scan 'dir' for ROMs in addition to the 'rom' resource paths. If 'dir' has a subdirectory
+    /// named for 'machine_type', such as 'ibm5160', it is scanned as well, but no other
+    /// subdirectories are. This lets ROMs for different machines be kept apart.
+    pub fn set_rom_dir(&mut self, dir: PathBuf, machine_type: MachineType) {
+        self.rom_dirs.clear();
+        if let Some(subdir) = machine_rom_subdir(&dir, machine_type) {
+            log::debug!("Using machine ROM directory: {:?}", subdir);
+            self.rom_dirs.push(subdir);
+        }
+        self.rom_dirs.insert(0, dir);
+    }
+
     pub fn load_defs(&mut self, rm: &ResourceManager) -> Result<(), Error> {
         let mut rom_defs: Vec<RomSetDefinition> = Vec::new();
 
@@ -419,18 +476,46 @@ impl RomManager {
         Ok(())
     }
 
+    /// Return the files in each of 'dirs'. A directory that can't be read is skipped with a
+    /// warning, so that a missing rom_dir doesn't prevent the 'rom' resource paths from loading.
+    fn rom_dir_files(dirs: &[PathBuf]) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for dir in dirs {
+            match std::fs::read_dir(dir) {
+                Ok(entries) => {
+                    files.extend(entries.filter_map(|e| e.ok().map(|e| e.path())).filter(|p| p.is_file()));
+                }
+                Err(e) => log::warn!("Skipping ROM directory {:?}: {}", dir, e),
+            }
+        }
+        files
+    }
+
     pub fn scan(&mut self, rm: &ResourceManager) -> Result<(), Error> {
-        let roms = rm.enumerate_items("rom", true, true, None)?;
+        let mut rom_paths: Vec<PathBuf> = rm
+            .enumerate_items("rom", true, true, None)?
+            .into_iter()
+            .map(|item| item.full_path)
+            .collect();
+
+        // Add the files in any configured ROM directories.
+        rom_paths.extend(RomManager::rom_dir_files(&self.rom_dirs));
 
-        // Clear the list of ROM candidates so we can rebuild it
+        // Clear the list of ROM candidates so we can rebuild it. ROM images added directly don't
+        // exist on disk, so carry them over.
         self.rom_candidates.clear();
+        self.rom_candidate_name_map.clear();
+        for (md5, image) in self.rom_images.iter() {
+            self.rom_candidates
+                .insert(md5.clone(), RomManager::image_candidate(md5, image.len()));
+        }
 
-        for rom_item in roms {
+        for rom_path in rom_paths {
             let mut new_candidate: RomFileCandidate = Default::default();
-            let file_vec = match std::fs::read(rom_item.full_path.clone()) {
+            let file_vec = match std::fs::read(&rom_path) {
                 Ok(vec) => vec,
                 Err(e) => {
-                    eprintln!("Error opening filename {:?}: {}", &rom_item.full_path, e);
+                    eprintln!("Error opening filename {:?}: {}", &rom_path, e);
                     continue;
                 }
             };
@@ -444,16 +529,15 @@ impl RomManager {
             new_candidate.size = file_vec.len();
 
             // Store the path and filename
-            new_candidate.path = rom_item.full_path.clone();
-            new_candidate.filename = rom_item
-                .filename_only
-                .clone()
+            new_candidate.filename = rom_path
+                .file_name()
+                .and_then(|f| f.to_str())
                 .unwrap_or_default()
-                .into_string()
-                .unwrap_or_default();
+                .to_string();
+            new_candidate.path = rom_path;
 
             if new_candidate.filename.len() == 0 {
-                eprintln!("Error: Non-UTF8 filename for {:?}", &rom_item.full_path);
+                eprintln!("Error: Non-UTF8 filename for {:?}", &new_candidate.path);
                 continue;
             }
 
@@ -580,6 +664,7 @@ impl RomManager {
 
             // Check if this chip has already been resolved. If it has, we can skip this ROM.
             if chip_set.contains(&rom.chip.clone().unwrap()) {
+                rom.present = false;
                 continue;
             }
 
@@ -598,18 +683,17 @@ impl RomManager {
             }
         }
 
-        // Drop any ROMs that are not present.
-        set.rom.retain(|rom| rom.present);
-
-        // If no ROMs are left in set, set is invalid.
-        if set.rom.is_empty() {
+        // ROMs that are not present are skipped when the manifest is created. They are kept in the
+        // definition so that the set can be resolved again if more ROMs are added later.
+        // If no ROMs are present, set is invalid.
+        if !set.rom.iter().any(|rom| rom.present) {
             return Err(anyhow::anyhow!("ROM set {} is invalid: no ROMs found.", set.alias));
         }
 
         // Add ROMs to a HashMap of ROMs by chip, on first-come first-serve basis. The first ROM
         // that satisfies a chip will be used.
         let mut chip_map: HashMap<String, RomDescriptor> = HashMap::new();
-        for rom in set.rom.iter() {
+        for rom in set.rom.iter().filter(|rom| rom.present) {
             let chip = rom.chip.clone().unwrap();
             chip_map.entry(chip).or_insert(rom.clone());
         }
@@ -660,6 +744,14 @@ impl RomManager {
         let mut requested_features = required.clone();
         requested_features.append(&mut optional.clone());
 
+        // Remember the requested features so that the ROM sets that can replace them may be listed.
+        self.features_required.clear();
+        for feature in requested_features.iter() {
+            if !self.features_required.contains(feature) {
+                self.features_required.push(feature.clone());
+            }
+        }
+
         for feature in requested_features.iter() {
             log::debug!(
                 "Features resolved: [{:?}] Resolving feature: {}...",
@@ -782,6 +874,8 @@ impl RomManager {
         rm: &ResourceManager,
     ) -> Result<MachineRomManifest, Error> {
         let mut new_manifest = MachineRomManifest::new();
+        let mut halves = Vec::new();
+        self.checkpoints_active.clear();
 
         for rom_set in rom_set_list.iter() {
             // Retrieve the rom set definition for this rom set name
@@ -791,73 +885,42 @@ impl RomManager {
                 .ok_or(anyhow::anyhow!("Rom set {} not found in rom set map.", rom_set))?;
 
             let rom_set_def = &self.rom_defs[*rom_set_idx];
-            if rom_set_def.rom.is_empty() {
+            if !rom_set_def.rom.iter().any(|rom| rom.present) {
                 return Err(anyhow::anyhow!("Rom set {} has no roms.", rom_set));
             }
 
             // Iterate over the roms in the rom set definition, load them from disk and add them to the manifest.
-            for rom_desc in rom_set_def.rom.iter() {
+            for rom_desc in rom_set_def.rom.iter().filter(|rom| rom.present) {
                 let rom_md5 = rom_desc.md5.clone().unwrap();
                 let rom_file = self.rom_candidates.get(&rom_md5).ok_or_else(|| {
                     anyhow::anyhow!("Rom {} not found in candidate list.", rom_desc.md5.as_ref().unwrap())
                 })?;
 
-                let mut rom_vec = rm.read_resource_from_path(&rom_file.path)?;
-
-                // Handle rom organization
-                // TODO: Interleaved organizations... double rom size and then interleave?
-                //log::trace!("create_manifest(): ROM organization is {:?}", rom_desc.org);
-                match rom_desc.org {
-                    None | Some(RomOrganization::Normal) => {
-                        let mut offset_len = 0;
-                        // Shorten ROM by dropping the first 'offset' bytes
-                        if let Some(offset) = rom_desc.offset {
-                            rom_vec = rom_vec[offset as usize..].to_vec();
-                            offset_len = offset as usize;
-                        }
-
-                        // Truncate to 'size' if specified
-                        if let Some(size) = rom_desc.size {
-                            rom_vec.truncate(size as usize - offset_len);
-                        }
-
-                        new_manifest.roms.push(MachineRomEntry {
-                            md5:  rom_desc.md5.clone().unwrap(),
-                            addr: rom_desc.addr,
-                            data: rom_vec,
-                        });
-                        new_manifest.rom_paths.push(rom_file.path.clone());
-                    }
-                    Some(RomOrganization::Reversed) => {
-                        rom_vec.reverse();
-
-                        let mut offset_len = 0;
-                        // Shorten ROM by dropping the first 'offset' bytes
-                        if let Some(offset) = rom_desc.offset {
-                            rom_vec = rom_vec[offset as usize..].to_vec();
-                            offset_len = offset as usize;
-                        }
-
-                        // Truncate to 'size' if specified
-                        if let Some(size) = rom_desc.size {
-                            rom_vec.truncate(size as usize - offset_len);
-                        }
-
-                        new_manifest.roms.push(MachineRomEntry {
-                            md5:  rom_desc.md5.clone().unwrap(),
-                            addr: rom_desc.addr,
-                            data: rom_vec,
-                        });
-                        new_manifest.rom_paths.push(rom_file.path.clone());
-                    }
-                    _ => {
-                        return Err(anyhow::anyhow!(
-                            "ROM organization '{:?}' not implemented for ROM {}.",
-                            rom_desc.org,
-                            rom_desc.md5.as_ref().unwrap()
-                        ))
-                    }
-                }
+                let rom_vec = match self.rom_images.get(&rom_md5) {
+                    Some(image) => image.clone(),
+                    None => rm.read_resource_from_path(&rom_file.path)?,
+                };
+                let rom_vec = RomManager::organize_rom(rom_vec, &rom_desc.org, rom_desc.offset, rom_desc.size)
+                    .map_err(|e| anyhow::anyhow!("{} for ROM {}.", e, rom_desc.md5.as_ref().unwrap()))?;
+
+                RomManager::add_rom_entry(
+                    &mut new_manifest,
+                    MachineRomEntry {
+                        md5:  rom_desc.md5.clone().unwrap(),
+                        addr: rom_desc.addr,
+                        data: rom_vec,
+                    },
+                    rom_file.path.clone(),
+                    &rom_desc.org,
+                    &mut halves,
+                );
+            }
+            for (idx, _) in halves.drain(..) {
+                log::warn!(
+                    "Interleaved ROM {:?} in rom set {} has no matching half.",
+                    new_manifest.rom_paths[idx],
+                    rom_set
+                );
             }
 
             // Add checkpoints to manifest
@@ -869,6 +932,7 @@ impl RomManager {
                         desc: checkpoint.desc.clone(),
                     };
                     new_manifest.checkpoints.push(new_checkpoint);
+                    self.checkpoints_active.insert(checkpoint.addr, checkpoint.clone());
                 }
             }
 
@@ -889,4 +953,678 @@ impl RomManager {
 
         Ok(new_manifest)
     }
+
+    /// Return the complete ROM sets that provide each feature requested by the last call to
+    /// resolve_requirements(), highest priority first. Features with no complete ROM sets are
+    /// omitted. Any of the aliases returned may be passed to select_rom_set().
+    pub fn complete_rom_sets_by_feature(&self) -> Vec<(String, Vec<String>)> {
+        self.features_required
+            .iter()
+            .filter_map(|feature| {
+                let rom_sets: Vec<String> = self
+                    .rom_sets_by_feature
+                    .get(feature)?
+                    .iter()
+                    .filter(|rom_set| self.rom_sets_complete.contains(*rom_set))
+                    .cloned()
+                    .collect();
+                (!rom_sets.is_empty()).then(|| (feature.clone(), rom_sets))
+            })
+            .collect()
+    }
+
+    /// Return the alias of the ROM set last chosen with select_rom_set(), if any.
+    pub fn active_rom_set(&self) -> Option<&str> {
+        self.rom_set_active.as_deref()
+    }
+
+    /// Select the ROM set `alias` and create a new manifest for the machine. The ROM directories
+    /// are rescanned first, so that ROM files added or changed since startup are used. The
+    /// selected set replaces any set in `rom_sets` that provides one of the same features, or is
+    /// added to it if there is none. `rom_sets` and the active checkpoints are only updated if the
+    /// manifest can be created.
+    pub fn select_rom_set(
+        &mut self,
+        alias: &str,
+        rom_sets: &mut Vec<String>,
+        rm: &ResourceManager,
+    ) -> Result<MachineRomManifest, Error> {
+        self.scan(rm)?;
+        self.resolve_rom_sets()?;
+        if !self.rom_sets_complete.contains(alias) {
+            return Err(anyhow::anyhow!("Rom set {} is not complete.", alias));
+        }
+
+        let selected_idx = *self
+            .rom_def_map
+            .get(alias)
+            .ok_or(anyhow::anyhow!("Rom set {} not found in rom set map.", alias))?;
+        let provides = &self.rom_defs[selected_idx].provides;
+
+        let mut new_sets: Vec<String> = rom_sets
+            .iter()
+            .filter(|rom_set| {
+                self.rom_def_map.get(*rom_set).map_or(true, |idx| {
+                    !self.rom_defs[*idx]
+                        .provides
+                        .iter()
+                        .any(|feature| provides.contains(feature))
+                })
+            })
+            .cloned()
+            .collect();
+        // Keep the selected set in the position of the set it replaces, so that BIOS sets stay first.
+        let position = rom_sets
+            .iter()
+            .position(|rom_set| !new_sets.contains(rom_set))
+            .unwrap_or(new_sets.len());
+        new_sets.insert(position, alias.to_string());
+
+        // create_manifest() repopulates the active checkpoints, so restore them if it fails.
+        let checkpoints = self.checkpoints_active.clone();
+        let manifest = match self.create_manifest(new_sets.clone(), rm) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                self.checkpoints_active = checkpoints;
+                return Err(e);
+            }
+        };
+        log::debug!("Selected ROM set {}: using ROM sets {:?}", alias, new_sets);
+        self.rom_set_active = Some(alias.to_string());
+        *rom_sets = new_sets;
+        Ok(manifest)
+    }
+
+    /// Return the byte lane an interleaved ROM organization fills, or None if the ROM is not
+    /// interleaved.
+    fn interleave_lane(org: &Option<RomOrganization>) -> Option<usize> {
+        match org {
+            Some(RomOrganization::InterleavedEven) => Some(0),
+            Some(RomOrganization::InterleavedOdd) => Some(1),
+            _ => None,
+        }
+    }
+
+    /// Apply a ROM's organization to an image as read from disk, then drop the first `offset`
+    /// bytes and truncate it to `size`, if specified. An interleaved image is one chip of a pair;
+    /// offset and size apply to the chip, which is then spread across the even or odd bytes of an
+    /// image twice its size. The other half is woven in by add_rom_entry().
+    fn organize_rom(
+        mut rom_vec: Vec<u8>,
+        org: &Option<RomOrganization>,
+        offset: Option<u32>,
+        size: Option<u32>,
+    ) -> Result<Vec<u8>, Error> {
+        //log::trace!("organize_rom(): ROM organization is {:?}", org);
+        if let Some(RomOrganization::Reversed) = org {
+            rom_vec.reverse();
+        }
+
+        let mut offset_len = 0;
+        // Shorten ROM by dropping the first 'offset' bytes
+        if let Some(offset) = offset {
+            offset_len = (offset as usize).min(rom_vec.len());
+            rom_vec = rom_vec[offset_len..].to_vec();
+        }
+
+        // Truncate to 'size' if specified
+        if let Some(size) = size {
+            rom_vec.truncate((size as usize).saturating_sub(offset_len));
+        }
+
+        if let Some(lane) = RomManager::interleave_lane(org) {
+            let mut woven = vec![0; rom_vec.len() * 2];
+            for (i, byte) in rom_vec.into_iter().enumerate() {
+                woven[i * 2 + lane] = byte;
+            }
+            return Ok(woven);
+        }
+        Ok(rom_vec)
+    }
+
+    /// Add an organized ROM image to the manifest. The second half of an interleaved pair is
+    /// woven into the first half's entry instead of being added separately. 'halves' tracks the
+    /// manifest entries still waiting for their other half, and the lane each one fills.
+    fn add_rom_entry(
+        manifest: &mut MachineRomManifest,
+        entry: MachineRomEntry,
+        path: PathBuf,
+        org: &Option<RomOrganization>,
+        halves: &mut Vec<(usize, usize)>,
+    ) {
+        if let Some(lane) = RomManager::interleave_lane(org) {
+            let pair = halves.iter().position(|&(idx, other_lane)| {
+                let rom = &manifest.roms[idx];
+                other_lane != lane && rom.addr == entry.addr && rom.data.len() == entry.data.len()
+            });
+            if let Some(pos) = pair {
+                let (idx, _) = halves.remove(pos);
+                let data = &mut manifest.roms[idx].data;
+                for i in (lane..data.len()).step_by(2) {
+                    data[i] = entry.data[i];
+                }
+                return;
+            }
+            halves.push((manifest.roms.len(), lane));
+        }
+        manifest.roms.push(entry);
+        manifest.rom_paths.push(path);
+    }
+
+    /// Add a ROM image from memory, such as one embedded in the binary with include_bytes!(), so
+    /// that it does not need to be found on disk. The image must match 'md5' and be referenced by a
+    /// loaded ROM set definition. Images added this way survive rescans. ROM sets are resolved
+    /// again afterward, so a set completed by this image becomes available to create_manifest().
+    pub fn add_rom_image(&mut self, md5: &str, bytes: Vec<u8>) -> Result<(), Error> {
+        let md5 = md5.to_lowercase();
+        let digest_str = format!("{:x}", md5::compute(&bytes));
+        if digest_str != md5 {
+            return Err(anyhow::anyhow!(
+                "ROM image hash {} does not match expected hash {}.",
+                digest_str,
+                md5
+            ));
+        }
+
+        if self.rom_defs.is_empty() {
+            return Err(anyhow::anyhow!("No ROM set definitions have been loaded."));
+        }
+        let defined = self
+            .rom_defs
+            .iter()
+            .any(|def| def.rom.iter().any(|rom| rom.md5.as_deref() == Some(md5.as_str())));
+        if !defined {
+            return Err(anyhow::anyhow!("ROM {} is not defined by any ROM set.", md5));
+        }
+
+        log::debug!("Adding ROM image {} from memory, {} bytes", md5, bytes.len());
+        self.rom_candidates
+            .insert(md5.clone(), RomManager::image_candidate(&md5, bytes.len()));
+        self.rom_images.insert(md5, bytes);
+
+        self.resolve_rom_sets()
+    }
+
+    fn image_candidate(md5: &str, size: usize) -> RomFileCandidate {
+        RomFileCandidate {
+            filename: format!("<image {}>", md5),
+            path: PathBuf::new(),
+            md5: md5.to_string(),
+            size,
+        }
+    }
+
+    /// Supply the contents of a raw ROM from memory, such as one fetched over the network. A raw
+    /// ROM descriptor with the same path then uses this image in add_raw_roms() instead of
+    /// reading the path from disk.
+    pub fn add_raw_rom_image(&mut self, path: PathBuf, bytes: Vec<u8>) {
+        log::debug!("Adding raw ROM image {:?} from memory, {} bytes", path, bytes.len());
+        self.raw_rom_images.insert(path, bytes);
+    }
+
+    /// Load a raw ROM image and apply its organization.
+    fn load_raw_rom(&self, rom_desc: &RawRomDescriptor, rm: &ResourceManager) -> Result<Vec<u8>, Error> {
+        let rom_vec = match self.raw_rom_images.get(&rom_desc.path) {
+            Some(image) => image.clone(),
+            None => rm.read_resource_from_path(&rom_desc.path)?,
+        };
+        let rom_vec = RomManager::organize_rom(rom_vec, &rom_desc.org, rom_desc.offset, None)
+            .map_err(|e| anyhow::anyhow!("{} for raw ROM {:?}.", e, rom_desc.path))?;
+        if rom_vec.is_empty() {
+            return Err(anyhow::anyhow!("Raw ROM {:?} is empty.", rom_desc.path));
+        }
+        Ok(rom_vec)
+    }
+
+    /// Load a list of raw ROM images and add them to the manifest at the addresses given by their
+    /// descriptors. All images are loaded before any is added, so an error leaves the manifest
+    /// unchanged. Raw ROMs that overlap each other are rejected, as it would be ambiguous which
+    /// one should win. A warning is logged if a raw ROM overlaps a ROM already in the manifest,
+    /// since replacing part of a ROM set is a legitimate use.
+    pub fn add_raw_roms(
+        &self,
+        manifest: &mut MachineRomManifest,
+        rom_descs: &[RawRomDescriptor],
+        rm: &ResourceManager,
+    ) -> Result<(), Error> {
+        let mut raw_roms = Vec::with_capacity(rom_descs.len());
+        for rom_desc in rom_descs {
+            raw_roms.push((rom_desc, self.load_raw_rom(rom_desc, rm)?));
+        }
+
+        let spans: Vec<RomSpan> = raw_roms
+            .iter()
+            .map(|(rom_desc, rom_vec)| RomSpan {
+                start: rom_desc.addr as usize,
+                end:   rom_desc.addr as usize + rom_vec.len(),
+                lane:  RomManager::interleave_lane(&rom_desc.org),
+            })
+            .collect();
+        if let Some((i, j)) = find_rom_overlap(&spans) {
+            return Err(anyhow::anyhow!(
+                "Raw ROM {:?} at {:05X}-{:05X} overlaps raw ROM {:?} at {:05X}-{:05X}",
+                raw_roms[i].0.path,
+                spans[i].start,
+                spans[i].end - 1,
+                raw_roms[j].0.path,
+                spans[j].start,
+                spans[j].end - 1
+            ));
+        }
+
+        for ((rom_desc, _), span) in raw_roms.iter().zip(spans.iter()) {
+            for (rom, path) in manifest.roms.iter().zip(manifest.rom_paths.iter()) {
+                let rom_start = rom.addr as usize;
+                let rom_end = rom_start + rom.data.len();
+                if span.start < rom_end && rom_start < span.end {
+                    log::warn!(
+                        "Raw ROM {:?} at {:05X}-{:05X} overlaps ROM {:?} at {:05X}-{:05X}",
+                        rom_desc.path,
+                        span.start,
+                        span.end - 1,
+                        path,
+                        rom_start,
+                        rom_end - 1
+                    );
+                }
+            }
+        }
+
+        // Weave interleaved halves into a scratch manifest first, so an unpaired half is an error
+        // that leaves the real manifest unchanged.
+        let mut raw_manifest = MachineRomManifest::new();
+        let mut halves = Vec::new();
+        for (rom_desc, rom_vec) in raw_roms {
+            log::debug!(
+                "Adding raw ROM {:?} at {:05X}, {} bytes",
+                rom_desc.path,
+                rom_desc.addr,
+                rom_vec.len()
+            );
+            RomManager::add_rom_entry(
+                &mut raw_manifest,
+                MachineRomEntry {
+                    md5:  String::new(),
+                    addr: rom_desc.addr,
+                    data: rom_vec,
+                },
+                rom_desc.path.clone(),
+                &rom_desc.org,
+                &mut halves,
+            );
+        }
+        if let Some(&(idx, _)) = halves.first() {
+            return Err(anyhow::anyhow!(
+                "Interleaved raw ROM {:?} has no matching half of the same size at {:05X}.",
+                raw_manifest.rom_paths[idx],
+                raw_manifest.roms[idx].addr
+            ));
+        }
+        manifest.roms.append(&mut raw_manifest.roms);
+        manifest.rom_paths.append(&mut raw_manifest.rom_paths);
+        Ok(())
+    }
+
+    /// Iterate over the checkpoints of the ROM sets in the last created manifest, as
+    /// (address, description) pairs sorted by address.
+    pub fn iter_checkpoints(&self) -> impl Iterator<Item = (usize, &str)> {
+        let mut checkpoints: Vec<(usize, &str)> = self
+            .checkpoints_active
+            .values()
+            .map(|checkpoint| (checkpoint.addr as usize, checkpoint.desc.as_str()))
+            .collect();
+        checkpoints.sort_by_key(|&(addr, _)| addr);
+        checkpoints.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource_manager::PathConfigItem;
+
+    fn add_half(
+        manifest: &mut MachineRomManifest,
+        halves: &mut Vec<(usize, usize)>,
+        chip: &[u8],
+        org: RomOrganization,
+    ) {
+        let org = Some(org);
+        let data = RomManager::organize_rom(chip.to_vec(), &org, None, None).unwrap();
+        let entry = MachineRomEntry {
+            md5:  String::new(),
+            addr: 0xF0000,
+            data,
+        };
+        RomManager::add_rom_entry(manifest, entry, PathBuf::from("chip.bin"), &org, halves);
+    }
+
+    #[test]
+    fn interleaved_halves_are_woven_together() {
+        let mut manifest = MachineRomManifest::new();
+        let mut halves = Vec::new();
+
+        add_half(
+            &mut manifest,
+            &mut halves,
+            &[0x00, 0x02, 0x04],
+            RomOrganization::InterleavedEven,
+        );
+        assert_eq!(manifest.roms[0].data, vec![0x00, 0x00, 0x02, 0x00, 0x04, 0x00]);
+        assert_eq!(halves, vec![(0, 0)]);
+
+        add_half(
+            &mut manifest,
+            &mut halves,
+            &[0x01, 0x03, 0x05],
+            RomOrganization::InterleavedOdd,
+        );
+        assert_eq!(manifest.roms.len(), 1);
+        assert_eq!(manifest.rom_paths.len(), 1);
+        assert_eq!(manifest.roms[0].data, vec![0x00, 0x01, 0x02, 0x03, 0x04, 0x05]);
+        assert!(halves.is_empty());
+    }
+
+    #[test]
+    fn interleaved_halves_of_different_sizes_are_not_paired() {
+        let mut manifest = MachineRomManifest::new();
+        let mut halves = Vec::new();
+
+        add_half(
+            &mut manifest,
+            &mut halves,
+            &[0x00, 0x02],
+            RomOrganization::InterleavedEven,
+        );
+        add_half(
+            &mut manifest,
+            &mut halves,
+            &[0x01, 0x03, 0x05],
+            RomOrganization::InterleavedOdd,
+        );
+        assert_eq!(manifest.roms.len(), 2);
+        assert_eq!(halves.len(), 2);
+    }
+
+    #[test]
+    fn missing_rom_dir_is_skipped() {
+        let dir = std::env::temp_dir().join(format!("martypc_rom_dir_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("bios.bin"), [0xEA]).unwrap();
+
+        let files = RomManager::rom_dir_files(&[dir.join("missing"), dir.clone()]);
+        _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(files, vec![dir.join("bios.bin")]);
+    }
+
+    /// Create a ResourceManager with 'dir' as its ROM path, and a RomManager that has loaded and
+    /// resolved the ROM sets found there.
+    fn test_rom_manager(dir: &PathBuf) -> (ResourceManager, RomManager) {
+        let rm = ResourceManager::from_config(
+            dir.clone(),
+            &[PathConfigItem {
+                resource: "rom".to_string(),
+                path: dir.to_str().unwrap().to_string(),
+                create: false,
+                recurse: true,
+            }],
+        )
+        .unwrap();
+        let mut romm = RomManager::new(false);
+        romm.load_defs(&rm).unwrap();
+        romm.scan(&rm).unwrap();
+        romm.resolve_rom_sets().unwrap();
+        (rm, romm)
+    }
+
+    #[test]
+    fn checkpoints_follow_the_last_manifest() {
+        let dir = std::env::temp_dir().join(format!("martypc_checkpoints_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("romdef.toml"),
+            r#"
+            [[romset]]
+            alias = "bios"
+            priority = 1
+            provides = ["bios"]
+            rom = [{ filename = "bios.bin", addr = 0xFE000 }]
+            checkpoint = [
+                { addr = 0xFE0AE, lvl = 0, desc = "POST start" },
+                { addr = 0xFE05B, lvl = 0, desc = "Reset" },
+            ]
+
+            [[romset]]
+            alias = "basic"
+            priority = 1
+            provides = ["basic"]
+            rom = [{ filename = "basic.bin", addr = 0xF6000 }]
+            checkpoint = [{ addr = 0xF6000, lvl = 1, desc = "BASIC" }]
+            "#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("bios.bin"), [0xAA; 16]).unwrap();
+        std::fs::write(dir.join("basic.bin"), [0xBB; 16]).unwrap();
+        let (rm, mut romm) = test_rom_manager(&dir);
+
+        romm.create_manifest(vec!["bios".to_string(), "basic".to_string()], &rm)
+            .unwrap();
+        assert_eq!(
+            romm.iter_checkpoints().collect::<Vec<_>>(),
+            vec![(0xF6000, "BASIC"), (0xFE05B, "Reset"), (0xFE0AE, "POST start")]
+        );
+
+        // A new manifest replaces the checkpoints of the previous one.
+        romm.create_manifest(vec!["basic".to_string()], &rm).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(romm.iter_checkpoints().collect::<Vec<_>>(), vec![(0xF6000, "BASIC")]);
+    }
+
+    #[test]
+    fn select_rom_set_rescans_for_new_roms() {
+        let dir = std::env::temp_dir().join(format!("martypc_romset_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("romdef.toml"),
+            r#"
+            [[romset]]
+            alias = "bios_a"
+            priority = 1
+            provides = ["bios"]
+            rom = [{ filename = "a.bin", addr = 0xFE000 }]
+
+            [[romset]]
+            alias = "bios_b"
+            priority = 2
+            provides = ["bios"]
+            rom = [{ filename = "b.bin", addr = 0xFE000 }]
+
+            [[romset]]
+            alias = "other"
+            priority = 1
+            provides = ["other"]
+            rom = [{ filename = "a.bin", addr = 0xF0000 }]
+            "#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("a.bin"), [0xAA; 16]).unwrap();
+
+        let (rm, mut romm) = test_rom_manager(&dir);
+        let mut rom_sets = romm
+            .resolve_requirements(vec!["bios".to_string()], Vec::new(), None)
+            .unwrap();
+        assert_eq!(rom_sets, vec!["bios_a".to_string()]);
+
+        // Only complete sets for the requested features are listed.
+        assert_eq!(
+            romm.complete_rom_sets_by_feature(),
+            vec![("bios".to_string(), vec!["bios_a".to_string()])]
+        );
+        assert!(romm.select_rom_set("bios_b", &mut rom_sets, &rm).is_err());
+        assert_eq!(rom_sets, vec!["bios_a".to_string()]);
+
+        // A ROM added after startup completes its set when it is selected.
+        std::fs::write(dir.join("b.bin"), [0xBB; 16]).unwrap();
+        let manifest = romm.select_rom_set("bios_b", &mut rom_sets, &rm).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(rom_sets, vec!["bios_b".to_string()]);
+        assert_eq!(manifest.roms.len(), 1);
+        assert_eq!(manifest.roms[0].data, vec![0xBB; 16]);
+        assert_eq!(romm.active_rom_set(), Some("bios_b"));
+        assert_eq!(
+            romm.complete_rom_sets_by_feature(),
+            vec![("bios".to_string(), vec!["bios_b".to_string(), "bios_a".to_string()])]
+        );
+    }
+
+    #[test]
+    fn rom_images_complete_sets_and_survive_rescans() {
+        let dir = std::env::temp_dir().join(format!("martypc_rom_image_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("romdef.toml"),
+            r#"
+            [[romset]]
+            alias = "bios"
+            priority = 1
+            provides = ["bios"]
+            rom = [{ md5 = "d92c4c80ba31a0e76dc3d6edfa1a135e", addr = 0xFE000 }]
+            "#,
+        )
+        .unwrap();
+        // Resolving requires at least one ROM on disk.
+        std::fs::write(dir.join("other.bin"), [0xEE; 16]).unwrap();
+        let (rm, mut romm) = test_rom_manager(&dir);
+        assert!(romm.create_manifest(vec!["bios".to_string()], &rm).is_err());
+
+        // The image must match its hash and be referenced by a ROM set.
+        assert!(romm
+            .add_rom_image("d92c4c80ba31a0e76dc3d6edfa1a135e", vec![0xDD; 16])
+            .is_err());
+        assert!(romm
+            .add_rom_image("8dd3f70efa1b34bfdbd680930e0d76a1", vec![0xDD; 16])
+            .is_err());
+
+        romm.add_rom_image("D92C4C80BA31A0E76DC3D6EDFA1A135E", vec![0xCC; 16])
+            .unwrap();
+        let manifest = romm.create_manifest(vec!["bios".to_string()], &rm).unwrap();
+        assert_eq!(manifest.roms[0].addr, 0xFE000);
+        assert_eq!(manifest.roms[0].data, vec![0xCC; 16]);
+
+        romm.scan(&rm).unwrap();
+        romm.resolve_rom_sets().unwrap();
+        let manifest = romm.create_manifest(vec!["bios".to_string()], &rm).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(manifest.roms[0].data, vec![0xCC; 16]);
+    }
+
+    #[test]
+    fn raw_roms_are_added_at_their_addresses() {
+        let dir = std::env::temp_dir().join(format!("martypc_raw_roms_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("option.bin"), [0x55, 0xAA, 0x01, 0x02]).unwrap();
+        std::fs::write(dir.join("reversed.bin"), [0x01, 0x02, 0x03, 0x04]).unwrap();
+        let rm = ResourceManager::new(dir.clone());
+        let mut romm = RomManager::new(false);
+        let raw_rom = |name: &str, addr, offset, org| RawRomDescriptor {
+            path: dir.join(name),
+            addr,
+            offset,
+            org,
+        };
+
+        // A raw ROM may replace part of a ROM set, so overlapping the manifest only warns.
+        let mut manifest = MachineRomManifest::new();
+        manifest.roms.push(MachineRomEntry {
+            md5:  String::new(),
+            addr: 0xC8000,
+            data: vec![0; 0x10],
+        });
+        manifest.rom_paths.push(PathBuf::from("set.bin"));
+        romm.add_raw_roms(
+            &mut manifest,
+            &[
+                raw_rom("option.bin", 0xC8000, Some(2), None),
+                raw_rom("reversed.bin", 0xD0000, None, Some(RomOrganization::Reversed)),
+            ],
+            &rm,
+        )
+        .unwrap();
+        assert_eq!(manifest.roms.len(), 3);
+        assert_eq!(
+            (manifest.roms[1].addr, &manifest.roms[1].data),
+            (0xC8000, &vec![0x01, 0x02])
+        );
+        assert_eq!(
+            (manifest.roms[2].addr, &manifest.roms[2].data),
+            (0xD0000, &vec![0x04, 0x03, 0x02, 0x01])
+        );
+
+        // Raw ROMs that overlap each other, or can't be read, leave the manifest unchanged.
+        let result = romm.add_raw_roms(
+            &mut manifest,
+            &[
+                raw_rom("option.bin", 0xE0000, None, None),
+                raw_rom("reversed.bin", 0xE0002, None, None),
+            ],
+            &rm,
+        );
+        assert!(result.is_err());
+        let result = romm.add_raw_roms(&mut manifest, &[raw_rom("missing.bin", 0xE0000, None, None)], &rm);
+        assert!(result.is_err());
+        assert_eq!(manifest.roms.len(), 3);
+
+        // A raw ROM supplied from memory doesn't need to exist on disk.
+        romm.add_raw_rom_image(dir.join("missing.bin"), vec![0xEA; 4]);
+        romm.add_raw_roms(&mut manifest, &[raw_rom("missing.bin", 0xE0000, None, None)], &rm)
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(
+            (manifest.roms[3].addr, &manifest.roms[3].data),
+            (0xE0000, &vec![0xEA; 4])
+        );
+    }
+
+    #[test]
+    fn overlapping_roms_are_rejected() {
+        let span = |start, end, lane| RomSpan { start, end, lane };
+
+        assert_eq!(
+            find_rom_overlap(&[span(0xC8000, 0xCA000, None), span(0xCA000, 0xCC000, None)]),
+            None
+        );
+        assert_eq!(
+            find_rom_overlap(&[
+                span(0xC0000, 0xC4000, None),
+                span(0xC8000, 0xCA000, None),
+                span(0xC9000, 0xCB000, None)
+            ]),
+            Some((1, 2))
+        );
+
+        // The two halves of an interleaved pair share a span without overlapping...
+        assert_eq!(
+            find_rom_overlap(&[
+                span(0xF0000, 0x100000, Some(0)),
+                span(0xF0000, 0x100000, Some(1))
+            ]),
+            None
+        );
+        // ...but two chips filling the same lane, or a pair that only partly coincides, do overlap.
+        assert_eq!(
+            find_rom_overlap(&[
+                span(0xF0000, 0x100000, Some(0)),
+                span(0xF0000, 0x100000, Some(0))
+            ]),
+            Some((0, 1))
+        );
+        assert_eq!(
+            find_rom_overlap(&[
+                span(0xF0000, 0x100000, Some(0)),
+                span(0xF8000, 0x100000, Some(1))
+            ]),
+            Some((0, 1))
+        );
+    }
 }
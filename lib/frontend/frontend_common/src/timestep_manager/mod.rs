@@ -38,6 +38,7 @@ const UPS_CAP: u64 = 1000; // Maximum number of window manager updates per secon
 const UPS_MIN_DURATION: Duration = Duration::from_millis(1000 / UPS_CAP as u64); // Minimum duration between window manager updates
 const DEFAULT_EMU_FPS_TARGET: u32 = 60; // Default rendering FPS for the emulator
 const FRAME_HISTORY_LEN: usize = 60; // Number of frames of history to keep
+const MIN_TIME_SCALE: f64 = 0.01; // Slowest permitted time scale
 
 #[derive(Copy, Clone, Default)]
 pub struct FrameEntry {
@@ -195,6 +196,7 @@ pub struct TimestepManager {
     cpu_cycle_update_target: u32, // Number of CPU cycles to execute per emulator update
     frame_target: Duration,       // Target frame time in microseconds
     throttle_factor: f64,         // Factor to adjust CPU cycle target by to keep up with emu_render_rate
+    time_scale: f64,              // Ratio of emulated time to wall-clock time. Less than 1.0 is slow motion.
 
     frame_history: HistoryBuffer<FrameEntry>,
     perf_stats: PerfStats,
@@ -220,6 +222,7 @@ impl Default for TimestepManager {
             cpu_cycle_update_target: 1_000_000 / DEFAULT_EMU_FPS_TARGET,
             frame_target: Duration::from_micros(1_000_000 / DEFAULT_EMU_FPS_TARGET as u64),
             throttle_factor: 1.0,
+            time_scale: 1.0,
 
             frame_history: HistoryBuffer::new(FRAME_HISTORY_LEN),
             total_running_time: Duration::from_secs(0),
@@ -341,13 +344,35 @@ impl TimestepManager {
     }
 
     pub fn set_cpu_mhz(&mut self, mhz: f64) {
-        self.cpu_cycle_update_target = (mhz * 1_000_000.0 / self.emu_update_rate.get() as f64) as u32;
+        self.cpu_mhz = mhz;
+        self.update_cycle_target();
         log::info!(
             "CPU clock has changed to {:.4}Mhz, new cycle target: {}",
             mhz,
             self.cpu_cycle_update_target,
         );
-        self.cpu_mhz = mhz;
+    }
+
+    /// Set the ratio of emulated time to wall-clock time. A scale of 0.25 runs the whole machine
+    /// at quarter speed. Unlike changing the CPU clock, all devices slow down together, so the
+    /// machine remains internally consistent. The scale is clamped to (0, 1].
+    pub fn set_time_scale(&mut self, scale: f64) {
+        self.time_scale = scale.clamp(MIN_TIME_SCALE, 1.0);
+        self.update_cycle_target();
+        log::info!(
+            "Time scale has changed to {:.2}x, new cycle target: {}",
+            self.time_scale,
+            self.cpu_cycle_update_target,
+        );
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    fn update_cycle_target(&mut self) {
+        self.cpu_cycle_update_target =
+            (self.cpu_mhz * 1_000_000.0 * self.time_scale / self.emu_update_rate.get() as f64) as u32;
     }
 
     pub fn set_emu_update_rate(&mut self, fps: u32) {
@@ -38,12 +38,19 @@ use strum_macros::EnumIter;
 pub enum HotkeyEvent {
     CaptureMouse,
     CtrlAltDel,
+    PasteText,
     Reboot,
+    WarmReboot,
     Screenshot,
     ToggleGui,
     ToggleFullscreen,
+    ToggleTurbo,
+    ToggleSlowMotion,
+    TogglePerfOverlay,
+    TogglePause,
     DebugStep,
     DebugStepOver,
+    DebugStepFrame,
 }
 
 #[derive(Copy, Clone, Debug, Deserialize)]
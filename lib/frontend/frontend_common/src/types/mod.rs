@@ -34,3 +34,4 @@ pub mod display_target_dimensions;
 pub mod display_target_margins;
 pub mod gui;
 pub mod hotkeys;
+pub mod resize_debounce;
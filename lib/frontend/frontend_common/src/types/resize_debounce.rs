@@ -0,0 +1,227 @@
+/*
+   MartyPC
+   https://github.com/dbalsom/martypc
+
+   Copyright 2022-2024 Daniel Balsom
+
+   Permission is hereby granted, free of charge, to any person obtaining a
+   copy of this software and associated documentation files (the “Software”),
+   to deal in the Software without restriction, including without limitation
+   the rights to use, copy, modify, merge, publish, distribute, sublicense,
+   and/or sell copies of the Software, and to permit persons to whom the
+   Software is furnished to do so, subject to the following conditions:
+
+   The above copyright notice and this permission notice shall be included in
+   all copies or substantial portions of the Software.
+
+   THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+   IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+   FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+   AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+   LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+   FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+   DEALINGS IN THE SOFTWARE.
+
+   ---------------------------------------------------------------------------
+
+   frontend_common::types::resize_debounce.rs
+
+   Define the ResizeDebouncer type and methods.
+
+   Software frequently programs several video modes in quick succession while
+   initializing, and a card's reported resolution can pass through a number
+   of intermediate states before settling. Resizing display targets for each
+   of these reallocates buffers and can resize host windows multiple times in
+   a fraction of a second. The ResizeDebouncer holds a change back until the
+   card has reported the same resolution for a few emulated frames.
+*/
+
+use std::collections::HashMap;
+
+use marty_core::device_traits::videocard::{DisplayExtents, VideoCardId};
+
+/// The number of emulated frames a new resolution must be stable for before it is committed.
+pub const RESIZE_SETTLE_FRAMES: u64 = 2;
+/// The number of host frames after which a pending resolution is committed even if the card has
+/// not produced enough frames, such as while the machine is paused or being single-stepped.
+pub const RESIZE_SETTLE_HOST_FRAMES: u32 = 30;
+
+/// The parts of a card's display extents that determine the size of its display targets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ResizeKey {
+    apertures:   Vec<(u32, u32)>,
+    double_scan: bool,
+}
+
+impl From<&DisplayExtents> for ResizeKey {
+    fn from(extents: &DisplayExtents) -> Self {
+        Self {
+            apertures:   extents.apertures.iter().map(|a| (a.w, a.h)).collect(),
+            double_scan: extents.double_scan,
+        }
+    }
+}
+
+struct PendingResize {
+    key: ResizeKey,
+    first_frame: u64,
+    host_frames: u32,
+}
+
+#[derive(Default)]
+pub struct ResizeDebouncer {
+    committed: HashMap<VideoCardId, ResizeKey>,
+    pending:   HashMap<VideoCardId, PendingResize>,
+}
+
+impl ResizeDebouncer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Report a card's current display extents along with its current frame count. This should be
+    /// called once per host frame for each card. Returns true if the frontend should process the
+    /// card's extents now, or false if a resolution change is still settling.
+    ///
+    /// The first report for a card, and any report matching the last committed resolution, always
+    /// returns true. A change that reverts to the committed resolution before settling is dropped.
+    pub fn update(&mut self, vid: &VideoCardId, extents: &DisplayExtents, frame_count: u64) -> bool {
+        let key = ResizeKey::from(extents);
+
+        match self.committed.get(vid) {
+            Some(committed) if *committed == key => {
+                self.pending.remove(vid);
+                return true;
+            }
+            Some(_) => {}
+            None => {
+                self.committed.insert(*vid, key);
+                return true;
+            }
+        }
+
+        match self.pending.get_mut(vid) {
+            Some(pending) if pending.key == key => {
+                pending.host_frames += 1;
+                if frame_count.wrapping_sub(pending.first_frame) >= RESIZE_SETTLE_FRAMES
+                    || pending.host_frames >= RESIZE_SETTLE_HOST_FRAMES
+                {
+                    log::debug!(
+                        "Committing resolution change for card {:?} after {} host frames",
+                        vid,
+                        pending.host_frames
+                    );
+                    self.pending.remove(vid);
+                    self.committed.insert(*vid, key);
+                    return true;
+                }
+            }
+            _ => {
+                self.pending.insert(
+                    *vid,
+                    PendingResize {
+                        key,
+                        first_frame: frame_count,
+                        host_frames: 0,
+                    },
+                );
+            }
+        }
+        false
+    }
+
+    /// Forget the state of all cards, so that the next report for each card is committed
+    /// immediately. This should be called when the machine is reset or replaced.
+    pub fn reset(&mut self) {
+        self.committed.clear();
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use marty_core::device_traits::videocard::{DisplayAperture, VideoType};
+
+    const CARD: VideoCardId = VideoCardId {
+        idx:   0,
+        vtype: VideoType::CGA,
+    };
+
+    fn extents(w: u32, h: u32) -> DisplayExtents {
+        DisplayExtents {
+            apertures: vec![DisplayAperture {
+                w,
+                h,
+                x: 0,
+                y: 0,
+                debug: false,
+            }],
+            field_w: 912,
+            field_h: 262,
+            row_stride: 912,
+            double_scan: false,
+            mode_byte: 0,
+            border_color: 0,
+        }
+    }
+
+    #[test]
+    fn first_report_commits_immediately() {
+        let mut debouncer = ResizeDebouncer::new();
+        assert!(debouncer.update(&CARD, &extents(640, 200), 0));
+        assert!(debouncer.update(&CARD, &extents(640, 200), 1));
+    }
+
+    #[test]
+    fn change_commits_after_settle_frames() {
+        let mut debouncer = ResizeDebouncer::new();
+        assert!(debouncer.update(&CARD, &extents(640, 200), 0));
+
+        // One emulated frame per host frame. The new resolution must hold for RESIZE_SETTLE_FRAMES
+        // emulated frames before it is committed.
+        for frame in 1..1 + RESIZE_SETTLE_FRAMES {
+            assert!(!debouncer.update(&CARD, &extents(320, 200), frame));
+        }
+        assert!(debouncer.update(&CARD, &extents(320, 200), 1 + RESIZE_SETTLE_FRAMES));
+        assert!(debouncer.update(&CARD, &extents(320, 200), 2 + RESIZE_SETTLE_FRAMES));
+    }
+
+    #[test]
+    fn reverting_drops_pending_change() {
+        let mut debouncer = ResizeDebouncer::new();
+        assert!(debouncer.update(&CARD, &extents(640, 200), 0));
+        assert!(!debouncer.update(&CARD, &extents(320, 200), 1));
+        assert!(debouncer.update(&CARD, &extents(640, 200), 2));
+
+        // The change starts settling over again rather than resuming from frame 1.
+        let frame = 2 + RESIZE_SETTLE_FRAMES;
+        assert!(!debouncer.update(&CARD, &extents(320, 200), frame));
+        assert!(!debouncer.update(&CARD, &extents(320, 200), frame + 1));
+    }
+
+    #[test]
+    fn host_frames_commit_while_paused() {
+        let mut debouncer = ResizeDebouncer::new();
+        assert!(debouncer.update(&CARD, &extents(640, 200), 10));
+
+        // The frame count doesn't advance while the machine is paused.
+        assert!(!debouncer.update(&CARD, &extents(720, 350), 10));
+        for _ in 1..RESIZE_SETTLE_HOST_FRAMES {
+            assert!(!debouncer.update(&CARD, &extents(720, 350), 10));
+        }
+        assert!(debouncer.update(&CARD, &extents(720, 350), 10));
+        assert!(debouncer.update(&CARD, &extents(720, 350), 10));
+    }
+
+    #[test]
+    fn reset_clears_state() {
+        let mut debouncer = ResizeDebouncer::new();
+        assert!(debouncer.update(&CARD, &extents(640, 200), 0));
+        assert!(!debouncer.update(&CARD, &extents(320, 200), 1));
+
+        debouncer.reset();
+        assert!(debouncer.update(&CARD, &extents(320, 200), 1));
+        assert!(!debouncer.update(&CARD, &extents(640, 200), 1));
+    }
+}
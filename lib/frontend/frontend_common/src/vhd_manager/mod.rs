@@ -242,7 +242,7 @@ impl VhdManager {
         Err(VhdManagerError::FileNotFound)
     }
 
-    pub fn find_first_name(&mut self, name: OsString) -> Option<PathBuf> {
+    pub fn find_first_name(&self, name: OsString) -> Option<PathBuf> {
         for path in self.image_map.keys() {
             if let Some(filename) = path.file_name() {
                 if filename == name {
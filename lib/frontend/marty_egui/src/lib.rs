@@ -98,6 +98,7 @@ pub enum GuiWindow {
     VHDCreator,
     CycleTraceViewer,
     TextModeViewer,
+    LogViewer,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -121,6 +122,9 @@ pub enum GuiBoolean {
     TurboButton,
     ShowBackBuffer,
     ShowRasterPosition,
+    ShowSplitFrame,
+    ShowPerfOverlay,
+    LightPen,
 }
 
 // Enums are hashed with a tuple of GuiEnumContext and their base discriminant.
@@ -146,6 +150,7 @@ pub enum GuiEnum {
     DisplayScalerMode(ScalerMode),
     DisplayScalerPreset(String),
     DisplayComposite(bool),
+    DisplayOverscan(bool),
     SerialPortBridge(usize),
 }
 
@@ -156,6 +161,7 @@ fn create_default_variant(ge: GuiEnum) -> GuiEnum {
         GuiEnum::DisplayScalerMode(_) => GuiEnum::DisplayAperture(Default::default()),
         GuiEnum::DisplayScalerPreset(_) => GuiEnum::DisplayScalerPreset(String::new()),
         GuiEnum::DisplayComposite(_) => GuiEnum::DisplayComposite(Default::default()),
+        GuiEnum::DisplayOverscan(_) => GuiEnum::DisplayOverscan(Default::default()),
         GuiEnum::SerialPortBridge(_) => GuiEnum::SerialPortBridge(Default::default()),
     }
 }
@@ -192,7 +198,10 @@ pub enum GuiEvent {
     TriggerParity,
     RescanMediaFolders,
     CtrlAltDel,
+    PasteText,
+    WarmReset,
     ZoomChanged(f32),
+    SelectRomSet(String),
 }
 
 pub enum DeviceSelection {
@@ -472,6 +481,16 @@ lazy_static! {
                 resizable: false,
             },
         ),
+        (
+            GuiWindow::LogViewer,
+            WorkspaceWindowDef {
+                id: GuiWindow::LogViewer,
+                title: "Log Viewer",
+                menu: "Log",
+                width: 600.0,
+                resizable: true,
+            },
+        ),
     ]
     .into();
 }
@@ -100,6 +100,23 @@ impl GuiState {
                     }
                 });
 
+                ui.add_enabled_ui(!self.rom_sets.is_empty(), |ui| {
+                    ui.menu_button("ROM Set", |ui| {
+                        // Only one ROM set may provide a feature, so each feature is its own group.
+                        for (feature, rom_sets) in self.rom_sets.iter() {
+                            ui.menu_button(feature.as_str(), |ui| {
+                                for rom_set in rom_sets.iter() {
+                                    let selected = self.rom_sets_active.contains(rom_set);
+                                    if ui.radio(selected, rom_set.as_str()).clicked() {
+                                        self.event_queue.send(GuiEvent::SelectRomSet(rom_set.clone()));
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                        }
+                    });
+                });
+
                 ui.separator();
 
                 let (is_on, is_paused) = match self.machine_state {
@@ -129,6 +146,20 @@ impl GuiState {
                     ui.close_menu();
                 }
 
+                if ui
+                    .checkbox(&mut self.get_option_mut(GuiBoolean::LightPen), "Mouse as Light Pen")
+                    .on_hover_text("Point with the mouse and hold the left button to trigger the light pen")
+                    .clicked()
+                {
+                    let new_opt = self.get_option(GuiBoolean::LightPen).unwrap();
+
+                    self.event_queue.send(GuiEvent::VariableChanged(
+                        GuiVariableContext::Global,
+                        GuiVariable::Bool(GuiBoolean::LightPen, new_opt),
+                    ));
+                    ui.close_menu();
+                }
+
                 ui.add_enabled_ui(is_on && !is_paused, |ui| {
                     if ui.button("⏸ Pause").clicked() {
                         self.event_queue
@@ -153,6 +184,13 @@ impl GuiState {
                     }
                 });
 
+                ui.add_enabled_ui(is_on, |ui| {
+                    if ui.button("⟲ Warm reset").clicked() {
+                        self.event_queue.send(GuiEvent::WarmReset);
+                        ui.close_menu();
+                    }
+                });
+
                 ui.add_enabled_ui(is_on, |ui| {
                     if ui.button("⟲ CTRL-ALT-DEL").clicked() {
                         self.event_queue.send(GuiEvent::CtrlAltDel);
@@ -160,6 +198,13 @@ impl GuiState {
                     }
                 });
 
+                ui.add_enabled_ui(is_on, |ui| {
+                    if ui.button("📋 Paste Text").clicked() {
+                        self.event_queue.send(GuiEvent::PasteText);
+                        ui.close_menu();
+                    }
+                });
+
                 ui.add_enabled_ui(is_on, |ui| {
                     if ui.button("🔌 Power off").clicked() {
                         self.event_queue.send(GuiEvent::MachineStateChange(MachineState::Off));
@@ -280,6 +325,7 @@ impl GuiState {
                     self.workspace_window_open_button(ui, GuiWindow::CycleTraceViewer, true);
                     self.workspace_window_open_button(ui, GuiWindow::CallStack, true);
                     self.workspace_window_open_button(ui, GuiWindow::DisassemblyViewer, true);
+                    self.workspace_window_open_button(ui, GuiWindow::LogViewer, true);
                 });
 
                 ui.menu_button("Memory", |ui| {
@@ -360,6 +406,21 @@ impl GuiState {
                     ));
                 }
 
+                if ui
+                    .checkbox(
+                        &mut self.get_option_mut(GuiBoolean::ShowSplitFrame),
+                        "Show Last Frame Below Beam",
+                    )
+                    .clicked()
+                {
+                    let new_opt = self.get_option(GuiBoolean::ShowSplitFrame).unwrap();
+
+                    self.event_queue.send(GuiEvent::VariableChanged(
+                        GuiVariableContext::Global,
+                        GuiVariable::Bool(GuiBoolean::ShowSplitFrame, new_opt),
+                    ));
+                }
+
                 if ui.button("Flush Trace Logs").clicked() {
                     self.event_queue.send(GuiEvent::FlushLogs);
                     ui.close_menu();
@@ -564,6 +625,25 @@ impl GuiState {
                 ));
             }
 
+            let mut overscan_changed = false;
+            let mut new_overscan = false;
+
+            if let Some(GuiEnum::DisplayOverscan(state)) =
+                self.get_option_enum_mut(GuiEnum::DisplayOverscan(Default::default()), Some(ctx))
+            {
+                if ui.checkbox(state, "Show Overscan").clicked() {
+                    overscan_changed = true;
+                    new_overscan = *state;
+                    ui.close_menu();
+                }
+            }
+            if overscan_changed {
+                self.event_queue.send(GuiEvent::VariableChanged(
+                    GuiVariableContext::Display(display_idx),
+                    GuiVariable::Enum(GuiEnum::DisplayOverscan(new_overscan)),
+                ));
+            }
+
             /* TODO: Snow should be set per-adapter, not per-display
             if ui
                 .checkbox(&mut self.get_option_mut(GuiBoolean::EnableSnow), "Enable Snow")
@@ -604,14 +684,51 @@ impl GuiState {
         };
     }
 
-    pub fn draw_status_widgets(&mut self, _ui: &mut egui::Ui) {
-        // Can we put stuff on the right hand side of the menu bar?
-        // ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
-        //     ui.label("💾");
-        // });
-        //
-        // ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
-        //     ui.label("🐢");
-        // });
+    pub fn draw_status_widgets(&mut self, ui: &mut egui::Ui) {
+        // Draw a drive light and the current cylinder for each floppy drive on the right hand side of the
+        // menu bar. Layout is right to left, so drives are drawn in reverse order.
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            for (idx, status) in self.floppy_status.iter().enumerate().rev() {
+                let led_color = if status.activity {
+                    egui::Color32::from_rgb(0x40, 0xF0, 0x40)
+                }
+                else if status.motor_on {
+                    egui::Color32::from_rgb(0x20, 0x70, 0x20)
+                }
+                else {
+                    egui::Color32::DARK_GRAY
+                };
+
+                let drive_label = format!("{}:{:02}", (b'A' + idx as u8) as char, status.cylinder);
+                ui.label(egui::RichText::new(drive_label).monospace())
+                    .on_hover_text(format!(
+                        "Drive {}: cylinder {} head {}{}",
+                        idx,
+                        status.cylinder,
+                        status.head,
+                        if status.have_disk { "" } else { " (empty)" }
+                    ));
+                ui.label(egui::RichText::new("⏺").color(led_color));
+            }
+
+            // Draw the POST card's two digit display, if the machine has a POST card.
+            if let Some((code, history)) = &self.post_card {
+                let digits = match code {
+                    Some(code) => format!("{:02X}", code),
+                    None => String::from("--"),
+                };
+                let history_str = history
+                    .iter()
+                    .map(|code| format!("{:02X}", code))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                ui.label(
+                    egui::RichText::new(format!("POST {}", digits))
+                        .monospace()
+                        .color(egui::Color32::from_rgb(0xF0, 0x40, 0x40)),
+                )
+                .on_hover_text(format!("Recent POST codes: {}", history_str));
+            }
+        });
     }
 }
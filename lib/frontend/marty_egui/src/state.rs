@@ -49,8 +49,8 @@ use frontend_common::{
 };
 use marty_core::{
     device_traits::videocard::{DisplayApertureDesc, VideoCardState, VideoCardStateEntry},
-    devices::{pit::PitDisplayState, serial::SerialPortDescriptor},
-    machine::{ExecutionControl, MachineState},
+    devices::{fdc::FloppyDriveStatus, pit::PitDisplayState, serial::SerialPortDescriptor},
+    machine::{ExecutionControl, MachineState, MachineStats},
 };
 use serde::{Deserialize, Serialize};
 use serialport::SerialPortInfo;
@@ -79,6 +79,7 @@ use crate::{
         dma_viewer::DmaViewerControl,
         instruction_history_viewer::InstructionHistoryControl,
         ivt_viewer::IvtViewerControl,
+        log_viewer::LogViewer,
         memory_viewer::MemoryViewerControl,
         performance_viewer::PerformanceViewerControl,
         pic_viewer::PicViewerControl,
@@ -164,6 +165,9 @@ pub struct GuiState {
 
     video_mem: ColorImage,
     pub(crate) perf_stats: PerformanceStats,
+    pub(crate) machine_stats: MachineStats,
+    pub(crate) floppy_status: Vec<FloppyDriveStatus>,
+    pub(crate) post_card: Option<(Option<u8>, Vec<u8>)>,
 
     // Display stuff
     pub(crate) display_apertures: HashMap<usize, Vec<DisplayApertureDesc>>,
@@ -182,6 +186,10 @@ pub struct GuiState {
     pub(crate) host_serial_ports: Vec<SerialPortInfo>,
     pub(crate) serial_port_name: String,
 
+    // ROM sets
+    pub(crate) rom_sets: Vec<(String, Vec<String>)>,
+    pub(crate) rom_sets_active: Vec<String>,
+
     pub(crate) exec_control: Rc<RefCell<ExecutionControl>>,
 
     pub(crate) error_string:   String,
@@ -213,6 +221,7 @@ pub struct GuiState {
     pub vhd_creator: VhdCreator,
     pub text_mode_viewer: TextModeViewer,
     pub call_stack_viewer: CallStackViewer,
+    pub log_viewer: LogViewer,
 
     pub floppy_tree_menu: FileTreeMenu,
     pub hdd_tree_menu:    FileTreeMenu,
@@ -269,6 +278,9 @@ impl GuiState {
             (GuiBoolean::TurboButton, false),
             (GuiBoolean::ShowBackBuffer, false),
             (GuiBoolean::ShowRasterPosition, true),
+            (GuiBoolean::ShowSplitFrame, true),
+            (GuiBoolean::ShowPerfOverlay, false),
+            (GuiBoolean::LightPen, false),
             //(GuiBoolean::EnableSnow, true),
         ]
         .into();
@@ -292,6 +304,9 @@ impl GuiState {
             video_mem: ColorImage::new([320, 200], egui::Color32::BLACK),
 
             perf_stats: Default::default(),
+            machine_stats: Default::default(),
+            floppy_status: Vec::new(),
+            post_card: None,
 
             display_apertures: Default::default(),
             scaler_modes: Vec::new(),
@@ -305,6 +320,9 @@ impl GuiState {
             host_serial_ports: Vec::new(),
             serial_port_name: String::new(),
 
+            rom_sets: Vec::new(),
+            rom_sets_active: Vec::new(),
+
             exec_control: exec_control.clone(),
 
             error_string: String::new(),
@@ -334,6 +352,7 @@ impl GuiState {
             vhd_creator: VhdCreator::new(),
             text_mode_viewer: TextModeViewer::new(),
             call_stack_viewer: CallStackViewer::new(),
+            log_viewer: LogViewer::new(),
 
             floppy_tree_menu: FileTreeMenu::new(),
             hdd_tree_menu: FileTreeMenu::new(),
@@ -414,6 +433,10 @@ impl GuiState {
         self.warning_string = String::new();
     }
 
+    pub fn set_machine_stats(&mut self, stats: MachineStats) {
+        self.machine_stats = stats;
+    }
+
     pub fn set_machine_state(&mut self, state: MachineState) {
         self.machine_state = state;
     }
@@ -430,6 +453,16 @@ impl GuiState {
         }
     }
 
+    /// Update the floppy drive status used to draw the drive indicators.
+    pub fn set_floppy_status(&mut self, status: Vec<FloppyDriveStatus>) {
+        self.floppy_status = status;
+    }
+
+    /// Update the POST card display with the latest POST code and the recent code history.
+    pub fn set_post_card(&mut self, code: Option<u8>, history: Vec<u8>) {
+        self.post_card = Some((code, history));
+    }
+
     pub fn set_floppy_write_protected(&mut self, drive: usize, state: bool) {
         self.floppy_drives[drive].write_protected = state;
     }
@@ -506,6 +539,13 @@ impl GuiState {
         self.host_serial_ports = ports;
     }
 
+    /// Set the complete ROM sets that may be selected for each requested feature, and the sets
+    /// currently in use.
+    pub fn set_rom_sets(&mut self, rom_sets: Vec<(String, Vec<String>)>, active: Vec<String>) {
+        self.rom_sets = rom_sets;
+        self.rom_sets_active = active;
+    }
+
     pub fn update_videocard_state(&mut self, state: HashMap<String, Vec<(String, VideoCardStateEntry)>>) {
         self.videocard_state = state;
     }
@@ -544,6 +584,10 @@ impl GuiState {
                     GuiEnum::DisplayComposite(renderer.composite),
                     Some(GuiVariableContext::Display(idx)),
                 ));
+                enum_vec.push((
+                    GuiEnum::DisplayOverscan(renderer.show_overscan),
+                    Some(GuiVariableContext::Display(idx)),
+                ));
             }
 
             // Create GuiEnums for each display scaler mode.
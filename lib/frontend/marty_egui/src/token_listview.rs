@@ -451,6 +451,7 @@ impl TokenListView {
                             }
                             SyntaxToken::Segment(s) => (Color32::from_rgb(245, 138, 52), s, 1.0),
                             SyntaxToken::Text(s) => (Color32::LIGHT_GRAY, s, 2.0),
+                            SyntaxToken::Label(s) => (Color32::from_rgb(255, 214, 102), s, 6.0),
                             SyntaxToken::ErrorString(s) => (Color32::RED, s, 2.0),
                             _ => (Color32::WHITE, &null, 2.0),
                         };
@@ -29,7 +29,7 @@
     Main UI drawing code for EGUI.
 */
 
-use crate::{state::GuiState};
+use crate::{state::GuiState, GuiBoolean};
 use egui::Context;
 
 impl GuiState {
@@ -40,6 +40,25 @@ impl GuiState {
         });
     }
 
+    /// Draw a small overlay of emulator performance statistics in the corner of the window.
+    pub fn draw_perf_overlay(&mut self, ctx: &Context) {
+        let stats = self.machine_stats;
+        egui::Area::new("perf_overlay")
+            .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 32.0))
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "FPS: {} (emulated: {})\nIPS: {}\nCPU: {:.3} MHz",
+                            stats.host_fps, stats.emulated_fps, stats.ips, stats.effective_mhz
+                        ))
+                        .monospace(),
+                    );
+                });
+            });
+    }
+
     /// Create the UI using egui.
     pub fn ui(&mut self, ctx: &Context) {
         self.toasts.show(ctx);
@@ -72,6 +91,10 @@ impl GuiState {
 
         self.draw_workspace(ctx);
 
+        if self.get_option(GuiBoolean::ShowPerfOverlay).unwrap_or(false) {
+            self.draw_perf_overlay(ctx);
+        }
+
         /*        egui::Window::new("About")
             .open(self.window_open_flags.get_mut(&GuiWindow::About).unwrap())
             .show(ctx, |ui| {
@@ -56,7 +56,7 @@ impl CpuControl {
 
         let (pause_enabled, step_enabled, run_enabled) = match exec_control.state {
             ExecutionState::Paused | ExecutionState::BreakpointHit => (false, true, true),
-            ExecutionState::Running => (true, false, false),
+            ExecutionState::Running | ExecutionState::StepFrame => (true, false, false),
             ExecutionState::Halted => (false, false, false),
         };
 
@@ -96,6 +96,16 @@ impl CpuControl {
                 }*/
             });
 
+            ui.add_enabled_ui(step_enabled, |ui| {
+                if ui
+                    .button(egui::RichText::new("⏭").font(egui::FontId::proportional(20.0)))
+                    .on_hover_text("Step to next frame")
+                    .clicked()
+                {
+                    exec_control.set_op(ExecutionOperation::StepFrame);
+                };
+            });
+
             ui.add_enabled_ui(run_enabled, |ui| {
                 if ui
                     .button(egui::RichText::new("▶").font(egui::FontId::proportional(20.0)))
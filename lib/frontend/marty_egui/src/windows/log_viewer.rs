@@ -0,0 +1,115 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    -------------------------------------------------------------------------
+
+    marty_egui::windows::log_viewer.rs
+
+    Displays the most recent log lines kept by the front end's log buffer.
+*/
+
+use crate::{layouts, layouts::MartyLayout, GuiEventQueue};
+use egui::Color32;
+use frontend_common::log_buffer::LogLine;
+use log::Level;
+
+const LEVELS: [Level; 5] = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace];
+
+pub struct LogViewer {
+    lines: Vec<LogLine>,
+    generation: u64,
+    max_level: Level,
+    target_filter: String,
+    follow: bool,
+}
+
+impl LogViewer {
+    pub fn new() -> Self {
+        Self {
+            lines: Vec::new(),
+            generation: 0,
+            max_level: Level::Info,
+            target_filter: String::new(),
+            follow: true,
+        }
+    }
+
+    pub fn draw(&mut self, ui: &mut egui::Ui, _events: &mut GuiEventQueue) {
+        MartyLayout::new(layouts::Layout::KeyValue, "log-viewer-grid").show(ui, |ui| {
+            MartyLayout::kv_row(ui, "Level", None, |ui| {
+                egui::ComboBox::from_id_source("log-viewer-level")
+                    .selected_text(self.max_level.to_string())
+                    .show_ui(ui, |ui| {
+                        for level in LEVELS {
+                            ui.selectable_value(&mut self.max_level, level, level.to_string());
+                        }
+                    });
+            });
+            MartyLayout::kv_row(ui, "Target", None, |ui| {
+                ui.text_edit_singleline(&mut self.target_filter);
+            });
+            MartyLayout::kv_row(ui, "Follow", None, |ui| {
+                ui.checkbox(&mut self.follow, "");
+            });
+        });
+
+        ui.separator();
+
+        egui::ScrollArea::both()
+            .auto_shrink([false; 2])
+            .stick_to_bottom(self.follow)
+            .show(ui, |ui| {
+                for line in self
+                    .lines
+                    .iter()
+                    .filter(|line| line.level <= self.max_level && line.target.contains(&self.target_filter))
+                {
+                    ui.label(
+                        egui::RichText::new(line.to_string())
+                            .monospace()
+                            .color(level_color(line.level)),
+                    );
+                }
+            });
+    }
+
+    /// Return the log buffer generation the viewer was last updated from.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn set_lines(&mut self, lines: Vec<LogLine>, generation: u64) {
+        self.lines = lines;
+        self.generation = generation;
+    }
+}
+
+fn level_color(level: Level) -> Color32 {
+    match level {
+        Level::Error => Color32::LIGHT_RED,
+        Level::Warn => Color32::YELLOW,
+        Level::Info => Color32::LIGHT_GRAY,
+        Level::Debug | Level::Trace => Color32::GRAY,
+    }
+}
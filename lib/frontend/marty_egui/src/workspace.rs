@@ -187,6 +187,9 @@ impl GuiState {
                 GuiWindow::TextModeViewer => {
                     self.text_mode_viewer.draw(ui, &mut self.event_queue);
                 }
+                GuiWindow::LogViewer => {
+                    self.log_viewer.draw(ui, &mut self.event_queue);
+                }
             });
 
             match inner_response_opt {
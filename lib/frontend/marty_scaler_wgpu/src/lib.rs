@@ -608,6 +608,35 @@ impl DisplayScaler<pixels::Pixels> for MartyScaler {
         self.mode
     }
 
+    fn surface_to_texture(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+        if self.screen_width == 0 || self.screen_height == 0 {
+            return None;
+        }
+        let matrix = ScalingMatrix::new(
+            self.mode,
+            (self.texture_width as f32, self.texture_height as f32),
+            (self.target_width as f32, self.target_height as f32),
+            (self.screen_width as f32, self.screen_height as f32),
+            self.screen_margin_y as f32,
+        );
+        let (sw, sh) = (matrix.transform.cols[0].x, matrix.transform.cols[1].y);
+        let (tx, ty) = (matrix.transform.cols[3].x, matrix.transform.cols[3].y);
+
+        // Invert the transform applied in the vertex shader, from surface pixels to NDC to quad
+        // position, then to texture coordinates. CRT curvature is not taken into account.
+        let ndc_x = (x / self.screen_width as f32) * 2.0 - 1.0;
+        let ndc_y = 1.0 - (y / self.screen_height as f32) * 2.0;
+        let u = ((ndc_x - tx) / sw) * 0.5 + 0.5;
+        let v = 0.5 - ((ndc_y - ty) / sh) * 0.5;
+
+        if (0.0..1.0).contains(&u) && (0.0..1.0).contains(&v) {
+            Some((u, v))
+        }
+        else {
+            None
+        }
+    }
+
     fn set_margins(&mut self, l: u32, r: u32, t: u32, b: u32) {
         self.margin_l = l;
         self.margin_r = r;
@@ -1040,7 +1069,8 @@ impl ScalingMatrix {
     }
 
     /// Create a transformation matrix that fits the texture by scaling it proportionally to the
-    /// largest size that will fit the surface, proportionally
+    /// largest size that will fit the surface. The aspect ratio of the target is preserved, with
+    /// any remaining area of the surface left as borders.
     fn fit_matrix(texture_size: (f32, f32), target_size: (f32, f32), screen_size: (f32, f32), margin_y: f32) -> Self {
         //let margin_y = margin_y / 2.0;
         let offset = 0.0;
@@ -1051,12 +1081,12 @@ impl ScalingMatrix {
         let (screen_width, screen_height) = screen_size;
         let adjusted_screen_h = screen_height - margin_y;
 
-        let max_height_factor = ((screen_height - margin_y) / screen_height).max(1.0);
-        let width_ratio = (screen_width / texture_width).max(1.0);
-        let height_ratio = (adjusted_screen_h / target_height).max(max_height_factor);
+        let width_ratio = screen_width / texture_width;
+        let height_ratio = adjusted_screen_h / target_height;
 
-        // Get smallest scale size. (Removed floor() call from integer scaler)
-        let scale = width_ratio.clamp(1.0, height_ratio);
+        // Get smallest scale size. Unlike the integer scaler, we may scale below 1.0 so that the
+        // image is letterboxed or pillarboxed within a surface smaller than the texture.
+        let scale = width_ratio.min(height_ratio).max(f32::EPSILON);
 
         let scaled_width = texture_width * scale;
         let scaled_height = target_height * scale;
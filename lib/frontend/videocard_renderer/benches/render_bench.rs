@@ -36,9 +36,14 @@ pub const CGA_FRAME_RGBA_RESIZED: usize = 768 * 576 * 4;
 
 use rand::Rng;
 
-use marty_render::{CompositeParams, VideoRenderer};
+use videocard_renderer::{AspectCorrectionMode, AspectRatio, CompositeParams, VideoRenderer};
 
 use marty_core::videocard::{DisplayExtents, VideoType};
+use marty_core::{
+    device_traits::videocard::{BufferSelect, ClockingMode, VideoCard},
+    devices::cga::CGACard,
+    tracelogger::TraceLogger,
+};
 
 use bytemuck;
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
@@ -151,26 +156,26 @@ pub fn render_cga_direct_bench(c: &mut Criterion) {
     c.bench_function("render_resize_linear_bench", |b| {
         // Per-sample (note that a sample can be many iterations) setup goes here
 
-        let mut resample_ctx = marty_render::ResampleContext::new();
+        let mut resample_ctx = videocard_renderer::ResampleContext::new();
 
         resample_ctx.precalc(768, 472, 768, 576);
 
         b.iter(|| {
             // Measured code goes here
-            marty_render::resize_linear(&frame_rgb, 768, 472, &mut frame_resized_rgb, 768, 576, &resample_ctx);
+            videocard_renderer::resize_linear(&frame_rgb, 768, 472, &mut frame_resized_rgb, 768, 576, &resample_ctx);
         });
     });
 
     c.bench_function("render_resize_linear_fast_bench", |b| {
         // Per-sample (note that a sample can be many iterations) setup goes here
 
-        let mut resample_ctx = marty_render::ResampleContext::new();
+        let mut resample_ctx = videocard_renderer::ResampleContext::new();
 
         resample_ctx.precalc(768, 472, 768, 576);
 
         b.iter(|| {
             // Measured code goes here
-            marty_render::resize_linear_fast(
+            videocard_renderer::resize_linear_fast(
                 &mut frame_rgb,
                 768,
                 472,
@@ -182,6 +187,43 @@ pub fn render_cga_direct_bench(c: &mut Criterion) {
         });
     });
 
+    // Compare single and multi-threaded aspect correction for a 640x400 frame into a 640x480 target.
+    let mut frame_640 = vec![0x80; 640 * 400 * 4];
+    let mut frame_640_resized = vec![0; 640 * 480 * 4];
+
+    c.bench_function("render_resize_linear_fast_640x480_bench", |b| {
+        let mut resample_ctx = videocard_renderer::ResampleContext::new();
+
+        b.iter(|| {
+            videocard_renderer::resize_linear_fast(
+                &mut frame_640,
+                640,
+                400,
+                &mut frame_640_resized,
+                640,
+                480,
+                &mut resample_ctx,
+            );
+        });
+    });
+
+    #[cfg(feature = "parallel")]
+    c.bench_function("render_resize_linear_fast_par_640x480_bench", |b| {
+        let mut resample_ctx = videocard_renderer::ResampleContext::new();
+
+        b.iter(|| {
+            videocard_renderer::resize_linear_fast_par(
+                &frame_640,
+                640,
+                400,
+                &mut frame_640_resized,
+                640,
+                480,
+                &mut resample_ctx,
+            );
+        });
+    });
+
     c.bench_function("render_bytemuck_u8_to_u32_bench", |b| {
         // Per-sample (note that a sample can be many iterations) setup goes here
 
@@ -192,5 +234,47 @@ pub fn render_cga_direct_bench(c: &mut Criterion) {
     });
 }
 
-criterion_group!(render_benches, render_cga_direct_bench);
+/// Compare software aspect correction through a scratch buffer owned by the frontend, as the web
+/// player used to do, with the renderer resampling from its own scratch buffer into the output.
+pub fn render_aspect_correction_bench(c: &mut Criterion) {
+    let mut cga = CGACard::new(TraceLogger::None, ClockingMode::Dynamic, false);
+    for _ in 0..CGA_FRAME_INDEX_SIZE {
+        cga.tick();
+    }
+    let extents = cga.get_display_extents();
+    let frame_i = cga.get_buf(BufferSelect::Front);
+
+    let mut renderer = VideoRenderer::new(VideoType::CGA);
+    let (mut scratch, w, h) = renderer.render_to_vec(&cga);
+    let aspect = VideoRenderer::get_aspect_corrected_res((w, h).into(), Some(AspectRatio::default()));
+    let mut output = vec![0; (aspect.w * aspect.h * 4) as usize];
+    let mut resample_ctx = videocard_renderer::ResampleContext::new();
+
+    c.bench_function("render_aspect_frontend_scratch_bench", |b| {
+        b.iter(|| {
+            renderer.draw(frame_i, &mut scratch, extents, None);
+            videocard_renderer::resize_linear_fast(
+                &mut scratch,
+                w,
+                h,
+                &mut output,
+                aspect.w,
+                aspect.h,
+                &mut resample_ctx,
+            );
+        });
+    });
+
+    let mut renderer = VideoRenderer::new(VideoType::CGA);
+    renderer.set_aspect_ratio(Some(AspectRatio::default()), Some(AspectCorrectionMode::Software));
+    let (mut output, _, _) = renderer.render_to_vec(&cga);
+
+    c.bench_function("render_aspect_renderer_scratch_bench", |b| {
+        b.iter(|| {
+            renderer.draw(frame_i, &mut output, extents, None);
+        });
+    });
+}
+
+criterion_group!(render_benches, render_cga_direct_bench, render_aspect_correction_bench);
 criterion_main!(render_benches);
@@ -190,6 +190,27 @@ pub const CGA_RGBA_COLORS_U32: &[[u32; 16]; 2] = &[
     ],
 ];
 
+// A high-contrast palette with fully saturated primaries and a darker intensity
+// gap between normal and bright colors. Intended for accessibility and small displays.
+pub const CGA_RGBA_COLORS_HIGH_CONTRAST: &[[u8; 4]; 16] = &[
+    [0x00, 0x00, 0x00, 0xFF], // 0 - Black
+    [0x00, 0x00, 0xC0, 0xFF], // 1 - Blue
+    [0x00, 0xC0, 0x00, 0xFF], // 2 - Green
+    [0x00, 0xC0, 0xC0, 0xFF], // 3 - Cyan
+    [0xC0, 0x00, 0x00, 0xFF], // 4 - Red
+    [0xC0, 0x00, 0xC0, 0xFF], // 5 - Magenta
+    [0xC0, 0x60, 0x00, 0xFF], // 6 - Brown
+    [0xC0, 0xC0, 0xC0, 0xFF], // 7 - Light Gray
+    [0x40, 0x40, 0x40, 0xFF], // 8 - Dark Gray
+    [0x40, 0x40, 0xFF, 0xFF], // 9 - Light Blue
+    [0x40, 0xFF, 0x40, 0xFF], // 10 - Light Green
+    [0x40, 0xFF, 0xFF, 0xFF], // 11 - Light Cyan
+    [0xFF, 0x40, 0x40, 0xFF], // 12 - Light Red
+    [0xFF, 0x40, 0xFF, 0xFF], // 13 - Light Magenta
+    [0xFF, 0xFF, 0x40, 0xFF], // 14 - Yellow
+    [0xFF, 0xFF, 0xFF, 0xFF], // 15 - White
+];
+
 pub const EGA_RGBA_COLORS: &[[u8; 4]; 64] = &[
     [0x00, 0x00, 0x00, 0xFF], // 000 000
     [0x00, 0x00, 0xAA, 0xFF], // 000 001
@@ -30,7 +30,7 @@
 */
 
 use crate::{consts::*, resize::*};
-use marty_core::devices::cga;
+use marty_core::{device_traits::videocard::VideoCard, devices::cga};
 use web_time::Instant;
 
 use super::*;
@@ -43,6 +43,10 @@ impl VideoRenderer {
     /// Draw the direct (indexed) framebuffer created by a Videocard to the specified output buffer, given
     /// the specified display extents. This base method will call the appropriate drawing routine based on
     /// video card type. Optionally, the raster beam position can be visualized if 'beam_pos' is specified.
+    ///
+    /// 'output_buf' should be the backend's frame buffer. When software aspect correction is enabled,
+    /// the frame is drawn into the renderer's own scratch buffer and resampled once into 'output_buf';
+    /// otherwise it is drawn into 'output_buf' directly. Callers never need an intermediate buffer.
     pub fn draw(
         &mut self,
         input_buf: &[u8],
@@ -68,6 +72,7 @@ impl VideoRenderer {
             (output_buf, None)
         };
 
+        let aperture = self.get_effective_aperture();
         match self.video_type {
             VideoType::MDA => {
                 VideoRenderer::draw_mda_direct_u32(
@@ -75,7 +80,7 @@ impl VideoRenderer {
                     self.params.render.w,
                     self.params.render.h,
                     input_buf,
-                    self.params.aperture,
+                    aperture,
                     extents,
                 );
             }
@@ -89,7 +94,7 @@ impl VideoRenderer {
                         &mut self.composite_bufs,
                         &mut self.composite_ctx,
                         &self.composite_params,
-                        self.params.aperture,
+                        aperture,
                         extents,
                     );
                 }
@@ -99,8 +104,10 @@ impl VideoRenderer {
                         self.params.render.w,
                         self.params.render.h,
                         input_buf,
-                        self.params.aperture,
+                        aperture,
                         extents,
+                        &self.cga_palette,
+                        self.params.show_overscan,
                     )
                 }
             }
@@ -110,7 +117,7 @@ impl VideoRenderer {
                 self.params.render.w,
                 self.params.render.h,
                 input_buf,
-                self.params.aperture,
+                aperture,
                 extents,
                 RenderBpp::Six,
             ),
@@ -118,8 +125,8 @@ impl VideoRenderer {
 
         // Draw raster beam position if provided
         if let Some(beam) = beam_pos {
-            let beam_x = beam.0 - extents.apertures[self.params.aperture as usize].x;
-            let mut beam_y = beam.1 - &extents.apertures[self.params.aperture as usize].y;
+            let beam_x = beam.0 - extents.apertures[aperture as usize].x;
+            let mut beam_y = beam.1 - &extents.apertures[aperture as usize].y;
             if self.params.line_double {
                 beam_y *= 2
             };
@@ -179,9 +186,35 @@ impl VideoRenderer {
         }
 
         // If we are doing software aspect correction, we now need to draw into the output_buf.
-        if do_software_aspect {
-            if let Some(second_pass) = second_pass_buf {
+        let (final_buf, final_dims) = match second_pass_buf {
+            Some(second_pass) if do_software_aspect && self.params.scaling == ScalingMode::Integer => {
+                // The output fills the surface. Scale vertically by as much more than horizontally
+                // as aspect correction calls for.
+                let corrected = VideoRenderer::get_aspect_corrected_res(self.params.render, self.aspect_ratio);
+                resize_integer(
+                    first_pass_buf,
+                    self.params.render.w,
+                    self.params.render.h,
+                    second_pass,
+                    self.params.aspect_corrected.w,
+                    self.params.aspect_corrected.h,
+                    corrected.h as f64 / self.params.render.h as f64,
+                );
+                (second_pass, self.params.aspect_corrected)
+            }
+            Some(second_pass) if do_software_aspect => {
                 //log::debug!("Performing aspect correction...");
+                #[cfg(feature = "parallel")]
+                resize_linear_fast_par(
+                    first_pass_buf,
+                    self.params.render.w,
+                    self.params.render.h,
+                    second_pass,
+                    self.params.aspect_corrected.w,
+                    self.params.aspect_corrected.h,
+                    &mut self.resample_context,
+                );
+                #[cfg(not(feature = "parallel"))]
                 resize_linear_fast(
                     first_pass_buf,
                     self.params.render.w,
@@ -191,8 +224,21 @@ impl VideoRenderer {
                     self.params.aspect_corrected.h,
                     &mut self.resample_context,
                 );
+                (second_pass, self.params.aspect_corrected)
             }
-        }
+            Some(second_pass) => (second_pass, self.params.render),
+            None => (first_pass_buf, self.params.render),
+        };
+
+        // Convert the finished frame to the backend's format. Screenshots were taken above, so
+        // they remain RGBA.
+        VideoRenderer::convert_output_format(
+            final_buf,
+            final_dims.w,
+            final_dims.h,
+            self.output_format,
+            &self.output_lut,
+        );
 
         if screenshot_taken {
             self.send_event(RendererEvent::ScreenshotSaved);
@@ -202,6 +248,72 @@ impl VideoRenderer {
         //log::debug!("render time: {}", self.last_render_time.as_secs_f64());
     }
 
+    /// Draw a frame that is still being scanned out, split at the raster beam position 'split_pos'.
+    /// Pixels the beam has already passed are taken from 'back_buf', the frame in progress, and the
+    /// remainder from 'front_buf', the last complete frame. This shows the effect of register changes
+    /// made partway down the frame while the emulator is paused.
+    pub fn draw_split(
+        &mut self,
+        back_buf: &[u8],
+        front_buf: &[u8],
+        output_buf: &mut [u8],
+        extents: &DisplayExtents,
+        split_pos: (u32, u32),
+        beam_pos: Option<(u32, u32)>,
+    ) {
+        let len = std::cmp::min(back_buf.len(), front_buf.len());
+        let split = std::cmp::min(split_pos.1 as usize * extents.row_stride + split_pos.0 as usize, len);
+
+        let mut split_buf = std::mem::take(&mut self.split_buf);
+        split_buf.resize(len, 0);
+        split_buf[..split].copy_from_slice(&back_buf[..split]);
+        split_buf[split..].copy_from_slice(&front_buf[split..len]);
+
+        self.draw(&split_buf, output_buf, extents, beam_pos);
+        self.split_buf = split_buf;
+    }
+
+    /// Render the current frame of 'videocard' into a new RGBA sRGB buffer, without a display backend.
+    /// Returns the buffer with its width and height. The renderer is resized to the card's
+    /// current aperture first, as the display manager does, so the output matches what would be
+    /// drawn to the screen with the same renderer settings. Intended for headless use such as
+    /// golden-image tests.
+    pub fn render_to_vec(&mut self, videocard: &dyn VideoCard) -> (Vec<u8>, u32, u32) {
+        let extents = videocard.get_display_extents();
+        self.set_line_double(extents.double_scan);
+
+        let aperture = self.get_effective_aperture();
+        let w = extents.apertures[aperture as usize].w;
+        let mut h = extents.apertures[aperture as usize].h;
+        if extents.double_scan {
+            h *= 2;
+        }
+        if self.would_resize((w, h).into()) {
+            self.resize((w, h).into());
+        }
+
+        if self.get_mode_byte() != extents.mode_byte {
+            self.cga_direct_mode_update(extents.mode_byte);
+            self.set_mode_byte(extents.mode_byte);
+        }
+
+        // Software aspect correction resamples into the output, so size it to the corrected
+        // dimensions. Otherwise the frame is drawn at native resolution.
+        let out_dims = match self.params.aspect_correction {
+            AspectCorrectionMode::Software => self.params.aspect_corrected,
+            _ => self.params.render,
+        };
+        // The returned buffer is always RGBA sRGB, whatever the backend's output format.
+        let output_format = self.output_format;
+        self.set_output_format(OutputFormat::default());
+
+        let mut output = vec![0; (out_dims.w * out_dims.h * 4) as usize];
+        self.draw(videocard.get_buf(self.buffer_select), &mut output, extents, None);
+
+        self.set_output_format(output_format);
+        (output, out_dims.w, out_dims.h)
+    }
+
     pub fn draw_horizontal_xor_line_2x(&mut self, frame: &mut [u8], w: u32, span: u32, h: u32, y: u32) {
         if y > (h - 1) {
             return;
@@ -289,6 +401,28 @@ impl VideoRenderer {
         }
     }
 
+    /// Convert the RGBA sRGB pixels in 'frame' to the specified output format, using 'lut' to map
+    /// each color channel. Does nothing for the default RGBA sRGB format.
+    pub fn convert_output_format(frame: &mut [u8], w: u32, h: u32, format: OutputFormat, lut: &[u8; 256]) {
+        if format == OutputFormat::default() {
+            return;
+        }
+
+        let len = std::cmp::min((w * h * 4) as usize, frame.len());
+        for pixel in frame[..len].chunks_exact_mut(4) {
+            let (r, g, b) = (lut[pixel[0] as usize], lut[pixel[1] as usize], lut[pixel[2] as usize]);
+            if format.bgra {
+                pixel[0] = b;
+                pixel[2] = r;
+            }
+            else {
+                pixel[0] = r;
+                pixel[2] = b;
+            }
+            pixel[1] = g;
+        }
+    }
+
     /// Set the alpha component of each pixel in a the specified buffer.
     pub fn set_alpha(frame: &mut [u8], w: u32, h: u32, a: u8) {
         //log::warn!("set_alpha: h: {}", h);
@@ -304,6 +438,11 @@ impl VideoRenderer {
     ///
     /// This version uses bytemuck to convert the framebuffer 32 bits at a time, which
     /// is much faster (benchmarked)
+    ///
+    /// Color indices are resolved through 'palette', which holds little-endian RGBA values.
+    ///
+    /// If 'show_overscan' is set, any part of the frame not covered by the card's display field
+    /// is filled with the border color latched by the card at the end of the last frame.
     pub fn draw_cga_direct_u32(
         frame: &mut [u8],
         w: u32,
@@ -311,6 +450,8 @@ impl VideoRenderer {
         dbuf: &[u8],
         aperture: DisplayApertureType,
         extents: &DisplayExtents,
+        palette: &[u32; 16],
+        show_overscan: bool,
     ) {
         let aperture = &extents.apertures[aperture as usize];
 
@@ -331,22 +472,24 @@ impl VideoRenderer {
 
         let frame_u32: &mut [u32] = bytemuck::cast_slice_mut(frame);
 
+        if show_overscan {
+            // Anything we don't draw below falls outside the field, so paint it as border.
+            frame_u32.fill(palette[(extents.border_color & 0x0F) as usize]);
+        }
+
         for y in 0..max_y {
-            let dbuf_row_offset = (y + vert_adjust) as usize * extents.row_stride;
+            let dbo = (y + vert_adjust) as usize * extents.row_stride + horiz_adjust as usize;
 
             let frame_row0_offset = ((y * 2) * w) as usize;
             let frame_row1_offset = (((y * 2) * w) + w) as usize;
 
-            for x in 0..max_x {
-                let fo0 = frame_row0_offset + x as usize;
-                let fo1 = frame_row1_offset + x as usize;
-
-                let dbo = dbuf_row_offset + (x + horiz_adjust) as usize;
-
-                // TODO: Would it be better for cache concurrency to do one line at a time?
-                frame_u32[fo0] = CGA_RGBA_COLORS_U32[0][(dbuf[dbo] & 0x0F) as usize];
-                frame_u32[fo1] = CGA_RGBA_COLORS_U32[0][(dbuf[dbo] & 0x0F) as usize];
-            }
+            // Expand one line, then copy it to the second (line-doubled) row.
+            expand_indexed_row(
+                &mut frame_u32[frame_row0_offset..frame_row0_offset + max_x as usize],
+                &dbuf[dbo..dbo + max_x as usize],
+                palette,
+            );
+            frame_u32.copy_within(frame_row0_offset..frame_row0_offset + max_x as usize, frame_row1_offset);
         }
     }
 
@@ -701,3 +844,113 @@ impl VideoRenderer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use marty_core::{
+        bus::{DeviceRunTimeUnit, IoDevice, MemoryMappedDevice},
+        device_traits::videocard::ClockingMode,
+        devices::cga::{CGACard, CGA_MEM_ADDRESS},
+        tracelogger::TraceLogger,
+    };
+
+    /// Return a CGA card that has drawn a full frame of 80-column text, so the frame has content
+    /// in both the active display and the border.
+    fn cga_with_frame() -> CGACard {
+        let mut cga = CGACard::new(TraceLogger::None, ClockingMode::Dynamic, false);
+        for i in 0..(80 * 25) {
+            cga.mmio_write_u8(CGA_MEM_ADDRESS + i * 2, 0xDB, 0);
+            cga.mmio_write_u8(CGA_MEM_ADDRESS + i * 2 + 1, (i % 16) as u8, 0);
+        }
+        // 80-column text with video enabled, and a blue border.
+        cga.write_u8(0x3D8, 0x09, None, DeviceRunTimeUnit::SystemTicks(0));
+        cga.write_u8(0x3D9, 0x01, None, DeviceRunTimeUnit::SystemTicks(0));
+        for _ in 0..500_000 {
+            cga.tick();
+        }
+        cga
+    }
+
+    #[test]
+    fn render_to_vec_matches_display_path() {
+        let cga = cga_with_frame();
+        let aspect = Some(AspectRatio { h: 4, v: 3 });
+
+        let mut headless_renderer = VideoRenderer::new(VideoType::CGA);
+        headless_renderer.set_aspect_ratio(aspect, Some(AspectCorrectionMode::Software));
+        let (headless, w, h) = headless_renderer.render_to_vec(&cga);
+        assert_eq!(headless.len(), (w * h * 4) as usize);
+        assert!(headless.iter().any(|&byte| byte != 0), "rendered frame is blank");
+
+        // Draw the same frame with a second renderer, following the steps the display manager and
+        // the desktop frontend take for a display target.
+        let mut renderer = VideoRenderer::new(VideoType::CGA);
+        renderer.set_aspect_ratio(aspect, Some(AspectCorrectionMode::Software));
+        let extents = cga.get_display_extents();
+        renderer.set_line_double(extents.double_scan);
+        let aperture = extents.apertures[renderer.get_effective_aperture() as usize];
+        let mut aperture_h = aperture.h;
+        if extents.double_scan {
+            aperture_h *= 2;
+        }
+        if renderer.would_resize((aperture.w, aperture_h).into()) {
+            renderer.resize((aperture.w, aperture_h).into());
+        }
+        let backend_dims = renderer.get_display_dimensions();
+        assert_eq!((backend_dims.w, backend_dims.h), (w, h));
+
+        if renderer.get_mode_byte() != extents.mode_byte {
+            renderer.cga_direct_mode_update(extents.mode_byte);
+            renderer.set_mode_byte(extents.mode_byte);
+        }
+        let mut backend_buf = vec![0; (backend_dims.w * backend_dims.h * 4) as usize];
+        renderer.draw(
+            cga.get_buf(renderer.get_selected_buffer()),
+            &mut backend_buf,
+            extents,
+            None,
+        );
+        assert!(headless == backend_buf, "headless frame differs from the display path");
+    }
+
+    #[test]
+    fn integer_scaling_fills_the_surface() {
+        let cga = cga_with_frame();
+
+        // The frame at 1x, for reference.
+        let mut renderer = VideoRenderer::new(VideoType::CGA);
+        let (reference, rw, rh) = renderer.render_to_vec(&cga);
+
+        // Leave room for 3x horizontally, with a margin on every side.
+        let aspect = Some(AspectRatio { h: 4, v: 3 });
+        let corrected = VideoRenderer::get_aspect_corrected_res((rw, rh).into(), aspect);
+        let surface: VideoDimensions = (rw * 3 + 8, corrected.h * 3 + rh).into();
+        let mut renderer = VideoRenderer::new(VideoType::CGA);
+        renderer.set_aspect_ratio(aspect, Some(AspectCorrectionMode::Software));
+        renderer.set_scaling(ScalingMode::Integer);
+        renderer.set_surface_size(surface);
+        let (frame, w, h) = renderer.render_to_vec(&cga);
+        assert_eq!((w, h), (surface.w, surface.h));
+
+        // Aspect correction stretches the frame vertically by a larger integer factor.
+        let (sx, sy) = integer_scale_factors(rw, rh, w, h, corrected.h as f64 / rh as f64);
+        assert!(sx == 3 && sy > sx, "unexpected scale factors {}x{}", sx, sy);
+
+        let (x_off, y_off) = ((w - rw * sx) / 2, (h - rh * sy) / 2);
+        for y in 0..h {
+            for x in 0..w {
+                let px = &frame[((y * w + x) * 4) as usize..((y * w + x) * 4 + 3) as usize];
+                let inside = (x_off..x_off + rw * sx).contains(&x) && (y_off..y_off + rh * sy).contains(&y);
+                if inside {
+                    let (src_x, src_y) = ((x - x_off) / sx, (y - y_off) / sy);
+                    let src = ((src_y * rw + src_x) * 4) as usize;
+                    assert_eq!(px, &reference[src..src + 3], "pixel ({}, {})", x, y);
+                }
+                else {
+                    assert_eq!(px, &[0, 0, 0], "border pixel ({}, {})", x, y);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+/*
+    MartyPC
+    https://github.com/dbalsom/martypc
+
+    Copyright 2022-2024 Daniel Balsom
+
+    Permission is hereby granted, free of charge, to any person obtaining a
+    copy of this software and associated documentation files (the “Software”),
+    to deal in the Software without restriction, including without limitation
+    the rights to use, copy, modify, merge, publish, distribute, sublicense,
+    and/or sell copies of the Software, and to permit persons to whom the
+    Software is furnished to do so, subject to the following conditions:
+
+    The above copyright notice and this permission notice shall be included in
+    all copies or substantial portions of the Software.
+
+    THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+    IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+    FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+    AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+    LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+    FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+    DEALINGS IN THE SOFTWARE.
+
+    ---------------------------------------------------------------------------
+
+    videocard_renderer::expand.rs
+
+    Routines for expanding indexed-color pixels to RGBA through a palette.
+
+    A SIMD path is used on x86 CPUs that support SSSE3, selected at runtime. Other targets,
+    including wasm32, use the scalar path.
+
+*/
+
+/// Expand a row of 4-bit color indices in `src` to 32-bit colors in `dst` using `palette`.
+/// Only the low four bits of each index are used. The number of pixels converted is the shorter
+/// of the two slices.
+pub fn expand_indexed_row(dst: &mut [u32], src: &[u8], palette: &[u32; 16]) {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("ssse3") {
+            // Safety: we have just checked that the CPU supports SSSE3.
+            unsafe { expand_indexed_row_ssse3(dst, src, palette) };
+            return;
+        }
+    }
+
+    expand_indexed_row_scalar(dst, src, palette);
+}
+
+pub fn expand_indexed_row_scalar(dst: &mut [u32], src: &[u8], palette: &[u32; 16]) {
+    for (pixel, index) in dst.iter_mut().zip(src.iter()) {
+        *pixel = palette[(*index & 0x0F) as usize];
+    }
+}
+
+/// Expand 16 pixels at a time. The palette is split into four 16-byte tables, one per color
+/// component, so a byte shuffle can look up one component of 16 pixels in one instruction. The
+/// four component vectors are then interleaved back into RGBA pixels.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3")]
+unsafe fn expand_indexed_row_ssse3(dst: &mut [u32], src: &[u8], palette: &[u32; 16]) {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    let mut planes = [[0u8; 16]; 4];
+    for (i, color) in palette.iter().enumerate() {
+        for (c, byte) in color.to_le_bytes().iter().enumerate() {
+            planes[c][i] = *byte;
+        }
+    }
+
+    let r_table = _mm_loadu_si128(planes[0].as_ptr() as *const __m128i);
+    let g_table = _mm_loadu_si128(planes[1].as_ptr() as *const __m128i);
+    let b_table = _mm_loadu_si128(planes[2].as_ptr() as *const __m128i);
+    let a_table = _mm_loadu_si128(planes[3].as_ptr() as *const __m128i);
+    let index_mask = _mm_set1_epi8(0x0F);
+
+    let len = std::cmp::min(dst.len(), src.len());
+    let simd_len = len - (len % 16);
+
+    for i in (0..simd_len).step_by(16) {
+        let indices = _mm_and_si128(_mm_loadu_si128(src.as_ptr().add(i) as *const __m128i), index_mask);
+
+        let r = _mm_shuffle_epi8(r_table, indices);
+        let g = _mm_shuffle_epi8(g_table, indices);
+        let b = _mm_shuffle_epi8(b_table, indices);
+        let a = _mm_shuffle_epi8(a_table, indices);
+
+        let rg_lo = _mm_unpacklo_epi8(r, g);
+        let rg_hi = _mm_unpackhi_epi8(r, g);
+        let ba_lo = _mm_unpacklo_epi8(b, a);
+        let ba_hi = _mm_unpackhi_epi8(b, a);
+
+        let out = dst.as_mut_ptr().add(i) as *mut __m128i;
+        _mm_storeu_si128(out, _mm_unpacklo_epi16(rg_lo, ba_lo));
+        _mm_storeu_si128(out.add(1), _mm_unpackhi_epi16(rg_lo, ba_lo));
+        _mm_storeu_si128(out.add(2), _mm_unpacklo_epi16(rg_hi, ba_hi));
+        _mm_storeu_si128(out.add(3), _mm_unpackhi_epi16(rg_hi, ba_hi));
+    }
+
+    expand_indexed_row_scalar(&mut dst[simd_len..len], &src[simd_len..len], palette);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn simd_expansion_matches_scalar() {
+        let mut rng = rand::thread_rng();
+
+        let mut palette = [0u32; 16];
+        rng.fill(&mut palette[..]);
+
+        // Use an odd length so the scalar tail of the SIMD path is exercised too. Random bytes
+        // also check that the high nibble of each index is ignored.
+        let mut vram = vec![0u8; 640 * 200 + 7];
+        rng.fill(&mut vram[..]);
+
+        let mut scalar_out = vec![0u32; vram.len()];
+        let mut simd_out = vec![0u32; vram.len()];
+
+        expand_indexed_row_scalar(&mut scalar_out, &vram, &palette);
+        expand_indexed_row(&mut simd_out, &vram, &palette);
+
+        assert_eq!(scalar_out, simd_out);
+    }
+}
@@ -63,12 +63,13 @@ use marty_core::device_traits::videocard::{
 use serde::Deserialize;
 
 // Re-export submodules
-pub use self::{color::*, composite::*, consts::*, resize::*};
+pub use self::{color::*, composite::*, consts::*, expand::*, resize::*};
 
 pub mod color;
 pub mod composite;
 pub mod consts;
 pub mod draw;
+pub mod expand;
 pub mod resize;
 // Reenigne composite
 pub mod composite_new;
@@ -88,6 +89,122 @@ pub enum AspectCorrectionMode {
     Hardware,
 }
 
+/// How software aspect correction scales the rendered frame into the output buffer.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScalingMode {
+    /// Resample to the aspect-corrected size with linear interpolation.
+    #[default]
+    Linear,
+    /// Scale to the surface by the largest integer factors that fit with nearest-neighbor
+    /// sampling, centered with black borders. Aspect correction is approximated by scaling
+    /// vertically by a different integer factor.
+    Integer,
+}
+
+impl std::str::FromStr for ScalingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "linear" => Ok(ScalingMode::Linear),
+            "integer" => Ok(ScalingMode::Integer),
+            _ => Err(format!(
+                "Invalid scaling mode: {} (expected \"linear\" or \"integer\")",
+                s
+            )),
+        }
+    }
+}
+
+/// Built-in palettes used to convert CGA color indices to RGBA in RGB (non-composite) mode.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+pub enum CgaPalettePreset {
+    #[default]
+    Standard,
+    Ibm5153,
+    HighContrast,
+}
+
+impl CgaPalettePreset {
+    pub fn colors(&self) -> &'static [[u8; 4]; 16] {
+        match self {
+            CgaPalettePreset::Standard => &CGA_RGBA_COLORS[0],
+            CgaPalettePreset::Ibm5153 => &CGA_RGBA_COLORS[1],
+            CgaPalettePreset::HighContrast => CGA_RGBA_COLORS_HIGH_CONTRAST,
+        }
+    }
+}
+
+/// A conversion applied to each color channel of the output buffer, for backends that don't
+/// present the buffer as sRGB.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ColorTransfer {
+    /// Channels are written as sRGB values, unmodified.
+    #[default]
+    None,
+    /// Channels are decoded from sRGB to linear. Used when a non-sRGB buffer is presented on an sRGB
+    /// surface, which would otherwise wash out the image.
+    SrgbToLinear,
+    /// Channels are encoded to sRGB a second time. Used when an sRGB buffer is presented on a
+    /// non-sRGB surface, which would otherwise darken the image.
+    LinearToSrgb,
+}
+
+/// The channel order and color encoding of the buffer the renderer draws into. The renderer
+/// produces RGBA sRGB pixels and converts them to this format as the last step of drawing.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct OutputFormat {
+    /// Pixels are stored in BGRA order rather than RGBA.
+    pub bgra: bool,
+    pub transfer: ColorTransfer,
+}
+
+impl OutputFormat {
+    /// Determine the output format for a buffer with the specified channel order and color
+    /// encoding, presented on a surface with the specified color encoding.
+    pub fn new(buffer_bgra: bool, buffer_srgb: bool, surface_srgb: bool) -> Self {
+        let transfer = match (buffer_srgb, surface_srgb) {
+            (false, true) => ColorTransfer::SrgbToLinear,
+            (true, false) => ColorTransfer::LinearToSrgb,
+            _ => ColorTransfer::None,
+        };
+        Self {
+            bgra: buffer_bgra,
+            transfer,
+        }
+    }
+
+    /// Return a lookup table mapping each sRGB channel value to its output value.
+    fn channel_lut(&self) -> [u8; 256] {
+        let mut lut = [0u8; 256];
+        for (i, out) in lut.iter_mut().enumerate() {
+            let c = i as f32 / 255.0;
+            let v = match self.transfer {
+                ColorTransfer::None => c,
+                ColorTransfer::SrgbToLinear => {
+                    if c <= 0.04045 {
+                        c / 12.92
+                    }
+                    else {
+                        ((c + 0.055) / 1.055).powf(2.4)
+                    }
+                }
+                ColorTransfer::LinearToSrgb => {
+                    if c <= 0.0031308 {
+                        c * 12.92
+                    }
+                    else {
+                        1.055 * c.powf(1.0 / 2.4) - 0.055
+                    }
+                }
+            };
+            *out = (v * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+        lut
+    }
+}
+
 #[derive(Copy, Clone, Debug, Deserialize)]
 pub struct RendererConfigParams {
     #[serde(default)]
@@ -95,7 +212,12 @@ pub struct RendererConfigParams {
     pub aspect_ratio: Option<AspectRatio>,
     pub display_aperture: Option<DisplayApertureType>,
     #[serde(default)]
+    pub show_overscan: bool,
+    #[serde(default)]
     pub composite: bool,
+    pub cga_palette: Option<CgaPalettePreset>,
+    #[serde(default)]
+    pub scaling: ScalingMode,
 }
 
 #[derive(Copy, Clone)]
@@ -106,7 +228,9 @@ pub struct VideoParams {
     pub surface: VideoDimensions, // The size of the backend surface (window client area)
     pub line_double: bool,       // Whether to double rows when rendering into the internal buffer.
     pub aspect_correction: AspectCorrectionMode, // Determines how to handle aspect correction.
+    pub scaling: ScalingMode,          // How software aspect correction scales the frame.
     pub aperture: DisplayApertureType, // Selected display aperture for renderer
+    pub show_overscan: bool,           // Whether to widen the aperture to include the full overscan border.
     pub debug_aperture: bool,
     pub composite_params: CompositeParams, // Parameters used for composite emulation.
     pub bpp: RenderBpp,
@@ -121,7 +245,9 @@ impl Default for VideoParams {
             surface: (640, 480).into(),
             line_double: false,
             aspect_correction: AspectCorrectionMode::None,
+            scaling: ScalingMode::Linear,
             aperture: DisplayApertureType::Cropped,
+            show_overscan: false,
             debug_aperture: false,
             composite_params: Default::default(),
             bpp: Default::default(),
@@ -198,6 +324,7 @@ pub struct VideoRenderer {
     initialized: bool, // Has the renderer received a resize event?
 
     buf: Vec<u8>,
+    split_buf: Vec<u8>,
     aspect_ratio: Option<AspectRatio>,
     aspect_dirty: bool,
     aperture_dirty: bool,
@@ -218,8 +345,15 @@ pub struct VideoRenderer {
     composite_params:  CompositeParams,
     resample_context:  ResampleContext,
 
+    // RGBA palette used for CGA RGB output. Composite output ignores this.
+    cga_palette: [u32; 16],
+    cga_palette_preset: Option<CgaPalettePreset>,
+
     buffer_select: BufferSelect,
 
+    output_format: OutputFormat,
+    output_lut: [u8; 256],
+
     screenshot_buf: Vec<u8>,
     screenshot_path: Option<std::path::PathBuf>,
     screenshot_requested: bool,
@@ -248,6 +382,7 @@ impl VideoRenderer {
             initialized: false,
 
             buf: vec![0; (DEFAULT_RENDER_WIDTH * DEFAULT_RENDER_HEIGHT * 4) as usize],
+            split_buf: Vec::new(),
             aspect_ratio: None,
             aspect_dirty: false,
             aperture_dirty: false,
@@ -267,8 +402,14 @@ impl VideoRenderer {
             composite_params: Default::default(),
             resample_context: ResampleContext::new(),
 
+            cga_palette: CGA_RGBA_COLORS_U32[0],
+            cga_palette_preset: Some(CgaPalettePreset::Standard),
+
             buffer_select: BufferSelect::Front,
 
+            output_format: OutputFormat::default(),
+            output_lut: OutputFormat::default().channel_lut(),
+
             screenshot_buf: Vec::new(),
             screenshot_path: None,
             screenshot_requested: false,
@@ -301,6 +442,12 @@ impl VideoRenderer {
         }
 
         self.set_aperture(cfg.display_aperture.unwrap_or(DisplayApertureType::Cropped));
+        self.set_show_overscan(cfg.show_overscan);
+
+        if let Some(preset) = cfg.cga_palette {
+            self.set_palette_preset(preset);
+        }
+        self.set_scaling(cfg.scaling);
     }
 
     pub fn get_config_params(&self) -> RendererConfigParams {
@@ -308,7 +455,10 @@ impl VideoRenderer {
             aspect_correction: if self.aspect_ratio.is_some() { true } else { false },
             aspect_ratio: self.aspect_ratio,
             display_aperture: Some(self.params.aperture),
+            show_overscan: self.params.show_overscan,
             composite: self.composite_enabled,
+            cga_palette: self.cga_palette_preset,
+            scaling: self.params.scaling,
         }
     }
     pub fn get_params(&self) -> &VideoParams {
@@ -333,12 +483,94 @@ impl VideoRenderer {
         self.composite_enabled
     }
 
+    /// Set the channel order and color encoding of the buffers passed to draw(). This should match
+    /// the display backend's buffer and surface formats.
+    pub fn set_output_format(&mut self, format: OutputFormat) {
+        if format != self.output_format {
+            log::debug!("Setting renderer output format to {:?}", format);
+            self.output_format = format;
+            self.output_lut = format.channel_lut();
+        }
+    }
+
+    pub fn get_output_format(&self) -> OutputFormat {
+        self.output_format
+    }
+
+    /// Replace the 16-color palette used to draw CGA output in RGB mode. Each entry is an
+    /// RGBA quad. The composite rendering path is not affected.
+    pub fn set_palette(&mut self, palette: [[u8; 4]; 16]) {
+        for (i, color) in palette.iter().enumerate() {
+            self.cga_palette[i] = u32::from_le_bytes(*color);
+        }
+        self.cga_palette_preset = None;
+    }
+
+    /// Select one of the built-in CGA palettes.
+    pub fn set_palette_preset(&mut self, preset: CgaPalettePreset) {
+        log::debug!("Setting CGA palette preset to {:?}", preset);
+        self.set_palette(*preset.colors());
+        self.cga_palette_preset = Some(preset);
+    }
+
+    /// Return the currently active built-in CGA palette, or None if a custom palette was set.
+    pub fn get_palette_preset(&self) -> Option<CgaPalettePreset> {
+        self.cga_palette_preset
+    }
+
     pub fn set_aperture(&mut self, aperture: DisplayApertureType) {
         log::debug!("Setting renderer aperture to {:?}", aperture);
         self.params.aperture = aperture;
         self.aperture_dirty = true;
     }
 
+    /// Enable or disable rendering of the full overscan border. When enabled, the Cropped and
+    /// Accurate apertures are widened to the Full aperture, and any area outside the card's
+    /// display field is filled with the current border color.
+    pub fn set_show_overscan(&mut self, state: bool) {
+        log::debug!("Setting overscan display to {}", state);
+        if self.params.show_overscan != state {
+            self.params.show_overscan = state;
+            self.aperture_dirty = true;
+        }
+    }
+
+    pub fn set_scaling(&mut self, scaling: ScalingMode) {
+        if self.params.scaling != scaling {
+            self.params.scaling = scaling;
+            self.aspect_dirty = true;
+        }
+    }
+
+    /// Set the size of the surface the renderer's output is presented on. Integer scaling fills
+    /// the surface, so with software aspect correction this determines the output size.
+    pub fn set_surface_size(&mut self, surface: VideoDimensions) {
+        if self.params.surface != surface {
+            self.params.surface = surface;
+            if self.params.scaling == ScalingMode::Integer {
+                self.aspect_dirty = true;
+            }
+        }
+    }
+
+    pub fn get_scaling(&self) -> ScalingMode {
+        self.params.scaling
+    }
+
+    pub fn get_show_overscan(&self) -> bool {
+        self.params.show_overscan
+    }
+
+    /// Return the aperture actually used for rendering, taking the overscan setting into account.
+    pub fn get_effective_aperture(&self) -> DisplayApertureType {
+        match self.params.aperture {
+            DisplayApertureType::Cropped | DisplayApertureType::Accurate if self.params.show_overscan => {
+                DisplayApertureType::Full
+            }
+            aperture => aperture,
+        }
+    }
+
     pub fn set_debug(&mut self, state: bool) {
         self.params.debug_aperture = state;
     }
@@ -363,7 +595,10 @@ impl VideoRenderer {
             }
             AspectCorrectionMode::Software => {
                 // For software aspect correction, we must ensure the backend buffer is large enough
-                // to receive the aspect-corrected image.
+                // to receive the aspect-corrected image. Integer scaling fills the whole surface.
+                if self.params.scaling == ScalingMode::Integer {
+                    new_aspect_corrected_dims = self.params.surface;
+                }
                 self.params.aspect_corrected = new_aspect_corrected_dims;
                 self.params.backend = new_aspect_corrected_dims;
             }
@@ -408,8 +643,11 @@ impl VideoRenderer {
                 }
             }
             AspectCorrectionMode::Software => {
-                let new_aspect = VideoRenderer::get_aspect_corrected_res(new, self.aspect_ratio);
-                if self.params.aspect_corrected != new_aspect {
+                let new_aspect = match self.params.scaling {
+                    ScalingMode::Linear => VideoRenderer::get_aspect_corrected_res(new, self.aspect_ratio),
+                    ScalingMode::Integer => self.params.surface,
+                };
+                if self.params.render != new || self.params.aspect_corrected != new_aspect {
                     return true;
                 }
             }
@@ -42,6 +42,28 @@ pub struct ResampleParam {
     y_off_high: usize,
     dy_offset: usize,
 }
+
+const RESIZE_ALG: ResizeAlg = ResizeAlg::SuperSampling(FilterType::Bilinear, 4);
+
+/// A vertical strip of the image resized on its own thread by resize_linear_fast_par().
+#[cfg(feature = "parallel")]
+struct ResampleStrip {
+    resizer: Resizer,
+    src:     Vec<u8>,
+    dst:     Vec<u8>,
+}
+
+#[cfg(feature = "parallel")]
+impl ResampleStrip {
+    fn new() -> Self {
+        Self {
+            resizer: Resizer::new(RESIZE_ALG),
+            src:     Vec::new(),
+            dst:     Vec::new(),
+        }
+    }
+}
+
 pub struct ResampleContext {
     resizer: Option<Resizer>,
     src_w:   u32,
@@ -49,11 +71,13 @@ pub struct ResampleContext {
     dst_w:   u32,
     dst_h:   u32,
     params:  Vec<ResampleParam>,
+    #[cfg(feature = "parallel")]
+    strips:  Vec<ResampleStrip>,
 }
 
 impl ResampleContext {
     pub fn new() -> Self {
-        let resizer = Resizer::new(ResizeAlg::SuperSampling(FilterType::Bilinear, 4));
+        let resizer = Resizer::new(RESIZE_ALG);
 
         /*
         unsafe {
@@ -69,6 +93,8 @@ impl ResampleContext {
             dst_w:   0,
             dst_h:   0,
             params:  Vec::new(),
+            #[cfg(feature = "parallel")]
+            strips:  Vec::new(),
         }
     }
 
@@ -171,3 +197,200 @@ pub fn resize_linear_fast(
         .resize(&src_img.view(), &mut dst_img.view_mut())
         .unwrap();
 }
+
+/// Performs the same resize as resize_linear_fast(), splitting the image into vertical strips that
+/// are resized on separate threads. Aspect correction never changes the horizontal resolution, so
+/// each output column depends only on the same input column and the result is identical.
+#[cfg(feature = "parallel")]
+pub fn resize_linear_fast_par(
+    src: &[u8],
+    src_w: u32,
+    src_h: u32,
+    dst: &mut [u8],
+    dst_w: u32,
+    dst_h: u32,
+    ctx: &mut ResampleContext,
+) {
+    use rayon::prelude::*;
+
+    assert_eq!(src_w, dst_w);
+
+    let (width, src_h, dst_h) = (src_w as usize, src_h as usize, dst_h as usize);
+    let row_len = width * 4;
+    let strip_w = (width + rayon::current_num_threads() - 1) / rayon::current_num_threads();
+    let strip_ct = (width + strip_w - 1) / strip_w;
+    let strip_span = |i: usize| (i * strip_w, strip_w.min(width - i * strip_w));
+
+    ctx.strips.resize_with(strip_ct, ResampleStrip::new);
+    ctx.strips.par_iter_mut().enumerate().for_each(|(i, strip)| {
+        let (x, w) = strip_span(i);
+
+        strip.src.clear();
+        for src_row in src[..row_len * src_h].chunks_exact(row_len) {
+            strip.src.extend_from_slice(&src_row[x * 4..(x + w) * 4]);
+        }
+        strip.dst.resize(w * dst_h * 4, 0);
+
+        let w = NonZeroU32::new(w as u32).unwrap();
+        let src_img = Image::from_slice_u8(
+            w,
+            NonZeroU32::new(src_h as u32).unwrap(),
+            &mut strip.src,
+            PixelType::U8x4,
+        )
+        .unwrap();
+        let mut dst_img = Image::from_slice_u8(
+            w,
+            NonZeroU32::new(dst_h as u32).unwrap(),
+            &mut strip.dst,
+            PixelType::U8x4,
+        )
+        .unwrap();
+
+        strip.resizer.resize(&src_img.view(), &mut dst_img.view_mut()).unwrap();
+    });
+
+    let strips = &ctx.strips;
+    dst[..row_len * dst_h]
+        .par_chunks_exact_mut(row_len)
+        .enumerate()
+        .for_each(|(y, dst_row)| {
+            for (i, strip) in strips.iter().enumerate() {
+                let (x, w) = strip_span(i);
+                dst_row[x * 4..(x + w) * 4].copy_from_slice(&strip.dst[y * w * 4..(y + 1) * w * 4]);
+            }
+        });
+}
+
+/// Return the largest integer horizontal and vertical scale factors at which a src_w x src_h image
+/// fits within dst_w x dst_h. 'y_ratio' is the vertical stretch aspect correction calls for,
+/// relative to the horizontal; the vertical factor is the horizontal factor times 'y_ratio',
+/// rounded to the nearest integer. Neither factor is ever less than 1.
+pub fn integer_scale_factors(src_w: u32, src_h: u32, dst_w: u32, dst_h: u32, y_ratio: f64) -> (u32, u32) {
+    if src_w == 0 || src_h == 0 {
+        return (1, 1);
+    }
+    let y_factor = |x: u32| std::cmp::max(1, (x as f64 * y_ratio).round() as u32);
+    (1..=dst_w / src_w)
+        .rev()
+        .map(|x| (x, y_factor(x)))
+        .find(|(_, y)| src_h * y <= dst_h)
+        .unwrap_or((1, y_factor(1)))
+}
+
+/// Performs a nearest-neighbor resize of the specified src into dst by the largest integer factors
+/// that fit, as determined by integer_scale_factors(). The scaled image is centered in dst and the
+/// remaining area is filled with black. If dst is too small, the image is clipped.
+pub fn resize_integer(src: &[u8], src_w: u32, src_h: u32, dst: &mut [u8], dst_w: u32, dst_h: u32, y_ratio: f64) {
+    assert!(src.len() >= (src_w * src_h * 4) as usize);
+    assert!(dst.len() >= (dst_w * dst_h * 4) as usize);
+
+    let (x_scale, y_scale) = integer_scale_factors(src_w, src_h, dst_w, dst_h, y_ratio);
+    let (x_scale, y_scale) = (x_scale as usize, y_scale as usize);
+    let (src_w, src_h, dst_w, dst_h) = (src_w as usize, src_h as usize, dst_w as usize, dst_h as usize);
+
+    let scaled_w = src_w * x_scale;
+    let scaled_h = src_h * y_scale;
+    let x_off = dst_w.saturating_sub(scaled_w) / 2;
+    let y_off = dst_h.saturating_sub(scaled_h) / 2;
+
+    for dst_px in dst[..dst_w * dst_h * 4].chunks_exact_mut(4) {
+        dst_px.copy_from_slice(&[0, 0, 0, 255]);
+    }
+
+    for y in 0..std::cmp::min(scaled_h, dst_h) {
+        let src_row = (y / y_scale) * src_w * 4;
+        let dst_row = ((y + y_off) * dst_w + x_off) * 4;
+
+        for x in 0..std::cmp::min(scaled_w, dst_w) {
+            let so = src_row + (x / x_scale) * 4;
+            let dyo = dst_row + x * 4;
+            dst[dyo..dyo + 3].copy_from_slice(&src[so..so + 3]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_scale_factors_fit_destination() {
+        assert_eq!(integer_scale_factors(320, 200, 640, 480, 1.0), (2, 2));
+        assert_eq!(integer_scale_factors(320, 200, 1280, 600, 1.0), (3, 3));
+        assert_eq!(integer_scale_factors(640, 400, 640, 480, 1.0), (1, 1));
+        // A destination smaller than the source still draws at 1x.
+        assert_eq!(integer_scale_factors(640, 400, 320, 200, 1.0), (1, 1));
+    }
+
+    #[test]
+    fn integer_scale_factors_approximate_aspect_correction() {
+        // 640x200 corrected to 4:3 needs 2.4x as much height as width.
+        assert_eq!(integer_scale_factors(640, 200, 1920, 1440, 2.4), (3, 7));
+        assert_eq!(integer_scale_factors(640, 200, 1920, 1080, 2.4), (2, 5));
+        // The horizontal factor drops until the vertical factor fits.
+        assert_eq!(integer_scale_factors(640, 200, 1920, 900, 2.4), (1, 2));
+    }
+
+    #[test]
+    fn integer_resize_centers_with_black_borders() {
+        // A 2x1 source of a red and a green pixel, scaled 2x into a 6x4 destination.
+        let src = [255, 0, 0, 255, 0, 255, 0, 255];
+        let mut dst = vec![0x55u8; 6 * 4 * 4];
+        resize_integer(&src, 2, 1, &mut dst, 6, 4, 1.0);
+
+        let pixel = |x: usize, y: usize| &dst[(y * 6 + x) * 4..(y * 6 + x) * 4 + 4];
+        for y in 0..4 {
+            for x in 0..6 {
+                let expected: &[u8] = match (x, y) {
+                    (1..=2, 1..=2) => &[255, 0, 0, 255],
+                    (3..=4, 1..=2) => &[0, 255, 0, 255],
+                    _ => &[0, 0, 0, 255],
+                };
+                assert_eq!(pixel(x, y), expected, "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn integer_resize_scales_axes_independently() {
+        // A 1x2 source of a red and a green pixel, scaled 1x wide and 2x high into a 3x4 destination.
+        let src = [255, 0, 0, 255, 0, 255, 0, 255];
+        let mut dst = vec![0x55u8; 3 * 4 * 4];
+        resize_integer(&src, 1, 2, &mut dst, 3, 4, 2.0);
+
+        let pixel = |x: usize, y: usize| &dst[(y * 3 + x) * 4..(y * 3 + x) * 4 + 4];
+        for y in 0..4 {
+            let expected: &[u8] = if y < 2 { &[255, 0, 0, 255] } else { &[0, 255, 0, 255] };
+            assert_eq!(pixel(1, y), expected, "pixel (1, {})", y);
+            assert_eq!(pixel(0, y), &[0, 0, 0, 255]);
+            assert_eq!(pixel(2, y), &[0, 0, 0, 255]);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "parallel"))]
+mod parallel_tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn parallel_resize_matches_scalar() {
+        let mut rng = rand::thread_rng();
+
+        // Use an odd width so the strips are not all the same size.
+        let (w, src_h, dst_h) = (641u32, 200u32, 480u32);
+        let mut src = vec![0u8; (w * src_h * 4) as usize];
+        rng.fill(&mut src[..]);
+
+        let mut scalar_out = vec![0u8; (w * dst_h * 4) as usize];
+        let mut par_out = vec![0u8; (w * dst_h * 4) as usize];
+
+        let mut ctx = ResampleContext::new();
+        let mut scalar_src = src.clone();
+        resize_linear_fast(&mut scalar_src, w, src_h, &mut scalar_out, w, dst_h, &mut ctx);
+        resize_linear_fast_par(&src, w, src_h, &mut par_out, w, dst_h, &mut ctx);
+
+        assert_eq!(scalar_out, par_out);
+    }
+}